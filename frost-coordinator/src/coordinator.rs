@@ -1,37 +1,93 @@
 use std::any::Any;
-use std::collections::BTreeMap;
-use std::time::Duration;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 
-use frost_signer::config::{Config, Error as ConfigError};
+use frost_signer::config::{Config, DkgBlamePolicy, DkgFailurePolicy, Error as ConfigError};
+use frost_signer::logging::target::COORDINATOR;
+use frost_signer::retention::{now, Retention};
 use frost_signer::{
+    auth::{Identity, COORDINATOR_SENDER_ID},
+    digest::MessageDigest,
     net::{Error as HttpNetError, Message, NetListen},
+    reshare::refresh_digest,
+    retry::RetryPolicy,
+    roster::group_commitment,
+    signer::PeerKeys,
     signing_round::{
-        DkgBegin, DkgPublicShare, MessageTypes, NonceRequest, NonceResponse, Signable,
-        SignatureShareRequest,
+        commitment_digest, find_equivocations, null_commitment, BatchMessage, BatchNonceRequest,
+        BatchNonceResponse, BatchSignRequest, DkgBegin, DkgBlame, DkgFailure, DkgPublicShare,
+        DkgStatus, EquivocationReport, MessageTypes, NonceRequest, NonceResponse, Rejection,
+        RejectionCode, ReshareBegin, ReshareCommit, RosterChange, RoundAbort, Signable,
+        SignatureShareRequest, UpgradeWindow, MAX_BATCH_SIZE,
     },
+    taproot::KeyPath,
+    thresholds::Thresholds,
     util::{parse_public_key, parse_public_keys},
 };
 use hashbrown::HashSet;
+use rand_core::OsRng;
+use rayon::prelude::*;
 use tracing::{debug, info, warn};
 use wtfrost::{
     bip340::{Error as Bip340Error, SchnorrProof},
     common::{PolyCommitment, PublicNonce, Signature},
     compute,
     errors::AggregatorError,
-    v1, Point, Scalar,
+    v1, Point,
 };
 
 use serde::{Deserialize, Serialize};
 
+use crate::events::{Progress, RoundEvent, RoundEvents};
+use crate::history::{Error as HistoryError, NewRound, Round, RoundHistory, RoundKind};
+use crate::journal::{Error as JournalError, Journal};
+use crate::quorum::{Error as QuorumError, SigningQuorum};
+use crate::share_verifier::ShareVerifier;
+use crate::upgrade_window::{Window, WindowState};
+use crate::watchdog::Watchdog;
+
 pub const DEVNET_COORDINATOR_ID: usize = 0;
 pub const DEVNET_COORDINATOR_DKG_ID: u64 = 0; //TODO: Remove, this is a correlation id
+/// Domain tag of the messages signed from the command line
+pub const DEFAULT_TAG: &str = "frost/message";
 
 #[derive(clap::Subcommand, Debug)]
 pub enum Command {
     Dkg,
-    Sign { msg: Vec<u8> },
-    DkgSign { msg: Vec<u8> },
+    /// Sign the tagged hash of `msg`, see [`frost_signer::digest`]
+    Sign {
+        #[arg(long, default_value = DEFAULT_TAG)]
+        tag: String,
+        msg: Vec<u8>,
+    },
+    DkgSign {
+        #[arg(long, default_value = DEFAULT_TAG)]
+        tag: String,
+        msg: Vec<u8>,
+    },
     GetAggregatePublicKey,
+    /// Refresh the key shares of the current generation, keeping its aggregate public key
+    Reshare,
+    /// Drop signers from the current generation, see [`Coordinator::remove_signers`]
+    RemoveSigners {
+        signer_ids: Vec<usize>,
+    },
+    /// Bring excluded signers into the current generation, see [`Coordinator::add_signers`]
+    AddSigners {
+        signer_ids: Vec<usize>,
+    },
+    /// List the rounds started within a window of unix times
+    Rounds {
+        #[arg(long)]
+        since: Option<u64>,
+        #[arg(long)]
+        until: Option<u64>,
+    },
+    /// Show one round of the history
+    Round {
+        id: i64,
+    },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -41,23 +97,93 @@ pub struct Coordinator<Network: NetListen> {
     current_dkg_public_id: u64,
     current_sign_id: u64,
     current_sign_nonce_id: u64,
+    /// Id of the last refresh of the current generation's key shares
+    #[serde(skip)]
+    current_reshare_id: u64,
+    /// Signers which sent refresh shares in the current refresh round
+    #[serde(skip)]
+    reshared: BTreeSet<u32>,
+    /// Id of the last change to the signers of the current generation
+    #[serde(skip)]
+    current_change_id: u64,
+    /// Joining signers which ended the current roster change
+    #[serde(skip)]
+    joined: BTreeSet<u32>,
     total_signers: usize, // Assuming the signers cover all id:s in {1, 2, ..., total_signers}
     total_keys: usize,
-    threshold: usize,
+    thresholds: Thresholds,
     network: Network,
     dkg_public_shares: BTreeMap<u32, DkgPublicShare>,
     public_nonces: BTreeMap<u32, NonceResponse>,
+    /// Nonces of the current batch signing round, see [`Coordinator::sign_digests`]
+    #[serde(skip)]
+    batch_nonces: BTreeMap<u32, BatchNonceResponse>,
     signature_shares: BTreeMap<u32, v1::SignatureShare>,
     aggregate_public_key: Point,
-    network_private_key: Scalar,
+    /// Signs every outbound message, see [`Coordinator::send`]
+    #[serde(skip)]
+    identity: Identity,
     signer_public_keys: Vec<String>,
     key_public_keys: Vec<String>,
     coordinator_public_key: String,
+    /// Digests of the requests sent in the current round, see [`MessageTypes::request_hash`]
+    #[serde(skip)]
+    outstanding_requests: BTreeSet<[u8; 32]>,
+    /// The last rejection received from each signer
+    #[serde(skip)]
+    rejections: BTreeMap<u32, Rejection>,
+    /// Signer owning each key id, learned from the messages of the current DKG round
+    #[serde(skip)]
+    key_owners: BTreeMap<u32, usize>,
+    #[serde(skip)]
+    dkg_failure_policy: DkgFailurePolicy,
+    #[serde(skip)]
+    dkg_blame_policy: DkgBlamePolicy,
+    /// Signers dropped from the roster under [`DkgBlamePolicy::Exclude`], with their key ids
+    #[serde(skip)]
+    excluded_signers: BTreeMap<usize, Vec<u32>>,
+    /// Keys whose signature shares had not arrived when the last signing round ended
+    #[serde(skip)]
+    share_stragglers: BTreeSet<u32>,
+    /// Output whose key path spend the signing round in progress signs, see [`KeyPath`]
+    #[serde(skip)]
+    sign_key_path: Option<KeyPath>,
+    /// Signers the operator chose to sign with, see [`crate::quorum`]
+    #[serde(skip)]
+    signing_quorum: SigningQuorum,
+    /// Attempts and backoff for rounds aborted by signer failures or the watchdog
+    #[serde(skip)]
+    round_retry: RetryPolicy,
+    #[serde(skip)]
+    watchdog: Watchdog,
+    /// Watches the commitment phase of DKG rounds
+    #[serde(skip)]
+    commitment_watchdog: Watchdog,
+    /// Journal of outbound messages, for recovering from a crash mid-round
+    #[serde(skip)]
+    journal: Option<Journal>,
+    /// Journal id of the round in progress
+    #[serde(skip)]
+    journal_round: Option<i64>,
+    /// Maintenance window announced to the signers and not yet closed
+    #[serde(skip)]
+    upgrade_window: Option<Window>,
+    #[serde(skip)]
+    last_upgrade_window_id: u64,
+    /// Usage of the artifacts pruned in the background, e.g. the journal
+    #[serde(skip)]
+    retention: Retention,
+    /// Finished DKG and signing rounds, see [`crate::history`]
+    #[serde(skip)]
+    history: Option<RoundHistory>,
+    /// Subscribers to the progress of rounds, see [`crate::events`]
+    #[serde(skip)]
+    events: RoundEvents,
 }
 
 impl<Network: NetListen> Coordinator<Network> {
     pub fn new(id: usize, dkg_id: u64, config: &Config, network: Network) -> Self {
-        let network_private_key = Scalar::try_from(config.network_private_key.as_str())
+        let identity = Identity::from_config(config, COORDINATOR_SENDER_ID)
             .expect("failed to parse network_private_key from config");
 
         Self {
@@ -66,18 +192,93 @@ impl<Network: NetListen> Coordinator<Network> {
             current_dkg_public_id: 1,
             current_sign_id: 1,
             current_sign_nonce_id: 1,
+            current_reshare_id: 0,
+            reshared: Default::default(),
+            current_change_id: 0,
+            joined: Default::default(),
             total_signers: config.total_signers,
             total_keys: config.total_keys,
-            threshold: config.keys_threshold,
+            thresholds: config.thresholds(),
             network,
             dkg_public_shares: Default::default(),
             public_nonces: Default::default(),
+            batch_nonces: Default::default(),
             aggregate_public_key: Point::default(),
             signature_shares: Default::default(),
-            network_private_key,
+            identity,
             signer_public_keys: config.signer_public_keys.clone(),
             key_public_keys: config.key_public_keys.clone(),
             coordinator_public_key: config.coordinator_public_key.clone(),
+            outstanding_requests: Default::default(),
+            rejections: Default::default(),
+            key_owners: Default::default(),
+            dkg_failure_policy: config.dkg_failure_policy,
+            dkg_blame_policy: config.dkg_blame_policy,
+            excluded_signers: Default::default(),
+            share_stragglers: Default::default(),
+            sign_key_path: None,
+            signing_quorum: Default::default(),
+            round_retry: config.retry.clone(),
+            watchdog: Watchdog::new(config.round_timeout_secs.map(Duration::from_secs)),
+            commitment_watchdog: Watchdog::new(
+                config.dkg_commitment_timeout_secs.map(Duration::from_secs),
+            ),
+            journal: None,
+            journal_round: None,
+            upgrade_window: None,
+            last_upgrade_window_id: 0,
+            retention: Retention::default(),
+            history: None,
+            events: RoundEvents::default(),
+        }
+    }
+
+    /// Journal outbound messages to `journal`, see [`Coordinator::recover`]
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Record every finished round in `history`
+    pub fn with_history(mut self, history: RoundHistory) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Progress of the rounds this coordinator runs, as they run
+    pub fn round_events(&self) -> &RoundEvents {
+        &self.events
+    }
+
+    /// Publish that a signer's message of type `message` moved the current round forward
+    fn progress(&self, kind: RoundKind, message: Progress, signer_id: u32, key_id: Option<u32>) {
+        self.events.publish(RoundEvent::Received {
+            kind,
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+            message,
+            signer_id,
+            key_id,
+        });
+    }
+
+    /// Usage of the artifacts pruned in the background, see [`frost_signer::retention`]
+    pub fn retention(&self) -> &Retention {
+        &self.retention
+    }
+
+    /// Rounds started within `since..until`, in unix seconds, none without a history
+    pub fn list_rounds(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Round>, Error> {
+        match &self.history {
+            Some(history) => Ok(history.list(since, until)?),
+            None => Ok(vec![]),
+        }
+    }
+
+    pub fn get_round(&self, id: i64) -> Result<Option<Round>, Error> {
+        match &self.history {
+            Some(history) => Ok(history.get(id)?),
+            None => Ok(None),
         }
     }
 }
@@ -92,25 +293,790 @@ where
                 self.run_distributed_key_generation()?;
                 Ok(())
             }
-            Command::Sign { msg } => {
-                self.sign_message(msg)?;
+            Command::Sign { tag, msg } => {
+                self.sign_bytes(tag, msg)?;
                 Ok(())
             }
-            Command::DkgSign { msg } => {
-                info!("sign msg: {:?}", msg);
+            Command::DkgSign { tag, msg } => {
+                info!(target: COORDINATOR, "sign msg: {:?} tagged {}", msg, tag);
                 self.run_distributed_key_generation()?;
-                self.sign_message(msg)?;
+                self.sign_bytes(tag, msg)?;
                 Ok(())
             }
             Command::GetAggregatePublicKey => {
                 let key = self.get_aggregate_public_key()?;
-                info!("aggregate public key {}", key);
+                info!(target: COORDINATOR, "aggregate public key {}", key);
+                Ok(())
+            }
+            Command::Reshare => {
+                let key = self.run_reshare()?;
+                info!(target: COORDINATOR, "refreshed the key shares of {}", key);
+                Ok(())
+            }
+            Command::RemoveSigners { signer_ids } => {
+                let reconfiguration = self.remove_signers(signer_ids)?;
+                info!(target: COORDINATOR, "removed signers {:?}: {}", signer_ids, reconfiguration);
+                Ok(())
+            }
+            Command::AddSigners { signer_ids } => {
+                let reconfiguration = self.add_signers(signer_ids)?;
+                info!(target: COORDINATOR, "added signers {:?}: {}", signer_ids, reconfiguration);
+                Ok(())
+            }
+            Command::Rounds { since, until } => {
+                for round in self.list_rounds(*since, *until)? {
+                    println!("{round}");
+                }
+                Ok(())
+            }
+            Command::Round { id } => {
+                match self.get_round(*id)? {
+                    Some(round) => println!("{round}"),
+                    None => println!("no round #{id}"),
+                }
                 Ok(())
             }
         }
     }
 
+    /// Run DKG, retrying rounds which too many signers failed or which timed out.
+    /// Under [`DkgBlamePolicy::Exclude`] a failed round which blamed signers is instead rerun
+    /// straight away without them.
     pub fn run_distributed_key_generation(&mut self) -> Result<Point, Error> {
+        self.check_upgrade_window()?;
+        let policy = self.dkg_blame_policy;
+        loop {
+            let retry = self.round_retry.clone();
+            let result = retry.retry_if(
+                || self.watched(RoundKind::Dkg, None, Self::run_dkg_round),
+                |e| match e {
+                    Error::DkgFailed(_, _, blamed) => {
+                        blamed.is_empty() || policy == DkgBlamePolicy::Flag
+                    }
+                    Error::RoundTimeout(_) => true,
+                    _ => false,
+                },
+            );
+            match (result, policy) {
+                (Err(Error::DkgFailed(_, _, blamed)), DkgBlamePolicy::Exclude { min_signers })
+                    if !blamed.is_empty() =>
+                {
+                    self.exclude_signers(&blamed, min_signers)?
+                }
+                (result, _) => return result,
+            }
+        }
+    }
+
+    /// Refresh the key shares of the current generation, see [`frost_signer::reshare`]. The
+    /// aggregate public key stays as it is. Signers only swap in their refreshed shares once
+    /// every signer of the roster refreshed from the same commitments, otherwise the round is
+    /// aborted and they keep their shares.
+    pub fn run_reshare(&mut self) -> Result<Point, Error> {
+        self.check_upgrade_window()?;
+        if self.aggregate_public_key == Point::default() {
+            return Err(Error::NoAggregatePublicKey);
+        }
+        let retry = self.round_retry.clone();
+        retry.retry_if(
+            || self.watched(RoundKind::Reshare, None, Self::run_reshare_round),
+            |e| matches!(e, Error::RoundTimeout(_)),
+        )
+    }
+
+    fn run_reshare_round(&mut self) -> Result<Point, Error> {
+        self.clear_requests();
+        self.reshared.clear();
+        self.current_reshare_id += 1;
+        let reshare_id = self.current_reshare_id;
+        self.begin_journal_round()?;
+        info!(
+            target: COORDINATOR,
+            "DKG Round #{}: starting refresh #{} of the key shares", self.current_dkg_id, reshare_id
+        );
+        self.events.publish(RoundEvent::Started {
+            kind: RoundKind::Reshare,
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+        });
+        self.send_request(MessageTypes::ReshareBegin(ReshareBegin {
+            dkg_id: self.current_dkg_id,
+            reshare_id,
+        }))?;
+
+        let excluded_key_ids = self.excluded_key_ids();
+        let mut ids_to_await = self.roster();
+        let mut commitments: BTreeMap<u32, Vec<Point>> = BTreeMap::new();
+        let mut statuses: BTreeMap<usize, (DkgStatus, [u8; 32])> = BTreeMap::new();
+        while !ids_to_await.is_empty() {
+            match self.wait_for_next_message()?.msg {
+                MessageTypes::ReshareShares(shares)
+                    if shares.dkg_id == self.current_dkg_id && shares.reshare_id == reshare_id =>
+                {
+                    for (key_id, commitment) in shares.commitments {
+                        if !excluded_key_ids.contains(&key_id)
+                            && self.claim_key(shares.signer_id, key_id)
+                        {
+                            commitments.entry(key_id).or_insert(commitment);
+                        }
+                    }
+                    if self.reshared.insert(shares.signer_id) {
+                        self.progress(
+                            RoundKind::Reshare,
+                            Progress::ReshareShares,
+                            shares.signer_id,
+                            None,
+                        );
+                    }
+                }
+                MessageTypes::ReshareEnd(end)
+                    if end.dkg_id == self.current_dkg_id && end.reshare_id == reshare_id =>
+                {
+                    if ids_to_await.remove(&(end.signer_id as usize)) {
+                        self.progress(
+                            RoundKind::Reshare,
+                            Progress::ReshareEnd,
+                            end.signer_id,
+                            None,
+                        );
+                        statuses.insert(end.signer_id as usize, (end.status, end.digest));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // the signers only refreshed from the commitments we saw if their digests match ours
+        let digest = refresh_digest(self.current_dkg_id, reshare_id, &commitments);
+        let failed: Vec<usize> = statuses
+            .iter()
+            .filter(|(_, (status, signer_digest))| {
+                *status != DkgStatus::Success || *signer_digest != digest
+            })
+            .map(|(signer_id, _)| *signer_id)
+            .collect();
+        if !failed.is_empty() {
+            self.abort_round(format!(
+                "refresh #{reshare_id} failed on signers {failed:?}"
+            ))?;
+            return Err(Error::ReshareFailed(reshare_id, failed));
+        }
+        self.send(MessageTypes::ReshareCommit(ReshareCommit {
+            dkg_id: self.current_dkg_id,
+            reshare_id,
+            digest,
+        }))?;
+
+        // fold the refresh into the party commitments signature shares are verified against
+        for (key_id, commitment) in commitments {
+            if let Some(share) = self.dkg_public_shares.get_mut(&key_id) {
+                for (a, d) in share.public_share.A.iter_mut().zip(commitment) {
+                    *a = *a + d;
+                }
+            }
+        }
+        info!(
+            target: COORDINATOR,
+            "DKG Round #{}: refresh #{} of the key shares committed", self.current_dkg_id, reshare_id
+        );
+        Ok(self.aggregate_public_key)
+    }
+
+    /// Drop `signer_ids` from the roster of the current generation, keeping its aggregate public
+    /// key: the remaining signers stop counting the removed signers' keys, and their shares are
+    /// refreshed so the shares the removed signers keep no longer combine with them. If the
+    /// refresh fails, DKG runs again without them. Fails with [`Error::RosterTooSmall`] if the
+    /// signers left could not meet the thresholds.
+    pub fn remove_signers(&mut self, signer_ids: &[usize]) -> Result<Reconfiguration, Error> {
+        self.check_upgrade_window()?;
+        if self.aggregate_public_key == Point::default() {
+            return Err(Error::NoAggregatePublicKey);
+        }
+        let roster = self.roster();
+        if let Some(signer_id) = signer_ids.iter().find(|id| !roster.contains(*id)) {
+            return Err(Error::NotInRoster(*signer_id));
+        }
+        let excluded_signers = self.excluded_signers.clone();
+        if let Err(e) = self.exclude_signers(signer_ids, 0) {
+            self.excluded_signers = excluded_signers;
+            return Err(e);
+        }
+        let retry = self.round_retry.clone();
+        retry.retry_if(
+            || {
+                self.watched(RoundKind::RosterChange, None, |c| {
+                    c.run_roster_change_round(&[])
+                })
+            },
+            |e| matches!(e, Error::RoundTimeout(_)),
+        )?;
+        match self.run_reshare() {
+            Ok(key) => Ok(Reconfiguration::Kept(key)),
+            Err(e @ (Error::ReshareFailed(..) | Error::RoundTimeout(_) | Error::Rejected(..))) => {
+                self.rekey(&e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Bring `signer_ids`, excluded from the current generation's DKG or removed since, into
+    /// its roster, keeping its aggregate public key: the shares of their keys are rebuilt from
+    /// the shares of `keys_threshold` keys of the roster, see [`frost_signer::roster`]. If the
+    /// repair fails, DKG runs again with them.
+    pub fn add_signers(&mut self, signer_ids: &[usize]) -> Result<Reconfiguration, Error> {
+        self.check_upgrade_window()?;
+        if self.aggregate_public_key == Point::default() {
+            return Err(Error::NoAggregatePublicKey);
+        }
+        if let Some(signer_id) = signer_ids
+            .iter()
+            .find(|id| !self.excluded_signers.contains_key(*id))
+        {
+            return Err(Error::AlreadyInRoster(*signer_id));
+        }
+        let retry = self.round_retry.clone();
+        let result = retry.retry_if(
+            || {
+                self.watched(RoundKind::RosterChange, None, |c| {
+                    c.run_roster_change_round(signer_ids)
+                })
+            },
+            |e| matches!(e, Error::RoundTimeout(_)),
+        );
+        match result {
+            Ok(key) => Ok(Reconfiguration::Kept(key)),
+            Err(
+                e @ (Error::RosterChangeFailed(..)
+                | Error::RoundTimeout(_)
+                | Error::Rejected(..)
+                | Error::RosterTooSmall(..)),
+            ) => {
+                for signer_id in signer_ids {
+                    self.excluded_signers.remove(signer_id);
+                }
+                self.rekey(&e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run DKG over the roster as it stands, since changing it failed with `reason`
+    fn rekey(&mut self, reason: &Error) -> Result<Reconfiguration, Error> {
+        warn!(
+            target: COORDINATOR,
+            "DKG Round #{}: {}, running DKG over the new roster", self.current_dkg_id, reason
+        );
+        let previous = self.aggregate_public_key;
+        let current = self.run_distributed_key_generation()?;
+        Ok(Reconfiguration::Rekeyed { previous, current })
+    }
+
+    /// Announce the roster of the current generation, with `added` back in it, and wait until
+    /// every signer of `added` rebuilt its shares
+    fn run_roster_change_round(&mut self, added: &[usize]) -> Result<Point, Error> {
+        self.clear_requests();
+        self.joined.clear();
+        self.current_change_id += 1;
+        let change_id = self.current_change_id;
+        self.begin_journal_round()?;
+        self.events.publish(RoundEvent::Started {
+            kind: RoundKind::RosterChange,
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+        });
+
+        let mut repaired_key_ids: Vec<u32> = added
+            .iter()
+            .filter_map(|signer_id| self.excluded_signers.get(signer_id))
+            .flatten()
+            .cloned()
+            .collect();
+        repaired_key_ids.sort();
+        let excluded_key_ids: BTreeSet<u32> = self
+            .excluded_key_ids()
+            .into_iter()
+            .filter(|key_id| !repaired_key_ids.contains(key_id))
+            .collect();
+        let key_owners: Vec<(u32, u32)> = self
+            .key_owners
+            .iter()
+            .filter(|(key_id, _)| !excluded_key_ids.contains(key_id))
+            .map(|(key_id, signer_id)| (*key_id, *signer_id as u32))
+            .collect();
+        let helper_key_ids: Vec<u32> = key_owners
+            .iter()
+            .map(|(key_id, _)| *key_id)
+            .filter(|key_id| !repaired_key_ids.contains(key_id))
+            .take(self.thresholds.keys)
+            .collect();
+        if !repaired_key_ids.is_empty() && helper_key_ids.len() < self.thresholds.keys {
+            let signers = self.roster().len();
+            return Err(Error::RosterTooSmall(signers, helper_key_ids.len()));
+        }
+        info!(
+            target: COORDINATOR,
+            "DKG Round #{}: roster change #{}, repairing key ids {:?} from key ids {:?}",
+            self.current_dkg_id,
+            change_id,
+            repaired_key_ids,
+            helper_key_ids
+        );
+        self.send_request(MessageTypes::RosterChange(RosterChange {
+            dkg_id: self.current_dkg_id,
+            change_id,
+            key_owners,
+            repaired_key_ids,
+            helper_key_ids,
+            group_commitment: group_commitment(
+                self.dkg_public_shares
+                    .values()
+                    .map(|share| &share.public_share),
+                self.thresholds.keys,
+            ),
+        }))?;
+
+        let mut ids_to_await: BTreeSet<usize> = added.iter().cloned().collect();
+        let mut failed = vec![];
+        while !ids_to_await.is_empty() {
+            match self.wait_for_next_message()?.msg {
+                MessageTypes::RosterChangeEnd(end)
+                    if end.dkg_id == self.current_dkg_id && end.change_id == change_id =>
+                {
+                    if ids_to_await.remove(&(end.signer_id as usize)) {
+                        self.progress(
+                            RoundKind::RosterChange,
+                            Progress::RosterChangeEnd,
+                            end.signer_id,
+                            None,
+                        );
+                        self.joined.insert(end.signer_id);
+                        if end.status != DkgStatus::Success {
+                            failed.push(end.signer_id as usize);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !failed.is_empty() {
+            self.abort_round(format!(
+                "roster change #{change_id} failed on signers {failed:?}"
+            ))?;
+            return Err(Error::RosterChangeFailed(change_id, failed));
+        }
+        for signer_id in added {
+            self.excluded_signers.remove(signer_id);
+        }
+        info!(
+            target: COORDINATOR,
+            "DKG Round #{}: roster change #{} done, signers {:?} are excluded",
+            self.current_dkg_id,
+            change_id,
+            self.excluded_signers()
+        );
+        Ok(self.aggregate_public_key)
+    }
+
+    /// Signers dropped from the roster under [`DkgBlamePolicy::Exclude`]
+    pub fn excluded_signers(&self) -> Vec<usize> {
+        self.excluded_signers.keys().cloned().collect()
+    }
+
+    /// Return every excluded signer to the roster from the next DKG round on
+    pub fn readmit_signers(&mut self) {
+        self.excluded_signers.clear();
+    }
+
+    /// Drop `blamed` from the roster, failing if too few signers or keys would remain
+    fn exclude_signers(&mut self, blamed: &[usize], min_signers: usize) -> Result<(), Error> {
+        for signer_id in blamed {
+            let key_ids = self
+                .key_owners
+                .iter()
+                .filter(|(_, owner)| *owner == signer_id)
+                .map(|(key_id, _)| *key_id)
+                .collect();
+            self.excluded_signers.insert(*signer_id, key_ids);
+        }
+        let signers = self.total_signers - self.excluded_signers.len();
+        let keys = self.total_keys - self.excluded_key_ids().len();
+        warn!(
+            target: COORDINATOR,
+            "DKG Round #{}: excluded signers {:?}, {} signers holding {} keys remain",
+            self.current_dkg_id,
+            self.excluded_signers(),
+            signers,
+            keys
+        );
+        if signers < min_signers || !self.thresholds.met(signers, keys) {
+            return Err(Error::RosterTooSmall(signers, keys));
+        }
+        Ok(())
+    }
+
+    fn excluded_key_ids(&self) -> Vec<u32> {
+        let mut key_ids: Vec<u32> = self.excluded_signers.values().flatten().cloned().collect();
+        key_ids.sort();
+        key_ids
+    }
+
+    /// Hold the signing rounds to come to `quorum`, failing if it names signers outside the
+    /// roster or leaves too few signers or keys to sign. An empty quorum clears it.
+    pub fn set_signing_quorum(&mut self, quorum: SigningQuorum) -> Result<(), Error> {
+        if quorum.is_empty() {
+            self.clear_signing_quorum();
+            return Ok(());
+        }
+        let key_ids = quorum.key_ids(&self.signing_roster(), self.thresholds)?;
+        info!(
+            target: COORDINATOR,
+            "Signing with {}, holding keys {:?}", quorum, key_ids
+        );
+        self.signing_quorum = quorum;
+        Ok(())
+    }
+
+    /// Signers the signing rounds are held to
+    pub fn signing_quorum(&self) -> &SigningQuorum {
+        &self.signing_quorum
+    }
+
+    /// Sign with every signer of the roster again
+    pub fn clear_signing_quorum(&mut self) {
+        self.signing_quorum = SigningQuorum::default();
+    }
+
+    /// The key ids each signer of the roster holds, by the keys configured for them
+    fn signing_roster(&self) -> BTreeMap<u32, Vec<u32>> {
+        let excluded_key_ids = self.excluded_key_ids();
+        self.roster()
+            .into_iter()
+            .map(|signer_id| {
+                let signer_key = self.signer_public_keys.get(signer_id - 1);
+                let key_ids = (0..self.total_keys as u32)
+                    .filter(|key_id| !excluded_key_ids.contains(key_id))
+                    .filter(|key_id| self.key_public_keys.get(*key_id as usize) == signer_key)
+                    .collect();
+                (signer_id as u32, key_ids)
+            })
+            .collect()
+    }
+
+    /// Key ids the current signing round waits for the nonces of
+    fn signing_key_ids(&self) -> Result<BTreeSet<u32>, Error> {
+        if self.signing_quorum.is_empty() {
+            // excluded keys hold no share of the group key
+            let excluded_key_ids = self.excluded_key_ids();
+            return Ok((0..self.total_keys as u32)
+                .filter(|key_id| !excluded_key_ids.contains(key_id))
+                .collect());
+        }
+        // the roster may have changed since the quorum was set
+        Ok(self
+            .signing_quorum
+            .key_ids(&self.signing_roster(), self.thresholds)?)
+    }
+
+    /// Signers taking part in DKG
+    fn roster(&self) -> HashSet<usize> {
+        (1..=self.total_signers)
+            .filter(|signer_id| !self.excluded_signers.contains_key(signer_id))
+            .collect()
+    }
+
+    /// Id of the last DKG round started, which identifies the current key generation
+    pub fn current_dkg_id(&self) -> u64 {
+        self.current_dkg_id
+    }
+
+    /// Id of the current signing round
+    pub fn current_sign_id(&self) -> u64 {
+        self.current_sign_id
+    }
+
+    /// Number of rounds the watchdogs have aborted
+    pub fn round_timeouts(&self) -> u64 {
+        self.watchdog.trips() + self.commitment_watchdog.trips()
+    }
+
+    /// Run `round` under the watchdog, aborting it on the signers if it overruns
+    fn watched<T: RoundResult>(
+        &mut self,
+        kind: RoundKind,
+        message: Option<&[u8]>,
+        round: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let started_at = now();
+        let start = Instant::now();
+        let armed = self.watchdog.arm();
+        let result = round(self);
+        drop(armed);
+        if let Err(Error::RoundTimeout(timeout)) = &result {
+            self.abort_round(format!("round timed out after {timeout:?}"))?;
+        }
+        // whatever the outcome, the round is over
+        self.close_journal_round()?;
+        let duration_ms = start.elapsed().as_millis() as u64;
+        self.events.publish(match &result {
+            Ok(_) => RoundEvent::Completed {
+                kind,
+                dkg_id: self.current_dkg_id,
+                sign_id: self.current_sign_id,
+                duration_ms,
+            },
+            Err(e) => RoundEvent::Failed {
+                kind,
+                dkg_id: self.current_dkg_id,
+                sign_id: self.current_sign_id,
+                duration_ms,
+                error: e.to_string(),
+            },
+        });
+        if let Some(history) = &self.history {
+            let participants = match kind {
+                RoundKind::Dkg => self
+                    .dkg_public_shares
+                    .values()
+                    .map(|share| share.signer_id)
+                    .filter(|signer_id| !self.excluded_signers.contains_key(&(*signer_id as usize)))
+                    .collect::<BTreeSet<_>>(),
+                RoundKind::Reshare => self.reshared.clone(),
+                RoundKind::RosterChange => self.joined.clone(),
+                RoundKind::Sign | RoundKind::KeyPathSign => self
+                    .public_nonces
+                    .values()
+                    .map(|nonce| nonce.signer_id)
+                    .chain(self.batch_nonces.values().map(|nonces| nonces.signer_id))
+                    .collect(),
+            };
+            history.record(&NewRound {
+                kind,
+                dkg_id: self.current_dkg_id,
+                sign_id: self.current_sign_id,
+                started_at,
+                duration_ms,
+                participants: participants.into_iter().collect(),
+                message: message.map(to_hex),
+                result: result.as_ref().ok().map(RoundResult::to_hex),
+                error: result.as_ref().err().map(|e| e.to_string()),
+            })?;
+        }
+        result
+    }
+
+    fn abort_round(&mut self, reason: String) -> Result<(), Error> {
+        self.send_abort(self.current_dkg_id, self.current_sign_id, reason)
+    }
+
+    fn send_abort(&mut self, dkg_id: u64, sign_id: u64, reason: String) -> Result<(), Error> {
+        warn!(
+            target: COORDINATOR,
+            "Aborting DKG round #{} sign round #{}: {}",
+            dkg_id, sign_id, reason
+        );
+        let abort = RoundAbort {
+            dkg_id,
+            sign_id,
+            reason,
+        };
+        self.clear_requests();
+        self.send(MessageTypes::RoundAbort(abort))
+    }
+
+    /// Announce a maintenance window opening in `starts_in` and lasting at most `duration`.
+    /// Rounds resume early once `quorum` signers have registered again since it opened.
+    /// Returns the window id.
+    pub fn announce_upgrade_window(
+        &mut self,
+        starts_in: Duration,
+        duration: Duration,
+        quorum: usize,
+        reason: String,
+    ) -> Result<u64, Error> {
+        self.last_upgrade_window_id += 1;
+        let window_id = self.last_upgrade_window_id;
+        info!(
+            target: COORDINATOR,
+            "Announcing upgrade window #{} in {:?} for up to {:?}: {}",
+            window_id, starts_in, duration, reason
+        );
+        let announcement = UpgradeWindow {
+            window_id,
+            starts_in_secs: starts_in.as_secs(),
+            duration_secs: duration.as_secs(),
+            reason,
+        };
+        self.send(MessageTypes::UpgradeWindow(announcement))?;
+        self.upgrade_window = Some(Window::new(
+            window_id,
+            Instant::now(),
+            starts_in,
+            duration,
+            quorum,
+        ));
+        Ok(window_id)
+    }
+
+    /// The maintenance window announced and not yet closed, see
+    /// [`Coordinator::announce_upgrade_window`]
+    pub fn upgrade_window(&self) -> Option<&Window> {
+        self.upgrade_window.as_ref()
+    }
+
+    /// Take in the acknowledgements and registrations received while the window is open, and
+    /// report its state. A window which closed is reported once, then forgotten.
+    pub fn poll_upgrade_window(&mut self) -> Result<Option<WindowState>, Error> {
+        let Some(window) = &self.upgrade_window else {
+            return Ok(None);
+        };
+        if window.state(Instant::now()) == WindowState::Open {
+            self.receive_upgrade_messages();
+        }
+        let Some(window) = &self.upgrade_window else {
+            return Ok(None);
+        };
+        let state = window.state(Instant::now());
+        match state {
+            WindowState::Resumed => info!(
+                target: COORDINATOR,
+                "Upgrade window #{} closed, signers {:?} registered again",
+                window.window_id,
+                window.registered.keys().collect::<Vec<_>>()
+            ),
+            WindowState::Expired => warn!(
+                target: COORDINATOR,
+                "Upgrade window #{} is up with only signers {:?} of a quorum of {} registered again",
+                window.window_id,
+                window.registered.keys().collect::<Vec<_>>(),
+                window.quorum
+            ),
+            WindowState::Upcoming | WindowState::Open => return Ok(Some(state)),
+        }
+        self.upgrade_window = None;
+        Ok(Some(state))
+    }
+
+    fn check_upgrade_window(&mut self) -> Result<(), Error> {
+        match (self.poll_upgrade_window()?, &self.upgrade_window) {
+            (Some(WindowState::Open), Some(window)) => {
+                Err(Error::UpgradeWindowOpen(window.window_id))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the messages waiting outside of any round, keeping only those about the window
+    fn receive_upgrade_messages(&mut self) {
+        let signer_public_keys = parse_public_keys(&self.signer_public_keys);
+        let signer_key =
+            |signer_id: u32| signer_public_keys.get((signer_id as usize).checked_sub(1)?);
+        self.network.poll(self.id);
+        while let Some(message) = self.network.next_message() {
+            let verified = match &message.msg {
+                MessageTypes::UpgradeAck(msg) => {
+                    signer_key(msg.signer_id).is_some_and(|key| msg.verify(&message.sig, key))
+                }
+                MessageTypes::Registration(msg) => {
+                    signer_key(msg.signer_id).is_some_and(|key| msg.verify(&message.sig, key))
+                }
+                // left over from earlier rounds
+                _ => continue,
+            };
+            if verified {
+                self.handle_upgrade_message(&message.msg);
+            } else {
+                warn!(target: COORDINATOR, "Dropping badly signed {:?}", message.msg.type_id());
+            }
+        }
+    }
+
+    /// Record an acknowledgement of the upgrade window or a signer registration
+    fn handle_upgrade_message(&mut self, msg: &MessageTypes) {
+        match msg {
+            MessageTypes::UpgradeAck(ack) => {
+                debug!(
+                    target: COORDINATOR,
+                    "Signer #{} acknowledged upgrade window #{}",
+                    ack.signer_id, ack.window_id
+                );
+                if let Some(window) = &mut self.upgrade_window {
+                    window.acknowledge(ack.signer_id, ack.window_id);
+                }
+            }
+            MessageTypes::Registration(registration) => {
+                info!(
+                    target: COORDINATOR,
+                    "Signer #{} registered running version {}",
+                    registration.signer_id, registration.version
+                );
+                if let Some(window) = &mut self.upgrade_window {
+                    window.register(
+                        registration.signer_id,
+                        registration.version.clone(),
+                        Instant::now(),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Pick up after a crash: continue from the last DKG id in the journal, and abort every
+    /// round the journal shows was left open so no signer keeps waiting on it
+    pub fn recover(&mut self) -> Result<(), Error> {
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+        if let Some(dkg_id) = journal.last_dkg_id()? {
+            self.current_dkg_id = self.current_dkg_id.max(dkg_id);
+        }
+        for round in journal.open_rounds()? {
+            warn!(
+                target: COORDINATOR,
+                "DKG round #{} sign round #{} was left open {} after sending {} messages",
+                round.dkg_id,
+                round.sign_id,
+                round.position(),
+                round.messages.len()
+            );
+            self.journal_round = Some(round.id);
+            self.send_abort(
+                round.dkg_id,
+                round.sign_id,
+                "coordinator restarted".to_string(),
+            )?;
+            self.close_journal_round()?;
+        }
+        Ok(())
+    }
+
+    /// Open a journal round for the round starting now, closing any round it supersedes
+    fn begin_journal_round(&mut self) -> Result<(), Error> {
+        self.close_journal_round()?;
+        if let Some(journal) = &self.journal {
+            self.journal_round = Some(journal.begin(self.current_dkg_id, self.current_sign_id)?);
+        }
+        Ok(())
+    }
+
+    fn close_journal_round(&mut self) -> Result<(), Error> {
+        if let (Some(journal), Some(round)) = (&self.journal, self.journal_round.take()) {
+            journal.close(round)?;
+        }
+        Ok(())
+    }
+
+    /// Sign `msg`, journal it, then send it. Every outbound message goes through here.
+    fn send(&mut self, msg: MessageTypes) -> Result<(), Error> {
+        let message = self.identity.sign_message(msg)?;
+        if let (Some(journal), Some(round)) = (&self.journal, self.journal_round) {
+            journal.record(round, &message)?;
+        }
+        self.network.send_message(message)?;
+        Ok(())
+    }
+
+    fn run_dkg_round(&mut self) -> Result<Point, Error> {
         self.start_public_shares()?;
         let public_key = self.wait_for_public_shares()?;
         self.start_private_shares()?;
@@ -120,79 +1086,110 @@ where
 
     fn start_public_shares(&mut self) -> Result<(), Error> {
         self.dkg_public_shares.clear();
+        self.key_owners.clear();
+        for (signer_id, key_ids) in &self.excluded_signers {
+            for key_id in key_ids {
+                self.key_owners.insert(*key_id, *signer_id);
+            }
+        }
+        self.clear_requests();
         self.current_dkg_id += 1;
-        info!("Starting DKG round #{}", self.current_dkg_id);
+        self.begin_journal_round()?;
+        info!(target: COORDINATOR, "Starting DKG round #{}", self.current_dkg_id);
+        self.events.publish(RoundEvent::Started {
+            kind: RoundKind::Dkg,
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+        });
         info!(
+            target: COORDINATOR,
             "DKG Round #{}: Starting Public Share Distribution",
             self.current_dkg_id
         );
         let dkg_begin = DkgBegin {
             dkg_id: self.current_dkg_id,
+            excluded_key_ids: self.excluded_key_ids(),
         };
+        // excluded keys count as having committed to a zero polynomial
+        for key_id in &dkg_begin.excluded_key_ids {
+            let public_share = DkgPublicShare {
+                dkg_id: self.current_dkg_id,
+                dkg_public_id: self.current_dkg_public_id,
+                signer_id: self.key_owners[key_id] as u32,
+                key_id: *key_id,
+                public_share: null_commitment(*key_id, self.thresholds.keys, &mut OsRng),
+            };
+            self.dkg_public_shares.insert(*key_id, public_share);
+        }
 
-        let dkg_begin_message = Message {
-            sig: dkg_begin.sign(&self.network_private_key).expect(""),
-            msg: MessageTypes::DkgBegin(dkg_begin),
-        };
-
-        self.network.send_message(dkg_begin_message)?;
+        self.send_request(MessageTypes::DkgBegin(dkg_begin))?;
         Ok(())
     }
 
     fn start_private_shares(&mut self) -> Result<(), Error> {
         info!(
+            target: COORDINATOR,
             "DKG Round #{}: Starting Private Share Distribution",
             self.current_dkg_id
         );
         let dkg_begin = DkgBegin {
             dkg_id: self.current_dkg_id,
+            excluded_key_ids: self.excluded_key_ids(),
         };
-        let dkg_private_begin_msg = Message {
-            sig: dkg_begin.sign(&self.network_private_key).expect(""),
-            msg: MessageTypes::DkgPrivateBegin(dkg_begin),
-        };
-
-        self.network.send_message(dkg_private_begin_msg)?;
+        self.send_request(MessageTypes::DkgPrivateBegin(dkg_begin))?;
         Ok(())
     }
 
     fn collect_nonces(&mut self) -> Result<(), Error> {
         self.public_nonces.clear();
+        self.batch_nonces.clear();
 
         let nonce_request = NonceRequest {
             dkg_id: self.current_dkg_id,
             sign_id: self.current_sign_id,
+            correlation_id: 0,
             sign_nonce_id: self.current_sign_nonce_id,
         };
 
-        let nonce_request_message = Message {
-            sig: nonce_request.sign(&self.network_private_key).expect(""),
-            msg: MessageTypes::NonceRequest(nonce_request),
-        };
-
-        debug!("dkg_id #{}. NonceRequest sent.", self.current_dkg_id);
-        self.network.send_message(nonce_request_message)?;
+        debug!(target: COORDINATOR, "dkg_id #{}. NonceRequest sent.", self.current_dkg_id);
+        self.send_request(MessageTypes::NonceRequest(nonce_request))?;
+        let key_ids = self.signing_key_ids()?;
 
         loop {
             match self.wait_for_next_message()?.msg {
                 MessageTypes::NonceRequest(_) => {}
-                MessageTypes::NonceResponse(nonce_response) => {
-                    let party_id = nonce_response.party_id;
-                    self.public_nonces.insert(party_id, nonce_response);
-                    debug!(
-                        "NonceResponse from party #{:?}. Got {} nonce responses of threshold {}",
-                        party_id,
-                        self.public_nonces.len(),
-                        self.threshold,
-                    );
+                MessageTypes::NonceResponse(nonce_response)
+                    if key_ids.contains(&nonce_response.key_id) =>
+                {
+                    let key_id = nonce_response.key_id;
+                    if self.claim_key(nonce_response.signer_id, key_id) {
+                        self.progress(
+                            self.sign_kind(),
+                            Progress::NonceResponse,
+                            nonce_response.signer_id,
+                            Some(key_id),
+                        );
+                        self.public_nonces.insert(key_id, nonce_response);
+                        debug!(
+                            target: COORDINATOR,
+                            "NonceResponse from key #{:?}. Got {} nonce responses of threshold {}",
+                            key_id,
+                            self.public_nonces.len(),
+                            self.thresholds.keys,
+                        );
+                    }
                 }
                 msg => {
-                    warn!("NonceLoop Got unexpected message {:?})", msg.type_id());
+                    warn!(
+                        target: COORDINATOR,
+                        "NonceLoop Got unexpected message {:?})",
+                        msg.type_id()
+                    );
                 }
             }
 
-            if self.public_nonces.len() == self.total_keys {
-                debug!("Nonce threshold of {} met.", self.threshold);
+            if self.public_nonces.len() == key_ids.len() {
+                debug!(target: COORDINATOR, "Nonce threshold of {} met.", self.thresholds.keys);
                 break;
             }
         }
@@ -201,7 +1198,7 @@ where
 
     #[allow(non_snake_case)]
     fn compute_aggregate_nonce(&mut self, msg: &[u8]) -> Result<Point, Error> {
-        info!("Computing aggregate nonce...");
+        info!(target: COORDINATOR, "Computing aggregate nonce...");
         self.collect_nonces()?;
         let ids: Vec<usize> = self.public_nonces.keys().map(|i| *i as usize).collect();
         let nonces: Vec<PublicNonce> = self
@@ -214,77 +1211,212 @@ where
     }
 
     fn request_signature_shares(
-        &self,
+        &mut self,
         nonces: &[(u32, PublicNonce)],
         msg: &[u8],
+        key_path: Option<&KeyPath>,
     ) -> Result<(), Error> {
-        for party_id in self.public_nonces.keys() {
+        // each request goes to the signer which sent the nonce of its key
+        let owners: Vec<(u32, u32)> = self
+            .public_nonces
+            .iter()
+            .map(|(key_id, nonce)| (*key_id, nonce.signer_id))
+            .collect();
+        for (key_id, signer_id) in owners {
             let signature_share_request = SignatureShareRequest {
                 dkg_id: self.current_dkg_id,
                 sign_id: self.current_sign_id,
                 correlation_id: 0,
-                party_id: *party_id,
+                signer_id,
+                key_id,
                 nonces: nonces.to_owned(),
                 message: msg.to_vec(),
+                key_path: key_path.cloned(),
             };
 
-            let signature_share_request_message = Message {
-                sig: signature_share_request
-                    .sign(&self.network_private_key)
-                    .expect(""),
-                msg: MessageTypes::SignShareRequest(signature_share_request),
-            };
-
-            self.network.send_message(signature_share_request_message)?;
+            self.send_request(MessageTypes::SignShareRequest(signature_share_request))?;
         }
         Ok(())
     }
 
-    fn collect_signature_shares(&mut self) -> Result<(), Error> {
+    /// Collect a share from every key of the signing set, verifying each on the thread pool
+    /// as it arrives. Fails as soon as a share fails verification.
+    fn collect_signature_shares(&mut self, verifier: Arc<ShareVerifier>) -> Result<(), Error> {
         // get the parties who responded with a nonce
-        let mut signature_shares: HashSet<u32> =
-            HashSet::from_iter(self.public_nonces.keys().cloned());
-        while !signature_shares.is_empty() {
-            match self.wait_for_next_message()?.msg {
-                MessageTypes::SignShareResponse(response) => {
-                    if let Some(_party_id) = signature_shares.take(&response.party_id) {
-                        self.signature_shares
-                            .insert(response.party_id, response.signature_share);
+        self.share_stragglers = self.public_nonces.keys().cloned().collect();
+        let mut unverified = self.share_stragglers.len();
+        let (sender, verified) = mpsc::channel();
+        while unverified > 0 {
+            // once every share is in only their verification is left to wait for
+            let results: Vec<_> = if self.share_stragglers.is_empty() {
+                verified.recv().into_iter().collect()
+            } else {
+                self.receive_signature_share(&verifier, &sender)?;
+                verified.try_iter().collect()
+            };
+            for (key_id, share, valid) in results {
+                if !valid {
+                    if !self.share_stragglers.is_empty() {
+                        info!(
+                            target: COORDINATOR,
+                            "Not waiting for signature shares from stragglers {:?}",
+                            self.share_stragglers
+                        );
                     }
-                    debug!(
-                        "signature share for {} received.  left to receive: {:?}",
-                        response.party_id, signature_shares
-                    );
+                    let signer_ids = self.key_owners.get(&key_id).into_iter().cloned().collect();
+                    return Err(Error::BadSignatureShares(vec![key_id], signer_ids));
                 }
-                MessageTypes::SignShareRequest(_) => {}
-                msg => {
-                    warn!("SigShare loop got unexpected msg {:?}", msg.type_id());
+                self.signature_shares.insert(key_id, share);
+                unverified -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Kind of the signing round in progress
+    fn sign_kind(&self) -> RoundKind {
+        match self.sign_key_path {
+            Some(_) => RoundKind::KeyPathSign,
+            None => RoundKind::Sign,
+        }
+    }
+
+    /// Wait for the next signature share and queue it for verification
+    fn receive_signature_share(
+        &mut self,
+        verifier: &Arc<ShareVerifier>,
+        sender: &mpsc::Sender<(u32, v1::SignatureShare, bool)>,
+    ) -> Result<(), Error> {
+        match self.wait_for_next_message()?.msg {
+            MessageTypes::SignShareResponse(response) => {
+                let key_id = response.key_id;
+                let owner = self.public_nonces.get(&key_id).map(|nonce| nonce.signer_id);
+                if owner != Some(response.signer_id) {
+                    warn!(
+                        target: COORDINATOR,
+                        "Dropping signature share for key #{} from signer #{}, expected signer #{:?}",
+                        key_id, response.signer_id, owner
+                    );
+                } else if self.share_stragglers.remove(&key_id) {
+                    self.progress(
+                        self.sign_kind(),
+                        Progress::SignShareResponse,
+                        response.signer_id,
+                        Some(key_id),
+                    );
+                    let (verifier, sender) = (verifier.clone(), sender.clone());
+                    rayon::spawn(move || {
+                        let share = response.signature_share;
+                        let valid = verifier.verify(key_id, &share);
+                        // the round may already have failed on another share
+                        let _ = sender.send((key_id, share, valid));
+                    });
                 }
+                debug!(
+                    target: COORDINATOR,
+                    "signature share for {} received.  left to receive: {:?}",
+                    key_id, self.share_stragglers
+                );
+            }
+            MessageTypes::SignShareRequest(_) => {}
+            msg => {
+                warn!(target: COORDINATOR, "SigShare loop got unexpected msg {:?}", msg.type_id());
             }
         }
         Ok(())
     }
 
+    /// The signer and key thresholds rounds are held to
+    pub fn thresholds(&self) -> Thresholds {
+        self.thresholds
+    }
+
+    /// Keys whose signature shares had not arrived when the last signing round ended
+    pub fn share_stragglers(&self) -> Vec<u32> {
+        self.share_stragglers.iter().cloned().collect()
+    }
+
+    /// Sign the tagged hash of `msg` under the domain tag `tag`
+    pub fn sign_bytes(
+        &mut self,
+        tag: &str,
+        msg: &[u8],
+    ) -> Result<(Signature, SchnorrProof), Error> {
+        self.sign_digest(&MessageDigest::tagged(tag, msg))
+    }
+
+    /// Sign `digest` as is, retrying rounds which timed out
+    pub fn sign_digest(
+        &mut self,
+        digest: &MessageDigest,
+    ) -> Result<(Signature, SchnorrProof), Error> {
+        let msg = digest.as_bytes().as_slice();
+        self.check_upgrade_window()?;
+        let retry = self.round_retry.clone();
+        retry.retry_if(
+            || {
+                self.watched(RoundKind::Sign, Some(msg), |coordinator| {
+                    coordinator.run_sign_round(msg, None)
+                })
+            },
+            |e| matches!(e, Error::RoundTimeout(_)),
+        )
+    }
+
+    /// Sign `digest`, the sighash of a taproot key path spend of the output of `key_path`, with
+    /// the group key tweaked for that output, so the signature verifies with the output key
+    /// rather than the group key. Retries rounds which timed out.
+    pub fn sign_key_path(
+        &mut self,
+        digest: &MessageDigest,
+        key_path: &KeyPath,
+    ) -> Result<(Signature, SchnorrProof), Error> {
+        let msg = digest.as_bytes().as_slice();
+        self.check_upgrade_window()?;
+        let retry = self.round_retry.clone();
+        retry.retry_if(
+            || {
+                self.watched(RoundKind::KeyPathSign, Some(msg), |coordinator| {
+                    coordinator.run_sign_round(msg, Some(key_path))
+                })
+            },
+            |e| matches!(e, Error::RoundTimeout(_)),
+        )
+    }
+
     #[allow(non_snake_case)]
-    pub fn sign_message(&mut self, msg: &[u8]) -> Result<(Signature, SchnorrProof), Error> {
-        debug!("Attempting to Sign Message");
+    fn run_sign_round(
+        &mut self,
+        msg: &[u8],
+        key_path: Option<&KeyPath>,
+    ) -> Result<(Signature, SchnorrProof), Error> {
+        debug!(target: COORDINATOR, "Attempting to Sign Message");
         if self.aggregate_public_key == Point::default() {
             return Err(Error::NoAggregatePublicKey);
         }
+        self.clear_requests();
+        self.sign_key_path = key_path.cloned();
+        self.begin_journal_round()?;
+        self.events.publish(RoundEvent::Started {
+            kind: self.sign_kind(),
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+        });
 
         //Continually compute a new aggregate nonce until we have a valid even R
         loop {
             let R = self.compute_aggregate_nonce(msg)?;
             if R.has_even_y() {
-                debug!("Success: R has even y coord: {}", &R);
+                debug!(target: COORDINATOR, "Success: R has even y coord: {}", &R);
                 break;
             } else {
-                warn!("Failure: R does not have even y coord: {}", R);
+                warn!(target: COORDINATOR, "Failure: R does not have even y coord: {}", R);
             }
         }
 
         // make an array of dkg public share polys for SignatureAggregator
         debug!(
+            target: COORDINATOR,
             "collecting commitments from 1..{} in {:?}",
             self.total_keys,
             self.dkg_public_shares.keys().collect::<Vec<&u32>>()
@@ -296,55 +1428,317 @@ where
             .collect();
 
         debug!(
-            "SignatureAggregator::new total_keys: {} threshold: {} commitments: {}",
+            target: COORDINATOR,
+            "ShareVerifier::new total_keys: {} threshold: {} commitments: {}",
             self.total_keys,
-            self.threshold,
+            self.thresholds.keys,
             polys.len()
         );
 
-        let mut aggregator = v1::SignatureAggregator::new(self.total_keys, self.threshold, polys)?;
-
         let id_nonces: Vec<(u32, PublicNonce)> = self
             .public_nonces
             .iter()
             .map(|(i, n)| (*i, n.nonce.clone()))
             .collect();
+        let verifier = Arc::new(match key_path {
+            Some(key_path) => {
+                ShareVerifier::for_key_path(&polys, self.thresholds.keys, msg, &id_nonces, key_path)
+            }
+            None => ShareVerifier::new(&polys, self.thresholds.keys, msg, &id_nonces),
+        });
 
         // request signature shares
-        self.request_signature_shares(&id_nonces, msg)?;
-        self.collect_signature_shares()?;
+        self.request_signature_shares(&id_nonces, msg, key_path)?;
+        self.collect_signature_shares(verifier.clone())?;
 
-        let nonces = id_nonces
-            .iter()
-            .map(|(_i, n)| n.clone())
-            .collect::<Vec<PublicNonce>>();
-        let shares = id_nonces
-            .iter()
-            .map(|(i, _n)| self.signature_shares[i].clone())
-            .collect::<Vec<v1::SignatureShare>>();
-        debug!(
-            "aggregator.sign({:?}, {:?}, {:?})",
-            msg,
-            nonces.len(),
-            shares.len()
-        );
+        // every share has been verified, so they are summed without checking them again
+        let sig = verifier.aggregate(id_nonces.iter().map(|(i, _n)| &self.signature_shares[i]));
 
-        let sig = aggregator.sign(msg, &nonces, &shares)?;
-
-        info!("Signature ({}, {})", sig.R, sig.z);
+        info!(target: COORDINATOR, "Signature ({}, {})", sig.R, sig.z);
 
         let proof = SchnorrProof::new(&sig).map_err(Error::Bip340)?;
 
-        info!("SchnorrProof ({}, {})", proof.r, proof.s);
+        info!(target: COORDINATOR, "SchnorrProof ({}, {})", proof.r, proof.s);
 
-        if !proof.verify(&self.aggregate_public_key.x(), msg) {
-            warn!("SchnorrProof failed to verify!");
+        if !proof.verify(&verifier.key().x(), msg) {
+            warn!(target: COORDINATOR, "SchnorrProof failed to verify!");
             return Err(Error::SchnorrProofFailed);
         }
 
         Ok((sig, proof))
     }
 
+    /// Sign every digest, each under its own nonces, in a single round trip for nonces and one
+    /// for signature shares, e.g. the sighashes of the inputs of a multi-input transaction.
+    /// Batches over [`MAX_BATCH_SIZE`] take a round per chunk. Retries rounds which timed out.
+    pub fn sign_digests(
+        &mut self,
+        digests: &[MessageDigest],
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        self.sign_batches(digests, None)
+    }
+
+    /// Sign every digest as [`Coordinator::sign_digests`] does, each the sighash of a taproot
+    /// key path spend of the output of `key_path`, with the group key tweaked for that output
+    /// as [`Coordinator::sign_key_path`] does
+    pub fn sign_digests_key_path(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: &KeyPath,
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        self.sign_batches(digests, Some(key_path))
+    }
+
+    fn sign_batches(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: Option<&KeyPath>,
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        self.check_upgrade_window()?;
+        let kind = match key_path {
+            Some(_) => RoundKind::KeyPathSign,
+            None => RoundKind::Sign,
+        };
+        let mut signatures = Vec::with_capacity(digests.len());
+        for batch in digests.chunks(MAX_BATCH_SIZE) {
+            let messages: Vec<u8> = batch
+                .iter()
+                .flat_map(|digest| digest.as_bytes().iter().copied())
+                .collect();
+            let retry = self.round_retry.clone();
+            signatures.extend(retry.retry_if(
+                || {
+                    self.watched(kind, Some(&messages), |coordinator| {
+                        coordinator.run_batch_sign_round(batch, key_path)
+                    })
+                },
+                |e| matches!(e, Error::RoundTimeout(_)),
+            )?);
+        }
+        Ok(signatures)
+    }
+
+    fn run_batch_sign_round(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: Option<&KeyPath>,
+    ) -> Result<Vec<(Signature, SchnorrProof)>, Error> {
+        debug!(target: COORDINATOR, "Attempting to sign {} messages", digests.len());
+        if self.aggregate_public_key == Point::default() {
+            return Err(Error::NoAggregatePublicKey);
+        }
+        self.clear_requests();
+        self.sign_key_path = key_path.cloned();
+        self.begin_journal_round()?;
+        self.events.publish(RoundEvent::Started {
+            kind: self.sign_kind(),
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+        });
+
+        // twice the nonces needed, so every message is likely to find some with an even R
+        let message_nonces = loop {
+            self.collect_batch_nonces(2 * digests.len())?;
+            if let Some(message_nonces) = self.assign_batch_nonces(digests) {
+                break message_nonces;
+            }
+            warn!(target: COORDINATOR, "Failure: not every message has nonces with an even R");
+        };
+
+        let polys: Vec<PolyCommitment> = self
+            .dkg_public_shares
+            .values()
+            .map(|ps| ps.public_share.clone())
+            .collect();
+        let verifiers: Vec<ShareVerifier> = digests
+            .iter()
+            .zip(&message_nonces)
+            .map(|(digest, nonces)| match key_path {
+                Some(key_path) => ShareVerifier::for_key_path(
+                    &polys,
+                    self.thresholds.keys,
+                    digest.as_bytes(),
+                    nonces,
+                    key_path,
+                ),
+                None => ShareVerifier::new(&polys, self.thresholds.keys, digest.as_bytes(), nonces),
+            })
+            .collect();
+
+        let owners: Vec<(u32, u32)> = self
+            .batch_nonces
+            .iter()
+            .map(|(key_id, nonces)| (*key_id, nonces.signer_id))
+            .collect();
+        for (key_id, signer_id) in owners {
+            let request = BatchSignRequest {
+                dkg_id: self.current_dkg_id,
+                sign_id: self.current_sign_id,
+                signer_id,
+                key_id,
+                messages: digests
+                    .iter()
+                    .zip(&message_nonces)
+                    .map(|(digest, nonces)| BatchMessage {
+                        nonces: nonces.clone(),
+                        message: digest.as_bytes().to_vec(),
+                    })
+                    .collect(),
+                key_path: key_path.cloned(),
+            };
+            self.send_request(MessageTypes::BatchSignRequest(request))?;
+        }
+        let shares = self.collect_batch_signature_shares(&verifiers)?;
+
+        let mut signatures = Vec::with_capacity(digests.len());
+        for (index, (digest, verifier)) in digests.iter().zip(&verifiers).enumerate() {
+            // every share has been verified, so they are summed without checking them again
+            let sig = verifier.aggregate(shares.values().map(|shares| &shares[index]));
+            let proof = SchnorrProof::new(&sig).map_err(Error::Bip340)?;
+            if !proof.verify(&verifier.key().x(), digest.as_bytes()) {
+                warn!(target: COORDINATOR, "SchnorrProof of message #{} failed to verify!", index);
+                return Err(Error::SchnorrProofFailed);
+            }
+            signatures.push((sig, proof));
+        }
+        info!(target: COORDINATOR, "Signed {} messages in one round", signatures.len());
+        Ok(signatures)
+    }
+
+    fn collect_batch_nonces(&mut self, count: usize) -> Result<(), Error> {
+        self.public_nonces.clear();
+        self.batch_nonces.clear();
+
+        let request = BatchNonceRequest {
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+            sign_nonce_id: self.current_sign_nonce_id,
+            count: count as u32,
+        };
+        debug!(target: COORDINATOR, "dkg_id #{}. BatchNonceRequest sent.", self.current_dkg_id);
+        self.send_request(MessageTypes::BatchNonceRequest(request))?;
+        let key_ids = self.signing_key_ids()?;
+
+        while self.batch_nonces.len() < key_ids.len() {
+            match self.wait_for_next_message()?.msg {
+                MessageTypes::BatchNonceRequest(_) => {}
+                MessageTypes::BatchNonceResponse(response)
+                    if key_ids.contains(&response.key_id) && response.nonces.len() == count =>
+                {
+                    let key_id = response.key_id;
+                    if self.claim_key(response.signer_id, key_id) {
+                        self.progress(
+                            self.sign_kind(),
+                            Progress::NonceResponse,
+                            response.signer_id,
+                            Some(key_id),
+                        );
+                        self.batch_nonces.insert(key_id, response);
+                    }
+                }
+                msg => {
+                    warn!(
+                        target: COORDINATOR,
+                        "BatchNonceLoop Got unexpected message {:?})",
+                        msg.type_id()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The nonces of each key signing each message: for every message, the first batch index
+    /// not taken by an earlier message whose aggregate nonce has an even y. `None` if some
+    /// message finds none.
+    #[allow(non_snake_case)]
+    fn assign_batch_nonces(
+        &self,
+        digests: &[MessageDigest],
+    ) -> Option<Vec<Vec<(u32, PublicNonce)>>> {
+        let ids: Vec<usize> = self.batch_nonces.keys().map(|i| *i as usize).collect();
+        let count = self.batch_nonces.values().next()?.nonces.len();
+        let mut unused: Vec<usize> = (0..count).collect();
+        let mut assigned = Vec::with_capacity(digests.len());
+        for digest in digests {
+            let at_index = |index: usize| -> Vec<PublicNonce> {
+                self.batch_nonces
+                    .values()
+                    .map(|response| response.nonces[index].clone())
+                    .collect()
+            };
+            let position = unused.iter().position(|index| {
+                let (_, R) = compute::intermediate(digest.as_bytes(), &ids, &at_index(*index));
+                R.has_even_y()
+            })?;
+            let index = unused.remove(position);
+            assigned.push(
+                self.batch_nonces
+                    .keys()
+                    .cloned()
+                    .zip(at_index(index))
+                    .collect(),
+            );
+        }
+        Some(assigned)
+    }
+
+    /// Collect every key's shares of the batch, failing on the first key whose shares do not
+    /// all verify. Returns the shares by key id, in the order of the messages.
+    fn collect_batch_signature_shares(
+        &mut self,
+        verifiers: &[ShareVerifier],
+    ) -> Result<BTreeMap<u32, Vec<v1::SignatureShare>>, Error> {
+        self.share_stragglers = self.batch_nonces.keys().cloned().collect();
+        let mut shares = BTreeMap::new();
+        while !self.share_stragglers.is_empty() {
+            match self.wait_for_next_message()?.msg {
+                MessageTypes::BatchSignResponse(response) => {
+                    let key_id = response.key_id;
+                    let owner = self.batch_nonces.get(&key_id).map(|nonce| nonce.signer_id);
+                    if owner != Some(response.signer_id) {
+                        warn!(
+                            target: COORDINATOR,
+                            "Dropping signature shares for key #{} from signer #{}, expected signer #{:?}",
+                            key_id, response.signer_id, owner
+                        );
+                        continue;
+                    }
+                    if !self.share_stragglers.remove(&key_id) {
+                        continue;
+                    }
+                    self.progress(
+                        self.sign_kind(),
+                        Progress::SignShareResponse,
+                        response.signer_id,
+                        Some(key_id),
+                    );
+                    let valid = response.signature_shares.len() == verifiers.len()
+                        && response
+                            .signature_shares
+                            .par_iter()
+                            .zip(verifiers.par_iter())
+                            .all(|(share, verifier)| verifier.verify(key_id, share));
+                    if !valid {
+                        let signer_ids =
+                            self.key_owners.get(&key_id).into_iter().cloned().collect();
+                        return Err(Error::BadSignatureShares(vec![key_id], signer_ids));
+                    }
+                    shares.insert(key_id, response.signature_shares);
+                }
+                MessageTypes::BatchSignRequest(_) => {}
+                msg => {
+                    warn!(
+                        target: COORDINATOR,
+                        "BatchSigShare loop got unexpected msg {:?}",
+                        msg.type_id()
+                    );
+                }
+            }
+        }
+        Ok(shares)
+    }
+
     fn calculate_aggregate_public_key(&mut self) -> Result<Point, Error> {
         self.aggregate_public_key = self
             .dkg_public_shares
@@ -353,6 +1747,15 @@ where
         Ok(self.aggregate_public_key)
     }
 
+    /// Polynomial commitments of the keys which took part in the last DKG round, by key id
+    pub fn party_commitments(&self) -> impl Iterator<Item = (u32, &PolyCommitment)> {
+        let excluded_key_ids = self.excluded_key_ids();
+        self.dkg_public_shares
+            .iter()
+            .filter(move |(key_id, _)| !excluded_key_ids.contains(key_id))
+            .map(|(key_id, share)| (*key_id, &share.public_share))
+    }
+
     pub fn get_aggregate_public_key(&self) -> Result<Point, Error> {
         if self.aggregate_public_key == Point::default() {
             Err(Error::NoAggregatePublicKey)
@@ -361,10 +1764,31 @@ where
         }
     }
 
+    /// [`commitment_digest`] of the commitments of the current DKG round, which signers must
+    /// have built their private shares on
+    fn commitment_digest(&self) -> [u8; 32] {
+        commitment_digest(
+            self.current_dkg_id,
+            self.dkg_public_shares
+                .iter()
+                .map(|(key_id, share)| (key_id, &share.public_share)),
+        )
+    }
+
+    /// Wait for the commitments of the roster, giving up on the round once the commitment
+    /// phase overruns `dkg_commitment_timeout_secs`
     fn wait_for_public_shares(&mut self) -> Result<Point, Error> {
-        let mut ids_to_await: HashSet<usize> = (1..=self.total_signers).collect();
+        let armed = self.commitment_watchdog.arm();
+        let result = self.gather_public_shares();
+        self.commitment_watchdog.disarm(armed);
+        result
+    }
+
+    fn gather_public_shares(&mut self) -> Result<Point, Error> {
+        let mut ids_to_await = self.roster();
 
         info!(
+            target: COORDINATOR,
             "DKG Round #{}: waiting for Dkg Public Shares from signers {:?}",
             self.current_dkg_id, ids_to_await
         );
@@ -374,63 +1798,405 @@ where
                 let key = self.calculate_aggregate_public_key()?;
                 // check to see if aggregate public key has even y
                 if key.has_even_y() {
-                    debug!("Aggregate public key has even y coord!");
-                    info!("Aggregate public key: {}", key);
+                    debug!(target: COORDINATOR, "Aggregate public key has even y coord!");
+                    info!(target: COORDINATOR, "Aggregate public key: {}", key);
                     self.aggregate_public_key = key;
                     return Ok(key);
                 } else {
-                    warn!("DKG Round #{} Failed: Aggregate public key does not have even y coord, re-running dkg.", self.current_dkg_id);
-                    ids_to_await = (1..=self.total_signers).collect();
+                    warn!(
+                        target: COORDINATOR,
+                        "DKG Round #{} Failed: Aggregate public key does not have even y coord, re-running dkg.",
+                        self.current_dkg_id
+                    );
+                    ids_to_await = self.roster();
                     self.start_public_shares()?;
                 }
             }
 
             match self.wait_for_next_message()?.msg {
                 MessageTypes::DkgPublicEnd(dkg_end_msg) => {
-                    ids_to_await.remove(&dkg_end_msg.signer_id);
+                    if ids_to_await.remove(&(dkg_end_msg.signer_id as usize)) {
+                        self.progress(
+                            RoundKind::Dkg,
+                            Progress::DkgPublicEnd,
+                            dkg_end_msg.signer_id,
+                            None,
+                        );
+                    }
                     debug!(
+                        target: COORDINATOR,
                         "DKG_Public_End round #{} from signer #{}. Waiting on {:?}",
                         dkg_end_msg.dkg_id, dkg_end_msg.signer_id, ids_to_await
                     );
                 }
-                MessageTypes::DkgPublicShare(dkg_public_share) => {
-                    self.dkg_public_shares
-                        .insert(dkg_public_share.party_id, dkg_public_share.clone());
-
-                    debug!(
-                        "DKG round #{} DkgPublicShare from party #{}",
-                        dkg_public_share.dkg_id, dkg_public_share.party_id
-                    );
+                MessageTypes::DkgPublicShare(dkg_public_share)
+                    if !self.excluded_key_ids().contains(&dkg_public_share.key_id) =>
+                {
+                    if self.claim_key(dkg_public_share.signer_id, dkg_public_share.key_id) {
+                        self.progress(
+                            RoundKind::Dkg,
+                            Progress::DkgPublicShare,
+                            dkg_public_share.signer_id,
+                            Some(dkg_public_share.key_id),
+                        );
+                        debug!(
+                            target: COORDINATOR,
+                            "DKG round #{} DkgPublicShare from signer #{} key #{}",
+                            dkg_public_share.dkg_id,
+                            dkg_public_share.signer_id,
+                            dkg_public_share.key_id
+                        );
+                        self.dkg_public_shares
+                            .insert(dkg_public_share.key_id, dkg_public_share);
+                    }
                 }
                 _ => {}
             }
         }
     }
 
+    /// Record `signer_id` as the owner of `key_id`, returning whether it is. Messages naming
+    /// another owner than the one on record are dropped by the caller.
+    fn claim_key(&mut self, signer_id: u32, key_id: u32) -> bool {
+        let owner = *self.key_owners.entry(key_id).or_insert(signer_id as usize);
+        if owner != signer_id as usize {
+            warn!(
+                target: COORDINATOR,
+                "Dropping message from signer #{} about key #{}, which signer #{} owns",
+                signer_id, key_id, owner
+            );
+        }
+        owner == signer_id as usize
+    }
+
     fn wait_for_dkg_end(&mut self) -> Result<(), Error> {
-        let mut ids_to_await: HashSet<usize> = (1..=self.total_signers).collect();
+        let mut ids_to_await = self.roster();
         info!(
+            target: COORDINATOR,
             "DKG Round #{}: waiting for Dkg End from signers {:?}",
             self.current_dkg_id, ids_to_await
         );
+        let mut statuses: BTreeMap<usize, DkgStatus> = BTreeMap::new();
+        let mut share_digests: BTreeMap<u32, BTreeMap<u32, [u8; 32]>> = BTreeMap::new();
+        let mut reports: Vec<EquivocationReport> = vec![];
+        let mut private_shares: BTreeMap<u32, [u8; 32]> = BTreeMap::new();
+        let mut blames: Vec<DkgBlame> = vec![];
+        let digest = self.commitment_digest();
         while !ids_to_await.is_empty() {
-            if let MessageTypes::DkgEnd(dkg_end_msg) = self.wait_for_next_message()?.msg {
-                ids_to_await.remove(&dkg_end_msg.signer_id);
-                debug!(
-                    "DKG_End round #{} from signer #{}. Waiting on {:?}",
-                    dkg_end_msg.dkg_id, dkg_end_msg.signer_id, ids_to_await
+            match self.wait_for_next_message()?.msg {
+                MessageTypes::DkgEnd(dkg_end_msg)
+                    if !self
+                        .excluded_signers
+                        .contains_key(&(dkg_end_msg.signer_id as usize)) =>
+                {
+                    if ids_to_await.remove(&(dkg_end_msg.signer_id as usize)) {
+                        self.progress(
+                            RoundKind::Dkg,
+                            Progress::DkgEnd,
+                            dkg_end_msg.signer_id,
+                            None,
+                        );
+                    }
+                    debug!(
+                        target: COORDINATOR,
+                        "DKG_End round #{} from signer #{}. Waiting on {:?}",
+                        dkg_end_msg.dkg_id, dkg_end_msg.signer_id, ids_to_await
+                    );
+                    statuses.insert(dkg_end_msg.signer_id as usize, dkg_end_msg.status);
+                }
+                MessageTypes::DkgPrivateShares(shares) => {
+                    for (key_id, _) in &shares.private_shares {
+                        self.claim_key(shares.signer_id, *key_id);
+                    }
+                    // signers reject these shares themselves, this only points at the relay
+                    if shares.commitment_digest != digest {
+                        warn!(
+                            target: COORDINATOR,
+                            "DKG round #{}: signer #{} built its private shares on other commitments than ours",
+                            self.current_dkg_id, shares.signer_id
+                        );
+                    }
+                    // what the signer sent, for blames about these shares to be checked against
+                    private_shares.insert(shares.signer_id, shares.digest());
+                }
+                // signers send these before their DkgEnd, so all are in once the DkgEnds are
+                MessageTypes::DkgShareDigests(digests) if digests.dkg_id == self.current_dkg_id => {
+                    share_digests.insert(digests.signer_id, digests.digests.into_iter().collect());
+                }
+                MessageTypes::EquivocationReport(report)
+                    if report.dkg_id == self.current_dkg_id =>
+                {
+                    reports.push(report);
+                }
+                MessageTypes::DkgBlame(blame) if blame.dkg_id == self.current_dkg_id => {
+                    blames.push(blame);
+                }
+                _ => {}
+            }
+        }
+        self.check_equivocations(&share_digests, &reports)?;
+        let blamed = self.check_blames(&private_shares, &blames, &digest);
+        self.check_dkg_statuses(&statuses, blamed)
+    }
+
+    /// Signers the [`DkgBlame`]s incriminate: the accused if the shares it sent the blaming
+    /// signer do not match its commitments, the blaming signer if they do or if its evidence
+    /// is not the message the accused sent, as we saw it go by. Blames about shares we did not
+    /// see, or built on other commitments than ours, are only logged.
+    fn check_blames(
+        &self,
+        private_shares: &BTreeMap<u32, [u8; 32]>,
+        blames: &[DkgBlame],
+        digest: &[u8; 32],
+    ) -> BTreeSet<usize> {
+        let mut blamed = BTreeSet::new();
+        for blame in blames {
+            let sent = private_shares.get(&blame.accused_id);
+            if sent.is_none() || blame.shares.commitment_digest != *digest {
+                warn!(
+                    target: COORDINATOR,
+                    "DKG Round #{}: cannot check the blame of signer #{} by signer #{}",
+                    self.current_dkg_id, blame.accused_id, blame.signer_id
+                );
+                continue;
+            }
+            let owned_by = |signer_id: u32| {
+                move |key_id: &u32| self.key_owners.get(key_id) == Some(&(signer_id as usize))
+            };
+            let key_ids: Vec<u32> = self
+                .key_owners
+                .keys()
+                .cloned()
+                .filter(owned_by(blame.signer_id))
+                .collect();
+            let accused = owned_by(blame.accused_id);
+            let proven = sent == Some(&blame.shares.digest())
+                && blame.proves_bad_shares(&key_ids, |key_id| {
+                    self.dkg_public_shares
+                        .get(&key_id)
+                        .filter(|_| accused(&key_id))
+                        .map(|share| &share.public_share)
+                });
+            if proven {
+                warn!(
+                    target: COORDINATOR,
+                    "DKG Round #{}: signer #{} proved signer #{} sent it bad private shares",
+                    self.current_dkg_id, blame.signer_id, blame.accused_id
+                );
+                blamed.insert(blame.accused_id as usize);
+            } else {
+                warn!(
+                    target: COORDINATOR,
+                    "DKG Round #{}: signer #{} blamed signer #{} without evidence",
+                    self.current_dkg_id, blame.signer_id, blame.accused_id
                 );
+                blamed.insert(blame.signer_id as usize);
             }
         }
-        Ok(())
+        blamed
     }
 
+    /// Fail the round if a signer sent different private shares to different signers, as
+    /// found in the share digests signers sent us or confirmed by them. Reports naming digests
+    /// other than the ones their receivers sent us are ignored.
+    fn check_equivocations(
+        &self,
+        share_digests: &BTreeMap<u32, BTreeMap<u32, [u8; 32]>>,
+        reports: &[EquivocationReport],
+    ) -> Result<(), Error> {
+        let mut equivocations = find_equivocations(share_digests);
+        for report in reports {
+            let confirmed = report.digests.iter().all(|(receiver_id, digest)| {
+                share_digests
+                    .get(receiver_id)
+                    .and_then(|digests| digests.get(&report.equivocator_id))
+                    .is_none_or(|received| received == digest)
+            }) && report
+                .digests
+                .iter()
+                .any(|(_, digest)| *digest != report.digests[0].1);
+            if !confirmed {
+                warn!(
+                    target: COORDINATOR,
+                    "DKG Round #{}: ignoring unconfirmed equivocation report from signer #{} about signer #{}",
+                    self.current_dkg_id, report.signer_id, report.equivocator_id
+                );
+                continue;
+            }
+            equivocations
+                .entry(report.equivocator_id)
+                .or_insert_with(|| report.digests.clone());
+        }
+        if equivocations.is_empty() {
+            return Ok(());
+        }
+        let receivers: BTreeSet<usize> = equivocations
+            .values()
+            .flatten()
+            .map(|(receiver_id, _)| *receiver_id as usize)
+            .collect();
+        let equivocators: Vec<usize> = equivocations.keys().map(|id| *id as usize).collect();
+        warn!(
+            target: COORDINATOR,
+            "DKG Round #{}: signers {:?} sent different private shares to signers {:?}, aborting",
+            self.current_dkg_id, equivocators, receivers
+        );
+        Err(Error::DkgFailed(
+            self.current_dkg_id,
+            receivers.into_iter().collect(),
+            equivocators,
+        ))
+    }
+
+    /// Apply the [`DkgFailurePolicy`] to the statuses signers reported in DkgEnd, blaming the
+    /// signers `blames` incriminated along with those the statuses name
+    fn check_dkg_statuses(
+        &self,
+        statuses: &BTreeMap<usize, DkgStatus>,
+        blames: BTreeSet<usize>,
+    ) -> Result<(), Error> {
+        let failed: Vec<usize> = statuses
+            .iter()
+            .filter(|(_, status)| matches!(status, DkgStatus::Failure(_)))
+            .map(|(signer_id, _)| *signer_id)
+            .collect();
+        if failed.is_empty() {
+            return Ok(());
+        }
+        let blamed = self.blame(statuses, blames);
+        // the failure policy may tolerate more failures than the signer threshold allows
+        let proceed = self
+            .dkg_failure_policy
+            .tolerates(failed.len(), self.total_signers)
+            && self.thresholds.signers <= statuses.len() - failed.len();
+        warn!(
+            target: COORDINATOR,
+            "DKG Round #{}: {} of {} signers failed blaming signers {:?}, {} under {:?}. Statuses {:?}",
+            self.current_dkg_id,
+            failed.len(),
+            self.total_signers,
+            blamed,
+            if proceed { "proceeding" } else { "aborting" },
+            self.dkg_failure_policy,
+            statuses
+        );
+        if proceed {
+            Ok(())
+        } else {
+            Err(Error::DkgFailed(self.current_dkg_id, failed, blamed))
+        }
+    }
+
+    /// Signers owning the key ids which failed signers reported as at fault, and the signers
+    /// `blames` incriminated. Bad shares are only held against their sender on the evidence of
+    /// a [`DkgBlame`].
+    fn blame(&self, statuses: &BTreeMap<usize, DkgStatus>, blames: BTreeSet<usize>) -> Vec<usize> {
+        let mut blamed = blames;
+        for status in statuses.values() {
+            let DkgStatus::Failure(failure) = status else {
+                continue;
+            };
+            if matches!(failure, DkgFailure::BadShares(_)) {
+                continue;
+            }
+            for key_id in failure.key_ids() {
+                match self.key_owners.get(key_id) {
+                    Some(signer_id) => {
+                        blamed.insert(*signer_id);
+                    }
+                    None => warn!(
+                        target: COORDINATOR,
+                        "DKG Round #{}: no signer sent private shares for key id {}",
+                        self.current_dkg_id, key_id
+                    ),
+                }
+            }
+        }
+        blamed.into_iter().collect()
+    }
+
+    /// Rejections received from signers since the current DKG or signing round began
+    pub fn rejections(&self) -> &BTreeMap<u32, Rejection> {
+        &self.rejections
+    }
+
+    fn clear_requests(&mut self) {
+        self.outstanding_requests.clear();
+        self.rejections.clear();
+    }
+
+    fn send_request(&mut self, msg: MessageTypes) -> Result<(), Error> {
+        self.outstanding_requests.insert(msg.request_hash());
+        self.send(msg)
+    }
+
+    /// Wait for the next message, handling any rejections of our requests along the way
     fn wait_for_next_message(&mut self) -> Result<Message, Error> {
-        let signer_public_keys = parse_public_keys(&self.key_public_keys);
-        let key_public_keys = parse_public_keys(&self.key_public_keys);
-        let coordinator_public_key = parse_public_key(&self.coordinator_public_key);
+        loop {
+            self.check_watchdog()?;
+            let message = self.poll_next_message()?;
+            match message.msg {
+                MessageTypes::Rejection(rejection) => self.handle_rejection(rejection)?,
+                MessageTypes::UpgradeAck(_) | MessageTypes::Registration(_) => {
+                    self.handle_upgrade_message(&message.msg)
+                }
+                _ => return Ok(message),
+            }
+        }
+    }
+
+    /// Record a rejection, failing the round if the signer will never accept the request
+    fn handle_rejection(&mut self, rejection: Rejection) -> Result<(), Error> {
+        let outstanding = <[u8; 32]>::try_from(rejection.request_hash.as_slice())
+            .is_ok_and(|hash| self.outstanding_requests.contains(&hash));
+        if !outstanding {
+            debug!(
+                target: COORDINATOR,
+                "Ignoring rejection from signer #{} of a request from another round",
+                rejection.signer_id
+            );
+            return Ok(());
+        }
+        warn!(
+            target: COORDINATOR,
+            "Signer #{} rejected request ({:?}): {}",
+            rejection.signer_id, rejection.reason_code, rejection.detail
+        );
+        self.rejections
+            .insert(rejection.signer_id, rejection.clone());
+        if rejection.reason_code.is_transient() {
+            Ok(())
+        } else {
+            Err(Error::Rejected(
+                rejection.signer_id,
+                rejection.reason_code,
+                rejection.detail,
+            ))
+        }
+    }
+
+    fn check_watchdog(&self) -> Result<(), Error> {
+        for watchdog in [&self.watchdog, &self.commitment_watchdog] {
+            match watchdog.timeout() {
+                Some(timeout) if watchdog.tripped() => return Err(Error::RoundTimeout(timeout)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_next_message(&mut self) -> Result<Message, Error> {
+        let keys = PeerKeys {
+            signers: parse_public_keys(&self.signer_public_keys),
+            keys: parse_public_keys(&self.key_public_keys),
+            coordinator: parse_public_key(&self.coordinator_public_key),
+        };
 
         let get_next_message = || {
+            // stop polling as soon as the watchdog gives up on the round
+            if self.watchdog.tripped() || self.commitment_watchdog.tripped() {
+                return Err(backoff::Error::permanent("Round timed out".to_owned()));
+            }
             self.network.poll(self.id);
             match self
                 .network
@@ -439,38 +2205,14 @@ where
                 .map_err(backoff::Error::transient)
             {
                 Ok(m) => {
-                    match &m.msg {
-                        MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => {
-                            assert!(msg.verify(&m.sig, &coordinator_public_key))
-                        }
-                        MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => {
-                            assert!(msg.verify(&m.sig, &signer_public_keys[msg.signer_id - 1]))
-                        }
-                        MessageTypes::DkgPublicShare(msg) => {
-                            assert!(msg.verify(&m.sig, &key_public_keys[msg.party_id as usize]))
-                        }
-                        MessageTypes::DkgPrivateShares(msg) => {
-                            assert!(msg.verify(&m.sig, &key_public_keys[msg.key_id as usize]))
-                        }
-                        MessageTypes::DkgQuery(msg) => {
-                            assert!(msg.verify(&m.sig, &coordinator_public_key))
-                        }
-                        MessageTypes::DkgQueryResponse(msg) => {
-                            let key_id = msg.public_share.id.id.get_u32();
-                            assert!(msg.verify(&m.sig, &key_public_keys[key_id as usize - 1]));
-                        }
-                        MessageTypes::NonceRequest(msg) => {
-                            assert!(msg.verify(&m.sig, &coordinator_public_key))
-                        }
-                        MessageTypes::NonceResponse(msg) => {
-                            assert!(msg.verify(&m.sig, &key_public_keys[msg.party_id as usize]))
-                        }
-                        MessageTypes::SignShareRequest(msg) => {
-                            assert!(msg.verify(&m.sig, &coordinator_public_key))
-                        }
-                        MessageTypes::SignShareResponse(msg) => {
-                            assert!(msg.verify(&m.sig, &key_public_keys[msg.party_id as usize]))
-                        }
+                    // hellos are verified by the signers' ceremony, not part of any round
+                    if !matches!(m.msg, MessageTypes::Hello(_)) && !keys.verify(&m) {
+                        warn!(
+                            target: COORDINATOR,
+                            "Dropped {} not signed by its author",
+                            m.msg.name()
+                        );
+                        return Err(backoff::Error::transient("Dropped message".to_owned()));
                     }
                     Ok(m)
                 }
@@ -479,17 +2221,48 @@ where
         };
 
         let notify = |_err, dur| {
-            debug!("No message. Next poll in {:?}", dur);
+            debug!(target: COORDINATOR, "No message. Next poll in {:?}", dur);
         };
 
         let backoff_timer = backoff::ExponentialBackoffBuilder::new()
             .with_initial_interval(Duration::from_millis(2))
             .with_max_interval(Duration::from_millis(128))
             .build();
-        backoff::retry_notify(backoff_timer, get_next_message, notify).map_err(|_| Error::Timeout)
+        backoff::retry_notify(backoff_timer, get_next_message, notify)
+            .map_err(|_| self.check_watchdog().err().unwrap_or(Error::Timeout))
     }
 }
 
+/// What a successful round produced, as recorded in the round history
+trait RoundResult {
+    fn to_hex(&self) -> String;
+}
+
+impl RoundResult for Point {
+    fn to_hex(&self) -> String {
+        to_hex(self.compress().as_bytes())
+    }
+}
+
+impl RoundResult for (Signature, SchnorrProof) {
+    fn to_hex(&self) -> String {
+        to_hex(&self.1.to_bytes())
+    }
+}
+
+impl RoundResult for Vec<(Signature, SchnorrProof)> {
+    fn to_hex(&self) -> String {
+        self.iter()
+            .map(RoundResult::to_hex)
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Http network error: {0}")]
@@ -504,6 +2277,61 @@ pub enum Error {
     SchnorrProofFailed,
     #[error("Operation timed out")]
     Timeout,
+    #[error("Round timed out after {0:?} and was aborted")]
+    RoundTimeout(Duration),
+    /// Round id, signers which failed, and the signers they blamed for sending bad data
+    #[error("DKG round #{0} failed on signers {1:?}, blaming signers {2:?}")]
+    DkgFailed(u64, Vec<usize>, Vec<usize>),
+    #[error("Signer #{0} rejected the request ({1:?}): {2}")]
+    Rejected(u32, RejectionCode, String),
     #[error("Config Error: {0}")]
     ConfigError(#[from] ConfigError),
+    #[error("{0}")]
+    InvalidKey(String),
+    /// Keys whose signature shares failed verification, and the signers owning them if known
+    #[error("Signature shares from keys {0:?} of signers {1:?} failed verification")]
+    BadSignatureShares(Vec<u32>, Vec<usize>),
+    /// Signers and keys left after excluding blamed signers
+    #[error("Only {0} signers holding {1} keys remain after excluding blamed signers")]
+    RosterTooSmall(usize, usize),
+    #[error("Journal Error: {0}")]
+    JournalError(#[from] JournalError),
+    #[error("History Error: {0}")]
+    HistoryError(#[from] HistoryError),
+    #[error("Upgrade window #{0} is open, rounds resume once signers register again")]
+    UpgradeWindowOpen(u64),
+    /// Refresh id, and the signers which failed it or refreshed from other commitments
+    #[error("Refresh #{0} of the key shares failed on signers {1:?}")]
+    ReshareFailed(u64, Vec<usize>),
+    #[error("Signer #{0} is not in the roster")]
+    NotInRoster(usize),
+    #[error("Signer #{0} already holds keys of the current generation")]
+    AlreadyInRoster(usize),
+    /// Roster change id, and the joining signers whose repaired shares failed their checks
+    #[error("Roster change #{0} failed on signers {1:?}")]
+    RosterChangeFailed(u64, Vec<usize>),
+    #[error("Signing quorum: {0}")]
+    SigningQuorum(#[from] QuorumError),
+}
+
+/// How the signers of a generation were changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reconfiguration {
+    /// The generation kept its aggregate public key
+    Kept(Point),
+    /// DKG ran again over the new roster. The wallet has to be handed off from the `previous`
+    /// aggregate public key to the `current` one.
+    Rekeyed { previous: Point, current: Point },
+}
+
+impl std::fmt::Display for Reconfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Kept(key) => write!(f, "kept aggregate public key {key}"),
+            Self::Rekeyed { previous, current } => write!(
+                f,
+                "re-keyed from aggregate public key {previous} to {current}, hand off the wallet"
+            ),
+        }
+    }
 }
@@ -0,0 +1,148 @@
+//! Live progress of DKG and signing rounds.
+//!
+//! The coordinator publishes a [`RoundEvent`] when a round starts, when a signer's message
+//! moves it forward, and when it completes or fails. [`RoundEvents`] serves them as server-sent
+//! events at `GET /events` on the control endpoints, one JSON object per event:
+//!
+//! ```text
+//! data: {"event":"started","kind":"dkg","dkg_id":3,"sign_id":1}
+//! data: {"event":"received","kind":"dkg","dkg_id":3,"sign_id":1,"message":"dkg_public_share","signer_id":2,"key_id":4}
+//! data: {"event":"completed","kind":"dkg","dkg_id":3,"sign_id":1,"duration_ms":1840}
+//! ```
+use std::sync::{mpsc, Arc, Mutex};
+
+use frost_signer::control::{Response, Route};
+use serde::Serialize;
+
+use crate::history::RoundKind;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RoundEvent {
+    Started {
+        kind: RoundKind,
+        dkg_id: u64,
+        sign_id: u64,
+    },
+    /// A signer's message moved the round forward
+    Received {
+        kind: RoundKind,
+        dkg_id: u64,
+        sign_id: u64,
+        message: Progress,
+        signer_id: u32,
+        key_id: Option<u32>,
+    },
+    Completed {
+        kind: RoundKind,
+        dkg_id: u64,
+        sign_id: u64,
+        duration_ms: u64,
+    },
+    Failed {
+        kind: RoundKind,
+        dkg_id: u64,
+        sign_id: u64,
+        duration_ms: u64,
+        error: String,
+    },
+}
+
+/// The signer messages a round waits on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Progress {
+    DkgPublicShare,
+    DkgPublicEnd,
+    DkgEnd,
+    NonceResponse,
+    SignShareResponse,
+    ReshareShares,
+    ReshareEnd,
+    RosterChangeEnd,
+}
+
+/// Subscribers to the events of one coordinator
+#[derive(Clone, Debug, Default)]
+pub struct RoundEvents {
+    subscribers: Arc<Mutex<Vec<mpsc::Sender<String>>>>,
+}
+
+impl RoundEvents {
+    /// Events published from now on, encoded as JSON
+    pub fn subscribe(&self) -> mpsc::Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(sender);
+        }
+        receiver
+    }
+
+    /// Send `event` to every subscriber, forgetting those which went away
+    pub fn publish(&self, event: RoundEvent) {
+        let Ok(mut subscribers) = self.subscribers.lock() else {
+            return;
+        };
+        if subscribers.is_empty() {
+            return;
+        }
+        match serde_json::to_string(&event) {
+            Ok(json) => subscribers.retain(|subscriber| subscriber.send(json.clone()).is_ok()),
+            Err(e) => tracing::warn!("failed to encode round event {:?}: {}", event, e),
+        }
+    }
+}
+
+impl Route for RoundEvents {
+    fn respond(&self, _method: &str, _path: &str, _body: &str) -> Option<Response> {
+        None
+    }
+
+    fn stream(&self, method: &str, path: &str) -> Option<mpsc::Receiver<String>> {
+        (method == "GET" && path == "/events").then(|| self.subscribe())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscribers_get_the_events_published_after_they_subscribe() {
+        let events = RoundEvents::default();
+        events.publish(RoundEvent::Started {
+            kind: RoundKind::Dkg,
+            dkg_id: 1,
+            sign_id: 1,
+        });
+        let first = events.subscribe();
+        let second = events.stream("GET", "/events").unwrap();
+        assert!(events.stream("GET", "/status").is_none());
+        events.publish(RoundEvent::Received {
+            kind: RoundKind::Sign,
+            dkg_id: 1,
+            sign_id: 2,
+            message: Progress::NonceResponse,
+            signer_id: 3,
+            key_id: Some(4),
+        });
+        drop(second);
+        events.publish(RoundEvent::Failed {
+            kind: RoundKind::Sign,
+            dkg_id: 1,
+            sign_id: 2,
+            duration_ms: 5,
+            error: "round timed out".to_string(),
+        });
+
+        let received: Vec<String> = first.try_iter().collect();
+        assert_eq!(
+            received,
+            vec![
+                r#"{"event":"received","kind":"sign","dkg_id":1,"sign_id":2,"message":"nonce_response","signer_id":3,"key_id":4}"#,
+                r#"{"event":"failed","kind":"sign","dkg_id":1,"sign_id":2,"duration_ms":5,"error":"round timed out"}"#,
+            ]
+        );
+        assert_eq!(events.subscribers.lock().unwrap().len(), 1);
+    }
+}
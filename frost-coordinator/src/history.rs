@@ -0,0 +1,373 @@
+//! History of the DKG and signing rounds the coordinator ran.
+//!
+//! Every round is recorded once it is over, whatever the outcome: which signers took part, how
+//! long it took, and the aggregate public key or signature it produced, so operators can answer
+//! "what did we sign during this window?" from [`RoundHistory::list`] instead of the logs. The
+//! history lives in the journal database and is pruned by `[retention.history]`.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use frost_signer::retention::{Artifact, Usage};
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension};
+use serde::Serialize;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Did not recognize round kind: {0}")]
+    InvalidKindError(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundKind {
+    Dkg,
+    Sign,
+    /// Signing of taproot key path spends, with the aggregate key tweaked for the output, see
+    /// [`frost_signer::taproot`]
+    KeyPathSign,
+    /// Refresh of the key shares of a generation, see [`frost_signer::reshare`]
+    Reshare,
+    /// Change of the signers of a generation, see [`frost_signer::roster`]
+    RosterChange,
+}
+
+impl RoundKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dkg => "dkg",
+            Self::Sign => "sign",
+            Self::KeyPathSign => "key_path_sign",
+            Self::Reshare => "reshare",
+            Self::RosterChange => "roster_change",
+        }
+    }
+}
+
+impl FromStr for RoundKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "dkg" => Self::Dkg,
+            "sign" => Self::Sign,
+            "key_path_sign" => Self::KeyPathSign,
+            "reshare" => Self::Reshare,
+            "roster_change" => Self::RosterChange,
+            other => return Err(Error::InvalidKindError(other.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for RoundKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A finished round about to be recorded
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NewRound {
+    pub kind: RoundKind,
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    /// Unix time in seconds
+    pub started_at: u64,
+    pub duration_ms: u64,
+    /// Signers which sent shares or nonces in the round
+    pub participants: Vec<u32>,
+    /// Hex of the message signed, signing rounds only
+    pub message: Option<String>,
+    /// Hex of the aggregate public key or BIP-340 signature, if the round succeeded
+    pub result: Option<String>,
+    /// Why the round failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Round {
+    pub id: i64,
+    #[serde(flatten)]
+    pub round: NewRound,
+}
+
+impl Round {
+    pub fn succeeded(&self) -> bool {
+        self.round.error.is_none()
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let round = &self.round;
+        write!(
+            f,
+            "#{} {} dkg_id={} sign_id={} started_at={} duration_ms={} signers={:?}",
+            self.id,
+            round.kind,
+            round.dkg_id,
+            round.sign_id,
+            round.started_at,
+            round.duration_ms,
+            round.participants
+        )?;
+        if let Some(message) = &round.message {
+            write!(f, " message={message}")?;
+        }
+        match (&round.result, &round.error) {
+            (_, Some(error)) => write!(f, " failed: {error}"),
+            (Some(result), None) => write!(f, " result={result}"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RoundHistory {
+    conn: RusqliteConnection,
+}
+
+impl RoundHistory {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute(Self::sql_schema(), rusqlite::params![])?;
+        Ok(Self { conn })
+    }
+
+    /// Record a finished round and return its id
+    pub fn record(&self, round: &NewRound) -> Result<i64, Error> {
+        let participants = round
+            .participants
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.conn.execute(
+            Self::sql_insert_round(),
+            rusqlite::params![
+                round.kind.as_str(),
+                round.dkg_id as i64,
+                round.sign_id as i64,
+                round.started_at as i64,
+                round.duration_ms as i64,
+                participants,
+                round.message,
+                round.result,
+                round.error
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Rounds started within `since..until`, in unix seconds, oldest first
+    pub fn list(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Round>, Error> {
+        let since = since.unwrap_or(0).min(i64::MAX as u64) as i64;
+        let until = until.unwrap_or(u64::MAX).min(i64::MAX as u64) as i64;
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_rounds())?
+            .query_map(rusqlite::params![since, until], Self::row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter().map(Self::parse).collect()
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<Round>, Error> {
+        self.conn
+            .query_row(Self::sql_select_round(), rusqlite::params![id], Self::row)
+            .optional()?
+            .map(Self::parse)
+            .transpose()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn row(row: &rusqlite::Row) -> Result<(i64, String, NewRound), RusqliteError> {
+        let participants: String = row.get(6)?;
+        Ok((
+            row.get(0)?,
+            row.get(1)?,
+            NewRound {
+                kind: RoundKind::Dkg,
+                dkg_id: row.get::<_, i64>(2)? as u64,
+                sign_id: row.get::<_, i64>(3)? as u64,
+                started_at: row.get::<_, i64>(4)? as u64,
+                duration_ms: row.get::<_, i64>(5)? as u64,
+                participants: participants
+                    .split(',')
+                    .filter_map(|id| id.parse().ok())
+                    .collect(),
+                message: row.get(7)?,
+                result: row.get(8)?,
+                error: row.get(9)?,
+            },
+        ))
+    }
+
+    fn parse((id, kind, mut round): (i64, String, NewRound)) -> Result<Round, Error> {
+        round.kind = kind.parse()?;
+        Ok(Round { id, round })
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS round_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            dkg_id INTEGER NOT NULL,
+            sign_id INTEGER NOT NULL,
+            started_at INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            participants TEXT NOT NULL,
+            message TEXT,
+            result TEXT,
+            error TEXT
+        )
+        "#
+    }
+
+    const fn sql_insert_round() -> &'static str {
+        r#"
+        INSERT INTO round_history
+            (kind, dkg_id, sign_id, started_at, duration_ms, participants, message, result, error)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#
+    }
+
+    const fn sql_select_rounds() -> &'static str {
+        r#"
+        SELECT id, kind, dkg_id, sign_id, started_at, duration_ms, participants, message, result, error
+        FROM round_history WHERE started_at >= ?1 AND started_at < ?2 ORDER BY id ASC
+        "#
+    }
+
+    const fn sql_select_round() -> &'static str {
+        r#"
+        SELECT id, kind, dkg_id, sign_id, started_at, duration_ms, participants, message, result, error
+        FROM round_history WHERE id=?1
+        "#
+    }
+
+    const fn sql_select_usage() -> &'static str {
+        r#"
+        SELECT COUNT(*),
+            COALESCE(SUM(LENGTH(participants) + COALESCE(LENGTH(message), 0)
+                + COALESCE(LENGTH(result), 0) + COALESCE(LENGTH(error), 0)), 0),
+            MIN(started_at)
+        FROM round_history
+        "#
+    }
+
+    const fn sql_delete_before() -> &'static str {
+        r#"
+        DELETE FROM round_history WHERE started_at < ?1
+        "#
+    }
+
+    const fn sql_delete_oldest() -> &'static str {
+        r#"
+        DELETE FROM round_history WHERE id = (SELECT MIN(id) FROM round_history)
+        "#
+    }
+}
+
+impl Artifact for RoundHistory {
+    type Error = Error;
+
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn usage(&self) -> Result<Usage, Error> {
+        Ok(self
+            .conn
+            .query_row(Self::sql_select_usage(), rusqlite::params![], |row| {
+                Ok(Usage {
+                    entries: row.get::<_, i64>(0)? as u64,
+                    bytes: row.get::<_, i64>(1)? as u64,
+                    oldest: row.get::<_, Option<i64>>(2)?.map(|time| time as u64),
+                })
+            })?)
+    }
+
+    fn prune(&self, before: Option<u64>, max_bytes: Option<u64>) -> Result<u64, Error> {
+        let mut pruned = 0;
+        if let Some(before) = before {
+            pruned += self
+                .conn
+                .execute(Self::sql_delete_before(), rusqlite::params![before as i64])?
+                as u64;
+        }
+        if let Some(max_bytes) = max_bytes {
+            while self.usage()?.bytes > max_bytes {
+                let deleted = self
+                    .conn
+                    .execute(Self::sql_delete_oldest(), rusqlite::params![])?;
+                if deleted == 0 {
+                    break;
+                }
+                pruned += deleted as u64;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(kind: RoundKind, started_at: u64, error: Option<&str>) -> NewRound {
+        NewRound {
+            kind,
+            dkg_id: 2,
+            sign_id: 1,
+            started_at,
+            duration_ms: 1500,
+            participants: vec![1, 3],
+            message: (kind == RoundKind::Sign).then(|| "68656c6c6f".to_string()),
+            result: error.is_none().then(|| "02ab".to_string()),
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn lists_the_rounds_of_a_window() {
+        let history = RoundHistory::in_memory().unwrap();
+        let dkg = history.record(&round(RoundKind::Dkg, 100, None)).unwrap();
+        let failed = history
+            .record(&round(RoundKind::Sign, 200, Some("Operation timed out")))
+            .unwrap();
+        let signed = history.record(&round(RoundKind::Sign, 300, None)).unwrap();
+
+        let ids = |rounds: Vec<Round>| rounds.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(history.list(None, None).unwrap()),
+            vec![dkg, failed, signed]
+        );
+        assert_eq!(
+            ids(history.list(Some(200), Some(300)).unwrap()),
+            vec![failed]
+        );
+
+        let round = history.get(signed).unwrap().unwrap();
+        assert!(round.succeeded());
+        assert_eq!(round.round.participants, vec![1, 3]);
+        assert_eq!(round.round.kind, RoundKind::Sign);
+        assert!(!history.get(failed).unwrap().unwrap().succeeded());
+        assert_eq!(history.get(99).unwrap(), None);
+        assert_eq!(
+            history.get(failed).unwrap().unwrap().to_string(),
+            format!("#{failed} sign dkg_id=2 sign_id=1 started_at=200 duration_ms=1500 signers=[1, 3] message=68656c6c6f failed: Operation timed out")
+        );
+
+        assert_eq!(history.prune(Some(250), None).unwrap(), 2);
+        assert_eq!(history.usage().unwrap().oldest, Some(300));
+    }
+}
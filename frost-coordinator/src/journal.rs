@@ -0,0 +1,393 @@
+//! Write-ahead journal of the protocol messages the coordinator sends.
+//!
+//! Each DKG or signing round is opened in the journal before its first request goes out, every
+//! outbound message is recorded before it is sent, and the round is closed once the coordinator
+//! is done with it, whatever the outcome. A round still open when the coordinator starts was
+//! interrupted by a crash: the signers may be waiting on it, so the coordinator aborts it on
+//! their side and carries on from the DKG id it had reached.
+//!
+//! Closed rounds are kept for inspection until retention prunes them, see
+//! [`frost_signer::retention`]; the round with the highest DKG id is always kept, as the
+//! coordinator continues from it.
+use std::path::Path;
+
+use frost_signer::retention::{self, Artifact, Usage};
+use frost_signer::{net::Message, signing_round::MessageTypes};
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Message codec error: {0}")]
+    CodecError(#[from] bincode::Error),
+}
+
+/// A round the coordinator never closed, with the messages it had sent, oldest first
+#[derive(Debug)]
+pub struct OpenRound {
+    pub id: i64,
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    pub messages: Vec<Message>,
+}
+
+impl OpenRound {
+    /// How far the round had got, judged by the last request sent
+    pub fn position(&self) -> &'static str {
+        match self.messages.last().map(|message| &message.msg) {
+            Some(MessageTypes::DkgBegin(_)) => "distributing public shares",
+            Some(MessageTypes::DkgPrivateBegin(_)) => "distributing private shares",
+            Some(MessageTypes::NonceRequest(_)) => "collecting nonces",
+            Some(MessageTypes::SignShareRequest(_)) => "collecting signature shares",
+            Some(MessageTypes::BatchNonceRequest(_)) => "collecting batch nonces",
+            Some(MessageTypes::BatchSignRequest(_)) => "collecting batch signature shares",
+            Some(MessageTypes::RoundAbort(_)) => "aborting",
+            Some(_) => "unknown",
+            None => "not started",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    conn: RusqliteConnection,
+}
+
+impl Journal {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute(Self::sql_schema_rounds(), rusqlite::params![])?;
+        conn.execute(Self::sql_schema_messages(), rusqlite::params![])?;
+        // journals written before rounds recorded when they closed
+        let has_closed_at: bool =
+            conn.query_row(Self::sql_has_closed_at(), rusqlite::params![], |row| {
+                row.get(0)
+            })?;
+        if !has_closed_at {
+            conn.execute(Self::sql_add_closed_at(), rusqlite::params![])?;
+        }
+        Ok(Self { conn })
+    }
+
+    /// Open a round, returning its journal id
+    pub fn begin(&self, dkg_id: u64, sign_id: u64) -> Result<i64, Error> {
+        self.conn.execute(
+            Self::sql_insert_round(),
+            rusqlite::params![dkg_id as i64, sign_id as i64],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Record `message` as sent in `round`
+    pub fn record(&self, round: i64, message: &Message) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_message(),
+            rusqlite::params![round, bincode::serialize(message)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn close(&self, round: i64) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_close_round(),
+            rusqlite::params![round, retention::now() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Rounds which were never closed, oldest first
+    pub fn open_rounds(&self) -> Result<Vec<OpenRound>, Error> {
+        let rounds = self
+            .conn
+            .prepare(Self::sql_select_open_rounds())?
+            .query_map(rusqlite::params![], |row| {
+                Ok(OpenRound {
+                    id: row.get(0)?,
+                    dkg_id: row.get::<_, i64>(1)? as u64,
+                    sign_id: row.get::<_, i64>(2)? as u64,
+                    messages: vec![],
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rounds
+            .into_iter()
+            .map(|mut round| {
+                let messages = self
+                    .conn
+                    .prepare(Self::sql_select_messages())?
+                    .query_map(rusqlite::params![round.id], |row| row.get::<_, Vec<u8>>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                round.messages = messages
+                    .iter()
+                    .map(|bytes| bincode::deserialize(bytes))
+                    .collect::<Result<_, _>>()?;
+                Ok(round)
+            })
+            .collect()
+    }
+
+    /// Highest DKG id any journaled round used
+    pub fn last_dkg_id(&self) -> Result<Option<u64>, Error> {
+        Ok(self
+            .conn
+            .query_row(Self::sql_select_last_dkg_id(), rusqlite::params![], |row| {
+                row.get::<_, Option<i64>>(0)
+            })?
+            .map(|dkg_id| dkg_id as u64))
+    }
+
+    /// Delete the prunable rounds which `clause` selects, and their messages. `clause` is
+    /// appended to the query of closed rounds and takes `params`.
+    fn delete_rounds(&self, clause: &str, params: &[&dyn rusqlite::ToSql]) -> Result<u64, Error> {
+        let rounds = format!(
+            "SELECT id FROM journal_rounds WHERE closed=1 AND id <> ({}) {}",
+            Self::sql_select_newest_round(),
+            clause
+        );
+        self.conn.execute(
+            &format!("DELETE FROM journal_messages WHERE round IN ({rounds})"),
+            params,
+        )?;
+        Ok(self.conn.execute(
+            &format!("DELETE FROM journal_rounds WHERE id IN ({rounds})"),
+            params,
+        )? as u64)
+    }
+
+    const fn sql_schema_rounds() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS journal_rounds (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            dkg_id INTEGER NOT NULL,
+            sign_id INTEGER NOT NULL,
+            closed INTEGER NOT NULL DEFAULT 0,
+            closed_at INTEGER
+        )
+        "#
+    }
+
+    const fn sql_schema_messages() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS journal_messages (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            round INTEGER NOT NULL REFERENCES journal_rounds(id),
+            message BLOB NOT NULL
+        )
+        "#
+    }
+
+    const fn sql_insert_round() -> &'static str {
+        r#"
+        INSERT INTO journal_rounds (dkg_id, sign_id) VALUES (?1, ?2)
+        "#
+    }
+
+    const fn sql_insert_message() -> &'static str {
+        r#"
+        INSERT INTO journal_messages (round, message) VALUES (?1, ?2)
+        "#
+    }
+
+    const fn sql_close_round() -> &'static str {
+        r#"
+        UPDATE journal_rounds SET closed=1, closed_at=?2 WHERE id=?1
+        "#
+    }
+
+    const fn sql_select_open_rounds() -> &'static str {
+        r#"
+        SELECT id, dkg_id, sign_id FROM journal_rounds WHERE closed=0 ORDER BY id ASC
+        "#
+    }
+
+    const fn sql_select_messages() -> &'static str {
+        r#"
+        SELECT message FROM journal_messages WHERE round=?1 ORDER BY seq ASC
+        "#
+    }
+
+    const fn sql_select_last_dkg_id() -> &'static str {
+        r#"
+        SELECT MAX(dkg_id) FROM journal_rounds
+        "#
+    }
+
+    const fn sql_has_closed_at() -> &'static str {
+        r#"
+        SELECT COUNT(*) > 0 FROM pragma_table_info('journal_rounds') WHERE name='closed_at'
+        "#
+    }
+
+    const fn sql_add_closed_at() -> &'static str {
+        r#"
+        ALTER TABLE journal_rounds ADD COLUMN closed_at INTEGER
+        "#
+    }
+
+    const fn sql_select_newest_round() -> &'static str {
+        r#"
+        SELECT id FROM journal_rounds ORDER BY dkg_id DESC, id DESC LIMIT 1
+        "#
+    }
+
+    const fn sql_select_usage() -> &'static str {
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM journal_rounds),
+            (SELECT COALESCE(SUM(LENGTH(message)), 0) FROM journal_messages),
+            (SELECT MIN(COALESCE(closed_at, 0)) FROM journal_rounds WHERE closed=1)
+        "#
+    }
+}
+
+impl Artifact for Journal {
+    type Error = Error;
+
+    fn name(&self) -> &'static str {
+        "journal"
+    }
+
+    fn usage(&self) -> Result<Usage, Error> {
+        Ok(self
+            .conn
+            .query_row(Self::sql_select_usage(), rusqlite::params![], |row| {
+                Ok(Usage {
+                    entries: row.get::<_, i64>(0)? as u64,
+                    bytes: row.get::<_, i64>(1)? as u64,
+                    oldest: row.get::<_, Option<i64>>(2)?.map(|time| time as u64),
+                })
+            })?)
+    }
+
+    /// Only closed rounds are pruned; those closed before the journal recorded when count
+    /// as the oldest
+    fn prune(&self, before: Option<u64>, max_bytes: Option<u64>) -> Result<u64, Error> {
+        let mut pruned = 0;
+        if let Some(before) = before {
+            pruned += self.delete_rounds(
+                "AND COALESCE(closed_at, 0) < ?1",
+                rusqlite::params![before as i64],
+            )?;
+        }
+        if let Some(max_bytes) = max_bytes {
+            while self.usage()?.bytes > max_bytes {
+                let deleted = self.delete_rounds("ORDER BY id ASC LIMIT 1", rusqlite::params![])?;
+                if deleted == 0 {
+                    break;
+                }
+                pruned += deleted;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frost_signer::signing_round::{DkgBegin, NonceRequest};
+
+    fn message(msg: MessageTypes) -> Message {
+        Message {
+            msg,
+            sig: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn reports_the_rounds_left_open() {
+        let journal = Journal::in_memory().unwrap();
+        assert_eq!(journal.last_dkg_id().unwrap(), None);
+
+        let dkg = journal.begin(1, 1).unwrap();
+        let begin = DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids: vec![],
+        };
+        journal
+            .record(dkg, &message(MessageTypes::DkgBegin(begin.clone())))
+            .unwrap();
+        journal
+            .record(dkg, &message(MessageTypes::DkgPrivateBegin(begin)))
+            .unwrap();
+        journal.close(dkg).unwrap();
+
+        let sign = journal.begin(1, 1).unwrap();
+        journal
+            .record(
+                sign,
+                &message(MessageTypes::NonceRequest(NonceRequest {
+                    dkg_id: 1,
+                    sign_id: 1,
+                    correlation_id: 0,
+                    sign_nonce_id: 1,
+                })),
+            )
+            .unwrap();
+        let dkg = journal.begin(2, 1).unwrap();
+        journal
+            .record(
+                dkg,
+                &message(MessageTypes::DkgBegin(DkgBegin {
+                    dkg_id: 2,
+                    excluded_key_ids: vec![3],
+                })),
+            )
+            .unwrap();
+
+        let open = journal.open_rounds().unwrap();
+        let positions: Vec<(u64, &str)> = open
+            .iter()
+            .map(|round| (round.dkg_id, round.position()))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![(1, "collecting nonces"), (2, "distributing public shares")]
+        );
+        assert!(matches!(
+            &open[1].messages[0].msg,
+            MessageTypes::DkgBegin(begin) if begin.excluded_key_ids == vec![3]
+        ));
+        assert_eq!(journal.last_dkg_id().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn prunes_closed_rounds_but_keeps_the_newest() {
+        let journal = Journal::in_memory().unwrap();
+        let begin = |dkg_id| {
+            message(MessageTypes::DkgBegin(DkgBegin {
+                dkg_id,
+                excluded_key_ids: vec![],
+            }))
+        };
+        for dkg_id in 1..=3 {
+            let round = journal.begin(dkg_id, 1).unwrap();
+            journal.record(round, &begin(dkg_id)).unwrap();
+            journal.close(round).unwrap();
+        }
+        let open = journal.begin(2, 1).unwrap();
+        journal.record(open, &begin(2)).unwrap();
+
+        let usage = journal.usage().unwrap();
+        assert_eq!(usage.entries, 4);
+        assert!(usage.oldest.is_some());
+
+        assert_eq!(journal.prune(None, Some(usage.bytes - 1)).unwrap(), 1);
+        // rounds closed just now are younger than anything pruned by age
+        assert_eq!(journal.prune(Some(0), None).unwrap(), 0);
+        assert_eq!(journal.prune(Some(u64::MAX >> 1), Some(0)).unwrap(), 1);
+
+        // the open round is needed for recovery, the newest to continue the DKG ids
+        assert_eq!(journal.usage().unwrap().entries, 2);
+        assert_eq!(journal.open_rounds().unwrap()[0].id, open);
+        assert_eq!(journal.last_dkg_id().unwrap(), Some(3));
+    }
+}
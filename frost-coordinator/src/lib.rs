@@ -1,26 +1,127 @@
 pub mod coordinator;
+pub mod events;
+pub mod history;
+pub mod journal;
+pub mod quorum;
+pub mod share_verifier;
+pub mod upgrade_window;
+pub mod watchdog;
+
+use std::thread;
 
 use coordinator::{Coordinator, Error};
 use frost_signer::{
+    auth::{Authenticator, COORDINATOR_SENDER_ID},
+    bus::Bus,
     config::Config,
-    net::{HttpNet, HttpNetListen},
+    net::{TransportNet, TransportNetListen},
+    secret::Secret,
+    signer::Signer,
 };
+use history::RoundHistory;
+use journal::Journal;
+use tracing::{info, warn};
 
 pub const DEVNET_COORDINATOR_ID: usize = 0;
 pub const DEVNET_COORDINATOR_DKG_ID: u64 = 0; //TODO: Remove, this is a correlation id
 
 pub fn create_coordinator(
     path: impl AsRef<std::path::Path>,
-) -> Result<Coordinator<HttpNetListen>, Error> {
+) -> Result<Coordinator<TransportNetListen>, Error> {
     let config = Config::from_path(path)?;
+    // relay or StackerDB, as configured
+    let net = TransportNet::from_config(&config, COORDINATOR_SENDER_ID)?;
+    coordinator_on(net, &config)
+}
+
+/// Like [`create_coordinator`], signing with `network_private_key` instead of the config's,
+/// e.g. a key fetched from a secret manager so that it need not be in the file
+pub fn create_coordinator_with_key(
+    path: impl AsRef<std::path::Path>,
+    network_private_key: Secret<String>,
+) -> Result<Coordinator<TransportNetListen>, Error> {
+    let mut config = Config::from_path(path)?;
+    config.network_private_key = network_private_key;
+    if config.lock_private_key {
+        config
+            .network_private_key
+            .mlock()
+            .map_err(frost_signer::config::Error::from)?;
+    }
+    let net = TransportNet::from_config(&config, COORDINATOR_SENDER_ID)?;
+    coordinator_on(net, &config)
+}
+
+/// A coordinator hosting every signer of the config in this process, all of them exchanging
+/// messages over an in-process [`Bus`] rather than the configured transport. Meant for devnets
+/// and tests, where the signers share the config and its network key. The signers serve no
+/// status or control endpoints, as they would all bind the same addresses.
+pub fn create_combined_coordinator(
+    path: impl AsRef<std::path::Path>,
+) -> Result<Coordinator<TransportNetListen>, Error> {
+    let config = Config::from_path(path)?;
+    let bus = Bus::new();
+    spawn_signers(&bus, &config);
+    let net = TransportNet::from_bus(&bus, &config, COORDINATOR_SENDER_ID)?;
+    coordinator_on(net, &config)
+}
+
+/// Run signers `1..=total_signers` of `config` on `bus`, each on a thread of its own
+pub fn spawn_signers(bus: &Bus, config: &Config) {
+    let signer_config = Config {
+        status_listen_addr: None,
+        log_control_listen_addr: None,
+        control: Default::default(),
+        ..config.clone()
+    };
+    for signer_id in 1..=config.total_signers as u32 {
+        let mut signer = Signer::new(signer_config.clone(), signer_id).with_bus(bus.clone());
+        thread::spawn(move || {
+            info!("signer #{} running in process", signer_id);
+            if let Err(e) = signer.start_p2p_sync() {
+                warn!("signer #{} stopped: {}", signer_id, e);
+            }
+        });
+    }
+}
 
-    let net: HttpNet = HttpNet::new(config.http_relay_url.clone());
-    let net_listen: HttpNetListen = HttpNetListen::new(net, vec![]);
+fn coordinator_on(
+    net: TransportNet,
+    config: &Config,
+) -> Result<Coordinator<TransportNetListen>, Error> {
+    let authenticator =
+        Authenticator::from_config(config).map_err(|e| Error::InvalidKey(e.to_string()))?;
+    let net_listen = TransportNetListen::new(net).with_authenticator(authenticator);
 
-    Ok(Coordinator::new(
+    let coordinator = Coordinator::new(
         DEVNET_COORDINATOR_ID,
         DEVNET_COORDINATOR_DKG_ID,
-        &config,
+        config,
         net_listen,
-    ))
+    );
+    match &config.coordinator_journal_path {
+        Some(path) => {
+            let mut coordinator = coordinator
+                .with_journal(Journal::new(path)?)
+                .with_history(RoundHistory::new(path)?);
+            coordinator.recover()?;
+            // closed rounds are pruned over a connection of their own
+            if let Some(limits) = config.retention.limits("journal") {
+                coordinator.retention().spawn(
+                    Journal::new(path)?,
+                    limits,
+                    config.retention.interval(),
+                );
+            }
+            if let Some(limits) = config.retention.limits("history") {
+                coordinator.retention().spawn(
+                    RoundHistory::new(path)?,
+                    limits,
+                    config.retention.interval(),
+                );
+            }
+            Ok(coordinator)
+        }
+        None => Ok(coordinator),
+    }
 }
@@ -1,7 +1,8 @@
 use clap::Parser;
 
 use frost_coordinator::coordinator::Command;
-use frost_coordinator::create_coordinator;
+use frost_coordinator::quorum::SigningQuorum;
+use frost_coordinator::{create_combined_coordinator, create_coordinator};
 use frost_signer::logging;
 use tracing::warn;
 
@@ -11,6 +12,15 @@ pub struct Cli {
     /// Config file path
     #[arg(short, long)]
     config: String,
+    /// Host every signer of the config in this process, over an in-process bus
+    #[arg(long)]
+    combined: bool,
+    /// Sign with these signers only
+    #[arg(long, value_delimiter = ',')]
+    pin: Vec<u32>,
+    /// Sign without these signers, e.g. one undergoing maintenance
+    #[arg(long, value_delimiter = ',')]
+    exclude: Vec<u32>,
     /// Subcommand action to take
     #[command(subcommand)]
     pub command: Command,
@@ -20,9 +30,20 @@ fn main() {
     logging::initiate_tracing_subscriber(tracing::Level::INFO).unwrap();
 
     let cli = Cli::parse();
-    match create_coordinator(cli.config) {
+    let coordinator = if cli.combined {
+        create_combined_coordinator(cli.config)
+    } else {
+        create_coordinator(cli.config)
+    };
+    match coordinator {
         Ok(mut coordinator) => {
-            let result = coordinator.run(&cli.command);
+            let quorum = SigningQuorum {
+                pinned: cli.pin.into_iter().collect(),
+                excluded: cli.exclude.into_iter().collect(),
+            };
+            let result = coordinator
+                .set_signing_quorum(quorum)
+                .and_then(|_| coordinator.run(&cli.command));
             if let Err(e) = result {
                 warn!("Failed to execute command: {}", e);
             }
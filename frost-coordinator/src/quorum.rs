@@ -0,0 +1,154 @@
+//! Signers an operator chose to sign with.
+//!
+//! A signing round asks the whole roster for nonces and signs with every key which answers. An
+//! operator can narrow that down for the rounds to come, e.g. to leave out a signer undergoing
+//! maintenance: a [`SigningQuorum`] pins the signers to sign with, excludes some, or both. The
+//! coordinator only takes a quorum on if the signers it leaves still meet the thresholds, and
+//! holds every signing round to it until it is cleared or the coordinator exits. DKG and roster
+//! changes still run over the whole roster.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use frost_signer::thresholds::Thresholds;
+use serde::{Deserialize, Serialize};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("Signer #{0} is not in the roster")]
+    NotInRoster(u32),
+    #[error("Signer #{0} is both pinned and excluded")]
+    PinnedAndExcluded(u32),
+    #[error("Only {0} signers holding {1} keys are left to sign with")]
+    TooSmall(usize, usize),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningQuorum {
+    /// Signers to sign with, the whole roster when empty
+    pub pinned: BTreeSet<u32>,
+    /// Signers not to sign with
+    pub excluded: BTreeSet<u32>,
+}
+
+impl SigningQuorum {
+    /// Sign with `signer_ids` only
+    pub fn pin(signer_ids: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            pinned: signer_ids.into_iter().collect(),
+            excluded: BTreeSet::new(),
+        }
+    }
+
+    /// Sign with every signer of the roster but `signer_ids`
+    pub fn exclude(signer_ids: impl IntoIterator<Item = u32>) -> Self {
+        Self {
+            pinned: BTreeSet::new(),
+            excluded: signer_ids.into_iter().collect(),
+        }
+    }
+
+    /// Whether the quorum is the whole roster
+    pub fn is_empty(&self) -> bool {
+        self.pinned.is_empty() && self.excluded.is_empty()
+    }
+
+    /// Whether signer `signer_id` of the roster signs
+    pub fn includes(&self, signer_id: u32) -> bool {
+        (self.pinned.is_empty() || self.pinned.contains(&signer_id))
+            && !self.excluded.contains(&signer_id)
+    }
+
+    /// The key ids to sign with, given the key ids each signer of the roster holds. Fails if
+    /// the quorum names a signer outside the roster, or leaves too few signers or keys to meet
+    /// `thresholds`.
+    pub fn key_ids(
+        &self,
+        roster: &BTreeMap<u32, Vec<u32>>,
+        thresholds: Thresholds,
+    ) -> Result<BTreeSet<u32>, Error> {
+        if let Some(signer_id) = self.pinned.intersection(&self.excluded).next() {
+            return Err(Error::PinnedAndExcluded(*signer_id));
+        }
+        if let Some(signer_id) = self
+            .pinned
+            .iter()
+            .chain(&self.excluded)
+            .find(|signer_id| !roster.contains_key(signer_id))
+        {
+            return Err(Error::NotInRoster(*signer_id));
+        }
+        let signers: Vec<&Vec<u32>> = roster
+            .iter()
+            .filter(|(signer_id, _)| self.includes(**signer_id))
+            .map(|(_, key_ids)| key_ids)
+            .collect();
+        let key_ids: BTreeSet<u32> = signers
+            .iter()
+            .flat_map(|key_ids| key_ids.iter())
+            .cloned()
+            .collect();
+        if !thresholds.met(signers.len(), key_ids.len()) {
+            return Err(Error::TooSmall(signers.len(), key_ids.len()));
+        }
+        Ok(key_ids)
+    }
+}
+
+impl fmt::Display for SigningQuorum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.pinned.is_empty(), self.excluded.is_empty()) {
+            (true, true) => write!(f, "every signer"),
+            (false, true) => write!(f, "signers {:?}", self.pinned),
+            (true, false) => write!(f, "every signer but {:?}", self.excluded),
+            (false, false) => write!(f, "signers {:?} but {:?}", self.pinned, self.excluded),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roster() -> BTreeMap<u32, Vec<u32>> {
+        (1..=4)
+            .map(|signer_id| (signer_id, vec![2 * signer_id - 2, 2 * signer_id - 1]))
+            .collect()
+    }
+
+    #[test]
+    fn quorums_are_held_to_the_roster_and_thresholds() {
+        let thresholds = Thresholds {
+            signers: 2,
+            keys: 5,
+        };
+        assert_eq!(
+            SigningQuorum::default().key_ids(&roster(), thresholds),
+            Ok((0..8).collect())
+        );
+        assert_eq!(
+            SigningQuorum::exclude([2]).key_ids(&roster(), thresholds),
+            Ok([0, 1, 4, 5, 6, 7].into_iter().collect())
+        );
+        // two signers are enough, but not the four keys they hold
+        assert_eq!(
+            SigningQuorum::pin([1, 3]).key_ids(&roster(), thresholds),
+            Err(Error::TooSmall(2, 4))
+        );
+        let quorum = SigningQuorum {
+            pinned: [1, 3, 4].into_iter().collect(),
+            excluded: [3].into_iter().collect(),
+        };
+        assert_eq!(
+            quorum.key_ids(&roster(), thresholds),
+            Err(Error::PinnedAndExcluded(3))
+        );
+        assert_eq!(
+            SigningQuorum::exclude([5]).key_ids(&roster(), thresholds),
+            Err(Error::NotInRoster(5))
+        );
+        assert_eq!(
+            SigningQuorum::pin([1, 2, 4]).to_string(),
+            "signers {1, 2, 4}"
+        );
+    }
+}
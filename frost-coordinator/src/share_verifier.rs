@@ -0,0 +1,213 @@
+//! Checks of individual signature shares.
+//!
+//! A share from key `i` is valid when `z_i * G == R_i + lambda_i * c * X_i`, where `R_i` is the
+//! key's binding nonce, `lambda_i` its Lagrange coefficient over the signing set, `c` the
+//! challenge and `X_i` its public key share, evaluated from the DKG commitments. Checking shares
+//! one by one lets the coordinator verify them on a thread pool as they arrive, blame the key
+//! which sent a bad one, and sum verified shares without checking them again.
+//!
+//! Shares of a taproot key path spend sign with the tweaked group key instead, see
+//! [`frost_signer::taproot`]: the challenge commits to the output key, `X_i` is weighted by the
+//! key factor of the tweak, and the tweak term is added to the sum.
+use std::collections::BTreeMap;
+
+use frost_signer::taproot::KeyPath;
+use wtfrost::{
+    common::{PolyCommitment, PublicNonce, Signature},
+    compute, v1, Point, Scalar,
+};
+
+pub struct ShareVerifier {
+    /// Sum of the DKG polynomial commitments, the first term is the group key
+    group_poly: Vec<Point>,
+    /// The key signatures verify with, the group key or its taproot output key
+    key: Point,
+    challenge: Scalar,
+    /// `-1` when the shares sign with their key shares negated, `1` otherwise
+    key_factor: Scalar,
+    /// What the shares sum to less than `z`, the taproot tweak times the challenge
+    tweak_term: Scalar,
+    aggregate_nonce: Point,
+    /// Binding nonce of each key in the signing set
+    nonces: BTreeMap<u32, Point>,
+    party_ids: Vec<usize>,
+}
+
+impl ShareVerifier {
+    /// Verifier for shares signing `msg` with the keys, and nonces, of `nonces`
+    pub fn new(
+        polys: &[PolyCommitment],
+        threshold: usize,
+        msg: &[u8],
+        nonces: &[(u32, PublicNonce)],
+    ) -> Self {
+        Self::build(polys, threshold, msg, nonces, None)
+    }
+
+    /// Verifier for shares signing `msg` as a key path spend of the output of `key_path`
+    pub fn for_key_path(
+        polys: &[PolyCommitment],
+        threshold: usize,
+        msg: &[u8],
+        nonces: &[(u32, PublicNonce)],
+        key_path: &KeyPath,
+    ) -> Self {
+        Self::build(polys, threshold, msg, nonces, Some(key_path))
+    }
+
+    fn build(
+        polys: &[PolyCommitment],
+        threshold: usize,
+        msg: &[u8],
+        nonces: &[(u32, PublicNonce)],
+        key_path: Option<&KeyPath>,
+    ) -> Self {
+        let mut group_poly = vec![Point::default(); threshold];
+        for poly in polys {
+            for (term, a) in group_poly.iter_mut().zip(&poly.A) {
+                *term += *a;
+            }
+        }
+        let party_ids: Vec<usize> = nonces.iter().map(|(id, _)| *id as usize).collect();
+        let public_nonces: Vec<PublicNonce> = nonces.iter().map(|(_, n)| n.clone()).collect();
+        let (binding_nonces, aggregate_nonce) =
+            compute::intermediate(msg, &party_ids, &public_nonces);
+        let tweak = key_path.map(|key_path| key_path.tweak(&group_poly[0]));
+        let key = tweak.map_or(group_poly[0], |tweak| tweak.output_key);
+        let challenge = compute::challenge(&key, &aggregate_nonce, msg);
+        Self {
+            group_poly,
+            key,
+            challenge,
+            key_factor: tweak.map_or(Scalar::from(1), |tweak| tweak.key_factor()),
+            tweak_term: tweak.map_or(Scalar::default(), |tweak| tweak.tweak_term(&challenge)),
+            aggregate_nonce,
+            nonces: nonces
+                .iter()
+                .map(|(id, _)| *id)
+                .zip(binding_nonces)
+                .collect(),
+            party_ids,
+        }
+    }
+
+    /// Whether `share` is a valid share from key `party_id`
+    pub fn verify(&self, party_id: u32, share: &v1::SignatureShare) -> bool {
+        let Some(nonce) = self.nonces.get(&party_id) else {
+            return false;
+        };
+        if share.id != party_id as usize {
+            return false;
+        }
+        let id = compute::id(share.id);
+        let public_key = self
+            .group_poly
+            .iter()
+            .rev()
+            .fold(Point::default(), |acc, a| id * acc + *a);
+        let lambda = compute::lambda(share.id, &self.party_ids);
+        Point::from(share.z_i) == *nonce + lambda * self.key_factor * self.challenge * public_key
+    }
+
+    /// The key the aggregate signature verifies with
+    pub fn key(&self) -> Point {
+        self.key
+    }
+
+    /// The signature made of verified `shares`, one from each key of the signing set
+    pub fn aggregate<'a>(&self, shares: impl Iterator<Item = &'a v1::SignatureShare>) -> Signature {
+        Signature {
+            R: self.aggregate_nonce,
+            z: shares.fold(self.tweak_term, |z, share| z + share.z_i),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frost_signer::lagrange;
+    use rand_core::OsRng;
+    use wtfrost::bip340::test_helpers::{dkg, sign};
+    use wtfrost::bip340::SchnorrProof;
+
+    #[test]
+    fn blames_the_key_with_a_bad_share() {
+        let mut rng = OsRng;
+        let mut signers = [
+            v1::Signer::new(&[0, 1], 4, 3, &mut rng),
+            v1::Signer::new(&[2, 3], 4, 3, &mut rng),
+        ];
+        let polys = dkg(&mut signers, &mut rng).unwrap();
+        let msg = b"It was many and many a year ago";
+        let (nonces, mut shares) = sign(msg, &mut signers, &mut rng);
+        let nonces: Vec<(u32, PublicNonce)> = shares
+            .iter()
+            .map(|share| share.id as u32)
+            .zip(nonces)
+            .collect();
+
+        let verifier = ShareVerifier::new(&polys, 3, msg, &nonces);
+        for share in &shares {
+            assert!(verifier.verify(share.id as u32, share));
+        }
+        let sig = verifier.aggregate(shares.iter());
+        assert!(sig.verify(&polys.iter().fold(Point::default(), |g, p| g + p.A[0]), msg));
+
+        shares[2].z_i += Scalar::from(1);
+        assert!(!verifier.verify(2, &shares[2]));
+        // a share is only valid for the key which made it
+        assert!(!verifier.verify(3, &shares[1]));
+    }
+
+    #[test]
+    fn key_path_shares_sign_for_the_output_key() {
+        let mut rng = OsRng;
+        let mut signers = [
+            v1::Signer::new(&[0, 1], 4, 3, &mut rng),
+            v1::Signer::new(&[2, 3], 4, 3, &mut rng),
+        ];
+        let polys = dkg(&mut signers, &mut rng).unwrap();
+        let group_key = polys.iter().fold(Point::default(), |g, p| g + p.A[0]);
+        let msg = [3; 32];
+        let key_path = KeyPath {
+            merkle_root: Some([5; 32]),
+        };
+        let tweak = key_path.tweak(&group_key);
+
+        // BIP-340 signatures need an R with an even y
+        let ids: Vec<usize> = vec![0, 1, 2, 3];
+        let nonces: Vec<PublicNonce> = loop {
+            let nonces: Vec<PublicNonce> = signers
+                .iter_mut()
+                .flat_map(|signer| signer.parties.iter_mut())
+                .map(|party| party.gen_nonce(&mut rng))
+                .collect();
+            if compute::intermediate(&msg, &ids, &nonces).1.has_even_y() {
+                break nonces;
+            }
+        };
+        let shares: Vec<v1::SignatureShare> = signers
+            .iter()
+            .flat_map(|signer| signer.parties.iter())
+            .map(|party| {
+                let nonce = party.save().nonce.unwrap();
+                let lambda = compute::lambda(party.id, &ids);
+                lagrange::sign_key_path(party, &nonce, &lambda, &msg, &ids, &nonces, &tweak)
+            })
+            .collect();
+        let nonces: Vec<(u32, PublicNonce)> = ids.iter().map(|id| *id as u32).zip(nonces).collect();
+
+        let verifier = ShareVerifier::for_key_path(&polys, 3, &msg, &nonces, &key_path);
+        assert_eq!(verifier.key(), tweak.output_key);
+        for share in &shares {
+            assert!(verifier.verify(share.id as u32, share));
+        }
+        let sig = verifier.aggregate(shares.iter());
+        let proof = SchnorrProof::new(&sig).unwrap();
+        assert!(proof.verify(&tweak.output_key.x(), &msg));
+        // nor does it verify with the untweaked group key
+        assert!(!proof.verify(&group_key.x(), &msg));
+        assert!(!ShareVerifier::new(&polys, 3, &msg, &nonces).verify(0, &shares[0]));
+    }
+}
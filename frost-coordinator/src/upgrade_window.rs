@@ -0,0 +1,125 @@
+//! Maintenance windows for upgrading the signer fleet.
+//!
+//! The coordinator announces a window ahead of time with an
+//! [`UpgradeWindow`](frost_signer::signing_round::UpgradeWindow) message, which each signer
+//! acknowledges. Rounds already running finish, but once the window opens no new DKG or
+//! signing round starts, so signers can restart into new software without a round breaking
+//! half way. A restarted signer registers again, and the window closes as soon as a quorum of
+//! signers has registered since it opened, or at the latest once its duration is up.
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowState {
+    /// Announced, rounds still start until it opens
+    Upcoming,
+    /// No rounds start
+    Open,
+    /// Closed early, a quorum of signers registered again
+    Resumed,
+    /// Closed once its duration was up, without a quorum registering
+    Expired,
+}
+
+impl WindowState {
+    /// Whether rounds may start
+    pub fn allows_rounds(&self) -> bool {
+        !matches!(self, WindowState::Open)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Window {
+    pub window_id: u64,
+    pub opens_at: Instant,
+    pub closes_at: Instant,
+    /// Signers which must register again for rounds to resume before `closes_at`
+    pub quorum: usize,
+    /// Signers which acknowledged the announcement
+    pub acknowledged: BTreeSet<u32>,
+    /// Software version of each signer which registered since the window opened
+    pub registered: BTreeMap<u32, String>,
+}
+
+impl Window {
+    pub fn new(
+        window_id: u64,
+        now: Instant,
+        starts_in: Duration,
+        duration: Duration,
+        quorum: usize,
+    ) -> Self {
+        Self {
+            window_id,
+            opens_at: now + starts_in,
+            closes_at: now + starts_in + duration,
+            quorum,
+            acknowledged: BTreeSet::new(),
+            registered: BTreeMap::new(),
+        }
+    }
+
+    pub fn acknowledge(&mut self, signer_id: u32, window_id: u64) {
+        if window_id == self.window_id {
+            self.acknowledged.insert(signer_id);
+        }
+    }
+
+    /// Record that `signer_id` started, running `version`. Only registrations once the window
+    /// is open count, those before it are not restarts for the upgrade.
+    pub fn register(&mut self, signer_id: u32, version: String, now: Instant) {
+        if now >= self.opens_at {
+            self.registered.insert(signer_id, version);
+        }
+    }
+
+    pub fn state(&self, now: Instant) -> WindowState {
+        if now < self.opens_at {
+            WindowState::Upcoming
+        } else if self.registered.len() >= self.quorum {
+            WindowState::Resumed
+        } else if now >= self.closes_at {
+            WindowState::Expired
+        } else {
+            WindowState::Open
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_resume_once_a_quorum_registers_again() {
+        let now = Instant::now();
+        let secs = Duration::from_secs;
+        let mut window = Window::new(1, now, secs(10), secs(60), 2);
+        window.acknowledge(1, 1);
+        window.acknowledge(2, 0);
+        assert_eq!(window.acknowledged, BTreeSet::from([1]));
+
+        // a registration before the window opens is no restart for the upgrade
+        window.register(1, "0.1.0".to_string(), now);
+        assert_eq!(WindowState::Upcoming, window.state(now + secs(9)));
+        assert!(window.state(now + secs(9)).allows_rounds());
+        assert_eq!(WindowState::Open, window.state(now + secs(10)));
+        assert!(!window.state(now + secs(10)).allows_rounds());
+
+        window.register(1, "0.2.0".to_string(), now + secs(20));
+        assert_eq!(WindowState::Open, window.state(now + secs(20)));
+        window.register(3, "0.2.0".to_string(), now + secs(30));
+        assert_eq!(WindowState::Resumed, window.state(now + secs(30)));
+    }
+
+    #[test]
+    fn rounds_resume_when_the_window_is_up() {
+        let now = Instant::now();
+        let secs = Duration::from_secs;
+        let mut window = Window::new(1, now, secs(0), secs(60), 2);
+        window.register(1, "0.2.0".to_string(), now + secs(1));
+        assert_eq!(WindowState::Open, window.state(now + secs(59)));
+        assert_eq!(WindowState::Expired, window.state(now + secs(60)));
+        assert!(window.state(now + secs(60)).allows_rounds());
+    }
+}
@@ -0,0 +1,136 @@
+//! Detects frost rounds which run past their deadline.
+//!
+//! The coordinator arms the watchdog when a DKG or signing round starts. If the round is
+//! still running once the timeout elapses, a background thread marks it tripped and counts
+//! the timeout; the coordinator notices at its next poll, aborts the round on the signers
+//! and returns [`Error::RoundTimeout`](crate::coordinator::Error::RoundTimeout).
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tracing::warn;
+
+#[derive(Debug, Default)]
+struct Round {
+    finished: Mutex<bool>,
+    finished_changed: Condvar,
+    tripped: AtomicBool,
+}
+
+#[derive(Debug, Default)]
+pub struct Watchdog {
+    /// `None` disables the watchdog
+    timeout: Option<Duration>,
+    round: Arc<Round>,
+    trips: Arc<AtomicU64>,
+}
+
+impl Watchdog {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout,
+            ..Default::default()
+        }
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Start watching a new round, which ends when the returned guard is dropped
+    pub fn arm(&mut self) -> Armed {
+        self.round = Arc::new(Round::default());
+        if let Some(timeout) = self.timeout {
+            let round = self.round.clone();
+            let trips = self.trips.clone();
+            thread::spawn(move || {
+                let finished = round.finished.lock().unwrap_or_else(|e| e.into_inner());
+                let (finished, _) = round
+                    .finished_changed
+                    .wait_timeout_while(finished, timeout, |finished| !*finished)
+                    .unwrap_or_else(|e| e.into_inner());
+                if !*finished {
+                    round.tripped.store(true, Ordering::SeqCst);
+                    trips.fetch_add(1, Ordering::SeqCst);
+                    warn!("Watchdog: round still running after {:?}", timeout);
+                }
+            });
+        }
+        Armed(self.round.clone())
+    }
+
+    /// End the round watched by `armed` and forget it, so that it no longer counts as tripped.
+    /// For watchdogs guarding only part of a round.
+    pub fn disarm(&mut self, armed: Armed) {
+        drop(armed);
+        self.round = Arc::default();
+    }
+
+    /// Whether the current round has overrun its timeout
+    pub fn tripped(&self) -> bool {
+        self.round.tripped.load(Ordering::SeqCst)
+    }
+
+    /// Number of rounds which have overrun their timeout
+    pub fn trips(&self) -> u64 {
+        self.trips.load(Ordering::SeqCst)
+    }
+}
+
+/// Marks the round finished on drop, however the round exits
+pub struct Armed(Arc<Round>);
+
+impl Drop for Armed {
+    fn drop(&mut self) {
+        let mut finished = self.0.finished.lock().unwrap_or_else(|e| e.into_inner());
+        *finished = true;
+        self.0.finished_changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trips_when_the_round_overruns() {
+        let mut watchdog = Watchdog::new(Some(Duration::from_millis(10)));
+        let _armed = watchdog.arm();
+        thread::sleep(Duration::from_millis(200));
+        assert!(watchdog.tripped());
+        assert_eq!(watchdog.trips(), 1);
+
+        // a new round starts untripped
+        let _armed = watchdog.arm();
+        assert!(!watchdog.tripped());
+    }
+
+    #[test]
+    fn finished_rounds_do_not_trip() {
+        let mut watchdog = Watchdog::new(Some(Duration::from_millis(50)));
+        drop(watchdog.arm());
+        thread::sleep(Duration::from_millis(200));
+        assert!(!watchdog.tripped());
+        assert_eq!(watchdog.trips(), 0);
+    }
+
+    #[test]
+    fn disarmed_rounds_do_not_count_as_tripped() {
+        let mut watchdog = Watchdog::new(Some(Duration::from_millis(10)));
+        let armed = watchdog.arm();
+        thread::sleep(Duration::from_millis(200));
+        assert!(watchdog.tripped());
+        watchdog.disarm(armed);
+        assert!(!watchdog.tripped());
+        assert_eq!(watchdog.trips(), 1);
+    }
+
+    #[test]
+    fn disabled_watchdog_never_trips() {
+        let mut watchdog = Watchdog::default();
+        let _armed = watchdog.arm();
+        thread::sleep(Duration::from_millis(20));
+        assert!(!watchdog.tripped());
+    }
+}
@@ -0,0 +1,157 @@
+use frost_coordinator::coordinator::{Coordinator, Error, Reconfiguration};
+use frost_coordinator::spawn_signers;
+use frost_signer::auth::COORDINATOR_SENDER_ID;
+use frost_signer::bus::Bus;
+use frost_signer::config::Config;
+use frost_signer::digest::MessageDigest;
+use frost_signer::net::{TransportNet, TransportNetListen};
+use frost_signer::taproot::KeyPath;
+use frost_signer::thresholds::Thresholds;
+use rand_core::OsRng;
+use wtfrost::{Point, Scalar};
+
+/// A devnet config whose nodes all share one network key
+fn config() -> Config {
+    let private_key = Scalar::random(&mut OsRng);
+    let public_key = Point::from(&private_key).to_string();
+    Config {
+        relay_namespace: "combined-test".to_string(),
+        total_signers: 3,
+        total_keys: 6,
+        keys_threshold: 4,
+        signers_threshold: Some(3),
+        network_private_key: private_key.to_string().into(),
+        signer_public_keys: vec![public_key.clone(); 3],
+        key_public_keys: vec![public_key.clone(); 6],
+        coordinator_public_key: public_key,
+        round_timeout_secs: Some(60),
+        dkg_commitment_timeout_secs: Some(30),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn coordinator_and_signers_run_dkg_and_sign_over_the_bus() {
+    let config = config();
+    let bus = Bus::new();
+    spawn_signers(&bus, &config);
+    let net = TransportNet::from_bus(&bus, &config, COORDINATOR_SENDER_ID).unwrap();
+    let mut coordinator = Coordinator::new(0, 0, &config, TransportNetListen::new(net));
+    let events = coordinator.round_events().subscribe();
+    assert_eq!(
+        coordinator.thresholds(),
+        Thresholds {
+            signers: 3,
+            keys: 4
+        }
+    );
+
+    let key = coordinator.run_distributed_key_generation().unwrap();
+    let (signature, proof) = coordinator
+        .sign_bytes("frost/test", b"combined devnet")
+        .unwrap();
+    let digest = MessageDigest::tagged("frost/test", b"combined devnet");
+    assert!(signature.verify(&key, digest.as_bytes()));
+    assert!(proof.verify(&key.x(), digest.as_bytes()));
+
+    // every round reported its start, each signer's progress and its end
+    let events: Vec<serde_json::Value> = events
+        .try_iter()
+        .map(|event| serde_json::from_str(&event).unwrap())
+        .collect();
+    let count = |kind: &str, event: &str, message: Option<&str>| {
+        events
+            .iter()
+            .filter(|e| {
+                e["kind"] == kind
+                    && e["event"] == event
+                    && message.is_none_or(|message| e["message"] == message)
+            })
+            .count()
+    };
+    // public shares are sent again each time the group key comes out with an odd y
+    let dkg_starts = count("dkg", "started", None);
+    assert!(dkg_starts >= 1);
+    assert_eq!(
+        count("dkg", "received", Some("dkg_public_share")),
+        6 * dkg_starts
+    );
+    assert_eq!(count("dkg", "received", Some("dkg_end")), 3);
+    assert_eq!(count("dkg", "completed", None), 1);
+    assert_eq!(count("sign", "started", None), 1);
+    assert_eq!(count("sign", "received", Some("sign_share_response")), 6);
+    assert_eq!(count("sign", "completed", None), 1);
+    assert_eq!(events.last().unwrap()["event"], "completed");
+
+    // a batch takes a single round, each digest signed under its own nonces
+    let digests: Vec<MessageDigest> = (0..3).map(|i| MessageDigest::new([i; 32])).collect();
+    let signatures = coordinator.sign_digests(&digests).unwrap();
+    assert_eq!(signatures.len(), 3);
+    for (digest, (signature, proof)) in digests.iter().zip(&signatures) {
+        assert!(signature.verify(&key, digest.as_bytes()));
+        assert!(proof.verify(&key.x(), digest.as_bytes()));
+    }
+
+    // a key path spend signs for the tweaked output key, not the group key
+    let key_path = KeyPath::default();
+    let digest = MessageDigest::new([9; 32]);
+    let (_, proof) = coordinator.sign_key_path(&digest, &key_path).unwrap();
+    let output_key = key_path.tweak(&key).output_key;
+    assert!(proof.verify(&output_key.x(), digest.as_bytes()));
+    assert!(!proof.verify(&key.x(), digest.as_bytes()));
+
+    // refreshed shares still sign for the same key
+    assert_eq!(coordinator.run_reshare().unwrap(), key);
+    let (signature, proof) = coordinator
+        .sign_bytes("frost/test", b"after refresh")
+        .unwrap();
+    let digest = MessageDigest::tagged("frost/test", b"after refresh");
+    assert!(signature.verify(&key, digest.as_bytes()));
+    assert!(proof.verify(&key.x(), digest.as_bytes()));
+}
+
+#[test]
+fn signers_leave_and_rejoin_a_generation_without_re_keying() {
+    // any signers holding keys_threshold keys suffice, so one signer can leave
+    let config = Config {
+        signers_threshold: None,
+        ..config()
+    };
+    let bus = Bus::new();
+    spawn_signers(&bus, &config);
+    let net = TransportNet::from_bus(&bus, &config, COORDINATOR_SENDER_ID).unwrap();
+    let mut coordinator = Coordinator::new(0, 0, &config, TransportNetListen::new(net));
+    let key = coordinator.run_distributed_key_generation().unwrap();
+    let sign = |coordinator: &mut Coordinator<_>, message: &[u8]| {
+        let (signature, proof) = coordinator.sign_bytes("frost/test", message).unwrap();
+        let digest = MessageDigest::tagged("frost/test", message);
+        assert!(signature.verify(&key, digest.as_bytes()));
+        assert!(proof.verify(&key.x(), digest.as_bytes()));
+    };
+
+    assert_eq!(
+        coordinator.remove_signers(&[3]).unwrap(),
+        Reconfiguration::Kept(key)
+    );
+    assert_eq!(coordinator.excluded_signers(), vec![3]);
+    sign(&mut coordinator, b"without signer 3");
+
+    // the shares of signer 3's keys are rebuilt from the others'
+    assert_eq!(
+        coordinator.add_signers(&[3]).unwrap(),
+        Reconfiguration::Kept(key)
+    );
+    assert!(coordinator.excluded_signers().is_empty());
+    sign(&mut coordinator, b"with signer 3 again");
+
+    assert!(matches!(
+        coordinator.add_signers(&[3]),
+        Err(Error::AlreadyInRoster(3))
+    ));
+    // two signers cannot leave while keys_threshold is 4
+    assert!(matches!(
+        coordinator.remove_signers(&[1, 2]),
+        Err(Error::RosterTooSmall(1, 2))
+    ));
+    assert!(coordinator.excluded_signers().is_empty());
+}
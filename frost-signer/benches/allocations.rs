@@ -0,0 +1,258 @@
+//! Heap allocations made hashing, signing, verifying and encoding the messages of a full DKG
+//! and signing round, counted by a wrapping global allocator. Hashing should not allocate at
+//! all, and encoding into a reused buffer stops allocating once the buffer has grown to the
+//! largest message. What signing and verifying allocate beyond the returned signature is down
+//! to the ecdsa backend.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use frost_signer::auth::Identity;
+use frost_signer::net::{Envelope, Message};
+use frost_signer::signer::PeerKeys;
+use frost_signer::signing_round::{
+    commitment_digest, DkgBegin, DkgEnd, DkgPrivateShares, DkgPublicShare, DkgStatus, MessageTypes,
+    NonceRequest, NonceResponse, Signable, SignatureShareRequest, SignatureShareResponse,
+};
+use p256k1::ecdsa;
+use rand_core::OsRng;
+use wtfrost::{
+    common::{PolyCommitment, PublicNonce},
+    v1, Scalar,
+};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const SIGNERS: usize = 5;
+const KEYS_PER_SIGNER: usize = 2;
+const ROUNDS: usize = 100;
+
+/// Allocations made by `f`
+fn count(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+/// The messages of one DKG round followed by one signing round
+/// Signer owning `key_id`, each signer holding `KEYS_PER_SIGNER` consecutive keys
+fn owner(key_id: usize) -> u32 {
+    (key_id / KEYS_PER_SIGNER) as u32 + 1
+}
+
+fn full_round() -> Vec<MessageTypes> {
+    let mut rng = OsRng;
+    let total = SIGNERS * KEYS_PER_SIGNER;
+    let threshold = total * 7 / 10;
+    let mut parties: Vec<v1::Party> = (0..total)
+        .map(|i| v1::Party::new(i, total, threshold, &mut rng))
+        .collect();
+
+    let mut round = vec![MessageTypes::DkgBegin(DkgBegin {
+        dkg_id: 1,
+        excluded_key_ids: vec![],
+    })];
+    let commitments: BTreeMap<u32, PolyCommitment> = parties
+        .iter()
+        .map(|party| (party.id as u32, party.get_poly_commitment(&mut rng)))
+        .collect();
+    for (key_id, commitment) in &commitments {
+        round.push(MessageTypes::DkgPublicShare(DkgPublicShare {
+            dkg_id: 1,
+            dkg_public_id: 1,
+            signer_id: owner(*key_id as usize),
+            key_id: *key_id,
+            public_share: commitment.clone(),
+        }));
+    }
+    let digest = commitment_digest(1, commitments.iter());
+    round.push(MessageTypes::DkgPrivateBegin(DkgBegin {
+        dkg_id: 1,
+        excluded_key_ids: vec![],
+    }));
+    for (signer, keys) in parties.chunks(KEYS_PER_SIGNER).enumerate() {
+        round.push(MessageTypes::DkgPrivateShares(DkgPrivateShares {
+            dkg_id: 1,
+            signer_id: signer as u32 + 1,
+            private_shares: keys
+                .iter()
+                .map(|party| (party.id as u32, party.get_shares()))
+                .collect(),
+            encrypted_shares: vec![],
+            commitment_digest: digest,
+        }));
+    }
+    for signer_id in 1..=SIGNERS {
+        round.push(MessageTypes::DkgEnd(DkgEnd {
+            dkg_id: 1,
+            signer_id: signer_id as u32,
+            status: DkgStatus::Success,
+        }));
+    }
+
+    let msg = b"It was many and many a year ago".to_vec();
+    round.push(MessageTypes::NonceRequest(NonceRequest {
+        dkg_id: 1,
+        sign_id: 1,
+        correlation_id: 1,
+        sign_nonce_id: 1,
+    }));
+    let nonces: Vec<(u32, PublicNonce)> = parties
+        .iter_mut()
+        .map(|party| (party.id as u32, party.gen_nonce(&mut rng)))
+        .collect();
+    for (key_id, nonce) in &nonces {
+        round.push(MessageTypes::NonceResponse(NonceResponse {
+            dkg_id: 1,
+            sign_id: 1,
+            correlation_id: 1,
+            sign_nonce_id: 1,
+            signer_id: owner(*key_id as usize),
+            key_id: *key_id,
+            nonce: nonce.clone(),
+        }));
+    }
+    let signers: Vec<usize> = (0..total).collect();
+    let public_nonces: Vec<PublicNonce> = nonces.iter().map(|(_, n)| n.clone()).collect();
+    for party in &parties {
+        round.push(MessageTypes::SignShareRequest(SignatureShareRequest {
+            dkg_id: 1,
+            sign_id: 1,
+            correlation_id: 1,
+            signer_id: owner(party.id),
+            key_id: party.id as u32,
+            nonces: nonces.clone(),
+            message: msg.clone(),
+            key_path: None,
+        }));
+        round.push(MessageTypes::SignShareResponse(SignatureShareResponse {
+            dkg_id: 1,
+            sign_id: 1,
+            correlation_id: 1,
+            signer_id: owner(party.id),
+            key_id: party.id as u32,
+            signature_share: party.sign(&msg, &signers, &public_nonces),
+        }));
+    }
+    round
+}
+
+fn sign(msg: &MessageTypes, key: &Scalar) -> Vec<u8> {
+    match msg {
+        MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => msg.sign(key),
+        MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => msg.sign(key),
+        MessageTypes::DkgPublicShare(msg) => msg.sign(key),
+        MessageTypes::DkgPrivateShares(msg) => msg.sign(key),
+        MessageTypes::NonceRequest(msg) => msg.sign(key),
+        MessageTypes::NonceResponse(msg) => msg.sign(key),
+        MessageTypes::SignShareRequest(msg) => msg.sign(key),
+        MessageTypes::SignShareResponse(msg) => msg.sign(key),
+        _ => unreachable!("not part of a round"),
+    }
+    .expect("")
+}
+
+fn main() {
+    let mut rng = OsRng;
+    let private_key = Scalar::random(&mut rng);
+    let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+    // every node shares one key, so any sender verifies
+    let peer_keys = PeerKeys {
+        signers: vec![public_key; SIGNERS],
+        keys: vec![public_key; SIGNERS * KEYS_PER_SIGNER],
+        coordinator: public_key,
+    };
+
+    let identity = Identity::new(1, private_key);
+    let round: Vec<Envelope> = full_round()
+        .into_iter()
+        .map(|msg| {
+            let message = Message {
+                sig: sign(&msg, &private_key),
+                msg,
+            };
+            identity.seal("bench".to_string(), message).unwrap()
+        })
+        .collect();
+    let messages = round.len() * ROUNDS;
+    let report = |what: &str, allocations: usize| {
+        println!(
+            "{what:<24} {allocations:>8} allocations, {:.2} per message",
+            allocations as f64 / messages as f64
+        )
+    };
+
+    report(
+        "request_hash",
+        count(|| {
+            for _ in 0..ROUNDS {
+                for envelope in &round {
+                    std::hint::black_box(envelope.message.msg.request_hash());
+                }
+            }
+        }),
+    );
+    report(
+        "sign",
+        count(|| {
+            for _ in 0..ROUNDS {
+                for envelope in &round {
+                    std::hint::black_box(sign(&envelope.message.msg, &private_key));
+                }
+            }
+        }),
+    );
+    report(
+        "verify",
+        count(|| {
+            for _ in 0..ROUNDS {
+                for envelope in &round {
+                    assert!(peer_keys.verify(&envelope.message));
+                }
+            }
+        }),
+    );
+    report(
+        "encode (fresh buffers)",
+        count(|| {
+            for _ in 0..ROUNDS {
+                for envelope in &round {
+                    std::hint::black_box(envelope.to_bytes().unwrap());
+                }
+            }
+        }),
+    );
+    let mut buf = Vec::new();
+    report(
+        "encode (reused buffer)",
+        count(|| {
+            for _ in 0..ROUNDS {
+                for envelope in &round {
+                    envelope.write_to(&mut buf).unwrap();
+                    std::hint::black_box(&buf);
+                }
+            }
+        }),
+    );
+}
@@ -0,0 +1,70 @@
+//! The private share phase of DKG for one signer among 16, 64 and 128, each holding
+//! `KEYS_PER_SIGNER` keys: building its shares for every key, and checking the shares one peer
+//! sent against the peer's commitments, one pair at a time and across cores.
+use std::collections::{BTreeMap, BTreeSet};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use frost_signer::signing_round::{mismatched_shares, party_shares, share_matches_commitment};
+use hashbrown::HashMap;
+use rand_core::OsRng;
+use wtfrost::{v1, Scalar};
+
+const KEYS_PER_SIGNER: usize = 4;
+
+fn dkg_private_shares(c: &mut Criterion) {
+    let mut rng = OsRng;
+    let mut group = c.benchmark_group("dkg_private_shares");
+    group.sample_size(10);
+    for signers in [16, 64, 128] {
+        let total = signers * KEYS_PER_SIGNER;
+        let threshold = total * 7 / 10;
+        let parties = |first: usize| -> Vec<v1::Party> {
+            (first..first + KEYS_PER_SIGNER)
+                .map(|id| v1::Party::new(id, total, threshold, &mut OsRng))
+                .collect()
+        };
+        let ours = parties(0);
+        let peer = parties(KEYS_PER_SIGNER);
+        let own_key_ids: Vec<usize> = ours.iter().map(|party| party.id).collect();
+        let commitments: BTreeMap<u32, _> = peer
+            .iter()
+            .map(|party| (party.id as u32, party.get_poly_commitment(&mut rng)))
+            .collect();
+        let received: Vec<(u32, HashMap<usize, Scalar>)> = party_shares(&peer, &BTreeSet::new())
+            .into_iter()
+            .map(|(src_key_id, shares)| {
+                let own = shares
+                    .into_iter()
+                    .filter(|(key_id, _)| own_key_ids.contains(key_id))
+                    .collect();
+                (src_key_id, own)
+            })
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("build", signers), &signers, |b, _| {
+            b.iter(|| party_shares(&ours, &BTreeSet::new()))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("check_serial", signers),
+            &signers,
+            |b, _| {
+                b.iter(|| {
+                    received.iter().all(|(src_key_id, shares)| {
+                        shares.iter().all(|(key_id, share)| {
+                            share_matches_commitment(&commitments[src_key_id], *key_id, share)
+                        })
+                    })
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("check_parallel", signers),
+            &signers,
+            |b, _| b.iter(|| mismatched_shares(&commitments, &received)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dkg_private_shares);
+criterion_main!(benches);
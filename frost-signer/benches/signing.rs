@@ -0,0 +1,46 @@
+//! Signature share generation for a batch of messages signed by the same party set,
+//! with and without the Lagrange coefficient cache.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use frost_signer::lagrange::{self, LagrangeCache};
+use rand_core::OsRng;
+use wtfrost::{common::PublicNonce, v1};
+
+const BATCH: usize = 16;
+
+fn signing_batch(c: &mut Criterion) {
+    let mut rng = OsRng;
+    let mut group = c.benchmark_group("signing_batch");
+    for total in [10, 40, 100] {
+        let threshold = total * 7 / 10;
+        let mut parties: Vec<v1::Party> = (0..total)
+            .map(|i| v1::Party::new(i, total, threshold, &mut rng))
+            .collect();
+        let signers: Vec<usize> = (0..threshold).collect();
+        let nonces: Vec<PublicNonce> = signers
+            .iter()
+            .map(|&i| parties[i].gen_nonce(&mut rng))
+            .collect();
+        let party = &parties[0];
+
+        group.bench_with_input(BenchmarkId::new("uncached", total), &total, |b, _| {
+            b.iter(|| {
+                for msg in 0..BATCH {
+                    party.sign(&msg.to_be_bytes(), &signers, &nonces);
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("cached", total), &total, |b, _| {
+            let mut cache = LagrangeCache::default();
+            b.iter(|| {
+                for msg in 0..BATCH {
+                    let lambda = cache.coefficient(party.id, &signers);
+                    lagrange::sign(party, &lambda, &msg.to_be_bytes(), &signers, &nonces);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, signing_batch);
+criterion_main!(benches);
@@ -0,0 +1,444 @@
+//! Authentication of relay envelopes.
+//!
+//! Every [`Envelope`] names the protocol version, the node which sent it and when, and carries
+//! the sender's network key signature over those fields, the namespace and the canonical digest
+//! of the message, see [`MessageTypes::request_hash`]. Receivers drop envelopes of another
+//! protocol version, from senders missing from their config, with a bad signature, or stamped
+//! further from their own clock than the configured skew tolerance. With `max_message_age_secs`
+//! set, envelopes stamped in the past are held to that window instead, so a signer can refuse
+//! traffic the relay queued long before it started while still tolerating clocks running ahead.
+//!
+//! The message inside is checked against the roster of the config too, before any round sees
+//! it: it must be signed by the key configured for its author, the signer id or key id it
+//! names, and sent by that author. A signer cannot pass off a message as another's, nor relay
+//! another's messages as its own.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use p256k1::ecdsa;
+use sha2::Digest;
+use tracing::info;
+use wtfrost::Scalar;
+
+use crate::config::Config;
+use crate::logging::target::AUDIT;
+use crate::net::{Envelope, Error as NetError, Message};
+use crate::protocol::{tag, Hasher, PROTOCOL_VERSION};
+use crate::secret::Secret;
+use crate::signer::{Error as SignerError, PeerKeys};
+use crate::signing_round::MessageTypes;
+
+/// Sender id of the coordinator, signers use their 1-based signer id
+pub const COORDINATOR_SENDER_ID: u32 = 0;
+
+/// Clock skew tolerated when the config does not set `max_clock_skew_secs`
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("protocol version {0} is not {PROTOCOL_VERSION}")]
+    Version(u16),
+    #[error("sender #{0} is not in the config")]
+    UnknownSender(u32),
+    #[error("sent {0}ms away from our clock, more than the tolerated skew")]
+    ClockSkew(u64),
+    #[error("sent {0}ms ago, more than the accepted message age")]
+    Stale(u64),
+    #[error("the envelope signature does not verify")]
+    BadSignature,
+    #[error("{0} names an author missing from the config")]
+    UnknownAuthor(&'static str),
+    #[error("{0} is not signed by its author's key")]
+    BadMessageSignature(&'static str),
+    #[error("sender #{0} is not the author of the {1} it sent")]
+    NotAuthor(u32, &'static str),
+}
+
+/// Digest the sender of an envelope signs
+pub fn digest(
+    version: u16,
+    namespace: &str,
+    sender_id: u32,
+    timestamp: u64,
+    message: &Message,
+) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update(tag::ENVELOPE.as_bytes());
+    hasher.update(version.to_be_bytes());
+    hasher.update((namespace.len() as u64).to_be_bytes());
+    hasher.update(namespace.as_bytes());
+    hasher.update(sender_id.to_be_bytes());
+    hasher.update(timestamp.to_be_bytes());
+    hasher.update(message.msg.request_hash());
+    hasher.update(&message.sig);
+    hasher.finalize().into()
+}
+
+/// Unix time in milliseconds
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// The sender id and network key this node signs its messages and envelopes with. The default
+/// identity holds no key and cannot sign.
+#[derive(Clone, Debug, Default)]
+pub struct Identity {
+    pub sender_id: u32,
+    private_key: Secret<Scalar>,
+}
+
+impl Identity {
+    pub fn new(sender_id: u32, private_key: Scalar) -> Self {
+        Self {
+            sender_id,
+            private_key: Secret::new(private_key),
+        }
+    }
+
+    pub fn from_config(config: &Config, sender_id: u32) -> Result<Self, SignerError> {
+        let private_key = Scalar::try_from(config.network_private_key.as_str())
+            .map_err(|_| SignerError::InvalidKey("network_private_key".to_string()))?;
+        Ok(Self::new(sender_id, private_key))
+    }
+
+    pub(crate) fn private_key(&self) -> &Scalar {
+        &self.private_key
+    }
+
+    /// Sign `msg` with the network key and record it in the audit log. This is the only way
+    /// outbound messages are signed, and the transports refuse messages it did not sign, see
+    /// [`Identity::check`].
+    pub fn sign_message(&self, msg: MessageTypes) -> Result<Message, NetError> {
+        let sig = msg
+            .sign(&self.private_key)
+            .map_err(|e| NetError::SigningError(e.to_string()))?;
+        info!(
+            target: AUDIT,
+            "sender #{} signed {} {}",
+            self.sender_id,
+            msg.name(),
+            hex::encode(msg.request_hash())
+        );
+        Ok(Message { msg, sig })
+    }
+
+    /// Fail unless `message` carries this node's signature, e.g. one built with an empty `sig`
+    pub fn check(&self, message: &Message) -> Result<(), NetError> {
+        let signed = ecdsa::PublicKey::new(&self.private_key)
+            .map(|key| message.msg.verify(&message.sig, &key))
+            .unwrap_or(false);
+        if signed {
+            Ok(())
+        } else {
+            Err(NetError::UnsignedMessage(message.msg.name()))
+        }
+    }
+
+    /// Signature over `digest`, see [`digest`]
+    pub fn sign(&self, digest: &[u8; 32]) -> Result<Vec<u8>, ecdsa::Error> {
+        Ok(ecdsa::Signature::new(digest, &self.private_key)?
+            .to_bytes()
+            .to_vec())
+    }
+
+    /// `message` in an envelope signed now
+    pub fn seal(&self, namespace: String, message: Message) -> Result<Envelope, ecdsa::Error> {
+        let timestamp = now_ms();
+        let sig = self.sign(&digest(
+            PROTOCOL_VERSION,
+            &namespace,
+            self.sender_id,
+            timestamp,
+            &message,
+        ))?;
+        Ok(Envelope {
+            version: PROTOCOL_VERSION,
+            namespace,
+            sender_id: self.sender_id,
+            timestamp,
+            message,
+            sig,
+        })
+    }
+}
+
+/// Checks inbound envelopes against the keys of the config
+#[derive(Clone, Debug)]
+pub struct Authenticator {
+    keys: PeerKeys,
+    max_clock_skew: Duration,
+    max_message_age: Option<Duration>,
+}
+
+impl Authenticator {
+    pub fn new(keys: PeerKeys, max_clock_skew: Duration) -> Self {
+        Self {
+            keys,
+            max_clock_skew,
+            max_message_age: None,
+        }
+    }
+
+    /// Drop envelopes sent longer than `max_message_age` ago, rather than those further in the
+    /// past than the clock skew tolerance
+    pub fn with_max_message_age(mut self, max_message_age: Option<Duration>) -> Self {
+        self.max_message_age = max_message_age;
+        self
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, SignerError> {
+        Ok(
+            Self::new(PeerKeys::try_from(config)?, config.max_clock_skew())
+                .with_max_message_age(config.max_message_age()),
+        )
+    }
+
+    /// Whether `envelope` is authentic and fresh
+    pub fn check(&self, envelope: &Envelope) -> Result<(), Error> {
+        self.check_at(envelope, now_ms())
+    }
+
+    /// Whether `envelope` is authentic and was sent close enough to `now`, in unix milliseconds
+    pub fn check_at(&self, envelope: &Envelope, now: u64) -> Result<(), Error> {
+        if envelope.version != PROTOCOL_VERSION {
+            return Err(Error::Version(envelope.version));
+        }
+        let skew = now.abs_diff(envelope.timestamp);
+        match self.max_message_age {
+            Some(max_age) if envelope.timestamp <= now => {
+                if skew > max_age.as_millis() as u64 {
+                    return Err(Error::Stale(skew));
+                }
+            }
+            _ if skew > self.max_clock_skew.as_millis() as u64 => {
+                return Err(Error::ClockSkew(skew))
+            }
+            _ => {}
+        }
+        let key = self
+            .sender_key(envelope)
+            .ok_or(Error::UnknownSender(envelope.sender_id))?;
+        let digest = digest(
+            envelope.version,
+            &envelope.namespace,
+            envelope.sender_id,
+            envelope.timestamp,
+            &envelope.message,
+        );
+        match ecdsa::Signature::try_from(envelope.sig.as_slice()) {
+            Ok(sig) if sig.verify(&digest, &key) => {}
+            _ => return Err(Error::BadSignature),
+        }
+        self.check_author(envelope, &key)
+    }
+
+    /// Whether the message in `envelope` is signed by its author's configured key, the
+    /// sender's key `sender_key`
+    fn check_author(
+        &self,
+        envelope: &Envelope,
+        sender_key: &ecdsa::PublicKey,
+    ) -> Result<(), Error> {
+        let Message { msg, sig } = &envelope.message;
+        // hellos were checked against the key they carry, see crate::ceremony
+        if matches!(msg, MessageTypes::Hello(_)) {
+            return Ok(());
+        }
+        let author = self
+            .keys
+            .signing_key(msg)
+            .ok_or(Error::UnknownAuthor(msg.name()))?;
+        if !msg.verify(sig, author) {
+            return Err(Error::BadMessageSignature(msg.name()));
+        }
+        if author.to_bytes() != sender_key.to_bytes() {
+            return Err(Error::NotAuthor(envelope.sender_id, msg.name()));
+        }
+        Ok(())
+    }
+
+    /// Hellos come from signers whose key may be missing from the config, so they are checked
+    /// against the key they carry
+    fn sender_key(&self, envelope: &Envelope) -> Option<ecdsa::PublicKey> {
+        match &envelope.message.msg {
+            MessageTypes::Hello(hello) if hello.signer_id == envelope.sender_id => {
+                ecdsa::PublicKey::try_from(hello.public_key.as_slice()).ok()
+            }
+            _ => self.keys.sender_key(envelope.sender_id).cloned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_round::{DkgBegin, UpgradeAck};
+    use rand_core::OsRng;
+
+    fn message() -> Message {
+        Message {
+            msg: MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 7,
+                excluded_key_ids: vec![],
+            }),
+            sig: vec![1, 2, 3],
+        }
+    }
+
+    /// A message `identity` authored and signed
+    fn authored(identity: &Identity) -> Message {
+        let msg = match identity.sender_id {
+            COORDINATOR_SENDER_ID => message().msg,
+            signer_id => MessageTypes::UpgradeAck(UpgradeAck {
+                signer_id,
+                window_id: 7,
+            }),
+        };
+        identity.sign_message(msg).unwrap()
+    }
+
+    fn group() -> (Vec<Identity>, Authenticator) {
+        let mut rng = OsRng;
+        let identities: Vec<Identity> = (0..3)
+            .map(|id| Identity::new(id, Scalar::random(&mut rng)))
+            .collect();
+        let mut keys: Vec<ecdsa::PublicKey> = identities
+            .iter()
+            .map(|identity| ecdsa::PublicKey::new(&identity.private_key).unwrap())
+            .collect();
+        let coordinator = keys.remove(0);
+        let keys = PeerKeys {
+            signers: keys.clone(),
+            keys,
+            coordinator,
+        };
+        (identities, Authenticator::new(keys, Duration::from_secs(5)))
+    }
+
+    #[test]
+    fn accepts_only_fresh_envelopes_signed_by_the_sender() {
+        let (identities, auth) = group();
+        let envelope = identities[2]
+            .seal("devnet".to_string(), authored(&identities[2]))
+            .unwrap();
+        let sent = envelope.timestamp;
+        assert_eq!(auth.check_at(&envelope, sent), Ok(()));
+        assert_eq!(auth.check_at(&envelope, sent + 5_000), Ok(()));
+        assert_eq!(auth.check_at(&envelope, sent - 5_000), Ok(()));
+        assert_eq!(
+            auth.check_at(&envelope, sent + 5_001),
+            Err(Error::ClockSkew(5_001))
+        );
+
+        // every signed field is covered
+        let mut forged = identities[1]
+            .seal("devnet".to_string(), authored(&identities[1]))
+            .unwrap();
+        forged.sender_id = 2;
+        assert_eq!(auth.check_at(&forged, sent), Err(Error::BadSignature));
+        let mut forged = identities[2]
+            .seal("devnet".to_string(), authored(&identities[2]))
+            .unwrap();
+        forged.namespace = "mainnet".to_string();
+        assert_eq!(auth.check_at(&forged, sent), Err(Error::BadSignature));
+        let mut forged = identities[2]
+            .seal("devnet".to_string(), authored(&identities[2]))
+            .unwrap();
+        forged.message.sig = vec![];
+        assert_eq!(auth.check_at(&forged, sent), Err(Error::BadSignature));
+
+        let mut envelope = identities[0]
+            .seal("devnet".to_string(), authored(&identities[0]))
+            .unwrap();
+        assert_eq!(auth.check_at(&envelope, sent), Ok(()));
+        envelope.sender_id = 3;
+        assert_eq!(auth.check_at(&envelope, sent), Err(Error::UnknownSender(3)));
+        envelope.version = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            auth.check_at(&envelope, sent),
+            Err(Error::Version(PROTOCOL_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn messages_must_be_signed_and_sent_by_their_author() {
+        let (identities, auth) = group();
+        let seal = |sender: &Identity, message: Message| {
+            let envelope = sender.seal("devnet".to_string(), message).unwrap();
+            auth.check_at(&envelope, envelope.timestamp)
+        };
+        assert_eq!(seal(&identities[1], authored(&identities[1])), Ok(()));
+        // signer #2 relaying a message of the coordinator or of signer #1
+        assert_eq!(
+            seal(&identities[2], authored(&identities[0])),
+            Err(Error::NotAuthor(2, "DkgBegin"))
+        );
+        assert_eq!(
+            seal(&identities[2], authored(&identities[1])),
+            Err(Error::NotAuthor(2, "UpgradeAck"))
+        );
+        // signer #2 passing off its own signature as signer #1's or the coordinator's
+        let forged = identities[2]
+            .sign_message(MessageTypes::UpgradeAck(UpgradeAck {
+                signer_id: 1,
+                window_id: 7,
+            }))
+            .unwrap();
+        assert_eq!(
+            seal(&identities[2], forged),
+            Err(Error::BadMessageSignature("UpgradeAck"))
+        );
+        assert_eq!(
+            seal(&identities[2], message()),
+            Err(Error::BadMessageSignature("DkgBegin"))
+        );
+        let unknown = identities[2]
+            .sign_message(MessageTypes::UpgradeAck(UpgradeAck {
+                signer_id: 9,
+                window_id: 7,
+            }))
+            .unwrap();
+        assert_eq!(
+            seal(&identities[2], unknown),
+            Err(Error::UnknownAuthor("UpgradeAck"))
+        );
+    }
+
+    #[test]
+    fn the_message_age_window_bounds_envelopes_from_the_past() {
+        let (identities, auth) = group();
+        let auth = auth.with_max_message_age(Some(Duration::from_secs(30)));
+        let envelope = identities[2]
+            .seal("devnet".to_string(), authored(&identities[2]))
+            .unwrap();
+        let sent = envelope.timestamp;
+        assert_eq!(auth.check_at(&envelope, sent + 30_000), Ok(()));
+        assert_eq!(
+            auth.check_at(&envelope, sent + 30_001),
+            Err(Error::Stale(30_001))
+        );
+        // clocks running ahead are still held to the skew tolerance
+        assert_eq!(auth.check_at(&envelope, sent - 5_000), Ok(()));
+        assert_eq!(
+            auth.check_at(&envelope, sent - 5_001),
+            Err(Error::ClockSkew(5_001))
+        );
+    }
+
+    #[test]
+    fn only_messages_signed_by_the_identity_pass_the_check() {
+        let (identities, _) = group();
+        let signed = identities[1].sign_message(message().msg).unwrap();
+        assert_eq!(identities[1].check(&signed).ok(), Some(()));
+        assert!(matches!(
+            identities[2].check(&signed),
+            Err(NetError::UnsignedMessage("DkgBegin"))
+        ));
+        assert!(matches!(
+            identities[1].check(&message()),
+            Err(NetError::UnsignedMessage(_))
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+//! In-process transport, for hosting the coordinator and signers in a single process.
+//!
+//! A [`Bus`] stands in for the relay: every envelope sent on a topic is queued once and each
+//! node reads the queue from its own high-water mark, as it would from the relay. The topic is
+//! the relay namespace, so nets on different topics never see each other's messages. Envelopes
+//! cross the bus encoded and signed exactly as they would cross the network, which makes the
+//! bus usable for integration tests of the full message path.
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use tracing::{debug, warn};
+
+use crate::auth::{Authenticator, Identity};
+use crate::logging::target::NET;
+use crate::net::{admit, authenticate, Envelope, Error, Message, Net, NetListen};
+use crate::traffic::Traffic;
+
+/// Topics shared by the nets of one process
+#[derive(Clone, Debug, Default)]
+pub struct Bus {
+    topics: Arc<Mutex<HashMap<String, Topic>>>,
+}
+
+/// Envelopes sent on a topic, and how far each node has read them
+#[derive(Debug, Default)]
+struct Topic {
+    queue: Vec<Vec<u8>>,
+    highwaters: HashMap<u32, usize>,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A net sending on `topic`, sealing its envelopes as `identity`
+    pub fn net(&self, topic: impl Into<String>, identity: Identity) -> BusNet {
+        BusNet {
+            bus: self.clone(),
+            topic: topic.into(),
+            identity,
+        }
+    }
+
+    fn post(&self, topic: &str, envelope: Vec<u8>) {
+        if let Ok(mut topics) = self.topics.lock() {
+            topics
+                .entry(topic.to_string())
+                .or_default()
+                .queue
+                .push(envelope);
+        }
+    }
+
+    /// Envelopes on `topic` node `id` has not read yet
+    fn unread(&self, topic: &str, id: u32) -> Vec<Vec<u8>> {
+        let Ok(mut topics) = self.topics.lock() else {
+            return vec![];
+        };
+        let Some(topic) = topics.get_mut(topic) else {
+            return vec![];
+        };
+        let highwater = topic.highwaters.entry(id).or_default();
+        let unread = topic.queue[*highwater..].to_vec();
+        *highwater = topic.queue.len();
+        unread
+    }
+}
+
+/// Sender side of a [`Bus`] topic
+#[derive(Clone, Debug)]
+pub struct BusNet {
+    bus: Bus,
+    topic: String,
+    identity: Identity,
+}
+
+impl Net for BusNet {
+    type Error = Error;
+
+    fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
+        self.identity.check(&msg)?;
+        let envelope = self
+            .identity
+            .seal(self.topic.clone(), msg)
+            .map_err(|e| Error::SigningError(e.to_string()))?;
+        debug!(target: NET, "bus {} sent {:?}", self.topic, envelope.message);
+        self.bus.post(&self.topic, envelope.to_bytes()?);
+        Ok(())
+    }
+}
+
+/// Receiving side of a [`Bus`] topic
+pub struct BusNetListen {
+    pub net: BusNet,
+    in_queue: VecDeque<Message>,
+    /// Drops inbound envelopes which fail authentication, if set
+    authenticator: Option<Authenticator>,
+    /// Counts inbound envelopes and drops our own, if set
+    traffic: Option<Traffic>,
+}
+
+impl BusNetListen {
+    pub fn new(net: BusNet) -> Self {
+        Self {
+            net,
+            in_queue: VecDeque::new(),
+            authenticator: None,
+            traffic: None,
+        }
+    }
+
+    /// Only queue messages in envelopes `authenticator` accepts
+    pub fn with_authenticator(mut self, authenticator: Authenticator) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Only queue messages in envelopes `traffic` admits
+    pub fn with_traffic(mut self, traffic: Traffic) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+}
+
+impl NetListen for BusNetListen {
+    type Error = Error;
+
+    fn listen(&self) {}
+
+    fn poll(&mut self, id: u32) {
+        for bytes in self.net.bus.unread(&self.net.topic, id) {
+            let envelope = match Envelope::from_bytes(&bytes) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    warn!(target: NET, "dropping undecodable envelope: {e}");
+                    continue;
+                }
+            };
+            match authenticate(&self.authenticator, &self.traffic, &envelope) {
+                Err(e) => warn!(
+                    target: NET,
+                    "dropping message from sender #{}: {e}", envelope.sender_id
+                ),
+                _ if !admit(&self.traffic, &envelope) => {
+                    debug!(target: NET, "dropping our own {:?}", envelope.message)
+                }
+                _ => self.in_queue.push_back(envelope.message),
+            }
+        }
+    }
+
+    fn next_message(&mut self) -> Option<Message> {
+        self.in_queue.pop_front()
+    }
+
+    fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
+        self.net.send_message(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::PeerKeys;
+    use crate::signing_round::{DkgBegin, MessageTypes};
+    use p256k1::ecdsa;
+    use rand_core::OsRng;
+    use std::time::Duration;
+    use wtfrost::Scalar;
+
+    fn dkg_begin(dkg_id: u64) -> MessageTypes {
+        MessageTypes::DkgBegin(DkgBegin {
+            dkg_id,
+            excluded_key_ids: vec![],
+        })
+    }
+
+    fn dkg_ids(listen: &mut BusNetListen, id: u32) -> Vec<u64> {
+        listen.poll(id);
+        std::iter::from_fn(|| listen.next_message())
+            .filter_map(|message| match message.msg {
+                MessageTypes::DkgBegin(msg) => Some(msg.dkg_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_node_reads_each_topic_once_in_order() {
+        let identity = Identity::new(0, Scalar::random(&mut OsRng));
+        let bus = Bus::new();
+        let devnet = bus.net("devnet", identity.clone());
+        let mut first = BusNetListen::new(devnet.clone());
+        let mut second = BusNetListen::new(bus.net("devnet", identity.clone()));
+        let mut other = BusNetListen::new(bus.net("testnet", identity.clone()));
+
+        for dkg_id in 1..=3 {
+            devnet
+                .send_message(identity.sign_message(dkg_begin(dkg_id)).unwrap())
+                .unwrap();
+        }
+        assert_eq!(dkg_ids(&mut first, 1), vec![1, 2, 3]);
+        assert_eq!(dkg_ids(&mut first, 1), Vec::<u64>::new());
+        devnet
+            .send_message(identity.sign_message(dkg_begin(4)).unwrap())
+            .unwrap();
+        assert_eq!(dkg_ids(&mut second, 2), vec![1, 2, 3, 4]);
+        assert_eq!(dkg_ids(&mut first, 1), vec![4]);
+        assert_eq!(dkg_ids(&mut other, 1), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn unsigned_and_unauthenticated_messages_do_not_cross_the_bus() {
+        let coordinator = Identity::new(0, Scalar::random(&mut OsRng));
+        let stranger = Identity::new(0, Scalar::random(&mut OsRng));
+        let key = ecdsa::PublicKey::new(coordinator.private_key()).unwrap();
+        let keys = PeerKeys {
+            signers: vec![key],
+            keys: vec![key],
+            coordinator: key,
+        };
+        let bus = Bus::new();
+        let mut listen = BusNetListen::new(bus.net("devnet", coordinator.clone()))
+            .with_authenticator(Authenticator::new(keys, Duration::from_secs(5)));
+
+        let unsigned = Message {
+            msg: dkg_begin(1),
+            sig: vec![],
+        };
+        assert!(bus
+            .net("devnet", coordinator.clone())
+            .send_message(unsigned)
+            .is_err());
+        bus.net("devnet", stranger.clone())
+            .send_message(stranger.sign_message(dkg_begin(2)).unwrap())
+            .unwrap();
+        bus.net("devnet", coordinator.clone())
+            .send_message(coordinator.sign_message(dkg_begin(3)).unwrap())
+            .unwrap();
+        assert_eq!(dkg_ids(&mut listen, 1), vec![3]);
+    }
+
+    #[test]
+    fn own_messages_are_dropped_unless_accepted() {
+        let coordinator = Identity::new(0, Scalar::random(&mut OsRng));
+        let bus = Bus::new();
+        let devnet = bus.net("devnet", coordinator.clone());
+        let traffic = Traffic::new(0, false);
+        let mut own = BusNetListen::new(devnet.clone()).with_traffic(traffic.clone());
+        let mut loopback = BusNetListen::new(devnet.clone()).with_traffic(Traffic::new(0, true));
+        let mut peer = BusNetListen::new(devnet.clone()).with_traffic(Traffic::new(1, false));
+
+        devnet
+            .send_message(coordinator.sign_message(dkg_begin(1)).unwrap())
+            .unwrap();
+        assert_eq!(dkg_ids(&mut own, 0), Vec::<u64>::new());
+        assert_eq!(dkg_ids(&mut loopback, 1), vec![1]);
+        assert_eq!(dkg_ids(&mut peer, 2), vec![1]);
+        assert_eq!((traffic.counts().own, traffic.counts().own_dropped), (1, 1));
+    }
+}
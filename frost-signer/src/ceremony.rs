@@ -0,0 +1,284 @@
+//! Pre-DKG handshake between the members of a signing group.
+//!
+//! Every signer broadcasts a signed [`Hello`] carrying the public key derived from its own
+//! private key and the [`Config::group_hash`] of its config. Once hellos from all signers
+//! have arrived each signer prints a short fingerprint of the sorted roster, which the
+//! operators read out to each other before the first key generation. Matching fingerprints
+//! mean every signer holds the same keys and the same group settings.
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use p256k1::ecdsa;
+use sha2::Digest;
+use tracing::{debug, info, warn};
+
+use crate::auth::Identity;
+use crate::config::Config;
+use crate::net::{Error as HttpNetError, Message, NetListen, TransportNet, TransportNetListen};
+use crate::protocol::{tag, Hasher};
+use crate::signing_round::{Hello, MessageTypes};
+
+/// Number of fingerprint bytes shown to operators
+const FINGERPRINT_BYTES: usize = 10;
+/// Pause between relay polls while hellos are outstanding
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Http Network Error: {0}")]
+    HttpNetError(#[from] HttpNetError),
+
+    #[error("Invalid key in config: {0}")]
+    InvalidKey(String),
+
+    #[error("Signer id {0} is not part of a group of {1} signers")]
+    InvalidSignerId(u32, usize),
+
+    #[error("Timed out waiting for hellos from signers {0:?}")]
+    Timeout(Vec<u32>),
+}
+
+/// One signer's view of the group roster
+pub struct Ceremony {
+    signer_id: u32,
+    identity: Identity,
+    total_signers: usize,
+    config_hash: [u8; 32],
+    configured_keys: Vec<Vec<u8>>,
+    hellos: BTreeMap<u32, Hello>,
+}
+
+impl Ceremony {
+    pub fn new(config: &Config, signer_id: u32) -> Result<Self, Error> {
+        if signer_id == 0 || signer_id as usize > config.total_signers {
+            return Err(Error::InvalidSignerId(signer_id, config.total_signers));
+        }
+        let identity = Identity::from_config(config, signer_id)
+            .map_err(|_| Error::InvalidKey("network_private_key".to_string()))?;
+        let configured_keys = config
+            .signer_public_keys
+            .iter()
+            .map(|key| {
+                ecdsa::PublicKey::try_from(key.as_str())
+                    .map(|key| key.to_bytes().to_vec())
+                    .map_err(|e| Error::InvalidKey(format!("signer_public_keys: {e}")))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            signer_id,
+            identity,
+            total_signers: config.total_signers,
+            config_hash: config.group_hash(),
+            configured_keys,
+            hellos: BTreeMap::new(),
+        })
+    }
+
+    /// This signer's signed hello
+    pub fn hello(&self) -> Result<Message, Error> {
+        let public_key = ecdsa::PublicKey::new(self.identity.private_key())
+            .map_err(|e| Error::InvalidKey(format!("network_private_key: {e}")))?;
+        let hello = Hello {
+            signer_id: self.signer_id,
+            public_key: public_key.to_bytes().to_vec(),
+            config_hash: self.config_hash.to_vec(),
+        };
+        Ok(self.identity.sign_message(MessageTypes::Hello(hello))?)
+    }
+
+    /// Record `message` if it is a hello signed by the key it carries.
+    /// Returns whether the message was accepted.
+    pub fn receive(&mut self, message: &Message) -> bool {
+        let MessageTypes::Hello(hello) = &message.msg else {
+            debug!("ignoring non-hello message during ceremony");
+            return false;
+        };
+        if hello.signer_id == 0 || hello.signer_id as usize > self.total_signers {
+            warn!("hello from unknown signer id {}", hello.signer_id);
+            return false;
+        }
+        if hello.config_hash.len() != self.config_hash.len() {
+            warn!(
+                "hello from signer {} has a malformed config hash",
+                hello.signer_id
+            );
+            return false;
+        }
+        if !hello.is_self_signed(&message.sig) {
+            warn!("hello from signer {} has a bad signature", hello.signer_id);
+            return false;
+        }
+        if let Some(previous) = self.hellos.insert(hello.signer_id, hello.clone()) {
+            if previous != *hello {
+                warn!("signer {} changed its hello", hello.signer_id);
+            }
+        }
+        true
+    }
+
+    /// Whether a hello from every signer in the group has arrived
+    pub fn is_complete(&self) -> bool {
+        self.missing().is_empty()
+    }
+
+    /// Signers which have not said hello yet
+    pub fn missing(&self) -> Vec<u32> {
+        (1..=self.total_signers as u32)
+            .filter(|id| !self.hellos.contains_key(id))
+            .collect()
+    }
+
+    /// Hellos received so far, ordered by signer id
+    pub fn roster(&self) -> impl Iterator<Item = &Hello> {
+        self.hellos.values()
+    }
+
+    /// Signers whose public key differs from the one in our config
+    pub fn key_mismatches(&self) -> Vec<u32> {
+        self.roster()
+            .filter(|hello| {
+                self.configured_keys.get(hello.signer_id as usize - 1) != Some(&hello.public_key)
+            })
+            .map(|hello| hello.signer_id)
+            .collect()
+    }
+
+    /// Signers whose group settings differ from ours
+    pub fn config_mismatches(&self) -> Vec<u32> {
+        self.roster()
+            .filter(|hello| hello.config_hash != self.config_hash)
+            .map(|hello| hello.signer_id)
+            .collect()
+    }
+
+    /// Short digest of the sorted roster, e.g. `3f2a-91c0-5be7-0d44-e813`
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Hasher::new();
+        hasher.update(tag::SIGNER_ROSTER.as_bytes());
+        for hello in self.roster() {
+            hasher.update(hello.signer_id.to_be_bytes());
+            hasher.update(&hello.public_key);
+            hasher.update(&hello.config_hash);
+        }
+        let digest = hasher.finalize();
+        digest[..FINGERPRINT_BYTES]
+            .chunks(2)
+            .map(|pair| pair.iter().map(|b| format!("{b:02x}")).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+}
+
+/// Broadcast our hello over the relay and wait up to `timeout` for every other signer's
+pub fn run(config: &Config, signer_id: u32, timeout: Duration) -> Result<Ceremony, Error> {
+    let mut ceremony = Ceremony::new(config, signer_id)?;
+    // hellos are checked by the ceremony itself, against the key they carry
+    let mut net_queue = TransportNetListen::new(TransportNet::from_config(config, signer_id)?);
+
+    info!("signer {} saying hello", signer_id);
+    net_queue.send_message(ceremony.hello()?)?;
+
+    let deadline = Instant::now() + timeout;
+    while !ceremony.is_complete() {
+        net_queue.poll(signer_id);
+        match net_queue.next_message() {
+            Some(message) => {
+                ceremony.receive(&message);
+            }
+            None if Instant::now() >= deadline => return Err(Error::Timeout(ceremony.missing())),
+            None => thread::sleep(POLL_INTERVAL),
+        }
+    }
+    Ok(ceremony)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+    use wtfrost::{Point, Scalar};
+
+    fn group(n: usize) -> (Vec<Scalar>, Config) {
+        let mut rng = OsRng;
+        let private_keys: Vec<Scalar> = (0..n).map(|_| Scalar::random(&mut rng)).collect();
+        let config = Config {
+            total_signers: n,
+            signer_public_keys: private_keys
+                .iter()
+                .map(|key| Point::from(key).to_string())
+                .collect(),
+            ..Default::default()
+        };
+        (private_keys, config)
+    }
+
+    fn ceremony(private_key: &Scalar, config: &Config, signer_id: u32) -> Ceremony {
+        let config = Config {
+            network_private_key: private_key.to_string().into(),
+            ..config.clone()
+        };
+        Ceremony::new(&config, signer_id).unwrap()
+    }
+
+    #[test]
+    fn fingerprint_is_independent_of_arrival_order() {
+        let (keys, config) = group(3);
+        let ceremonies: Vec<Ceremony> = (0..3)
+            .map(|i| ceremony(&keys[i], &config, i as u32 + 1))
+            .collect();
+        let hellos: Vec<Message> = ceremonies.iter().map(|c| c.hello().unwrap()).collect();
+
+        let mut forward = ceremony(&keys[0], &config, 1);
+        let mut backward = ceremony(&keys[1], &config, 2);
+        for hello in &hellos {
+            assert!(forward.receive(hello));
+        }
+        assert!(!backward.is_complete());
+        for hello in hellos.iter().rev() {
+            assert!(backward.receive(hello));
+        }
+
+        assert!(forward.is_complete());
+        assert!(backward.is_complete());
+        assert_eq!(forward.fingerprint(), backward.fingerprint());
+        assert!(forward.key_mismatches().is_empty());
+        assert!(forward.config_mismatches().is_empty());
+    }
+
+    #[test]
+    fn diverging_views_change_the_fingerprint() {
+        let (keys, config) = group(2);
+        let honest = ceremony(&keys[1], &config, 2).hello().unwrap();
+        let mut ours = ceremony(&keys[0], &config, 1);
+        ours.receive(&ours.hello().unwrap());
+        ours.receive(&honest);
+
+        // signer 2 runs with a stale threshold and a key nobody else has configured
+        let stale = Config {
+            keys_threshold: config.keys_threshold + 1,
+            ..config.clone()
+        };
+        let rogue_key = Scalar::random(&mut OsRng);
+        let rogue = ceremony(&rogue_key, &stale, 2).hello().unwrap();
+        let mut theirs = ceremony(&keys[0], &config, 1);
+        theirs.receive(&theirs.hello().unwrap());
+        theirs.receive(&rogue);
+
+        assert_ne!(ours.fingerprint(), theirs.fingerprint());
+        assert_eq!(theirs.key_mismatches(), vec![2]);
+        assert_eq!(theirs.config_mismatches(), vec![2]);
+    }
+
+    #[test]
+    fn forged_hellos_are_rejected() {
+        let (keys, config) = group(2);
+        let mut ceremony = ceremony(&keys[0], &config, 1);
+        let mut hello = ceremony.hello().unwrap();
+        if let MessageTypes::Hello(msg) = &mut hello.msg {
+            msg.signer_id = 2;
+        }
+        assert!(!ceremony.receive(&hello));
+        assert_eq!(ceremony.missing(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,622 @@
+//! Local control endpoints, such as the round status and the log filter, served together.
+//!
+//! A `[control]` table in the signer or coordinator config serves every endpoint of the process
+//! from one listener on a TCP address, a Unix domain socket, or both, and optionally requires a
+//! bearer token:
+//!
+//! ```toml
+//! [control]
+//! listen_addr = "127.0.0.1:9900"
+//! socket_path = "/run/stacks-signer/control.sock"
+//! # permissions of the socket file, 0o600 unless set
+//! socket_mode = 0o660
+//! # required as `authorization: Bearer <token>` when set
+//! token = "..."
+//! ```
+//!
+//! The endpoints given their own address, e.g. `status_listen_addr`, require the token as well.
+//! Routes may also stream server-sent events, holding the connection open, see
+//! [`Route::stream`].
+//!
+//! Each connection is served from a thread of its own, and must send its request within
+//! [`IO_TIMEOUT`]. A request is refused before its body is read if it lacks the token, or if the
+//! body is longer than [`MAX_BODY_LEN`].
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::secret::Secret;
+
+/// A `[control]` config table
+#[derive(Clone, Debug, Deserialize)]
+pub struct ControlConfig {
+    /// TCP address serving every control endpoint, e.g. "127.0.0.1:9900"
+    #[serde(default)]
+    pub listen_addr: Option<String>,
+    /// Unix domain socket serving every control endpoint
+    #[serde(default)]
+    pub socket_path: Option<String>,
+    /// Permissions of the socket file
+    #[serde(default = "default_socket_mode")]
+    pub socket_mode: u32,
+    /// Bearer token every control request must carry
+    #[serde(default)]
+    pub token: Option<Secret<String>>,
+}
+
+fn default_socket_mode() -> u32 {
+    0o600
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: None,
+            socket_path: None,
+            socket_mode: default_socket_mode(),
+            token: None,
+        }
+    }
+}
+
+impl ControlConfig {
+    /// Where a client reaches the endpoints, the socket if both are configured
+    pub fn endpoint(&self) -> Option<Endpoint> {
+        match (&self.socket_path, &self.listen_addr) {
+            (Some(path), _) => Some(Endpoint::Unix(PathBuf::from(path))),
+            (None, Some(addr)) => Some(Endpoint::Tcp(addr.clone())),
+            (None, None) => None,
+        }
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_ref().map(|token| token.as_str())
+    }
+}
+
+/// Address of a control listener
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => f.write_str(addr),
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+pub struct Response {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn text(status: &'static str, body: String) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body,
+        }
+    }
+
+    pub fn json(status: &'static str, body: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+}
+
+/// One or more control endpoints
+pub trait Route: Send + Sync {
+    /// Answer a request, or `None` if `path` is not served by this route
+    fn respond(&self, method: &str, path: &str, body: &str) -> Option<Response>;
+
+    /// Events to stream for a request, or `None` if `path` is not streamed by this route. Each
+    /// event is sent as one server-sent event until the sender is dropped or the client leaves.
+    fn stream(&self, _method: &str, _path: &str) -> Option<mpsc::Receiver<String>> {
+        None
+    }
+}
+
+/// Comment sent on an idle event stream, to notice clients which went away
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// How long a connection may take to send its request, or to take in a write of the response
+pub const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest request body read, a longer one is answered with `413 Payload Too Large`
+pub const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// Longest request line and headers read
+const MAX_HEAD_LEN: u64 = 16 * 1024;
+
+/// The request line and the headers the local endpoints read of an HTTP request
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestHead {
+    pub request_line: String,
+    pub authorization: Option<String>,
+    pub content_length: usize,
+}
+
+impl RequestHead {
+    /// Read the request line and headers, leaving `reader` at the start of the body
+    pub fn read(reader: &mut impl BufRead) -> std::io::Result<Self> {
+        let mut head = reader.take(MAX_HEAD_LEN);
+        let mut request_line = String::new();
+        head.read_line(&mut request_line)?;
+        let mut content_length = 0;
+        let mut authorization = None;
+        loop {
+            let mut header = String::new();
+            if head.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                } else if name.trim().eq_ignore_ascii_case("authorization") {
+                    authorization = Some(value.trim().to_string());
+                }
+            }
+        }
+        Ok(Self {
+            request_line,
+            authorization,
+            content_length,
+        })
+    }
+
+    /// Whether the body is too long to be read
+    pub fn too_large(&self) -> bool {
+        self.content_length > MAX_BODY_LEN
+    }
+
+    /// Read the body following the head, which must not be [`too_large`](Self::too_large)
+    pub fn read_body(&self, reader: &mut impl Read) -> std::io::Result<String> {
+        let mut body = vec![0; self.content_length.min(MAX_BODY_LEN)];
+        reader.read_exact(&mut body)?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Write a whole HTTP response to `stream`
+pub fn write_response(mut stream: impl Write, response: &Response) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )?;
+    stream.flush()
+}
+
+/// Serves its routes, checking the token of every request
+#[derive(Clone, Default)]
+pub struct Server {
+    routes: Vec<Arc<dyn Route>>,
+    token: Option<Arc<Secret<String>>>,
+}
+
+impl std::fmt::Debug for Server {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Server")
+            .field("routes", &self.routes.len())
+            .field("token", &self.token.is_some())
+            .finish()
+    }
+}
+
+impl Server {
+    /// A server requiring the token of `config`, if it sets one
+    pub fn new(config: &ControlConfig) -> Self {
+        Self {
+            routes: vec![],
+            token: config.token.clone().map(Arc::new),
+        }
+    }
+
+    pub fn with_route(mut self, route: impl Route + 'static) -> Self {
+        self.routes.push(Arc::new(route));
+        self
+    }
+
+    /// Serve on the listen address and socket of `config`, whichever are set
+    pub fn serve(&self, config: &ControlConfig) -> std::io::Result<()> {
+        if let Some(addr) = &config.listen_addr {
+            self.serve_tcp(addr.as_str())?;
+        }
+        if let Some(path) = &config.socket_path {
+            self.serve_unix(path, config.socket_mode)?;
+        }
+        Ok(())
+    }
+
+    /// Serve on `addr` from a background thread
+    pub fn serve_tcp(&self, addr: impl ToSocketAddrs) -> std::io::Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(addr)?;
+        info!("control endpoint listening on {}", listener.local_addr()?);
+        let server = self.clone();
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.and_then(|stream| {
+                    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                    Ok(stream)
+                });
+                match stream {
+                    Ok(stream) => server.spawn_handler(stream),
+                    Err(e) => warn!("control request failed: {}", e),
+                }
+            }
+        }))
+    }
+
+    /// Serve on a Unix domain socket at `path` with permissions `mode`, from a background
+    /// thread. A socket file left by an earlier run is replaced.
+    pub fn serve_unix(&self, path: impl AsRef<Path>, mode: u32) -> std::io::Result<JoinHandle<()>> {
+        unix::serve(self.clone(), path.as_ref(), mode)
+    }
+
+    /// Serve `stream` from a thread of its own, so that a slow client holds up no other
+    fn spawn_handler<S: Read + Write + Send + 'static>(&self, stream: S) {
+        let server = self.clone();
+        thread::spawn(move || {
+            if let Err(e) = server.handle(stream) {
+                warn!("control request failed: {}", e);
+            }
+        });
+    }
+
+    fn handle<S: Read + Write>(&self, stream: S) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let head = RequestHead::read(&mut reader)?;
+        let authorization = head.authorization.as_deref();
+        // nothing more is read of a request which would be refused
+        if !self.authorized(authorization) {
+            let response = Response::text("401 Unauthorized", String::new());
+            return write_response(reader.get_mut(), &response);
+        }
+        if head.too_large() {
+            let response = Response::text("413 Payload Too Large", String::new());
+            return write_response(reader.get_mut(), &response);
+        }
+        let body = head.read_body(&mut reader)?;
+        if let Some(events) = self.stream(&head.request_line, authorization) {
+            // the connection stays open until either side goes away
+            return write_events(reader.into_inner(), events);
+        }
+        let response = self.respond(&head.request_line, authorization, &body);
+        write_response(reader.into_inner(), &response)
+    }
+
+    fn authorized(&self, authorization: Option<&str>) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        let bearer = authorization.and_then(|value| value.strip_prefix("Bearer "));
+        bearer.is_some_and(|bearer| constant_time_eq(bearer.trim(), token.as_str()))
+    }
+
+    fn stream(
+        &self,
+        request_line: &str,
+        authorization: Option<&str>,
+    ) -> Option<mpsc::Receiver<String>> {
+        if !self.authorized(authorization) {
+            return None;
+        }
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next()?, parts.next()?);
+        self.routes
+            .iter()
+            .find_map(|route| route.stream(method, path))
+    }
+
+    fn respond(&self, request_line: &str, authorization: Option<&str>, body: &str) -> Response {
+        if !self.authorized(authorization) {
+            return Response::text("401 Unauthorized", String::new());
+        }
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+            return Response::text("400 Bad Request", String::new());
+        };
+        self.routes
+            .iter()
+            .find_map(|route| route.respond(method, path, body))
+            .unwrap_or_else(|| Response::text("404 Not Found", String::new()))
+    }
+}
+
+/// Write `events` to `stream` as server-sent events until either side goes away
+fn write_events(mut stream: impl Write, events: mpsc::Receiver<String>) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\n\r\n"
+    )?;
+    stream.flush()?;
+    loop {
+        match events.recv_timeout(KEEPALIVE) {
+            Ok(event) => write!(stream, "data: {event}\n\n")?,
+            Err(mpsc::RecvTimeoutError::Timeout) => write!(stream, ": keepalive\n\n")?,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Send `method` `path` with `body` to the control endpoint at `endpoint`, returning the
+/// response body if the request succeeded
+pub fn request(
+    endpoint: &Endpoint,
+    token: Option<&str>,
+    method: &str,
+    path: &str,
+    body: &str,
+) -> std::io::Result<String> {
+    let mut request = format!(
+        "{method} {path} HTTP/1.0\r\ncontent-length: {}\r\n",
+        body.len()
+    );
+    if let Some(token) = token {
+        request.push_str(&format!("authorization: Bearer {token}\r\n"));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    let response = match endpoint {
+        Endpoint::Tcp(addr) => exchange(TcpStream::connect(addr.as_str())?, &request)?,
+        Endpoint::Unix(path) => unix::exchange(path, &request)?,
+    };
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head.lines().next().unwrap_or_default();
+    if status.split_whitespace().nth(1) == Some("200") {
+        Ok(body.trim().to_string())
+    } else {
+        Err(std::io::Error::other(format!("{status}: {}", body.trim())))
+    }
+}
+
+fn exchange(mut stream: impl Read + Write, request: &str) -> std::io::Result<String> {
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::thread::{self, JoinHandle};
+
+    use tracing::{info, warn};
+
+    use super::{Server, IO_TIMEOUT};
+
+    pub fn serve(server: Server, path: &Path, mode: u32) -> std::io::Result<JoinHandle<()>> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        // the socket is created with no more than `mode` allows, so that it is never open to
+        // others between binding and the chmod. The umask is process wide, and is restored
+        // right after.
+        let umask = unsafe { libc::umask(!(mode as libc::mode_t) & 0o777) };
+        let listener = UnixListener::bind(path);
+        unsafe { libc::umask(umask) };
+        let listener = listener?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        info!("control endpoint listening on {}", path.display());
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.and_then(|stream| {
+                    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+                    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+                    Ok(stream)
+                });
+                match stream {
+                    Ok(stream) => server.spawn_handler(stream),
+                    Err(e) => warn!("control request failed: {}", e),
+                }
+            }
+        }))
+    }
+
+    pub fn exchange(path: &Path, request: &str) -> std::io::Result<String> {
+        super::exchange(UnixStream::connect(path)?, request)
+    }
+}
+
+#[cfg(not(unix))]
+mod unix {
+    use std::path::Path;
+    use std::thread::JoinHandle;
+
+    use super::Server;
+
+    pub fn serve(_server: Server, _path: &Path, _mode: u32) -> std::io::Result<JoinHandle<()>> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn exchange(_path: &Path, _request: &str) -> std::io::Result<String> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ping;
+
+    impl Route for Ping {
+        fn respond(&self, method: &str, path: &str, body: &str) -> Option<Response> {
+            (method == "POST" && path == "/ping").then(|| Response::text("200 OK", body.into()))
+        }
+    }
+
+    /// Streams the lines of its body
+    struct Echo;
+
+    impl Route for Echo {
+        fn respond(&self, _method: &str, _path: &str, _body: &str) -> Option<Response> {
+            None
+        }
+
+        fn stream(&self, method: &str, path: &str) -> Option<mpsc::Receiver<String>> {
+            let (sender, receiver) = mpsc::channel();
+            let events = path.strip_prefix("/echo/")?;
+            for event in events.split(',') {
+                sender.send(event.to_string()).unwrap();
+            }
+            (method == "GET").then_some(receiver)
+        }
+    }
+
+    #[test]
+    fn streams_server_sent_events() {
+        let config = ControlConfig {
+            token: Some(Secret::new("s3cret".to_string())),
+            ..Default::default()
+        };
+        let server = Server::new(&config).with_route(Echo);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                server.handle(stream.unwrap()).unwrap();
+            }
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET /echo/one,two HTTP/1.0\r\nauthorization: Bearer s3cret\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.0 200 OK"));
+        assert!(head.contains("content-type: text/event-stream"));
+        assert_eq!(body, "data: one\n\ndata: two\n\n");
+
+        let endpoint = Endpoint::Tcp(addr.to_string());
+        let unauthorized = request(&endpoint, None, "GET", "/echo/one", "");
+        assert!(unauthorized.unwrap_err().to_string().contains("401"));
+    }
+
+    #[test]
+    fn refuses_requests_before_reading_their_body() {
+        let config = ControlConfig {
+            token: Some(Secret::new("s3cret".to_string())),
+            ..Default::default()
+        };
+        let server = Server::new(&config).with_route(Ping);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                server.handle(stream.unwrap()).unwrap();
+            }
+        });
+        // each request announces a body it never sends, so waiting for it would hang
+        let status = |request: String| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response.lines().next().unwrap_or_default().to_string()
+        };
+
+        assert_eq!(
+            status("POST /ping HTTP/1.0\r\ncontent-length: 4\r\n\r\n".to_string()),
+            "HTTP/1.0 401 Unauthorized"
+        );
+        assert_eq!(
+            status(format!(
+                "POST /ping HTTP/1.0\r\nauthorization: Bearer s3cret\r\ncontent-length: {}\r\n\r\n",
+                MAX_BODY_LEN + 1
+            )),
+            "HTTP/1.0 413 Payload Too Large"
+        );
+    }
+
+    #[test]
+    fn requires_the_token_when_configured() {
+        let config = ControlConfig {
+            token: Some(Secret::new("s3cret".to_string())),
+            ..Default::default()
+        };
+        let server = Server::new(&config).with_route(Ping);
+        let respond = |authorization| {
+            server
+                .respond("POST /ping HTTP/1.0\r\n", authorization, "pong")
+                .status
+        };
+        assert_eq!(respond(Some("Bearer s3cret")), "200 OK");
+        assert_eq!(respond(Some("Bearer s3cre")), "401 Unauthorized");
+        assert_eq!(respond(None), "401 Unauthorized");
+
+        let open = Server::default().with_route(Ping);
+        assert_eq!(
+            open.respond("POST /ping HTTP/1.0\r\n", None, "").status,
+            "200 OK"
+        );
+        assert_eq!(
+            open.respond("GET /log HTTP/1.0\r\n", None, "").status,
+            "404 Not Found"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serves_over_a_unix_socket() {
+        let path = std::env::temp_dir().join(format!("control-{}.sock", std::process::id()));
+        let config = ControlConfig {
+            socket_path: Some(path.to_string_lossy().into_owned()),
+            token: Some(Secret::new("s3cret".to_string())),
+            ..Default::default()
+        };
+        Server::new(&config)
+            .with_route(Ping)
+            .serve(&config)
+            .unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions();
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&mode) & 0o777,
+            0o600
+        );
+
+        let endpoint = config.endpoint().unwrap();
+        let reply = request(&endpoint, config.token(), "POST", "/ping", "pong").unwrap();
+        assert_eq!(reply, "pong");
+        assert!(request(&endpoint, None, "POST", "/ping", "pong").is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+//! What signing rounds sign.
+//!
+//! Signers sign a 32-byte digest as it is, the way BIP-340 signs a taproot sighash, and reject
+//! requests to sign anything else. A message which is not a digest already is reduced to one
+//! with the BIP-340 tagged hash `sha256(sha256(tag) || sha256(tag) || msg)` under a domain tag
+//! naming what the message is. A digest is thus never hashed a second time, and the same bytes
+//! signed under two tags give two different digests.
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("A message digest is 32 bytes, not {0}")]
+    InvalidLength(usize),
+}
+
+/// A digest signing rounds sign as is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MessageDigest([u8; 32]);
+
+impl MessageDigest {
+    pub fn new(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+
+    /// The BIP-340 tagged hash of `msg` under the domain tag `tag`
+    pub fn tagged(tag: &str, msg: &[u8]) -> Self {
+        let tag = Sha256::digest(tag.as_bytes());
+        let mut hasher = Sha256::new();
+        hasher.update(tag);
+        hasher.update(tag);
+        hasher.update(msg);
+        Self(hasher.finalize().into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for MessageDigest {
+    fn from(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+}
+
+impl TryFrom<&[u8]> for MessageDigest {
+    type Error = Error;
+    fn try_from(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Self(
+            bytes
+                .try_into()
+                .map_err(|_| Error::InvalidLength(bytes.len()))?,
+        ))
+    }
+}
+
+impl fmt::Display for MessageDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tagged_hashes_follow_bip340() {
+        // BIP-340's own challenge tag, over an empty message
+        let tag = Sha256::digest(b"BIP0340/challenge");
+        let expected: [u8; 32] = Sha256::digest([tag.as_slice(), tag.as_slice()].concat()).into();
+        assert_eq!(
+            MessageDigest::tagged("BIP0340/challenge", &[]).as_bytes(),
+            &expected
+        );
+        assert_ne!(
+            MessageDigest::tagged("sbtc/a", b"msg"),
+            MessageDigest::tagged("sbtc/b", b"msg")
+        );
+    }
+
+    #[test]
+    fn only_32_bytes_make_a_digest() {
+        assert_eq!(
+            MessageDigest::try_from([7; 32].as_slice()),
+            Ok(MessageDigest::new([7; 32]))
+        );
+        assert_eq!(
+            MessageDigest::try_from(b"message".as_slice()),
+            Err(Error::InvalidLength(7))
+        );
+    }
+}
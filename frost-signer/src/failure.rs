@@ -0,0 +1,165 @@
+//! Exit codes and failure reports for fatal errors.
+//!
+//! A signer or coordinator which stops on an error it cannot recover from exits with the code of
+//! the error's [`ErrorClass`], so an orchestrator can tell a node worth restarting from one which
+//! needs a human. Given a `failure_report_path` in its config it first writes a [`FailureReport`]
+//! there, with the error, the rounds in flight and the last heights it processed.
+//!
+//! | code | class         | restart helps |
+//! |------|---------------|---------------|
+//! | 0    |               | clean exit    |
+//! | 1    |               | a one-off command failed |
+//! | 69   | `unavailable` | yes, once the relay or node is back |
+//! | 70   | `internal`    | no            |
+//! | 74   | `storage`     | no            |
+//! | 78   | `config`      | no            |
+//!
+//! The codes are those of `sysexits.h`.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::retention::now;
+
+/// What kind of failure stopped a node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// The config or a key in it is invalid
+    Config,
+    /// The relay, a node or another dependency stayed unreachable
+    Unavailable,
+    /// A local file or database could not be read or written
+    Storage,
+    /// A bug or a broken invariant
+    Internal,
+}
+
+impl ErrorClass {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Unavailable => 69,
+            Self::Internal => 70,
+            Self::Storage => 74,
+            Self::Config => 78,
+        }
+    }
+
+    /// Whether restarting the node may get it past the failure
+    pub fn restartable(&self) -> bool {
+        matches!(self, Self::Unavailable)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Config => "config",
+            Self::Unavailable => "unavailable",
+            Self::Storage => "storage",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Why a node stopped, as written to its `failure_report_path`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FailureReport {
+    /// Name and version of the binary
+    pub program: String,
+    pub error_class: ErrorClass,
+    pub exit_code: i32,
+    pub restartable: bool,
+    pub error: String,
+    /// Unix time in seconds
+    pub failed_at: u64,
+    /// Ids of the rounds in flight, e.g. `dkg_id` and `sign_id`
+    pub rounds: BTreeMap<String, u64>,
+    /// Last heights processed, e.g. `burn_block_height`
+    pub heights: BTreeMap<String, u64>,
+}
+
+impl FailureReport {
+    pub fn new(program: &str, error_class: ErrorClass, error: &impl fmt::Display) -> Self {
+        Self {
+            program: program.to_string(),
+            error_class,
+            exit_code: error_class.exit_code(),
+            restartable: error_class.restartable(),
+            error: error.to_string(),
+            failed_at: now(),
+            rounds: BTreeMap::new(),
+            heights: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_round(mut self, name: &str, id: u64) -> Self {
+        self.rounds.insert(name.to_string(), id);
+        self
+    }
+
+    pub fn with_height(mut self, name: &str, height: u64) -> Self {
+        self.heights.insert(name.to_string(), height);
+        self
+    }
+
+    /// Replace the file at `path` with the report as JSON
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        // renamed into place, so a reader never sees half a report
+        let tmp = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Log the report, write it to `path` if one is configured, and exit with its code
+    pub fn exit(self, path: Option<&str>) -> ! {
+        error!(
+            "{} stopped on a {} error, exiting with {}: {}",
+            self.program, self.error_class, self.exit_code, self.error
+        );
+        if let Some(path) = path {
+            if let Err(e) = self.write(path) {
+                warn!("Failed to write the failure report to {}: {}", path, e);
+            }
+        }
+        std::process::exit(self.exit_code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_are_written_as_json() {
+        let dir = std::env::temp_dir().join(format!("failure-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("failure.json");
+        let report = FailureReport::new("stacks-signer", ErrorClass::Unavailable, &"relay down")
+            .with_round("dkg_id", 3)
+            .with_height("burn_block_height", 812);
+        report.write(&path).unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(json["error_class"], "unavailable");
+        assert_eq!(json["exit_code"], 69);
+        assert_eq!(json["restartable"], true);
+        assert_eq!(json["error"], "relay down");
+        assert_eq!(json["rounds"]["dkg_id"], 3);
+        assert_eq!(json["heights"]["burn_block_height"], 812);
+        assert!(!dir.join("failure.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,454 @@
+//! The signing rounds of every wallet generation a signer serves at once.
+//!
+//! Each successful DKG produces a new generation of keys. While the wallet is re-keyed the old
+//! generation still has to sign the handoff spends, so a signer keeps one [`SigningRound`] per
+//! generation, keyed by the DKG id which produced it, and routes each message to the round of
+//! the DKG id it names. Messages without one, or naming a DKG id no live round ran, go to the
+//! newest round.
+//!
+//! A `DkgBegin` for a new DKG id starts a fresh round once the newest round holds keys, and
+//! otherwise reruns DKG on the newest round, so failed attempts never pile up. Once a new
+//! generation's DKG succeeds, the oldest generations are retired until at most
+//! `max_generations` remain; [`Generations::retire`] retires one early, e.g. after the handoff
+//! confirmed. Retiring a generation drops its keys from the [`crate::key_store`] too, and
+//! [`Generations::restore`] serves the generations whose keys it kept across a restart.
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use tracing::{info, warn};
+
+use crate::key_store::StoredKeys;
+use crate::logging::target::SIGNING_ROUND;
+use crate::signing_round::{
+    DkgStatus, Error as SigningRoundError, GroupKeyView, MessageTypes, RoundSnapshot, SigningRound,
+};
+
+/// Generations served unless configured: the current one and the one it replaces
+pub const DEFAULT_MAX_GENERATIONS: usize = 2;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("No live generation was produced by DKG round #{0}")]
+    UnknownGeneration(u64),
+    #[error("DKG round #{0} produced the newest generation, which cannot be retired")]
+    NewestGeneration(u64),
+}
+
+pub struct Generations {
+    /// Rounds by the id of the DKG they ran last, the newest last
+    rounds: BTreeMap<u64, SigningRound>,
+    /// Builds the round of a new generation
+    new_round: Box<dyn Fn() -> SigningRound + Send>,
+    max_generations: usize,
+}
+
+impl Generations {
+    /// Serve the generations `new_round` builds rounds for, at most `max_generations` at once
+    pub fn new(
+        new_round: impl Fn() -> SigningRound + Send + 'static,
+        max_generations: usize,
+    ) -> Self {
+        let first = new_round();
+        Self {
+            rounds: BTreeMap::from([(first.dkg_id, first)]),
+            new_round: Box::new(new_round),
+            max_generations: max_generations.max(1),
+        }
+    }
+
+    /// Serve the generations of `stored` keys, e.g. as kept before a restart, in place of a
+    /// round holding none
+    pub fn restore(&mut self, stored: Vec<StoredKeys>) {
+        for keys in stored {
+            let mut round = (self.new_round)();
+            if keys.signer_id != round.signer.signer_id {
+                warn!(
+                    target: SIGNING_ROUND,
+                    "ignoring the stored keys of DKG round #{}, they belong to signer #{}",
+                    keys.dkg_id,
+                    keys.signer_id
+                );
+                continue;
+            }
+            let dkg_id = keys.dkg_id;
+            round.restore(keys);
+            self.rounds.insert(dkg_id, round);
+        }
+        if self.rounds.values().any(holds_keys) {
+            self.rounds.retain(|_, round| holds_keys(round));
+        }
+        self.retire_oldest();
+    }
+
+    /// DKG ids of the live generations, oldest first
+    pub fn generations(&self) -> Vec<u64> {
+        self.rounds.keys().cloned().collect()
+    }
+
+    pub fn round(&self, dkg_id: u64) -> Option<&SigningRound> {
+        self.rounds.get(&dkg_id)
+    }
+
+    /// The round DKG runs on
+    pub fn newest(&self) -> &SigningRound {
+        self.rounds
+            .values()
+            .next_back()
+            .expect("there is always a round")
+    }
+
+    fn newest_mut(&mut self) -> &mut SigningRound {
+        self.rounds
+            .values_mut()
+            .next_back()
+            .expect("there is always a round")
+    }
+
+    /// Snapshot of the newest round, with the group key of the newest generation holding keys
+    pub fn snapshot(&self) -> RoundSnapshot {
+        let mut snapshot = self.newest().snapshot();
+        snapshot.group_key = self.group_key_view();
+        snapshot
+    }
+
+    /// View of the group key of the newest generation holding keys, `None` before any
+    pub fn group_key_view(&self) -> Option<GroupKeyView> {
+        self.rounds
+            .values()
+            .rev()
+            .find_map(SigningRound::group_key_view)
+    }
+
+    /// The message announcing this signer to the coordinator when it starts
+    pub fn registration(&self) -> MessageTypes {
+        self.newest().registration()
+    }
+
+    /// Process `message` on the round of the generation it is for
+    pub fn process(
+        &mut self,
+        message: MessageTypes,
+    ) -> Result<Vec<MessageTypes>, SigningRoundError> {
+        let dkg_id = dkg_id(&message);
+        match &message {
+            MessageTypes::DkgBegin(begin) => self.begin(begin.dkg_id),
+            // a signer joining a generation it holds no keys of gets a round for it
+            MessageTypes::RosterChange(change) if self.newest().joins(change) => {
+                self.begin(change.dkg_id)
+            }
+            _ => {}
+        }
+        let out = match dkg_id.and_then(|dkg_id| self.rounds.get_mut(&dkg_id)) {
+            Some(round) => round.process(message)?,
+            None => self.newest_mut().process(message)?,
+        };
+        if holds_keys(self.newest()) {
+            self.retire_oldest();
+        }
+        Ok(out)
+    }
+
+    /// Fail the DKG round of the newest generation if it timed out at `now`
+    pub fn expire_dkg(&mut self, now: Instant) -> Vec<MessageTypes> {
+        self.newest_mut().expire_dkg(now)
+    }
+
+    /// Ask for the pieces the DKG round of the newest generation is missing, if it stalled
+    pub fn missing_share_requests(&mut self) -> Vec<MessageTypes> {
+        self.newest_mut().missing_share_requests()
+    }
+
+    /// Retire the generation produced by DKG round `dkg_id`, dropping its keys
+    pub fn retire(&mut self, dkg_id: u64) -> Result<(), Error> {
+        if !self.rounds.contains_key(&dkg_id) {
+            return Err(Error::UnknownGeneration(dkg_id));
+        }
+        if self.newest().dkg_id == dkg_id {
+            return Err(Error::NewestGeneration(dkg_id));
+        }
+        if let Some(round) = self.rounds.remove(&dkg_id) {
+            forget_keys(&round);
+        }
+        info!(target: SIGNING_ROUND, "retired the generation of DKG round #{}", dkg_id);
+        Ok(())
+    }
+
+    /// Make way for DKG round `dkg_id`, keeping the keys of the newest round if it has any
+    fn begin(&mut self, dkg_id: u64) {
+        if self.rounds.contains_key(&dkg_id) {
+            return;
+        }
+        let newest_dkg_id = self.newest().dkg_id;
+        let mut round = if holds_keys(self.newest()) {
+            info!(
+                target: SIGNING_ROUND,
+                "DKG round #{} starts a new generation, still serving {:?}",
+                dkg_id,
+                self.generations()
+            );
+            (self.new_round)()
+        } else {
+            self.rounds
+                .remove(&newest_dkg_id)
+                .expect("there is always a round")
+        };
+        // keyed by the new id from now on, the round resets to it on processing the DkgBegin
+        round.dkg_id = dkg_id;
+        self.rounds.insert(dkg_id, round);
+    }
+
+    fn retire_oldest(&mut self) {
+        while self.rounds.len() > self.max_generations {
+            if let Some((dkg_id, round)) = self.rounds.pop_first() {
+                forget_keys(&round);
+                info!(
+                    target: SIGNING_ROUND,
+                    "retired the generation of DKG round #{}, {} generations are served at most",
+                    dkg_id,
+                    self.max_generations
+                );
+            }
+        }
+    }
+}
+
+/// Drop the stored keys of the retired `round`
+fn forget_keys(round: &SigningRound) {
+    if let Err(e) = round.key_store.remove(round.dkg_id) {
+        warn!(
+            target: SIGNING_ROUND,
+            "failed to drop the stored keys of DKG round #{}: {}", round.dkg_id, e
+        );
+    }
+}
+
+/// Whether `round` completed its last DKG and holds keys from it
+fn holds_keys(round: &SigningRound) -> bool {
+    matches!(
+        round.generations.get(&round.dkg_id),
+        Some(outcome) if outcome.status == DkgStatus::Success
+    )
+}
+
+/// The DKG round `message` is about, if it names one
+fn dkg_id(message: &MessageTypes) -> Option<u64> {
+    match message {
+        MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgQueryResponse(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgPublicShare(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgPrivateShares(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgShareDigests(msg) => Some(msg.dkg_id),
+        MessageTypes::EquivocationReport(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgBlame(msg) => Some(msg.dkg_id),
+        MessageTypes::MissingShareRequest(msg) => Some(msg.dkg_id),
+        MessageTypes::NonceRequest(msg) => Some(msg.dkg_id),
+        MessageTypes::NonceResponse(msg) => Some(msg.dkg_id),
+        MessageTypes::SignShareRequest(msg) => Some(msg.dkg_id),
+        MessageTypes::SignShareResponse(msg) => Some(msg.dkg_id),
+        MessageTypes::BatchNonceRequest(msg) => Some(msg.dkg_id),
+        MessageTypes::BatchNonceResponse(msg) => Some(msg.dkg_id),
+        MessageTypes::BatchSignRequest(msg) => Some(msg.dkg_id),
+        MessageTypes::BatchSignResponse(msg) => Some(msg.dkg_id),
+        MessageTypes::RoundOutcomeQuery(msg) => Some(msg.dkg_id),
+        MessageTypes::RoundOutcomeResponse(msg) => Some(msg.dkg_id),
+        MessageTypes::RoundAbort(msg) => Some(msg.dkg_id),
+        MessageTypes::ReshareBegin(msg) => Some(msg.dkg_id),
+        MessageTypes::ReshareShares(msg) => Some(msg.dkg_id),
+        MessageTypes::ReshareEnd(msg) => Some(msg.dkg_id),
+        MessageTypes::ReshareCommit(msg) => Some(msg.dkg_id),
+        MessageTypes::RosterChange(msg) => Some(msg.dkg_id),
+        MessageTypes::RepairShares(msg) => Some(msg.dkg_id),
+        MessageTypes::RepairedShares(msg) => Some(msg.dkg_id),
+        MessageTypes::RosterChangeEnd(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgGroupKey(msg) => Some(msg.dkg_id),
+        MessageTypes::DkgQuery(_)
+        | MessageTypes::Rejection(_)
+        | MessageTypes::Hello(_)
+        | MessageTypes::UpgradeWindow(_)
+        | MessageTypes::UpgradeAck(_)
+        | MessageTypes::Registration(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_store::KeyStore;
+    use crate::signing_round::{DkgBegin, NonceRequest, SignatureShareRequest};
+    use wtfrost::common::{PublicNonce, Signature};
+    use wtfrost::{compute, Scalar};
+
+    fn signers() -> Vec<Generations> {
+        [vec![0, 1], vec![2]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, key_ids)| {
+                Generations::new(
+                    move || SigningRound::new(2, 3, i as u32 + 1, key_ids.clone()),
+                    2,
+                )
+            })
+            .collect()
+    }
+
+    fn signers_with_key_stores(stores: &[KeyStore]) -> Vec<Generations> {
+        [vec![0, 1], vec![2]]
+            .into_iter()
+            .zip(stores.to_vec())
+            .enumerate()
+            .map(|(i, (key_ids, store))| {
+                let new_round = move || {
+                    SigningRound::new(2, 3, i as u32 + 1, key_ids.clone())
+                        .with_key_store(store.clone())
+                };
+                Generations::new(new_round, 2)
+            })
+            .collect()
+    }
+
+    /// Feed every message to every signer until none are left, like the relay does
+    fn broadcast(signers: &mut [Generations], msgs: Vec<MessageTypes>) {
+        let mut msgs = msgs;
+        while !msgs.is_empty() {
+            let mut out = vec![];
+            for msg in msgs {
+                for signer in signers.iter_mut() {
+                    out.extend(signer.process(msg.clone()).unwrap());
+                }
+            }
+            msgs = out;
+        }
+    }
+
+    fn run_dkg(signers: &mut [Generations], dkg_id: u64) {
+        let begin = DkgBegin {
+            dkg_id,
+            excluded_key_ids: vec![],
+        };
+        broadcast(signers, vec![MessageTypes::DkgBegin(begin.clone())]);
+        broadcast(signers, vec![MessageTypes::DkgPrivateBegin(begin)]);
+    }
+
+    fn group_key(signer: &Generations, dkg_id: u64) -> wtfrost::Point {
+        let round = signer.round(dkg_id).unwrap();
+        round.generations[&dkg_id].group_key.unwrap()
+    }
+
+    #[test]
+    fn serves_the_previous_generation_until_a_third_succeeds() {
+        let mut signers = signers();
+        run_dkg(&mut signers, 1);
+        run_dkg(&mut signers, 2);
+        assert_eq!(signers[0].generations(), vec![1, 2]);
+        assert_ne!(group_key(&signers[0], 1), group_key(&signers[0], 2));
+        assert_eq!(group_key(&signers[0], 1), group_key(&signers[1], 1));
+
+        // requests are answered by the round of the generation they name
+        let nonce_request = |dkg_id| {
+            MessageTypes::NonceRequest(NonceRequest {
+                dkg_id,
+                sign_id: 1,
+                correlation_id: 0,
+                sign_nonce_id: 1,
+            })
+        };
+        for dkg_id in [1, 2] {
+            let out = signers[0].process(nonce_request(dkg_id)).unwrap();
+            assert_eq!(out.len(), 2);
+            assert!(out.iter().all(
+                |msg| matches!(msg, MessageTypes::NonceResponse(response) if response.dkg_id == dkg_id)
+            ));
+        }
+
+        run_dkg(&mut signers, 3);
+        assert_eq!(signers[0].generations(), vec![2, 3]);
+        assert_eq!(signers[1].generations(), vec![2, 3]);
+    }
+
+    #[test]
+    fn stored_keys_sign_after_a_restart() {
+        let dir = std::env::temp_dir().join(format!("generations-keys-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let open = || -> Vec<KeyStore> {
+            (1..=2)
+                .map(|i| KeyStore::open(dir.join(format!("keys-{i}.json")), "network key").unwrap())
+                .collect()
+        };
+        let stores = open();
+        let mut signers = signers_with_key_stores(&stores);
+        for dkg_id in 1..=3 {
+            run_dkg(&mut signers, dkg_id);
+        }
+        // retired generations are dropped from the stores too
+        assert_eq!(stores[0].generations(), vec![2, 3]);
+        let views: Vec<_> = signers.iter().map(Generations::group_key_view).collect();
+        let group_key = group_key(&signers[0], 2);
+        drop(signers);
+
+        let stores = open();
+        let mut signers = signers_with_key_stores(&stores);
+        for (signer, store) in signers.iter_mut().zip(&stores) {
+            signer.restore(store.load().unwrap());
+            assert_eq!(signer.generations(), vec![2, 3]);
+        }
+        let restored: Vec<_> = signers.iter().map(Generations::group_key_view).collect();
+        assert_eq!(restored, views);
+
+        // the generation before the newest still signs
+        let mut nonces: Vec<(u32, PublicNonce)> = vec![];
+        for signer in signers.iter_mut() {
+            let request = MessageTypes::NonceRequest(NonceRequest {
+                dkg_id: 2,
+                sign_id: 1,
+                correlation_id: 0,
+                sign_nonce_id: 1,
+            });
+            for msg in signer.process(request).unwrap() {
+                if let MessageTypes::NonceResponse(response) = msg {
+                    nonces.push((response.key_id, response.nonce));
+                }
+            }
+        }
+        let message = [9; 32];
+        let mut z = Scalar::default();
+        for (key_id, signer_id) in [(0, 1), (1, 1), (2, 2)] {
+            let request = MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 2,
+                sign_id: 1,
+                correlation_id: 0,
+                signer_id,
+                key_id,
+                nonces: nonces.clone(),
+                message: message.to_vec(),
+                key_path: None,
+            });
+            for msg in signers[signer_id as usize - 1].process(request).unwrap() {
+                if let MessageTypes::SignShareResponse(response) = msg {
+                    z += response.signature_share.z_i;
+                }
+            }
+        }
+        let ids: Vec<usize> = nonces.iter().map(|(id, _)| *id as usize).collect();
+        let public_nonces: Vec<PublicNonce> = nonces.into_iter().map(|(_, n)| n).collect();
+        let (_, aggregate_nonce) = compute::intermediate(&message, &ids, &public_nonces);
+        let signature = Signature {
+            R: aggregate_nonce,
+            z,
+        };
+        assert!(signature.verify(&group_key, &message));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_older_generations_can_be_retired() {
+        let mut signers = signers();
+        run_dkg(&mut signers, 1);
+        run_dkg(&mut signers, 2);
+        let signer = &mut signers[0];
+        assert_eq!(signer.retire(2), Err(Error::NewestGeneration(2)));
+        assert_eq!(signer.retire(7), Err(Error::UnknownGeneration(7)));
+        signer.retire(1).unwrap();
+        assert_eq!(signer.generations(), vec![2]);
+    }
+}
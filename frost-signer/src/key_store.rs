@@ -0,0 +1,258 @@
+//! Key shares kept across restarts.
+//!
+//! The shares of the group key a signer computes in DKG only live in its parties, so a signer
+//! restarting between DKG and signing could no longer produce signature shares. With
+//! `key_store_path` set, the party state of every generation whose DKG succeeded is written to
+//! that file, encrypted under a key derived from the signer's network private key, and reloaded
+//! at startup. A generation's keys are removed from the file once it is retired.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use wtfrost::{v1, Point};
+
+use crate::protocol::{tag, Hasher};
+use crate::secret::Secret;
+
+const IV_LEN: usize = 12;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Key store I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Key store is not valid JSON: {0}")]
+    Corrupt(#[from] serde_json::Error),
+    #[error("Failed to encode key shares: {0}")]
+    Encoding(#[from] bincode::Error),
+    #[error("Stored keys of DKG round #{0} do not decrypt, was the network key changed?")]
+    Decrypt(u64),
+}
+
+/// What a signer needs to sign with the keys of one generation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredKeys {
+    /// The DKG round which produced the keys
+    pub dkg_id: u64,
+    pub signer_id: u32,
+    pub group_key: Point,
+    /// Signer id owning each key id of the round
+    pub key_owners: BTreeMap<u32, u32>,
+    /// Key ids excluded from the round
+    pub excluded_key_ids: BTreeSet<u32>,
+    /// The parties with their secret shares
+    pub frost_signer: v1::Signer,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    dkg_id: u64,
+    signer_id: u32,
+    /// Hex of the cipher's 12-byte nonce
+    iv: String,
+    /// Hex of the encrypted keys
+    ciphertext: String,
+}
+
+impl Entry {
+    /// Binds the ciphertext to the generation and signer it was stored for
+    fn aad(&self) -> Vec<u8> {
+        let mut aad = vec![];
+        aad.extend_from_slice(&self.dkg_id.to_be_bytes());
+        aad.extend_from_slice(&self.signer_id.to_be_bytes());
+        aad
+    }
+}
+
+struct Inner {
+    path: PathBuf,
+    key: Secret<Vec<u8>>,
+    entries: Vec<Entry>,
+}
+
+/// Key shares on disk, shared by the rounds of every generation.
+/// The default store keeps nothing.
+#[derive(Clone, Default)]
+pub struct KeyStore(Option<Arc<Mutex<Inner>>>);
+
+impl KeyStore {
+    /// Store keys at `path`, encrypted under a key derived from `network_private_key`
+    pub fn open(path: impl AsRef<Path>, network_private_key: &str) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let mut hasher = Hasher::new();
+        hasher.update(tag::KEY_STORE_KEY.as_bytes());
+        hasher.update(network_private_key.as_bytes());
+        Ok(Self(Some(Arc::new(Mutex::new(Inner {
+            path,
+            key: Secret::new(hasher.finalize().to_vec()),
+            entries,
+        })))))
+    }
+
+    /// Keep `keys`, replacing any stored for the same DKG round
+    pub fn put(&self, keys: &StoredKeys) -> Result<(), Error> {
+        let Some(inner) = &self.0 else {
+            return Ok(());
+        };
+        let mut inner = inner.lock().expect("key store lock poisoned");
+        let mut iv = [0u8; IV_LEN];
+        OsRng.fill_bytes(&mut iv);
+        let mut entry = Entry {
+            dkg_id: keys.dkg_id,
+            signer_id: keys.signer_id,
+            iv: hex::encode(iv),
+            ciphertext: String::new(),
+        };
+        let plaintext = Secret::new(bincode::serialize(keys)?);
+        let ciphertext = inner
+            .cipher()
+            .encrypt(
+                (&iv).into(),
+                Payload {
+                    msg: plaintext.as_slice(),
+                    aad: &entry.aad(),
+                },
+            )
+            .expect("encrypting to a vec does not fail");
+        entry.ciphertext = hex::encode(ciphertext);
+        inner.entries.retain(|stored| stored.dkg_id != keys.dkg_id);
+        inner.entries.push(entry);
+        inner.entries.sort_by_key(|stored| stored.dkg_id);
+        inner.write()
+    }
+
+    /// The stored keys of every generation, oldest first
+    pub fn load(&self) -> Result<Vec<StoredKeys>, Error> {
+        let Some(inner) = &self.0 else {
+            return Ok(vec![]);
+        };
+        let inner = inner.lock().expect("key store lock poisoned");
+        inner
+            .entries
+            .iter()
+            .map(|entry| {
+                let decrypt = || -> Option<Secret<Vec<u8>>> {
+                    let iv: [u8; IV_LEN] = hex::decode(&entry.iv).ok()?.try_into().ok()?;
+                    let ciphertext = hex::decode(&entry.ciphertext).ok()?;
+                    let plaintext = inner
+                        .cipher()
+                        .decrypt(
+                            (&iv).into(),
+                            Payload {
+                                msg: &ciphertext,
+                                aad: &entry.aad(),
+                            },
+                        )
+                        .ok()?;
+                    Some(Secret::new(plaintext))
+                };
+                let plaintext = decrypt().ok_or(Error::Decrypt(entry.dkg_id))?;
+                Ok(bincode::deserialize(&plaintext)?)
+            })
+            .collect()
+    }
+
+    /// Drop the keys of DKG round `dkg_id`, e.g. once its generation is retired
+    pub fn remove(&self, dkg_id: u64) -> Result<(), Error> {
+        let Some(inner) = &self.0 else {
+            return Ok(());
+        };
+        let mut inner = inner.lock().expect("key store lock poisoned");
+        let stored = inner.entries.len();
+        inner.entries.retain(|entry| entry.dkg_id != dkg_id);
+        if inner.entries.len() == stored {
+            return Ok(());
+        }
+        inner.write()
+    }
+
+    /// DKG ids of the generations stored, oldest first
+    pub fn generations(&self) -> Vec<u64> {
+        self.0.as_ref().map_or(vec![], |inner| {
+            let inner = inner.lock().expect("key store lock poisoned");
+            inner.entries.iter().map(|entry| entry.dkg_id).collect()
+        })
+    }
+}
+
+impl Inner {
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new_from_slice(&self.key).expect("the key is 32 bytes")
+    }
+
+    /// Replace the file, readable by its owner only
+    fn write(&self) -> Result<(), Error> {
+        let tmp = self.path.with_extension("tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp)?;
+        file.write_all(&serde_json::to_vec(&self.entries)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(dkg_id: u64) -> StoredKeys {
+        let frost_signer = v1::Signer::new(&[0, 1], 4, 3, &mut OsRng);
+        StoredKeys {
+            dkg_id,
+            signer_id: 1,
+            group_key: Point::default(),
+            key_owners: BTreeMap::from([(2, 2), (3, 2)]),
+            excluded_key_ids: BTreeSet::new(),
+            frost_signer,
+        }
+    }
+
+    #[test]
+    fn keys_survive_a_reopen_encrypted() {
+        let dir = std::env::temp_dir().join(format!("key-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keys.json");
+        let _ = fs::remove_file(&path);
+
+        let store = KeyStore::open(&path, "network key").unwrap();
+        let second = keys(2);
+        store.put(&second).unwrap();
+        store.put(&keys(1)).unwrap();
+        store.put(&second).unwrap();
+        drop(store);
+        let file = fs::read_to_string(&path).unwrap();
+        let polynomial = &second.frost_signer.parties[0].save().polynomial[0];
+        assert!(!file.contains(&hex::encode(polynomial.to_bytes())));
+
+        let store = KeyStore::open(&path, "network key").unwrap();
+        assert_eq!(store.generations(), vec![1, 2]);
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded[1].key_owners, second.key_owners);
+        assert_eq!(
+            loaded[1].frost_signer.parties[0].save(),
+            second.frost_signer.parties[0].save()
+        );
+        store.remove(1).unwrap();
+        assert_eq!(store.generations(), vec![2]);
+
+        let other_key = KeyStore::open(&path, "other key").unwrap();
+        assert!(matches!(other_key.load(), Err(Error::Decrypt(2))));
+        assert!(KeyStore::default().load().unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+//! Lagrange coefficients reused across signing rounds.
+//!
+//! A party's signature share weights its private key by the Lagrange coefficient of its id
+//! over the set of parties taking part, which costs one field inversion per other party.
+//! Peg-out batches sign many messages with the same party set, so the coefficients are
+//! computed once per set and kept until the next DKG round replaces the key shares.
+use hashbrown::HashMap;
+use wtfrost::{
+    common::{Nonce, PublicNonce},
+    compute,
+    v1::{self, SignatureShare},
+    Scalar,
+};
+
+use crate::secret::Secret;
+use crate::taproot::TapTweak;
+
+/// Distinct party sets remembered before the cache starts over
+const MAX_PARTY_SETS: usize = 64;
+
+#[derive(Debug, Default)]
+pub struct LagrangeCache {
+    /// Coefficient of each party id, keyed by the sorted ids of the signing parties
+    coefficients: HashMap<Vec<usize>, HashMap<usize, Scalar>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl LagrangeCache {
+    /// Lagrange coefficient of `party_id` when signing with `signers`
+    pub fn coefficient(&mut self, party_id: usize, signers: &[usize]) -> Scalar {
+        let mut key = signers.to_vec();
+        key.sort_unstable();
+        key.dedup();
+        if let Some(lambda) = self
+            .coefficients
+            .get(&key)
+            .and_then(|set| set.get(&party_id))
+        {
+            self.hits += 1;
+            return *lambda;
+        }
+        self.misses += 1;
+        if !self.coefficients.contains_key(&key) && self.coefficients.len() >= MAX_PARTY_SETS {
+            self.coefficients.clear();
+        }
+        let lambda = compute::lambda(party_id, &key);
+        self.coefficients
+            .entry(key)
+            .or_default()
+            .insert(party_id, lambda);
+        lambda
+    }
+
+    /// Forget every coefficient, e.g. once a DKG round starts a new key generation
+    pub fn clear(&mut self) {
+        self.coefficients.clear();
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+/// Same share as [`v1::Party::sign`], weighted by a precomputed coefficient `lambda`.
+/// Returns `None` if the party holds no secret nonce.
+pub fn sign(
+    party: &v1::Party,
+    lambda: &Scalar,
+    msg: &[u8],
+    signers: &[usize],
+    nonces: &[PublicNonce],
+) -> Option<SignatureShare> {
+    let state = Secret::new(party.save());
+    let nonce = Secret::new(state.nonce.clone()?);
+    Some(sign_with_nonce(party, &nonce, lambda, msg, signers, nonces))
+}
+
+/// The same, with the secret `nonce` the party handed out before, e.g. one reloaded from the
+/// [`crate::nonce_store`] after a restart replaced the party's own
+pub fn sign_with_nonce(
+    party: &v1::Party,
+    nonce: &Nonce,
+    lambda: &Scalar,
+    msg: &[u8],
+    signers: &[usize],
+    nonces: &[PublicNonce],
+) -> SignatureShare {
+    sign_for(party, nonce, lambda, msg, signers, nonces, None)
+}
+
+/// The same, signing a key path spend of the output `tweak` tweaks the group key for, see
+/// [`crate::taproot`]
+pub fn sign_key_path(
+    party: &v1::Party,
+    nonce: &Nonce,
+    lambda: &Scalar,
+    msg: &[u8],
+    signers: &[usize],
+    nonces: &[PublicNonce],
+    tweak: &TapTweak,
+) -> SignatureShare {
+    sign_for(party, nonce, lambda, msg, signers, nonces, Some(tweak))
+}
+
+fn sign_for(
+    party: &v1::Party,
+    nonce: &Nonce,
+    lambda: &Scalar,
+    msg: &[u8],
+    signers: &[usize],
+    nonces: &[PublicNonce],
+    tweak: Option<&TapTweak>,
+) -> SignatureShare {
+    let state = Secret::new(party.save());
+    let (_, group_nonce) = compute::intermediate(msg, signers, nonces);
+    let (key, key_factor) = match tweak {
+        Some(tweak) => (tweak.output_key, tweak.key_factor()),
+        None => (party.group_key, Scalar::from(1)),
+    };
+    let c = compute::challenge(&key, &group_nonce, msg);
+    let z_i = nonce.d
+        + nonce.e * compute::binding(&party.id(), nonces, msg)
+        + *lambda * key_factor * state.private_key * c;
+    SignatureShare {
+        id: party.id,
+        z_i,
+        public_key: party.public_key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+
+    #[test]
+    fn shares_match_uncached_signing() {
+        let mut rng = OsRng;
+        let mut parties: Vec<v1::Party> =
+            (0..4).map(|i| v1::Party::new(i, 4, 3, &mut rng)).collect();
+        let signers = vec![2, 0, 3];
+        let nonces: Vec<PublicNonce> = signers
+            .iter()
+            .map(|&i| parties[i].gen_nonce(&mut rng))
+            .collect();
+
+        let mut cache = LagrangeCache::default();
+        for _ in 0..2 {
+            for &i in &signers {
+                let lambda = cache.coefficient(i, &signers);
+                let share = sign(&parties[i], &lambda, b"msg", &signers, &nonces).unwrap();
+                assert_eq!(share, parties[i].sign(b"msg", &signers, &nonces));
+            }
+        }
+        // party order does not matter to the coefficients
+        cache.coefficient(0, &[3, 2, 0]);
+        assert_eq!(cache.misses(), 3);
+        assert_eq!(cache.hits(), 4);
+
+        cache.clear();
+        cache.coefficient(0, &signers);
+        assert_eq!(cache.misses(), 4);
+    }
+}
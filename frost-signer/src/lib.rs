@@ -1,9 +1,39 @@
+pub mod auth;
+pub mod bus;
+pub mod ceremony;
 pub mod config;
+pub mod control;
+pub mod digest;
+pub mod failure;
+pub mod generations;
+pub mod key_store;
+pub mod lagrange;
+pub mod lint;
 pub mod logging;
+pub mod middleware;
 pub mod net;
+pub mod nonce_store;
+pub mod pacing;
+pub mod preimage;
+pub mod protocol;
+pub mod proxy;
+pub mod redact;
+pub mod reshare;
+pub mod retention;
+pub mod retry;
+pub mod roster;
+pub mod secret;
+pub mod sessions;
+pub mod share_cipher;
 pub mod signer;
 pub mod signing_round;
+pub mod stackerdb;
 pub mod state_machine;
+pub mod status;
+pub mod supervisor;
+pub mod taproot;
+pub mod thresholds;
+pub mod traffic;
 pub mod util;
 
 // set via _compile-time_ envars
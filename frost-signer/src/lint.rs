@@ -0,0 +1,446 @@
+//! Schema checks for config files which point at the offending key.
+//!
+//! Deserialization ignores misspelled optional keys and stops at the first error. A [`Lint`]
+//! collects every problem it finds, each with its key path and, where it can be found, its line.
+use std::fmt;
+use std::net::SocketAddr;
+
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+
+use crate::control::ControlConfig;
+use crate::proxy::ProxyConfig;
+use crate::retry::RetryPolicy;
+
+/// One problem with a config file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// Dotted path of the offending key, empty if the problem is not tied to one
+    pub key: String,
+    /// 1-based line of the key, if it appears in the file
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Every problem found in one config file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub file: String,
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "{}: ok", self.file);
+        }
+        for (i, finding) in self.findings.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", self.file)?;
+            if let Some(line) = finding.line {
+                write!(f, ":{line}")?;
+            }
+            if !finding.key.is_empty() {
+                write!(f, ": {}", finding.key)?;
+            }
+            write!(f, ": {}", finding.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects findings for one config file
+pub struct Lint<'a> {
+    source: &'a str,
+    /// Key of the table linted, empty for the whole file
+    scope: String,
+    findings: Vec<Finding>,
+}
+
+impl<'a> Lint<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            scope: String::new(),
+            findings: vec![],
+        }
+    }
+
+    /// Lint the table at `scope` of the file instead, reporting keys with their full path
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        self.scope = scope.to_string();
+        self
+    }
+
+    pub fn report(&mut self, key: &str, message: impl Into<String>) {
+        let key = join(&self.scope, key);
+        self.findings.push(Finding {
+            line: self.line_of(&key),
+            key,
+            message: message.into(),
+        });
+    }
+
+    pub fn finish(self, file: impl Into<String>) -> Report {
+        Report {
+            file: file.into(),
+            findings: self.findings,
+        }
+    }
+
+    /// Parse the file, or the table in scope, as a TOML table, reporting syntax errors
+    pub fn table(&mut self) -> Option<Table> {
+        let table = self.parse_file::<Table>()?;
+        if self.scope.is_empty() {
+            return Some(table);
+        }
+        match table.get(&self.scope) {
+            Some(Value::Table(inner)) => Some(inner.clone()),
+            _ => {
+                self.report("", "missing table");
+                None
+            }
+        }
+    }
+
+    /// Deserialize the file, or the table in scope, reporting missing keys and values of the
+    /// wrong type
+    pub fn parse<T: DeserializeOwned>(&mut self) -> Option<T> {
+        if self.scope.is_empty() {
+            return self.parse_file();
+        }
+        // values of a table have no span left, so findings point at the table
+        match self.table()?.try_into() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.report("", e.message().to_string());
+                None
+            }
+        }
+    }
+
+    fn parse_file<T: DeserializeOwned>(&mut self) -> Option<T> {
+        match toml::from_str(self.source) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                let (key, line) = match e.span() {
+                    Some(span) => self.key_at(span.start),
+                    None => (String::new(), None),
+                };
+                self.findings.push(Finding {
+                    key,
+                    line,
+                    message: e.message().to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    /// Report keys of `table`, found at `prefix`, which are not in `known`
+    pub fn unknown_keys(&mut self, table: &Table, prefix: &str, known: &[&str]) {
+        for key in table.keys() {
+            if known.contains(&key.as_str()) {
+                continue;
+            }
+            let message = match closest(key, known) {
+                Some(suggestion) => format!("unknown key, did you mean `{suggestion}`?"),
+                None => format!("unknown key, expected one of {}", known.join(", ")),
+            };
+            self.report(&join(prefix, key), message);
+        }
+    }
+
+    /// Report unknown keys of the table at `key`, if present
+    pub fn unknown_keys_in(&mut self, table: &Table, key: &str, known: &[&str]) {
+        if let Some(Value::Table(inner)) = table.get(key) {
+            self.unknown_keys(inner, key, known);
+        }
+    }
+
+    /// Check that `value` is an http or https URL
+    pub fn url(&mut self, key: &str, value: &str) {
+        match url::Url::parse(value) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(url) => self.report(
+                key,
+                format!("unsupported scheme `{}`, use http or https", url.scheme()),
+            ),
+            Err(e) => self.report(
+                key,
+                format!("`{value}` is not a URL ({e}), e.g. \"http://localhost:9776\""),
+            ),
+        }
+    }
+
+    /// Check that `value` is an `ip:port` socket address
+    pub fn socket_addr(&mut self, key: &str, value: &str) {
+        if value.parse::<SocketAddr>().is_err() {
+            self.report(
+                key,
+                format!("`{value}` is not an address, e.g. \"127.0.0.1:9900\""),
+            );
+        }
+    }
+
+    /// Check that `value` lies within `min..=max`
+    pub fn range<T: PartialOrd + fmt::Display>(&mut self, key: &str, value: T, min: T, max: T) {
+        if value < min || value > max {
+            self.report(key, format!("{value} is out of range {min}..={max}"));
+        }
+    }
+
+    /// Check the keys of a `[retry]` table and the policy read from it
+    pub fn retry(&mut self, table: &Table, key: &str, policy: &RetryPolicy) {
+        let Some(Value::Table(retry)) = table.get(key) else {
+            return;
+        };
+        self.unknown_keys(retry, key, &["max_attempts", "backoff"]);
+        if let Some(Value::Table(backoff)) = retry.get("backoff") {
+            let known: &[&str] = match backoff.get("kind").and_then(Value::as_str) {
+                Some("fixed") => &["kind", "interval_ms"],
+                _ => &["kind", "initial_ms", "max_ms", "multiplier"],
+            };
+            self.unknown_keys(backoff, &join(key, "backoff"), known);
+        }
+        if policy.max_attempts == 0 {
+            self.report(
+                &join(key, "max_attempts"),
+                "must be at least 1, the first attempt counts",
+            );
+        }
+    }
+
+    /// Check the keys of a `[control]` table, see [`crate::control`]
+    pub fn control(&mut self, table: &Table, key: &str, control: &ControlConfig) {
+        let Some(Value::Table(listeners)) = table.get(key) else {
+            return;
+        };
+        self.unknown_keys(
+            listeners,
+            key,
+            &["listen_addr", "socket_path", "socket_mode", "token"],
+        );
+        if let Some(addr) = &control.listen_addr {
+            self.socket_addr(&join(key, "listen_addr"), addr);
+        }
+        if control.socket_mode > 0o777 {
+            self.report(
+                &join(key, "socket_mode"),
+                format!("{:#o} is not a file mode", control.socket_mode),
+            );
+        }
+        if control.token().is_some_and(|token| token.trim().is_empty()) {
+            self.report(&join(key, "token"), "must not be empty");
+        }
+    }
+
+    /// Check the keys and URLs of a `[proxy]` table, see [`crate::proxy`]
+    pub fn proxy(&mut self, table: &Table, key: &str, proxy: &ProxyConfig) {
+        let Some(Value::Table(proxy_table)) = table.get(key) else {
+            return;
+        };
+        self.unknown_keys(proxy_table, key, &["url", "overrides"]);
+        if let Err((proxy_key, e)) = proxy.validate() {
+            self.report(&join(key, &proxy_key), e.to_string());
+        }
+    }
+
+    /// Check the keys of a `[retention]` table limiting the artifact types in `artifacts`
+    pub fn retention(&mut self, table: &Table, key: &str, artifacts: &[&str]) {
+        let Some(Value::Table(retention)) = table.get(key) else {
+            return;
+        };
+        let known: Vec<&str> = ["interval_secs"].iter().chain(artifacts).copied().collect();
+        self.unknown_keys(retention, key, &known);
+        for artifact in artifacts {
+            if let Some(Value::Table(limits)) = retention.get(*artifact) {
+                self.unknown_keys(limits, &join(key, artifact), &["max_age_secs", "max_bytes"]);
+            }
+        }
+        if retention.get("interval_secs").and_then(Value::as_integer) == Some(0) {
+            self.report(&join(key, "interval_secs"), "must be at least 1");
+        }
+    }
+
+    /// 1-based line where `key` is set, ignoring any `[index]` suffix
+    fn line_of(&self, key: &str) -> Option<usize> {
+        let key = key.split('[').next().unwrap_or(key);
+        let mut table = String::new();
+        let mut enclosing = None;
+        for (i, line) in self.source.lines().enumerate() {
+            let line = line.trim();
+            if let Some(header) = header(line) {
+                table = header;
+                if table == key {
+                    return Some(i + 1);
+                }
+                continue;
+            }
+            let Some((name, _)) = line.split_once('=') else {
+                continue;
+            };
+            let path = join(&table, name.trim().trim_matches('"'));
+            if path == key {
+                return Some(i + 1);
+            }
+            // keys of inline tables are reported on the line of the table
+            if enclosing.is_none() && key.starts_with(&format!("{path}.")) {
+                enclosing = Some(i + 1);
+            }
+        }
+        enclosing
+    }
+
+    /// Key path and 1-based line of the byte at `offset`
+    fn key_at(&self, offset: usize) -> (String, Option<usize>) {
+        let mut table = String::new();
+        let mut start = 0;
+        for (i, line) in self.source.split_inclusive('\n').enumerate() {
+            let end = start + line.len();
+            let trimmed = line.trim();
+            if offset < end {
+                let key = match trimmed.split_once('=') {
+                    Some((name, _)) if header(trimmed).is_none() => {
+                        join(&table, name.trim().trim_matches('"'))
+                    }
+                    _ => table,
+                };
+                return (key, Some(i + 1));
+            }
+            if let Some(header) = header(trimmed) {
+                table = header;
+            }
+            start = end;
+        }
+        (table, None)
+    }
+}
+
+fn header(line: &str) -> Option<String> {
+    let name = line.strip_prefix('[')?.split(']').next()?;
+    Some(name.trim_start_matches('[').trim().to_string())
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else if key.is_empty() {
+        prefix.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// The known key within two edits of `key`, if any
+fn closest<'k>(key: &str, known: &[&'k str]) -> Option<&'k str> {
+    known
+        .iter()
+        .map(|candidate| (distance(key, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1)
+                .min(row[j] + 1)
+                .min(diagonal + usize::from(ca != *cb));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"name = "x"
+policy = { kind = "fixed" }
+
+[retry]
+max_attempts = 0
+"#;
+
+    #[test]
+    fn locates_keys() {
+        let lint = Lint::new(SOURCE);
+        assert_eq!(lint.line_of("name"), Some(1));
+        assert_eq!(lint.line_of("policy.kind"), Some(2));
+        assert_eq!(lint.line_of("retry"), Some(4));
+        assert_eq!(lint.line_of("retry.max_attempts"), Some(5));
+        assert_eq!(lint.line_of("missing"), None);
+        let offset = SOURCE.find("0\n").unwrap();
+        assert_eq!(
+            lint.key_at(offset),
+            ("retry.max_attempts".to_string(), Some(5))
+        );
+    }
+
+    #[test]
+    fn scoped_lints_report_full_key_paths() {
+        #[derive(serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Named {
+            name: u32,
+        }
+        let source = "[frost]\nnmae = 1\n\n[stacks]\nname = 2\n";
+        let mut lint = Lint::new(source).with_scope("frost");
+        let table = lint.table().unwrap();
+        lint.unknown_keys(&table, "", &["name"]);
+        assert!(lint.parse::<Named>().is_none());
+        let report = lint.finish("f");
+        assert_eq!(report.findings[0].key, "frost.nmae");
+        assert_eq!(report.findings[0].line, Some(2));
+        assert_eq!(report.findings[1].key, "frost");
+        assert_eq!(report.findings[1].line, Some(1));
+
+        let mut lint = Lint::new(source).with_scope("proxy");
+        assert!(lint.table().is_none());
+        assert_eq!(lint.finish("f").findings[0].key, "proxy");
+    }
+
+    #[test]
+    fn suggests_close_keys() {
+        let mut lint = Lint::new("nmae = 1\nother = 2\n");
+        let table = lint.table().unwrap();
+        lint.unknown_keys(&table, "", &["name"]);
+        let report = lint.finish("test.toml");
+        assert_eq!(
+            report.to_string(),
+            "test.toml:1: nmae: unknown key, did you mean `name`?\n\
+             test.toml:2: other: unknown key, expected one of name"
+        );
+    }
+
+    #[test]
+    fn reports_position_of_type_errors() {
+        #[derive(serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Counts {
+            total: usize,
+        }
+        let mut lint = Lint::new("\ntotal = \"three\"\n");
+        assert!(lint.parse::<Counts>().is_none());
+        let report = lint.finish("test.toml");
+        assert_eq!(report.findings[0].key, "total");
+        assert_eq!(report.findings[0].line, Some(2));
+    }
+}
@@ -1,9 +1,166 @@
+//! Tracing setup, with a log filter which can be changed while the process runs.
+//!
+//! The protocol modules log under the short targets in [`target`] rather than their module
+//! paths, so that filter directives such as `info,signing_round=trace` keep working as code
+//! moves between crates. [`LogControl`] is a [`crate::control`] route which lets operators
+//! replace the filter over a local endpoint, e.g. to trace a signing round during an incident
+//! without restarting the process and losing its round state:
+//!
+//! ```text
+//! curl http://<log_control_listen_addr>/log
+//! curl -X PUT --data 'info,signing_round=trace' http://<log_control_listen_addr>/log
+//! ```
+use tracing::info;
+use tracing_subscriber::filter::{LevelFilter, ParseError, Targets};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, Registry};
+
+use crate::control::{self, Endpoint, Response, Route};
+
+/// Targets the protocol modules log under
+pub mod target {
+    /// Relay transport, `net`
+    pub const NET: &str = "net";
+    /// Signer side of DKG and signing rounds, `signing_round`
+    pub const SIGNING_ROUND: &str = "signing_round";
+    /// Coordinator side of DKG and signing rounds, and the peg op loop
+    pub const COORDINATOR: &str = "coordinator";
+    /// Stacks and bitcoin transaction building
+    pub const WALLET: &str = "wallet";
+    /// Every protocol message this node signs, with its request hash
+    pub const AUDIT: &str = "audit";
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid filter directives: {0}")]
+    Directives(#[from] ParseError),
+    #[error("Reload Error: {0}")]
+    Reload(#[from] reload::Error),
+}
+
 pub fn initiate_tracing_subscriber(
     level: tracing::Level,
-) -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_max_level(level)
-        .finish();
+) -> Result<LogControl, tracing::subscriber::SetGlobalDefaultError> {
+    let (filter, control) = LogControl::new(level);
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer());
+
+    tracing::subscriber::set_global_default(subscriber)?;
+    Ok(control)
+}
+
+/// Replaces the filter of the global subscriber
+#[derive(Clone, Debug)]
+pub struct LogControl {
+    handle: reload::Handle<Targets, Registry>,
+}
+
+impl LogControl {
+    /// A filter passing events at `level` and above, with the control replacing it
+    fn new(level: tracing::Level) -> (reload::Layer<Targets, Registry>, Self) {
+        let filter = Targets::new().with_default(LevelFilter::from_level(level));
+        let (filter, handle) = reload::Layer::new(filter);
+        (filter, Self { handle })
+    }
+
+    /// The directives events are currently filtered by
+    pub fn directives(&self) -> Result<String, Error> {
+        Ok(self.handle.with_current(|filter| filter.to_string())?)
+    }
+
+    /// Filter events by `directives`, comma separated `target=level` pairs and an optional
+    /// bare default level, e.g. "info,signing_round=trace"
+    pub fn set(&self, directives: &str) -> Result<(), Error> {
+        let filter: Targets = directives.trim().parse()?;
+        self.handle.reload(filter)?;
+        info!("log filter set to {}", directives.trim());
+        Ok(())
+    }
+}
+
+impl Route for LogControl {
+    fn respond(&self, method: &str, path: &str, body: &str) -> Option<Response> {
+        let result = match (method, path) {
+            ("GET", "/log") => self.directives(),
+            ("PUT", "/log") => match self.set(body) {
+                Ok(()) => self.directives(),
+                Err(e) => return Some(Response::text("400 Bad Request", format!("{e}\n"))),
+            },
+            _ => return None,
+        };
+        Some(match result {
+            Ok(directives) => Response::text("200 OK", format!("{directives}\n")),
+            Err(e) => Response::text("500 Internal Server Error", format!("{e}\n")),
+        })
+    }
+}
+
+/// Ask the log control endpoint at `endpoint` for its directives, replacing them with
+/// `directives` first if given
+pub fn request(
+    endpoint: &Endpoint,
+    token: Option<&str>,
+    directives: Option<&str>,
+) -> std::io::Result<String> {
+    match directives {
+        Some(directives) => control::request(endpoint, token, "PUT", "/log", directives),
+        None => control::request(endpoint, token, "GET", "/log", ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::Server;
+
+    #[test]
+    fn replaces_the_filter_at_runtime() {
+        let (_filter, control) = LogControl::new(tracing::Level::INFO);
+        assert_eq!(control.directives().unwrap(), "info");
+
+        let response = control
+            .respond("PUT", "/log", "info,signing_round=trace\n")
+            .unwrap();
+        assert_eq!(response.status, "200 OK");
+        assert!(
+            response.body.contains("signing_round=trace"),
+            "{}",
+            response.body
+        );
+
+        // invalid directives leave the filter as it was
+        let response = control
+            .respond("PUT", "/log", "signing_round=loud")
+            .unwrap();
+        assert_eq!(response.status, "400 Bad Request");
+        let response = control.respond("GET", "/log", "").unwrap();
+        assert_eq!(response.status, "200 OK");
+        assert!(
+            response.body.contains("signing_round=trace"),
+            "{}",
+            response.body
+        );
+
+        assert!(control.respond("GET", "/status", "").is_none());
+    }
+
+    #[test]
+    fn serves_the_filter_over_tcp() {
+        let (_filter, control) = LogControl::new(tracing::Level::WARN);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        Server::default()
+            .with_route(control)
+            .serve_tcp(addr)
+            .unwrap();
 
-    tracing::subscriber::set_global_default(subscriber)
+        let endpoint = Endpoint::Tcp(addr.to_string());
+        assert_eq!(request(&endpoint, None, None).unwrap(), "warn");
+        let directives = request(&endpoint, None, Some("debug,net=trace")).unwrap();
+        assert!(directives.contains("net=trace"), "{directives}");
+        assert!(request(&endpoint, None, Some("net=loud")).is_err());
+    }
 }
@@ -2,17 +2,25 @@ use clap::Parser;
 use tracing::{info, warn};
 
 use frost_signer::config::{Cli, Config};
+use frost_signer::control::Server as ControlServer;
 use frost_signer::logging;
 use frost_signer::signer::Signer;
 
 fn main() {
-    logging::initiate_tracing_subscriber(tracing::Level::INFO).unwrap();
+    let log_control = logging::initiate_tracing_subscriber(tracing::Level::INFO).unwrap();
 
     let cli = Cli::parse();
 
     match Config::from_path(cli.config.clone()) {
         Ok(config) => {
-            let mut signer = Signer::new(config, cli.id);
+            if let Some(addr) = &config.log_control_listen_addr {
+                let server = ControlServer::new(&config.control).with_route(log_control.clone());
+                if let Err(e) = server.serve_tcp(addr.as_str()) {
+                    warn!("Failed to serve the log filter on {}: {}", addr, e);
+                }
+            }
+            let control = ControlServer::new(&config.control).with_route(log_control);
+            let mut signer = Signer::new(config, cli.id).with_control(control);
             info!(
                 "{} signer id #{}",
                 frost_signer::version(),
@@ -21,7 +29,8 @@ fn main() {
 
             //Start listening for p2p messages
             if let Err(e) = signer.start_p2p_sync() {
-                warn!("An error occurred in the P2P Network: {}", e);
+                warn!("Signer stopped: {}", e);
+                std::process::exit(1);
             }
         }
         Err(e) => {
@@ -0,0 +1,346 @@
+//! Filters on the signer's receive path.
+//!
+//! Every message the transport authenticated passes through a [`Chain`] of [`Middleware`]
+//! before the rounds process it. Each layer in turn can drop the message, annotate it for the
+//! layers after it, or let it through as it is. Rate limiting, deduplication and message
+//! counts are layers like any an operator compiles in:
+//!
+//! ```ignore
+//! let signer = Signer::new(config, id)
+//!     .with_middleware(Dedup::new(1024))
+//!     .with_middleware(RateLimit::new(100, Duration::from_secs(1)))
+//!     .with_middleware(Filter::new("no-reshare", |inbound: &Inbound| {
+//!         match inbound.message.msg {
+//!             MessageTypes::ReshareBegin(_) => Verdict::Drop("reshares are disabled".into()),
+//!             _ => Verdict::Pass,
+//!         }
+//!     }));
+//! ```
+//!
+//! Layers run on the thread driving the rounds, in the order they were added, so a slow layer
+//! delays every message after it. A signer without middleware processes every message.
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::control::{Response, Route};
+use crate::net::Message;
+
+/// An inbound message on its way to the rounds
+#[derive(Debug)]
+pub struct Inbound {
+    pub message: Message,
+    pub received_at: Instant,
+    /// Notes left by the layers the message went through, by layer name
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Inbound {
+    pub fn new(message: Message, received_at: Instant) -> Self {
+        Self {
+            message,
+            received_at,
+            annotations: BTreeMap::new(),
+        }
+    }
+}
+
+/// What a layer decided about a message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    /// Drop the message, for the logged reason
+    Drop(String),
+}
+
+pub trait Middleware: Send {
+    /// Name the layer's drops and annotations are logged under
+    fn name(&self) -> &str;
+
+    /// Decide about `inbound`, which the layer may annotate
+    fn inspect(&mut self, inbound: &mut Inbound) -> Verdict;
+}
+
+/// Layers a message goes through in order, stopping at the first which drops it
+#[derive(Default)]
+pub struct Chain {
+    layers: Vec<Box<dyn Middleware>>,
+}
+
+impl fmt::Debug for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.layers.iter().map(|layer| layer.name()))
+            .finish()
+    }
+}
+
+impl Chain {
+    pub fn with(mut self, layer: impl Middleware + 'static) -> Self {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    pub fn push(&mut self, layer: Box<dyn Middleware>) {
+        self.layers.push(layer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Run `message` through the layers, returning it unless one of them dropped it
+    pub fn run(&mut self, message: Message, received_at: Instant) -> Option<Inbound> {
+        let mut inbound = Inbound::new(message, received_at);
+        for layer in &mut self.layers {
+            if let Verdict::Drop(reason) = layer.inspect(&mut inbound) {
+                debug!(
+                    "{} dropped {}: {}",
+                    layer.name(),
+                    inbound.message.msg.name(),
+                    reason
+                );
+                return None;
+            }
+        }
+        if !inbound.annotations.is_empty() {
+            debug!(
+                "{} annotated {:?}",
+                inbound.message.msg.name(),
+                inbound.annotations
+            );
+        }
+        Some(inbound)
+    }
+}
+
+/// A layer deciding with a closure, e.g. a signing policy
+pub struct Filter<F> {
+    name: String,
+    decide: F,
+}
+
+impl<F: FnMut(&Inbound) -> Verdict + Send> Filter<F> {
+    pub fn new(name: &str, decide: F) -> Self {
+        Self {
+            name: name.to_string(),
+            decide,
+        }
+    }
+}
+
+impl<F: FnMut(&Inbound) -> Verdict + Send> Middleware for Filter<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn inspect(&mut self, inbound: &mut Inbound) -> Verdict {
+        (self.decide)(inbound)
+    }
+}
+
+/// Drops messages carrying a signature seen among the last `capacity` messages, e.g. an
+/// envelope the relay delivered twice
+pub struct Dedup {
+    capacity: usize,
+    seen: HashSet<Vec<u8>>,
+    order: VecDeque<Vec<u8>>,
+}
+
+impl Dedup {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+}
+
+impl Middleware for Dedup {
+    fn name(&self) -> &str {
+        "dedup"
+    }
+
+    fn inspect(&mut self, inbound: &mut Inbound) -> Verdict {
+        let sig = &inbound.message.sig;
+        // unsigned messages cannot be told apart from their copies
+        if sig.is_empty() {
+            return Verdict::Pass;
+        }
+        if !self.seen.insert(sig.clone()) {
+            return Verdict::Drop("already received".to_string());
+        }
+        self.order.push_back(sig.clone());
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        Verdict::Pass
+    }
+}
+
+/// Lets through at most `max` messages of each type per `window`
+pub struct RateLimit {
+    max: usize,
+    window: Duration,
+    /// When the messages let through within the window arrived, by message type
+    arrivals: HashMap<&'static str, VecDeque<Instant>>,
+}
+
+impl RateLimit {
+    pub fn new(max: usize, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            arrivals: HashMap::new(),
+        }
+    }
+}
+
+impl Middleware for RateLimit {
+    fn name(&self) -> &str {
+        "rate_limit"
+    }
+
+    fn inspect(&mut self, inbound: &mut Inbound) -> Verdict {
+        let name = inbound.message.msg.name();
+        let arrivals = self.arrivals.entry(name).or_default();
+        while arrivals
+            .front()
+            .is_some_and(|arrived| inbound.received_at.duration_since(*arrived) >= self.window)
+        {
+            arrivals.pop_front();
+        }
+        if arrivals.len() >= self.max {
+            return Verdict::Drop(format!(
+                "more than {} {name} in {:?}",
+                self.max, self.window
+            ));
+        }
+        arrivals.push_back(inbound.received_at);
+        Verdict::Pass
+    }
+}
+
+/// Counts the messages reaching it by type, a [`crate::control`] route serving the counts at
+/// `GET /messages`. Clones share the counts, so keep one to serve them.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    counts: Arc<Mutex<BTreeMap<&'static str, u64>>>,
+}
+
+impl Metrics {
+    pub fn counts(&self) -> BTreeMap<&'static str, u64> {
+        self.counts
+            .lock()
+            .map(|counts| counts.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Middleware for Metrics {
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    fn inspect(&mut self, inbound: &mut Inbound) -> Verdict {
+        if let Ok(mut counts) = self.counts.lock() {
+            *counts.entry(inbound.message.msg.name()).or_default() += 1;
+        }
+        Verdict::Pass
+    }
+}
+
+impl Route for Metrics {
+    fn respond(&self, method: &str, path: &str, _body: &str) -> Option<Response> {
+        if (method, path) != ("GET", "/messages") {
+            return None;
+        }
+        Some(match serde_json::to_string(&self.counts()) {
+            Ok(body) => Response::json("200 OK", body),
+            Err(_) => Response::json("500 Internal Server Error", "null".to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_round::{DkgBegin, MessageTypes};
+
+    fn dkg_begin(dkg_id: u64, sig: &[u8]) -> Message {
+        Message {
+            msg: MessageTypes::DkgBegin(DkgBegin {
+                dkg_id,
+                excluded_key_ids: vec![],
+            }),
+            sig: sig.to_vec(),
+        }
+    }
+
+    struct Stamp;
+
+    impl Middleware for Stamp {
+        fn name(&self) -> &str {
+            "stamp"
+        }
+
+        fn inspect(&mut self, inbound: &mut Inbound) -> Verdict {
+            let len = inbound.message.sig.len().to_string();
+            inbound.annotations.insert("sig_len".to_string(), len);
+            Verdict::Pass
+        }
+    }
+
+    #[test]
+    fn layers_run_in_order_until_one_drops() {
+        let metrics = Metrics::default();
+        let mut chain = Chain::default()
+            .with(Stamp)
+            .with(Dedup::new(2))
+            .with(metrics.clone())
+            .with(Filter::new("odd", |inbound: &Inbound| {
+                match &inbound.message.msg {
+                    MessageTypes::DkgBegin(begin) if begin.dkg_id % 2 == 1 => {
+                        Verdict::Drop("odd round".to_string())
+                    }
+                    _ => Verdict::Pass,
+                }
+            }));
+        let now = Instant::now();
+        let inbound = chain.run(dkg_begin(2, b"a"), now).unwrap();
+        assert_eq!(inbound.annotations["sig_len"], "1");
+        // the copy stops at dedup, before the metrics count it
+        assert!(chain.run(dkg_begin(2, b"a"), now).is_none());
+        assert!(chain.run(dkg_begin(3, b"b"), now).is_none());
+        assert!(chain.run(dkg_begin(4, b"c"), now).is_some());
+        // "a" fell out of the last 2 signatures seen
+        assert!(chain.run(dkg_begin(2, b"a"), now).is_some());
+        assert_eq!(metrics.counts().get("DkgBegin"), Some(&4));
+        let response = metrics.respond("GET", "/messages", "").unwrap();
+        assert_eq!(response.body, r#"{"DkgBegin":4}"#);
+    }
+
+    #[test]
+    fn rate_limits_apply_per_message_type_and_window() {
+        let mut limit = RateLimit::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+        let mut inspect =
+            |message: Message, at: Duration| limit.inspect(&mut Inbound::new(message, start + at));
+        assert_eq!(inspect(dkg_begin(1, b""), Duration::ZERO), Verdict::Pass);
+        assert_eq!(inspect(dkg_begin(1, b""), Duration::ZERO), Verdict::Pass);
+        assert!(matches!(
+            inspect(dkg_begin(1, b""), Duration::from_millis(500)),
+            Verdict::Drop(_)
+        ));
+        assert_eq!(
+            inspect(dkg_begin(1, b""), Duration::from_secs(1)),
+            Verdict::Pass
+        );
+    }
+}
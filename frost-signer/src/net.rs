@@ -1,32 +1,161 @@
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
+use crate::auth::{self, Authenticator, Identity};
+use crate::bus::{Bus, BusNet, BusNetListen};
+use crate::config::{Config, Transport};
+use crate::logging::target::NET;
+use crate::protocol::PROTOCOL_VERSION;
+use crate::proxy::{self, Egress};
+use crate::retry::{Classify, Retry, RetryPolicy};
 use crate::signing_round;
-// Message is the format over the wire
+use crate::stackerdb::{self, StackerDbNet, StackerDbNetListen};
+use crate::traffic::Traffic;
+// Message is what signers and the coordinator exchange, it travels inside an Envelope
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Message {
     pub msg: signing_round::MessageTypes,
     pub sig: Vec<u8>,
 }
 
+/// The format over the wire. The namespace lets several signer groups share one relay,
+/// each dropping the others' messages. See [`crate::auth`] for how envelopes are signed.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope {
+    /// [`PROTOCOL_VERSION`] of the sender
+    pub version: u16,
+    pub namespace: String,
+    /// 0 for the coordinator, the signer id otherwise
+    pub sender_id: u32,
+    /// Unix time in milliseconds the envelope was sent at
+    pub timestamp: u64,
+    pub message: Message,
+    /// Sender's network key signature over [`auth::digest`] of the fields above
+    pub sig: Vec<u8>,
+}
+
+impl Envelope {
+    /// Decode a blob as posted to the relay
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Encode into `buf`, replacing its contents but keeping its capacity
+    pub fn write_to(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        EnvelopeRef {
+            version: self.version,
+            namespace: &self.namespace,
+            sender_id: self.sender_id,
+            timestamp: self.timestamp,
+            message: &self.message,
+            sig: &self.sig,
+        }
+        .write_to(buf)
+    }
+}
+
+/// An [`Envelope`] borrowing its parts, encoded the same way
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    version: u16,
+    namespace: &'a str,
+    sender_id: u32,
+    timestamp: u64,
+    message: &'a Message,
+    sig: &'a [u8],
+}
+
+impl EnvelopeRef<'_> {
+    fn write_to(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        buf.clear();
+        bincode::serialize_into(&mut *buf, self)?;
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// Encoding buffer reused by every message sent from a thread
+    static SEND_BUF: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
 // Http listen/poll with queue (requires mutable access, is configured by passing in HttpNet)
 pub struct HttpNetListen {
     pub net: HttpNet,
     in_queue: Vec<Message>,
+    /// Cleared once the relay answers a long poll without the long-poll header
+    long_poll_supported: bool,
+    /// Drops inbound envelopes which fail authentication, if set
+    authenticator: Option<Authenticator>,
+    /// Counts inbound envelopes and drops our own, if set
+    traffic: Option<Traffic>,
 }
 
 impl HttpNetListen {
     pub fn new(net: HttpNet, in_queue: Vec<Message>) -> Self {
-        HttpNetListen { net, in_queue }
+        HttpNetListen {
+            net,
+            in_queue,
+            long_poll_supported: true,
+            authenticator: None,
+            traffic: None,
+        }
+    }
+
+    /// Only queue messages in envelopes `authenticator` accepts
+    pub fn with_authenticator(mut self, authenticator: Authenticator) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Only queue messages in envelopes `traffic` admits
+    pub fn with_traffic(mut self, traffic: Traffic) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+
+    fn long_poll_secs(&self) -> Option<u64> {
+        match self.net.long_poll_secs {
+            0 => None,
+            _ if !self.long_poll_supported => None,
+            secs => Some(secs),
+        }
     }
 }
 
+/// Response header set by relays which support long polling
+const LONG_POLL_HEADER: &str = "x-long-poll";
+/// Extra time allowed on top of the long-poll wait before a poll request times out
+const LONG_POLL_GRACE: Duration = Duration::from_secs(5);
+/// Response header set by relays which queued a batched post as the envelopes it frames
+const BATCH_HEADER: &str = "x-batch";
+
 // Http send (does not require mutable access, can be cloned to pass to threads)
 #[derive(Clone)]
 pub struct HttpNet {
     pub http_relay_url: String,
     connected: bool,
+    retry_policy: RetryPolicy,
+    /// How long the relay may hold a poll open waiting for a message, 0 for short polls
+    long_poll_secs: u64,
+    /// Signer group this node belongs to, empty for the default group
+    namespace: String,
+    /// Signs outbound envelopes, which are sent unsigned and unchecked without one
+    identity: Option<Identity>,
+    /// Envelopes per post when sending several, 1 to post each on its own
+    batch_size: usize,
+    /// Posts in flight at once when sending several
+    max_in_flight: usize,
+    /// Sends requests to the relay, through its proxy if it has one
+    egress: Egress,
 }
 
 impl HttpNet {
@@ -34,8 +163,50 @@ impl HttpNet {
         HttpNet {
             http_relay_url,
             connected: true,
+            retry_policy: RetryPolicy::never(),
+            long_poll_secs: 0,
+            namespace: String::new(),
+            identity: None,
+            batch_size: 1,
+            max_in_flight: 1,
+            egress: Egress::direct(),
         }
     }
+
+    /// Post up to `batch_size` envelopes at once, with up to `max_in_flight` posts in flight,
+    /// when sending several. Zero for either means one.
+    pub fn with_batching(mut self, batch_size: usize, max_in_flight: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_long_poll(mut self, long_poll_secs: u64) -> Self {
+        self.long_poll_secs = long_poll_secs;
+        self
+    }
+
+    /// Only exchange messages with nodes using the same namespace
+    pub fn with_namespace(mut self, namespace: String) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Send requests to the relay through `egress`, see [`crate::proxy`]
+    pub fn with_egress(mut self, egress: Egress) -> Self {
+        self.egress = egress;
+        self
+    }
 }
 
 // these functions manipulate the inbound message queue
@@ -54,16 +225,52 @@ impl NetListen for HttpNetListen {
     fn listen(&self) {}
 
     fn poll(&mut self, id: u32) {
-        let url = url_with_id(&self.net.http_relay_url, id);
-        debug!("poll {}", url);
-        match ureq::get(&url).call() {
+        let long_poll_secs = self.long_poll_secs();
+        let mut url = url_with_id(&self.net.http_relay_url, id, &self.net.namespace);
+        if let Some(secs) = long_poll_secs {
+            url.push_str(&format!("&wait={secs}"));
+        }
+        let mut request = self.net.egress.get(&url);
+        if let Some(secs) = long_poll_secs {
+            request = request.timeout(Duration::from_secs(secs) + LONG_POLL_GRACE);
+        }
+        debug!(target: NET, "poll {}", url);
+        match request.call() {
             Ok(response) => {
                 self.net.connected = true;
+                if long_poll_secs.is_some() && response.header(LONG_POLL_HEADER).is_none() {
+                    info!(
+                        target: NET,
+                        "relay does not support long polling, falling back to short polls"
+                    );
+                    self.long_poll_supported = false;
+                }
                 if response.status() == 200 {
-                    match bincode::deserialize_from::<_, Message>(response.into_reader()) {
-                        Ok(msg) => {
-                            debug!("received {:?}", msg);
-                            self.in_queue.push(msg);
+                    match bincode::deserialize_from::<_, Envelope>(response.into_reader()) {
+                        Ok(envelope) if envelope.namespace == self.net.namespace => {
+                            match authenticate(&self.authenticator, &self.traffic, &envelope) {
+                                Err(e) => {
+                                    warn!(
+                                        target: NET,
+                                        "dropping message from sender #{}: {e}",
+                                        envelope.sender_id
+                                    )
+                                }
+                                _ if !admit(&self.traffic, &envelope) => {
+                                    debug!(target: NET, "dropping our own {:?}", envelope.message);
+                                }
+                                _ => {
+                                    debug!(target: NET, "received {:?}", envelope.message);
+                                    self.in_queue.push(envelope.message);
+                                }
+                            }
+                        }
+                        Ok(envelope) => {
+                            debug!(
+                                target: NET,
+                                "dropping message from namespace {:?}",
+                                envelope.namespace
+                            );
                         }
                         Err(_e) => {}
                     };
@@ -71,7 +278,7 @@ impl NetListen for HttpNetListen {
             }
             Err(e) => {
                 if self.net.connected {
-                    warn!("{} U: {}", e, url);
+                    warn!(target: NET, "{} U: {}", e, url);
                     self.net.connected = false;
                 }
             }
@@ -87,27 +294,185 @@ impl NetListen for HttpNetListen {
     }
 }
 
+/// Check `envelope` with `authenticator` if set, counting stale envelopes in `traffic` if set
+pub(crate) fn authenticate(
+    authenticator: &Option<Authenticator>,
+    traffic: &Option<Traffic>,
+    envelope: &Envelope,
+) -> Result<(), auth::Error> {
+    let Some(authenticator) = authenticator else {
+        return Ok(());
+    };
+    let result = authenticator.check(envelope);
+    if let (Err(auth::Error::Stale(_)), Some(traffic)) = (&result, traffic) {
+        traffic.drop_stale();
+    }
+    result
+}
+
+/// Whether to queue an authenticated `envelope`, counting it in `traffic` if set
+pub(crate) fn admit(traffic: &Option<Traffic>, envelope: &Envelope) -> bool {
+    traffic
+        .as_ref()
+        .is_none_or(|traffic| traffic.admit(envelope.sender_id))
+}
+
 // for threads that only send data, use immutable Net
 pub trait Net {
     type Error: Debug;
 
     fn send_message(&self, msg: Message) -> Result<(), Self::Error>;
+
+    /// Send the messages a round step produced, one at a time unless the transport can do
+    /// better
+    fn send_messages(&self, msgs: Vec<Message>) -> Result<(), Self::Error> {
+        for msg in msgs {
+            self.send_message(msg)?;
+        }
+        Ok(())
+    }
 }
 
 impl Net for HttpNet {
     type Error = Error;
 
     fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
-        let req = ureq::post(&self.http_relay_url);
+        SEND_BUF.with(|bytes| {
+            let mut bytes = bytes.borrow_mut();
+            self.seal(&msg, &mut bytes)?;
+            self.post(&msg, &bytes)
+        })
+    }
 
-        // sign message
+    /// Coalesce the messages into batches of `batch_size`, posting up to `max_in_flight`
+    /// batches at once. Envelopes of different batches may reach the relay in any order.
+    fn send_messages(&self, msgs: Vec<Message>) -> Result<(), Self::Error> {
+        if self.batch_size == 1 && self.max_in_flight == 1 {
+            for msg in msgs {
+                self.send_message(msg)?;
+            }
+            return Ok(());
+        }
+        let batches = msgs
+            .chunks(self.batch_size)
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|msg| {
+                        let mut bytes = vec![];
+                        self.seal(msg, &mut bytes)?;
+                        Ok(bytes)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // each poster takes the next unsent batch until none are left
+        let next = AtomicUsize::new(0);
+        let post_batches = || -> Result<(), Error> {
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match batches.get(i) {
+                    Some(batch) if batch.len() == 1 => {
+                        self.post(&msgs[i * self.batch_size], &batch[0])?
+                    }
+                    Some(batch) => self.post_batch(batch)?,
+                    None => return Ok(()),
+                }
+            }
+        };
+        thread::scope(|scope| {
+            let posters: Vec<_> = (1..self.max_in_flight.min(batches.len()))
+                .map(|_| scope.spawn(post_batches))
+                .collect();
+            let mut result = post_batches();
+            for poster in posters {
+                let posted = poster
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e));
+                result = result.and(posted);
+            }
+            result
+        })
+    }
+}
 
-        let bytes = bincode::serialize(&msg)?;
-        let result = req.send_bytes(&bytes[..]);
+impl HttpNet {
+    /// Sign `msg` into an envelope and encode it into `bytes`
+    fn seal(&self, msg: &Message, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        let timestamp = auth::now_ms();
+        let (sender_id, sig) = match &self.identity {
+            Some(identity) => {
+                identity.check(&msg)?;
+                let digest = auth::digest(
+                    PROTOCOL_VERSION,
+                    &self.namespace,
+                    identity.sender_id,
+                    timestamp,
+                    &msg,
+                );
+                let sig = identity
+                    .sign(&digest)
+                    .map_err(|e| Error::SigningError(e.to_string()))?;
+                (identity.sender_id, sig)
+            }
+            None => (0, vec![]),
+        };
+        EnvelopeRef {
+            version: PROTOCOL_VERSION,
+            namespace: &self.namespace,
+            sender_id,
+            timestamp,
+            message: msg,
+            sig: &sig,
+        }
+        .write_to(bytes)
+    }
+
+    /// Post `envelopes` framed into one batch, which the relay queues as separate envelopes
+    fn post_batch(&self, envelopes: &[Vec<u8>]) -> Result<(), Error> {
+        let url = url_with_batch(&self.http_relay_url, &self.namespace);
+        let body = encode_batch(envelopes);
+        let response = self.retry_policy.retry(|| {
+            self.egress
+                .post(&url)
+                .send_bytes(&body)
+                .map_err(|e| Error::from(Box::new(e)))
+        });
+        match response {
+            Ok(response) if response.header(BATCH_HEADER).is_some() => {
+                debug!(
+                    target: NET,
+                    "sent {} envelopes {} bytes to {}",
+                    envelopes.len(),
+                    body.len(),
+                    self.http_relay_url
+                );
+                Ok(())
+            }
+            Ok(_) => Err(Error::ConfigError(format!(
+                "{} does not support batched posts, unset http_relay_batch_size",
+                self.http_relay_url
+            ))),
+            Err(e) => {
+                info!(target: NET, "batch post failed to {} {}", self.http_relay_url, e);
+                Err(e)
+            }
+        }
+    }
+
+    fn post(&self, msg: &Message, bytes: &[u8]) -> Result<(), Error> {
+        let url = url_with_namespace(&self.http_relay_url, &self.namespace);
+        let result = self.retry_policy.retry(|| {
+            self.egress
+                .post(&url)
+                .send_bytes(bytes)
+                .map_err(|e| Error::from(Box::new(e)))
+        });
 
         match result {
             Ok(response) => {
                 debug!(
+                    target: NET,
                     "sent {:?} {} bytes {:?} to {}",
                     &msg.msg,
                     bytes.len(),
@@ -116,8 +481,8 @@ impl Net for HttpNet {
                 )
             }
             Err(e) => {
-                info!("post failed to {} {}", self.http_relay_url, e);
-                return Err(Box::new(e).into());
+                info!(target: NET, "post failed to {} {}", self.http_relay_url, e);
+                return Err(e);
             }
         };
 
@@ -125,6 +490,158 @@ impl Net for HttpNet {
     }
 }
 
+/// Sender side of the transport picked by [`Config::transport`]
+#[derive(Clone)]
+pub enum TransportNet {
+    Relay(HttpNet),
+    StackerDb(StackerDbNet),
+    /// In-process, see [`crate::bus`]
+    Bus(BusNet),
+}
+
+impl TransportNet {
+    /// The configured transport, signing envelopes as `sender_id`
+    pub fn from_config(config: &Config, sender_id: u32) -> Result<Self, Error> {
+        let identity = Identity::from_config(config, sender_id)
+            .map_err(|e| Error::ConfigError(e.to_string()))?;
+        Ok(match &config.transport {
+            Transport::Relay => TransportNet::Relay(
+                HttpNet::new(config.http_relay_url.clone())
+                    .with_retry_policy(config.retry.clone())
+                    .with_long_poll(config.http_relay_long_poll_secs)
+                    .with_batching(
+                        config.http_relay_batch_size,
+                        config.http_relay_max_in_flight,
+                    )
+                    .with_namespace(config.relay_namespace.clone())
+                    .with_identity(identity)
+                    .with_egress(config.proxy.egress(&config.http_relay_url)?),
+            ),
+            Transport::StackerDb { node_url, contract } => TransportNet::StackerDb(
+                StackerDbNet::new(
+                    node_url.clone(),
+                    contract.parse()?,
+                    config.total_signers as u32 + 1,
+                    identity,
+                )
+                .with_retry_policy(config.retry.clone())
+                .with_namespace(config.relay_namespace.clone())
+                .with_egress(config.proxy.egress(node_url)?),
+            ),
+        })
+    }
+
+    /// A net on `bus` in place of the configured transport, signing envelopes as `sender_id`
+    pub fn from_bus(bus: &Bus, config: &Config, sender_id: u32) -> Result<Self, Error> {
+        let identity = Identity::from_config(config, sender_id)
+            .map_err(|e| Error::ConfigError(e.to_string()))?;
+        Ok(TransportNet::Bus(
+            bus.net(config.relay_namespace.clone(), identity),
+        ))
+    }
+}
+
+impl Net for TransportNet {
+    type Error = Error;
+
+    fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
+        match self {
+            TransportNet::Relay(net) => net.send_message(msg),
+            TransportNet::StackerDb(net) => net.send_message(msg),
+            TransportNet::Bus(net) => net.send_message(msg),
+        }
+    }
+
+    fn send_messages(&self, msgs: Vec<Message>) -> Result<(), Self::Error> {
+        match self {
+            TransportNet::Relay(net) => net.send_messages(msgs),
+            TransportNet::StackerDb(net) => net.send_messages(msgs),
+            TransportNet::Bus(net) => net.send_messages(msgs),
+        }
+    }
+}
+
+/// Receiving side of the transport picked by [`Config::transport`]
+pub enum TransportNetListen {
+    Relay(HttpNetListen),
+    StackerDb(StackerDbNetListen),
+    Bus(BusNetListen),
+}
+
+impl TransportNetListen {
+    pub fn new(net: TransportNet) -> Self {
+        match net {
+            TransportNet::Relay(net) => TransportNetListen::Relay(HttpNetListen::new(net, vec![])),
+            TransportNet::StackerDb(net) => {
+                TransportNetListen::StackerDb(StackerDbNetListen::new(net))
+            }
+            TransportNet::Bus(net) => TransportNetListen::Bus(BusNetListen::new(net)),
+        }
+    }
+
+    /// Only queue messages in envelopes `authenticator` accepts
+    pub fn with_authenticator(self, authenticator: Authenticator) -> Self {
+        match self {
+            TransportNetListen::Relay(net) => {
+                TransportNetListen::Relay(net.with_authenticator(authenticator))
+            }
+            TransportNetListen::StackerDb(net) => {
+                TransportNetListen::StackerDb(net.with_authenticator(authenticator))
+            }
+            TransportNetListen::Bus(net) => {
+                TransportNetListen::Bus(net.with_authenticator(authenticator))
+            }
+        }
+    }
+
+    /// Only queue messages in envelopes `traffic` admits
+    pub fn with_traffic(self, traffic: Traffic) -> Self {
+        match self {
+            TransportNetListen::Relay(net) => TransportNetListen::Relay(net.with_traffic(traffic)),
+            TransportNetListen::StackerDb(net) => {
+                TransportNetListen::StackerDb(net.with_traffic(traffic))
+            }
+            TransportNetListen::Bus(net) => TransportNetListen::Bus(net.with_traffic(traffic)),
+        }
+    }
+}
+
+impl NetListen for TransportNetListen {
+    type Error = Error;
+
+    fn listen(&self) {
+        match self {
+            TransportNetListen::Relay(net) => net.listen(),
+            TransportNetListen::StackerDb(net) => net.listen(),
+            TransportNetListen::Bus(net) => net.listen(),
+        }
+    }
+
+    fn poll(&mut self, id: u32) {
+        match self {
+            TransportNetListen::Relay(net) => net.poll(id),
+            TransportNetListen::StackerDb(net) => net.poll(id),
+            TransportNetListen::Bus(net) => net.poll(id),
+        }
+    }
+
+    fn next_message(&mut self) -> Option<Message> {
+        match self {
+            TransportNetListen::Relay(net) => net.next_message(),
+            TransportNetListen::StackerDb(net) => net.next_message(),
+            TransportNetListen::Bus(net) => net.next_message(),
+        }
+    }
+
+    fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
+        match self {
+            TransportNetListen::Relay(net) => net.send_message(msg),
+            TransportNetListen::StackerDb(net) => net.send_message(msg),
+            TransportNetListen::Bus(net) => net.send_message(msg),
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Serialization failed: {0}")]
@@ -132,10 +649,138 @@ pub enum Error {
 
     #[error("Network error: {0}")]
     NetworkError(#[from] Box<ureq::Error>),
+
+    #[error("Failed to sign envelope: {0}")]
+    SigningError(String),
+
+    #[error("Refusing to send a {0} message not signed by this node")]
+    UnsignedMessage(&'static str),
+
+    #[error("StackerDB error: {0}")]
+    StackerDbError(#[from] stackerdb::Error),
+
+    #[error("Invalid config: {0}")]
+    ConfigError(String),
+
+    #[error("Proxy error: {0}")]
+    ProxyError(#[from] proxy::Error),
 }
 
-fn url_with_id(base: &str, id: u32) -> String {
+impl Classify for Error {
+    fn classify(&self) -> Retry {
+        match self {
+            Error::SerializationError(_)
+            | Error::SigningError(_)
+            | Error::UnsignedMessage(_)
+            | Error::ConfigError(_)
+            | Error::ProxyError(_) => Retry::Permanent,
+            Error::StackerDbError(stackerdb::Error::Io(_)) => Retry::Transient,
+            Error::StackerDbError(_) => Retry::Permanent,
+            Error::NetworkError(e) => match e.as_ref() {
+                // a relay shedding load asks us to back off and try again
+                ureq::Error::Status(429, _) => Retry::Transient,
+                ureq::Error::Status(code, _) if *code < 500 => Retry::Permanent,
+                _ => Retry::Transient,
+            },
+        }
+    }
+}
+
+fn url_with_id(base: &str, id: u32, namespace: &str) -> String {
     let mut url = base.to_owned();
     url.push_str(&format!("?id={id}"));
+    if !namespace.is_empty() {
+        url.push_str(&format!("&namespace={namespace}"));
+    }
     url
 }
+
+fn url_with_namespace(base: &str, namespace: &str) -> String {
+    if namespace.is_empty() {
+        base.to_owned()
+    } else {
+        format!("{base}?namespace={namespace}")
+    }
+}
+
+fn url_with_batch(base: &str, namespace: &str) -> String {
+    let url = url_with_namespace(base, namespace);
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{url}{separator}batch=1")
+}
+
+/// Frame `envelopes` the way the relay splits batched posts: each is preceded by its length
+/// as a big-endian `u32`
+fn encode_batch(envelopes: &[Vec<u8>]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(envelopes.iter().map(|e| e.len() + 4).sum());
+    for envelope in envelopes {
+        body.extend_from_slice(&(envelope.len() as u32).to_be_bytes());
+        body.extend_from_slice(envelope);
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_round::{DkgBegin, MessageTypes};
+    use rand_core::OsRng;
+    use wtfrost::Scalar;
+
+    #[test]
+    fn namespace_is_added_to_relay_urls() {
+        assert_eq!(url_with_id("http://relay", 3, ""), "http://relay?id=3");
+        assert_eq!(
+            url_with_id("http://relay", 3, "testnet"),
+            "http://relay?id=3&namespace=testnet"
+        );
+        assert_eq!(url_with_namespace("http://relay", ""), "http://relay");
+        assert_eq!(
+            url_with_namespace("http://relay", "testnet"),
+            "http://relay?namespace=testnet"
+        );
+    }
+
+    #[test]
+    fn batches_are_framed_by_length() {
+        assert_eq!(url_with_batch("http://relay", ""), "http://relay?batch=1");
+        assert_eq!(
+            url_with_batch("http://relay", "testnet"),
+            "http://relay?namespace=testnet&batch=1"
+        );
+        assert_eq!(
+            encode_batch(&[vec![1, 2], vec![3]]),
+            vec![0, 0, 0, 2, 1, 2, 0, 0, 0, 1, 3]
+        );
+    }
+
+    #[test]
+    fn envelope_carries_the_namespace() {
+        let envelope = Identity::new(2, Scalar::random(&mut OsRng))
+            .seal(
+                "devnet".to_string(),
+                Message {
+                    msg: MessageTypes::DkgBegin(DkgBegin {
+                        dkg_id: 7,
+                        excluded_key_ids: vec![],
+                    }),
+                    sig: vec![1, 2, 3],
+                },
+            )
+            .unwrap();
+        let bytes = envelope.to_bytes().unwrap();
+        let decoded = Envelope::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.version, PROTOCOL_VERSION);
+        assert_eq!(decoded.namespace, "devnet");
+        assert_eq!(decoded.sender_id, 2);
+        assert_eq!(decoded.timestamp, envelope.timestamp);
+        assert_eq!(decoded.sig, envelope.sig);
+        assert_eq!(decoded.message.sig, vec![1, 2, 3]);
+
+        // a reused buffer holds the same encoding, whatever it held before
+        let mut buf = vec![0xff; 1024];
+        envelope.write_to(&mut buf).unwrap();
+        assert_eq!(buf, bytes);
+        assert!(buf.capacity() >= 1024);
+    }
+}
@@ -0,0 +1,406 @@
+//! Secret nonces kept across restarts.
+//!
+//! A signer answers a `NonceRequest` with fresh public nonces and only later receives the
+//! `SignatureShareRequest` which uses them. The secret halves live in its parties, so a signer
+//! restarting in between would answer with shares that fail to verify. With `nonce_store_path`
+//! set, every secret nonce handed out is also written to that file, encrypted under a key
+//! derived from the signer's network private key, until it signs or `nonce_ttl_secs` pass.
+//! Signing takes the nonce whose public half the request names from the session holding it, see
+//! [`crate::sessions`], and from the store only once a restart dropped the session, so it still
+//! works after a restart, as long as the key shares survived it too, see [`crate::key_store`].
+//! Either way the stored copy is spent under the store's lock before the nonce signs, so a nonce
+//! signs once whichever way it is asked for.
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use wtfrost::common::{Nonce, PublicNonce};
+
+use crate::protocol::{tag, Hasher};
+use crate::secret::Secret;
+use crate::sessions::{SessionId, Sessions};
+
+/// Time a secret nonce is kept unless configured
+pub const DEFAULT_NONCE_TTL: Duration = Duration::from_secs(600);
+
+const IV_LEN: usize = 12;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Nonce store I/O failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Nonce store is not valid JSON: {0}")]
+    Corrupt(#[from] serde_json::Error),
+    #[error("Failed to encode a nonce: {0}")]
+    Encoding(#[from] bincode::Error),
+    #[error("Stored nonce for key id {0} does not decrypt, was the network key changed?")]
+    Decrypt(u32),
+}
+
+/// The nonce request a nonce answered, and the key id it belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NonceId {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    /// Session of signing round `sign_id` the nonce was handed out in, see [`SessionId`]
+    #[serde(default)]
+    pub correlation_id: u64,
+    pub sign_nonce_id: u64,
+    pub key_id: u32,
+    /// Position of the nonce among those of a batch nonce request, 0 for a single nonce
+    #[serde(default)]
+    pub index: u32,
+}
+
+impl NonceId {
+    /// The signing session the nonce was handed out in
+    pub fn session(&self) -> SessionId {
+        SessionId {
+            dkg_id: self.dkg_id,
+            sign_id: self.sign_id,
+            correlation_id: self.correlation_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    id: NonceId,
+    public_nonce: PublicNonce,
+    /// Unix time in seconds from which the nonce is dropped
+    expires_at: u64,
+    /// Hex of the cipher's 12-byte nonce
+    iv: String,
+    /// Hex of the encrypted secret nonce
+    ciphertext: String,
+}
+
+impl Entry {
+    /// Binds the ciphertext to the nonce request and public nonce it was stored for
+    fn aad(&self) -> Vec<u8> {
+        let mut aad = vec![];
+        aad.extend_from_slice(&self.id.dkg_id.to_be_bytes());
+        aad.extend_from_slice(&self.id.sign_id.to_be_bytes());
+        aad.extend_from_slice(&self.id.correlation_id.to_be_bytes());
+        aad.extend_from_slice(&self.id.sign_nonce_id.to_be_bytes());
+        aad.extend_from_slice(&self.id.key_id.to_be_bytes());
+        aad.extend_from_slice(&self.id.index.to_be_bytes());
+        aad.extend_from_slice(self.public_nonce.D.compress().as_bytes());
+        aad.extend_from_slice(self.public_nonce.E.compress().as_bytes());
+        aad
+    }
+}
+
+struct Inner {
+    path: PathBuf,
+    key: Secret<Vec<u8>>,
+    ttl: Duration,
+    entries: Vec<Entry>,
+}
+
+/// Secret nonces on disk, shared by the rounds of every generation.
+/// The default store keeps nothing.
+#[derive(Clone, Default)]
+pub struct NonceStore(Option<Arc<Mutex<Inner>>>);
+
+impl NonceStore {
+    /// Store nonces at `path` for `ttl`, encrypted under a key derived from
+    /// `network_private_key`, reloading the ones which have not expired yet
+    pub fn open(
+        path: impl AsRef<Path>,
+        network_private_key: &str,
+        ttl: Duration,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let mut hasher = Hasher::new();
+        hasher.update(tag::NONCE_STORE_KEY.as_bytes());
+        hasher.update(network_private_key.as_bytes());
+        let mut inner = Inner {
+            path,
+            key: Secret::new(hasher.finalize().to_vec()),
+            ttl,
+            entries,
+        };
+        inner.expire(now());
+        inner.write()?;
+        Ok(Self(Some(Arc::new(Mutex::new(inner)))))
+    }
+
+    /// Keep the secret `nonce` behind `public_nonce` until it signs or expires
+    pub fn put(&self, id: NonceId, public_nonce: &PublicNonce, nonce: &Nonce) -> Result<(), Error> {
+        let Some(inner) = &self.0 else {
+            return Ok(());
+        };
+        let mut inner = inner.lock().expect("nonce store lock poisoned");
+        let now = now();
+        inner.expire(now);
+        let mut iv = [0u8; IV_LEN];
+        OsRng.fill_bytes(&mut iv);
+        let mut entry = Entry {
+            id,
+            public_nonce: public_nonce.clone(),
+            expires_at: now + inner.ttl.as_secs(),
+            iv: hex::encode(iv),
+            ciphertext: String::new(),
+        };
+        let plaintext = Secret::new(bincode::serialize(nonce)?);
+        let ciphertext = inner
+            .cipher()
+            .encrypt(
+                (&iv).into(),
+                Payload {
+                    msg: plaintext.as_slice(),
+                    aad: &entry.aad(),
+                },
+            )
+            .expect("encrypting to a vec does not fail");
+        entry.ciphertext = hex::encode(ciphertext);
+        inner.entries.retain(|stored| stored.id != id);
+        inner.entries.push(entry);
+        inner.write()
+    }
+
+    /// Drop the nonces stored for `session`, e.g. once a new nonce request starts it afresh
+    pub fn discard(&self, session: &SessionId) -> Result<(), Error> {
+        let Some(inner) = &self.0 else {
+            return Ok(());
+        };
+        let mut inner = inner.lock().expect("nonce store lock poisoned");
+        inner.expire(now());
+        inner.entries.retain(|entry| entry.id.session() != *session);
+        inner.write()
+    }
+
+    /// Spend the secret nonce of `key_id` behind `public_nonce`, handed out in `session`. The
+    /// nonce comes from `sessions` if it holds the session, else from the store, after a restart
+    /// dropped the session. The stored copy is removed and the file rewritten first, under the
+    /// lock, so a nonce is never returned twice, nor once more after a restart.
+    pub fn spend(
+        &self,
+        sessions: &mut Sessions,
+        session: &SessionId,
+        key_id: u32,
+        public_nonce: &PublicNonce,
+    ) -> Result<Option<Secret<Nonce>>, Error> {
+        let Some(inner) = &self.0 else {
+            return Ok(sessions.take(session, key_id, public_nonce));
+        };
+        let mut inner = inner.lock().expect("nonce store lock poisoned");
+        inner.expire(now());
+        let stored = match inner.entries.iter().position(|entry| {
+            entry.id.session() == *session
+                && entry.id.key_id == key_id
+                && entry.public_nonce == *public_nonce
+        }) {
+            Some(position) => {
+                let entry = inner.entries.remove(position);
+                inner.write()?;
+                Some(entry)
+            }
+            None => None,
+        };
+        if sessions.holds(session) {
+            return Ok(sessions.take(session, key_id, public_nonce));
+        }
+        let Some(entry) = stored else {
+            return Ok(None);
+        };
+        let decrypt = || -> Option<Secret<Vec<u8>>> {
+            let iv: [u8; IV_LEN] = hex::decode(&entry.iv).ok()?.try_into().ok()?;
+            let ciphertext = hex::decode(&entry.ciphertext).ok()?;
+            let plaintext = inner
+                .cipher()
+                .decrypt(
+                    (&iv).into(),
+                    Payload {
+                        msg: &ciphertext,
+                        aad: &entry.aad(),
+                    },
+                )
+                .ok()?;
+            Some(Secret::new(plaintext))
+        };
+        let plaintext = decrypt().ok_or(Error::Decrypt(key_id))?;
+        Ok(Some(Secret::new(bincode::deserialize(&plaintext)?)))
+    }
+
+    /// Nonces stored and not expired
+    pub fn len(&self) -> usize {
+        self.0.as_ref().map_or(0, |inner| {
+            let mut inner = inner.lock().expect("nonce store lock poisoned");
+            inner.expire(now());
+            inner.entries.len()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Inner {
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new_from_slice(&self.key).expect("the key is 32 bytes")
+    }
+
+    fn expire(&mut self, now: u64) {
+        self.entries.retain(|entry| entry.expires_at > now);
+    }
+
+    /// Replace the file, readable by its owner only
+    fn write(&self) -> Result<(), Error> {
+        let tmp = self.path.with_extension("tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp)?;
+        file.write_all(&serde_json::to_vec(&self.entries)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wtfrost::{Point, Scalar};
+
+    fn nonce() -> (Nonce, PublicNonce) {
+        let nonce = Nonce {
+            d: Scalar::random(&mut OsRng),
+            e: Scalar::random(&mut OsRng),
+        };
+        let public_nonce = PublicNonce {
+            D: Point::from(nonce.d),
+            E: Point::from(nonce.e),
+        };
+        (nonce, public_nonce)
+    }
+
+    fn id(correlation_id: u64, key_id: u32, index: u32) -> NonceId {
+        NonceId {
+            dkg_id: 1,
+            sign_id: 2,
+            correlation_id,
+            sign_nonce_id: 1,
+            key_id,
+            index,
+        }
+    }
+
+    fn session(correlation_id: u64) -> SessionId {
+        id(correlation_id, 0, 0).session()
+    }
+
+    /// Spend a stored nonce as a signer holding no session does, after a restart
+    fn spend(
+        store: &NonceStore,
+        session: &SessionId,
+        key_id: u32,
+        public_nonce: &PublicNonce,
+    ) -> Result<Option<Nonce>, Error> {
+        Ok(store
+            .spend(&mut Sessions::default(), session, key_id, public_nonce)?
+            .map(|nonce| (*nonce).clone()))
+    }
+
+    #[test]
+    fn nonces_survive_a_reopen_encrypted_and_sign_once() {
+        let dir = std::env::temp_dir().join(format!("nonce-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nonces.json");
+        let _ = fs::remove_file(&path);
+        let (first, first_public) = nonce();
+        let (second, second_public) = nonce();
+
+        let store = NonceStore::open(&path, "network key", DEFAULT_NONCE_TTL).unwrap();
+        store.put(id(1, 0, 0), &first_public, &first).unwrap();
+        store.put(id(1, 0, 1), &second_public, &second).unwrap();
+        drop(store);
+        let file = fs::read_to_string(&path).unwrap();
+        assert!(!file.contains(&hex::encode(first.d.to_bytes())));
+
+        let store = NonceStore::open(&path, "network key", DEFAULT_NONCE_TTL).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(spend(&store, &session(1), 1, &first_public).unwrap(), None);
+        assert_eq!(spend(&store, &session(2), 0, &first_public).unwrap(), None);
+        assert_eq!(
+            spend(&store, &session(1), 0, &first_public).unwrap(),
+            Some(first)
+        );
+        assert_eq!(spend(&store, &session(1), 0, &first_public).unwrap(), None);
+        assert_eq!(store.len(), 1);
+
+        let other_key = NonceStore::open(&path, "other key", DEFAULT_NONCE_TTL).unwrap();
+        assert!(matches!(
+            spend(&other_key, &session(1), 0, &second_public),
+            Err(Error::Decrypt(0))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn expired_nonces_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("nonce-store-ttl-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = NonceStore::open(dir.join("nonces.json"), "key", Duration::ZERO).unwrap();
+        let (nonce, public_nonce) = nonce();
+        store.put(id(1, 0, 0), &public_nonce, &nonce).unwrap();
+        assert!(store.is_empty());
+        assert_eq!(spend(&store, &session(1), 0, &public_nonce).unwrap(), None);
+        assert!(NonceStore::default().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sessions_keep_their_own_nonces() {
+        let dir = std::env::temp_dir().join(format!("nonce-store-sessions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = NonceStore::open(dir.join("nonces.json"), "key", DEFAULT_NONCE_TTL).unwrap();
+        let (first, first_public) = nonce();
+        let (second, second_public) = nonce();
+        // the same key and index in two sessions of one signing round
+        store.put(id(1, 0, 0), &first_public, &first).unwrap();
+        store.put(id(2, 0, 0), &second_public, &second).unwrap();
+        assert_eq!(store.len(), 2);
+
+        // a held session spends its own nonce, and the stored copy with it
+        let mut sessions = Sessions::default();
+        sessions.hold(
+            session(1),
+            0,
+            first_public.clone(),
+            Secret::new(first.clone()),
+        );
+        let held = store
+            .spend(&mut sessions, &session(1), 0, &first_public)
+            .unwrap();
+        assert_eq!(held.map(|nonce| (*nonce).clone()), Some(first));
+        assert_eq!(spend(&store, &session(1), 0, &first_public).unwrap(), None);
+
+        store.discard(&session(2)).unwrap();
+        assert!(store.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+//! When a signer polls the transport and sends its responses.
+//!
+//! Every signer polls the relay on the same schedule and answers a request the moment it sees
+//! it, so each round hits the relay with one burst per fleet member at once. A [`PacingPolicy`]
+//! spreads the fleet out: it adds a random delay to every poll and to every batch of responses,
+//! and polls often only while a round is active, backing off further once the signer has seen
+//! no message for `active_window_ms`.
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use serde::Deserialize;
+
+/// Poll and response timing.
+///
+/// Deserializes from a `[pacing]` table, for example
+/// ```toml
+/// [pacing]
+/// active_max_poll_ms = 128
+/// idle_max_poll_ms = 1000
+/// active_window_ms = 10000
+/// poll_jitter_ms = 50
+/// response_jitter_ms = 200
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PacingPolicy {
+    /// Longest wait between two polls while a round is active
+    pub active_max_poll_ms: u64,
+    /// Longest wait between two polls while idle
+    pub idle_max_poll_ms: u64,
+    /// Time after the last inbound message during which a round counts as active
+    pub active_window_ms: u64,
+    /// Most random delay added to each wait between polls
+    pub poll_jitter_ms: u64,
+    /// Most random delay before sending the responses to a message
+    pub response_jitter_ms: u64,
+}
+
+impl Default for PacingPolicy {
+    fn default() -> Self {
+        Self {
+            active_max_poll_ms: 128,
+            idle_max_poll_ms: 1_000,
+            active_window_ms: 10_000,
+            poll_jitter_ms: 0,
+            response_jitter_ms: 0,
+        }
+    }
+}
+
+impl PacingPolicy {
+    /// The random delay before sending responses
+    pub fn response_delay(&self) -> Duration {
+        jitter(self.response_jitter_ms)
+    }
+}
+
+/// The wait between polls: none right after a message, then doubling from
+/// [`Poller::BASE_MS`] up to the policy's active or idle maximum
+#[derive(Clone, Debug)]
+pub struct Poller {
+    policy: PacingPolicy,
+    wait_ms: u64,
+    last_message: Option<Instant>,
+}
+
+impl Poller {
+    /// Wait after the first empty poll
+    pub const BASE_MS: u64 = 2;
+
+    pub fn new(policy: PacingPolicy) -> Self {
+        Self {
+            policy,
+            wait_ms: Self::BASE_MS,
+            last_message: None,
+        }
+    }
+
+    /// Whether a message arrived within the active window before `now`
+    pub fn active(&self, now: Instant) -> bool {
+        self.last_message.is_some_and(|last| {
+            now.duration_since(last) < Duration::from_millis(self.policy.active_window_ms)
+        })
+    }
+
+    /// The wait before the next poll, given whether the last poll returned a message
+    pub fn next_wait(&mut self, received: bool, now: Instant) -> Duration {
+        if received {
+            self.last_message = Some(now);
+            self.wait_ms = 0;
+        } else {
+            let max_ms = if self.active(now) {
+                self.policy.active_max_poll_ms
+            } else {
+                self.policy.idle_max_poll_ms
+            };
+            self.wait_ms = match self.wait_ms {
+                0 => Self::BASE_MS,
+                wait_ms => wait_ms.saturating_mul(2),
+            }
+            .min(max_ms);
+        }
+        Duration::from_millis(self.wait_ms) + jitter(self.policy.poll_jitter_ms)
+    }
+}
+
+/// A delay drawn uniformly from zero to `max_ms`
+fn jitter(max_ms: u64) -> Duration {
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=max_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(wait: Duration) -> u64 {
+        wait.as_millis() as u64
+    }
+
+    #[test]
+    fn polls_back_off_further_once_idle() {
+        let mut poller = Poller::new(PacingPolicy::default());
+        let start = Instant::now();
+        assert_eq!(ms(poller.next_wait(true, start)), 0);
+        let waits: Vec<u64> = (0..10)
+            .map(|_| ms(poller.next_wait(false, start)))
+            .collect();
+        assert_eq!(waits, [2, 4, 8, 16, 32, 64, 128, 128, 128, 128]);
+
+        let idle = start + Duration::from_secs(11);
+        assert!(!poller.active(idle));
+        assert_eq!(ms(poller.next_wait(false, idle)), 256);
+        let waits: Vec<u64> = (0..4).map(|_| ms(poller.next_wait(false, idle))).collect();
+        assert_eq!(waits, [512, 1000, 1000, 1000]);
+
+        // a message makes the round active again
+        assert_eq!(ms(poller.next_wait(true, idle)), 0);
+        assert!(poller.active(idle));
+        assert_eq!(ms(poller.next_wait(false, idle)), 2);
+    }
+
+    #[test]
+    fn jitter_stays_within_its_bound() {
+        let policy = PacingPolicy {
+            poll_jitter_ms: 50,
+            response_jitter_ms: 200,
+            ..Default::default()
+        };
+        let mut poller = Poller::new(policy.clone());
+        let now = Instant::now();
+        for _ in 0..100 {
+            assert!(ms(policy.response_delay()) <= 200);
+            assert!(ms(poller.next_wait(true, now)) <= 50);
+        }
+        assert_eq!(PacingPolicy::default().response_delay(), Duration::ZERO);
+    }
+}
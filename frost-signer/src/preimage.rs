@@ -0,0 +1,51 @@
+//! Canonical encoding of the preimages hashed when signing protocol messages.
+//!
+//! Every party has to hash a message to the same bytes, whatever language it is written in and
+//! however it holds the message in memory, so [`crate::signing_round::Signable`] impls write
+//! their fields with these rules only:
+//!
+//! - integers are big-endian, at their declared width (`u32` ids, `u64` round ids)
+//! - points are 33 byte compressed encodings, scalars 32 bytes big-endian
+//! - byte strings, text and tags are prefixed with their length as a big-endian `u32`
+//! - lists are prefixed with their item count as a big-endian `u32`, items in list order
+//! - maps are lists of their entries in ascending key order
+//! - optional fields are a `0` byte if absent, otherwise a `1` byte followed by the value
+//!
+//! Each message starts with its tag, e.g. `DKG_BEGIN`, so no two message types share a preimage.
+//! The tags and the hash function are listed in [`crate::protocol`].
+use sha2::Digest;
+
+use crate::protocol::Hasher;
+
+/// Length-prefixed and tagged updates of a preimage hasher
+pub trait Preimage {
+    /// Variable length bytes, prefixed with their length
+    fn update_bytes(&mut self, bytes: &[u8]);
+
+    /// Text, prefixed with its length in bytes
+    fn update_str(&mut self, s: &str) {
+        self.update_bytes(s.as_bytes());
+    }
+
+    /// Item count of a list or map whose items are hashed next
+    fn update_len(&mut self, len: usize);
+
+    /// Presence of an optional field, hashed before its value if `present`
+    fn update_present(&mut self, present: bool);
+}
+
+impl Preimage for Hasher {
+    fn update_bytes(&mut self, bytes: &[u8]) {
+        self.update_len(bytes.len());
+        self.update(bytes);
+    }
+
+    fn update_len(&mut self, len: usize) {
+        let len = u32::try_from(len).expect("preimage fields are shorter than 4 GiB");
+        self.update(len.to_be_bytes());
+    }
+
+    fn update_present(&mut self, present: bool) {
+        self.update([present as u8]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Constants of the wire protocol, kept in one place for protocol reviews.
+//!
+//! Message preimages are hashed with [`Hasher`] under the encoding rules of [`crate::preimage`],
+//! each starting with its domain tag from [`tag`]. Every [`MessageTypes`] variant registers its
+//! tag in [`MessageTypes::tag`], whose match fails to compile for a variant without one, and the
+//! tags are checked to be distinct at compile time, so no two preimages can collide. Moving to
+//! another hash function means changing [`Hasher`] and bumping [`PROTOCOL_VERSION`].
+//!
+//! [`MessageTypes`]: crate::signing_round::MessageTypes
+//! [`MessageTypes::tag`]: crate::signing_round::MessageTypes::tag
+
+/// Version of the envelope format, bumped on incompatible changes
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Hash function of message preimages and protocol digests
+pub type Hasher = sha2::Sha256;
+
+/// Domain tags which start each preimage
+pub mod tag {
+    pub const DKG_BEGIN: &str = "DKG_BEGIN";
+    pub const DKG_PRIVATE_BEGIN: &str = "DKG_PRIVATE_BEGIN";
+    pub const DKG_END: &str = "DKG_END";
+    pub const DKG_PUBLIC_END: &str = "DKG_PUBLIC_END";
+    pub const DKG_QUERY: &str = "DKG_QUERY";
+    pub const DKG_QUERY_RESPONSE: &str = "DKG_QUERY_RESPONSE";
+    pub const DKG_PUBLIC_SHARE: &str = "DKG_PUBLIC_SHARE";
+    pub const DKG_PRIVATE_SHARES: &str = "DKG_PRIVATE_SHARES";
+    pub const DKG_SHARE_DIGESTS: &str = "DKG_SHARE_DIGESTS";
+    pub const EQUIVOCATION_REPORT: &str = "EQUIVOCATION_REPORT";
+    pub const DKG_BLAME: &str = "DKG_BLAME";
+    pub const NONCE_REQUEST: &str = "NONCE_REQUEST";
+    pub const NONCE_RESPONSE: &str = "NONCE_RESPONSE";
+    pub const SIGNATURE_SHARE_REQUEST: &str = "SIGNATURE_SHARE_REQUEST";
+    pub const SIGNATURE_SHARE_RESPONSE: &str = "SIGNATURE_SHARE_RESPONSE";
+    pub const ROUND_OUTCOME_QUERY: &str = "ROUND_OUTCOME_QUERY";
+    pub const ROUND_OUTCOME_RESPONSE: &str = "ROUND_OUTCOME_RESPONSE";
+    pub const REJECTION: &str = "REJECTION";
+    pub const HELLO: &str = "HELLO";
+    pub const ROUND_ABORT: &str = "ROUND_ABORT";
+    pub const UPGRADE_WINDOW: &str = "UPGRADE_WINDOW";
+    pub const UPGRADE_ACK: &str = "UPGRADE_ACK";
+    pub const REGISTRATION: &str = "REGISTRATION";
+    pub const BATCH_NONCE_REQUEST: &str = "BATCH_NONCE_REQUEST";
+    pub const BATCH_NONCE_RESPONSE: &str = "BATCH_NONCE_RESPONSE";
+    pub const BATCH_SIGN_REQUEST: &str = "BATCH_SIGN_REQUEST";
+    pub const BATCH_SIGN_RESPONSE: &str = "BATCH_SIGN_RESPONSE";
+    pub const MISSING_SHARE_REQUEST: &str = "MISSING_SHARE_REQUEST";
+    pub const RESHARE_BEGIN: &str = "RESHARE_BEGIN";
+    pub const RESHARE_SHARES: &str = "RESHARE_SHARES";
+    pub const RESHARE_END: &str = "RESHARE_END";
+    pub const RESHARE_COMMIT: &str = "RESHARE_COMMIT";
+    pub const ROSTER_CHANGE: &str = "ROSTER_CHANGE";
+    pub const REPAIR_SHARES: &str = "REPAIR_SHARES";
+    pub const REPAIRED_SHARES: &str = "REPAIRED_SHARES";
+    pub const ROSTER_CHANGE_END: &str = "ROSTER_CHANGE_END";
+    pub const DKG_GROUP_KEY: &str = "DKG_GROUP_KEY";
+
+    /// Digest of the DKG commitments private shares are bound to
+    pub const DKG_COMMITMENTS: &str = "DKG_COMMITMENTS";
+    /// Digest of the commitments a key share refresh is bound to, see [`crate::reshare`]
+    pub const RESHARE_COMMITMENTS: &str = "RESHARE_COMMITMENTS";
+    /// Digest of the key ids and signers behind a group key
+    pub const GROUP_KEY_ROSTER: &str = "GROUP_KEY_ROSTER";
+    /// Digest a relay envelope's sender signs
+    pub const ENVELOPE: &str = "ENVELOPE";
+    /// Fingerprint of a ceremony roster
+    pub const SIGNER_ROSTER: &str = "SIGNER_ROSTER";
+    /// Digest of the settings a signing group agrees on
+    pub const SIGNER_GROUP: &str = "SIGNER_GROUP";
+    /// Signer threshold within the [`SIGNER_GROUP`] digest
+    pub const SIGNERS_THRESHOLD: &str = "SIGNERS_THRESHOLD";
+    /// Key encrypting the secret nonces a signer stores, see [`crate::nonce_store`]
+    pub const NONCE_STORE_KEY: &str = "NONCE_STORE_KEY";
+    /// Key encrypting the key shares a signer stores, see [`crate::key_store`]
+    pub const KEY_STORE_KEY: &str = "KEY_STORE_KEY";
+    /// Key sealing the private shares sent to a signer, see [`crate::share_cipher`]
+    pub const SHARE_CIPHER_KEY: &str = "SHARE_CIPHER_KEY";
+    /// Nonce sealing the private shares sent to a signer
+    pub const SHARE_CIPHER_NONCE: &str = "SHARE_CIPHER_NONCE";
+    /// Fingerprint of a secret config value, see [`crate::redact`]
+    pub const CONFIG_FINGERPRINT: &str = "CONFIG_FINGERPRINT";
+}
+
+/// Tags of the message types, one per [`crate::signing_round::MessageTypes`] variant
+pub const MESSAGE_TAGS: &[&str] = &[
+    tag::DKG_BEGIN,
+    tag::DKG_PRIVATE_BEGIN,
+    tag::DKG_END,
+    tag::DKG_PUBLIC_END,
+    tag::DKG_QUERY,
+    tag::DKG_QUERY_RESPONSE,
+    tag::DKG_PUBLIC_SHARE,
+    tag::DKG_PRIVATE_SHARES,
+    tag::DKG_SHARE_DIGESTS,
+    tag::EQUIVOCATION_REPORT,
+    tag::DKG_BLAME,
+    tag::NONCE_REQUEST,
+    tag::NONCE_RESPONSE,
+    tag::SIGNATURE_SHARE_REQUEST,
+    tag::SIGNATURE_SHARE_RESPONSE,
+    tag::ROUND_OUTCOME_QUERY,
+    tag::ROUND_OUTCOME_RESPONSE,
+    tag::REJECTION,
+    tag::HELLO,
+    tag::ROUND_ABORT,
+    tag::UPGRADE_WINDOW,
+    tag::UPGRADE_ACK,
+    tag::REGISTRATION,
+    tag::BATCH_NONCE_REQUEST,
+    tag::BATCH_NONCE_RESPONSE,
+    tag::BATCH_SIGN_REQUEST,
+    tag::BATCH_SIGN_RESPONSE,
+    tag::MISSING_SHARE_REQUEST,
+    tag::RESHARE_BEGIN,
+    tag::RESHARE_SHARES,
+    tag::RESHARE_END,
+    tag::RESHARE_COMMIT,
+    tag::ROSTER_CHANGE,
+    tag::REPAIR_SHARES,
+    tag::REPAIRED_SHARES,
+    tag::ROSTER_CHANGE_END,
+    tag::DKG_GROUP_KEY,
+];
+
+/// Tags of the digests which are not messages
+pub const DIGEST_TAGS: &[&str] = &[
+    tag::DKG_COMMITMENTS,
+    tag::RESHARE_COMMITMENTS,
+    tag::GROUP_KEY_ROSTER,
+    tag::ENVELOPE,
+    tag::SIGNER_ROSTER,
+    tag::SIGNER_GROUP,
+    tag::SIGNERS_THRESHOLD,
+    tag::NONCE_STORE_KEY,
+    tag::KEY_STORE_KEY,
+    tag::SHARE_CIPHER_KEY,
+    tag::SHARE_CIPHER_NONCE,
+    tag::CONFIG_FINGERPRINT,
+];
+
+const _: () = assert!(distinct(MESSAGE_TAGS), "two message types share a tag");
+const _: () = assert!(distinct(DIGEST_TAGS), "two digests share a tag");
+const _: () = assert!(
+    disjoint(MESSAGE_TAGS, DIGEST_TAGS),
+    "a digest shares a message type's tag"
+);
+
+const fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn contains(tags: &[&str], tag: &str) -> bool {
+    let mut i = 0;
+    while i < tags.len() {
+        if same(tags[i], tag) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn distinct(tags: &[&str]) -> bool {
+    let mut i = 0;
+    while i < tags.len() {
+        let mut j = i + 1;
+        while j < tags.len() {
+            if same(tags[i], tags[j]) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn disjoint(a: &[&str], b: &[&str]) -> bool {
+    let mut i = 0;
+    while i < a.len() {
+        if contains(b, a[i]) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Whether `tag` is registered for a message type
+pub const fn is_message_tag(tag: &str) -> bool {
+    contains(MESSAGE_TAGS, tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_checks_catch_duplicates() {
+        assert!(!distinct(&[tag::HELLO, tag::REJECTION, tag::HELLO]));
+        assert!(!disjoint(&[tag::HELLO], &[tag::ENVELOPE, tag::HELLO]));
+        // a tag is not confused with one it prefixes
+        assert!(distinct(&[tag::DKG_END, tag::DKG_PUBLIC_END, "DKG_EN"]));
+        assert!(is_message_tag(tag::DKG_QUERY));
+        assert!(!is_message_tag(tag::DKG_COMMITMENTS));
+    }
+}
@@ -0,0 +1,177 @@
+//! Proactive refresh of key shares.
+//!
+//! A refresh round gives every key a new share of the same group secret. Each key deals a
+//! random polynomial whose constant term is zero and sends every other key its value there;
+//! adding the values received to a key's share moves the share onto a new polynomial through
+//! the same secret. The group key, and so the sBTC wallet address, stay as they are, while shares
+//! leaked before the refresh no longer combine with shares taken after it. That only holds while
+//! the values stay secret too, so each is sealed to the signer owning its key, see
+//! [`crate::share_cipher`].
+//!
+//! A signer only swaps in its refreshed shares once the coordinator confirmed that every signer
+//! refreshed from the same commitments, see [`crate::signing_round::ReshareCommit`].
+use std::collections::{BTreeMap, BTreeSet};
+
+use hashbrown::HashMap;
+use rand_core::{CryptoRng, RngCore};
+use sha2::Digest;
+use wtfrost::{compute, v1, Point, Scalar};
+
+use crate::preimage::Preimage;
+use crate::protocol::{tag, Hasher};
+use crate::roster;
+use crate::secret::{Secret, Wipe};
+
+/// Coefficients of a random polynomial of `threshold` terms whose constant term is zero
+pub fn zero_polynomial<RNG: RngCore + CryptoRng>(threshold: usize, rng: &mut RNG) -> Vec<Scalar> {
+    let mut polynomial = Vec::with_capacity(threshold);
+    polynomial.push(Scalar::from(0));
+    polynomial.extend((1..threshold).map(|_| Scalar::random(rng)));
+    polynomial
+}
+
+/// Public commitment to each coefficient of `polynomial`
+pub fn commit(polynomial: &[Scalar]) -> Vec<Point> {
+    polynomial.iter().map(|a| Point::from(*a)).collect()
+}
+
+/// Value of `polynomial` at the x coordinate of key `key_id`
+pub fn evaluate(polynomial: &[Scalar], key_id: usize) -> Scalar {
+    let x = compute::id(key_id);
+    polynomial
+        .iter()
+        .rev()
+        .fold(Scalar::from(0), |acc, a| acc * x + *a)
+}
+
+/// Whether `commitment` commits to a polynomial of `threshold` terms with a zero constant
+/// term, i.e. one which leaves the group key alone
+pub fn is_refresh_commitment(commitment: &[Point], threshold: usize) -> bool {
+    commitment.len() == threshold && commitment.first() == Some(&Point::default())
+}
+
+/// Digest binding the refresh commitments of refresh `reshare_id` of generation `dkg_id`.
+/// Signers report it when they end the refresh, and the coordinator only confirms the
+/// refresh if they all refreshed from the same commitments.
+pub fn refresh_digest(
+    dkg_id: u64,
+    reshare_id: u64,
+    commitments: &BTreeMap<u32, Vec<Point>>,
+) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update_str(tag::RESHARE_COMMITMENTS);
+    hasher.update(dkg_id.to_be_bytes());
+    hasher.update(reshare_id.to_be_bytes());
+    hasher.update_len(commitments.len());
+    for (key_id, commitment) in commitments {
+        hasher.update(key_id.to_be_bytes());
+        hasher.update_len(commitment.len());
+        for a in commitment {
+            hasher.update(a.compress().as_bytes());
+        }
+    }
+    hasher.finalize().into()
+}
+
+/// Add `delta` to the private key of `party`, updating its public key to match
+pub fn add_to_private_key(party: &mut v1::Party, delta: &Scalar) -> Result<(), serde_json::Error> {
+    let private_key = Secret::new(party.save()).private_key;
+    let private_key = Secret::new(private_key + *delta);
+    let group_key = party.group_key;
+    roster::set_keys(party, &private_key, &group_key)
+}
+
+/// A refresh round in progress on a signer
+pub struct Reshare {
+    pub reshare_id: u64,
+    /// Refresh commitment of each key id heard from, ours included
+    pub commitments: BTreeMap<u32, Vec<Point>>,
+    /// Sum of the refresh shares received so far, by our key id
+    pub deltas: HashMap<usize, Scalar>,
+    /// Key ids whose refresh shares to us do not match their commitments
+    pub bad_shares: BTreeSet<u32>,
+    /// [`refresh_digest`] sent in our ReshareEnd, set once every key was heard from
+    pub digest: Option<[u8; 32]>,
+}
+
+impl Reshare {
+    pub fn new(reshare_id: u64) -> Self {
+        Self {
+            reshare_id,
+            commitments: BTreeMap::new(),
+            deltas: HashMap::new(),
+            bad_shares: BTreeSet::new(),
+            digest: None,
+        }
+    }
+}
+
+impl Drop for Reshare {
+    fn drop(&mut self) {
+        self.deltas.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+
+    #[test]
+    fn refreshed_shares_keep_the_secret() {
+        let mut rng = OsRng;
+        let threshold = 3;
+        let keys: Vec<usize> = (0..5).collect();
+        let polynomials: Vec<Vec<Scalar>> = keys
+            .iter()
+            .map(|_| zero_polynomial(threshold, &mut rng))
+            .collect();
+        for polynomial in &polynomials {
+            assert!(is_refresh_commitment(&commit(polynomial), threshold));
+            // the commitment checks out against the dealt values
+            let commitment = commit(polynomial);
+            for key_id in &keys {
+                let x = compute::id(*key_id);
+                let expected = commitment
+                    .iter()
+                    .rev()
+                    .fold(Point::default(), |acc, a| x * acc + *a);
+                assert_eq!(Point::from(evaluate(polynomial, *key_id)), expected);
+            }
+        }
+        // any threshold of the summed deltas interpolates to zero at x = 0
+        let signers = vec![0, 2, 4];
+        let zero = signers.iter().fold(Scalar::from(0), |acc, key_id| {
+            let delta = polynomials
+                .iter()
+                .fold(Scalar::from(0), |sum, p| sum + evaluate(p, *key_id));
+            acc + compute::lambda(*key_id, &signers) * delta
+        });
+        assert_eq!(zero, Scalar::from(0));
+    }
+
+    #[test]
+    fn private_key_moves_by_delta() {
+        let mut rng = OsRng;
+        let mut party = v1::Party::new(0, 3, 2, &mut rng);
+        let before = party.save().private_key;
+        let delta = Scalar::random(&mut rng);
+        add_to_private_key(&mut party, &delta).unwrap();
+        assert_eq!(party.save().private_key, before + delta);
+        assert_eq!(party.public_key, Point::from(before + delta));
+        assert_eq!(party.id, 0);
+    }
+
+    #[test]
+    fn refresh_digest_binds_the_commitments() {
+        let mut rng = OsRng;
+        let commitments: BTreeMap<u32, Vec<Point>> = (0..3)
+            .map(|key_id| (key_id, commit(&zero_polynomial(2, &mut rng))))
+            .collect();
+        let digest = refresh_digest(1, 1, &commitments);
+        assert_ne!(digest, refresh_digest(1, 2, &commitments));
+        let mut other = commitments.clone();
+        other.insert(1, commit(&zero_polynomial(2, &mut rng)));
+        assert_ne!(digest, refresh_digest(1, 1, &other));
+    }
+}
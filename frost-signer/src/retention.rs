@@ -0,0 +1,243 @@
+//! Age and size limits on persisted artifacts, enforced in the background.
+//!
+//! Each store which grows without bound, such as the coordinator journal or the incident
+//! log, implements [`Artifact`]. A `[retention]` config table limits each artifact type by
+//! name, and [`Retention::spawn`] prunes the store on a background thread at every interval,
+//! keeping the usage it measured for status reports. Only entries the store no longer needs,
+//! e.g. closed rounds or resolved incidents, are ever pruned.
+//!
+//! ```toml
+//! [retention]
+//! interval_secs = 3600
+//!
+//! [retention.journal]
+//! max_age_secs = 604800
+//! max_bytes = 104857600
+//! ```
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Seconds between pruning passes unless configured
+const DEFAULT_INTERVAL_SECS: u64 = 3600;
+
+/// Limits on one artifact type, none if unset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct RetentionLimits {
+    /// Prune entries once they are this many seconds old
+    #[serde(default)]
+    pub max_age_secs: Option<u64>,
+    /// Prune the oldest entries while the artifact holds more bytes than this
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+}
+
+/// A `[retention]` config table
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct RetentionPolicy {
+    /// Seconds between pruning passes, 3600 if unset
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+    /// Limits of each artifact type by name, e.g. `[retention.journal]`
+    #[serde(flatten)]
+    pub limits: BTreeMap<String, RetentionLimits>,
+}
+
+impl RetentionPolicy {
+    /// The limits configured for `artifact`, if any
+    pub fn limits(&self, artifact: &str) -> Option<RetentionLimits> {
+        self.limits.get(artifact).copied()
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs.unwrap_or(DEFAULT_INTERVAL_SECS))
+    }
+}
+
+/// Disk usage of one artifact type
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    /// Entries stored, prunable or not
+    pub entries: u64,
+    /// Bytes of payload the entries hold, not counting database overhead
+    pub bytes: u64,
+    /// Unix time in seconds of the oldest prunable entry
+    pub oldest: Option<u64>,
+}
+
+/// A store whose entries can be pruned
+pub trait Artifact {
+    type Error: std::fmt::Display;
+
+    /// Name the limits of the artifact are configured under
+    fn name(&self) -> &'static str;
+
+    fn usage(&self) -> Result<Usage, Self::Error>;
+
+    /// Delete the prunable entries older than `before`, in unix seconds, then the oldest ones
+    /// left while more than `max_bytes` are stored, returning how many were deleted
+    fn prune(&self, before: Option<u64>, max_bytes: Option<u64>) -> Result<u64, Self::Error>;
+}
+
+/// Enforce `limits` on `artifact` once, returning the entries deleted and the usage left
+pub fn prune<A: Artifact>(artifact: &A, limits: RetentionLimits) -> Result<(u64, Usage), A::Error> {
+    let before = limits.max_age_secs.map(|secs| now().saturating_sub(secs));
+    let pruned = artifact.prune(before, limits.max_bytes)?;
+    Ok((pruned, artifact.usage()?))
+}
+
+/// The usage each pruned artifact had after its last pass
+#[derive(Clone, Debug, Default)]
+pub struct Retention {
+    usage: Arc<Mutex<BTreeMap<&'static str, Usage>>>,
+}
+
+impl Retention {
+    pub fn usage(&self) -> BTreeMap<&'static str, Usage> {
+        match self.usage.lock() {
+            Ok(usage) => usage.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    fn record(&self, name: &'static str, usage: Usage) {
+        match self.usage.lock() {
+            Ok(mut map) => map.insert(name, usage),
+            Err(poisoned) => poisoned.into_inner().insert(name, usage),
+        };
+    }
+
+    /// Prune `artifact` by `limits` every `interval` on a background thread, the first time
+    /// right away
+    pub fn spawn<A>(
+        &self,
+        artifact: A,
+        limits: RetentionLimits,
+        interval: Duration,
+    ) -> JoinHandle<()>
+    where
+        A: Artifact + Send + 'static,
+    {
+        let retention = self.clone();
+        thread::spawn(move || loop {
+            match prune(&artifact, limits) {
+                Ok((pruned, usage)) => {
+                    info!(
+                        "{}: pruned {} entries, {} left holding {} bytes",
+                        artifact.name(),
+                        pruned,
+                        usage.entries,
+                        usage.bytes
+                    );
+                    retention.record(artifact.name(), usage);
+                }
+                Err(e) => warn!("Pruning {} failed: {}", artifact.name(), e),
+            }
+            thread::sleep(interval);
+        })
+    }
+}
+
+/// Unix time in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Entries as (unix time, bytes), oldest first
+    struct Entries(RefCell<Vec<(u64, u64)>>);
+
+    impl Artifact for Entries {
+        type Error = String;
+
+        fn name(&self) -> &'static str {
+            "entries"
+        }
+
+        fn usage(&self) -> Result<Usage, String> {
+            let entries = self.0.borrow();
+            Ok(Usage {
+                entries: entries.len() as u64,
+                bytes: entries.iter().map(|(_, bytes)| bytes).sum(),
+                oldest: entries.first().map(|(time, _)| *time),
+            })
+        }
+
+        fn prune(&self, before: Option<u64>, max_bytes: Option<u64>) -> Result<u64, String> {
+            let mut entries = self.0.borrow_mut();
+            let len = entries.len();
+            entries.retain(|(time, _)| !matches!(before, Some(before) if *time < before));
+            while entries.iter().map(|(_, bytes)| bytes).sum::<u64>()
+                > max_bytes.unwrap_or(u64::MAX)
+            {
+                entries.remove(0);
+            }
+            Ok((len - entries.len()) as u64)
+        }
+    }
+
+    #[test]
+    fn prunes_by_age_then_size() {
+        let now = now();
+        let entries = Entries(RefCell::new(vec![
+            (now - 100, 10),
+            (now - 50, 10),
+            (now - 20, 10),
+            (now, 10),
+        ]));
+        let unlimited = RetentionLimits::default();
+        assert_eq!(prune(&entries, unlimited).unwrap().0, 0);
+
+        let limits = RetentionLimits {
+            max_age_secs: Some(60),
+            max_bytes: Some(25),
+        };
+        let (pruned, usage) = prune(&entries, limits).unwrap();
+        assert_eq!(pruned, 2);
+        assert_eq!(
+            usage,
+            Usage {
+                entries: 2,
+                bytes: 20,
+                oldest: Some(now - 20),
+            }
+        );
+    }
+
+    #[test]
+    fn reads_limits_by_artifact_name() {
+        let policy: RetentionPolicy = toml::from_str(
+            r#"
+interval_secs = 60
+
+[journal]
+max_age_secs = 3600
+"#,
+        )
+        .unwrap();
+        assert_eq!(policy.interval(), Duration::from_secs(60));
+        assert_eq!(
+            policy.limits("journal"),
+            Some(RetentionLimits {
+                max_age_secs: Some(3600),
+                max_bytes: None,
+            })
+        );
+        assert_eq!(policy.limits("incidents"), None);
+        assert_eq!(
+            RetentionPolicy::default().interval(),
+            Duration::from_secs(DEFAULT_INTERVAL_SECS)
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::thread;
+use std::time::Duration;
+
+use rand::Rng;
+use serde::Deserialize;
+use tracing::debug;
+
+/// How long to wait between attempts
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Backoff {
+    /// Wait the same interval between every attempt
+    Fixed { interval_ms: u64 },
+    /// Multiply the interval by `multiplier` after every attempt, up to `max_ms`
+    Exponential {
+        initial_ms: u64,
+        max_ms: u64,
+        multiplier: f64,
+    },
+    /// Exponential backoff where each delay is drawn uniformly from zero to the exponential delay
+    Jittered {
+        initial_ms: u64,
+        max_ms: u64,
+        multiplier: f64,
+    },
+}
+
+/// Whether a failed attempt is worth repeating
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Retry {
+    /// The failure may go away on its own, e.g. a timeout or a 5xx response
+    Transient,
+    /// Repeating the attempt will fail the same way, e.g. a malformed request
+    Permanent,
+}
+
+/// Errors which can classify themselves for a [`RetryPolicy`]
+pub trait Classify {
+    fn classify(&self) -> Retry;
+}
+
+/// Retry configuration shared by the relay, node clients and broadcasters.
+///
+/// Deserializes from a `[retry]` table, for example
+/// ```toml
+/// [retry]
+/// max_attempts = 5
+/// backoff = { kind = "jittered", initial_ms = 100, max_ms = 5000, multiplier = 2.0 }
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff: Backoff::Jittered {
+                initial_ms: 100,
+                max_ms: 5000,
+                multiplier: 2.0,
+            },
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy which makes a single attempt
+    pub fn never() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: Backoff::Fixed { interval_ms: 0 },
+        }
+    }
+
+    /// The delay to wait after the given failed attempt (counting from 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = |initial_ms: u64, max_ms: u64, multiplier: f64| {
+            let exponent = attempt.saturating_sub(1) as i32;
+            let ms = initial_ms as f64 * multiplier.powi(exponent);
+            ms.min(max_ms as f64) as u64
+        };
+        let ms = match self.backoff {
+            Backoff::Fixed { interval_ms } => interval_ms,
+            Backoff::Exponential {
+                initial_ms,
+                max_ms,
+                multiplier,
+            } => exponential(initial_ms, max_ms, multiplier),
+            Backoff::Jittered {
+                initial_ms,
+                max_ms,
+                multiplier,
+            } => rand::thread_rng().gen_range(0..=exponential(initial_ms, max_ms, multiplier)),
+        };
+        Duration::from_millis(ms)
+    }
+
+    /// Run `op` until it succeeds, fails permanently or runs out of attempts
+    pub fn retry<T, E, F>(&self, op: F) -> Result<T, E>
+    where
+        E: Classify,
+        F: FnMut() -> Result<T, E>,
+    {
+        self.retry_if(op, |e: &E| e.classify() == Retry::Transient)
+    }
+
+    /// Like [`RetryPolicy::retry`], using `is_transient` to classify failures
+    pub fn retry_if<T, E, F, P>(&self, mut op: F, is_transient: P) -> Result<T, E>
+    where
+        F: FnMut() -> Result<T, E>,
+        P: Fn(&E) -> bool,
+    {
+        let mut attempt = 1;
+        loop {
+            match op() {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = self.delay(attempt);
+                    debug!("Attempt {} failed, retrying in {:?}", attempt, delay);
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct TestError(Retry);
+
+    impl Classify for TestError {
+        fn classify(&self) -> Retry {
+            self.0
+        }
+    }
+
+    fn fixed(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            backoff: Backoff::Fixed { interval_ms: 0 },
+        }
+    }
+
+    #[test]
+    fn retries_transient_errors_until_success() {
+        let calls = Cell::new(0);
+        let result = fixed(5).retry(|| {
+            calls.set(calls.get() + 1);
+            if calls.get() < 3 {
+                Err(TestError(Retry::Transient))
+            } else {
+                Ok(calls.get())
+            }
+        });
+        assert_eq!(result, Ok(3));
+    }
+
+    #[test]
+    fn stops_at_max_attempts() {
+        let calls = Cell::new(0);
+        let result: Result<(), _> = fixed(4).retry(|| {
+            calls.set(calls.get() + 1);
+            Err(TestError(Retry::Transient))
+        });
+        assert_eq!(result, Err(TestError(Retry::Transient)));
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn does_not_retry_permanent_errors() {
+        let calls = Cell::new(0);
+        let result: Result<(), _> = fixed(4).retry(|| {
+            calls.set(calls.get() + 1);
+            Err(TestError(Retry::Permanent))
+        });
+        assert_eq!(result, Err(TestError(Retry::Permanent)));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn exponential_delay_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            backoff: Backoff::Exponential {
+                initial_ms: 10,
+                max_ms: 50,
+                multiplier: 2.0,
+            },
+        };
+        let delays: Vec<u64> = (1..=5)
+            .map(|attempt| policy.delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![10, 20, 40, 50, 50]);
+    }
+
+    #[test]
+    fn jittered_delay_stays_below_exponential_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            backoff: Backoff::Jittered {
+                initial_ms: 10,
+                max_ms: 50,
+                multiplier: 2.0,
+            },
+        };
+        for attempt in 1..=5 {
+            assert!(policy.delay(attempt) <= Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn deserialize_from_toml() {
+        let policy: RetryPolicy = toml::from_str(
+            r#"
+            max_attempts = 3
+            backoff = { kind = "fixed", interval_ms = 250 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.max_attempts, 3);
+        assert_eq!(policy.delay(2), Duration::from_millis(250));
+    }
+}
@@ -0,0 +1,196 @@
+//! Changes to the signers of a generation which keep its group key.
+//!
+//! Removing a signer only takes the others to stop counting its keys: the coordinator
+//! announces the new roster in a [`RosterChange`], then refreshes the remaining shares (see
+//! [`crate::reshare`]) so the shares the removed signer kept no longer combine with theirs.
+//!
+//! Adding a signer, one left out of the generation's DKG or removed since, rebuilds the shares
+//! of its keys from the shares of `threshold` helper keys. Helper key `i` holds `f(i)` of the
+//! group polynomial `f`, and the share of key `j` is the sum of `lambda_i(j) * f(i)` over the
+//! helpers, `lambda_i(j)` being the Lagrange coefficient of `i` at the x coordinate of `j`.
+//! Each helper signer splits its terms into random pieces, one per helper signer, so no helper
+//! learns another's term. Each helper signer then sends the joining signer the sum of the
+//! pieces it received, and the joining signer checks the sum of those against the commitment
+//! to `f` before taking it as its share. Pieces and sums cross the relay sealed to their
+//! recipient, see [`crate::share_cipher`]; a signer without a share cipher sends them in the
+//! clear, where anyone seeing every message of the repair can add up the joining shares.
+//!
+//! When the keys left cannot meet the thresholds, or the repair fails, the coordinator runs
+//! DKG over the new roster instead, and the wallet has to move to the new group key.
+//!
+//! [`RosterChange`]: crate::signing_round::RosterChange
+use std::collections::{BTreeMap, BTreeSet};
+
+use hashbrown::HashMap;
+use rand_core::{CryptoRng, RngCore};
+use wtfrost::{common::PolyCommitment, compute, v1, Point, Scalar};
+
+use crate::secret::{Secret, Wipe};
+
+/// Lagrange coefficient of key `key_id` over the keys `helpers`, at the x coordinate of key
+/// `at`
+pub fn lambda_at(key_id: usize, helpers: &[usize], at: usize) -> Scalar {
+    let x = compute::id(at);
+    let x_i = compute::id(key_id);
+    helpers
+        .iter()
+        .filter(|helper| **helper != key_id)
+        .fold(Scalar::from(1), |lambda, helper| {
+            let x_m = compute::id(*helper);
+            lambda * ((x - x_m) / (x_i - x_m))
+        })
+}
+
+/// `n` random scalars adding up to `value`
+pub fn split<RNG: RngCore + CryptoRng>(value: &Scalar, n: usize, rng: &mut RNG) -> Vec<Scalar> {
+    let mut pieces: Vec<Scalar> = (1..n).map(|_| Scalar::random(rng)).collect();
+    let rest = pieces.iter().fold(*value, |rest, piece| rest - *piece);
+    pieces.push(rest);
+    pieces
+}
+
+/// Commitment to the group polynomial of `threshold` terms: the sum of every key's DKG
+/// commitment. Its first term is the group key.
+pub fn group_commitment<'a>(
+    commitments: impl IntoIterator<Item = &'a PolyCommitment>,
+    threshold: usize,
+) -> Vec<Point> {
+    let mut group = vec![Point::default(); threshold];
+    for commitment in commitments {
+        for (term, a) in group.iter_mut().zip(&commitment.A) {
+            *term += *a;
+        }
+    }
+    group
+}
+
+/// Give `party` the private key `private_key` of the group key `group_key`. wtfrost gives no
+/// way to set a party's keys, so the party is rebuilt from its serialized form.
+pub fn set_keys(
+    party: &mut v1::Party,
+    private_key: &Scalar,
+    group_key: &Point,
+) -> Result<(), serde_json::Error> {
+    let mut fields = serde_json::to_value(&*party)?;
+    fields["private_key"] = serde_json::to_value(*private_key)?;
+    fields["group_key"] = serde_json::to_value(*group_key)?;
+    let mut rebuilt: v1::Party = serde_json::from_value(fields)?;
+    rebuilt.public_key = Point::from(*private_key);
+    *party = rebuilt;
+    Ok(())
+}
+
+/// A repair of key shares in progress on a signer, as a helper or as the signer joining
+pub struct Repair {
+    pub change_id: u64,
+    /// Key ids whose shares are rebuilt
+    pub repaired_key_ids: BTreeSet<u32>,
+    /// Key ids whose shares rebuild them
+    pub helper_key_ids: Vec<usize>,
+    /// Signers owning the helper keys
+    pub helpers: BTreeSet<u32>,
+    /// Commitment to the group polynomial the rebuilt shares are checked against
+    pub group_commitment: Vec<Point>,
+    /// Helpers heard from, in the phase this signer takes part in
+    pub heard: BTreeSet<u32>,
+    /// Sum of what was received so far, by repaired key id
+    pub sums: HashMap<u32, Scalar>,
+}
+
+impl Repair {
+    pub fn new(
+        change_id: u64,
+        repaired_key_ids: BTreeSet<u32>,
+        key_owners: &BTreeMap<u32, u32>,
+        helper_key_ids: &[u32],
+        group_commitment: Vec<Point>,
+    ) -> Self {
+        Self {
+            change_id,
+            repaired_key_ids,
+            helper_key_ids: helper_key_ids.iter().map(|id| *id as usize).collect(),
+            helpers: helper_key_ids
+                .iter()
+                .filter_map(|key_id| key_owners.get(key_id).copied())
+                .collect(),
+            group_commitment,
+            heard: BTreeSet::new(),
+            sums: HashMap::new(),
+        }
+    }
+
+    /// Add `value` to what was received for key `key_id`
+    pub fn add(&mut self, key_id: u32, value: &Scalar) {
+        let sum = self.sums.entry(key_id).or_insert_with(Scalar::default);
+        *sum = *sum + *value;
+    }
+
+    /// Whether every helper was heard from
+    pub fn complete(&self) -> bool {
+        self.heard.len() == self.helpers.len()
+    }
+
+    /// This signer's term of the share of key `at`: the sum of `lambda_i(at) * f(i)` over
+    /// `parties`, the helper keys it owns
+    pub fn term(&self, parties: &[&v1::Party], at: u32) -> Scalar {
+        parties.iter().fold(Scalar::from(0), |term, party| {
+            let private_key = Secret::new(party.save()).private_key;
+            term + lambda_at(party.id, &self.helper_key_ids, at as usize) * private_key
+        })
+    }
+}
+
+impl Drop for Repair {
+    fn drop(&mut self) {
+        self.sums.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reshare;
+    use rand_core::OsRng;
+
+    #[test]
+    fn helpers_rebuild_a_share_from_split_terms() {
+        let mut rng = OsRng;
+        let threshold = 3;
+        let polynomial: Vec<Scalar> = (0..threshold).map(|_| Scalar::random(&mut rng)).collect();
+        let commitment = reshare::commit(&polynomial);
+        let helpers = vec![0, 2, 5];
+        let repaired = 4;
+        // every helper splits its term among the helpers, which each add up what they got
+        let mut sums = vec![Scalar::from(0); helpers.len()];
+        for helper in &helpers {
+            let term =
+                lambda_at(*helper, &helpers, repaired) * reshare::evaluate(&polynomial, *helper);
+            for (sum, piece) in sums.iter_mut().zip(split(&term, helpers.len(), &mut rng)) {
+                *sum = *sum + piece;
+            }
+        }
+        let share = sums.iter().fold(Scalar::from(0), |share, sum| share + *sum);
+        assert_eq!(share, reshare::evaluate(&polynomial, repaired));
+        assert!(crate::signing_round::share_matches_points(
+            &commitment,
+            repaired,
+            &share
+        ));
+        // at a helper's own x coordinate only its own term counts
+        assert_eq!(lambda_at(2, &helpers, 2), Scalar::from(1));
+        assert_eq!(lambda_at(0, &helpers, 2), Scalar::from(0));
+    }
+
+    #[test]
+    fn set_keys_replaces_the_private_and_group_keys() {
+        let mut rng = OsRng;
+        let mut party = v1::Party::new(3, 4, 2, &mut rng);
+        let private_key = Scalar::random(&mut rng);
+        let group_key = Point::from(Scalar::random(&mut rng));
+        set_keys(&mut party, &private_key, &group_key).unwrap();
+        assert_eq!(party.save().private_key, private_key);
+        assert_eq!(party.public_key, Point::from(private_key));
+        assert_eq!(party.group_key, group_key);
+        assert_eq!(party.id, 3);
+    }
+}
@@ -0,0 +1,233 @@
+use hashbrown::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use p256k1::scalar::Scalar;
+use serde::{Deserialize, Deserializer};
+use wtfrost::{common::Nonce, v1};
+use zeroize::Zeroize;
+
+/// Secret material which can overwrite itself in place
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl Wipe for Scalar {
+    fn wipe(&mut self) {
+        // volatile so the store is not optimized away right before the value is freed
+        unsafe { ptr::write_volatile(self, Scalar::new()) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Wipe for Vec<u8> {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<const N: usize> Wipe for [u8; N] {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<K: Eq + Hash> Wipe for HashMap<K, Scalar> {
+    fn wipe(&mut self) {
+        self.values_mut().for_each(Wipe::wipe);
+        self.clear();
+    }
+}
+
+impl Wipe for Nonce {
+    fn wipe(&mut self) {
+        self.d.wipe();
+        self.e.wipe();
+    }
+}
+
+impl Wipe for v1::PartyState {
+    fn wipe(&mut self) {
+        self.polynomial.iter_mut().for_each(Wipe::wipe);
+        self.private_key.wipe();
+        if let Some(nonce) = self.nonce.as_mut() {
+            nonce.wipe();
+        }
+    }
+}
+
+/// Owns secret material and wipes it when dropped.
+/// Only shared access is given out so the backing buffer never moves while it is locked.
+pub struct Secret<T: Wipe> {
+    value: T,
+    /// Address and length of the mlocked buffer, if any
+    locked: Option<(usize, usize)>,
+}
+
+/// Private shares received from one party, keyed by destination key id
+pub type SecretShares = Secret<HashMap<usize, Scalar>>;
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            locked: None,
+        }
+    }
+}
+
+impl Secret<String> {
+    /// Lock the backing buffer into RAM so it is never written to swap
+    pub fn mlock(&mut self) -> std::io::Result<()> {
+        if self.locked.is_none() && self.value.capacity() > 0 {
+            memory::lock(self.value.as_ptr(), self.value.capacity())?;
+            self.locked = Some((self.value.as_ptr() as usize, self.value.capacity()));
+        }
+        Ok(())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.is_some()
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.value.wipe();
+        if let Some((addr, len)) = self.locked {
+            memory::unlock(addr as *const u8, len);
+        }
+    }
+}
+
+impl<T: Wipe> Deref for Secret<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Wipe + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<T: Wipe + Default> Default for Secret<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Wipe> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl<'de, T: Wipe + Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl<T: Wipe> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+#[cfg(unix)]
+mod memory {
+    pub fn lock(ptr: *const u8, len: usize) -> std::io::Result<()> {
+        if unsafe { libc::mlock(ptr as *const libc::c_void, len) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    pub fn unlock(ptr: *const u8, len: usize) {
+        unsafe { libc::munlock(ptr as *const libc::c_void, len) };
+    }
+}
+
+#[cfg(not(unix))]
+mod memory {
+    pub fn lock(_ptr: *const u8, _len: usize) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    pub fn unlock(_ptr: *const u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct Probe(Rc<Cell<bool>>);
+
+    impl Wipe for Probe {
+        fn wipe(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    #[test]
+    fn drop_wipes_secret() {
+        let wiped = Rc::new(Cell::new(false));
+        let secret = Secret::new(Probe(wiped.clone()));
+        assert!(!wiped.get());
+        drop(secret);
+        assert!(wiped.get());
+    }
+
+    #[test]
+    fn wipe_clears_scalar_shares() {
+        let mut shares: HashMap<usize, Scalar> = HashMap::new();
+        shares.insert(0, Scalar::from(7u32));
+        shares.insert(1, Scalar::from(11u32));
+
+        let mut share = shares[&0];
+        share.wipe();
+        assert_eq!(share, Scalar::new());
+
+        shares.wipe();
+        assert!(shares.is_empty());
+    }
+
+    #[test]
+    fn wipe_clears_private_key() {
+        let mut key = String::from("private key");
+        key.wipe();
+        assert!(key.is_empty());
+    }
+
+    #[test]
+    fn debug_is_redacted() {
+        let secret = Secret::new(String::from("private key"));
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+    }
+
+    #[test]
+    fn deserialize_secret_string() {
+        #[derive(Deserialize)]
+        struct Keys {
+            key: Secret<String>,
+        }
+        let keys: Keys = toml::from_str(r#"key = "private key""#).unwrap();
+        assert_eq!(keys.key.as_str(), "private key");
+    }
+}
@@ -0,0 +1,199 @@
+//! Signing sessions a signer has handed out nonces for.
+//!
+//! The coordinator may sign several messages at once, e.g. the fulfillments of several peg-outs,
+//! each in a session of its own named by `(dkg_id, sign_id, correlation_id)`. The secret nonces a
+//! signer hands out for a session are held here rather than in its parties, which only hold the
+//! nonce they generated last, so that a nonce request for one session does not replace the nonces
+//! another session is about to sign with. Each nonce signs once: it is taken from its session
+//! when it does, and a session is closed once every nonce in it is spent. A signer holds at most
+//! [`MAX_SESSIONS`] sessions, closing the oldest to open another.
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use wtfrost::common::{Nonce, PublicNonce};
+
+use crate::secret::Secret;
+
+/// Sessions a signer holds nonces for at once
+pub const MAX_SESSIONS: usize = 64;
+
+/// Names a signing session
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SessionId {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    /// Tells apart sessions the coordinator runs concurrently under one `sign_id`
+    pub correlation_id: u64,
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "dkg #{} sign #{} correlation #{}",
+            self.dkg_id, self.sign_id, self.correlation_id
+        )
+    }
+}
+
+struct Session {
+    /// When the session was opened, in the order of [`Sessions::opened`]
+    opened: u64,
+    /// Secret nonces handed out and not yet spent, by key id
+    nonces: BTreeMap<u32, Vec<(PublicNonce, Secret<Nonce>)>>,
+}
+
+/// Secret nonces of the signing sessions in flight
+#[derive(Default)]
+pub struct Sessions {
+    sessions: BTreeMap<SessionId, Session>,
+    opened: u64,
+}
+
+impl Sessions {
+    /// Start `id` afresh, dropping the nonces it held from an earlier nonce request
+    pub fn open(&mut self, id: SessionId) {
+        if self.sessions.remove(&id).is_none() && self.sessions.len() >= MAX_SESSIONS {
+            let oldest = self
+                .sessions
+                .iter()
+                .min_by_key(|(_, session)| session.opened)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.sessions.remove(&oldest);
+            }
+        }
+        self.opened += 1;
+        self.sessions.insert(
+            id,
+            Session {
+                opened: self.opened,
+                nonces: BTreeMap::new(),
+            },
+        );
+    }
+
+    /// Hold the secret `nonce` handed out by `key_id` in session `id`, opening it if needed
+    pub fn hold(&mut self, id: SessionId, key_id: u32, public: PublicNonce, nonce: Secret<Nonce>) {
+        if !self.sessions.contains_key(&id) {
+            self.open(id);
+        }
+        if let Some(session) = self.sessions.get_mut(&id) {
+            session
+                .nonces
+                .entry(key_id)
+                .or_default()
+                .push((public, nonce));
+        }
+    }
+
+    /// Spend the secret half of `public`, handed out by `key_id` in session `id`
+    pub fn take(
+        &mut self,
+        id: &SessionId,
+        key_id: u32,
+        public: &PublicNonce,
+    ) -> Option<Secret<Nonce>> {
+        let session = self.sessions.get_mut(id)?;
+        let held = session.nonces.get_mut(&key_id)?;
+        let position = held.iter().position(|(held, _)| held == public)?;
+        let (_, nonce) = held.remove(position);
+        if held.is_empty() {
+            session.nonces.remove(&key_id);
+        }
+        if session.nonces.is_empty() {
+            self.sessions.remove(id);
+        }
+        Some(nonce)
+    }
+
+    /// Whether session `id` is open, so that its nonces are held here
+    pub fn holds(&self, id: &SessionId) -> bool {
+        self.sessions.contains_key(id)
+    }
+
+    /// Close every session of signing round `sign_id` of `dkg_id`, e.g. once it is aborted
+    pub fn close(&mut self, dkg_id: u64, sign_id: u64) {
+        self.sessions
+            .retain(|id, _| (id.dkg_id, id.sign_id) != (dkg_id, sign_id));
+    }
+
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+
+    /// Sessions holding nonces, in order of their ids
+    pub fn ids(&self) -> Vec<SessionId> {
+        self.sessions.keys().copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+    use wtfrost::{Point, Scalar};
+
+    fn nonce() -> (PublicNonce, Secret<Nonce>) {
+        let nonce = Nonce {
+            d: Scalar::random(&mut OsRng),
+            e: Scalar::random(&mut OsRng),
+        };
+        let public_nonce = PublicNonce {
+            D: Point::from(nonce.d),
+            E: Point::from(nonce.e),
+        };
+        (public_nonce, Secret::new(nonce))
+    }
+
+    fn session(correlation_id: u64) -> SessionId {
+        SessionId {
+            dkg_id: 1,
+            sign_id: 1,
+            correlation_id,
+        }
+    }
+
+    #[test]
+    fn nonces_are_spent_from_their_own_session_once() {
+        let mut sessions = Sessions::default();
+        let (public_a, secret_a) = nonce();
+        let (public_b, secret_b) = nonce();
+        sessions.hold(session(1), 0, public_a.clone(), secret_a);
+        sessions.hold(session(2), 0, public_b.clone(), secret_b);
+        assert_eq!(sessions.ids(), vec![session(1), session(2)]);
+
+        assert!(sessions.take(&session(1), 0, &public_b).is_none());
+        assert!(sessions.take(&session(1), 0, &public_a).is_some());
+        assert!(sessions.take(&session(1), 0, &public_a).is_none());
+        // spent sessions are closed, the others are left alone
+        assert_eq!(sessions.ids(), vec![session(2)]);
+
+        // reopening a session drops the nonces of its earlier nonce request
+        sessions.open(session(2));
+        assert!(sessions.take(&session(2), 0, &public_b).is_none());
+        sessions.close(1, 1);
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn the_oldest_session_makes_way_for_a_new_one() {
+        let mut sessions = Sessions::default();
+        for correlation_id in 0..=MAX_SESSIONS as u64 {
+            let (public, secret) = nonce();
+            sessions.hold(session(correlation_id), 0, public, secret);
+        }
+        assert_eq!(sessions.len(), MAX_SESSIONS);
+        assert!(!sessions.ids().contains(&session(0)));
+        assert!(sessions.ids().contains(&session(MAX_SESSIONS as u64)));
+    }
+}
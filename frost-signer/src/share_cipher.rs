@@ -0,0 +1,398 @@
+//! Encryption of the private shares of DKG.
+//!
+//! A [`DkgPrivateShares`] is broadcast over the relay, but the shares it carries for a signer's
+//! keys are for that signer only. Each signer's shares are sealed with ChaCha20-Poly1305 under a
+//! key only the sender and the recipient can derive, from the ECDH of the sender's network
+//! private key and the recipient's network public key. The relay sees which signers sent shares
+//! to which, never the shares.
+//!
+//! Sealing is deterministic: the key is bound to the DKG round, the sender and the recipient,
+//! and the nonce to the commitments the shares were built on, for which a party's shares are
+//! fixed. A signer asked for its shares again sends the same bytes, so peers comparing
+//! [`DkgShareDigests`] do not take it for equivocation, and a key and nonce pair only ever
+//! seals the same shares.
+//!
+//! A signer blaming a sender for bad shares reveals the key of the shares that sender sealed to
+//! it in a [`DkgBlame`], so the coordinator can open them and check them itself.
+//!
+//! The refresh shares of a [`ReshareShares`], bound to the sender's refresh commitments, and the
+//! repair values of a [`RepairShares`] or [`RepairedShares`] are sealed the same way, under keys
+//! bound to their [`Round`], so the key a DKG blame reveals opens nothing sent in a later round of
+//! the generation. Repair values are drawn anew each time they are sent, so they are sealed under
+//! a random nonce instead.
+//!
+//! [`DkgPrivateShares`]: crate::signing_round::DkgPrivateShares
+//! [`ReshareShares`]: crate::signing_round::ReshareShares
+//! [`RepairShares`]: crate::signing_round::RepairShares
+//! [`RepairedShares`]: crate::signing_round::RepairedShares
+//! [`DkgShareDigests`]: crate::signing_round::DkgShareDigests
+//! [`DkgBlame`]: crate::signing_round::DkgBlame
+use std::collections::BTreeMap;
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::ChaCha20Poly1305;
+use hashbrown::HashMap;
+use p256k1::{ecdsa, point::Compressed};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use wtfrost::{Point, Scalar};
+
+use crate::config::Config;
+use crate::protocol::{tag, Hasher};
+use crate::secret::{Secret, Wipe};
+use crate::signer::Error as SignerError;
+
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 12;
+
+/// (src_key_id, Map<dst_key_id, share>) for every key of the sender
+pub type PlainShares = Vec<(u32, HashMap<usize, Scalar>)>;
+
+/// The shares a sender's keys dealt to one signer's keys, sealed to that signer
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EncryptedShares {
+    /// The recipient
+    pub signer_id: u32,
+    /// The sealed bincode of the [`PlainShares`], with its tag
+    pub ciphertext: Vec<u8>,
+}
+
+/// The round of a generation shares are sent in, each sealing under keys of its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Round {
+    /// DKG round `dkg_id`
+    Dkg(u64),
+    /// Refresh `reshare_id` of the key shares of generation `dkg_id`, see [`crate::reshare`]
+    Reshare { dkg_id: u64, reshare_id: u64 },
+    /// Repair of key shares in roster change `change_id` of generation `dkg_id`, see
+    /// [`crate::roster`]
+    Repair { dkg_id: u64, change_id: u64 },
+}
+
+/// Seals the private shares a signer sends, and opens those it receives
+#[derive(Clone, Debug)]
+pub struct ShareCipher {
+    signer_id: u32,
+    private_key: Secret<Scalar>,
+    /// Network public key of every signer, by signer id
+    peers: BTreeMap<u32, Point>,
+}
+
+impl ShareCipher {
+    pub fn new(signer_id: u32, private_key: Scalar, peers: BTreeMap<u32, Point>) -> Self {
+        Self {
+            signer_id,
+            private_key: Secret::new(private_key),
+            peers,
+        }
+    }
+
+    /// The cipher of signer `signer_id`, with the network keys of the config
+    pub fn from_config(config: &Config, signer_id: u32) -> Result<Self, SignerError> {
+        let private_key = Scalar::try_from(config.network_private_key.as_str())
+            .map_err(|_| SignerError::InvalidKey("network_private_key".to_string()))?;
+        let mut peers = BTreeMap::new();
+        for (i, public_key) in config.signer_public_keys.iter().enumerate() {
+            let invalid = || SignerError::InvalidKey(format!("signer_public_keys[{i}]"));
+            let public_key =
+                ecdsa::PublicKey::try_from(public_key.as_str()).map_err(|_| invalid())?;
+            let point =
+                Point::try_from(&Compressed::from(public_key.to_bytes())).map_err(|_| invalid())?;
+            peers.insert(i as u32 + 1, point);
+        }
+        Ok(Self::new(signer_id, private_key, peers))
+    }
+
+    /// `shares` sealed to signer `recipient`, `None` if its network key is unknown
+    pub fn seal(
+        &self,
+        recipient: u32,
+        round: Round,
+        commitment_digest: &[u8; 32],
+        shares: &PlainShares,
+    ) -> Option<EncryptedShares> {
+        let key = self.key(recipient, round, self.signer_id, recipient)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&*key).expect("the key is 32 bytes");
+        // sorted, as the order of a HashMap differs from one instance to the next
+        let mut sorted: Vec<(u32, Vec<(usize, Scalar)>)> = shares
+            .iter()
+            .map(|(src_key_id, shares)| {
+                let mut shares: Vec<(usize, Scalar)> =
+                    shares.iter().map(|(id, share)| (*id, *share)).collect();
+                shares.sort_by_key(|(dst_key_id, _)| *dst_key_id);
+                (*src_key_id, shares)
+            })
+            .collect();
+        sorted.sort_by_key(|(src_key_id, _)| *src_key_id);
+        let plaintext = Secret::new(bincode::serialize(&sorted).expect("shares serialize"));
+        for (_, shares) in sorted.iter_mut() {
+            for (_, share) in shares.iter_mut() {
+                share.wipe();
+            }
+        }
+        let ciphertext = cipher
+            .encrypt((&nonce(commitment_digest)).into(), plaintext.as_slice())
+            .expect("encrypting to a vec does not fail");
+        Some(EncryptedShares {
+            signer_id: recipient,
+            ciphertext,
+        })
+    }
+
+    /// The shares signer `sender` sealed to us, `None` unless they were sealed with the key we
+    /// share with it, for `round` and the commitments of `commitment_digest`
+    pub fn open(
+        &self,
+        sender: u32,
+        round: Round,
+        commitment_digest: &[u8; 32],
+        sealed: &EncryptedShares,
+    ) -> Option<PlainShares> {
+        if sealed.signer_id != self.signer_id {
+            return None;
+        }
+        let key = self.key(sender, round, sender, self.signer_id)?;
+        open_with_key(&key, commitment_digest, sealed)
+    }
+
+    /// `values`, as (key_id, value), sealed to signer `recipient` under a random nonce leading
+    /// the ciphertext, `None` if its network key is unknown
+    pub fn seal_values(
+        &self,
+        recipient: u32,
+        round: Round,
+        values: &[(u32, Scalar)],
+    ) -> Option<EncryptedShares> {
+        let key = self.key(recipient, round, self.signer_id, recipient)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&*key).expect("the key is 32 bytes");
+        let mut iv = [0u8; IV_LEN];
+        OsRng.fill_bytes(&mut iv);
+        let plaintext = Secret::new(bincode::serialize(values).expect("values serialize"));
+        let mut ciphertext = iv.to_vec();
+        ciphertext.extend(
+            cipher
+                .encrypt((&iv).into(), plaintext.as_slice())
+                .expect("encrypting to a vec does not fail"),
+        );
+        Some(EncryptedShares {
+            signer_id: recipient,
+            ciphertext,
+        })
+    }
+
+    /// The values signer `sender` sealed to us with [`Self::seal_values`], `None` unless they
+    /// were sealed with the key we share with it for `round`
+    pub fn open_values(
+        &self,
+        sender: u32,
+        round: Round,
+        sealed: &EncryptedShares,
+    ) -> Option<Vec<(u32, Scalar)>> {
+        if sealed.signer_id != self.signer_id || sealed.ciphertext.len() < IV_LEN {
+            return None;
+        }
+        let key = self.key(sender, round, sender, self.signer_id)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&*key).expect("the key is 32 bytes");
+        let (iv, ciphertext) = sealed.ciphertext.split_at(IV_LEN);
+        let plaintext = cipher
+            .decrypt(iv.into(), ciphertext)
+            .ok()
+            .map(Secret::new)?;
+        bincode::deserialize(&plaintext).ok()
+    }
+
+    /// The key the shares signer `sender` seals to us in DKG round `dkg_id` open with, revealed
+    /// to blame it for them
+    pub fn share_key(&self, sender: u32, dkg_id: u64) -> Option<[u8; KEY_LEN]> {
+        self.key(sender, Round::Dkg(dkg_id), sender, self.signer_id)
+            .map(|key| *key)
+    }
+
+    /// The key of the shares `sender` deals to `recipient` in `round`, from our ECDH with
+    /// `peer`, whichever of the two is not us
+    fn key(
+        &self,
+        peer: u32,
+        round: Round,
+        sender: u32,
+        recipient: u32,
+    ) -> Option<Secret<[u8; KEY_LEN]>> {
+        let shared = *self.private_key * *self.peers.get(&peer)?;
+        let mut hasher = Hasher::new();
+        hasher.update(tag::SHARE_CIPHER_KEY.as_bytes());
+        hasher.update(shared.compress().as_bytes());
+        let dkg_id = match round {
+            Round::Dkg(dkg_id) | Round::Reshare { dkg_id, .. } | Round::Repair { dkg_id, .. } => {
+                dkg_id
+            }
+        };
+        hasher.update(dkg_id.to_be_bytes());
+        hasher.update(sender.to_be_bytes());
+        hasher.update(recipient.to_be_bytes());
+        // the rounds after DKG under keys of their own, which a DKG blame does not reveal
+        match round {
+            Round::Dkg(_) => {}
+            Round::Reshare { reshare_id, .. } => {
+                hasher.update(tag::RESHARE_SHARES.as_bytes());
+                hasher.update(reshare_id.to_be_bytes());
+            }
+            Round::Repair { change_id, .. } => {
+                hasher.update(tag::REPAIR_SHARES.as_bytes());
+                hasher.update(change_id.to_be_bytes());
+            }
+        }
+        Some(Secret::new(hasher.finalize().into()))
+    }
+}
+
+/// The shares of `sealed`, `None` unless they were sealed with `key`, for the commitments of
+/// `commitment_digest`
+pub fn open_with_key(
+    key: &[u8; KEY_LEN],
+    commitment_digest: &[u8; 32],
+    sealed: &EncryptedShares,
+) -> Option<PlainShares> {
+    let cipher = ChaCha20Poly1305::new_from_slice(key).expect("the key is 32 bytes");
+    let plaintext = cipher
+        .decrypt(
+            (&nonce(commitment_digest)).into(),
+            sealed.ciphertext.as_slice(),
+        )
+        .ok()
+        .map(Secret::new)?;
+    let mut sorted: Vec<(u32, Vec<(usize, Scalar)>)> = bincode::deserialize(&plaintext).ok()?;
+    let shares = sorted
+        .iter()
+        .map(|(src_key_id, shares)| (*src_key_id, shares.iter().cloned().collect()))
+        .collect();
+    for (_, shares) in sorted.iter_mut() {
+        for (_, share) in shares.iter_mut() {
+            share.wipe();
+        }
+    }
+    Some(shares)
+}
+
+/// Nonce of the shares built on the commitments of `commitment_digest`
+fn nonce(commitment_digest: &[u8; 32]) -> [u8; IV_LEN] {
+    let mut hasher = Hasher::new();
+    hasher.update(tag::SHARE_CIPHER_NONCE.as_bytes());
+    hasher.update(commitment_digest);
+    let mut iv = [0u8; IV_LEN];
+    iv.copy_from_slice(&hasher.finalize()[..IV_LEN]);
+    iv
+}
+
+/// Wipe every share of `shares`
+pub fn wipe(shares: &mut PlainShares) {
+    for (_, shares) in shares.iter_mut() {
+        shares.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ciphers(n: u32) -> Vec<ShareCipher> {
+        let keys: Vec<Scalar> = (0..n).map(|_| Scalar::random(&mut OsRng)).collect();
+        let peers: BTreeMap<u32, Point> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i as u32 + 1, Point::from(*key)))
+            .collect();
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| ShareCipher::new(i as u32 + 1, *key, peers.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn only_the_recipient_opens_the_shares() {
+        let ciphers = ciphers(3);
+        let shares: PlainShares = vec![
+            (
+                0,
+                HashMap::from([(2, Scalar::from(7)), (3, Scalar::from(8))]),
+            ),
+            (
+                1,
+                HashMap::from([(2, Scalar::from(9)), (3, Scalar::from(10))]),
+            ),
+        ];
+        let digest = [1; 32];
+        let sealed = ciphers[0].seal(2, Round::Dkg(4), &digest, &shares).unwrap();
+        assert_eq!(
+            ciphers[1].open(1, Round::Dkg(4), &digest, &sealed),
+            Some(shares.clone())
+        );
+        // sent again, even from a rebuilt map, the shares seal to the same bytes
+        let rebuilt: PlainShares = shares
+            .iter()
+            .map(|(src_key_id, shares)| (*src_key_id, shares.clone().into_iter().collect()))
+            .collect();
+        assert_eq!(
+            ciphers[0].seal(2, Round::Dkg(4), &digest, &rebuilt),
+            Some(sealed.clone())
+        );
+        // the key the recipient reveals opens them, and only them
+        let key = ciphers[1].share_key(1, 4).unwrap();
+        assert_eq!(open_with_key(&key, &digest, &sealed), Some(shares.clone()));
+        let other = ciphers[1].share_key(3, 4).unwrap();
+        assert_eq!(open_with_key(&other, &digest, &sealed), None);
+
+        // another signer, round, sender or set of commitments cannot open them
+        let mut forwarded = sealed.clone();
+        forwarded.signer_id = 3;
+        assert_eq!(ciphers[2].open(1, Round::Dkg(4), &digest, &forwarded), None);
+        assert_eq!(ciphers[1].open(1, Round::Dkg(5), &digest, &sealed), None);
+        assert_eq!(ciphers[1].open(3, Round::Dkg(4), &digest, &sealed), None);
+        assert_eq!(ciphers[1].open(1, Round::Dkg(4), &[2; 32], &sealed), None);
+        let bytes = bincode::serialize(&sealed).unwrap();
+        assert!(!bytes
+            .windows(32)
+            .any(|window| window == Scalar::from(7).to_bytes()));
+        assert_eq!(ciphers[0].seal(4, Round::Dkg(4), &digest, &shares), None);
+    }
+
+    #[test]
+    fn values_open_only_for_their_recipient_and_round() {
+        let ciphers = ciphers(3);
+        let values = vec![(4, Scalar::from(11)), (5, Scalar::from(12))];
+        let round = Round::Repair {
+            dkg_id: 4,
+            change_id: 1,
+        };
+        let sealed = ciphers[0].seal_values(2, round, &values).unwrap();
+        assert_eq!(
+            ciphers[1].open_values(1, round, &sealed),
+            Some(values.clone())
+        );
+        // drawn anew each time, values never seal to the same bytes twice
+        assert_ne!(
+            ciphers[0].seal_values(2, round, &values),
+            Some(sealed.clone())
+        );
+
+        let mut forwarded = sealed.clone();
+        forwarded.signer_id = 3;
+        assert_eq!(ciphers[2].open_values(1, round, &forwarded), None);
+        let next = Round::Repair {
+            dkg_id: 4,
+            change_id: 2,
+        };
+        assert_eq!(ciphers[1].open_values(1, next, &sealed), None);
+        assert_eq!(ciphers[1].open_values(1, Round::Dkg(4), &sealed), None);
+        // the key a DKG blame reveals opens nothing of a later round of the generation
+        let shares: PlainShares = vec![(0, HashMap::from([(2, Scalar::from(7))]))];
+        let refresh = Round::Reshare {
+            dkg_id: 4,
+            reshare_id: 1,
+        };
+        let sealed = ciphers[0].seal(2, refresh, &[1; 32], &shares).unwrap();
+        assert_eq!(ciphers[1].open(1, refresh, &[1; 32], &sealed), Some(shares));
+        let key = ciphers[1].share_key(1, 4).unwrap();
+        assert_eq!(open_with_key(&key, &[1; 32], &sealed), None);
+    }
+}
@@ -1,105 +1,369 @@
+use crate::auth::{Authenticator, Identity, COORDINATOR_SENDER_ID};
+use crate::bus::Bus;
 use crate::config::Config;
-use crate::net::{Error as HttpNetError, HttpNet, HttpNetListen, Message, Net, NetListen};
-use crate::signing_round::{Error as SigningRoundError, MessageTypes, Signable, SigningRound};
-use crate::util::{parse_public_key, parse_public_keys};
+use crate::control::Server as ControlServer;
+use crate::failure::{ErrorClass, FailureReport};
+use crate::generations::Generations;
+use crate::key_store::{Error as KeyStoreError, KeyStore};
+use crate::logging::target::NET;
+use crate::middleware::{Chain, Middleware};
+use crate::net::{
+    Error as HttpNetError, Message, Net, NetListen, TransportNet, TransportNetListen,
+};
+use crate::nonce_store::{Error as NonceStoreError, NonceStore};
+use crate::pacing::{PacingPolicy, Poller};
+use crate::redact::ConfigRoute;
+use crate::retry::{Classify, Retry};
+use crate::share_cipher::ShareCipher;
+use crate::signing_round::{Error as SigningRoundError, MessageTypes, SigningRound};
+use crate::status::{SharedSnapshot, Status};
+use crate::supervisor::Supervisor;
+use crate::traffic::Traffic;
 use p256k1::ecdsa;
 use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread::spawn;
 use std::{thread, time};
-use wtfrost::Scalar;
+use tracing::{info, warn};
 
 // on-disk format for frost save data
 #[derive(Clone, Deserialize, Default, Debug)]
 pub struct Signer {
     pub config: Config,
     pub signer_id: u32,
+    /// Control routes served next to the status, e.g. the log filter
+    #[serde(skip)]
+    control: ControlServer,
+    /// In-process bus used in place of the configured transport, see [`crate::bus`]
+    #[serde(skip)]
+    bus: Option<Bus>,
+    /// Latest round state, kept here so a fatal error can report the rounds in flight
+    #[serde(skip)]
+    snapshot: SharedSnapshot,
+    /// Layers inbound messages pass through before the rounds process them, see
+    /// [`crate::middleware`]
+    #[serde(skip)]
+    middleware: Arc<Mutex<Chain>>,
 }
 
 impl Signer {
     pub fn new(config: Config, signer_id: u32) -> Self {
-        Self { config, signer_id }
+        Self {
+            config,
+            signer_id,
+            control: ControlServer::default(),
+            bus: None,
+            snapshot: SharedSnapshot::default(),
+            middleware: Arc::default(),
+        }
+    }
+
+    /// Run inbound messages through `layer`, after the layers added before it
+    pub fn with_middleware(self, layer: impl Middleware + 'static) -> Self {
+        if let Ok(mut chain) = self.middleware.lock() {
+            chain.push(Box::new(layer));
+        }
+        self
+    }
+
+    /// Exchange messages over `bus` rather than the configured transport
+    pub fn with_bus(mut self, bus: Bus) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Serve the routes of `control` on the `[control]` listeners, along with the status
+    pub fn with_control(mut self, control: ControlServer) -> Self {
+        self.control = control;
+        self
     }
 
+    /// Run the signer, restarting the network loop after transient failures.
+    /// Only configuration and key errors are returned.
     pub fn start_p2p_sync(&mut self) -> Result<(), Error> {
-        let signer_public_keys = parse_public_keys(&self.config.signer_public_keys);
-        let key_public_keys = parse_public_keys(&self.config.key_public_keys);
-        let coordinator_public_key = parse_public_key(&self.config.coordinator_public_key);
+        let keys = PeerKeys::try_from(&self.config)?;
+        let identity = Identity::from_config(&self.config, self.signer_id)?;
+
+        // relay or StackerDB, as configured, unless hosted next to its peers
+        let net = match &self.bus {
+            Some(bus) => TransportNet::from_bus(bus, &self.config, self.signer_id)?,
+            None => TransportNet::from_config(&self.config, self.signer_id)?,
+        };
 
-        //Create http relay
-        let net: HttpNet = HttpNet::new(self.config.http_relay_url.clone());
-        let net_queue = HttpNetListen::new(net.clone(), vec![]);
+        let nonce_store = match &self.config.nonce_store_path {
+            Some(path) => NonceStore::open(
+                path,
+                self.config.network_private_key.as_str(),
+                self.config.nonce_ttl(),
+            )?,
+            None => NonceStore::default(),
+        };
+        let key_store = match &self.config.key_store_path {
+            Some(path) => KeyStore::open(path, self.config.network_private_key.as_str())?,
+            None => KeyStore::default(),
+        };
+        let stored_keys = key_store.load()?;
+        let share_cipher = ShareCipher::from_config(&self.config, self.signer_id)?;
+
+        // the rounds outlive restarts of the network loop, so an in-flight DKG is not lost
+        let signer = self.clone();
+        let mut rounds = Generations::new(
+            move || {
+                SigningRound::from(&signer)
+                    .with_nonce_store(nonce_store.clone())
+                    .with_key_store(key_store.clone())
+                    .with_share_cipher(share_cipher.clone())
+            },
+            self.config.max_generations(),
+        );
+        if !stored_keys.is_empty() {
+            rounds.restore(stored_keys);
+            info!("Restored the keys of DKG rounds {:?}", rounds.generations());
+        }
+        let effective = self.config.effective();
+        info!("Effective configuration:\n{}", effective);
+        let snapshot = self.snapshot.clone();
+        let status = Status::new(snapshot.clone());
+        let traffic = Traffic::new(self.signer_id, self.config.accept_own_messages);
+        if let Some(addr) = &self.config.status_listen_addr {
+            ControlServer::new(&self.config.control)
+                .with_route(status.clone())
+                .serve_tcp(addr.as_str())?;
+        }
+        self.control
+            .clone()
+            .with_route(status)
+            .with_route(traffic.clone())
+            .with_route(ConfigRoute::new(effective))
+            .serve(&self.config.control)?;
+
+        Supervisor::new(self.config.retry.clone())
+            .run(|| self.run_network_loop(&net, &keys, &identity, &mut rounds, &snapshot, &traffic))
+    }
+
+    /// Report of `error` stopping the signer, with the rounds it had in flight
+    pub fn failure_report(&self, program: &str, error: &Error) -> FailureReport {
+        let report = FailureReport::new(program, error.error_class(), error);
+        match self.snapshot.lock().ok().and_then(|latest| latest.clone()) {
+            Some(latest) => report
+                .with_round("dkg_id", latest.dkg_id)
+                .with_round("dkg_public_id", latest.dkg_public_id)
+                .with_round("sign_id", latest.sign_id)
+                .with_round("sign_nonce_id", latest.sign_nonce_id),
+            None => report,
+        }
+    }
+
+    fn run_network_loop(
+        &self,
+        net: &TransportNet,
+        keys: &PeerKeys,
+        identity: &Identity,
+        rounds: &mut Generations,
+        snapshot: &SharedSnapshot,
+        traffic: &Traffic,
+    ) -> Result<(), Error> {
+        let net_queue = TransportNetListen::new(net.clone())
+            .with_authenticator(
+                Authenticator::new(keys.clone(), self.config.max_clock_skew())
+                    .with_max_message_age(self.config.max_message_age()),
+            )
+            .with_traffic(traffic.clone());
         // thread coordination
         let (tx, rx): (Sender<Message>, Receiver<Message>) = mpsc::channel();
+        // stops the poll thread once this loop exits, however it exits
+        let running = Running::default();
 
         // start p2p sync
         let id = self.signer_id;
-        spawn(move || {
-            poll_loop(
-                net_queue,
-                tx,
-                id,
-                signer_public_keys,
-                key_public_keys,
-                coordinator_public_key,
-            )
-        });
+        let poll_keys = keys.clone();
+        let poll_running = running.0.clone();
+        let pacing = self.config.pacing.clone();
+        spawn(move || poll_loop(net_queue, tx, id, poll_keys, poll_running, pacing));
 
         // listen to p2p messages
-        self.start_signing_round(&net, rx)
+        self.start_signing_round(net, identity, rounds, snapshot, rx)
     }
 
-    fn start_signing_round(&self, net: &HttpNet, rx: Receiver<Message>) -> Result<(), Error> {
-        let network_private_key = Scalar::try_from(self.config.network_private_key.as_str())
-            .expect("failed to parse network_private_key from config");
-        let mut round = SigningRound::from(self);
+    fn start_signing_round(
+        &self,
+        net: &TransportNet,
+        identity: &Identity,
+        rounds: &mut Generations,
+        snapshot: &SharedSnapshot,
+        rx: Receiver<Message>,
+    ) -> Result<(), Error> {
+        // tell the coordinator we are (back) up, e.g. after restarting in an upgrade window
+        net.send_message(identity.sign_message(rounds.registration())?)?;
+        let stall = self.config.dkg_stall();
         loop {
+            if let Ok(mut latest) = snapshot.lock() {
+                *latest = Some(rounds.snapshot());
+            }
             // Retreive a message from coordinator
-            let inbound = rx.recv()?; // blocking
-            let outbounds = round.process(inbound.msg)?;
-            for out in outbounds {
-                let msg = Message {
-                    msg: out.clone(),
-                    sig: match out {
-                        MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::DkgQuery(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::DkgQueryResponse(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::DkgPublicShare(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::DkgPrivateShares(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::NonceRequest(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::NonceResponse(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::SignShareRequest(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                        MessageTypes::SignShareResponse(msg) => {
-                            msg.sign(&network_private_key).expect("").to_vec()
-                        }
-                    },
-                };
-                net.send_message(msg)?;
+            let mut outbounds = match rx.recv_timeout(stall) {
+                Ok(inbound) => match self.admit(inbound) {
+                    Some(inbound) => rounds.process(inbound.msg)?,
+                    None => vec![],
+                },
+                // a DKG round waiting this long lost a message, ask for it again
+                Err(mpsc::RecvTimeoutError::Timeout) => rounds.missing_share_requests(),
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError.into()),
+            };
+            // checked whether or not messages arrive, since those for other rounds don't help
+            outbounds.extend(rounds.expire_dkg(time::Instant::now()));
+            let outbounds = outbounds
+                .into_iter()
+                .map(|out| identity.sign_message(out))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !outbounds.is_empty() {
+                // so the fleet's responses do not reach the relay all at once
+                thread::sleep(self.config.pacing.response_delay());
             }
+            // one message per local party, coalesced by transports which can
+            net.send_messages(outbounds)?;
         }
     }
 }
 
+impl Signer {
+    /// `message`, unless a middleware layer drops it
+    fn admit(&self, message: Message) -> Option<Message> {
+        let Ok(mut chain) = self.middleware.lock() else {
+            return Some(message);
+        };
+        chain
+            .run(message, time::Instant::now())
+            .map(|inbound| inbound.message)
+    }
+}
+
+/// Public keys used to verify inbound messages
+#[derive(Clone, Debug)]
+pub struct PeerKeys {
+    pub signers: Vec<ecdsa::PublicKey>,
+    pub keys: Vec<ecdsa::PublicKey>,
+    pub coordinator: ecdsa::PublicKey,
+}
+
+impl PeerKeys {
+    /// The key `msg` must be signed with, if its sender is in the config.
+    /// Hellos carry their own key and have none here, and neither do messages about a key id
+    /// whose configured key is not the key of the signer claiming it.
+    pub fn signing_key(&self, msg: &MessageTypes) -> Option<&ecdsa::PublicKey> {
+        let signer = |signer_id: usize| self.signers.get(signer_id.checked_sub(1)?);
+        let key_owner = |signer_id: u32, key_id: u32| {
+            let key = signer(signer_id as usize)?;
+            let configured = self.keys.get(key_id as usize)?;
+            (configured.to_bytes() == key.to_bytes()).then_some(key)
+        };
+        match msg {
+            MessageTypes::DkgBegin(_)
+            | MessageTypes::DkgPrivateBegin(_)
+            | MessageTypes::DkgQuery(_)
+            | MessageTypes::NonceRequest(_)
+            | MessageTypes::SignShareRequest(_)
+            | MessageTypes::BatchNonceRequest(_)
+            | MessageTypes::BatchSignRequest(_)
+            | MessageTypes::RoundAbort(_)
+            | MessageTypes::UpgradeWindow(_)
+            | MessageTypes::ReshareBegin(_)
+            | MessageTypes::ReshareCommit(_)
+            | MessageTypes::RosterChange(_) => Some(&self.coordinator),
+            MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => {
+                signer(msg.signer_id as usize)
+            }
+            MessageTypes::DkgPrivateShares(msg) => signer(msg.signer_id as usize),
+            MessageTypes::DkgShareDigests(msg) => signer(msg.signer_id as usize),
+            MessageTypes::EquivocationReport(msg) => signer(msg.signer_id as usize),
+            MessageTypes::DkgBlame(msg) => signer(msg.signer_id as usize),
+            MessageTypes::MissingShareRequest(msg) => signer(msg.signer_id as usize),
+            MessageTypes::RoundOutcomeQuery(msg) => signer(msg.signer_id as usize),
+            MessageTypes::RoundOutcomeResponse(msg) => signer(msg.signer_id as usize),
+            MessageTypes::Rejection(msg) => signer(msg.signer_id as usize),
+            MessageTypes::UpgradeAck(msg) => signer(msg.signer_id as usize),
+            MessageTypes::Registration(msg) => signer(msg.signer_id as usize),
+            MessageTypes::ReshareShares(msg) => signer(msg.signer_id as usize),
+            MessageTypes::ReshareEnd(msg) => signer(msg.signer_id as usize),
+            MessageTypes::RepairShares(msg) => signer(msg.signer_id as usize),
+            MessageTypes::RepairedShares(msg) => signer(msg.signer_id as usize),
+            MessageTypes::RosterChangeEnd(msg) => signer(msg.signer_id as usize),
+            MessageTypes::DkgGroupKey(msg) => signer(msg.signer_id as usize),
+            MessageTypes::DkgPublicShare(msg) => key_owner(msg.signer_id, msg.key_id),
+            MessageTypes::NonceResponse(msg) => key_owner(msg.signer_id, msg.key_id),
+            MessageTypes::SignShareResponse(msg) => key_owner(msg.signer_id, msg.key_id),
+            MessageTypes::BatchNonceResponse(msg) => key_owner(msg.signer_id, msg.key_id),
+            MessageTypes::BatchSignResponse(msg) => key_owner(msg.signer_id, msg.key_id),
+            MessageTypes::DkgQueryResponse(msg) => {
+                let key_id = msg.public_share.id.id.get_u32() as usize;
+                self.keys.get(key_id.checked_sub(1)?)
+            }
+            MessageTypes::Hello(_) => None,
+        }
+    }
+
+    /// Network key of envelope sender `sender_id`, see [`crate::auth`]
+    pub fn sender_key(&self, sender_id: u32) -> Option<&ecdsa::PublicKey> {
+        match sender_id {
+            COORDINATOR_SENDER_ID => Some(&self.coordinator),
+            signer_id => self.signers.get(signer_id as usize - 1),
+        }
+    }
+
+    /// Whether `message` carries a valid signature by its sender's configured key
+    pub fn verify(&self, message: &Message) -> bool {
+        let Some(key) = self.signing_key(&message.msg) else {
+            return false;
+        };
+        match &message.msg {
+            // hellos carry their own key, see crate::ceremony
+            MessageTypes::Hello(_) => false,
+            msg => msg.verify(&message.sig, key),
+        }
+    }
+}
+
+impl TryFrom<&Config> for PeerKeys {
+    type Error = Error;
+
+    fn try_from(config: &Config) -> Result<Self, Error> {
+        let parse = |name: &str, key: &str| {
+            ecdsa::PublicKey::try_from(key).map_err(|e| Error::InvalidKey(format!("{name}: {e}")))
+        };
+        Ok(Self {
+            signers: config
+                .signer_public_keys
+                .iter()
+                .map(|key| parse("signer_public_keys", key))
+                .collect::<Result<_, _>>()?,
+            keys: config
+                .key_public_keys
+                .iter()
+                .map(|key| parse("key_public_keys", key))
+                .collect::<Result<_, _>>()?,
+            coordinator: parse("coordinator_public_key", &config.coordinator_public_key)?,
+        })
+    }
+}
+
+/// Cleared on drop to tell the poll thread to exit
+struct Running(Arc<AtomicBool>);
+
+impl Default for Running {
+    fn default() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Http Network Error: {0}")]
@@ -113,6 +377,47 @@ pub enum Error {
 
     #[error("Failed to send message")]
     SendError,
+
+    #[error("Status endpoint error: {0}")]
+    StatusError(#[from] std::io::Error),
+
+    #[error("Invalid key in config: {0}")]
+    InvalidKey(String),
+
+    #[error("Nonce store error: {0}")]
+    NonceStoreError(#[from] NonceStoreError),
+
+    #[error("Key store error: {0}")]
+    KeyStoreError(#[from] KeyStoreError),
+}
+
+impl Classify for Error {
+    fn classify(&self) -> Retry {
+        match self {
+            // the network loop can recover from anything but bad configuration
+            Error::StatusError(_)
+            | Error::InvalidKey(_)
+            | Error::NonceStoreError(_)
+            | Error::KeyStoreError(_) => Retry::Permanent,
+            _ => Retry::Transient,
+        }
+    }
+}
+
+impl Error {
+    /// What an orchestrator should make of the signer stopping on this error
+    pub fn error_class(&self) -> ErrorClass {
+        match self {
+            Error::InvalidKey(_) => ErrorClass::Config,
+            Error::NonceStoreError(_) | Error::KeyStoreError(_) => ErrorClass::Storage,
+            Error::SigningRoundError(_) => ErrorClass::Internal,
+            // the relay stayed down past the retry policy, or a listener could not bind
+            Error::HttpNetError(_)
+            | Error::RecvError(_)
+            | Error::SendError
+            | Error::StatusError(_) => ErrorClass::Unavailable,
+        }
+    }
 }
 
 impl From<mpsc::SendError<Message>> for Error {
@@ -122,67 +427,79 @@ impl From<mpsc::SendError<Message>> for Error {
 }
 
 fn poll_loop(
-    mut net: HttpNetListen,
+    mut net: TransportNetListen,
     tx: Sender<Message>,
     id: u32,
-    signer_public_keys: Vec<ecdsa::PublicKey>,
-    key_public_keys: Vec<ecdsa::PublicKey>,
-    coordinator_public_key: ecdsa::PublicKey,
+    keys: PeerKeys,
+    running: Arc<AtomicBool>,
+    pacing: PacingPolicy,
 ) -> Result<(), Error> {
-    const BASE_TIMEOUT: u64 = 2;
-    const MAX_TIMEOUT: u64 = 128;
-    let mut timeout = BASE_TIMEOUT;
-    loop {
+    let mut poller = Poller::new(pacing);
+    while running.load(Ordering::SeqCst) {
         net.poll(id);
-        match net.next_message() {
-            None => {
-                timeout = if timeout == 0 {
-                    BASE_TIMEOUT
-                } else if timeout >= MAX_TIMEOUT {
-                    MAX_TIMEOUT
-                } else {
-                    timeout * 2
-                };
+        let message = net.next_message();
+        let wait = poller.next_wait(message.is_some(), time::Instant::now());
+        if let Some(m) = message {
+            // ceremony hellos may carry keys missing from the config, the
+            // ceremony verifies them itself and rounds ignore them
+            if !matches!(m.msg, MessageTypes::Hello(_)) && !keys.verify(&m) {
+                warn!(target: NET, "dropped {} not signed by its author", m.msg.name());
+            } else {
+                tx.send(m)?;
             }
-            Some(m) => {
-                timeout = 0;
-                match &m.msg {
-                    MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => {
-                        assert!(msg.verify(&m.sig, &coordinator_public_key))
-                    }
-                    MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => {
-                        assert!(msg.verify(&m.sig, &signer_public_keys[msg.signer_id - 1]))
-                    }
-                    MessageTypes::DkgPublicShare(msg) => {
-                        assert!(msg.verify(&m.sig, &key_public_keys[msg.party_id as usize]))
-                    }
-                    MessageTypes::DkgPrivateShares(msg) => {
-                        assert!(msg.verify(&m.sig, &key_public_keys[msg.key_id as usize]))
-                    }
-                    MessageTypes::DkgQuery(msg) => {
-                        assert!(msg.verify(&m.sig, &coordinator_public_key))
-                    }
-                    MessageTypes::DkgQueryResponse(msg) => {
-                        let key_id = msg.public_share.id.id.get_u32();
-                        assert!(msg.verify(&m.sig, &key_public_keys[key_id as usize - 1]));
-                    }
-                    MessageTypes::NonceRequest(msg) => {
-                        assert!(msg.verify(&m.sig, &coordinator_public_key))
-                    }
-                    MessageTypes::NonceResponse(msg) => {
-                        assert!(msg.verify(&m.sig, &key_public_keys[msg.party_id as usize]))
-                    }
-                    MessageTypes::SignShareRequest(msg) => {
-                        assert!(msg.verify(&m.sig, &coordinator_public_key))
-                    }
-                    MessageTypes::SignShareResponse(msg) => {
-                        assert!(msg.verify(&m.sig, &key_public_keys[msg.party_id as usize]))
-                    }
-                }
+        }
+        thread::sleep(wait);
+    }
+    Ok(())
+}
 
-                tx.send(m)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_round::{NonceResponse, Signable};
+    use rand_core::OsRng;
+    use wtfrost::{common::PublicNonce, Scalar};
+
+    #[test]
+    fn key_messages_are_verified_against_the_owning_signer() {
+        let private_keys: Vec<Scalar> = (0..2).map(|_| Scalar::random(&mut OsRng)).collect();
+        let public_keys: Vec<ecdsa::PublicKey> = private_keys
+            .iter()
+            .map(|key| ecdsa::PublicKey::new(key).unwrap())
+            .collect();
+        // signer #1 owns keys 0 and 1, signer #2 keys 2 and 3
+        let keys = PeerKeys {
+            signers: public_keys.clone(),
+            keys: vec![
+                public_keys[0],
+                public_keys[0],
+                public_keys[1],
+                public_keys[1],
+            ],
+            coordinator: public_keys[0],
+        };
+        let nonce_response = |signer_id, key_id| {
+            let msg = NonceResponse {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 0,
+                sign_nonce_id: 1,
+                signer_id,
+                key_id,
+                nonce: PublicNonce {
+                    D: Default::default(),
+                    E: Default::default(),
+                },
+            };
+            Message {
+                sig: msg.sign(&private_keys[signer_id as usize - 1]).unwrap(),
+                msg: MessageTypes::NonceResponse(msg),
             }
         };
-        thread::sleep(time::Duration::from_millis(timeout));
+
+        assert!(keys.verify(&nonce_response(1, 1)));
+        assert!(keys.verify(&nonce_response(2, 2)));
+        // signed by its sender, but about a key the sender does not own
+        assert!(!keys.verify(&nonce_response(2, 1)));
     }
 }
@@ -1,23 +1,45 @@
+use crate::digest::{Error as DigestError, MessageDigest};
+use crate::key_store::{KeyStore, StoredKeys};
+use crate::lagrange::{self, LagrangeCache};
+use crate::logging::target::SIGNING_ROUND;
+use crate::nonce_store::{NonceId, NonceStore};
+use crate::preimage::Preimage;
+use crate::protocol::{tag, Hasher};
+use crate::reshare::{self, Reshare};
+use crate::roster::{self, Repair};
+use crate::secret::{Secret, SecretShares, Wipe};
+use crate::sessions::{SessionId, Sessions};
+use crate::share_cipher::{self, EncryptedShares, Round, ShareCipher};
 use crate::signer::Signer as FrostSigner;
+use crate::taproot::KeyPath;
 use hashbrown::HashMap;
 use p256k1::ecdsa;
 use rand_core::{CryptoRng, OsRng, RngCore};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::BTreeMap;
-use tracing::{debug, info};
+use sha2::Digest;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 pub use wtfrost;
 use wtfrost::{
     common::{PolyCommitment, PublicNonce},
-    v1, Scalar,
+    compute,
+    errors::DkgError,
+    schnorr::ID,
+    v1, Point, Scalar,
 };
 
 use crate::state_machine::{Error as StateMachineError, StateMachine, States};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("InvalidPartyID")]
-    InvalidPartyID,
+    #[error("InvalidKeyId")]
+    InvalidKeyId,
+    #[error("Key #{key_id} is not owned by signer #{signer_id}")]
+    KeyOwnerMismatch { signer_id: u32, key_id: u32 },
     #[error("InvalidDkgPublicShare")]
     InvalidDkgPublicShare,
     #[error("InvalidDkgPrivateShares")]
@@ -26,19 +48,38 @@ pub enum Error {
     InvalidNonceResponse,
     #[error("InvalidSignatureShare")]
     InvalidSignatureShare,
+    #[error("Invalid message to sign: {0}")]
+    InvalidMessage(#[from] DigestError),
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+    #[error("No keys of DKG round #{0} to refresh")]
+    NoKeys(u64),
+    #[error("InvalidReshareShares")]
+    InvalidReshareShares(u32),
+    #[error("Failed to refresh key shares: {0}")]
+    Reshare(#[from] serde_json::Error),
+    #[error("Invalid roster change: {0}")]
+    InvalidRosterChange(String),
+    #[error("InvalidRepairShares")]
+    InvalidRepairShares(u32),
+    #[error("No unspent nonce of key #{key_id} was handed out in {session}")]
+    UnknownNonce { session: SessionId, key_id: u32 },
     #[error("State Machine Error: {0}")]
     StateMachineError(#[from] StateMachineError),
 }
 
 pub trait Signable {
-    fn hash(&self, hasher: &mut Sha256);
-
-    fn sign(&self, private_key: &Scalar) -> Result<Vec<u8>, ecdsa::Error> {
-        let mut hasher = Sha256::new();
+    fn hash(&self, hasher: &mut Hasher);
 
+    /// Digest of the message, as signed
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = Hasher::new();
         self.hash(&mut hasher);
+        hasher.finalize().into()
+    }
 
-        let hash = hasher.finalize();
+    fn sign(&self, private_key: &Scalar) -> Result<Vec<u8>, ecdsa::Error> {
+        let hash = self.digest();
         match ecdsa::Signature::new(hash.as_slice(), private_key) {
             Ok(sig) => Ok(sig.to_bytes().to_vec()),
             Err(e) => Err(e),
@@ -46,11 +87,7 @@ pub trait Signable {
     }
 
     fn verify(&self, signature: &[u8], public_key: &ecdsa::PublicKey) -> bool {
-        let mut hasher = Sha256::new();
-
-        self.hash(&mut hasher);
-
-        let hash = hasher.finalize();
+        let hash = self.digest();
         let sig = match ecdsa::Signature::try_from(signature) {
             Ok(sig) => sig,
             Err(_) => return false,
@@ -65,13 +102,68 @@ pub struct SigningRound {
     pub dkg_public_id: u64,
     pub sign_id: u64,
     pub sign_nonce_id: u64,
+    /// Keys whose shares are needed to sign
     pub threshold: usize,
+    /// Distinct signers which must take part in DKG, see [`crate::thresholds`]
+    pub signers_threshold: usize,
     pub total: usize,
     pub signer: Signer,
     pub state: States,
     pub commitments: BTreeMap<u32, PolyCommitment>,
-    pub shares: HashMap<u32, HashMap<usize, Scalar>>,
+    pub shares: HashMap<u32, SecretShares>,
     pub public_nonces: Vec<PublicNonce>,
+    /// Final outcome of every DKG round this signer knows about, keyed by dkg_id
+    pub generations: BTreeMap<u64, RoundOutcome>,
+    /// Peer attestations for rounds we are catching up on, keyed by dkg_id then signer_id
+    pub outcome_attestations: BTreeMap<u64, HashMap<u32, RoundOutcomeResponse>>,
+    /// Lagrange coefficients for the current key generation
+    pub lagrange: LagrangeCache,
+    /// Key ids excluded from the current DKG round, see [`DkgBegin::excluded_key_ids`]
+    pub excluded_key_ids: BTreeSet<u32>,
+    /// Signer id owning each key id, as claimed by the messages of the current DKG round
+    pub key_owners: BTreeMap<u32, u32>,
+    /// [`commitment_digest`] of the commitments of the current DKG round, set when the
+    /// commitment phase closes
+    pub commitment_digest: Option<[u8; 32]>,
+    /// Source key ids whose private shares were built on a different set of commitments
+    pub commitment_mismatches: BTreeSet<u32>,
+    /// Source key ids whose private shares to us do not match their polynomial commitments
+    pub bad_shares: BTreeSet<u32>,
+    /// Digest of the DkgPrivateShares we received from each signer in the current DKG round
+    pub share_digests: BTreeMap<u32, [u8; 32]>,
+    /// The same, as reported by each peer in its [`DkgShareDigests`]
+    pub peer_share_digests: BTreeMap<u32, BTreeMap<u32, [u8; 32]>>,
+    /// Signers we have sent an [`EquivocationReport`] about in the current DKG round
+    pub equivocators: BTreeSet<u32>,
+    /// Signers we have sent a [`DkgBlame`] about in the current DKG round
+    pub blamed: BTreeSet<u32>,
+    /// Group key each signer of the current DKG round computed, ourselves included, as sent
+    /// in its [`DkgGroupKey`]
+    pub group_keys: BTreeMap<u32, Option<Point>>,
+    /// Secret nonces handed out, kept until they sign, see [`crate::nonce_store`]
+    pub nonce_store: NonceStore,
+    /// Key shares of the DKG rounds which succeeded, kept across restarts, see
+    /// [`crate::key_store`]
+    pub key_store: KeyStore,
+    /// Secret nonces handed out in each signing session in flight, each spent by the first
+    /// request naming its public half. Batches are the sessions of correlation id 0.
+    pub sessions: Sessions,
+    /// Stalls of the current DKG round we sent [`MissingShareRequest`]s for
+    pub share_request_attempts: u32,
+    /// [`MissingShareRequest`]s answered in the current DKG round, as (requesting signer_id,
+    /// attempt, key id of the commitment sent again or `None` for the private shares)
+    pub retransmitted: BTreeSet<(u32, u32, Option<u32>)>,
+    /// How long each gather phase of a DKG round may take before the round fails
+    pub dkg_timeouts: DkgTimeouts,
+    /// When the DKG gather phase we are in started
+    pub phase_started: Option<Instant>,
+    /// Refresh of the current generation's key shares in progress, see [`crate::reshare`]
+    pub reshare: Option<Reshare>,
+    /// Repair of key shares for signers joining the current generation, see [`crate::roster`]
+    pub repair: Option<Repair>,
+    /// Seals the private shares we send to each signer and opens those sent to us, see
+    /// [`crate::share_cipher`]. Shares go in the clear without one.
+    pub share_cipher: Option<ShareCipher>,
 }
 
 pub struct Signer {
@@ -82,6 +174,11 @@ pub struct Signer {
 impl StateMachine for SigningRound {
     fn move_to(&mut self, state: States) -> Result<(), StateMachineError> {
         self.can_move_to(&state)?;
+        self.phase_started = matches!(
+            state,
+            States::DkgPublicGather | States::DkgPrivateGather | States::DkgConfirm
+        )
+        .then(Instant::now);
         self.state = state;
         Ok(())
     }
@@ -98,11 +195,12 @@ impl StateMachine for SigningRound {
             States::DkgPublicGather => prev_state == &States::DkgPublicDistribute,
             States::DkgPrivateDistribute => prev_state == &States::DkgPublicGather,
             States::DkgPrivateGather => prev_state == &States::DkgPrivateDistribute,
+            States::DkgConfirm => prev_state == &States::DkgPrivateGather,
             States::SignGather => prev_state == &States::Idle,
             States::Signed => prev_state == &States::SignGather,
         };
         if accepted {
-            info!("state change from {:?} to {:?}", prev_state, state);
+            info!(target: SIGNING_ROUND, "state change from {:?} to {:?}", prev_state, state);
             Ok(())
         } else {
             Err(StateMachineError::BadStateChange(format!(
@@ -113,12 +211,171 @@ impl StateMachine for SigningRound {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum DkgStatus {
     Success,
-    Failure(String),
+    Failure(DkgFailure),
+}
+
+impl DkgStatus {
+    fn hash(&self, hasher: &mut Hasher) {
+        match self {
+            DkgStatus::Success => hasher.update([0u8]),
+            DkgStatus::Failure(failure) => {
+                hasher.update([1u8]);
+                failure.hash(hasher);
+            }
+        }
+    }
+}
+
+/// Why a signer could not compute its secret at the end of DKG.
+/// Each variant lists the key ids of the senders at fault.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum DkgFailure {
+    /// No private share arrived from these key ids
+    MissingShares(Vec<u32>),
+    /// The polynomial commitments of these key ids carry an invalid proof of knowledge
+    BadCommitments(Vec<u32>),
+    /// The private shares from these key ids do not match their polynomial commitments
+    BadShares(Vec<u32>),
+    /// The private shares from these key ids were built on a different set of commitments than
+    /// ours, so one of us was shown commitments the others were not
+    CommitmentMismatch(Vec<u32>),
+    /// No polynomial commitment arrived from these key ids before the round timed out
+    MissingCommitments(Vec<u32>),
+    /// The signers owning these key ids computed a different group key than ours
+    GroupKeyMismatch(Vec<u32>),
+    /// The signers owning these key ids did not tell us their group key before the round
+    /// timed out
+    MissingGroupKeys(Vec<u32>),
+}
+
+impl DkgFailure {
+    /// Key ids of the senders at fault
+    pub fn key_ids(&self) -> &[u32] {
+        match self {
+            DkgFailure::MissingShares(ids)
+            | DkgFailure::BadCommitments(ids)
+            | DkgFailure::BadShares(ids)
+            | DkgFailure::CommitmentMismatch(ids)
+            | DkgFailure::MissingCommitments(ids)
+            | DkgFailure::GroupKeyMismatch(ids)
+            | DkgFailure::MissingGroupKeys(ids) => ids,
+        }
+    }
+
+    fn hash(&self, hasher: &mut Hasher) {
+        let tag = match self {
+            DkgFailure::MissingShares(_) => 0u8,
+            DkgFailure::BadCommitments(_) => 1,
+            DkgFailure::BadShares(_) => 2,
+            DkgFailure::CommitmentMismatch(_) => 3,
+            DkgFailure::MissingCommitments(_) => 4,
+            DkgFailure::GroupKeyMismatch(_) => 5,
+            DkgFailure::MissingGroupKeys(_) => 6,
+        };
+        hasher.update([tag]);
+        hasher.update_len(self.key_ids().len());
+        for id in self.key_ids() {
+            hasher.update(id.to_be_bytes());
+        }
+    }
+}
+
+impl fmt::Display for DkgFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let problem = match self {
+            DkgFailure::MissingShares(_) => "missing private shares",
+            DkgFailure::BadCommitments(_) => "invalid polynomial commitments",
+            DkgFailure::BadShares(_) => "private shares failing verification",
+            DkgFailure::CommitmentMismatch(_) => "private shares built on other commitments",
+            DkgFailure::MissingCommitments(_) => "missing polynomial commitments",
+            DkgFailure::GroupKeyMismatch(_) => "a different group key",
+            DkgFailure::MissingGroupKeys(_) => "no group key",
+        };
+        write!(f, "{} from key ids {:?}", problem, self.key_ids())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RoundOutcome {
+    pub status: DkgStatus,
+    pub group_key: Option<Point>,
+}
+
+/// Non-secret view of a [`SigningRound`], for diagnosing a signer stuck mid-round.
+/// Key ids are the party ids used in DKG messages.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct RoundSnapshot {
+    pub signer_id: u32,
+    pub state: States,
+    pub dkg_id: u64,
+    pub dkg_public_id: u64,
+    /// Ids of the last nonce request this signer answered
+    pub sign_id: u64,
+    pub sign_nonce_id: u64,
+    /// Keys whose shares are needed to sign
+    pub threshold: usize,
+    /// Distinct signers which must take part in DKG
+    pub signers_threshold: usize,
+    pub total: usize,
+    /// Key ids owned by this signer
+    pub key_ids: Vec<u32>,
+    /// Key ids whose public commitments have been received
+    pub commitments_received: Vec<u32>,
+    pub commitments_missing: Vec<u32>,
+    /// Source key ids whose private shares have been received
+    pub shares_received: Vec<u32>,
+    pub shares_missing: Vec<u32>,
+    /// Signers whose private shares have been received, this signer included
+    pub signers_received: Vec<u32>,
+    /// DKG rounds with a known outcome
+    pub generations: Vec<u64>,
+    /// DKG rounds whose outcome we are still collecting peer attestations for
+    pub pending_outcome_queries: Vec<u64>,
+    /// The group key of the newest generation holding keys, `None` before any
+    #[serde(default)]
+    pub group_key: Option<GroupKeyView>,
+    /// Signing sessions holding nonces this signer handed out, see [`crate::sessions`]
+    #[serde(default)]
+    pub signing_sessions: Vec<SessionId>,
+}
+
+/// A signer's view of the group key it holds, for diffing views across the fleet when signers
+/// disagree on the key. Key ids are the party ids used in DKG messages.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct GroupKeyView {
+    pub signer_id: u32,
+    /// DKG round which produced the key
+    pub dkg_id: u64,
+    /// Compressed group public key, hex encoded
+    pub group_key: String,
+    /// Key ids owned by this signer
+    pub key_ids: Vec<u32>,
+    /// Signer id owning each key id whose shares went into the key
+    pub roster: BTreeMap<u32, u32>,
+    /// [`roster_hash`] of the roster, hex encoded
+    pub roster_hash: String,
+}
+
+/// Hash of the key ids whose shares built a group key and the signers owning them
+pub fn roster_hash(roster: &BTreeMap<u32, u32>) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update_str(tag::GROUP_KEY_ROSTER);
+    hasher.update_len(roster.len());
+    for (key_id, signer_id) in roster {
+        hasher.update(key_id.to_be_bytes());
+        hasher.update(signer_id.to_be_bytes());
+    }
+    hasher.finalize().into()
 }
 
+/// Protocol messages between the coordinator and the signers.
+///
+/// A `signer_id` is the 1-based id of a signer process from the config, a `key_id` the 0-based
+/// id of a frost key (a wtfrost party). One signer owns several keys, so messages about a key
+/// carry both, and receivers check the pair against the owners they know of.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub enum MessageTypes {
     DkgBegin(DkgBegin),
@@ -129,73 +386,854 @@ pub enum MessageTypes {
     DkgQueryResponse(DkgQueryResponse),
     DkgPublicShare(DkgPublicShare),
     DkgPrivateShares(DkgPrivateShares),
+    DkgShareDigests(DkgShareDigests),
+    EquivocationReport(EquivocationReport),
+    DkgBlame(DkgBlame),
     NonceRequest(NonceRequest),
     NonceResponse(NonceResponse),
     SignShareRequest(SignatureShareRequest),
     SignShareResponse(SignatureShareResponse),
+    RoundOutcomeQuery(RoundOutcomeQuery),
+    RoundOutcomeResponse(RoundOutcomeResponse),
+    Rejection(Rejection),
+    Hello(Hello),
+    RoundAbort(RoundAbort),
+    UpgradeWindow(UpgradeWindow),
+    UpgradeAck(UpgradeAck),
+    Registration(Registration),
+    BatchNonceRequest(BatchNonceRequest),
+    BatchNonceResponse(BatchNonceResponse),
+    BatchSignRequest(BatchSignRequest),
+    BatchSignResponse(BatchSignResponse),
+    MissingShareRequest(MissingShareRequest),
+    ReshareBegin(ReshareBegin),
+    ReshareShares(ReshareShares),
+    ReshareEnd(ReshareEnd),
+    ReshareCommit(ReshareCommit),
+    RosterChange(RosterChange),
+    RepairShares(RepairShares),
+    RepairedShares(RepairedShares),
+    RosterChangeEnd(RosterChangeEnd),
+    DkgGroupKey(DkgGroupKey),
+}
+
+impl MessageTypes {
+    /// Name of the message type, as logged
+    pub fn name(&self) -> &'static str {
+        match self {
+            MessageTypes::DkgBegin(_) => "DkgBegin",
+            MessageTypes::DkgPrivateBegin(_) => "DkgPrivateBegin",
+            MessageTypes::DkgEnd(_) => "DkgEnd",
+            MessageTypes::DkgPublicEnd(_) => "DkgPublicEnd",
+            MessageTypes::DkgQuery(_) => "DkgQuery",
+            MessageTypes::DkgQueryResponse(_) => "DkgQueryResponse",
+            MessageTypes::DkgPublicShare(_) => "DkgPublicShare",
+            MessageTypes::DkgPrivateShares(_) => "DkgPrivateShares",
+            MessageTypes::DkgShareDigests(_) => "DkgShareDigests",
+            MessageTypes::EquivocationReport(_) => "EquivocationReport",
+            MessageTypes::DkgBlame(_) => "DkgBlame",
+            MessageTypes::NonceRequest(_) => "NonceRequest",
+            MessageTypes::NonceResponse(_) => "NonceResponse",
+            MessageTypes::SignShareRequest(_) => "SignShareRequest",
+            MessageTypes::SignShareResponse(_) => "SignShareResponse",
+            MessageTypes::BatchNonceRequest(_) => "BatchNonceRequest",
+            MessageTypes::BatchNonceResponse(_) => "BatchNonceResponse",
+            MessageTypes::BatchSignRequest(_) => "BatchSignRequest",
+            MessageTypes::BatchSignResponse(_) => "BatchSignResponse",
+            MessageTypes::MissingShareRequest(_) => "MissingShareRequest",
+            MessageTypes::RoundOutcomeQuery(_) => "RoundOutcomeQuery",
+            MessageTypes::RoundOutcomeResponse(_) => "RoundOutcomeResponse",
+            MessageTypes::Rejection(_) => "Rejection",
+            MessageTypes::Hello(_) => "Hello",
+            MessageTypes::RoundAbort(_) => "RoundAbort",
+            MessageTypes::UpgradeWindow(_) => "UpgradeWindow",
+            MessageTypes::UpgradeAck(_) => "UpgradeAck",
+            MessageTypes::Registration(_) => "Registration",
+            MessageTypes::ReshareBegin(_) => "ReshareBegin",
+            MessageTypes::ReshareShares(_) => "ReshareShares",
+            MessageTypes::ReshareEnd(_) => "ReshareEnd",
+            MessageTypes::ReshareCommit(_) => "ReshareCommit",
+            MessageTypes::RosterChange(_) => "RosterChange",
+            MessageTypes::RepairShares(_) => "RepairShares",
+            MessageTypes::RepairedShares(_) => "RepairedShares",
+            MessageTypes::RosterChangeEnd(_) => "RosterChangeEnd",
+            MessageTypes::DkgGroupKey(_) => "DkgGroupKey",
+        }
+    }
+
+    /// Domain tag of the message type, see [`crate::protocol`]. Messages sharing a struct are
+    /// told apart in [`Self::request_hash`] by this tag.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            MessageTypes::DkgBegin(_) => tag::DKG_BEGIN,
+            MessageTypes::DkgPrivateBegin(_) => tag::DKG_PRIVATE_BEGIN,
+            MessageTypes::DkgEnd(_) => tag::DKG_END,
+            MessageTypes::DkgPublicEnd(_) => tag::DKG_PUBLIC_END,
+            MessageTypes::DkgQuery(_) => tag::DKG_QUERY,
+            MessageTypes::DkgQueryResponse(_) => tag::DKG_QUERY_RESPONSE,
+            MessageTypes::DkgPublicShare(_) => tag::DKG_PUBLIC_SHARE,
+            MessageTypes::DkgPrivateShares(_) => tag::DKG_PRIVATE_SHARES,
+            MessageTypes::DkgShareDigests(_) => tag::DKG_SHARE_DIGESTS,
+            MessageTypes::EquivocationReport(_) => tag::EQUIVOCATION_REPORT,
+            MessageTypes::DkgBlame(_) => tag::DKG_BLAME,
+            MessageTypes::NonceRequest(_) => tag::NONCE_REQUEST,
+            MessageTypes::NonceResponse(_) => tag::NONCE_RESPONSE,
+            MessageTypes::SignShareRequest(_) => tag::SIGNATURE_SHARE_REQUEST,
+            MessageTypes::SignShareResponse(_) => tag::SIGNATURE_SHARE_RESPONSE,
+            MessageTypes::BatchNonceRequest(_) => tag::BATCH_NONCE_REQUEST,
+            MessageTypes::BatchNonceResponse(_) => tag::BATCH_NONCE_RESPONSE,
+            MessageTypes::BatchSignRequest(_) => tag::BATCH_SIGN_REQUEST,
+            MessageTypes::BatchSignResponse(_) => tag::BATCH_SIGN_RESPONSE,
+            MessageTypes::MissingShareRequest(_) => tag::MISSING_SHARE_REQUEST,
+            MessageTypes::RoundOutcomeQuery(_) => tag::ROUND_OUTCOME_QUERY,
+            MessageTypes::RoundOutcomeResponse(_) => tag::ROUND_OUTCOME_RESPONSE,
+            MessageTypes::Rejection(_) => tag::REJECTION,
+            MessageTypes::Hello(_) => tag::HELLO,
+            MessageTypes::RoundAbort(_) => tag::ROUND_ABORT,
+            MessageTypes::UpgradeWindow(_) => tag::UPGRADE_WINDOW,
+            MessageTypes::UpgradeAck(_) => tag::UPGRADE_ACK,
+            MessageTypes::Registration(_) => tag::REGISTRATION,
+            MessageTypes::ReshareBegin(_) => tag::RESHARE_BEGIN,
+            MessageTypes::ReshareShares(_) => tag::RESHARE_SHARES,
+            MessageTypes::ReshareEnd(_) => tag::RESHARE_END,
+            MessageTypes::ReshareCommit(_) => tag::RESHARE_COMMIT,
+            MessageTypes::RosterChange(_) => tag::ROSTER_CHANGE,
+            MessageTypes::RepairShares(_) => tag::REPAIR_SHARES,
+            MessageTypes::RepairedShares(_) => tag::REPAIRED_SHARES,
+            MessageTypes::RosterChangeEnd(_) => tag::ROSTER_CHANGE_END,
+            MessageTypes::DkgGroupKey(_) => tag::DKG_GROUP_KEY,
+        }
+    }
+
+    /// The [`Signable`] message inside
+    fn signable(&self) -> &dyn Signable {
+        match self {
+            MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => msg,
+            MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => msg,
+            MessageTypes::DkgQuery(msg) => msg,
+            MessageTypes::DkgQueryResponse(msg) => msg,
+            MessageTypes::DkgPublicShare(msg) => msg,
+            MessageTypes::DkgPrivateShares(msg) => msg,
+            MessageTypes::DkgShareDigests(msg) => msg,
+            MessageTypes::EquivocationReport(msg) => msg,
+            MessageTypes::DkgBlame(msg) => msg,
+            MessageTypes::NonceRequest(msg) => msg,
+            MessageTypes::NonceResponse(msg) => msg,
+            MessageTypes::SignShareRequest(msg) => msg,
+            MessageTypes::SignShareResponse(msg) => msg,
+            MessageTypes::BatchNonceRequest(msg) => msg,
+            MessageTypes::BatchNonceResponse(msg) => msg,
+            MessageTypes::BatchSignRequest(msg) => msg,
+            MessageTypes::BatchSignResponse(msg) => msg,
+            MessageTypes::MissingShareRequest(msg) => msg,
+            MessageTypes::RoundOutcomeQuery(msg) => msg,
+            MessageTypes::RoundOutcomeResponse(msg) => msg,
+            MessageTypes::Rejection(msg) => msg,
+            MessageTypes::Hello(msg) => msg,
+            MessageTypes::RoundAbort(msg) => msg,
+            MessageTypes::UpgradeWindow(msg) => msg,
+            MessageTypes::UpgradeAck(msg) => msg,
+            MessageTypes::Registration(msg) => msg,
+            MessageTypes::ReshareBegin(msg) => msg,
+            MessageTypes::ReshareShares(msg) => msg,
+            MessageTypes::ReshareEnd(msg) => msg,
+            MessageTypes::ReshareCommit(msg) => msg,
+            MessageTypes::RosterChange(msg) => msg,
+            MessageTypes::RepairShares(msg) => msg,
+            MessageTypes::RepairedShares(msg) => msg,
+            MessageTypes::RosterChangeEnd(msg) => msg,
+            MessageTypes::DkgGroupKey(msg) => msg,
+        }
+    }
+
+    /// Signature over the message with `private_key`. Outbound messages are signed by
+    /// [`crate::auth::Identity::sign_message`], which also records them in the audit log.
+    pub fn sign(&self, private_key: &Scalar) -> Result<Vec<u8>, ecdsa::Error> {
+        self.signable().sign(private_key)
+    }
+
+    /// Whether `signature` was made over the message by the key of `public_key`
+    pub fn verify(&self, signature: &[u8], public_key: &ecdsa::PublicKey) -> bool {
+        self.signable().verify(signature, public_key)
+    }
+
+    /// Digest identifying a request, echoed back in a [`Rejection`]
+    pub fn request_hash(&self) -> [u8; 32] {
+        let mut hasher = Hasher::new();
+        match self {
+            MessageTypes::DkgBegin(msg) | MessageTypes::DkgPrivateBegin(msg) => {
+                hasher.update_str(self.tag());
+                msg.hash(&mut hasher);
+            }
+            MessageTypes::DkgEnd(msg) | MessageTypes::DkgPublicEnd(msg) => {
+                hasher.update_str(self.tag());
+                msg.hash(&mut hasher);
+            }
+            MessageTypes::DkgQuery(msg) => msg.hash(&mut hasher),
+            MessageTypes::DkgQueryResponse(msg) => msg.hash(&mut hasher),
+            MessageTypes::DkgPublicShare(msg) => msg.hash(&mut hasher),
+            MessageTypes::DkgPrivateShares(msg) => msg.hash(&mut hasher),
+            MessageTypes::DkgShareDigests(msg) => msg.hash(&mut hasher),
+            MessageTypes::EquivocationReport(msg) => msg.hash(&mut hasher),
+            MessageTypes::DkgBlame(msg) => msg.hash(&mut hasher),
+            MessageTypes::NonceRequest(msg) => msg.hash(&mut hasher),
+            MessageTypes::NonceResponse(msg) => msg.hash(&mut hasher),
+            MessageTypes::SignShareRequest(msg) => msg.hash(&mut hasher),
+            MessageTypes::SignShareResponse(msg) => msg.hash(&mut hasher),
+            MessageTypes::BatchNonceRequest(msg) => msg.hash(&mut hasher),
+            MessageTypes::BatchNonceResponse(msg) => msg.hash(&mut hasher),
+            MessageTypes::BatchSignRequest(msg) => msg.hash(&mut hasher),
+            MessageTypes::BatchSignResponse(msg) => msg.hash(&mut hasher),
+            MessageTypes::MissingShareRequest(msg) => msg.hash(&mut hasher),
+            MessageTypes::RoundOutcomeQuery(msg) => msg.hash(&mut hasher),
+            MessageTypes::RoundOutcomeResponse(msg) => msg.hash(&mut hasher),
+            MessageTypes::Rejection(msg) => msg.hash(&mut hasher),
+            MessageTypes::Hello(msg) => msg.hash(&mut hasher),
+            MessageTypes::RoundAbort(msg) => msg.hash(&mut hasher),
+            MessageTypes::UpgradeWindow(msg) => msg.hash(&mut hasher),
+            MessageTypes::UpgradeAck(msg) => msg.hash(&mut hasher),
+            MessageTypes::Registration(msg) => msg.hash(&mut hasher),
+            MessageTypes::ReshareBegin(msg) => msg.hash(&mut hasher),
+            MessageTypes::ReshareShares(msg) => msg.hash(&mut hasher),
+            MessageTypes::ReshareEnd(msg) => msg.hash(&mut hasher),
+            MessageTypes::ReshareCommit(msg) => msg.hash(&mut hasher),
+            MessageTypes::RosterChange(msg) => msg.hash(&mut hasher),
+            MessageTypes::RepairShares(msg) => msg.hash(&mut hasher),
+            MessageTypes::RepairedShares(msg) => msg.hash(&mut hasher),
+            MessageTypes::RosterChangeEnd(msg) => msg.hash(&mut hasher),
+            MessageTypes::DkgGroupKey(msg) => msg.hash(&mut hasher),
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// Why a signer refused to act on a request
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum RejectionCode {
+    /// The request arrived while the signer was in the wrong state
+    InvalidState,
+    InvalidKeyId,
+    /// A message named a signer as the owner of a key it does not own
+    KeyOwnerMismatch,
+    InvalidDkgPublicShare,
+    InvalidDkgPrivateShares,
+    InvalidNonceResponse,
+    InvalidSignatureShare,
+    /// A signature share was requested for a message which is not a 32-byte digest
+    InvalidMessage,
+    /// A batch was too large, or named a nonce the signer did not hand out
+    InvalidBatch,
+    /// A signature share was requested with a nonce the signer did not hand out in that
+    /// session, or has spent
+    UnknownNonce,
+    /// A key share refresh named a generation the signer holds no keys of
+    NoKeys,
+    /// Refresh shares were malformed, or could not be applied
+    InvalidReshare,
+    /// A roster change, or the shares repairing a key, were malformed or came from a signer
+    /// which is no helper
+    InvalidRosterChange,
+}
+
+impl RejectionCode {
+    /// Whether the signer may accept the request later, e.g. once it has caught up
+    pub fn is_transient(&self) -> bool {
+        matches!(self, RejectionCode::InvalidState)
+    }
+}
+
+impl From<&Error> for RejectionCode {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::InvalidKeyId => RejectionCode::InvalidKeyId,
+            Error::KeyOwnerMismatch { .. } => RejectionCode::KeyOwnerMismatch,
+            Error::InvalidDkgPublicShare => RejectionCode::InvalidDkgPublicShare,
+            Error::InvalidDkgPrivateShares(_) => RejectionCode::InvalidDkgPrivateShares,
+            Error::InvalidNonceResponse => RejectionCode::InvalidNonceResponse,
+            Error::InvalidSignatureShare => RejectionCode::InvalidSignatureShare,
+            Error::InvalidMessage(_) => RejectionCode::InvalidMessage,
+            Error::InvalidBatch(_) => RejectionCode::InvalidBatch,
+            Error::UnknownNonce { .. } => RejectionCode::UnknownNonce,
+            Error::NoKeys(_) => RejectionCode::NoKeys,
+            Error::InvalidReshareShares(_) | Error::Reshare(_) => RejectionCode::InvalidReshare,
+            Error::InvalidRosterChange(_) | Error::InvalidRepairShares(_) => {
+                RejectionCode::InvalidRosterChange
+            }
+            Error::StateMachineError(_) => RejectionCode::InvalidState,
+        }
+    }
+}
+
+/// Sent by a signer in place of a response when it refuses a request
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    pub signer_id: u32,
+    /// [`MessageTypes::request_hash`] of the refused request
+    pub request_hash: Vec<u8>,
+    pub reason_code: RejectionCode,
+    pub detail: String,
+}
+
+impl Signable for Rejection {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::REJECTION);
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_bytes(&self.request_hash);
+        hasher.update([self.reason_code as u8]);
+        hasher.update_str(&self.detail);
+    }
+}
+
+/// Sent by the coordinator when it gives up on a DKG or signing round, so signers
+/// drop the round's partial state instead of waiting for messages that won't come
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RoundAbort {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    pub reason: String,
+}
+
+impl Signable for RoundAbort {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::ROUND_ABORT);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update_str(&self.reason);
+    }
+}
+
+/// Sent by the coordinator ahead of a maintenance window. From `starts_in_secs` after it is
+/// sent, no new round starts until enough signers have sent a [`Registration`] again, or for at
+/// most `duration_secs`, so signers can restart into upgraded software between rounds.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct UpgradeWindow {
+    pub window_id: u64,
+    pub starts_in_secs: u64,
+    pub duration_secs: u64,
+    pub reason: String,
+}
+
+impl Signable for UpgradeWindow {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::UPGRADE_WINDOW);
+        hasher.update(self.window_id.to_be_bytes());
+        hasher.update(self.starts_in_secs.to_be_bytes());
+        hasher.update(self.duration_secs.to_be_bytes());
+        hasher.update_str(&self.reason);
+    }
+}
+
+/// A signer's acknowledgement of an [`UpgradeWindow`]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct UpgradeAck {
+    pub signer_id: u32,
+    pub window_id: u64,
+}
+
+impl Signable for UpgradeAck {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::UPGRADE_ACK);
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.window_id.to_be_bytes());
+    }
+}
+
+/// Sent by a signer whenever it (re)starts, with the version of the software it runs
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Registration {
+    pub signer_id: u32,
+    pub version: String,
+}
+
+impl Signable for Registration {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::REGISTRATION);
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_str(&self.version);
+    }
+}
+
+/// Broadcast by each signer during the pre-DKG ceremony, see [`crate::ceremony`]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Hello {
+    pub signer_id: u32,
+    /// Compressed network public key derived from the signer's own private key
+    pub public_key: Vec<u8>,
+    /// [`crate::config::Config::group_hash`] of the signer's config
+    pub config_hash: Vec<u8>,
+}
+
+impl Signable for Hello {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::HELLO);
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_bytes(&self.public_key);
+        hasher.update_bytes(&self.config_hash);
+    }
+}
+
+impl Hello {
+    /// Whether `signature` was made by the key the hello carries
+    pub fn is_self_signed(&self, signature: &[u8]) -> bool {
+        ecdsa::PublicKey::try_from(self.public_key.as_slice())
+            .map(|key| self.verify(signature, &key))
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DkgPublicShare {
     pub dkg_id: u64,
     pub dkg_public_id: u64,
-    pub party_id: u32,
+    /// Signer owning `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
     pub public_share: PolyCommitment,
 }
 
 impl Signable for DkgPublicShare {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("DKG_PUBLIC_SHARE".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_PUBLIC_SHARE);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.dkg_public_id.to_be_bytes());
-        hasher.update(self.party_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
+        hasher.update_len(self.public_share.A.len());
         for a in &self.public_share.A {
             hasher.update(a.compress().as_bytes());
         }
     }
 }
 
+thread_local! {
+    /// Scratch space for sorting the destination key ids of [`DkgPrivateShares`] while hashing
+    static DST_KEY_IDS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DkgPrivateShares {
     pub dkg_id: u64,
-    pub key_id: u32,
-    pub private_shares: HashMap<usize, Scalar>,
+    pub signer_id: u32,
+    /// (src_key_id, Map<dst_key_id, share>) for every key_id owned by the sender, in the
+    /// clear. Empty when the shares are sent in `encrypted_shares`.
+    pub private_shares: Vec<(u32, HashMap<usize, Scalar>)>,
+    /// The same shares, sealed to the signer owning each dst_key_id, see
+    /// [`crate::share_cipher`]
+    pub encrypted_shares: Vec<EncryptedShares>,
+    /// [`commitment_digest`] of the commitments the sender built its shares on
+    pub commitment_digest: [u8; 32],
 }
 
-impl Signable for DkgPrivateShares {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("DKG_PRIVATE_SHARES".as_bytes());
+/// Digests of the DkgPrivateShares a signer received in a DKG round, by sender. Signers
+/// exchange them as they end DKG so that a sender showing different shares to different peers
+/// is caught.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DkgShareDigests {
+    pub dkg_id: u64,
+    pub signer_id: u32,
+    /// (sender signer_id, [`Signable::digest`] of its DkgPrivateShares)
+    pub digests: Vec<(u32, [u8; 32])>,
+}
+
+impl Signable for DkgShareDigests {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_SHARE_DIGESTS);
         hasher.update(self.dkg_id.to_be_bytes());
-        hasher.update(self.key_id.to_be_bytes());
-        for (id, share) in &self.private_shares {
-            hasher.update(id.to_be_bytes());
-            hasher.update(share.to_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_len(self.digests.len());
+        for (sender_id, digest) in &self.digests {
+            hasher.update(sender_id.to_be_bytes());
+            hasher.update(digest);
+        }
+    }
+}
+
+/// Sent to the coordinator by a signer which found a peer sending different private shares
+/// to different signers in a DKG round
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EquivocationReport {
+    pub dkg_id: u64,
+    pub signer_id: u32,
+    /// The signer which equivocated
+    pub equivocator_id: u32,
+    /// (receiving signer_id, digest of the DkgPrivateShares it got) for every receiver we know of
+    pub digests: Vec<(u32, [u8; 32])>,
+}
+
+impl Signable for EquivocationReport {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::EQUIVOCATION_REPORT);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.equivocator_id.to_be_bytes());
+        hasher.update_len(self.digests.len());
+        for (receiver_id, digest) in &self.digests {
+            hasher.update(receiver_id.to_be_bytes());
+            hasher.update(digest);
+        }
+    }
+}
+
+/// Sent to the coordinator by a signer whose private shares from a peer do not match the
+/// peer's commitments, with the evidence: the peer's [`DkgPrivateShares`] as it went over the
+/// relay, and the key the shares sealed to us open with. The coordinator saw the message and
+/// the commitments itself, so it can tell a peer which sent bad shares from a signer blaming it
+/// falsely. The key only opens the shares the peer dealt us in a round which is failing.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DkgBlame {
+    pub dkg_id: u64,
+    pub signer_id: u32,
+    /// The signer which sent the bad shares
+    pub accused_id: u32,
+    pub shares: DkgPrivateShares,
+    /// Key of the entry of `shares` sealed to us, see [`ShareCipher::share_key`], none if the
+    /// shares came in the clear
+    pub share_key: Option<[u8; 32]>,
+}
+
+impl DkgBlame {
+    /// Whether `shares` holds a share for one of `key_ids`, the keys of the blaming signer,
+    /// which does not match the commitment of the key which sent it, as given by `commitment`.
+    /// The caller checks that `shares` is the message the accused sent, built on the
+    /// commitments of the round.
+    pub fn proves_bad_shares<'a>(
+        &self,
+        key_ids: &[u32],
+        commitment: impl Fn(u32) -> Option<&'a PolyCommitment>,
+    ) -> bool {
+        if self.shares.dkg_id != self.dkg_id || self.shares.signer_id != self.accused_id {
+            return false;
+        }
+        let mut received = match &self.share_key {
+            Some(share_key) => {
+                let sealed = self
+                    .shares
+                    .encrypted_shares
+                    .iter()
+                    .find(|sealed| sealed.signer_id == self.signer_id);
+                let opened = sealed.and_then(|sealed| {
+                    share_cipher::open_with_key(share_key, &self.shares.commitment_digest, sealed)
+                });
+                match opened {
+                    Some(opened) => opened,
+                    None => return false,
+                }
+            }
+            None => self.shares.private_shares.clone(),
+        };
+        let proven = received.iter().any(|(src_key_id, shares)| {
+            let Some(commitment) = commitment(*src_key_id) else {
+                return false;
+            };
+            key_ids.iter().any(|key_id| {
+                shares.get(&(*key_id as usize)).is_some_and(|share| {
+                    !share_matches_commitment(commitment, *key_id as usize, share)
+                })
+            })
+        });
+        share_cipher::wipe(&mut received);
+        proven
+    }
+}
+
+impl Signable for DkgBlame {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_BLAME);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.accused_id.to_be_bytes());
+        hasher.update(self.shares.digest());
+        hasher.update_present(self.share_key.is_some());
+        if let Some(share_key) = &self.share_key {
+            hasher.update(share_key);
+        }
+    }
+}
+
+/// Broadcast by a signer once it computed its secret in a DKG round, with the group key it
+/// got, or none if it failed. A signer only reports success in its [`DkgEnd`] once every
+/// signer of the round sent its group key and none differs from its own.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct DkgGroupKey {
+    pub dkg_id: u64,
+    pub signer_id: u32,
+    pub group_key: Option<Point>,
+}
+
+impl Signable for DkgGroupKey {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_GROUP_KEY);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_present(self.group_key.is_some());
+        if let Some(group_key) = &self.group_key {
+            hasher.update(group_key.compress().as_bytes());
+        }
+    }
+}
+
+/// Stalls of a DKG round a signer asks its peers for the pieces it is missing
+pub const MAX_SHARE_REQUEST_ATTEMPTS: u32 = 3;
+
+/// Quiet time after which a signer counts a DKG round it is gathering for as stalled
+pub const DEFAULT_DKG_STALL: Duration = Duration::from_secs(10);
+
+/// Time a signer waits in each gather phase of a DKG round, unless configured otherwise
+pub const DEFAULT_DKG_PHASE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long a signer waits for the commitments and for the private shares of a DKG round
+/// before failing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DkgTimeouts {
+    pub public: Duration,
+    pub private: Duration,
+}
+
+impl Default for DkgTimeouts {
+    fn default() -> Self {
+        Self {
+            public: DEFAULT_DKG_PHASE_TIMEOUT,
+            private: DEFAULT_DKG_PHASE_TIMEOUT,
         }
     }
 }
 
+/// The phase of DKG a [`MissingShareRequest`] is about
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum DkgPhase {
+    /// The polynomial commitments, sent as [`DkgPublicShare`]s
+    Public,
+    /// The private shares, sent as [`DkgPrivateShares`]
+    Private,
+}
+
+/// Broadcast by a signer stalled in a DKG round, asking the owner of party `from_party` to send
+/// again what party `to_party` is missing from it: its commitment, or its private shares. A
+/// signer sends the private shares of all its parties in one message, so it sends that message
+/// again whole, and peers comparing [`DkgShareDigests`] see the same digest as before.
+/// Each signer answers a requester once per party and `attempt`, for at most
+/// [`MAX_SHARE_REQUEST_ATTEMPTS`] attempts a round.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct MissingShareRequest {
+    pub dkg_id: u64,
+    /// Signer asking, the owner of `to_party`
+    pub signer_id: u32,
+    pub from_party: u32,
+    pub to_party: u32,
+    pub phase: DkgPhase,
+    /// Stalls of the round the requester asked for pieces before, from 0
+    pub attempt: u32,
+}
+
+impl Signable for MissingShareRequest {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::MISSING_SHARE_REQUEST);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.from_party.to_be_bytes());
+        hasher.update(self.to_party.to_be_bytes());
+        hasher.update([self.phase as u8]);
+        hasher.update(self.attempt.to_be_bytes());
+    }
+}
+
+/// Senders whose DkgPrivateShares reached receivers in more than one version, given the
+/// digests each receiver got by sender. Maps each equivocator to the (receiver, digest) pairs.
+pub fn find_equivocations<'a>(
+    views: impl IntoIterator<Item = (&'a u32, &'a BTreeMap<u32, [u8; 32]>)>,
+) -> BTreeMap<u32, Vec<(u32, [u8; 32])>> {
+    let mut received: BTreeMap<u32, Vec<(u32, [u8; 32])>> = BTreeMap::new();
+    for (receiver_id, digests) in views {
+        for (sender_id, digest) in digests {
+            received
+                .entry(*sender_id)
+                .or_default()
+                .push((*receiver_id, *digest));
+        }
+    }
+    received.retain(|_, digests| digests.iter().any(|(_, digest)| *digest != digests[0].1));
+    for digests in received.values_mut() {
+        digests.sort();
+    }
+    received
+}
+
+/// Digest binding the commitments of DKG round `dkg_id`, given in key id order. Signers send it
+/// with their private shares, and receivers whose commitments hash differently drop the shares:
+/// a relay showing different commitments to different signers cannot go unnoticed.
+pub fn commitment_digest<'a>(
+    dkg_id: u64,
+    commitments: impl ExactSizeIterator<Item = (&'a u32, &'a PolyCommitment)>,
+) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update_str(tag::DKG_COMMITMENTS);
+    hasher.update(dkg_id.to_be_bytes());
+    hasher.update_len(commitments.len());
+    for (key_id, commitment) in commitments {
+        hasher.update(key_id.to_be_bytes());
+        hasher.update_len(commitment.A.len());
+        for a in &commitment.A {
+            hasher.update(a.compress().as_bytes());
+        }
+    }
+    hasher.finalize().into()
+}
+
+impl Signable for DkgPrivateShares {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_PRIVATE_SHARES);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.commitment_digest);
+        hash_private_shares(hasher, &self.private_shares);
+        hash_encrypted_shares(hasher, &self.encrypted_shares);
+    }
+}
+
+/// Hash sealed shares, as sent in [`DkgPrivateShares`], [`ReshareShares`], [`RepairShares`] and
+/// [`RepairedShares`]
+fn hash_encrypted_shares(hasher: &mut Hasher, encrypted_shares: &[EncryptedShares]) {
+    hasher.update_len(encrypted_shares.len());
+    for sealed in encrypted_shares {
+        hasher.update(sealed.signer_id.to_be_bytes());
+        hasher.update_bytes(&sealed.ciphertext);
+    }
+}
+
+/// Hash (src_key_id, Map<dst_key_id, share>) entries, as sent in [`DkgPrivateShares`] and
+/// [`ReshareShares`]
+fn hash_private_shares(hasher: &mut Hasher, private_shares: &[(u32, HashMap<usize, Scalar>)]) {
+    hasher.update_len(private_shares.len());
+    for (src_key_id, shares) in private_shares {
+        hasher.update(src_key_id.to_be_bytes());
+        hasher.update_len(shares.len());
+        // HashMap iteration order is not stable across processes, so hash in dst_key_id order,
+        // as u32 whatever the width of usize. The ids are sorted in a per-thread buffer rather
+        // than a new vector per message.
+        DST_KEY_IDS.with(|dst_key_ids| {
+            let mut dst_key_ids = dst_key_ids.borrow_mut();
+            dst_key_ids.clear();
+            dst_key_ids.extend(shares.keys().copied());
+            dst_key_ids.sort_unstable();
+            for dst_key_id in dst_key_ids.iter() {
+                hasher.update((*dst_key_id as u32).to_be_bytes());
+                hasher.update(shares[dst_key_id].to_bytes());
+            }
+        });
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DkgBegin {
     pub dkg_id: u64, //TODO: Strong typing for this, alternatively introduce a type alias
+    /// Key ids of signers the coordinator dropped from the roster. Their commitments and
+    /// shares are taken to be zero and no shares are sent to them.
+    pub excluded_key_ids: Vec<u32>,
 }
 
 impl Signable for DkgBegin {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("DKG_BEGIN".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_BEGIN);
         hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update_len(self.excluded_key_ids.len());
+        for key_id in &self.excluded_key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+    }
+}
+
+/// Whether `share`, sent to key `dst_key_id`, is the value at that key of the polynomial
+/// `commitment` commits to, i.e. `share * G == A_0 + A_1 * x + A_2 * x^2 + ...` at the key's
+/// x coordinate
+pub fn share_matches_commitment(
+    commitment: &PolyCommitment,
+    dst_key_id: usize,
+    share: &Scalar,
+) -> bool {
+    share_matches_points(&commitment.A, dst_key_id, share)
+}
+
+/// The same, given the commitment's points `A_0, A_1, ...`
+pub fn share_matches_points(a: &[Point], dst_key_id: usize, share: &Scalar) -> bool {
+    let x = compute::id(dst_key_id);
+    let expected = a.iter().rev().fold(Point::default(), |acc, a| x * acc + *a);
+    Point::from(*share) == expected
+}
+
+/// Each of `parties`' private shares for every key not `excluded`, by the party's key id.
+/// The parties' shares are computed across the available cores.
+pub fn party_shares(
+    parties: &[v1::Party],
+    excluded: &BTreeSet<u32>,
+) -> Vec<(u32, HashMap<usize, Scalar>)> {
+    parties
+        .par_iter()
+        .map(|party| {
+            let mut shares = party.get_shares();
+            for key_id in excluded {
+                if let Some(mut share) = shares.remove(&(*key_id as usize)) {
+                    share.wipe();
+                }
+            }
+            (party.id as u32, shares)
+        })
+        .collect()
+}
+
+/// Source key ids of `received` with a share that does not match the source's commitment in
+/// `commitments`, see [`share_matches_commitment`]. Sources without a commitment are not
+/// checked. Every (source, destination) pair is checked across the available cores.
+pub fn mismatched_shares(
+    commitments: &BTreeMap<u32, PolyCommitment>,
+    received: &[(u32, HashMap<usize, Scalar>)],
+) -> BTreeSet<u32> {
+    let pairs: Vec<(u32, &PolyCommitment, usize, &Scalar)> = received
+        .iter()
+        .filter_map(|(src_key_id, shares)| {
+            commitments
+                .get(src_key_id)
+                .map(|commitment| (*src_key_id, commitment, shares))
+        })
+        .flat_map(|(src_key_id, commitment, shares)| {
+            shares
+                .iter()
+                .map(move |(dst_key_id, share)| (src_key_id, commitment, *dst_key_id, share))
+        })
+        .collect();
+    pairs
+        .into_par_iter()
+        .filter(|(_, commitment, dst_key_id, share)| {
+            !share_matches_commitment(commitment, *dst_key_id, share)
+        })
+        .map(|(src_key_id, ..)| src_key_id)
+        .collect::<Vec<u32>>()
+        .into_iter()
+        .collect()
+}
+
+/// Commitment standing in for an excluded key: a zero polynomial, which adds nothing to the
+/// group key and matches the zero shares the excluded key is taken to have sent
+pub fn null_commitment<RNG: RngCore + CryptoRng>(
+    key_id: u32,
+    threshold: usize,
+    rng: &mut RNG,
+) -> PolyCommitment {
+    PolyCommitment {
+        id: ID::new(&compute::id(key_id as usize), &Scalar::from(0), rng),
+        A: vec![Point::default(); threshold],
     }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct DkgEnd {
     pub dkg_id: u64,
-    pub signer_id: usize,
+    pub signer_id: u32,
     pub status: DkgStatus,
 }
 
 impl Signable for DkgEnd {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("DKG_END".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_END);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.signer_id.to_be_bytes());
     }
@@ -205,8 +1243,8 @@ impl Signable for DkgEnd {
 pub struct DkgQuery {}
 
 impl Signable for DkgQuery {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("DKG_QUERY".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_QUERY);
     }
 }
 
@@ -217,10 +1255,11 @@ pub struct DkgQueryResponse {
 }
 
 impl Signable for DkgQueryResponse {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("DKG_QUERY_RESPONSE".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::DKG_QUERY_RESPONSE);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.public_share.id.id.to_bytes());
+        hasher.update_len(self.public_share.A.len());
         for a in &self.public_share.A {
             hasher.update(a.compress().as_bytes());
         }
@@ -231,14 +1270,17 @@ impl Signable for DkgQueryResponse {
 pub struct NonceRequest {
     pub dkg_id: u64,
     pub sign_id: u64,
+    /// Signing session the nonces are for, see [`crate::sessions`]
+    pub correlation_id: u64,
     pub sign_nonce_id: u64,
 }
 
 impl Signable for NonceRequest {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("NONCE_REQUEST".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::NONCE_REQUEST);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.correlation_id.to_be_bytes());
         hasher.update(self.sign_nonce_id.to_be_bytes());
     }
 }
@@ -247,48 +1289,71 @@ impl Signable for NonceRequest {
 pub struct NonceResponse {
     pub dkg_id: u64,
     pub sign_id: u64,
+    pub correlation_id: u64,
     pub sign_nonce_id: u64,
-    pub party_id: u32,
+    /// Signer owning `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
     pub nonce: PublicNonce,
 }
 
 impl Signable for NonceResponse {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("NONCE_RESPONSE".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::NONCE_RESPONSE);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.correlation_id.to_be_bytes());
         hasher.update(self.sign_nonce_id.to_be_bytes());
-        hasher.update(self.party_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
         hasher.update(self.nonce.D.compress().as_bytes());
         hasher.update(self.nonce.E.compress().as_bytes());
     }
 }
 
+/// Asks a key for a share of the signature of one message. Several messages are signed in a
+/// single round trip with a [`BatchSignRequest`] instead, answered with a share per message.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct SignatureShareRequest {
     pub dkg_id: u64,
     pub sign_id: u64,
     pub correlation_id: u64,
-    pub party_id: u32,
+    /// Signer the request is addressed to, the owner of `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
+    /// Public nonces of the key ids taking part in the signature
     pub nonces: Vec<(u32, PublicNonce)>,
+    /// The 32-byte digest to sign, see [`crate::digest`]
     pub message: Vec<u8>,
+    /// The output `message` spends by its key path, whose tweak of the group key the shares
+    /// sign with, see [`crate::taproot`]
+    pub key_path: Option<KeyPath>,
 }
 
 impl Signable for SignatureShareRequest {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("SIGNATURE_SHARE_REQUEST".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::SIGNATURE_SHARE_REQUEST);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.sign_id.to_be_bytes());
         hasher.update(self.correlation_id.to_be_bytes());
-        hasher.update(self.party_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
 
+        hasher.update_len(self.nonces.len());
         for (id, nonce) in &self.nonces {
             hasher.update(id.to_be_bytes());
             hasher.update(nonce.D.compress().as_bytes());
             hasher.update(nonce.E.compress().as_bytes());
         }
 
-        hasher.update(self.message.as_slice());
+        hasher.update_bytes(&self.message);
+        hasher.update_present(self.key_path.is_some());
+        if let Some(key_path) = &self.key_path {
+            hasher.update_present(key_path.merkle_root.is_some());
+            if let Some(merkle_root) = &key_path.merkle_root {
+                hasher.update(merkle_root);
+            }
+        }
     }
 }
 
@@ -297,22 +1362,419 @@ pub struct SignatureShareResponse {
     pub dkg_id: u64,
     pub sign_id: u64,
     pub correlation_id: u64,
-    pub party_id: u32,
+    /// Signer owning `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
     pub signature_share: wtfrost::v1::SignatureShare,
 }
 
 impl Signable for SignatureShareResponse {
-    fn hash(&self, hasher: &mut Sha256) {
-        hasher.update("SIGNATURE_SHARE_RESPONSE".as_bytes());
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::SIGNATURE_SHARE_RESPONSE);
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.sign_id.to_be_bytes());
         hasher.update(self.correlation_id.to_be_bytes());
-        hasher.update(self.party_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
         hasher.update(self.signature_share.id.to_be_bytes());
         hasher.update(self.signature_share.z_i.to_bytes());
     }
 }
 
+/// Messages a single [`BatchSignRequest`] may carry, and nonces a [`BatchNonceRequest`] may
+/// ask each key for
+pub const MAX_BATCH_SIZE: usize = 256;
+
+/// Asks every key for `count` nonces at once, to sign a batch of messages in one round
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchNonceRequest {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    pub sign_nonce_id: u64,
+    pub count: u32,
+}
+
+impl Signable for BatchNonceRequest {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::BATCH_NONCE_REQUEST);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.sign_nonce_id.to_be_bytes());
+        hasher.update(self.count.to_be_bytes());
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchNonceResponse {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    pub sign_nonce_id: u64,
+    /// Signer owning `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
+    pub nonces: Vec<PublicNonce>,
+}
+
+impl Signable for BatchNonceResponse {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::BATCH_NONCE_RESPONSE);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.sign_nonce_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
+        hasher.update_len(self.nonces.len());
+        for nonce in &self.nonces {
+            hasher.update(nonce.D.compress().as_bytes());
+            hasher.update(nonce.E.compress().as_bytes());
+        }
+    }
+}
+
+/// One message of a [`BatchSignRequest`]
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchMessage {
+    /// Public nonces of the key ids taking part in the signature, each spent on this message only
+    pub nonces: Vec<(u32, PublicNonce)>,
+    /// The 32-byte digest to sign, see [`crate::digest`]
+    pub message: Vec<u8>,
+}
+
+/// Asks a key for a share of the signature of every message, e.g. the sighashes of the
+/// inputs of a multi-input transaction, in a single round trip
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchSignRequest {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    /// Signer the request is addressed to, the owner of `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
+    pub messages: Vec<BatchMessage>,
+    /// The output every message spends by its key path, see [`SignatureShareRequest::key_path`]
+    pub key_path: Option<KeyPath>,
+}
+
+impl Signable for BatchSignRequest {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::BATCH_SIGN_REQUEST);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
+        hasher.update_len(self.messages.len());
+        for message in &self.messages {
+            hasher.update_len(message.nonces.len());
+            for (id, nonce) in &message.nonces {
+                hasher.update(id.to_be_bytes());
+                hasher.update(nonce.D.compress().as_bytes());
+                hasher.update(nonce.E.compress().as_bytes());
+            }
+            hasher.update_bytes(&message.message);
+        }
+        hasher.update_present(self.key_path.is_some());
+        if let Some(key_path) = &self.key_path {
+            hasher.update_present(key_path.merkle_root.is_some());
+            if let Some(merkle_root) = &key_path.merkle_root {
+                hasher.update(merkle_root);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BatchSignResponse {
+    pub dkg_id: u64,
+    pub sign_id: u64,
+    /// Signer owning `key_id`
+    pub signer_id: u32,
+    pub key_id: u32,
+    /// A share for each message of the request, in its order
+    pub signature_shares: Vec<wtfrost::v1::SignatureShare>,
+}
+
+impl Signable for BatchSignResponse {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::BATCH_SIGN_RESPONSE);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.key_id.to_be_bytes());
+        hasher.update_len(self.signature_shares.len());
+        for share in &self.signature_shares {
+            hasher.update(share.id.to_be_bytes());
+            hasher.update(share.z_i.to_bytes());
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RoundOutcomeQuery {
+    pub dkg_id: u64,
+    pub signer_id: u32,
+}
+
+impl Signable for RoundOutcomeQuery {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::ROUND_OUTCOME_QUERY);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+    }
+}
+
+/// A signer's signed attestation of how a past DKG round ended
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RoundOutcomeResponse {
+    pub dkg_id: u64,
+    pub signer_id: u32,
+    pub key_ids: Vec<u32>,
+    pub outcome: RoundOutcome,
+}
+
+impl Signable for RoundOutcomeResponse {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::ROUND_OUTCOME_RESPONSE);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_len(self.key_ids.len());
+        for key_id in &self.key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+        self.outcome.status.hash(hasher);
+        hasher.update_present(self.outcome.group_key.is_some());
+        if let Some(group_key) = &self.outcome.group_key {
+            hasher.update(group_key.compress().as_bytes());
+        }
+    }
+}
+
+/// Sent by the coordinator to refresh the key shares of generation `dkg_id` without changing
+/// its group key, see [`crate::reshare`]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ReshareBegin {
+    pub dkg_id: u64,
+    /// Refresh of the generation, counted by the coordinator
+    pub reshare_id: u64,
+}
+
+impl Signable for ReshareBegin {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::RESHARE_BEGIN);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.reshare_id.to_be_bytes());
+    }
+}
+
+/// Broadcast by each signer in a refresh round: for every key it owns, the commitment to the
+/// key's zero polynomial and the polynomial's value at every key not excluded
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ReshareShares {
+    pub dkg_id: u64,
+    pub reshare_id: u64,
+    pub signer_id: u32,
+    /// (src_key_id, commitment) for every key_id owned by the sender
+    pub commitments: Vec<(u32, Vec<Point>)>,
+    /// (src_key_id, Map<dst_key_id, share>) for every key_id owned by the sender, in the
+    /// clear. Empty when the shares are sent in `encrypted_shares`.
+    pub shares: Vec<(u32, HashMap<usize, Scalar>)>,
+    /// The same shares, sealed to the signer owning each dst_key_id, see
+    /// [`crate::share_cipher`]
+    pub encrypted_shares: Vec<EncryptedShares>,
+}
+
+impl ReshareShares {
+    /// [`reshare::refresh_digest`] of the sender's commitments, which its shares are sealed to
+    pub fn commitment_digest(&self) -> [u8; 32] {
+        let commitments = self.commitments.iter().cloned().collect();
+        reshare::refresh_digest(self.dkg_id, self.reshare_id, &commitments)
+    }
+}
+
+impl Signable for ReshareShares {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::RESHARE_SHARES);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.reshare_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_len(self.commitments.len());
+        for (src_key_id, commitment) in &self.commitments {
+            hasher.update(src_key_id.to_be_bytes());
+            hasher.update_len(commitment.len());
+            for a in commitment {
+                hasher.update(a.compress().as_bytes());
+            }
+        }
+        hash_private_shares(hasher, &self.shares);
+        hash_encrypted_shares(hasher, &self.encrypted_shares);
+    }
+}
+
+/// Sent by a signer once it heard from every key in a refresh round. Its refreshed shares are
+/// held back until a [`ReshareCommit`] naming the same digest arrives.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ReshareEnd {
+    pub dkg_id: u64,
+    pub reshare_id: u64,
+    pub signer_id: u32,
+    pub status: DkgStatus,
+    /// [`reshare::refresh_digest`] of the refresh commitments the signer received
+    pub digest: [u8; 32],
+}
+
+impl Signable for ReshareEnd {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::RESHARE_END);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.reshare_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        self.status.hash(hasher);
+        hasher.update(self.digest);
+    }
+}
+
+/// Sent by the coordinator once every signer ended a refresh round successfully on the same
+/// commitments, telling them to swap in their refreshed shares
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct ReshareCommit {
+    pub dkg_id: u64,
+    pub reshare_id: u64,
+    /// [`reshare::refresh_digest`] every signer reported
+    pub digest: [u8; 32],
+}
+
+impl Signable for ReshareCommit {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::RESHARE_COMMIT);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.reshare_id.to_be_bytes());
+        hasher.update(self.digest);
+    }
+}
+
+/// Sent by the coordinator to change the signers of generation `dkg_id` without re-keying it,
+/// see [`crate::roster`]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RosterChange {
+    pub dkg_id: u64,
+    /// Change of the generation's roster, counted by the coordinator
+    pub change_id: u64,
+    /// (key_id, signer_id) of every key holding a share once the change is done. Keys not
+    /// listed are excluded from the generation.
+    pub key_owners: Vec<(u32, u32)>,
+    /// Key ids of the signers joining, whose shares are rebuilt. Empty when only removing.
+    pub repaired_key_ids: Vec<u32>,
+    /// `threshold` key ids whose shares rebuild the repaired ones
+    pub helper_key_ids: Vec<u32>,
+    /// Commitment to the group polynomial the rebuilt shares are checked against
+    pub group_commitment: Vec<Point>,
+}
+
+impl Signable for RosterChange {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::ROSTER_CHANGE);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.change_id.to_be_bytes());
+        hasher.update_len(self.key_owners.len());
+        for (key_id, signer_id) in &self.key_owners {
+            hasher.update(key_id.to_be_bytes());
+            hasher.update(signer_id.to_be_bytes());
+        }
+        hasher.update_len(self.repaired_key_ids.len());
+        for key_id in &self.repaired_key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+        hasher.update_len(self.helper_key_ids.len());
+        for key_id in &self.helper_key_ids {
+            hasher.update(key_id.to_be_bytes());
+        }
+        hasher.update_len(self.group_commitment.len());
+        for a in &self.group_commitment {
+            hasher.update(a.compress().as_bytes());
+        }
+    }
+}
+
+/// Broadcast by each helper signer in a roster change: its terms of the repaired shares, split
+/// into one piece per helper signer
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RepairShares {
+    pub dkg_id: u64,
+    pub change_id: u64,
+    pub signer_id: u32,
+    /// (dst_signer_id, [(repaired key_id, piece)]) for every helper signer, in the clear.
+    /// Empty when the pieces are sent in `encrypted_pieces`.
+    pub pieces: Vec<(u32, Vec<(u32, Scalar)>)>,
+    /// The same pieces, sealed to each helper signer, see [`crate::share_cipher`]
+    pub encrypted_pieces: Vec<EncryptedShares>,
+}
+
+impl Signable for RepairShares {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::REPAIR_SHARES);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.change_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update_len(self.pieces.len());
+        for (dst_signer_id, pieces) in &self.pieces {
+            hasher.update(dst_signer_id.to_be_bytes());
+            hash_repair_values(hasher, pieces);
+        }
+        hash_encrypted_shares(hasher, &self.encrypted_pieces);
+    }
+}
+
+/// Sent by each helper signer once it has every helper's pieces: their sum for each repaired
+/// key, which the joining signers add up to their shares
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RepairedShares {
+    pub dkg_id: u64,
+    pub change_id: u64,
+    pub signer_id: u32,
+    /// (repaired key_id, sum of the pieces received for it), in the clear. Empty when the sums
+    /// are sent in `encrypted_sums`.
+    pub sums: Vec<(u32, Scalar)>,
+    /// The same sums, sealed to the joining signer owning each repaired key, see
+    /// [`crate::share_cipher`]
+    pub encrypted_sums: Vec<EncryptedShares>,
+}
+
+impl Signable for RepairedShares {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::REPAIRED_SHARES);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.change_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hash_repair_values(hasher, &self.sums);
+        hash_encrypted_shares(hasher, &self.encrypted_sums);
+    }
+}
+
+fn hash_repair_values(hasher: &mut Hasher, values: &[(u32, Scalar)]) {
+    hasher.update_len(values.len());
+    for (key_id, value) in values {
+        hasher.update(key_id.to_be_bytes());
+        hasher.update(value.to_bytes());
+    }
+}
+
+/// Sent by a joining signer once its shares were rebuilt and checked, or failed to be
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct RosterChangeEnd {
+    pub dkg_id: u64,
+    pub change_id: u64,
+    pub signer_id: u32,
+    pub status: DkgStatus,
+}
+
+impl Signable for RosterChangeEnd {
+    fn hash(&self, hasher: &mut Hasher) {
+        hasher.update_str(tag::ROSTER_CHANGE_END);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.change_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        self.status.hash(hasher);
+    }
+}
+
 impl SigningRound {
     pub fn new(
         threshold: usize,
@@ -334,25 +1796,203 @@ impl SigningRound {
             sign_id: 1,
             sign_nonce_id: 1,
             threshold,
+            signers_threshold: 1,
             total,
             signer,
             state: States::Idle,
             commitments: BTreeMap::new(),
             shares: HashMap::new(),
             public_nonces: vec![],
+            generations: BTreeMap::new(),
+            outcome_attestations: BTreeMap::new(),
+            lagrange: LagrangeCache::default(),
+            excluded_key_ids: BTreeSet::new(),
+            key_owners: BTreeMap::new(),
+            commitment_digest: None,
+            commitment_mismatches: BTreeSet::new(),
+            bad_shares: BTreeSet::new(),
+            share_digests: BTreeMap::new(),
+            peer_share_digests: BTreeMap::new(),
+            equivocators: BTreeSet::new(),
+            blamed: BTreeSet::new(),
+            group_keys: BTreeMap::new(),
+            nonce_store: NonceStore::default(),
+            key_store: KeyStore::default(),
+            sessions: Sessions::default(),
+            share_request_attempts: 0,
+            retransmitted: BTreeSet::new(),
+            dkg_timeouts: DkgTimeouts::default(),
+            phase_started: None,
+            reshare: None,
+            repair: None,
+            share_cipher: None,
+        }
+    }
+
+    /// Fail DKG rounds whose gather phases run longer than `dkg_timeouts`
+    pub fn with_dkg_timeouts(mut self, dkg_timeouts: DkgTimeouts) -> Self {
+        self.dkg_timeouts = dkg_timeouts;
+        self
+    }
+
+    /// Wait for `signers_threshold` distinct signers' shares before ending DKG
+    pub fn with_signers_threshold(mut self, signers_threshold: usize) -> Self {
+        self.signers_threshold = signers_threshold;
+        self
+    }
+
+    /// Keep the secret nonces this round hands out in `nonce_store` until they sign
+    pub fn with_nonce_store(mut self, nonce_store: NonceStore) -> Self {
+        self.nonce_store = nonce_store;
+        self
+    }
+
+    /// Keep the key shares of each DKG round that succeeds in `key_store`
+    pub fn with_key_store(mut self, key_store: KeyStore) -> Self {
+        self.key_store = key_store;
+        self
+    }
+
+    /// Send private shares sealed to their recipients with `share_cipher`, and only accept
+    /// sealed ones
+    pub fn with_share_cipher(mut self, share_cipher: ShareCipher) -> Self {
+        self.share_cipher = Some(share_cipher);
+        self
+    }
+
+    /// The keys the last DKG round produced, `None` unless it succeeded
+    pub fn stored_keys(&self) -> Option<StoredKeys> {
+        let outcome = self.generations.get(&self.dkg_id)?;
+        let group_key = match (&outcome.status, outcome.group_key) {
+            (DkgStatus::Success, Some(group_key)) => group_key,
+            _ => return None,
+        };
+        Some(StoredKeys {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer.signer_id,
+            group_key,
+            key_owners: self.key_owners.clone(),
+            excluded_key_ids: self.excluded_key_ids.clone(),
+            frost_signer: self.signer.frost_signer.clone(),
+        })
+    }
+
+    /// Pick up the keys a DKG round produced before a restart, ready to sign with them
+    pub fn restore(&mut self, keys: StoredKeys) {
+        let mut rng = OsRng::default();
+        self.reset(keys.dkg_id, &mut rng);
+        self.state = States::Idle;
+        self.signer.frost_signer = keys.frost_signer;
+        self.key_owners = keys.key_owners;
+        self.excluded_key_ids = keys.excluded_key_ids;
+        self.generations.insert(
+            keys.dkg_id,
+            RoundOutcome {
+                status: DkgStatus::Success,
+                group_key: Some(keys.group_key),
+            },
+        );
+        info!(
+            target: SIGNING_ROUND,
+            "restored the keys of DKG round #{} for key ids {:?}",
+            self.dkg_id,
+            self.key_ids()
+        );
+    }
+
+    /// Capture the round state without any secret material
+    pub fn snapshot(&self) -> RoundSnapshot {
+        let missing = |received: &[u32]| -> Vec<u32> {
+            (0..self.total as u32)
+                .filter(|key_id| !received.contains(key_id))
+                .collect()
+        };
+        let commitments_received: Vec<u32> = self.commitments.keys().cloned().collect();
+        let mut shares_received: Vec<u32> = self.shares.keys().cloned().collect();
+        shares_received.sort();
+        RoundSnapshot {
+            signer_id: self.signer.signer_id,
+            state: self.state.clone(),
+            dkg_id: self.dkg_id,
+            dkg_public_id: self.dkg_public_id,
+            sign_id: self.sign_id,
+            sign_nonce_id: self.sign_nonce_id,
+            threshold: self.threshold,
+            signers_threshold: self.signers_threshold,
+            total: self.total,
+            key_ids: self.key_ids().iter().map(|id| *id as u32).collect(),
+            commitments_missing: missing(&commitments_received),
+            commitments_received,
+            shares_missing: missing(&shares_received),
+            shares_received,
+            signers_received: self.signers_with_shares().into_iter().collect(),
+            generations: self.generations.keys().cloned().collect(),
+            pending_outcome_queries: self.outcome_attestations.keys().cloned().collect(),
+            group_key: self.group_key_view(),
+            signing_sessions: self.sessions.ids(),
         }
     }
 
+    /// This signer's view of the group key its last DKG round produced, `None` unless that
+    /// round succeeded
+    pub fn group_key_view(&self) -> Option<GroupKeyView> {
+        let outcome = self.generations.get(&self.dkg_id)?;
+        let group_key = match (&outcome.status, &outcome.group_key) {
+            (DkgStatus::Success, Some(group_key)) => group_key,
+            _ => return None,
+        };
+        let key_ids: Vec<u32> = self.key_ids().iter().map(|id| *id as u32).collect();
+        // every key's shares are in once DKG succeeded, and restored keys come without them
+        let roster: BTreeMap<u32, u32> = (0..self.total as u32)
+            .filter(|key_id| !self.excluded_key_ids.contains(key_id))
+            .filter_map(|key_id| {
+                let owner = if key_ids.contains(&key_id) {
+                    Some(self.signer.signer_id)
+                } else {
+                    self.key_owners.get(&key_id).copied()
+                };
+                owner.map(|owner| (key_id, owner))
+            })
+            .collect();
+        Some(GroupKeyView {
+            signer_id: self.signer.signer_id,
+            dkg_id: self.dkg_id,
+            group_key: hex::encode(group_key.compress().as_bytes()),
+            key_ids,
+            roster_hash: hex::encode(roster_hash(&roster)),
+            roster,
+        })
+    }
+
     fn reset<T: RngCore + CryptoRng>(&mut self, dkg_id: u64, rng: &mut T) {
         self.dkg_id = dkg_id;
         self.dkg_public_id = 1;
         self.commitments.clear();
         self.shares.clear();
         self.public_nonces.clear();
+        self.sessions.clear();
+        self.lagrange.clear();
+        self.excluded_key_ids.clear();
+        self.key_owners.clear();
+        self.commitment_digest = None;
+        self.commitment_mismatches.clear();
+        self.bad_shares.clear();
+        self.share_digests.clear();
+        self.peer_share_digests.clear();
+        self.equivocators.clear();
+        self.blamed.clear();
+        self.group_keys.clear();
+        self.share_request_attempts = 0;
+        self.retransmitted.clear();
+        self.reshare = None;
+        self.repair = None;
         self.signer.frost_signer.reset_polys(rng);
     }
 
     pub fn process(&mut self, message: MessageTypes) -> Result<Vec<MessageTypes>, Error> {
+        let request_hash = message.request_hash();
+        let asked_to_answer = self.asked_to_answer(&message);
+        let name = message.name();
         let out_msgs = match message {
             MessageTypes::DkgBegin(dkg_begin) => self.dkg_begin(dkg_begin),
             MessageTypes::DkgPrivateBegin(_) => self.dkg_private_begin(),
@@ -362,10 +2002,25 @@ impl SigningRound {
             MessageTypes::DkgPrivateShares(dkg_private_shares) => {
                 self.dkg_private_shares(dkg_private_shares)
             }
+            MessageTypes::DkgShareDigests(digests) => self.dkg_share_digests(digests),
+            MessageTypes::DkgGroupKey(group_key) => self.dkg_group_key(group_key),
             MessageTypes::SignShareRequest(sign_share_request) => {
                 self.sign_share_request(sign_share_request)
             }
             MessageTypes::NonceRequest(nonce_request) => self.nonce_request(nonce_request),
+            MessageTypes::BatchNonceRequest(request) => self.batch_nonce_request(request),
+            MessageTypes::BatchSignRequest(request) => self.batch_sign_request(request),
+            MessageTypes::RoundOutcomeQuery(query) => self.round_outcome_query(query),
+            MessageTypes::RoundOutcomeResponse(response) => self.round_outcome_response(response),
+            MessageTypes::RoundAbort(abort) => self.round_abort(abort),
+            MessageTypes::UpgradeWindow(window) => self.upgrade_window(window),
+            MessageTypes::MissingShareRequest(request) => Ok(self.missing_share_request(request)),
+            MessageTypes::ReshareBegin(begin) => self.reshare_begin(begin),
+            MessageTypes::ReshareShares(shares) => self.reshare_shares(shares),
+            MessageTypes::ReshareCommit(commit) => self.reshare_commit(commit),
+            MessageTypes::RosterChange(change) => self.roster_change(change),
+            MessageTypes::RepairShares(shares) => self.repair_shares(shares),
+            MessageTypes::RepairedShares(shares) => self.repaired_shares(shares),
             _ => Ok(vec![]), // TODO
         };
 
@@ -373,6 +2028,7 @@ impl SigningRound {
             Ok(mut out) => {
                 if self.public_shares_done() {
                     debug!(
+                        target: SIGNING_ROUND,
                         "public_shares_done==true. commitments {}",
                         self.commitments.len()
                     );
@@ -381,113 +2037,399 @@ impl SigningRound {
                     self.move_to(States::DkgPrivateDistribute)?;
                 } else if self.can_dkg_end() {
                     debug!(
+                        target: SIGNING_ROUND,
                         "can_dkg_end==true. shares {} commitments {}",
                         self.shares.len(),
                         self.commitments.len()
                     );
-                    let dkg_end_msgs = self.dkg_ended()?;
-                    out.push(dkg_end_msgs);
+                    out.push(self.share_digests_message());
+                    out.extend(self.dkg_ended()?);
+                    // only a signer which computed its group key waits for the others
+                    if matches!(self.group_keys.get(&self.signer.signer_id), Some(Some(_))) {
+                        self.move_to(States::DkgConfirm)?;
+                    } else {
+                        self.move_to(States::Idle)?;
+                    }
+                }
+                if self.group_keys_confirmed() {
+                    out.extend(self.dkg_confirmed());
                     self.move_to(States::Idle)?;
                 }
                 Ok(out)
             }
-            Err(e) => Err(e),
+            Err(e) if asked_to_answer => Ok(vec![self.reject(request_hash.to_vec(), &e)]),
+            Err(e) => {
+                warn!(
+                    target: SIGNING_ROUND,
+                    "signer #{} dropping {}: {}", self.signer.signer_id, name, e
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Whether `message` is a coordinator request this signer was asked to answer. Only these
+    /// get a [`Rejection`] when they fail, a bad message from a peer is dropped.
+    fn asked_to_answer(&self, message: &MessageTypes) -> bool {
+        match message {
+            MessageTypes::SignShareRequest(request) => request.signer_id == self.signer.signer_id,
+            MessageTypes::BatchSignRequest(request) => request.signer_id == self.signer.signer_id,
+            MessageTypes::DkgBegin(_)
+            | MessageTypes::DkgPrivateBegin(_)
+            | MessageTypes::DkgQuery(_)
+            | MessageTypes::NonceRequest(_)
+            | MessageTypes::BatchNonceRequest(_)
+            | MessageTypes::RoundAbort(_)
+            | MessageTypes::UpgradeWindow(_)
+            | MessageTypes::ReshareBegin(_)
+            | MessageTypes::ReshareCommit(_)
+            | MessageTypes::RosterChange(_) => true,
+            _ => false,
         }
     }
 
+    fn reject(&self, request_hash: Vec<u8>, error: &Error) -> MessageTypes {
+        warn!(
+            target: SIGNING_ROUND,
+            "signer #{} rejecting request: {}",
+            self.signer.signer_id, error
+        );
+        MessageTypes::Rejection(Rejection {
+            signer_id: self.signer.signer_id,
+            request_hash,
+            reason_code: RejectionCode::from(error),
+            detail: error.to_string(),
+        })
+    }
+
+    /// Stop accepting commitments for the current DKG round, returning the digest binding the
+    /// ones we have
+    fn close_commitment_phase(&mut self) -> [u8; 32] {
+        *self
+            .commitment_digest
+            .get_or_insert_with(|| commitment_digest(self.dkg_id, self.commitments.iter()))
+    }
+
     fn dkg_public_ended(&mut self) -> Result<MessageTypes, Error> {
+        self.close_commitment_phase();
         let dkg_end = DkgEnd {
             dkg_id: self.dkg_id,
-            signer_id: self.signer.signer_id as usize,
+            signer_id: self.signer.signer_id,
             status: DkgStatus::Success,
         };
         let dkg_end = MessageTypes::DkgPublicEnd(dkg_end);
         info!(
+            target: SIGNING_ROUND,
             "DKG_END round #{} signer_id {}",
             self.dkg_id, self.signer.signer_id
         );
         Ok(dkg_end)
     }
 
-    fn dkg_ended(&mut self) -> Result<MessageTypes, Error> {
+    /// Compute our secret from the shares of the current DKG round, returning the DkgGroupKey
+    /// telling our peers the group key we got, or the DkgEnd reporting why we could not
+    fn dkg_ended(&mut self) -> Result<Vec<MessageTypes>, Error> {
+        if !self.commitment_mismatches.is_empty() {
+            let failure = DkgFailure::CommitmentMismatch(
+                self.commitment_mismatches.iter().cloned().collect(),
+            );
+            warn!(
+                target: SIGNING_ROUND,
+                "DKG round #{} failed: {}", self.dkg_id, failure
+            );
+            return Ok(self.dkg_failed(failure));
+        }
+        if !self.bad_shares.is_empty() {
+            let failure = DkgFailure::BadShares(self.bad_shares.iter().cloned().collect());
+            warn!(
+                target: SIGNING_ROUND,
+                "DKG round #{} failed: {}", self.dkg_id, failure
+            );
+            return Ok(self.dkg_failed(failure));
+        }
         for party in &mut self.signer.frost_signer.parties {
             let commitments: Vec<PolyCommitment> = self.commitments.clone().into_values().collect();
             let mut shares: HashMap<usize, Scalar> = HashMap::new();
             for (key_id, key_shares) in &self.shares {
                 info!(
+                    target: SIGNING_ROUND,
                     "building shares with k: {} v: key_shares[{}] len {} keys: {:?}",
                     key_id,
                     party.id,
                     key_shares.len(),
                     key_shares.keys()
                 );
-                shares.insert(*key_id as usize, key_shares[&party.id]);
+                // a missing share is reported by compute_secret as DkgError::MissingShares
+                if let Some(share) = key_shares.get(&party.id) {
+                    shares.insert(*key_id as usize, *share);
+                }
             }
             info!(
+                target: SIGNING_ROUND,
                 "party{}.compute_secret shares_for_id:{:?}",
                 party.id,
                 shares.keys()
             );
             if let Err(secret_error) = party.compute_secret(shares, &commitments) {
-                let dkg_end = DkgEnd {
-                    dkg_id: self.dkg_id,
-                    signer_id: self.signer.signer_id as usize,
-                    status: DkgStatus::Failure(secret_error.to_string()),
-                };
-                return Ok(MessageTypes::DkgEnd(dkg_end));
+                warn!(
+                    target: SIGNING_ROUND,
+                    "DKG round #{} party #{} failed: {}",
+                    self.dkg_id, party.id, secret_error
+                );
+                let failure = self.dkg_failure(secret_error);
+                return Ok(self.dkg_failed(failure));
             }
-            info!("Party #{} group key {}", party.id, party.group_key);
+            info!(target: SIGNING_ROUND, "Party #{} group key {}", party.id, party.group_key);
         }
-        let dkg_end = DkgEnd {
+        let group_key = self
+            .signer
+            .frost_signer
+            .parties
+            .first()
+            .map(|party| party.group_key);
+        self.group_keys.insert(self.signer.signer_id, group_key);
+        Ok(vec![self.group_key_message()])
+    }
+
+    /// Our group key as we last computed it in the current DKG round, for our peers to
+    /// compare with theirs
+    fn group_key_message(&self) -> MessageTypes {
+        MessageTypes::DkgGroupKey(DkgGroupKey {
             dkg_id: self.dkg_id,
-            signer_id: self.signer.signer_id as usize,
-            status: DkgStatus::Success,
-        };
-        let dkg_end = MessageTypes::DkgEnd(dkg_end);
-        info!(
-            "DKG_END round #{} signer_id {}",
-            self.dkg_id, self.signer.signer_id
-        );
-        Ok(dkg_end)
+            signer_id: self.signer.signer_id,
+            group_key: self
+                .group_keys
+                .get(&self.signer.signer_id)
+                .copied()
+                .flatten(),
+        })
     }
 
-    fn public_shares_done(&self) -> bool {
-        debug!(
-            "public_shares_done state {:?} commitments {}",
-            self.state,
-            self.commitments.len(),
-        );
-        self.state == States::DkgPublicGather && self.commitments.len() == self.total
+    fn dkg_group_key(&mut self, group_key: DkgGroupKey) -> Result<Vec<MessageTypes>, Error> {
+        if group_key.dkg_id != self.dkg_id || group_key.signer_id == self.signer.signer_id {
+            return Ok(vec![]);
+        }
+        self.group_keys
+            .insert(group_key.signer_id, group_key.group_key);
+        Ok(vec![])
     }
 
+    /// Whether we wait on no signer of the current DKG round for its group key. The signers
+    /// which sent us shares are the ones computing one.
+    fn group_keys_confirmed(&self) -> bool {
+        self.state == States::DkgConfirm
+            && self
+                .signers_with_shares()
+                .iter()
+                .all(|signer_id| self.group_keys.contains_key(signer_id))
+    }
+
+    /// End the current DKG round once every signer sent its group key: a success if none
+    /// differs from ours, ignoring the signers which failed and report so themselves
+    fn dkg_confirmed(&mut self) -> Vec<MessageTypes> {
+        let own = self
+            .group_keys
+            .get(&self.signer.signer_id)
+            .copied()
+            .flatten();
+        let mismatched: Vec<u32> = self
+            .group_keys
+            .iter()
+            .filter(|(_, group_key)| group_key.is_some() && **group_key != own)
+            .map(|(signer_id, _)| *signer_id)
+            .collect();
+        if !mismatched.is_empty() {
+            let failure = DkgFailure::GroupKeyMismatch(self.key_ids_of(&mismatched));
+            warn!(
+                target: SIGNING_ROUND,
+                "DKG round #{} failed: signers {:?} computed {}",
+                self.dkg_id,
+                mismatched,
+                failure
+            );
+            return self.dkg_failed(failure);
+        }
+        self.generations.insert(
+            self.dkg_id,
+            RoundOutcome {
+                status: DkgStatus::Success,
+                group_key: own,
+            },
+        );
+        if let Some(keys) = self.stored_keys() {
+            // the keys still sign until we restart, so this is no reason to fail the round
+            if let Err(e) = self.key_store.put(&keys) {
+                warn!(
+                    target: SIGNING_ROUND,
+                    "failed to store the keys of DKG round #{}: {}", self.dkg_id, e
+                );
+            }
+        }
+        let dkg_end = DkgEnd {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer.signer_id,
+            status: DkgStatus::Success,
+        };
+        info!(
+            target: SIGNING_ROUND,
+            "DKG_END round #{} signer_id {}",
+            self.dkg_id, self.signer.signer_id
+        );
+        vec![MessageTypes::DkgEnd(dkg_end)]
+    }
+
+    /// Key ids owned by the signers `signer_ids` in the current DKG round
+    fn key_ids_of(&self, signer_ids: &[u32]) -> Vec<u32> {
+        let own_key_ids = self.key_ids();
+        (0..self.total as u32)
+            .filter(|key_id| {
+                let owner = if own_key_ids.contains(&(*key_id as usize)) {
+                    Some(self.signer.signer_id)
+                } else {
+                    self.key_owners.get(key_id).copied()
+                };
+                owner.is_some_and(|owner| signer_ids.contains(&owner))
+            })
+            .collect()
+    }
+
+    /// Record the current DKG round as failed, returning the DkgEnd reporting it, preceded by
+    /// a DkgGroupKey without a key if we had not sent ours, so peers stop waiting for it
+    fn dkg_failed(&mut self, failure: DkgFailure) -> Vec<MessageTypes> {
+        let mut out = vec![];
+        if !self.group_keys.contains_key(&self.signer.signer_id) {
+            self.group_keys.insert(self.signer.signer_id, None);
+            out.push(self.group_key_message());
+        }
+        let dkg_end = DkgEnd {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer.signer_id,
+            status: DkgStatus::Failure(failure),
+        };
+        self.generations.insert(
+            self.dkg_id,
+            RoundOutcome {
+                status: dkg_end.status.clone(),
+                group_key: None,
+            },
+        );
+        out.push(MessageTypes::DkgEnd(dkg_end));
+        out
+    }
+
+    /// Name the senders at fault for a failed `compute_secret`
+    fn dkg_failure(&self, error: DkgError) -> DkgFailure {
+        let ids = |ids: Vec<usize>| ids.into_iter().map(|id| id as u32).collect();
+        match error {
+            DkgError::MissingShares(key_ids) => DkgFailure::MissingShares(ids(key_ids)),
+            // commitments are passed in key id order, so these are indices into it
+            DkgError::BadIds(indices) => DkgFailure::BadCommitments(
+                indices
+                    .into_iter()
+                    .filter_map(|i| self.commitments.keys().nth(i).copied())
+                    .collect(),
+            ),
+            DkgError::BadShares(key_ids) => DkgFailure::BadShares(ids(key_ids)),
+        }
+    }
+
+    fn public_shares_done(&self) -> bool {
+        debug!(
+            target: SIGNING_ROUND,
+            "public_shares_done state {:?} commitments {}",
+            self.state,
+            self.commitments.len(),
+        );
+        self.state == States::DkgPublicGather && self.commitments.len() == self.total
+    }
+
+    /// DKG ends once every key's commitment and shares are in, since computing our secret
+    /// needs all of them, and they came from at least `signers_threshold` signers. Excluded
+    /// keys count as in, with null commitments and shares, but not their signers.
     fn can_dkg_end(&self) -> bool {
+        let signers = self.signers_with_shares().len();
         debug!(
-            "can_dkg_end state {:?} commitments {} shares {}",
+            target: SIGNING_ROUND,
+            "can_dkg_end state {:?} commitments {} shares {} signers {}",
             self.state,
             self.commitments.len(),
-            self.shares.len()
+            self.shares.len(),
+            signers
         );
         self.state == States::DkgPrivateGather
             && self.commitments.len() == self.total
             && self.shares.len() == self.total
+            && signers >= self.signers_threshold
+    }
+
+    /// Signers whose private shares for this round arrived, ourselves included
+    fn signers_with_shares(&self) -> BTreeSet<u32> {
+        let own_key_ids = self.key_ids();
+        self.shares
+            .keys()
+            .filter(|key_id| !self.excluded_key_ids.contains(key_id))
+            .filter_map(|key_id| {
+                if own_key_ids.contains(&(*key_id as usize)) {
+                    Some(self.signer.signer_id)
+                } else {
+                    self.key_owners.get(key_id).copied()
+                }
+            })
+            .collect()
     }
 
     fn nonce_request(&mut self, nonce_request: NonceRequest) -> Result<Vec<MessageTypes>, Error> {
         let mut rng = OsRng::default();
-        let mut msgs = vec![];
+        self.sign_id = nonce_request.sign_id;
+        self.sign_nonce_id = nonce_request.sign_nonce_id;
+        let session = SessionId {
+            dkg_id: nonce_request.dkg_id,
+            sign_id: nonce_request.sign_id,
+            correlation_id: nonce_request.correlation_id,
+        };
+        // a new sign_nonce_id retries the nonce phase, the nonces sent before are never used
+        self.sessions.open(session);
+        if let Err(e) = self.nonce_store.discard(&session) {
+            warn!(target: SIGNING_ROUND, "failed to drop the stored nonces of {}: {}", session, e);
+        }
+        let mut msgs: Vec<MessageTypes> = self
+            .round_outcome_catch_up(nonce_request.dkg_id)
+            .into_iter()
+            .collect();
         for party in &mut self.signer.frost_signer.parties {
             let response = NonceResponse {
                 dkg_id: nonce_request.dkg_id,
                 sign_id: nonce_request.sign_id,
+                correlation_id: nonce_request.correlation_id,
                 sign_nonce_id: nonce_request.sign_nonce_id,
-                party_id: party.id as u32,
+                signer_id: self.signer.signer_id,
+                key_id: party.id as u32,
                 nonce: party.gen_nonce(&mut rng),
             };
+            let state = Secret::new(party.save());
+            if let Some(nonce) = &state.nonce {
+                let id = NonceId {
+                    dkg_id: response.dkg_id,
+                    sign_id: response.sign_id,
+                    correlation_id: response.correlation_id,
+                    sign_nonce_id: response.sign_nonce_id,
+                    key_id: response.key_id,
+                    index: 0,
+                };
+                // the round can still complete unless we restart before it does
+                if let Err(e) = self.nonce_store.put(id, &response.nonce, nonce) {
+                    warn!(target: SIGNING_ROUND, "failed to store the nonce of key_id {}: {}", party.id, e);
+                }
+                let nonce = Secret::new(nonce.clone());
+                self.sessions
+                    .hold(session, response.key_id, response.nonce.clone(), nonce);
+            }
 
             let response = MessageTypes::NonceResponse(response);
 
             info!(
-                "nonce request with dkg_id {:?}. response sent from party_id {}",
+                target: SIGNING_ROUND,
+                "nonce request with dkg_id {:?}. response sent from key_id {}",
                 nonce_request.dkg_id, party.id
             );
             msgs.push(response);
@@ -499,17 +2441,36 @@ impl SigningRound {
         &mut self,
         sign_request: SignatureShareRequest,
     ) -> Result<Vec<MessageTypes>, Error> {
-        let mut msgs = vec![];
-        let party_id: usize = sign_request
-            .party_id
+        let key_id: usize = sign_request
+            .key_id
             .try_into()
-            .map_err(|_| Error::InvalidPartyID)?;
+            .map_err(|_| Error::InvalidKeyId)?;
+        let addressed_to_us = sign_request.signer_id == self.signer.signer_id;
+        if addressed_to_us && !self.key_ids().contains(&key_id) {
+            return Err(Error::KeyOwnerMismatch {
+                signer_id: sign_request.signer_id,
+                key_id: sign_request.key_id,
+            });
+        }
+        let mut msgs: Vec<MessageTypes> = self
+            .round_outcome_catch_up(sign_request.dkg_id)
+            .into_iter()
+            .collect();
+        if !addressed_to_us {
+            debug!(
+                target: SIGNING_ROUND,
+                "SignShareRequest for signer #{} dropped.",
+                sign_request.signer_id
+            );
+            return Ok(msgs);
+        }
+        let digest = MessageDigest::try_from(sign_request.message.as_slice())?;
         if let Some(party) = self
             .signer
             .frost_signer
             .parties
-            .iter()
-            .find(|p| p.id == party_id)
+            .iter_mut()
+            .find(|p| p.id == key_id)
         {
             //let party_nonces = &self.public_nonces;
             let signer_ids: Vec<usize> = sign_request
@@ -519,13 +2480,66 @@ impl SigningRound {
                 .collect();
             let signer_nonces: Vec<PublicNonce> =
                 sign_request.nonces.iter().map(|(_, n)| n.clone()).collect();
-            let share = party.sign(&sign_request.message, &signer_ids, &signer_nonces);
+            let lambda = self.lagrange.coefficient(key_id, &signer_ids);
+            let session = SessionId {
+                dkg_id: sign_request.dkg_id,
+                sign_id: sign_request.sign_id,
+                correlation_id: sign_request.correlation_id,
+            };
+            let unknown = || Error::UnknownNonce {
+                session,
+                key_id: sign_request.key_id,
+            };
+            let public_nonce = sign_request
+                .nonces
+                .iter()
+                .find(|(id, _)| *id == sign_request.key_id)
+                .map(|(_, public_nonce)| public_nonce)
+                .ok_or_else(unknown)?;
+            // the nonce we handed out in this session, never one our party generated for
+            // another, which would sign two messages with one nonce. It is spent from both
+            // places at once, and only taken from the store if we restarted since.
+            let nonce = self
+                .nonce_store
+                .spend(
+                    &mut self.sessions,
+                    &session,
+                    sign_request.key_id,
+                    public_nonce,
+                )
+                .unwrap_or_else(|e| {
+                    warn!(target: SIGNING_ROUND, "failed to spend the nonce of key_id {}: {}", key_id, e);
+                    None
+                })
+                .ok_or_else(unknown)?;
+            let share = match &sign_request.key_path {
+                Some(key_path) => lagrange::sign_key_path(
+                    party,
+                    &nonce,
+                    &lambda,
+                    digest.as_bytes(),
+                    &signer_ids,
+                    &signer_nonces,
+                    &key_path.tweak(&party.group_key),
+                ),
+                None => lagrange::sign_with_nonce(
+                    party,
+                    &nonce,
+                    &lambda,
+                    digest.as_bytes(),
+                    &signer_ids,
+                    &signer_nonces,
+                ),
+            };
+            // overwrite the spent secret nonce so it neither lingers nor gets reused
+            party.gen_nonce(&mut OsRng::default());
 
             let response = SignatureShareResponse {
                 dkg_id: sign_request.dkg_id,
                 sign_id: sign_request.sign_id,
                 correlation_id: sign_request.correlation_id,
-                party_id: sign_request.party_id,
+                signer_id: self.signer.signer_id,
+                key_id: sign_request.key_id,
                 signature_share: share,
             };
 
@@ -533,15 +2547,267 @@ impl SigningRound {
 
             msgs.push(response);
         } else {
-            debug!("SignShareRequest for {} dropped.", sign_request.party_id);
+            debug!(target: SIGNING_ROUND, "SignShareRequest for {} dropped.", sign_request.key_id);
+        }
+        Ok(msgs)
+    }
+
+    fn batch_nonce_request(
+        &mut self,
+        request: BatchNonceRequest,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        let count = request.count as usize;
+        if count > MAX_BATCH_SIZE {
+            return Err(Error::InvalidBatch(format!(
+                "{count} nonces asked for, at most {MAX_BATCH_SIZE} are handed out"
+            )));
+        }
+        let mut rng = OsRng::default();
+        self.sign_id = request.sign_id;
+        self.sign_nonce_id = request.sign_nonce_id;
+        let session = SessionId {
+            dkg_id: request.dkg_id,
+            sign_id: request.sign_id,
+            correlation_id: 0,
+        };
+        self.sessions.open(session);
+        if let Err(e) = self.nonce_store.discard(&session) {
+            warn!(target: SIGNING_ROUND, "failed to drop the stored nonces of {}: {}", session, e);
+        }
+        let mut msgs: Vec<MessageTypes> = self
+            .round_outcome_catch_up(request.dkg_id)
+            .into_iter()
+            .collect();
+        for party in &mut self.signer.frost_signer.parties {
+            let key_id = party.id as u32;
+            let mut nonces = Vec::with_capacity(count);
+            for index in 0..count {
+                let public_nonce = party.gen_nonce(&mut rng);
+                let Some(nonce) = Secret::new(party.save()).nonce.clone() else {
+                    continue;
+                };
+                let id = NonceId {
+                    dkg_id: request.dkg_id,
+                    sign_id: request.sign_id,
+                    correlation_id: session.correlation_id,
+                    sign_nonce_id: request.sign_nonce_id,
+                    key_id,
+                    index: index as u32,
+                };
+                if let Err(e) = self.nonce_store.put(id, &public_nonce, &nonce) {
+                    warn!(target: SIGNING_ROUND, "failed to store a nonce of key_id {}: {}", key_id, e);
+                }
+                nonces.push((public_nonce, Secret::new(nonce)));
+            }
+            // the batch's secret nonces live in its session only, never in the party
+            party.gen_nonce(&mut rng);
+
+            info!(
+                target: SIGNING_ROUND,
+                "batch nonce request with dkg_id {:?}. {} nonces sent from key_id {}",
+                request.dkg_id, count, key_id
+            );
+            msgs.push(MessageTypes::BatchNonceResponse(BatchNonceResponse {
+                dkg_id: request.dkg_id,
+                sign_id: request.sign_id,
+                sign_nonce_id: request.sign_nonce_id,
+                signer_id: self.signer.signer_id,
+                key_id,
+                nonces: nonces.iter().map(|(public, _)| public.clone()).collect(),
+            }));
+            for (public_nonce, nonce) in nonces {
+                self.sessions.hold(session, key_id, public_nonce, nonce);
+            }
+        }
+        Ok(msgs)
+    }
+
+    fn batch_sign_request(
+        &mut self,
+        request: BatchSignRequest,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        let key_id: usize = request.key_id.try_into().map_err(|_| Error::InvalidKeyId)?;
+        let addressed_to_us = request.signer_id == self.signer.signer_id;
+        if addressed_to_us && !self.key_ids().contains(&key_id) {
+            return Err(Error::KeyOwnerMismatch {
+                signer_id: request.signer_id,
+                key_id: request.key_id,
+            });
+        }
+        if request.messages.len() > MAX_BATCH_SIZE {
+            return Err(Error::InvalidBatch(format!(
+                "{} messages, at most {MAX_BATCH_SIZE} are signed at once",
+                request.messages.len()
+            )));
         }
+        let mut msgs: Vec<MessageTypes> = self
+            .round_outcome_catch_up(request.dkg_id)
+            .into_iter()
+            .collect();
+        if !addressed_to_us {
+            debug!(
+                target: SIGNING_ROUND,
+                "BatchSignRequest for signer #{} dropped.",
+                request.signer_id
+            );
+            return Ok(msgs);
+        }
+        let digests = request
+            .messages
+            .iter()
+            .map(|message| MessageDigest::try_from(message.message.as_slice()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(party) = self
+            .signer
+            .frost_signer
+            .parties
+            .iter()
+            .find(|p| p.id == key_id)
+        else {
+            debug!(target: SIGNING_ROUND, "BatchSignRequest for {} dropped.", request.key_id);
+            return Ok(msgs);
+        };
+        let tweak = request
+            .key_path
+            .map(|key_path| key_path.tweak(&party.group_key));
+        let mut signature_shares = Vec::with_capacity(digests.len());
+        for (message, digest) in request.messages.iter().zip(&digests) {
+            let public_nonce = message
+                .nonces
+                .iter()
+                .find(|(id, _)| *id == request.key_id)
+                .map(|(_, public_nonce)| public_nonce)
+                .ok_or_else(|| Error::InvalidBatch(format!("no nonce of key {key_id}")))?;
+            // spent from both places at once, so neither a restart nor a replay signs with it
+            // again
+            let session = SessionId {
+                dkg_id: request.dkg_id,
+                sign_id: request.sign_id,
+                correlation_id: 0,
+            };
+            let nonce = self
+                .nonce_store
+                .spend(&mut self.sessions, &session, request.key_id, public_nonce)
+                .unwrap_or_else(|e| {
+                    warn!(target: SIGNING_ROUND, "failed to spend a nonce of key_id {}: {}", key_id, e);
+                    None
+                })
+                .ok_or_else(|| {
+                Error::InvalidBatch(format!(
+                    "nonce of key {key_id} was not handed out or is spent"
+                ))
+            })?;
+
+            let signer_ids: Vec<usize> =
+                message.nonces.iter().map(|(id, _)| *id as usize).collect();
+            let signer_nonces: Vec<PublicNonce> =
+                message.nonces.iter().map(|(_, n)| n.clone()).collect();
+            let lambda = self.lagrange.coefficient(key_id, &signer_ids);
+            signature_shares.push(match &tweak {
+                Some(tweak) => lagrange::sign_key_path(
+                    party,
+                    &nonce,
+                    &lambda,
+                    digest.as_bytes(),
+                    &signer_ids,
+                    &signer_nonces,
+                    tweak,
+                ),
+                None => lagrange::sign_with_nonce(
+                    party,
+                    &nonce,
+                    &lambda,
+                    digest.as_bytes(),
+                    &signer_ids,
+                    &signer_nonces,
+                ),
+            });
+        }
+
+        msgs.push(MessageTypes::BatchSignResponse(BatchSignResponse {
+            dkg_id: request.dkg_id,
+            sign_id: request.sign_id,
+            signer_id: self.signer.signer_id,
+            key_id: request.key_id,
+            signature_shares,
+        }));
         Ok(msgs)
     }
 
+    fn round_abort(&mut self, abort: RoundAbort) -> Result<Vec<MessageTypes>, Error> {
+        warn!(
+            target: SIGNING_ROUND,
+            "coordinator aborted dkg round #{} sign round #{}: {}",
+            abort.dkg_id, abort.sign_id, abort.reason
+        );
+        // a DKG which already ended here keeps its shares, only an unfinished one is dropped
+        if abort.dkg_id == self.dkg_id && self.state != States::Idle {
+            self.commitments.clear();
+            self.shares.clear();
+            self.move_to(States::Idle)?;
+        }
+        if abort.dkg_id == self.dkg_id && self.reshare.take().is_some() {
+            info!(
+                target: SIGNING_ROUND,
+                "dropped the unconfirmed refresh of DKG round #{}", self.dkg_id
+            );
+        }
+        if abort.dkg_id == self.dkg_id && self.repair.take().is_some() {
+            info!(
+                target: SIGNING_ROUND,
+                "dropped the unfinished key repair of DKG round #{}", self.dkg_id
+            );
+        }
+        if abort.sign_id == self.sign_id {
+            self.public_nonces.clear();
+        }
+        self.sessions.close(abort.dkg_id, abort.sign_id);
+        Ok(vec![])
+    }
+
+    fn upgrade_window(&mut self, window: UpgradeWindow) -> Result<Vec<MessageTypes>, Error> {
+        info!(
+            target: SIGNING_ROUND,
+            "coordinator announced upgrade window #{} ({}): no rounds start {}s from now for up to {}s, restart once it opens",
+            window.window_id, window.reason, window.starts_in_secs, window.duration_secs
+        );
+        Ok(vec![MessageTypes::UpgradeAck(UpgradeAck {
+            signer_id: self.signer.signer_id,
+            window_id: window.window_id,
+        })])
+    }
+
+    /// The message announcing this signer to the coordinator when it starts
+    pub fn registration(&self) -> MessageTypes {
+        MessageTypes::Registration(Registration {
+            signer_id: self.signer.signer_id,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        })
+    }
+
     fn dkg_begin(&mut self, dkg_begin: DkgBegin) -> Result<Vec<MessageTypes>, Error> {
         let mut rng = OsRng::default();
 
         self.reset(dkg_begin.dkg_id, &mut rng);
+        self.excluded_key_ids = dkg_begin.excluded_key_ids.into_iter().collect();
+        if self.is_excluded() {
+            warn!(
+                target: SIGNING_ROUND,
+                "signer #{} was excluded from dkg round #{}",
+                self.signer.signer_id, self.dkg_id
+            );
+            if self.state != States::Idle {
+                self.move_to(States::Idle)?;
+            }
+            return Ok(vec![]);
+        }
+        let key_ids = self.key_ids();
+        for key_id in self.excluded_key_ids.clone() {
+            self.commitments
+                .insert(key_id, null_commitment(key_id, self.threshold, &mut rng));
+            let zeros = key_ids.iter().map(|id| (*id, Scalar::from(0))).collect();
+            self.shares.insert(key_id, Secret::new(zeros));
+        }
         self.move_to(States::DkgPublicDistribute)?;
 
         let _party_state = self.signer.frost_signer.save();
@@ -554,6 +2820,7 @@ impl SigningRound {
         let mut msgs = vec![];
         for party in &self.signer.frost_signer.parties {
             info!(
+                target: SIGNING_ROUND,
                 "sending dkg round #{} public commitment for party #{}",
                 self.dkg_id, party.id
             );
@@ -561,45 +2828,282 @@ impl SigningRound {
             let public_share = DkgPublicShare {
                 dkg_id: self.dkg_id,
                 dkg_public_id: self.dkg_public_id,
-                party_id: party.id as u32,
+                signer_id: self.signer.signer_id,
+                key_id: party.id as u32,
                 public_share: party.get_poly_commitment(&mut rng),
             };
-
-            let public_share = MessageTypes::DkgPublicShare(public_share);
             msgs.push(public_share);
         }
+        // record our own commitments here rather than when the transport delivers them back
+        for public_share in &msgs {
+            self.dkg_public_share(public_share.clone())?;
+        }
+        let msgs = msgs.into_iter().map(MessageTypes::DkgPublicShare).collect();
 
         self.move_to(States::DkgPublicGather)?;
         Ok(msgs)
     }
 
     fn dkg_private_begin(&mut self) -> Result<Vec<MessageTypes>, Error> {
-        let mut msgs = vec![];
-        for party in &self.signer.frost_signer.parties {
-            info!("sending dkg private share for party #{}", party.id);
-            let private_shares = DkgPrivateShares {
-                dkg_id: self.dkg_id,
-                key_id: party.id as u32,
-                private_shares: party.get_shares(),
-            };
-
-            let private_shares = MessageTypes::DkgPrivateShares(private_shares);
-            msgs.push(private_shares);
+        if self.is_excluded() {
+            return Ok(vec![]);
         }
+        let commitment_digest = self.close_commitment_phase();
+        let private_shares = self.private_shares_message(commitment_digest);
+
+        // keep the shares for our own keys, as for the commitments in `dkg_public_begin`
+        self.dkg_private_shares(private_shares.clone())?;
+        let msgs = vec![MessageTypes::DkgPrivateShares(private_shares)];
 
         self.move_to(States::DkgPrivateGather)?;
         Ok(msgs)
     }
 
+    /// Our private shares for every key not excluded, built on the commitments of
+    /// `commitment_digest`. Each party's polynomial is fixed for the round, so building the
+    /// message again gives the same shares.
+    fn private_shares_message(&self, commitment_digest: [u8; 32]) -> DkgPrivateShares {
+        let mut private_shares = DkgPrivateShares {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer.signer_id,
+            private_shares: Vec::new(),
+            encrypted_shares: Vec::new(),
+            commitment_digest,
+        };
+        private_shares.private_shares =
+            party_shares(&self.signer.frost_signer.parties, &self.excluded_key_ids);
+        for (key_id, _) in &private_shares.private_shares {
+            info!(target: SIGNING_ROUND, "sending dkg private share for party #{}", key_id);
+        }
+        if let Some(cipher) = &self.share_cipher {
+            let mut plain = std::mem::take(&mut private_shares.private_shares);
+            private_shares.encrypted_shares =
+                self.seal_shares(cipher, Round::Dkg(self.dkg_id), &plain, &commitment_digest);
+            share_cipher::wipe(&mut plain);
+        }
+        private_shares
+    }
+
+    /// `shares` split by the signer owning each dst_key_id, and sealed to it for `round`
+    fn seal_shares(
+        &self,
+        cipher: &ShareCipher,
+        round: Round,
+        shares: &[(u32, HashMap<usize, Scalar>)],
+        commitment_digest: &[u8; 32],
+    ) -> Vec<EncryptedShares> {
+        let recipients: BTreeSet<u32> = self.key_owners.values().cloned().collect();
+        let mut sealed = Vec::with_capacity(recipients.len());
+        for recipient in recipients {
+            let owned = |key_id: &usize| self.key_owners.get(&(*key_id as u32)) == Some(&recipient);
+            let mut theirs: Vec<(u32, HashMap<usize, Scalar>)> = shares
+                .iter()
+                .map(|(src_key_id, shares)| {
+                    let shares = shares
+                        .iter()
+                        .filter(|(dst_key_id, _)| owned(dst_key_id))
+                        .map(|(dst_key_id, share)| (*dst_key_id, *share))
+                        .collect();
+                    (*src_key_id, shares)
+                })
+                .collect();
+            // excluded signers get no shares
+            if theirs.iter().any(|(_, shares)| !shares.is_empty()) {
+                match cipher.seal(recipient, round, commitment_digest, &theirs) {
+                    Some(shares) => sealed.push(shares),
+                    None => warn!(
+                        target: SIGNING_ROUND,
+                        "no network key to seal signer #{}'s private shares to", recipient
+                    ),
+                }
+            }
+            share_cipher::wipe(&mut theirs);
+        }
+        sealed
+    }
+
+    /// Key ids whose commitments or private shares, as `phase` says, have not arrived
+    fn missing_key_ids(&self, phase: DkgPhase) -> Vec<u32> {
+        (0..self.total as u32)
+            .filter(|key_id| match phase {
+                DkgPhase::Public => !self.commitments.contains_key(key_id),
+                DkgPhase::Private => !self.shares.contains_key(key_id),
+            })
+            .collect()
+    }
+
+    /// Fail the current DKG round if, at `now`, its gather phase has run longer than its
+    /// timeout, returning the DkgEnd naming the key ids still missing. The round goes back to
+    /// idle, so the coordinator's next DkgBegin starts afresh.
+    pub fn expire_dkg(&mut self, now: Instant) -> Vec<MessageTypes> {
+        let timeout = match self.state {
+            States::DkgPublicGather => self.dkg_timeouts.public,
+            // the group keys are sent as soon as the shares are in, so they share a timeout
+            States::DkgPrivateGather | States::DkgConfirm => self.dkg_timeouts.private,
+            _ => return vec![],
+        };
+        let Some(started) = self.phase_started else {
+            return vec![];
+        };
+        if now.saturating_duration_since(started) < timeout {
+            return vec![];
+        }
+        let failure = match self.state {
+            States::DkgPublicGather => {
+                DkgFailure::MissingCommitments(self.missing_key_ids(DkgPhase::Public))
+            }
+            States::DkgPrivateGather => {
+                DkgFailure::MissingShares(self.missing_key_ids(DkgPhase::Private))
+            }
+            _ => {
+                let silent: Vec<u32> = self
+                    .signers_with_shares()
+                    .into_iter()
+                    .filter(|signer_id| !self.group_keys.contains_key(signer_id))
+                    .collect();
+                DkgFailure::MissingGroupKeys(self.key_ids_of(&silent))
+            }
+        };
+        warn!(
+            target: SIGNING_ROUND,
+            "DKG round #{} timed out after {:?}: {}", self.dkg_id, timeout, failure
+        );
+        let dkg_end = self.dkg_failed(failure);
+        if let Err(e) = self.move_to(States::Idle) {
+            warn!(target: SIGNING_ROUND, "{}", e);
+        }
+        dkg_end
+    }
+
+    /// Ask for the commitments or private shares still missing once the current DKG round
+    /// stalls, at most [`MAX_SHARE_REQUEST_ATTEMPTS`] times a round
+    pub fn missing_share_requests(&mut self) -> Vec<MessageTypes> {
+        let phase = match self.state {
+            States::DkgPublicGather => DkgPhase::Public,
+            States::DkgPrivateGather => DkgPhase::Private,
+            _ => return vec![],
+        };
+        let Some(to_party) = self.key_ids().first().map(|key_id| *key_id as u32) else {
+            return vec![];
+        };
+        if self.share_request_attempts >= MAX_SHARE_REQUEST_ATTEMPTS {
+            return vec![];
+        }
+        let missing = self.missing_key_ids(phase);
+        if missing.is_empty() {
+            return vec![];
+        }
+        let attempt = self.share_request_attempts;
+        self.share_request_attempts += 1;
+        warn!(
+            target: SIGNING_ROUND,
+            "DKG round #{} stalled, asking for the {:?} pieces of key ids {:?} (attempt {}/{})",
+            self.dkg_id,
+            phase,
+            missing,
+            attempt + 1,
+            MAX_SHARE_REQUEST_ATTEMPTS
+        );
+        missing
+            .into_iter()
+            .map(|from_party| {
+                MessageTypes::MissingShareRequest(MissingShareRequest {
+                    dkg_id: self.dkg_id,
+                    signer_id: self.signer.signer_id,
+                    from_party,
+                    to_party,
+                    phase,
+                    attempt,
+                })
+            })
+            .collect()
+    }
+
+    /// Send again the commitment or private shares a stalled peer is missing from one of our
+    /// parties, once per party and attempt. Requests we cannot or need not answer are dropped.
+    fn missing_share_request(&mut self, request: MissingShareRequest) -> Vec<MessageTypes> {
+        let key_ids = self.key_ids();
+        // the owner of `to_party` as far as we know, which must be the requester
+        let to_owner = if key_ids.contains(&(request.to_party as usize)) {
+            Some(self.signer.signer_id)
+        } else {
+            self.key_owners.get(&request.to_party).copied()
+        };
+        if request.dkg_id != self.dkg_id
+            || request.signer_id == self.signer.signer_id
+            || !key_ids.contains(&(request.from_party as usize))
+            || request.attempt >= MAX_SHARE_REQUEST_ATTEMPTS
+            || request.to_party as usize >= self.total
+            || to_owner.is_some_and(|owner| owner != request.signer_id)
+            || self.is_excluded()
+        {
+            return vec![];
+        }
+        let sent = match request.phase {
+            DkgPhase::Public => Some(request.from_party),
+            DkgPhase::Private => None,
+        };
+        if !self
+            .retransmitted
+            .insert((request.signer_id, request.attempt, sent))
+        {
+            return vec![];
+        }
+        let resent = match request.phase {
+            DkgPhase::Public => self.commitments.get(&request.from_party).map(|commitment| {
+                MessageTypes::DkgPublicShare(DkgPublicShare {
+                    dkg_id: self.dkg_id,
+                    dkg_public_id: self.dkg_public_id,
+                    signer_id: self.signer.signer_id,
+                    key_id: request.from_party,
+                    public_share: commitment.clone(),
+                })
+            }),
+            // only once we have sent them, i.e. kept those for our own keys
+            DkgPhase::Private if self.shares.contains_key(&request.from_party) => self
+                .commitment_digest
+                .map(|digest| MessageTypes::DkgPrivateShares(self.private_shares_message(digest))),
+            DkgPhase::Private => None,
+        };
+        if resent.is_some() {
+            info!(
+                target: SIGNING_ROUND,
+                "sending the {:?} pieces of our keys again for signer #{} (attempt {})",
+                request.phase,
+                request.signer_id,
+                request.attempt
+            );
+        }
+        resent.into_iter().collect()
+    }
+
     fn dkg_public_share(
         &mut self,
         dkg_public_share: DkgPublicShare,
     ) -> Result<Vec<MessageTypes>, Error> {
+        self.claim_key(dkg_public_share.signer_id, dkg_public_share.key_id)?;
+        if self.excluded_key_ids.contains(&dkg_public_share.key_id) {
+            debug!(
+                target: SIGNING_ROUND,
+                "dropping commitment from excluded key #{}",
+                dkg_public_share.key_id
+            );
+            return Ok(vec![]);
+        }
+        if self.commitment_digest.is_some() {
+            warn!(
+                target: SIGNING_ROUND,
+                "dropping commitment from key #{} after the commitment phase closed",
+                dkg_public_share.key_id
+            );
+            return Ok(vec![]);
+        }
         self.commitments
-            .insert(dkg_public_share.party_id, dkg_public_share.public_share);
+            .insert(dkg_public_share.key_id, dkg_public_share.public_share);
         info!(
+            target: SIGNING_ROUND,
             "received party #{} PUBLIC commitments {}/{}",
-            dkg_public_share.party_id,
+            dkg_public_share.key_id,
             self.commitments.len(),
             self.total
         );
@@ -608,101 +3112,3315 @@ impl SigningRound {
 
     fn dkg_private_shares(
         &mut self,
-        dkg_private_shares: DkgPrivateShares,
+        mut dkg_private_shares: DkgPrivateShares,
     ) -> Result<Vec<MessageTypes>, Error> {
-        let shares_clone = dkg_private_shares.private_shares.clone();
-        self.shares
-            .insert(dkg_private_shares.key_id, dkg_private_shares.private_shares);
-        info!(
-            "received party #{} PRIVATE shares {}/{} {:?}",
-            dkg_private_shares.key_id,
-            self.shares.len(),
-            self.total,
-            shares_clone.keys(),
+        let digest = dkg_private_shares.digest();
+        // the message as it went over the relay, the evidence of a DkgBlame should its shares
+        // to us be bad. Shares in it are in the clear already.
+        let sent = dkg_private_shares.clone();
+        if let Some(cipher) = &self.share_cipher {
+            // shares in the clear went past the relay: take none of them
+            if !dkg_private_shares.private_shares.is_empty() {
+                share_cipher::wipe(&mut dkg_private_shares.private_shares);
+                return Err(Error::InvalidDkgPrivateShares(dkg_private_shares.signer_id));
+            }
+            let sealed = dkg_private_shares
+                .encrypted_shares
+                .iter()
+                .find(|sealed| sealed.signer_id == self.signer.signer_id);
+            if let Some(sealed) = sealed {
+                dkg_private_shares.private_shares = cipher
+                    .open(
+                        dkg_private_shares.signer_id,
+                        dkg_private_shares.dkg_id,
+                        &dkg_private_shares.commitment_digest,
+                        sealed,
+                    )
+                    .ok_or(Error::InvalidDkgPrivateShares(dkg_private_shares.signer_id))?;
+            }
+        }
+        if dkg_private_shares
+            .private_shares
+            .iter()
+            .any(|(src_key_id, _)| *src_key_id as usize >= self.total)
+        {
+            return Err(Error::InvalidDkgPrivateShares(dkg_private_shares.signer_id));
+        }
+        for (src_key_id, _) in &dkg_private_shares.private_shares {
+            self.claim_key(dkg_private_shares.signer_id, *src_key_id)?;
+        }
+        self.share_digests
+            .insert(dkg_private_shares.signer_id, digest);
+        let mut reports = self.audit_share_digests();
+        if dkg_private_shares.commitment_digest != self.close_commitment_phase() {
+            warn!(
+                target: SIGNING_ROUND,
+                "signer #{} built its private shares on other commitments than ours",
+                dkg_private_shares.signer_id
+            );
+            // the shares count as received, so that DKG ends and reports the mismatch
+            for (src_key_id, mut shares) in dkg_private_shares.private_shares {
+                shares.wipe();
+                if !self.excluded_key_ids.contains(&src_key_id) {
+                    self.commitment_mismatches.insert(src_key_id);
+                    self.shares.insert(src_key_id, Secret::new(HashMap::new()));
+                }
+            }
+            return Ok(reports);
+        }
+
+        let key_ids = self.key_ids();
+        let mut received = Vec::with_capacity(dkg_private_shares.private_shares.len());
+        for (src_key_id, mut shares) in dkg_private_shares.private_shares {
+            if self.excluded_key_ids.contains(&src_key_id) {
+                shares.wipe();
+                continue;
+            }
+            // only keep the shares destined for our own key_ids, and wipe the rest
+            let own_shares: HashMap<usize, Scalar> = key_ids
+                .iter()
+                .filter_map(|key_id| shares.remove(key_id).map(|share| (*key_id, share)))
+                .collect();
+            shares.wipe();
+            received.push((src_key_id, own_shares));
+        }
+        // the commitments are all in once the commitment digests match
+        let mismatched = mismatched_shares(&self.commitments, &received);
+        if !mismatched.is_empty() && self.blamed.insert(sent.signer_id) {
+            reports.push(self.blame_message(sent));
+        }
+        for (src_key_id, mut own_shares) in received {
+            if mismatched.contains(&src_key_id) {
+                warn!(
+                    target: SIGNING_ROUND,
+                    "signer #{} sent key #{} PRIVATE shares which do not match its commitment",
+                    dkg_private_shares.signer_id,
+                    src_key_id
+                );
+                // the shares count as received, so that DKG ends and reports them
+                own_shares.wipe();
+                self.bad_shares.insert(src_key_id);
+                self.shares.insert(src_key_id, Secret::new(HashMap::new()));
+                continue;
+            }
+            let dst_key_ids: Vec<usize> = own_shares.keys().cloned().collect();
+            self.shares.insert(src_key_id, Secret::new(own_shares));
+            info!(
+                target: SIGNING_ROUND,
+                "received signer #{} key #{} PRIVATE shares {}/{} {:?}",
+                dkg_private_shares.signer_id,
+                src_key_id,
+                self.shares.len(),
+                self.total,
+                dst_key_ids,
+            );
+        }
+        if self.bad_shares.is_empty() || self.state != States::DkgPrivateGather {
+            return Ok(reports);
+        }
+        // no secret can be computed from a bad share, so end the round now rather than wait
+        // for every other share to arrive
+        let mut out = reports;
+        out.push(self.share_digests_message());
+        out.extend(self.dkg_ended()?);
+        self.move_to(States::Idle)?;
+        Ok(out)
+    }
+
+    /// Blame the sender of `shares`, whose shares to us do not match its commitments, revealing
+    /// the key they were sealed to us with
+    fn blame_message(&self, shares: DkgPrivateShares) -> MessageTypes {
+        warn!(
+            target: SIGNING_ROUND,
+            "DKG round #{}: blaming signer #{} for its private shares",
+            self.dkg_id,
+            shares.signer_id
         );
-        Ok(vec![])
+        let share_key = self
+            .share_cipher
+            .as_ref()
+            .and_then(|cipher| cipher.share_key(shares.signer_id, shares.dkg_id));
+        MessageTypes::DkgBlame(DkgBlame {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer.signer_id,
+            accused_id: shares.signer_id,
+            shares,
+            share_key,
+        })
     }
-}
 
-impl From<&FrostSigner> for SigningRound {
-    fn from(signer: &FrostSigner) -> Self {
-        let signer_id = signer.signer_id;
-        assert!(signer_id > 0 && signer_id as usize <= signer.config.total_signers);
-        let party_ids = vec![(signer_id * 2 - 2) as usize, (signer_id * 2 - 1) as usize]; // make two party_ids based on signer_id
+    /// The digests of the private shares we received, for our peers to check theirs against
+    fn share_digests_message(&self) -> MessageTypes {
+        MessageTypes::DkgShareDigests(DkgShareDigests {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer.signer_id,
+            digests: self
+                .share_digests
+                .iter()
+                .map(|(sender_id, digest)| (*sender_id, *digest))
+                .collect(),
+        })
+    }
 
-        assert!(signer.config.keys_threshold <= signer.config.total_keys);
-        let mut rng = OsRng::default();
-        let frost_signer = v1::Signer::new(
-            &party_ids,
-            signer.config.total_keys,
-            signer.config.keys_threshold,
-            &mut rng,
+    fn dkg_share_digests(&mut self, digests: DkgShareDigests) -> Result<Vec<MessageTypes>, Error> {
+        if digests.dkg_id != self.dkg_id || digests.signer_id == self.signer.signer_id {
+            return Ok(vec![]);
+        }
+        self.peer_share_digests
+            .insert(digests.signer_id, digests.digests.into_iter().collect());
+        Ok(self.audit_share_digests())
+    }
+
+    /// Report the signers which sent us and a peer different private shares, once each
+    fn audit_share_digests(&mut self) -> Vec<MessageTypes> {
+        let views = self
+            .peer_share_digests
+            .iter()
+            .chain([(&self.signer.signer_id, &self.share_digests)]);
+        let mut reports = vec![];
+        for (equivocator_id, digests) in find_equivocations(views) {
+            if !self.equivocators.insert(equivocator_id) {
+                continue;
+            }
+            warn!(
+                target: SIGNING_ROUND,
+                "DKG round #{}: signer #{} sent different private shares to signers {:?}",
+                self.dkg_id,
+                equivocator_id,
+                digests.iter().map(|(receiver_id, _)| receiver_id).collect::<Vec<_>>()
+            );
+            reports.push(MessageTypes::EquivocationReport(EquivocationReport {
+                dkg_id: self.dkg_id,
+                signer_id: self.signer.signer_id,
+                equivocator_id,
+                digests,
+            }));
+        }
+        reports
+    }
+
+    /// Ask peers how a round ended if we were not around to see its DkgEnd
+    fn round_outcome_catch_up(&mut self, dkg_id: u64) -> Option<MessageTypes> {
+        if self.generations.contains_key(&dkg_id) || self.outcome_attestations.contains_key(&dkg_id)
+        {
+            return None;
+        }
+        info!(
+            target: SIGNING_ROUND,
+            "signer #{} has no outcome for dkg round #{}, querying peers",
+            self.signer.signer_id, dkg_id
         );
+        self.outcome_attestations.insert(dkg_id, HashMap::new());
+        Some(MessageTypes::RoundOutcomeQuery(RoundOutcomeQuery {
+            dkg_id,
+            signer_id: self.signer.signer_id,
+        }))
+    }
 
-        SigningRound {
+    fn round_outcome_query(
+        &mut self,
+        query: RoundOutcomeQuery,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        if query.signer_id == self.signer.signer_id {
+            return Ok(vec![]);
+        }
+        match self.generations.get(&query.dkg_id) {
+            Some(outcome) => {
+                let response = RoundOutcomeResponse {
+                    dkg_id: query.dkg_id,
+                    signer_id: self.signer.signer_id,
+                    key_ids: self.key_ids().iter().map(|id| *id as u32).collect(),
+                    outcome: outcome.clone(),
+                };
+                Ok(vec![MessageTypes::RoundOutcomeResponse(response)])
+            }
+            None => Ok(vec![]),
+        }
+    }
+
+    fn round_outcome_response(
+        &mut self,
+        response: RoundOutcomeResponse,
+    ) -> Result<Vec<MessageTypes>, Error> {
+        if response.signer_id == self.signer.signer_id
+            || self.generations.contains_key(&response.dkg_id)
+        {
+            return Ok(vec![]);
+        }
+        let dkg_id = response.dkg_id;
+        let attestations = self.outcome_attestations.entry(dkg_id).or_default();
+        attestations.insert(response.signer_id, response);
+
+        // accept an outcome once distinct signers attesting to it hold threshold keys. A signer
+        // only vouches for the keys it holds, whatever key ids it claims.
+        let attestations = &self.outcome_attestations[&dkg_id];
+        let mut accepted = None;
+        for candidate in attestations.values() {
+            let attesting: Vec<&RoundOutcomeResponse> = attestations
+                .values()
+                .filter(|attestation| attestation.outcome == candidate.outcome)
+                .collect();
+            let key_ids: BTreeSet<u32> = attesting
+                .iter()
+                .flat_map(|attestation| {
+                    let held = self.held_key_ids(attestation.signer_id);
+                    attestation
+                        .key_ids
+                        .iter()
+                        .filter(|key_id| held.contains(*key_id))
+                        .cloned()
+                        .collect::<Vec<u32>>()
+                })
+                .collect();
+            if key_ids.len() >= self.threshold && attesting.len() >= self.signers_threshold {
+                accepted = Some(candidate.outcome.clone());
+                break;
+            }
+        }
+
+        if let Some(outcome) = accepted {
+            info!(
+                target: SIGNING_ROUND,
+                "dkg round #{} outcome {:?} attested by peers",
+                dkg_id, outcome
+            );
+            self.outcome_attestations.remove(&dkg_id);
+            self.generations.insert(dkg_id, outcome);
+        }
+        Ok(vec![])
+    }
+
+    /// Deal a zero polynomial for each of our keys, refreshing the shares of the current
+    /// generation, see [`crate::reshare`]
+    fn reshare_begin(&mut self, begin: ReshareBegin) -> Result<Vec<MessageTypes>, Error> {
+        let holds_keys = matches!(
+            self.generations.get(&self.dkg_id),
+            Some(outcome) if outcome.status == DkgStatus::Success
+        );
+        if begin.dkg_id != self.dkg_id || !holds_keys {
+            return Err(Error::NoKeys(begin.dkg_id));
+        }
+        if self.state != States::Idle {
+            return Err(
+                StateMachineError::BadStateChange(format!("{:?} to refresh", self.state)).into(),
+            );
+        }
+        if self.is_excluded() {
+            return Ok(vec![]);
+        }
+        info!(
+            target: SIGNING_ROUND,
+            "refreshing the key shares of DKG round #{} (refresh #{})",
+            self.dkg_id, begin.reshare_id
+        );
+        let mut rng = OsRng::default();
+        let mut shares = ReshareShares {
+            dkg_id: self.dkg_id,
+            reshare_id: begin.reshare_id,
+            signer_id: self.signer.signer_id,
+            commitments: vec![],
+            shares: vec![],
+            encrypted_shares: vec![],
+        };
+        for party in &self.signer.frost_signer.parties {
+            let mut polynomial = reshare::zero_polynomial(self.threshold, &mut rng);
+            shares
+                .commitments
+                .push((party.id as u32, reshare::commit(&polynomial)));
+            let party_shares = (0..self.total)
+                .filter(|key_id| !self.excluded_key_ids.contains(&(*key_id as u32)))
+                .map(|key_id| (key_id, reshare::evaluate(&polynomial, key_id)))
+                .collect();
+            shares.shares.push((party.id as u32, party_shares));
+            polynomial.iter_mut().for_each(Wipe::wipe);
+        }
+        if let Some(cipher) = &self.share_cipher {
+            let round = Round::Reshare {
+                dkg_id: self.dkg_id,
+                reshare_id: begin.reshare_id,
+            };
+            let mut plain = std::mem::take(&mut shares.shares);
+            shares.encrypted_shares =
+                self.seal_shares(cipher, round, &plain, &shares.commitment_digest());
+            share_cipher::wipe(&mut plain);
+        }
+        self.reshare = Some(Reshare::new(begin.reshare_id));
+        // add our own shares here rather than when the transport delivers them back
+        let mut out = self.reshare_shares(shares.clone())?;
+        out.insert(0, MessageTypes::ReshareShares(shares));
+        Ok(out)
+    }
+
+    /// Check a peer's refresh shares to our keys against its commitments and add them up,
+    /// sending our ReshareEnd once every key was heard from
+    fn reshare_shares(&mut self, mut msg: ReshareShares) -> Result<Vec<MessageTypes>, Error> {
+        let key_ids = self.key_ids();
+        let dkg_id = self.dkg_id;
+        let participants = (0..self.total as u32)
+            .filter(|key_id| !self.excluded_key_ids.contains(key_id))
+            .count();
+        let wanted = |reshare: &Reshare| {
+            msg.dkg_id == dkg_id && msg.reshare_id == reshare.reshare_id && reshare.digest.is_none()
+        };
+        if !self.reshare.as_ref().is_some_and(wanted) {
+            debug!(
+                target: SIGNING_ROUND,
+                "dropping refresh #{} shares of DKG round #{} from signer #{}",
+                msg.reshare_id,
+                msg.dkg_id,
+                msg.signer_id
+            );
+            for (_, shares) in &mut msg.shares {
+                shares.wipe();
+            }
+            return Ok(vec![]);
+        }
+        if let Some(cipher) = &self.share_cipher {
+            // shares in the clear went past the relay: take none of them
+            if !msg.shares.is_empty() {
+                share_cipher::wipe(&mut msg.shares);
+                return Err(Error::InvalidReshareShares(msg.signer_id));
+            }
+            let round = Round::Reshare {
+                dkg_id,
+                reshare_id: msg.reshare_id,
+            };
+            let sealed = msg
+                .encrypted_shares
+                .iter()
+                .find(|sealed| sealed.signer_id == self.signer.signer_id);
+            if let Some(sealed) = sealed {
+                msg.shares = cipher
+                    .open(msg.signer_id, round, &msg.commitment_digest(), sealed)
+                    .ok_or(Error::InvalidReshareShares(msg.signer_id))?;
+            }
+        }
+        if msg
+            .commitments
+            .iter()
+            .any(|(src_key_id, _)| *src_key_id as usize >= self.total)
+            || msg.commitments.len() != msg.shares.len()
+        {
+            share_cipher::wipe(&mut msg.shares);
+            return Err(Error::InvalidReshareShares(msg.signer_id));
+        }
+        for (src_key_id, _) in &msg.commitments {
+            self.claim_key(msg.signer_id, *src_key_id)?;
+        }
+        let mut shares: BTreeMap<u32, HashMap<usize, Scalar>> = msg.shares.drain(..).collect();
+        let excluded_key_ids = &self.excluded_key_ids;
+        let threshold = self.threshold;
+        let Some(refresh) = self.reshare.as_mut() else {
+            return Ok(vec![]);
+        };
+        for (src_key_id, commitment) in msg.commitments {
+            let mut src_shares = shares.remove(&src_key_id).unwrap_or_default();
+            // excluded keys hold no share, and a key is only counted once, e.g. when the
+            // transport delivers our own shares back
+            if excluded_key_ids.contains(&src_key_id)
+                || refresh.commitments.contains_key(&src_key_id)
+            {
+                src_shares.wipe();
+                continue;
+            }
+            let valid = reshare::is_refresh_commitment(&commitment, threshold)
+                && key_ids.iter().all(|key_id| {
+                    src_shares
+                        .get(key_id)
+                        .is_some_and(|share| share_matches_points(&commitment, *key_id, share))
+                });
+            if valid {
+                for key_id in &key_ids {
+                    let delta = refresh
+                        .deltas
+                        .entry(*key_id)
+                        .or_insert_with(Scalar::default);
+                    *delta = *delta + src_shares[key_id];
+                }
+            } else {
+                warn!(
+                    target: SIGNING_ROUND,
+                    "signer #{} sent key #{} refresh shares which do not match its commitment",
+                    msg.signer_id,
+                    src_key_id
+                );
+                refresh.bad_shares.insert(src_key_id);
+            }
+            src_shares.wipe();
+            refresh.commitments.insert(src_key_id, commitment);
+        }
+        for (_, mut rest) in shares {
+            rest.wipe();
+        }
+        if refresh.commitments.len() < participants {
+            return Ok(vec![]);
+        }
+        let digest = reshare::refresh_digest(dkg_id, refresh.reshare_id, &refresh.commitments);
+        refresh.digest = Some(digest);
+        let status = if refresh.bad_shares.is_empty() {
+            DkgStatus::Success
+        } else {
+            DkgStatus::Failure(DkgFailure::BadShares(
+                refresh.bad_shares.iter().cloned().collect(),
+            ))
+        };
+        info!(
+            target: SIGNING_ROUND,
+            "refresh #{} of DKG round #{} ended {:?}", refresh.reshare_id, dkg_id, status
+        );
+        Ok(vec![MessageTypes::ReshareEnd(ReshareEnd {
+            dkg_id,
+            reshare_id: refresh.reshare_id,
+            signer_id: self.signer.signer_id,
+            status,
+            digest,
+        })])
+    }
+
+    /// Swap in our refreshed shares once the coordinator confirmed every signer refreshed from
+    /// the commitments we did, and store them in place of the old ones
+    fn reshare_commit(&mut self, commit: ReshareCommit) -> Result<Vec<MessageTypes>, Error> {
+        let Some(refresh) = self.reshare.take() else {
+            return Ok(vec![]);
+        };
+        if commit.dkg_id != self.dkg_id || commit.reshare_id != refresh.reshare_id {
+            self.reshare = Some(refresh);
+            return Ok(vec![]);
+        }
+        if refresh.digest != Some(commit.digest) || !refresh.bad_shares.is_empty() {
+            warn!(
+                target: SIGNING_ROUND,
+                "dropping refresh #{} of DKG round #{}, which ended on other commitments here",
+                refresh.reshare_id,
+                self.dkg_id
+            );
+            return Ok(vec![]);
+        }
+        // every party is refreshed, or none is
+        let mut parties = self.signer.frost_signer.parties.clone();
+        for party in &mut parties {
+            let delta = refresh
+                .deltas
+                .get(&party.id)
+                .copied()
+                .ok_or(Error::InvalidReshareShares(self.signer.signer_id))?;
+            reshare::add_to_private_key(party, &delta)?;
+        }
+        self.signer.frost_signer.parties = parties;
+        info!(
+            target: SIGNING_ROUND,
+            "refreshed the key shares of DKG round #{} for key ids {:?}",
+            self.dkg_id,
+            self.key_ids()
+        );
+        if let Some(keys) = self.stored_keys() {
+            if let Err(e) = self.key_store.put(&keys) {
+                warn!(
+                    target: SIGNING_ROUND,
+                    "failed to store the refreshed keys of DKG round #{}: {}", self.dkg_id, e
+                );
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Take up the roster `change` announces for the current generation, and start repairing
+    /// the shares of the signers joining it, see [`crate::roster`]
+    fn roster_change(&mut self, change: RosterChange) -> Result<Vec<MessageTypes>, Error> {
+        let signer_id = self.signer.signer_id;
+        let key_ids: Vec<u32> = self.key_ids().iter().map(|id| *id as u32).collect();
+        let key_owners: BTreeMap<u32, u32> = change.key_owners.iter().cloned().collect();
+        let repaired: BTreeSet<u32> = change.repaired_key_ids.iter().cloned().collect();
+        let invalid = |reason: &str| Err(Error::InvalidRosterChange(reason.to_string()));
+        if key_owners
+            .keys()
+            .any(|key_id| *key_id as usize >= self.total)
+        {
+            return invalid("unknown key id");
+        }
+        if let Some(key_id) = key_ids.iter().find(|key_id| {
+            key_owners
+                .get(key_id)
+                .is_some_and(|owner| *owner != signer_id)
+        }) {
+            return Err(Error::KeyOwnerMismatch {
+                signer_id: key_owners[key_id],
+                key_id: *key_id,
+            });
+        }
+        if !repaired
+            .iter()
+            .all(|key_id| key_owners.contains_key(key_id))
+            || change
+                .helper_key_ids
+                .iter()
+                .any(|key_id| repaired.contains(key_id) || !key_owners.contains_key(key_id))
+        {
+            return invalid("repaired and helper keys must be distinct keys of the roster");
+        }
+        if !repaired.is_empty()
+            && (change.helper_key_ids.len() < self.threshold
+                || change.group_commitment.len() != self.threshold)
+        {
+            return invalid("too few helper keys, or a group commitment of the wrong size");
+        }
+        let joining = key_ids.iter().any(|key_id| repaired.contains(key_id));
+        if joining && !key_ids.iter().all(|key_id| repaired.contains(key_id)) {
+            return invalid("only some of our keys are repaired");
+        }
+        if self.state != States::Idle {
+            return Err(StateMachineError::BadStateChange(format!(
+                "{:?} to roster change",
+                self.state
+            ))
+            .into());
+        }
+        let holds_keys = matches!(
+            self.generations.get(&self.dkg_id),
+            Some(outcome) if outcome.status == DkgStatus::Success
+        );
+        if joining {
+            let mut rng = OsRng::default();
+            self.reset(change.dkg_id, &mut rng);
+        } else if change.dkg_id != self.dkg_id {
+            return Err(Error::NoKeys(change.dkg_id));
+        } else if !holds_keys {
+            // a signer left out of the generation has nothing to take up
+            return Ok(vec![]);
+        }
+
+        self.key_owners = key_owners;
+        self.excluded_key_ids = (0..self.total as u32)
+            .filter(|key_id| !self.key_owners.contains_key(key_id))
+            .collect();
+        self.lagrange.clear();
+        info!(
+            target: SIGNING_ROUND,
+            "roster change #{} of DKG round #{}: key ids {:?} excluded, repairing key ids {:?}",
+            change.change_id,
+            self.dkg_id,
+            self.excluded_key_ids,
+            repaired
+        );
+        if !joining {
+            if let Some(keys) = self.stored_keys() {
+                if let Err(e) = self.key_store.put(&keys) {
+                    warn!(
+                        target: SIGNING_ROUND,
+                        "failed to store the new roster of DKG round #{}: {}", self.dkg_id, e
+                    );
+                }
+            }
+        }
+        if repaired.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let repair = Repair::new(
+            change.change_id,
+            repaired,
+            &self.key_owners,
+            &change.helper_key_ids,
+            change.group_commitment,
+        );
+        let helper_parties: Vec<&v1::Party> = self
+            .signer
+            .frost_signer
+            .parties
+            .iter()
+            .filter(|party| change.helper_key_ids.contains(&(party.id as u32)))
+            .collect();
+        if helper_parties.is_empty() {
+            // joining signers wait for the sums, the other signers have no part in the repair
+            if joining {
+                self.repair = Some(repair);
+            }
+            return Ok(vec![]);
+        }
+        let mut rng = OsRng::default();
+        let mut pieces: Vec<(u32, Vec<(u32, Scalar)>)> = repair
+            .helpers
+            .iter()
+            .map(|helper| (*helper, vec![]))
+            .collect();
+        for key_id in &repair.repaired_key_ids {
+            let mut term = repair.term(&helper_parties, *key_id);
+            let split = roster::split(&term, repair.helpers.len(), &mut rng);
+            for ((_, dst_pieces), piece) in pieces.iter_mut().zip(split) {
+                dst_pieces.push((*key_id, piece));
+            }
+            term.wipe();
+        }
+        self.repair = Some(repair);
+        let mut shares = RepairShares {
+            dkg_id: self.dkg_id,
+            change_id: change.change_id,
+            signer_id,
+            pieces,
+            encrypted_pieces: vec![],
+        };
+        if let Some(cipher) = &self.share_cipher {
+            let round = Round::Repair {
+                dkg_id: self.dkg_id,
+                change_id: change.change_id,
+            };
+            for (recipient, mut pieces) in std::mem::take(&mut shares.pieces) {
+                match cipher.seal_values(recipient, round, &pieces) {
+                    Some(sealed) => shares.encrypted_pieces.push(sealed),
+                    None => warn!(
+                        target: SIGNING_ROUND,
+                        "no network key to seal signer #{}'s repair pieces to", recipient
+                    ),
+                }
+                pieces.iter_mut().for_each(|(_, piece)| piece.wipe());
+            }
+        }
+        // add our own pieces here rather than when the transport delivers them back
+        let mut out = self.repair_shares(shares.clone())?;
+        out.insert(0, MessageTypes::RepairShares(shares));
+        Ok(out)
+    }
+
+    /// Add up the pieces helper signers sent us, sending the joining signers our sums once
+    /// every helper was heard from
+    fn repair_shares(&mut self, mut msg: RepairShares) -> Result<Vec<MessageTypes>, Error> {
+        let signer_id = self.signer.signer_id;
+        let dkg_id = self.dkg_id;
+        let wipe = |msg: &mut RepairShares| {
+            for (_, pieces) in &mut msg.pieces {
+                pieces.iter_mut().for_each(|(_, piece)| piece.wipe());
+            }
+        };
+        let wanted = |repair: &Repair| {
+            msg.dkg_id == dkg_id
+                && msg.change_id == repair.change_id
+                && repair.helpers.contains(&signer_id)
+        };
+        let Some(repair) = self.repair.as_mut().filter(|repair| wanted(repair)) else {
+            wipe(&mut msg);
+            return Ok(vec![]);
+        };
+        if !repair.helpers.contains(&msg.signer_id) {
+            wipe(&mut msg);
+            return Err(Error::InvalidRepairShares(msg.signer_id));
+        }
+        if let Some(cipher) = &self.share_cipher {
+            // pieces in the clear went past the relay: take none of them
+            if !msg.pieces.is_empty() {
+                wipe(&mut msg);
+                return Err(Error::InvalidRepairShares(msg.signer_id));
+            }
+            let round = Round::Repair {
+                dkg_id,
+                change_id: msg.change_id,
+            };
+            let sealed = msg
+                .encrypted_pieces
+                .iter()
+                .find(|sealed| sealed.signer_id == signer_id);
+            if let Some(sealed) = sealed {
+                let pieces = cipher
+                    .open_values(msg.signer_id, round, sealed)
+                    .ok_or(Error::InvalidRepairShares(msg.signer_id))?;
+                msg.pieces = vec![(signer_id, pieces)];
+            }
+        }
+        // a helper is only counted once, e.g. when the transport delivers our own pieces back
+        if repair.heard.insert(msg.signer_id) {
+            for (dst_signer_id, pieces) in &msg.pieces {
+                if *dst_signer_id != signer_id {
+                    continue;
+                }
+                for (key_id, piece) in pieces {
+                    if repair.repaired_key_ids.contains(key_id) {
+                        repair.add(*key_id, piece);
+                    }
+                }
+            }
+        }
+        wipe(&mut msg);
+        if !repair.complete() {
+            return Ok(vec![]);
+        }
+        let mut sums: Vec<(u32, Scalar)> = repair
+            .repaired_key_ids
+            .iter()
+            .map(|key_id| {
+                (
+                    *key_id,
+                    repair.sums.get(key_id).copied().unwrap_or_default(),
+                )
+            })
+            .collect();
+        let mut encrypted_sums = vec![];
+        if let Some(cipher) = &self.share_cipher {
+            let round = Round::Repair {
+                dkg_id,
+                change_id: repair.change_id,
+            };
+            // each joining signer gets the sums of its own keys only
+            let mut joining: BTreeMap<u32, Vec<(u32, Scalar)>> = BTreeMap::new();
+            for (key_id, sum) in sums.drain(..) {
+                if let Some(owner) = self.key_owners.get(&key_id) {
+                    joining.entry(*owner).or_default().push((key_id, sum));
+                }
+            }
+            for (recipient, mut sums) in joining {
+                match cipher.seal_values(recipient, round, &sums) {
+                    Some(sealed) => encrypted_sums.push(sealed),
+                    None => warn!(
+                        target: SIGNING_ROUND,
+                        "no network key to seal signer #{}'s repaired shares to", recipient
+                    ),
+                }
+                sums.iter_mut().for_each(|(_, sum)| sum.wipe());
+            }
+        }
+        info!(
+            target: SIGNING_ROUND,
+            "sending the repaired shares of roster change #{} of DKG round #{}",
+            repair.change_id,
+            dkg_id
+        );
+        let repaired = RepairedShares {
+            dkg_id,
+            change_id: repair.change_id,
+            signer_id,
+            sums,
+            encrypted_sums,
+        };
+        // a helper is done once it sent its sums
+        self.repair = None;
+        Ok(vec![MessageTypes::RepairedShares(repaired)])
+    }
+
+    /// Add up the sums helper signers sent for our keys and, once every helper was heard from,
+    /// take them as our shares if they match the group commitment
+    fn repaired_shares(&mut self, mut msg: RepairedShares) -> Result<Vec<MessageTypes>, Error> {
+        let key_ids = self.key_ids();
+        let dkg_id = self.dkg_id;
+        let wanted = |repair: &Repair| {
+            msg.dkg_id == dkg_id
+                && msg.change_id == repair.change_id
+                && key_ids
+                    .iter()
+                    .all(|key_id| repair.repaired_key_ids.contains(&(*key_id as u32)))
+        };
+        let Some(repair) = self.repair.as_mut().filter(|repair| wanted(repair)) else {
+            msg.sums.iter_mut().for_each(|(_, sum)| sum.wipe());
+            return Ok(vec![]);
+        };
+        if !repair.helpers.contains(&msg.signer_id) {
+            msg.sums.iter_mut().for_each(|(_, sum)| sum.wipe());
+            return Err(Error::InvalidRepairShares(msg.signer_id));
+        }
+        if let Some(cipher) = &self.share_cipher {
+            // sums in the clear went past the relay: take none of them
+            if !msg.sums.is_empty() {
+                msg.sums.iter_mut().for_each(|(_, sum)| sum.wipe());
+                return Err(Error::InvalidRepairShares(msg.signer_id));
+            }
+            let round = Round::Repair {
+                dkg_id,
+                change_id: msg.change_id,
+            };
+            let sealed = msg
+                .encrypted_sums
+                .iter()
+                .find(|sealed| sealed.signer_id == self.signer.signer_id);
+            if let Some(sealed) = sealed {
+                msg.sums = cipher
+                    .open_values(msg.signer_id, round, sealed)
+                    .ok_or(Error::InvalidRepairShares(msg.signer_id))?;
+            }
+        }
+        if repair.heard.insert(msg.signer_id) {
+            for (key_id, sum) in &msg.sums {
+                if key_ids.contains(&(*key_id as usize)) {
+                    repair.add(*key_id, sum);
+                }
+            }
+        }
+        msg.sums.iter_mut().for_each(|(_, sum)| sum.wipe());
+        if !repair.complete() {
+            return Ok(vec![]);
+        }
+        let Some(repair) = self.repair.take() else {
+            return Ok(vec![]);
+        };
+        let valid = key_ids.iter().all(|key_id| {
+            repair
+                .sums
+                .get(&(*key_id as u32))
+                .is_some_and(|share| share_matches_points(&repair.group_commitment, *key_id, share))
+        });
+        let status = if valid {
+            let group_key = repair.group_commitment[0];
+            // every party takes its repaired share, or none does
+            let mut parties = self.signer.frost_signer.parties.clone();
+            for party in &mut parties {
+                roster::set_keys(party, &repair.sums[&(party.id as u32)], &group_key)?;
+            }
+            self.signer.frost_signer.parties = parties;
+            self.generations.insert(
+                dkg_id,
+                RoundOutcome {
+                    status: DkgStatus::Success,
+                    group_key: Some(group_key),
+                },
+            );
+            if let Some(keys) = self.stored_keys() {
+                if let Err(e) = self.key_store.put(&keys) {
+                    warn!(
+                        target: SIGNING_ROUND,
+                        "failed to store the repaired keys of DKG round #{}: {}", dkg_id, e
+                    );
+                }
+            }
+            DkgStatus::Success
+        } else {
+            warn!(
+                target: SIGNING_ROUND,
+                "the repaired shares of roster change #{} do not match the group commitment",
+                repair.change_id
+            );
+            DkgStatus::Failure(DkgFailure::BadShares(
+                repair.helper_key_ids.iter().map(|id| *id as u32).collect(),
+            ))
+        };
+        info!(
+            target: SIGNING_ROUND,
+            "roster change #{} of DKG round #{} ended {:?} for key ids {:?}",
+            repair.change_id,
+            dkg_id,
+            status,
+            key_ids
+        );
+        Ok(vec![MessageTypes::RosterChangeEnd(RosterChangeEnd {
+            dkg_id,
+            change_id: repair.change_id,
+            signer_id: self.signer.signer_id,
+            status,
+        })])
+    }
+
+    /// Record `signer_id` as the owner of `key_id`, unless the key is ours or another signer
+    /// already claimed it
+    fn claim_key(&mut self, signer_id: u32, key_id: u32) -> Result<(), Error> {
+        let owner = if self.key_ids().contains(&(key_id as usize)) {
+            Some(self.signer.signer_id)
+        } else {
+            self.key_owners.get(&key_id).copied()
+        };
+        match owner {
+            Some(owner) if owner != signer_id => Err(Error::KeyOwnerMismatch { signer_id, key_id }),
+            _ => {
+                self.key_owners.insert(key_id, signer_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Key ids signer `signer_id` holds: those the key owners of the current generation give it,
+    /// or before any are known, the two the roster assigns each signer
+    fn held_key_ids(&self, signer_id: u32) -> BTreeSet<u32> {
+        if !self.key_owners.is_empty() {
+            return self
+                .key_owners
+                .iter()
+                .filter(|(_, owner)| **owner == signer_id)
+                .map(|(key_id, _)| *key_id)
+                .collect();
+        }
+        match signer_id.checked_mul(2) {
+            Some(last) if signer_id > 0 => [last - 2, last - 1]
+                .into_iter()
+                .filter(|key_id| (*key_id as usize) < self.total)
+                .collect(),
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Whether `change` repairs the shares of our keys, i.e. adds this signer to its generation
+    pub fn joins(&self, change: &RosterChange) -> bool {
+        self.key_ids()
+            .iter()
+            .any(|key_id| change.repaired_key_ids.contains(&(*key_id as u32)))
+    }
+
+    /// Whether the coordinator dropped this signer from the current DKG round
+    fn is_excluded(&self) -> bool {
+        self.key_ids()
+            .iter()
+            .any(|key_id| self.excluded_key_ids.contains(&(*key_id as u32)))
+    }
+
+    fn key_ids(&self) -> Vec<usize> {
+        self.signer
+            .frost_signer
+            .parties
+            .iter()
+            .map(|party| party.id)
+            .collect()
+    }
+}
+
+impl From<&FrostSigner> for SigningRound {
+    fn from(signer: &FrostSigner) -> Self {
+        let signer_id = signer.signer_id;
+        assert!(signer_id > 0 && signer_id as usize <= signer.config.total_signers);
+        let key_ids = vec![(signer_id * 2 - 2) as usize, (signer_id * 2 - 1) as usize]; // make two key_ids based on signer_id
+
+        assert!(signer.config.keys_threshold <= signer.config.total_keys);
+        let mut rng = OsRng::default();
+        let frost_signer = v1::Signer::new(
+            &key_ids,
+            signer.config.total_keys,
+            signer.config.keys_threshold,
+            &mut rng,
+        );
+
+        SigningRound {
+            dkg_id: 1,
+            dkg_public_id: 1,
+            sign_id: 1,
+            sign_nonce_id: 1,
+            threshold: signer.config.keys_threshold,
+            signers_threshold: signer.config.thresholds().signers,
+            total: signer.config.total_keys,
+            signer: Signer {
+                frost_signer,
+                signer_id,
+            },
+            state: States::Idle,
+            commitments: BTreeMap::new(),
+            shares: HashMap::new(),
+            public_nonces: vec![],
+            generations: BTreeMap::new(),
+            outcome_attestations: BTreeMap::new(),
+            lagrange: LagrangeCache::default(),
+            excluded_key_ids: BTreeSet::new(),
+            key_owners: BTreeMap::new(),
+            commitment_digest: None,
+            commitment_mismatches: BTreeSet::new(),
+            bad_shares: BTreeSet::new(),
+            share_digests: BTreeMap::new(),
+            peer_share_digests: BTreeMap::new(),
+            equivocators: BTreeSet::new(),
+            blamed: BTreeSet::new(),
+            group_keys: BTreeMap::new(),
+            nonce_store: NonceStore::default(),
+            key_store: KeyStore::default(),
+            sessions: Sessions::default(),
+            share_request_attempts: 0,
+            retransmitted: BTreeSet::new(),
+            dkg_timeouts: signer.config.dkg_timeouts(),
+            phase_started: None,
+            reshare: None,
+            repair: None,
+            share_cipher: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hashbrown::HashMap;
+    use rand_core::{CryptoRng, OsRng, RngCore};
+    use sha2::{Digest, Sha256};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::time::Duration;
+    use wtfrost::{
+        bip340::SchnorrProof,
+        common::{PolyCommitment, PublicNonce},
+        compute,
+        schnorr::ID,
+        v1, Point, Scalar,
+    };
+
+    use crate::nonce_store::{NonceStore, DEFAULT_NONCE_TTL};
+    use crate::protocol::MESSAGE_TAGS;
+    use crate::roster;
+    use crate::secret::Secret;
+    use crate::share_cipher::{EncryptedShares, Round, ShareCipher};
+    use crate::signing_round::{
+        commitment_digest, mismatched_shares, null_commitment, party_shares, roster_hash,
+        BatchMessage, BatchNonceRequest, BatchNonceResponse, BatchSignRequest, BatchSignResponse,
+        DkgBegin, DkgBlame, DkgEnd, DkgFailure, DkgGroupKey, DkgPhase, DkgPrivateShares,
+        DkgPublicShare, DkgQuery, DkgQueryResponse, DkgShareDigests, DkgStatus, DkgTimeouts,
+        EquivocationReport, Error, GroupKeyView, Hello, MessageTypes, MissingShareRequest,
+        NonceRequest, NonceResponse, Registration, Rejection, RejectionCode, RepairShares,
+        RepairedShares, ReshareBegin, ReshareCommit, ReshareEnd, ReshareShares, RosterChange,
+        RosterChangeEnd, RoundAbort, RoundOutcome, RoundOutcomeQuery, RoundOutcomeResponse,
+        Signable, SignatureShareRequest, SignatureShareResponse, SigningRound, UpgradeAck,
+        UpgradeWindow, MAX_SHARE_REQUEST_ATTEMPTS,
+    };
+    use crate::state_machine::States;
+    use crate::taproot::KeyPath;
+
+    fn get_rng() -> impl RngCore + CryptoRng {
+        let rnd = OsRng::default();
+        //rand::rngs::StdRng::seed_from_u64(rnd.next_u64()) // todo: fix trait `rand_core::RngCore` is not implemented for `StdRng`
+        rnd
+    }
+
+    #[test]
+    fn dkg_public_share() {
+        let mut rnd = get_rng();
+        let mut signing_round = SigningRound::new(1, 1, 1, vec![1]);
+        let public_share = DkgPublicShare {
+            dkg_id: 0,
+            signer_id: 2,
+            key_id: 0,
+            public_share: PolyCommitment {
+                id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                A: vec![],
+            },
+            dkg_public_id: 0,
+        };
+        signing_round.dkg_public_share(public_share).unwrap();
+        assert_eq!(1, signing_round.commitments.len())
+    }
+
+    #[test]
+    fn dkg_private_shares() {
+        let mut signing_round = SigningRound::new(1, 1, 1, vec![1]);
+        let mut shares = HashMap::new();
+        shares.insert(1, Scalar::new());
+        let private_shares = DkgPrivateShares {
+            dkg_id: 0,
+            signer_id: 0,
+            private_shares: vec![(0, shares)],
+            encrypted_shares: vec![],
+            commitment_digest: commitment_digest(
+                signing_round.dkg_id,
+                signing_round.commitments.iter(),
+            ),
+        };
+        signing_round.dkg_private_shares(private_shares).unwrap();
+        assert_eq!(1, signing_round.shares.len())
+    }
+
+    #[test]
+    fn dkg_private_shares_invalid_key_id() {
+        let mut signing_round = SigningRound::new(1, 1, 1, vec![0]);
+        let private_shares = DkgPrivateShares {
+            dkg_id: 0,
+            signer_id: 2,
+            private_shares: vec![(1, HashMap::new())],
+            encrypted_shares: vec![],
+            commitment_digest: commitment_digest(
+                signing_round.dkg_id,
+                signing_round.commitments.iter(),
+            ),
+        };
+        assert!(signing_round.dkg_private_shares(private_shares).is_err());
+        assert!(signing_round.shares.is_empty());
+    }
+
+    #[test]
+    fn process_rejects_invalid_request() {
+        let mut signing_round = SigningRound::new(1, 1, 1, vec![0]);
+        let request = MessageTypes::SignShareRequest(SignatureShareRequest {
+            dkg_id: 0,
+            sign_id: 1,
+            correlation_id: 1,
+            signer_id: 1,
+            key_id: 0,
+            nonces: vec![],
+            message: b"message".to_vec(),
+            key_path: None,
+        });
+        let out = signing_round.process(request.clone()).unwrap();
+        match &out[..] {
+            [MessageTypes::Rejection(rejection)] => {
+                assert_eq!(1, rejection.signer_id);
+                assert_eq!(request.request_hash().to_vec(), rejection.request_hash);
+                assert_eq!(RejectionCode::InvalidMessage, rejection.reason_code);
+                assert!(!rejection.reason_code.is_transient());
+            }
+            _ => panic!("expected a single Rejection"),
+        }
+    }
+
+    #[test]
+    fn process_drops_invalid_peer_message() {
+        let mut signing_round = SigningRound::new(1, 1, 1, vec![0]);
+        let shares = MessageTypes::DkgPrivateShares(DkgPrivateShares {
+            dkg_id: 0,
+            signer_id: 2,
+            private_shares: vec![(1, HashMap::new())],
+            encrypted_shares: vec![],
+            commitment_digest: commitment_digest(
+                signing_round.dkg_id,
+                signing_round.commitments.iter(),
+            ),
+        });
+        assert!(signing_round.process(shares).unwrap().is_empty());
+        assert!(signing_round.shares.is_empty());
+    }
+
+    #[test]
+    fn snapshot_shows_what_the_round_is_waiting_on() {
+        let mut rnd = get_rng();
+        let mut signing_round = SigningRound::new(1, 3, 1, vec![1]);
+        signing_round
+            .process(MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 3,
+                excluded_key_ids: vec![],
+            }))
+            .unwrap();
+        signing_round
+            .process(MessageTypes::DkgPublicShare(DkgPublicShare {
+                dkg_id: 3,
+                dkg_public_id: 1,
+                signer_id: 2,
+                key_id: 0,
+                public_share: PolyCommitment {
+                    id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                    A: vec![],
+                },
+            }))
+            .unwrap();
+
+        let snapshot = signing_round.snapshot();
+        assert_eq!(States::DkgPublicGather, snapshot.state);
+        assert_eq!(3, snapshot.dkg_id);
+        assert_eq!(vec![1], snapshot.key_ids);
+        // our own commitment is recorded as it is sent
+        assert_eq!(vec![0, 1], snapshot.commitments_received);
+        assert_eq!(vec![2], snapshot.commitments_missing);
+        assert!(snapshot.shares_received.is_empty());
+        assert_eq!(vec![0, 1, 2], snapshot.shares_missing);
+    }
+
+    #[test]
+    fn upgrade_window_is_acknowledged() {
+        let mut signing_round = SigningRound::new(1, 2, 2, vec![1]);
+        assert_eq!(
+            MessageTypes::Registration(Registration {
+                signer_id: 2,
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            })
+            .request_hash(),
+            signing_round.registration().request_hash()
+        );
+        let out = signing_round
+            .process(MessageTypes::UpgradeWindow(UpgradeWindow {
+                window_id: 4,
+                starts_in_secs: 60,
+                duration_secs: 600,
+                reason: "signer release".to_string(),
+            }))
+            .unwrap();
+        match &out[..] {
+            [MessageTypes::UpgradeAck(ack)] => {
+                assert_eq!(
+                    &UpgradeAck {
+                        signer_id: 2,
+                        window_id: 4
+                    },
+                    ack
+                );
+            }
+            out => panic!("expected an UpgradeAck, got {:?}", out),
+        }
+        assert_eq!(States::Idle, signing_round.state);
+    }
+
+    #[test]
+    fn round_abort_drops_an_unfinished_dkg() {
+        let mut rnd = get_rng();
+        let mut signing_round = SigningRound::new(1, 3, 1, vec![1]);
+        signing_round
+            .process(MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 3,
+                excluded_key_ids: vec![],
+            }))
+            .unwrap();
+        signing_round
+            .process(MessageTypes::DkgPublicShare(DkgPublicShare {
+                dkg_id: 3,
+                dkg_public_id: 1,
+                signer_id: 2,
+                key_id: 0,
+                public_share: PolyCommitment {
+                    id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                    A: vec![],
+                },
+            }))
+            .unwrap();
+
+        // an abort for another round is ignored
+        let abort = |dkg_id| {
+            MessageTypes::RoundAbort(RoundAbort {
+                dkg_id,
+                sign_id: 0,
+                reason: "round timed out".to_string(),
+            })
+        };
+        assert!(signing_round.process(abort(2)).unwrap().is_empty());
+        assert_eq!(States::DkgPublicGather, signing_round.state);
+
+        assert!(signing_round.process(abort(3)).unwrap().is_empty());
+        assert_eq!(States::Idle, signing_round.state);
+        assert!(signing_round.commitments.is_empty());
+
+        // the next round starts cleanly
+        signing_round
+            .process(MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 4,
+                excluded_key_ids: vec![],
+            }))
+            .unwrap();
+        assert_eq!(States::DkgPublicGather, signing_round.state);
+    }
+
+    #[test]
+    fn request_hash_distinguishes_dkg_begin_variants() {
+        let dkg_begin = DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids: vec![],
+        };
+        assert_ne!(
+            MessageTypes::DkgBegin(dkg_begin.clone()).request_hash(),
+            MessageTypes::DkgPrivateBegin(dkg_begin).request_hash()
+        );
+    }
+
+    /// A message of every type
+    fn one_of_each() -> Vec<MessageTypes> {
+        let begin = DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids: vec![],
+        };
+        let end = DkgEnd {
+            dkg_id: 1,
+            signer_id: 1,
+            status: DkgStatus::Success,
+        };
+        let commitment = null_commitment(0, 2, &mut OsRng);
+        let nonce = PublicNonce {
+            D: Point::default(),
+            E: Point::default(),
+        };
+        vec![
+            MessageTypes::DkgBegin(begin.clone()),
+            MessageTypes::DkgPrivateBegin(begin),
+            MessageTypes::DkgEnd(end.clone()),
+            MessageTypes::DkgPublicEnd(end),
+            MessageTypes::DkgQuery(DkgQuery {}),
+            MessageTypes::DkgQueryResponse(DkgQueryResponse {
+                dkg_id: 1,
+                public_share: commitment.clone(),
+            }),
+            MessageTypes::DkgPublicShare(DkgPublicShare {
+                dkg_id: 1,
+                dkg_public_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                public_share: commitment,
+            }),
+            MessageTypes::DkgPrivateShares(DkgPrivateShares {
+                dkg_id: 1,
+                signer_id: 1,
+                private_shares: vec![],
+                encrypted_shares: vec![],
+                commitment_digest: [0; 32],
+            }),
+            MessageTypes::DkgShareDigests(DkgShareDigests {
+                dkg_id: 1,
+                signer_id: 1,
+                digests: vec![],
+            }),
+            MessageTypes::EquivocationReport(EquivocationReport {
+                dkg_id: 1,
+                signer_id: 1,
+                equivocator_id: 2,
+                digests: vec![],
+            }),
+            MessageTypes::DkgBlame(DkgBlame {
+                dkg_id: 1,
+                signer_id: 1,
+                accused_id: 2,
+                shares: DkgPrivateShares {
+                    dkg_id: 1,
+                    signer_id: 2,
+                    private_shares: vec![],
+                    encrypted_shares: vec![],
+                    commitment_digest: [0; 32],
+                },
+                share_key: None,
+            }),
+            MessageTypes::NonceRequest(NonceRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                sign_nonce_id: 1,
+            }),
+            MessageTypes::NonceResponse(NonceResponse {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                sign_nonce_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                nonce: nonce.clone(),
+            }),
+            MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                nonces: vec![(0, nonce.clone())],
+                message: vec![],
+                key_path: None,
+            }),
+            MessageTypes::SignShareResponse(SignatureShareResponse {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                signature_share: wtfrost::v1::SignatureShare {
+                    id: 0,
+                    z_i: Scalar::from(1),
+                    public_key: Point::default(),
+                },
+            }),
+            MessageTypes::RoundOutcomeQuery(RoundOutcomeQuery {
+                dkg_id: 1,
+                signer_id: 1,
+            }),
+            MessageTypes::RoundOutcomeResponse(RoundOutcomeResponse {
+                dkg_id: 1,
+                signer_id: 1,
+                key_ids: vec![],
+                outcome: RoundOutcome {
+                    status: DkgStatus::Success,
+                    group_key: None,
+                },
+            }),
+            MessageTypes::Rejection(Rejection {
+                signer_id: 1,
+                request_hash: vec![],
+                reason_code: RejectionCode::InvalidState,
+                detail: String::new(),
+            }),
+            MessageTypes::Hello(Hello {
+                signer_id: 1,
+                public_key: vec![],
+                config_hash: vec![],
+            }),
+            MessageTypes::RoundAbort(RoundAbort {
+                dkg_id: 1,
+                sign_id: 1,
+                reason: String::new(),
+            }),
+            MessageTypes::UpgradeWindow(UpgradeWindow {
+                window_id: 1,
+                starts_in_secs: 0,
+                duration_secs: 0,
+                reason: String::new(),
+            }),
+            MessageTypes::UpgradeAck(UpgradeAck {
+                signer_id: 1,
+                window_id: 1,
+            }),
+            MessageTypes::Registration(Registration {
+                signer_id: 1,
+                version: String::new(),
+            }),
+            MessageTypes::BatchNonceRequest(BatchNonceRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                sign_nonce_id: 1,
+                count: 1,
+            }),
+            MessageTypes::BatchNonceResponse(BatchNonceResponse {
+                dkg_id: 1,
+                sign_id: 1,
+                sign_nonce_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                nonces: vec![nonce.clone()],
+            }),
+            MessageTypes::BatchSignRequest(BatchSignRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                messages: vec![BatchMessage {
+                    nonces: vec![(0, nonce)],
+                    message: vec![],
+                }],
+                key_path: None,
+            }),
+            MessageTypes::BatchSignResponse(BatchSignResponse {
+                dkg_id: 1,
+                sign_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                signature_shares: vec![],
+            }),
+            MessageTypes::MissingShareRequest(MissingShareRequest {
+                dkg_id: 1,
+                signer_id: 1,
+                from_party: 0,
+                to_party: 0,
+                phase: DkgPhase::Public,
+                attempt: 0,
+            }),
+            MessageTypes::ReshareBegin(ReshareBegin {
+                dkg_id: 1,
+                reshare_id: 1,
+            }),
+            MessageTypes::ReshareShares(ReshareShares {
+                dkg_id: 1,
+                reshare_id: 1,
+                signer_id: 1,
+                commitments: vec![],
+                shares: vec![],
+                encrypted_shares: vec![],
+            }),
+            MessageTypes::ReshareEnd(ReshareEnd {
+                dkg_id: 1,
+                reshare_id: 1,
+                signer_id: 1,
+                status: DkgStatus::Success,
+                digest: [0; 32],
+            }),
+            MessageTypes::ReshareCommit(ReshareCommit {
+                dkg_id: 1,
+                reshare_id: 1,
+                digest: [0; 32],
+            }),
+            MessageTypes::RosterChange(RosterChange {
+                dkg_id: 1,
+                change_id: 1,
+                key_owners: vec![],
+                repaired_key_ids: vec![],
+                helper_key_ids: vec![],
+                group_commitment: vec![],
+            }),
+            MessageTypes::RepairShares(RepairShares {
+                dkg_id: 1,
+                change_id: 1,
+                signer_id: 1,
+                pieces: vec![],
+                encrypted_pieces: vec![],
+            }),
+            MessageTypes::RepairedShares(RepairedShares {
+                dkg_id: 1,
+                change_id: 1,
+                signer_id: 1,
+                sums: vec![],
+                encrypted_sums: vec![],
+            }),
+            MessageTypes::RosterChangeEnd(RosterChangeEnd {
+                dkg_id: 1,
+                change_id: 1,
+                signer_id: 1,
+                status: DkgStatus::Success,
+            }),
+            MessageTypes::DkgGroupKey(DkgGroupKey {
+                dkg_id: 1,
+                signer_id: 1,
+                group_key: Some(Point::default()),
+            }),
+        ]
+    }
+
+    #[test]
+    fn every_message_type_registers_its_own_tag() {
+        let messages = one_of_each();
+        let tags: Vec<_> = messages.iter().map(MessageTypes::tag).collect();
+        assert_eq!(tags, MESSAGE_TAGS);
+        // and so every type has a request hash of its own, even with the same fields
+        let hashes: std::collections::BTreeSet<_> =
+            messages.iter().map(MessageTypes::request_hash).collect();
+        assert_eq!(hashes.len(), messages.len());
+    }
+
+    #[test]
+    fn dkg_private_shares_routing() {
+        // signer 1 holds key_ids 0 and 1, signer 2 holds key_id 2
+        let mut signing_round = SigningRound::new(2, 3, 2, vec![2]);
+        let shares: HashMap<usize, Scalar> =
+            (0..3).map(|i| (i, Scalar::from(i as u32 + 1))).collect();
+        let private_shares = DkgPrivateShares {
+            dkg_id: 0,
+            signer_id: 1,
+            private_shares: vec![(0, shares.clone()), (1, shares)],
+            encrypted_shares: vec![],
+            commitment_digest: commitment_digest(
+                signing_round.dkg_id,
+                signing_round.commitments.iter(),
+            ),
+        };
+        signing_round.dkg_private_shares(private_shares).unwrap();
+
+        assert_eq!(2, signing_round.shares.len());
+        for key_shares in signing_round.shares.values() {
+            assert_eq!(1, key_shares.len());
+            assert_eq!(Some(&Scalar::from(3u32)), key_shares.get(&2));
+        }
+    }
+
+    /// Run a full DKG across signers that own the given key_ids, feeding every
+    /// outbound message to every signer like the relay does.
+    fn run_dkg(key_ids: &[Vec<usize>], threshold: usize) -> Vec<SigningRound> {
+        let (rounds, ends) = run_dkg_with(key_ids, threshold, &[], |_| {});
+        for msg in ends {
+            match msg {
+                MessageTypes::DkgEnd(end) => {
+                    assert!(matches!(end.status, DkgStatus::Success), "{:?}", end)
+                }
+                _ => panic!("expected DkgEnd"),
+            }
+        }
+        rounds
+    }
+
+    /// Like [`run_dkg`], excluding the signers owning `excluded_key_ids` and letting `tamper`
+    /// alter the private shares before they are delivered.
+    /// Returns the rounds, and the DkgEnd messages followed by any EquivocationReport and
+    /// DkgBlame.
+    /// Signers failing before they confirm the group key end first.
+    fn run_dkg_with(
+        key_ids: &[Vec<usize>],
+        threshold: usize,
+        excluded_key_ids: &[u32],
+        tamper: impl Fn(&mut DkgPrivateShares),
+    ) -> (Vec<SigningRound>, Vec<MessageTypes>) {
+        let total = key_ids.iter().map(|ids| ids.len()).sum();
+        let participants = key_ids
+            .iter()
+            .filter(|ids| {
+                !ids.iter()
+                    .any(|id| excluded_key_ids.contains(&(*id as u32)))
+            })
+            .count();
+        let rounds: Vec<SigningRound> = key_ids
+            .iter()
+            .enumerate()
+            .map(|(i, ids)| SigningRound::new(threshold, total, i as u32 + 1, ids.clone()))
+            .collect();
+        run_dkg_rounds(rounds, participants, excluded_key_ids, tamper)
+    }
+
+    /// Like [`run_dkg_with`], over `rounds` built by the caller, of which `participants` are
+    /// not excluded
+    fn run_dkg_rounds(
+        mut rounds: Vec<SigningRound>,
+        participants: usize,
+        excluded_key_ids: &[u32],
+        tamper: impl Fn(&mut DkgPrivateShares),
+    ) -> (Vec<SigningRound>, Vec<MessageTypes>) {
+        let broadcast = |rounds: &mut Vec<SigningRound>, msgs: Vec<MessageTypes>| {
+            let mut out = vec![];
+            for msg in msgs {
+                for round in rounds.iter_mut() {
+                    out.extend(round.process(msg.clone()).unwrap());
+                }
+            }
+            out
+        };
+
+        let public_shares = broadcast(
+            &mut rounds,
+            vec![MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 1,
+                excluded_key_ids: excluded_key_ids.to_vec(),
+            })],
+        );
+        let public_ends = broadcast(&mut rounds, public_shares);
+        assert_eq!(participants, public_ends.len());
+        assert!(public_ends
+            .iter()
+            .all(|msg| matches!(msg, MessageTypes::DkgPublicEnd(_))));
+
+        let mut private_shares = broadcast(
+            &mut rounds,
+            vec![MessageTypes::DkgPrivateBegin(DkgBegin {
+                dkg_id: 1,
+                excluded_key_ids: excluded_key_ids.to_vec(),
+            })],
+        );
+        // one DkgPrivateShares message per signer, carrying one entry per owned key_id
+        assert_eq!(participants, private_shares.len());
+        for msg in &mut private_shares {
+            match msg {
+                MessageTypes::DkgPrivateShares(shares) => {
+                    let round = &rounds[shares.signer_id as usize - 1];
+                    if round.share_cipher.is_some() {
+                        // sealed to every participant, ourselves included
+                        assert!(shares.private_shares.is_empty());
+                        assert_eq!(participants, shares.encrypted_shares.len());
+                    } else {
+                        assert_eq!(round.key_ids().len(), shares.private_shares.len());
+                    }
+                    tamper(shares);
+                }
+                _ => panic!("expected DkgPrivateShares"),
+            }
+        }
+
+        let (share_digests, rest): (Vec<_>, Vec<_>) = broadcast(&mut rounds, private_shares)
+            .into_iter()
+            .partition(|msg| matches!(msg, MessageTypes::DkgShareDigests(_)));
+        let (blames, rest): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|msg| matches!(msg, MessageTypes::DkgBlame(_)));
+        let (group_keys, mut ends): (Vec<_>, Vec<_>) = rest
+            .into_iter()
+            .partition(|msg| matches!(msg, MessageTypes::DkgGroupKey(_)));
+        assert_eq!(participants, share_digests.len());
+        assert_eq!(participants, group_keys.len());
+        ends.extend(broadcast(&mut rounds, group_keys));
+        assert_eq!(participants, ends.len());
+        ends.extend(broadcast(&mut rounds, share_digests));
+        ends.extend(blames);
+        (rounds, ends)
+    }
+
+    #[test]
+    fn private_shares_are_sealed_to_their_recipients() {
+        let key_ids = [vec![0, 1], vec![2], vec![3]];
+        let keys: Vec<Scalar> = key_ids.iter().map(|_| Scalar::random(&mut OsRng)).collect();
+        let peers: BTreeMap<u32, Point> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i as u32 + 1, Point::from(*key)))
+            .collect();
+        let cipher = |i: usize| ShareCipher::new(i as u32 + 1, keys[i], peers.clone());
+        let rounds = key_ids
+            .iter()
+            .enumerate()
+            .map(|(i, ids)| {
+                SigningRound::new(2, 4, i as u32 + 1, ids.clone()).with_share_cipher(cipher(i))
+            })
+            .collect();
+        let (rounds, ends) = run_dkg_rounds(rounds, 3, &[], |_| {});
+        assert!(ends.iter().all(|msg| matches!(
+            msg,
+            MessageTypes::DkgEnd(DkgEnd {
+                status: DkgStatus::Success,
+                ..
+            })
+        )));
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        assert!(rounds
+            .iter()
+            .all(|round| round.signer.frost_signer.parties[0].group_key == group_key));
+
+        // shares sent in the clear are refused
+        let mut round = SigningRound::new(2, 4, 2, vec![2]).with_share_cipher(cipher(1));
+        let shares = DkgPrivateShares {
+            dkg_id: round.dkg_id,
+            signer_id: 1,
+            private_shares: vec![(0, HashMap::from([(2, Scalar::from(5))]))],
+            encrypted_shares: vec![],
+            commitment_digest: commitment_digest(round.dkg_id, round.commitments.iter()),
+        };
+        assert!(round.dkg_private_shares(shares).is_err());
+        assert!(round.shares.is_empty());
+    }
+
+    #[test]
+    fn blames_carry_evidence_of_bad_shares() {
+        let key_ids = [vec![0, 1], vec![2], vec![3]];
+        let keys: Vec<Scalar> = key_ids.iter().map(|_| Scalar::random(&mut OsRng)).collect();
+        let peers: BTreeMap<u32, Point> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i as u32 + 1, Point::from(*key)))
+            .collect();
+        let cipher = |i: usize| ShareCipher::new(i as u32 + 1, keys[i], peers.clone());
+        let rounds = key_ids
+            .iter()
+            .enumerate()
+            .map(|(i, ids)| {
+                SigningRound::new(2, 4, i as u32 + 1, ids.clone()).with_share_cipher(cipher(i))
+            })
+            .collect();
+        // signer 2 seals a corrupt share from key_id 2 to key_id 0
+        let (rounds, ends) = run_dkg_rounds(rounds, 3, &[], |shares| {
+            if shares.signer_id != 2 {
+                return;
+            }
+            let (dkg_id, digest) = (shares.dkg_id, shares.commitment_digest);
+            let sealed = shares
+                .encrypted_shares
+                .iter_mut()
+                .find(|sealed| sealed.signer_id == 1)
+                .unwrap();
+            let mut plain = cipher(0)
+                .open(2, Round::Dkg(dkg_id), &digest, sealed)
+                .unwrap();
+            *plain[0].1.get_mut(&0).unwrap() += Scalar::from(1);
+            *sealed = cipher(1)
+                .seal(1, Round::Dkg(dkg_id), &digest, &plain)
+                .unwrap();
+        });
+        let blames: Vec<DkgBlame> = ends
+            .iter()
+            .filter_map(|msg| match msg {
+                MessageTypes::DkgBlame(blame) => Some(blame.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(blames.len(), 1);
+        let blame = &blames[0];
+        assert_eq!((blame.signer_id, blame.accused_id), (1, 2));
+        assert!(
+            dkg_statuses(ends).contains(&(1, DkgStatus::Failure(DkgFailure::BadShares(vec![2]))))
+        );
+
+        let commitments = &rounds[2].commitments;
+        assert!(blame.proves_bad_shares(&[0, 1], |key_id| commitments.get(&key_id)));
+        // the shares signer 2 dealt signer 3 were good, and a key not revealed by the
+        // recipient opens nothing
+        let mut false_blame = blame.clone();
+        false_blame.signer_id = 3;
+        false_blame.share_key = cipher(2).share_key(2, blame.dkg_id);
+        assert!(!false_blame.proves_bad_shares(&[3], |key_id| commitments.get(&key_id)));
+        false_blame.signer_id = 1;
+        assert!(!false_blame.proves_bad_shares(&[0, 1], |key_id| commitments.get(&key_id)));
+    }
+
+    /// Signer ids and statuses of the DkgEnd messages among `msgs`, by signer id
+    fn dkg_statuses(msgs: Vec<MessageTypes>) -> Vec<(u32, DkgStatus)> {
+        let mut statuses: Vec<_> = msgs
+            .into_iter()
+            .filter_map(|msg| match msg {
+                MessageTypes::DkgEnd(end) => Some((end.signer_id, end.status)),
+                _ => None,
+            })
+            .collect();
+        statuses.sort_by_key(|(signer_id, _)| *signer_id);
+        statuses
+    }
+
+    #[test]
+    fn dkg_without_excluded_signers() {
+        let (rounds, ends) = run_dkg_with(&[vec![0, 1], vec![2], vec![3]], 2, &[2], |_| {});
+        assert!(ends.iter().all(|msg| matches!(
+            msg,
+            MessageTypes::DkgEnd(DkgEnd {
+                status: DkgStatus::Success,
+                ..
+            })
+        )));
+        // the excluded signer sat the round out and received no shares
+        assert_eq!(States::Idle, rounds[1].state);
+        assert!(rounds[1].shares.values().all(|shares| shares.is_empty()));
+
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        assert_eq!(
+            group_key,
+            rounds[2].signer.frost_signer.parties[0].group_key
+        );
+        let honest_key: Point = [0u32, 1, 3]
+            .iter()
+            .map(|key_id| rounds[0].commitments[key_id].A[0])
+            .fold(Point::default(), |sum, a| sum + a);
+        assert_eq!(honest_key, group_key);
+    }
+
+    #[test]
+    fn dkg_does_not_wait_for_our_own_messages() {
+        // a lone signer never hears back from anyone
+        let mut signing_round = SigningRound::new(1, 1, 1, vec![0]);
+        let begin = DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids: vec![],
+        };
+        let out = signing_round
+            .process(MessageTypes::DkgBegin(begin.clone()))
+            .unwrap();
+        assert!(matches!(
+            out[..],
+            [
+                MessageTypes::DkgPublicShare(_),
+                MessageTypes::DkgPublicEnd(_)
+            ]
+        ));
+        let out = signing_round
+            .process(MessageTypes::DkgPrivateBegin(begin))
+            .unwrap();
+        assert!(matches!(
+            out[..],
+            [
+                MessageTypes::DkgPrivateShares(_),
+                MessageTypes::DkgShareDigests(_),
+                MessageTypes::DkgGroupKey(_),
+                MessageTypes::DkgEnd(DkgEnd {
+                    status: DkgStatus::Success,
+                    ..
+                })
+            ]
+        ));
+        assert_eq!(States::Idle, signing_round.state);
+    }
+
+    #[test]
+    fn stalled_dkg_asks_for_the_lost_pieces_only() {
+        let mut rounds: Vec<SigningRound> = [vec![0, 1], vec![2], vec![3]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, ids)| SigningRound::new(2, 4, i as u32 + 1, ids))
+            .collect();
+        let deliver = |rounds: &mut Vec<SigningRound>, to: &[usize], msgs: &[MessageTypes]| {
+            let mut out = vec![];
+            for msg in msgs {
+                for i in to {
+                    out.extend(rounds[*i].process(msg.clone()).unwrap());
+                }
+            }
+            out
+        };
+        let begin = DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids: vec![],
+        };
+        let public_shares = deliver(
+            &mut rounds,
+            &[0, 1, 2],
+            &[MessageTypes::DkgBegin(begin.clone())],
+        );
+        // signer 3 never gets the commitment of key 2
+        let lost: Vec<_> = public_shares
+            .iter()
+            .filter(|msg| !matches!(msg, MessageTypes::DkgPublicShare(share) if share.key_id == 2))
+            .cloned()
+            .collect();
+        deliver(&mut rounds, &[0, 1], &public_shares);
+        deliver(&mut rounds, &[2], &lost);
+        assert_eq!(States::DkgPublicGather, rounds[2].state);
+
+        let requests = rounds[2].missing_share_requests();
+        assert!(matches!(
+            &requests[..],
+            [MessageTypes::MissingShareRequest(MissingShareRequest {
+                from_party: 2,
+                to_party: 3,
+                phase: DkgPhase::Public,
+                attempt: 0,
+                ..
+            })]
+        ));
+        let resent = deliver(&mut rounds, &[0, 1], &requests);
+        assert!(matches!(
+            &resent[..],
+            [MessageTypes::DkgPublicShare(share)] if share.key_id == 2 && share.signer_id == 2
+        ));
+        // a request is answered once per attempt
+        assert!(deliver(&mut rounds, &[1], &requests).is_empty());
+        deliver(&mut rounds, &[2], &resent);
+        assert_eq!(States::DkgPrivateDistribute, rounds[2].state);
+
+        let private_shares = deliver(
+            &mut rounds,
+            &[0, 1, 2],
+            &[MessageTypes::DkgPrivateBegin(begin)],
+        );
+        // signer 1 never gets the shares of signer 3
+        let lost: Vec<_> = private_shares
+            .iter()
+            .filter(|msg| !matches!(msg, MessageTypes::DkgPrivateShares(shares) if shares.signer_id == 3))
+            .cloned()
+            .collect();
+        let group_keys = deliver(&mut rounds, &[1, 2], &private_shares);
+        deliver(&mut rounds, &[0], &lost);
+        assert_eq!(States::DkgPrivateGather, rounds[0].state);
+
+        let requests = rounds[0].missing_share_requests();
+        assert!(matches!(
+            &requests[..],
+            [MessageTypes::MissingShareRequest(MissingShareRequest {
+                from_party: 3,
+                to_party: 0,
+                phase: DkgPhase::Private,
+                attempt: 0,
+                ..
+            })]
+        ));
+        // only the owner of key 3 answers, with the message it sent the first time
+        let resent = deliver(&mut rounds, &[1, 2], &requests);
+        let original = private_shares
+            .iter()
+            .find_map(|msg| match msg {
+                MessageTypes::DkgPrivateShares(shares) if shares.signer_id == 3 => {
+                    Some(shares.digest())
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert!(matches!(
+            &resent[..],
+            [MessageTypes::DkgPrivateShares(shares)] if shares.digest() == original
+        ));
+        // the others computed their group keys and wait for signer 1's
+        deliver(&mut rounds, &[0, 1, 2], &group_keys);
+        let ends = deliver(&mut rounds, &[0], &resent);
+        deliver(&mut rounds, &[1, 2], &ends);
+        assert!(rounds.iter().all(|round| round.state == States::Idle));
+        assert!(ends.iter().any(|msg| matches!(
+            msg,
+            MessageTypes::DkgEnd(DkgEnd {
+                status: DkgStatus::Success,
+                ..
+            })
+        )));
+        // nothing is missing once DKG ended
+        assert!(rounds[0].missing_share_requests().is_empty());
+    }
+
+    #[test]
+    fn stalled_dkg_times_out_and_makes_way_for_the_next_round() {
+        let timeout = Duration::from_secs(5);
+        let mut signing_round =
+            SigningRound::new(2, 3, 1, vec![0]).with_dkg_timeouts(DkgTimeouts {
+                public: timeout,
+                private: timeout,
+            });
+        let begin = |dkg_id| {
+            MessageTypes::DkgBegin(DkgBegin {
+                dkg_id,
+                excluded_key_ids: vec![],
+            })
+        };
+        signing_round.process(begin(1)).unwrap();
+        assert_eq!(signing_round.state, States::DkgPublicGather);
+        let started = signing_round.phase_started.unwrap();
+        assert!(signing_round
+            .expire_dkg(started + timeout - Duration::from_secs(1))
+            .is_empty());
+
+        let out = signing_round.expire_dkg(started + timeout);
+        match &out[..] {
+            [MessageTypes::DkgGroupKey(DkgGroupKey {
+                group_key: None, ..
+            }), MessageTypes::DkgEnd(end)] => assert_eq!(
+                end.status,
+                DkgStatus::Failure(DkgFailure::MissingCommitments(vec![1, 2]))
+            ),
+            out => panic!("expected one DkgEnd, got {out:?}"),
+        }
+        assert_eq!(signing_round.state, States::Idle);
+        assert!(signing_round.expire_dkg(started + timeout).is_empty());
+
+        signing_round.process(begin(2)).unwrap();
+        assert_eq!(signing_round.state, States::DkgPublicGather);
+        assert_eq!(signing_round.dkg_id, 2);
+    }
+
+    #[test]
+    fn missing_share_requests_are_capped() {
+        let mut signing_round = SigningRound::new(2, 2, 1, vec![0]);
+        signing_round
+            .process(MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 1,
+                excluded_key_ids: vec![],
+            }))
+            .unwrap();
+        for _ in 0..MAX_SHARE_REQUEST_ATTEMPTS {
+            assert_eq!(signing_round.missing_share_requests().len(), 1);
+        }
+        assert!(signing_round.missing_share_requests().is_empty());
+
+        // answers are capped by attempt, and only go to the owner of `to_party`
+        let mut request = MissingShareRequest {
+            dkg_id: 1,
+            signer_id: 2,
+            from_party: 0,
+            to_party: 1,
+            phase: DkgPhase::Public,
+            attempt: MAX_SHARE_REQUEST_ATTEMPTS,
+        };
+        let answered = |round: &mut SigningRound, request: &MissingShareRequest| {
+            !round.missing_share_request(request.clone()).is_empty()
+        };
+        assert!(!answered(&mut signing_round, &request));
+        request.attempt = 0;
+        request.to_party = 0;
+        assert!(!answered(&mut signing_round, &request));
+        request.to_party = 1;
+        assert!(answered(&mut signing_round, &request));
+        assert!(!answered(&mut signing_round, &request));
+        // no private shares were sent yet
+        request.phase = DkgPhase::Private;
+        assert!(!answered(&mut signing_round, &request));
+    }
+
+    #[test]
+    fn dkg_end_names_senders_of_bad_shares() {
+        // signer 2 sends a corrupt share from key_id 2 to key_id 0
+        let (_, ends) = run_dkg_with(&[vec![0, 1], vec![2]], 2, &[], |shares| {
+            if shares.signer_id == 2 {
+                let (_, key_shares) = &mut shares.private_shares[0];
+                let share = key_shares.get_mut(&0).unwrap();
+                *share += Scalar::from(1);
+            }
+        });
+        assert_eq!(
+            dkg_statuses(ends),
+            vec![
+                (1, DkgStatus::Failure(DkgFailure::BadShares(vec![2]))),
+                (2, DkgStatus::Success),
+            ]
+        );
+    }
+
+    #[test]
+    fn mismatched_shares_names_the_sources_of_bad_shares() {
+        let mut rng = OsRng::default();
+        let parties: Vec<v1::Party> = (0..4)
+            .map(|id| v1::Party::new(id, 4, 3, &mut rng))
+            .collect();
+        let commitments: BTreeMap<u32, PolyCommitment> = parties
+            .iter()
+            .map(|party| (party.id as u32, party.get_poly_commitment(&mut rng)))
+            .collect();
+        let mut received = party_shares(&parties, &BTreeSet::from([3]));
+        assert!(received.iter().all(|(_, shares)| !shares.contains_key(&3)));
+        assert!(mismatched_shares(&commitments, &received).is_empty());
+
+        *received[1].1.get_mut(&2).unwrap() += Scalar::from(1);
+        *received[3].1.get_mut(&0).unwrap() += Scalar::from(1);
+        assert_eq!(
+            mismatched_shares(&commitments, &received),
+            BTreeSet::from([1, 3])
+        );
+        // sources without a commitment are not checked
+        let without_3: BTreeMap<u32, PolyCommitment> = commitments
+            .into_iter()
+            .filter(|(key_id, _)| *key_id != 3)
+            .collect();
+        assert_eq!(
+            mismatched_shares(&without_3, &received),
+            BTreeSet::from([1])
+        );
+    }
+
+    #[test]
+    fn bad_shares_end_dkg_as_soon_as_they_arrive() {
+        // signer 1 sends a corrupt share from key_id 0 to key_id 1, and is the first to send
+        let (rounds, ends) = run_dkg_with(&[vec![0], vec![1], vec![2]], 2, &[], |shares| {
+            if shares.signer_id == 1 {
+                let (_, key_shares) = &mut shares.private_shares[0];
+                *key_shares.get_mut(&1).unwrap() += Scalar::from(1);
+            }
+        });
+        // signer 2 ends before the shares of signers 2 and 3 are even processed
+        match &ends[0] {
+            MessageTypes::DkgEnd(end) => {
+                assert_eq!(end.signer_id, 2);
+                assert_eq!(
+                    end.status,
+                    DkgStatus::Failure(DkgFailure::BadShares(vec![0]))
+                );
+            }
+            msg => panic!("expected DkgEnd, got {msg:?}"),
+        }
+        assert_eq!(
+            dkg_statuses(ends),
+            vec![
+                (1, DkgStatus::Success),
+                (2, DkgStatus::Failure(DkgFailure::BadShares(vec![0]))),
+                (3, DkgStatus::Success),
+            ]
+        );
+        assert_eq!(rounds[1].state, States::Idle);
+    }
+
+    #[test]
+    fn dkg_end_names_senders_built_on_other_commitments() {
+        // signer 2 was shown a different set of commitments than signer 1
+        let (_, ends) = run_dkg_with(&[vec![0, 1], vec![2]], 2, &[], |shares| {
+            if shares.signer_id == 2 {
+                shares.commitment_digest = [0; 32];
+            }
+        });
+        assert_eq!(
+            dkg_statuses(ends),
+            vec![
+                (
+                    1,
+                    DkgStatus::Failure(DkgFailure::CommitmentMismatch(vec![2]))
+                ),
+                (2, DkgStatus::Success),
+            ]
+        );
+    }
+
+    #[test]
+    fn dkg_only_succeeds_once_every_signer_computed_the_same_group_key() {
+        let mut rounds: Vec<SigningRound> = [vec![0, 1], vec![2]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, ids)| SigningRound::new(2, 3, i as u32 + 1, ids))
+            .collect();
+        let broadcast = |rounds: &mut Vec<SigningRound>, msgs: &[MessageTypes]| {
+            let mut out = vec![];
+            for msg in msgs {
+                for round in rounds.iter_mut() {
+                    out.extend(round.process(msg.clone()).unwrap());
+                }
+            }
+            out
+        };
+        let begin = DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids: vec![],
+        };
+        let public_shares = broadcast(&mut rounds, &[MessageTypes::DkgBegin(begin.clone())]);
+        broadcast(&mut rounds, &public_shares);
+        let private_shares = broadcast(&mut rounds, &[MessageTypes::DkgPrivateBegin(begin)]);
+        let out = broadcast(&mut rounds, &private_shares);
+        // no signer ends DKG before it heard the group keys of the others
+        assert!(!out.iter().any(|msg| matches!(msg, MessageTypes::DkgEnd(_))));
+        assert!(rounds.iter().all(|round| round.state == States::DkgConfirm));
+
+        // signer 1 is told signer 2 got another key
+        let group_keys: Vec<MessageTypes> = out
+            .into_iter()
+            .filter_map(|msg| match msg {
+                MessageTypes::DkgGroupKey(mut group_key) => {
+                    if group_key.signer_id == 2 {
+                        group_key.group_key = Some(Point::from(Scalar::from(7)));
+                    }
+                    Some(MessageTypes::DkgGroupKey(group_key))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(group_keys.len(), 2);
+        let ends = broadcast(&mut rounds, &group_keys);
+        assert_eq!(
+            dkg_statuses(ends),
+            vec![
+                (1, DkgStatus::Failure(DkgFailure::GroupKeyMismatch(vec![2]))),
+                (2, DkgStatus::Success),
+            ]
+        );
+        assert_eq!(rounds[0].generations[&1].group_key, None);
+        assert!(rounds.iter().all(|round| round.state == States::Idle));
+    }
+
+    #[test]
+    fn signers_report_peers_sending_different_shares_to_different_signers() {
+        // signer 2 kept the shares it sent, signer 1 got altered ones
+        let (_, ends) = run_dkg_with(&[vec![0, 1], vec![2]], 2, &[], |shares| {
+            if shares.signer_id == 2 {
+                shares.private_shares[0].1.insert(0, Scalar::from(1));
+            }
+        });
+        let reports: Vec<_> = ends
+            .into_iter()
+            .filter_map(|msg| match msg {
+                MessageTypes::EquivocationReport(report) => Some(report),
+                _ => None,
+            })
+            .collect();
+        // signer 2 hears its own altered shares back only after sending its digests
+        assert_eq!(1, reports.len());
+        for report in &reports {
+            assert_eq!(1, report.dkg_id);
+            assert_eq!(1, report.signer_id);
+            assert_eq!(2, report.equivocator_id);
+            assert_eq!(
+                vec![1, 2],
+                report
+                    .digests
+                    .iter()
+                    .map(|(receiver_id, _)| *receiver_id)
+                    .collect::<Vec<_>>()
+            );
+            assert_ne!(report.digests[0].1, report.digests[1].1);
+        }
+    }
+
+    #[test]
+    fn equivocators_are_reported_once() {
+        let mut signing_round = SigningRound::new(1, 3, 1, vec![0]);
+        signing_round.share_digests.insert(2, [2; 32]);
+        signing_round.share_digests.insert(3, [3; 32]);
+        let digests = |digests: Vec<(u32, [u8; 32])>| {
+            MessageTypes::DkgShareDigests(DkgShareDigests {
+                dkg_id: 1,
+                signer_id: 2,
+                digests,
+            })
+        };
+        assert!(signing_round
+            .process(digests(vec![(2, [2; 32]), (3, [3; 32])]))
+            .unwrap()
+            .is_empty());
+
+        let out = signing_round
+            .process(digests(vec![(2, [2; 32]), (3, [4; 32])]))
+            .unwrap();
+        match &out[..] {
+            [MessageTypes::EquivocationReport(report)] => assert_eq!(
+                *report,
+                EquivocationReport {
+                    dkg_id: 1,
+                    signer_id: 1,
+                    equivocator_id: 3,
+                    digests: vec![(1, [3; 32]), (2, [4; 32])],
+                }
+            ),
+            _ => panic!("expected a single EquivocationReport"),
+        }
+        assert!(signing_round
+            .process(digests(vec![(2, [2; 32]), (3, [4; 32])]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn commitments_are_dropped_once_the_commitment_phase_closes() {
+        let mut rng = OsRng::default();
+        let mut signing_round = SigningRound::new(1, 2, 1, vec![0]);
+        let public_share = |key_id: u32, rng: &mut OsRng| {
+            MessageTypes::DkgPublicShare(DkgPublicShare {
+                dkg_id: 1,
+                signer_id: key_id + 1,
+                key_id,
+                public_share: PolyCommitment {
+                    id: ID::new(&Scalar::new(), &Scalar::new(), rng),
+                    A: vec![],
+                },
+                dkg_public_id: 0,
+            })
+        };
+        signing_round.process(public_share(0, &mut rng)).unwrap();
+        let digest = signing_round.close_commitment_phase();
+        assert_eq!(
+            digest,
+            commitment_digest(1, signing_round.commitments.iter())
+        );
+
+        signing_round.process(public_share(1, &mut rng)).unwrap();
+        assert_eq!(vec![0], signing_round.snapshot().commitments_received);
+        assert_eq!(Some(digest), signing_round.commitment_digest);
+    }
+
+    fn assert_same_group_key(rounds: &[SigningRound]) {
+        let group_keys: Vec<_> = rounds
+            .iter()
+            .flat_map(|round| round.signer.frost_signer.parties.iter())
+            .map(|party| party.group_key)
+            .collect();
+        assert!(group_keys.windows(2).all(|keys| keys[0] == keys[1]));
+    }
+
+    #[test]
+    fn dkg_asymmetric_key_allocation() {
+        let rounds = run_dkg(&[vec![0, 1, 2], vec![3]], 3);
+        assert_same_group_key(&rounds);
+        for round in &rounds {
+            assert_eq!(4, round.shares.len());
+            assert!(round
+                .shares
+                .values()
+                .all(|shares| shares.len() == round.signer.frost_signer.parties.len()));
+        }
+    }
+
+    #[test]
+    fn signers_agree_on_the_group_key_view() {
+        let rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let views: Vec<GroupKeyView> = rounds
+            .iter()
+            .map(|round| round.group_key_view().unwrap())
+            .collect();
+        let roster = BTreeMap::from([(0, 1), (1, 1), (2, 2), (3, 2), (4, 3), (5, 3)]);
+        for (view, signer_id) in views.iter().zip(1..) {
+            assert_eq!(view.signer_id, signer_id);
+            assert_eq!(view.dkg_id, 1);
+            assert_eq!(view.group_key, views[0].group_key);
+            assert_eq!(view.roster, roster);
+            assert_eq!(view.roster_hash, hex::encode(roster_hash(&roster)));
+        }
+        assert_eq!(views[1].key_ids, vec![2, 3]);
+        assert_eq!(rounds[0].snapshot().group_key.as_ref(), Some(&views[0]));
+
+        // a signer which has not run DKG has no view
+        assert_eq!(
+            SigningRound::new(4, 6, 1, vec![0, 1]).group_key_view(),
+            None
+        );
+    }
+
+    #[test]
+    fn round_outcome_catch_up() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        assert!(rounds
+            .iter()
+            .all(|round| round.generations[&1].group_key == Some(group_key)));
+
+        // signer 3 restarts and has forgotten how round #1 ended
+        rounds[2] = SigningRound::new(4, 6, 3, vec![4, 5]);
+        let out = rounds[2]
+            .process(MessageTypes::NonceRequest(NonceRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                sign_nonce_id: 1,
+            }))
+            .unwrap();
+        let query = out
+            .into_iter()
+            .find(|msg| matches!(msg, MessageTypes::RoundOutcomeQuery(_)))
+            .expect("missing RoundOutcomeQuery");
+
+        let mut responses = vec![];
+        for round in rounds.iter_mut() {
+            responses.extend(round.process(query.clone()).unwrap());
+        }
+        // the querying signer does not answer itself
+        assert_eq!(2, responses.len());
+
+        // a single signer's two keys are not enough to meet the threshold of 4
+        rounds[2].process(responses[0].clone()).unwrap();
+        assert!(!rounds[2].generations.contains_key(&1));
+
+        rounds[2].process(responses[1].clone()).unwrap();
+        assert_eq!(
+            Some(&RoundOutcome {
+                status: DkgStatus::Success,
+                group_key: Some(group_key),
+            }),
+            rounds[2].generations.get(&1)
+        );
+        assert!(rounds[2].outcome_attestations.is_empty());
+    }
+
+    #[test]
+    fn sign_share_request_rotates_nonce() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let nonces: Vec<(u32, PublicNonce)> = rounds
+            .iter_mut()
+            .flat_map(|round| {
+                round
+                    .process(MessageTypes::NonceRequest(NonceRequest {
+                        dkg_id: 1,
+                        sign_id: 1,
+                        correlation_id: 1,
+                        sign_nonce_id: 1,
+                    }))
+                    .unwrap()
+            })
+            .filter_map(|msg| match msg {
+                MessageTypes::NonceResponse(response) => Some((response.key_id, response.nonce)),
+                _ => None,
+            })
+            .collect();
+
+        let spent_nonce = rounds[0].signer.frost_signer.parties[0].save().nonce;
+        let out = rounds[0]
+            .process(MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                nonces,
+                message: vec![7; 32],
+                key_path: None,
+            }))
+            .unwrap();
+        assert!(matches!(out[..], [MessageTypes::SignShareResponse(_)]));
+        assert_ne!(
+            spent_nonce,
+            rounds[0].signer.frost_signer.parties[0].save().nonce
+        );
+    }
+
+    #[test]
+    fn stored_nonces_sign_after_a_restart() {
+        let path = std::env::temp_dir().join(format!("round-nonces-{}.json", std::process::id()));
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let store = NonceStore::open(&path, "network key", DEFAULT_NONCE_TTL).unwrap();
+        rounds[0].nonce_store = store.clone();
+        let nonces: Vec<(u32, PublicNonce)> = rounds
+            .iter_mut()
+            .flat_map(|round| {
+                round
+                    .process(MessageTypes::NonceRequest(NonceRequest {
+                        dkg_id: 1,
+                        sign_id: 1,
+                        correlation_id: 1,
+                        sign_nonce_id: 1,
+                    }))
+                    .unwrap()
+            })
+            .filter_map(|msg| match msg {
+                MessageTypes::NonceResponse(response) => Some((response.key_id, response.nonce)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(store.len(), 2);
+        let signer_ids: Vec<usize> = nonces.iter().map(|(id, _)| *id as usize).collect();
+        let public_nonces: Vec<PublicNonce> = nonces.iter().map(|(_, n)| n.clone()).collect();
+        let party = &mut rounds[0].signer.frost_signer.parties[0];
+        let expected = party.sign(&[7; 32], &signer_ids, &public_nonces);
+        // a restart leaves the party with a nonce it never handed out, and no sessions
+        party.gen_nonce(&mut OsRng);
+        rounds[0].sessions.clear();
+
+        let out = rounds[0]
+            .process(MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                nonces,
+                message: vec![7; 32],
+                key_path: None,
+            }))
+            .unwrap();
+        match &out[..] {
+            [MessageTypes::SignShareResponse(response)] => {
+                assert_eq!(response.signature_share, expected)
+            }
+            out => panic!("unexpected {out:?}"),
+        }
+        // spent, only the nonce of key 1 is left
+        assert_eq!(store.len(), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn nonces_sign_once_whichever_path_they_come_through() {
+        let path = std::env::temp_dir().join(format!("spent-nonces-{}.json", std::process::id()));
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let store = NonceStore::open(&path, "network key", DEFAULT_NONCE_TTL).unwrap();
+        rounds[0].nonce_store = store.clone();
+        let rejected = |out: Vec<MessageTypes>, code: RejectionCode| match &out[..] {
+            [MessageTypes::Rejection(rejection)] => assert_eq!(code, rejection.reason_code),
+            out => panic!("expected a Rejection, got {out:?}"),
+        };
+
+        let nonces: Vec<(u32, PublicNonce)> = rounds
+            .iter_mut()
+            .flat_map(|round| {
+                round
+                    .process(MessageTypes::NonceRequest(NonceRequest {
+                        dkg_id: 1,
+                        sign_id: 1,
+                        correlation_id: 1,
+                        sign_nonce_id: 1,
+                    }))
+                    .unwrap()
+            })
+            .filter_map(|msg| match msg {
+                MessageTypes::NonceResponse(response) => Some((response.key_id, response.nonce)),
+                _ => None,
+            })
+            .collect();
+        let request = |correlation_id| {
+            MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id,
+                signer_id: 1,
+                key_id: 0,
+                nonces: nonces.clone(),
+                message: vec![7; 32],
+                key_path: None,
+            })
+        };
+        // the store does not give the nonce of one session to another
+        rejected(
+            rounds[0].process(request(2)).unwrap(),
+            RejectionCode::UnknownNonce,
+        );
+        assert!(matches!(
+            rounds[0].process(request(1)).unwrap()[..],
+            [MessageTypes::SignShareResponse(_)]
+        ));
+        rejected(
+            rounds[0].process(request(1)).unwrap(),
+            RejectionCode::UnknownNonce,
+        );
+        // nor does a restart bring a spent nonce back
+        rounds[0].sessions.clear();
+        rejected(
+            rounds[0].process(request(1)).unwrap(),
+            RejectionCode::UnknownNonce,
+        );
+
+        // the same for batches, signed before a restart and after one
+        for restart_first in [false, true] {
+            let messages = batch_messages(&mut rounds, &[[8; 32]]);
+            let request = MessageTypes::BatchSignRequest(BatchSignRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                signer_id: 1,
+                key_id: 0,
+                messages,
+                key_path: None,
+            });
+            if restart_first {
+                rounds[0].sessions.clear();
+            }
+            assert!(matches!(
+                rounds[0].process(request.clone()).unwrap()[..],
+                [MessageTypes::BatchSignResponse(_)]
+            ));
+            rejected(
+                rounds[0].process(request.clone()).unwrap(),
+                RejectionCode::InvalidBatch,
+            );
+            rounds[0].sessions.clear();
+            rejected(
+                rounds[0].process(request).unwrap(),
+                RejectionCode::InvalidBatch,
+            );
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn batches_sign_every_message_in_one_round() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        let messages = batch_messages(&mut rounds, &[[7; 32], [8; 32]]);
+        let request = |key_id: u32| BatchSignRequest {
             dkg_id: 1,
-            dkg_public_id: 1,
             sign_id: 1,
-            sign_nonce_id: 1,
-            threshold: signer.config.keys_threshold,
-            total: signer.config.total_keys,
-            signer: Signer {
-                frost_signer,
-                signer_id,
-            },
-            state: States::Idle,
-            commitments: BTreeMap::new(),
-            shares: HashMap::new(),
-            public_nonces: vec![],
+            signer_id: key_id / 2 + 1,
+            key_id,
+            messages: messages.clone(),
+            key_path: None,
+        };
+
+        let mut z = [Scalar::default(), Scalar::default()];
+        for key_id in 0..6 {
+            let round = &mut rounds[key_id as usize / 2];
+            match &round
+                .process(MessageTypes::BatchSignRequest(request(key_id)))
+                .unwrap()[..]
+            {
+                [MessageTypes::BatchSignResponse(response)] => {
+                    for (z, share) in z.iter_mut().zip(&response.signature_shares) {
+                        *z += share.z_i;
+                    }
+                }
+                out => panic!("expected a BatchSignResponse, got {out:?}"),
+            }
+        }
+        for (message, z) in messages.iter().zip(z) {
+            let ids: Vec<usize> = message.nonces.iter().map(|(id, _)| *id as usize).collect();
+            let nonces: Vec<PublicNonce> = message.nonces.iter().map(|(_, n)| n.clone()).collect();
+            let (_, aggregate_nonce) = compute::intermediate(&message.message, &ids, &nonces);
+            let signature = wtfrost::common::Signature {
+                R: aggregate_nonce,
+                z,
+            };
+            assert!(signature.verify(&group_key, &message.message));
+        }
+
+        // each nonce signs once
+        match &rounds[0]
+            .process(MessageTypes::BatchSignRequest(request(0)))
+            .unwrap()[..]
+        {
+            [MessageTypes::Rejection(rejection)] => {
+                assert_eq!(RejectionCode::InvalidBatch, rejection.reason_code);
+            }
+            out => panic!("expected a Rejection, got {out:?}"),
         }
     }
-}
 
-#[cfg(test)]
-mod test {
-    use hashbrown::HashMap;
-    use rand_core::{CryptoRng, OsRng, RngCore};
-    use wtfrost::{common::PolyCommitment, schnorr::ID, Scalar};
+    #[test]
+    fn batched_key_path_spends_verify_with_the_p2tr_output_key() {
+        use bitcoin::hashes::Hash;
+        use bitcoin::util::taproot::TapBranchHash;
+        use bitcoin::{Script, XOnlyPublicKey};
 
-    use crate::signing_round::{
-        DkgPrivateShares, DkgPublicShare, DkgStatus, MessageTypes, SigningRound,
-    };
-    use crate::state_machine::States;
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        let key_path = KeyPath {
+            merkle_root: Some([9; 32]),
+        };
+        let tweak = key_path.tweak(&group_key);
+        let messages = batch_messages(&mut rounds, &[[7; 32], [8; 32]]);
 
-    fn get_rng() -> impl RngCore + CryptoRng {
-        let rnd = OsRng::default();
-        //rand::rngs::StdRng::seed_from_u64(rnd.next_u64()) // todo: fix trait `rand_core::RngCore` is not implemented for `StdRng`
-        rnd
+        let mut z = [Scalar::default(), Scalar::default()];
+        for key_id in 0..6 {
+            let round = &mut rounds[key_id as usize / 2];
+            match &round
+                .process(MessageTypes::BatchSignRequest(BatchSignRequest {
+                    dkg_id: 1,
+                    sign_id: 1,
+                    signer_id: key_id / 2 + 1,
+                    key_id,
+                    messages: messages.clone(),
+                    key_path: Some(key_path),
+                }))
+                .unwrap()[..]
+            {
+                [MessageTypes::BatchSignResponse(response)] => {
+                    for (z, share) in z.iter_mut().zip(&response.signature_shares) {
+                        *z += share.z_i;
+                    }
+                }
+                out => panic!("expected a BatchSignResponse, got {out:?}"),
+            }
+        }
+
+        // the output a wallet of the group key pays to, as the bitcoin crate derives it
+        let secp = secp256k1::Secp256k1::new();
+        let internal_key =
+            XOnlyPublicKey::from_slice(&group_key.compress().as_bytes()[1..]).unwrap();
+        let script = Script::new_v1_p2tr(
+            &secp,
+            internal_key,
+            Some(TapBranchHash::from_inner([9; 32])),
+        );
+        let output_key = XOnlyPublicKey::from_slice(&script.as_bytes()[2..]).unwrap();
+        assert_eq!(output_key.serialize(), tweak.output_key_bytes());
+
+        for (message, z) in messages.iter().zip(z) {
+            let ids: Vec<usize> = message.nonces.iter().map(|(id, _)| *id as usize).collect();
+            let nonces: Vec<PublicNonce> = message.nonces.iter().map(|(_, n)| n.clone()).collect();
+            let (_, aggregate_nonce) = compute::intermediate(&message.message, &ids, &nonces);
+            let c = compute::challenge(&tweak.output_key, &aggregate_nonce, &message.message);
+            let signature = wtfrost::common::Signature {
+                R: aggregate_nonce,
+                z: z + tweak.tweak_term(&c),
+            };
+            let proof = SchnorrProof::new(&signature).unwrap();
+            secp.verify_schnorr(
+                &secp256k1::schnorr::Signature::from_slice(&proof.to_bytes()).unwrap(),
+                &secp256k1::Message::from_slice(&message.message).unwrap(),
+                &output_key,
+            )
+            .unwrap();
+        }
+    }
+
+    /// A message for each of `digests`, with nonces of a BatchNonceRequest of every key whose
+    /// aggregate R has an even y, assigned as the coordinator does, so that every signature of
+    /// the batch is a valid BIP-340 signature
+    fn batch_messages(rounds: &mut [SigningRound], digests: &[[u8; 32]]) -> Vec<BatchMessage> {
+        loop {
+            let responses: Vec<BatchNonceResponse> = rounds
+                .iter_mut()
+                .flat_map(|round| {
+                    round
+                        .process(MessageTypes::BatchNonceRequest(BatchNonceRequest {
+                            dkg_id: 1,
+                            sign_id: 1,
+                            sign_nonce_id: 1,
+                            count: 2 * digests.len() as u32,
+                        }))
+                        .unwrap()
+                })
+                .filter_map(|msg| match msg {
+                    MessageTypes::BatchNonceResponse(response) => Some(response),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(responses.len(), 6);
+            let ids: Vec<usize> = responses.iter().map(|r| r.key_id as usize).collect();
+            let at_index = |index: usize| -> Vec<(u32, PublicNonce)> {
+                responses
+                    .iter()
+                    .map(|response| (response.key_id, response.nonces[index].clone()))
+                    .collect()
+            };
+            let mut unused: Vec<usize> = (0..2 * digests.len()).collect();
+            let messages: Option<Vec<BatchMessage>> = digests
+                .iter()
+                .map(|message| {
+                    let position = unused.iter().position(|index| {
+                        let nonces: Vec<PublicNonce> =
+                            at_index(*index).into_iter().map(|(_, n)| n).collect();
+                        compute::intermediate(message, &ids, &nonces).1.has_even_y()
+                    })?;
+                    Some(BatchMessage {
+                        nonces: at_index(unused.remove(position)),
+                        message: message.to_vec(),
+                    })
+                })
+                .collect();
+            if let Some(messages) = messages {
+                return messages;
+            }
+        }
+    }
+
+    /// Run refresh `reshare_id` of DKG round #1 across `rounds`, letting `tamper` alter the
+    /// refresh shares before they are delivered. Returns the ReshareEnds, by signer id.
+    fn run_reshare(
+        rounds: &mut [SigningRound],
+        reshare_id: u64,
+        tamper: impl Fn(&mut ReshareShares),
+    ) -> Vec<ReshareEnd> {
+        let mut shares = vec![];
+        let mut ends = vec![];
+        for round in rounds.iter_mut() {
+            for msg in round
+                .process(MessageTypes::ReshareBegin(ReshareBegin {
+                    dkg_id: 1,
+                    reshare_id,
+                }))
+                .unwrap()
+            {
+                match msg {
+                    MessageTypes::ReshareShares(mut msg) => {
+                        tamper(&mut msg);
+                        shares.push(msg);
+                    }
+                    msg => panic!("expected ReshareShares, got {msg:?}"),
+                }
+            }
+        }
+        // every signer gets every message, its own included
+        for msg in shares {
+            for round in rounds.iter_mut() {
+                for out in round
+                    .process(MessageTypes::ReshareShares(msg.clone()))
+                    .unwrap()
+                {
+                    match out {
+                        MessageTypes::ReshareEnd(end) => ends.push(end),
+                        out => panic!("expected ReshareEnd, got {out:?}"),
+                    }
+                }
+            }
+        }
+        ends.sort_by_key(|end| end.signer_id);
+        ends
+    }
+
+    /// Give each of `rounds` a share cipher, signer ids counting from 1, so that the shares
+    /// they send each other are sealed
+    fn give_share_ciphers(rounds: &mut [SigningRound]) {
+        let keys: Vec<Scalar> = rounds.iter().map(|_| Scalar::random(&mut OsRng)).collect();
+        let peers: BTreeMap<u32, Point> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (i as u32 + 1, Point::from(*key)))
+            .collect();
+        for (i, round) in rounds.iter_mut().enumerate() {
+            round.share_cipher = Some(ShareCipher::new(i as u32 + 1, keys[i], peers.clone()));
+        }
+    }
+
+    /// Sign `message` with every key of `rounds`, two keys per signer
+    fn sign_with_every_key(
+        rounds: &mut [SigningRound],
+        message: [u8; 32],
+    ) -> wtfrost::common::Signature {
+        let nonces: Vec<(u32, PublicNonce)> = rounds
+            .iter_mut()
+            .flat_map(|round| {
+                round
+                    .process(MessageTypes::NonceRequest(NonceRequest {
+                        dkg_id: 1,
+                        sign_id: 1,
+                        correlation_id: 1,
+                        sign_nonce_id: 1,
+                    }))
+                    .unwrap()
+            })
+            .filter_map(|msg| match msg {
+                MessageTypes::NonceResponse(response) => Some((response.key_id, response.nonce)),
+                _ => None,
+            })
+            .collect();
+        let mut z = Scalar::default();
+        for (key_id, _) in &nonces {
+            let request = MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                signer_id: key_id / 2 + 1,
+                key_id: *key_id,
+                nonces: nonces.clone(),
+                message: message.to_vec(),
+                key_path: None,
+            });
+            match &rounds[*key_id as usize / 2].process(request).unwrap()[..] {
+                [MessageTypes::SignShareResponse(response)] => z += response.signature_share.z_i,
+                out => panic!("expected a SignShareResponse, got {out:?}"),
+            }
+        }
+        let ids: Vec<usize> = nonces.iter().map(|(id, _)| *id as usize).collect();
+        let public_nonces: Vec<PublicNonce> = nonces.iter().map(|(_, n)| n.clone()).collect();
+        let (_, aggregate_nonce) = compute::intermediate(&message, &ids, &public_nonces);
+        wtfrost::common::Signature {
+            R: aggregate_nonce,
+            z,
+        }
+    }
+
+    fn private_keys(rounds: &[SigningRound]) -> Vec<Scalar> {
+        rounds
+            .iter()
+            .flat_map(|round| &round.signer.frost_signer.parties)
+            .map(|party| party.save().private_key)
+            .collect()
     }
 
     #[test]
-    fn dkg_public_share() {
+    fn refreshed_shares_sign_for_the_same_group_key() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        let before = private_keys(&rounds);
+        give_share_ciphers(&mut rounds);
+
+        // the refresh shares cross the relay sealed to each signer
+        let ends = run_reshare(&mut rounds, 1, |msg| {
+            assert!(msg.shares.is_empty());
+            assert_eq!(msg.encrypted_shares.len(), 3);
+        });
+        assert_eq!(ends.len(), 3);
+        assert!(ends.iter().all(|end| end.status == DkgStatus::Success));
+        assert!(ends.iter().all(|end| end.digest == ends[0].digest));
+        // nothing changes until the coordinator confirms the refresh
+        assert_eq!(private_keys(&rounds), before);
+
+        for round in rounds.iter_mut() {
+            let out = round
+                .process(MessageTypes::ReshareCommit(ReshareCommit {
+                    dkg_id: 1,
+                    reshare_id: 1,
+                    digest: ends[0].digest,
+                }))
+                .unwrap();
+            assert!(out.is_empty());
+        }
+        let after = private_keys(&rounds);
+        assert!(before.iter().zip(&after).all(|(old, new)| old != new));
+        for round in &rounds {
+            for party in &round.signer.frost_signer.parties {
+                assert_eq!(party.group_key, group_key);
+            }
+        }
+        let signature = sign_with_every_key(&mut rounds, [7; 32]);
+        assert!(signature.verify(&group_key, &[7; 32]));
+
+        // and are refused in the clear
+        rounds[0]
+            .process(MessageTypes::ReshareBegin(ReshareBegin {
+                dkg_id: 1,
+                reshare_id: 2,
+            }))
+            .unwrap();
+        let clear = ReshareShares {
+            dkg_id: 1,
+            reshare_id: 2,
+            signer_id: 2,
+            commitments: vec![(2, vec![Point::default(); 4])],
+            shares: vec![(2, HashMap::from([(0, Scalar::from(5))]))],
+            encrypted_shares: vec![],
+        };
+        assert!(matches!(
+            rounds[0].reshare_shares(clear),
+            Err(Error::InvalidReshareShares(2))
+        ));
+    }
+
+    #[test]
+    fn bad_refresh_shares_are_never_applied() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let before = private_keys(&rounds);
+        let ends = run_reshare(&mut rounds, 1, |msg| {
+            if msg.signer_id == 2 {
+                for (_, shares) in &mut msg.shares {
+                    for share in shares.values_mut() {
+                        *share = *share + Scalar::from(1);
+                    }
+                }
+            }
+        });
+        // signer 2 applies its own shares before they are tampered with
+        for end in &ends {
+            match (end.signer_id, &end.status) {
+                (2, status) => assert_eq!(*status, DkgStatus::Success),
+                (_, status) => {
+                    assert_eq!(
+                        *status,
+                        DkgStatus::Failure(DkgFailure::BadShares(vec![2, 3]))
+                    )
+                }
+            }
+        }
+        for round in rounds.iter_mut() {
+            round
+                .process(MessageTypes::ReshareCommit(ReshareCommit {
+                    dkg_id: 1,
+                    reshare_id: 1,
+                    digest: ends[0].digest,
+                }))
+                .unwrap();
+        }
+        // the coordinator never confirms a refresh which failed anywhere, and the signers which
+        // received bad shares would not apply it anyway
+        let after = private_keys(&rounds);
+        assert_eq!(after[..2], before[..2]);
+        assert_eq!(after[4..], before[4..]);
+        assert!(rounds.iter().all(|round| round.reshare.is_none()));
+    }
+
+    #[test]
+    fn joining_signer_repairs_shares_of_the_same_group_key() {
+        let (mut rounds, _) =
+            run_dkg_with(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4, &[4, 5], |_| {});
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        give_share_ciphers(&mut rounds);
+        let change = RosterChange {
+            dkg_id: 1,
+            change_id: 1,
+            key_owners: (0..6).map(|key_id| (key_id, key_id / 2 + 1)).collect(),
+            repaired_key_ids: vec![4, 5],
+            helper_key_ids: vec![0, 1, 2, 3],
+            group_commitment: roster::group_commitment(rounds[0].commitments.values(), 4),
+        };
+        // every signer gets every message until none is left
+        let mut msgs = vec![MessageTypes::RosterChange(change)];
+        let mut ends = vec![];
+        while !msgs.is_empty() {
+            let mut out = vec![];
+            for msg in msgs {
+                for round in rounds.iter_mut() {
+                    out.extend(round.process(msg.clone()).unwrap());
+                }
+            }
+            msgs = vec![];
+            for msg in out {
+                match &msg {
+                    MessageTypes::RosterChangeEnd(end) => ends.push(end.clone()),
+                    // nothing crosses the relay in the clear, and only the joining signer gets
+                    // the sums
+                    MessageTypes::RepairShares(shares) => {
+                        assert!(shares.pieces.is_empty());
+                        assert_eq!(shares.encrypted_pieces.len(), 2);
+                        msgs.push(msg)
+                    }
+                    MessageTypes::RepairedShares(repaired) => {
+                        assert!(repaired.sums.is_empty());
+                        let recipients: Vec<u32> = repaired
+                            .encrypted_sums
+                            .iter()
+                            .map(|sealed| sealed.signer_id)
+                            .collect();
+                        assert_eq!(recipients, vec![3]);
+                        msgs.push(msg)
+                    }
+                    msg => panic!("unexpected {msg:?}"),
+                }
+            }
+        }
+        assert_eq!(ends.len(), 1);
+        assert_eq!(ends[0].signer_id, 3);
+        assert_eq!(ends[0].status, DkgStatus::Success);
+        assert!(rounds.iter().all(|round| round.excluded_key_ids.is_empty()));
+        assert!(rounds.iter().all(|round| round.repair.is_none()));
+        for party in &rounds[2].signer.frost_signer.parties {
+            assert_eq!(party.group_key, group_key);
+        }
+        assert_eq!(
+            rounds[2].group_key_view(),
+            rounds[0].group_key_view().map(|view| {
+                GroupKeyView {
+                    signer_id: 3,
+                    key_ids: vec![4, 5],
+                    ..view
+                }
+            })
+        );
+        let signature = sign_with_every_key(&mut rounds, [9; 32]);
+        assert!(signature.verify(&group_key, &[9; 32]));
+    }
+
+    #[test]
+    fn concurrent_sessions_sign_with_their_own_nonces() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let group_key = rounds[0].signer.frost_signer.parties[0].group_key;
+        let mut nonces = |correlation_id| -> Vec<(u32, PublicNonce)> {
+            rounds
+                .iter_mut()
+                .flat_map(|round| {
+                    round
+                        .process(MessageTypes::NonceRequest(NonceRequest {
+                            dkg_id: 1,
+                            sign_id: 1,
+                            correlation_id,
+                            sign_nonce_id: 1,
+                        }))
+                        .unwrap()
+                })
+                .filter_map(|msg| match msg {
+                    MessageTypes::NonceResponse(response) => {
+                        assert_eq!(response.correlation_id, correlation_id);
+                        Some((response.key_id, response.nonce))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        // the second nonce request replaces the nonces the parties hold, not the first session's
+        let sessions = [(1, nonces(1), [7; 32]), (2, nonces(2), [8; 32])];
+        assert_eq!(rounds[0].snapshot().signing_sessions.len(), 2);
+
+        // signed in the reverse order the nonces were handed out in
+        for (correlation_id, nonces, message) in sessions.iter().rev() {
+            let mut z = Scalar::default();
+            for key_id in 0..6 {
+                let request = MessageTypes::SignShareRequest(SignatureShareRequest {
+                    dkg_id: 1,
+                    sign_id: 1,
+                    correlation_id: *correlation_id,
+                    signer_id: key_id / 2 + 1,
+                    key_id,
+                    nonces: nonces.clone(),
+                    message: message.to_vec(),
+                    key_path: None,
+                });
+                match &rounds[key_id as usize / 2].process(request).unwrap()[..] {
+                    [MessageTypes::SignShareResponse(response)] => {
+                        z += response.signature_share.z_i
+                    }
+                    out => panic!("expected a SignShareResponse, got {out:?}"),
+                }
+            }
+            let ids: Vec<usize> = nonces.iter().map(|(id, _)| *id as usize).collect();
+            let public_nonces: Vec<PublicNonce> = nonces.iter().map(|(_, n)| n.clone()).collect();
+            let (_, aggregate_nonce) = compute::intermediate(message, &ids, &public_nonces);
+            let signature = wtfrost::common::Signature {
+                R: aggregate_nonce,
+                z,
+            };
+            assert!(signature.verify(&group_key, message));
+        }
+        assert!(rounds[0].snapshot().signing_sessions.is_empty());
+
+        // each nonce signs once, and only in the session it was handed out in
+        let (_, nonces, message) = &sessions[0];
+        let request = |correlation_id| {
+            MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id,
+                signer_id: 1,
+                key_id: 0,
+                nonces: nonces.clone(),
+                message: message.to_vec(),
+                key_path: None,
+            })
+        };
+        for correlation_id in [1, 2] {
+            match &rounds[0].process(request(correlation_id)).unwrap()[..] {
+                [MessageTypes::Rejection(rejection)] => {
+                    assert_eq!(RejectionCode::UnknownNonce, rejection.reason_code);
+                }
+                out => panic!("expected a Rejection, got {out:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn sign_share_request_is_answered_by_the_owner_of_the_key() {
+        let mut rounds = run_dkg(&[vec![0, 1], vec![2, 3], vec![4, 5]], 4);
+        let nonces: Vec<(u32, PublicNonce)> = rounds
+            .iter_mut()
+            .flat_map(|round| {
+                round
+                    .process(MessageTypes::NonceRequest(NonceRequest {
+                        dkg_id: 1,
+                        sign_id: 1,
+                        correlation_id: 1,
+                        sign_nonce_id: 1,
+                    }))
+                    .unwrap()
+            })
+            .filter_map(|msg| match msg {
+                MessageTypes::NonceResponse(response) => {
+                    assert_eq!(response.key_id / 2 + 1, response.signer_id);
+                    Some((response.key_id, response.nonce))
+                }
+                _ => None,
+            })
+            .collect();
+        let request = |signer_id, key_id| {
+            MessageTypes::SignShareRequest(SignatureShareRequest {
+                dkg_id: 1,
+                sign_id: 1,
+                correlation_id: 1,
+                signer_id,
+                key_id,
+                nonces: nonces.clone(),
+                message: vec![7; 32],
+                key_path: None,
+            })
+        };
+
+        // signer #2 holds keys 2 and 3
+        match &rounds[1].process(request(2, 3)).unwrap()[..] {
+            [MessageTypes::SignShareResponse(response)] => {
+                assert_eq!((2, 3), (response.signer_id, response.key_id));
+            }
+            out => panic!("expected a SignShareResponse, got {:?}", out),
+        }
+        // a request addressed to another signer is not for it to answer, even for its own key
+        assert!(rounds[1].process(request(1, 2)).unwrap().is_empty());
+        // nor does it sign for a key it is wrongly addressed as the owner of
+        match &rounds[1].process(request(2, 0)).unwrap()[..] {
+            [MessageTypes::Rejection(rejection)] => {
+                assert_eq!(RejectionCode::KeyOwnerMismatch, rejection.reason_code);
+            }
+            out => panic!("expected a Rejection, got {:?}", out),
+        }
+        // and it signs digests only, never a message it would have to hash first
+        let mut undigested = request(2, 3);
+        if let MessageTypes::SignShareRequest(request) = &mut undigested {
+            request.message = b"message".to_vec();
+        }
+        match &rounds[1].process(undigested).unwrap()[..] {
+            [MessageTypes::Rejection(rejection)] => {
+                assert_eq!(RejectionCode::InvalidMessage, rejection.reason_code);
+            }
+            out => panic!("expected a Rejection, got {:?}", out),
+        }
+    }
+
+    #[test]
+    fn dkg_drops_conflicting_key_owners() {
         let mut rnd = get_rng();
-        let mut signing_round = SigningRound::new(1, 1, 1, vec![1]);
-        let public_share = DkgPublicShare {
-            dkg_id: 0,
-            party_id: 0,
-            public_share: PolyCommitment {
-                id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
-                A: vec![],
-            },
-            dkg_public_id: 0,
+        let mut signing_round = SigningRound::new(2, 4, 2, vec![2, 3]);
+        signing_round
+            .process(MessageTypes::DkgBegin(DkgBegin {
+                dkg_id: 1,
+                excluded_key_ids: vec![],
+            }))
+            .unwrap();
+        let mut public_share = |signer_id, key_id| {
+            MessageTypes::DkgPublicShare(DkgPublicShare {
+                dkg_id: 1,
+                dkg_public_id: 1,
+                signer_id,
+                key_id,
+                public_share: PolyCommitment {
+                    id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                    A: vec![],
+                },
+            })
         };
-        signing_round.dkg_public_share(public_share).unwrap();
-        assert_eq!(1, signing_round.commitments.len())
+        assert!(signing_round
+            .process(public_share(1, 0))
+            .unwrap()
+            .is_empty());
+        assert_eq!(Some(&1), signing_round.key_owners.get(&0));
+        // a second owner of key 0, or another owner of one of our keys, is dropped unanswered
+        assert!(signing_round
+            .process(public_share(3, 0))
+            .unwrap()
+            .is_empty());
+        assert!(signing_round
+            .process(public_share(1, 3))
+            .unwrap()
+            .is_empty());
+        assert_eq!(Some(&1), signing_round.key_owners.get(&0));
+        assert_eq!(vec![0, 2, 3], signing_round.snapshot().commitments_received);
+
+        let private_shares = MessageTypes::DkgPrivateShares(DkgPrivateShares {
+            dkg_id: 1,
+            signer_id: 3,
+            private_shares: vec![(0, HashMap::new())],
+            encrypted_shares: vec![],
+            commitment_digest: commitment_digest(
+                signing_round.dkg_id,
+                signing_round.commitments.iter(),
+            ),
+        });
+        assert!(signing_round.process(private_shares).unwrap().is_empty());
+        assert!(signing_round.shares.is_empty());
     }
 
     #[test]
-    fn dkg_private_shares() {
-        let mut signing_round = SigningRound::new(1, 1, 1, vec![1]);
-        let mut private_shares = DkgPrivateShares {
-            dkg_id: 0,
-            key_id: 0,
-            private_shares: HashMap::new(),
+    fn round_outcome_conflicting_attestations() {
+        let mut signing_round = SigningRound::new(3, 4, 3, vec![3]);
+        let response = |signer_id: u32, key_ids: Vec<u32>, status: DkgStatus| {
+            MessageTypes::RoundOutcomeResponse(RoundOutcomeResponse {
+                dkg_id: 7,
+                signer_id,
+                key_ids,
+                outcome: RoundOutcome {
+                    status,
+                    group_key: None,
+                },
+            })
         };
-        private_shares.private_shares.insert(1, Scalar::new());
-        signing_round.dkg_private_shares(private_shares).unwrap();
-        assert_eq!(1, signing_round.shares.len())
+        signing_round
+            .process(response(1, vec![0, 1], DkgStatus::Success))
+            .unwrap();
+        signing_round
+            .process(response(
+                2,
+                vec![2],
+                DkgStatus::Failure(DkgFailure::BadShares(vec![2])),
+            ))
+            .unwrap();
+        assert!(!signing_round.generations.contains_key(&7));
+
+        // re-sending the same attestation does not count twice
+        signing_round
+            .process(response(1, vec![0, 1], DkgStatus::Success))
+            .unwrap();
+        assert!(!signing_round.generations.contains_key(&7));
+
+        signing_round
+            .process(response(2, vec![2], DkgStatus::Success))
+            .unwrap();
+        assert_eq!(DkgStatus::Success, signing_round.generations[&7].status);
+    }
+
+    #[test]
+    fn round_outcome_counts_only_the_keys_a_signer_holds() {
+        let mut signing_round = SigningRound::new(3, 6, 3, vec![4, 5]);
+        let response = |signer_id: u32, key_ids: Vec<u32>| {
+            MessageTypes::RoundOutcomeResponse(RoundOutcomeResponse {
+                dkg_id: 7,
+                signer_id,
+                key_ids,
+                outcome: RoundOutcome {
+                    status: DkgStatus::Success,
+                    group_key: Some(Point::from(Scalar::from(9))),
+                },
+            })
+        };
+        // signer #1 holds keys 0 and 1 only, claiming every key does not make it a threshold
+        signing_round
+            .process(response(1, vec![0, 1, 2, 3, 4, 5]))
+            .unwrap();
+        assert!(!signing_round.generations.contains_key(&7));
+        // nor does signer #2 claiming the keys signer #1 holds
+        signing_round.process(response(2, vec![0, 1])).unwrap();
+        assert!(!signing_round.generations.contains_key(&7));
+
+        signing_round.process(response(2, vec![2, 3])).unwrap();
+        assert_eq!(
+            Some(Point::from(Scalar::from(9))),
+            signing_round.generations[&7].group_key
+        );
+    }
+
+    #[test]
+    fn dkg_uneven_key_allocation() {
+        let rounds = run_dkg(&[vec![0], vec![1, 2], vec![3, 4, 5, 6]], 5);
+        assert_same_group_key(&rounds);
     }
 
     #[test]
@@ -743,17 +6461,43 @@ mod test {
             },
         );
         let shares: HashMap<usize, Scalar> = HashMap::new();
-        signing_round.shares.insert(1, shares);
+        signing_round.shares.insert(1, Secret::new(shares));
 
         // can_dkg_end should be true
         assert!(signing_round.can_dkg_end());
     }
 
+    #[test]
+    fn dkg_ends_once_enough_signers_sent_shares() {
+        let mut rnd = get_rng();
+        let mut signing_round = SigningRound::new(1, 2, 1, vec![0]).with_signers_threshold(2);
+        signing_round.state = States::DkgPrivateGather;
+        for key_id in [0, 1] {
+            signing_round.commitments.insert(
+                key_id,
+                PolyCommitment {
+                    id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                    A: vec![],
+                },
+            );
+            signing_round
+                .shares
+                .insert(key_id, Secret::new(HashMap::new()));
+        }
+        // every key is in, but the signer which sent key 1's shares is unknown
+        assert!(!signing_round.can_dkg_end());
+        assert_eq!(signing_round.snapshot().signers_received, vec![1]);
+
+        signing_round.key_owners.insert(1, 2);
+        assert!(signing_round.can_dkg_end());
+        assert_eq!(signing_round.snapshot().signers_received, vec![1, 2]);
+    }
+
     #[test]
     fn dkg_ended() {
         let mut signing_round = SigningRound::new(1, 1, 1, vec![1]);
         match signing_round.dkg_ended() {
-            Ok(dkg_end) => match dkg_end {
+            Ok(mut msgs) => match msgs.pop().unwrap() {
                 MessageTypes::DkgEnd(dkg_end) => match dkg_end.status {
                     DkgStatus::Failure(_) => assert!(true),
                     _ => assert!(false),
@@ -763,4 +6507,126 @@ mod test {
             _ => assert!(false),
         }
     }
+
+    /// Hex of the sha256 of the preimage `msg` signs
+    fn preimage_digest(msg: &impl Signable) -> String {
+        let mut hasher = Sha256::new();
+        msg.hash(&mut hasher);
+        hex::encode(hasher.finalize())
+    }
+
+    #[test]
+    fn preimages_match_the_test_vectors() {
+        // digests of the canonical encoding described in crate::preimage, for other
+        // implementations of the protocol to check theirs against
+        let begin = DkgBegin {
+            dkg_id: 7,
+            excluded_key_ids: vec![2, 5],
+        };
+        assert_eq!(
+            preimage_digest(&begin),
+            "81cb46c024c5330be9394a520be578ea9915db0b8fef0475a3d5473849c2fb23"
+        );
+        let request = NonceRequest {
+            dkg_id: 7,
+            sign_id: 3,
+            correlation_id: 1,
+            sign_nonce_id: 4,
+        };
+        assert_eq!(
+            preimage_digest(&request),
+            "41b196d36e596eee568a89678f58c45925fea5505b39cddf552c699efbaa8b18"
+        );
+        let abort = RoundAbort {
+            dkg_id: 7,
+            sign_id: 3,
+            reason: "timeout".to_string(),
+        };
+        assert_eq!(
+            preimage_digest(&abort),
+            "8dc5aaa66ee9f3eaa31e56a69ddb30f72b0523417c765d52382cbd3743e93e82"
+        );
+        let rejection = Rejection {
+            signer_id: 2,
+            request_hash: vec![0xab; 4],
+            reason_code: RejectionCode::InvalidState,
+            detail: "busy".to_string(),
+        };
+        assert_eq!(
+            preimage_digest(&rejection),
+            "b9abff14a3bc5ee0536089080fd13b9097516721e403e50d1419a8e37a741ae4"
+        );
+        let outcome = RoundOutcomeResponse {
+            dkg_id: 7,
+            signer_id: 2,
+            key_ids: vec![2, 3],
+            outcome: RoundOutcome {
+                status: DkgStatus::Failure(DkgFailure::MissingShares(vec![0])),
+                group_key: None,
+            },
+        };
+        assert_eq!(
+            preimage_digest(&outcome),
+            "b02653e272d927fb181604fafd9ffc6d8b2e3c179f87e5d48bc0ebb16306d42a"
+        );
+        let shares = DkgPrivateShares {
+            dkg_id: 7,
+            signer_id: 2,
+            private_shares: vec![(
+                2,
+                HashMap::from([(0, Scalar::from(10)), (1, Scalar::from(11))]),
+            )],
+            encrypted_shares: vec![EncryptedShares {
+                signer_id: 3,
+                ciphertext: vec![1, 2, 3],
+            }],
+            commitment_digest: [7; 32],
+        };
+        assert_eq!(
+            preimage_digest(&shares),
+            "f27128c2609d09b0d319bfefb74eeba79f3666e89af9b29442f8ca6de8f031ed"
+        );
+    }
+
+    #[test]
+    fn preimages_do_not_depend_on_map_order_or_field_boundaries() {
+        let shares = |entries: Vec<(usize, u32)>| DkgPrivateShares {
+            dkg_id: 1,
+            signer_id: 1,
+            private_shares: vec![(
+                0,
+                entries
+                    .into_iter()
+                    .map(|(key_id, share)| (key_id, Scalar::from(share)))
+                    .collect(),
+            )],
+            encrypted_shares: vec![],
+            commitment_digest: [0; 32],
+        };
+        let ascending: Vec<_> = (0..32).map(|key_id| (key_id, key_id as u32 + 1)).collect();
+        let descending = ascending.iter().rev().cloned().collect();
+        assert_eq!(
+            preimage_digest(&shares(ascending)),
+            preimage_digest(&shares(descending))
+        );
+
+        // the same bytes split differently between two fields hash differently
+        let hello = |public_key: &[u8], config_hash: &[u8]| Hello {
+            signer_id: 1,
+            public_key: public_key.to_vec(),
+            config_hash: config_hash.to_vec(),
+        };
+        assert_ne!(
+            preimage_digest(&hello(&[1, 2], &[3])),
+            preimage_digest(&hello(&[1], &[2, 3]))
+        );
+        let begin = |excluded_key_ids| DkgBegin {
+            dkg_id: 1,
+            excluded_key_ids,
+        };
+        assert_ne!(
+            preimage_digest(&begin(vec![])),
+            preimage_digest(&begin(vec![0]))
+        );
+    }
 }
@@ -0,0 +1,512 @@
+//! Message exchange through a stacks-node StackerDB instead of the relay.
+//!
+//! Every node owns the slot of the signer set's StackerDB contract matching its sender id: the
+//! coordinator slot 0 and signer `i` slot `i`. A slot only keeps its latest chunk, so each write
+//! carries the last [`OUTBOX_LEN`] envelopes the node sent, numbered from the start of the
+//! process, and bumps the slot version. Readers poll the slot versions and queue the envelopes
+//! of every changed slot they have not seen yet.
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use secp256k1::{Message as SecpMessage, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512_256};
+use tracing::{debug, info, warn};
+
+use crate::auth::{self, Authenticator, Identity};
+use crate::net::{admit, authenticate, Envelope, Error as NetError, Message, Net, NetListen};
+use crate::proxy::Egress;
+use crate::retry::RetryPolicy;
+use crate::traffic::Traffic;
+
+/// Envelopes kept in a node's slot
+pub const OUTBOX_LEN: usize = 64;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid StackerDB contract id {0:?}, expected <address>.<name>")]
+    InvalidContract(String),
+    #[error("StackerDB rejected version {1} of slot {0}: {2}")]
+    Rejected(u32, u32, String),
+    #[error("The network private key cannot sign chunks")]
+    InvalidKey,
+    #[error("Unreadable StackerDB response: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The StackerDB contract of the signer set
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractId {
+    pub address: String,
+    pub name: String,
+}
+
+impl FromStr for ContractId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('.') {
+            Some((address, name)) if !address.is_empty() && !name.is_empty() => Ok(Self {
+                address: address.to_string(),
+                name: name.to_string(),
+            }),
+            _ => Err(Error::InvalidContract(s.to_string())),
+        }
+    }
+}
+
+/// Slot metadata as listed by the node
+#[derive(Deserialize, Debug)]
+struct SlotMetadata {
+    slot_id: u32,
+    slot_version: u32,
+}
+
+/// Chunk upload as accepted by `POST /v2/stackerdb/<address>/<name>/chunks`
+#[derive(Serialize, Debug)]
+struct ChunkData {
+    slot_id: u32,
+    slot_version: u32,
+    /// Hex of the recoverable signature, recovery id first
+    sig: String,
+    /// Hex of the chunk
+    data: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChunkAck {
+    accepted: bool,
+    reason: Option<String>,
+    metadata: Option<SlotMetadata>,
+}
+
+/// What a node writes to its slot
+#[derive(Serialize, Deserialize, Debug)]
+struct Chunk<E> {
+    /// Unix time in milliseconds the writer started at, sequence numbers restart with it
+    epoch: u64,
+    /// Sequence number of the first envelope
+    first_seq: u64,
+    envelopes: E,
+}
+
+struct Outbox {
+    epoch: u64,
+    first_seq: u64,
+    envelopes: VecDeque<Envelope>,
+    slot_version: u32,
+}
+
+// StackerDB send (does not require mutable access, can be cloned to pass to threads)
+#[derive(Clone)]
+pub struct StackerDbNet {
+    node_url: String,
+    contract: ContractId,
+    /// Number of slots, one per sender id
+    slots: u32,
+    namespace: String,
+    identity: Identity,
+    retry_policy: RetryPolicy,
+    outbox: Arc<Mutex<Outbox>>,
+    /// Sends requests to the node, through its proxy if it has one
+    egress: Egress,
+}
+
+impl StackerDbNet {
+    pub fn new(node_url: String, contract: ContractId, slots: u32, identity: Identity) -> Self {
+        StackerDbNet {
+            node_url: node_url.trim_end_matches('/').to_string(),
+            contract,
+            slots,
+            namespace: String::new(),
+            identity,
+            retry_policy: RetryPolicy::never(),
+            outbox: Arc::new(Mutex::new(Outbox {
+                epoch: auth::now_ms(),
+                first_seq: 0,
+                envelopes: VecDeque::new(),
+                slot_version: 0,
+            })),
+            egress: Egress::direct(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Only exchange messages with nodes using the same namespace
+    pub fn with_namespace(mut self, namespace: String) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    /// Send requests to the node through `egress`, see [`crate::proxy`]
+    pub fn with_egress(mut self, egress: Egress) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!(
+            "{}/v2/stackerdb/{}/{}{path}",
+            self.node_url, self.contract.address, self.contract.name
+        )
+    }
+
+    /// `data` as a chunk of our slot, signed the way the node authenticates slot writers
+    fn chunk_data(&self, slot_version: u32, data: &[u8]) -> Result<ChunkData, Error> {
+        let slot_id = self.identity.sender_id;
+        let mut hasher = Sha512_256::new();
+        hasher.update(slot_id.to_be_bytes());
+        hasher.update(slot_version.to_be_bytes());
+        hasher.update(Sha512_256::digest(data));
+        let digest = hasher.finalize();
+
+        let key = SecretKey::from_slice(&self.identity.private_key().to_bytes())
+            .map_err(|_| Error::InvalidKey)?;
+        let message = SecpMessage::from_slice(&digest).map_err(|_| Error::InvalidKey)?;
+        let (recovery_id, compact) = Secp256k1::signing_only()
+            .sign_ecdsa_recoverable(&message, &key)
+            .serialize_compact();
+        let mut sig = vec![recovery_id.to_i32() as u8];
+        sig.extend_from_slice(&compact);
+        Ok(ChunkData {
+            slot_id,
+            slot_version,
+            sig: hex::encode(sig),
+            data: hex::encode(data),
+        })
+    }
+
+    fn post_chunk(&self, chunk: &ChunkData) -> Result<ChunkAck, NetError> {
+        let url = self.url("/chunks");
+        let response = self.retry_policy.retry(|| {
+            self.egress
+                .post(&url)
+                .send_json(chunk)
+                .map_err(|e| NetError::from(Box::new(e)))
+        })?;
+        Ok(response.into_json().map_err(Error::from)?)
+    }
+}
+
+impl Net for StackerDbNet {
+    type Error = NetError;
+
+    fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
+        self.identity.check(&msg)?;
+        let envelope = self
+            .identity
+            .seal(self.namespace.clone(), msg)
+            .map_err(|e| NetError::SigningError(e.to_string()))?;
+        let mut outbox = self.outbox.lock().expect("StackerDB outbox poisoned");
+        outbox.envelopes.push_back(envelope);
+        if outbox.envelopes.len() > OUTBOX_LEN {
+            outbox.envelopes.pop_front();
+            outbox.first_seq += 1;
+        }
+        let data = bincode::serialize(&Chunk {
+            epoch: outbox.epoch,
+            first_seq: outbox.first_seq,
+            envelopes: &outbox.envelopes,
+        })?;
+
+        // the slot version is only known once the node refuses a stale one, e.g. after a restart
+        for _ in 0..2 {
+            let slot_version = outbox.slot_version + 1;
+            let ack = self.post_chunk(&self.chunk_data(slot_version, &data)?)?;
+            if ack.accepted {
+                outbox.slot_version = slot_version;
+                debug!(
+                    "wrote version {slot_version} of slot {}",
+                    self.identity.sender_id
+                );
+                return Ok(());
+            }
+            match ack.metadata {
+                Some(metadata) if metadata.slot_version >= slot_version => {
+                    outbox.slot_version = metadata.slot_version;
+                }
+                _ => {
+                    return Err(Error::Rejected(
+                        self.identity.sender_id,
+                        slot_version,
+                        ack.reason.unwrap_or_default(),
+                    )
+                    .into())
+                }
+            }
+        }
+        Err(Error::Rejected(
+            self.identity.sender_id,
+            outbox.slot_version + 1,
+            "slot version keeps moving".to_string(),
+        )
+        .into())
+    }
+}
+
+/// Position read up to in a peer's slot
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct ReadPosition {
+    slot_version: u32,
+    epoch: u64,
+    next_seq: u64,
+}
+
+// StackerDB poll with queue (requires mutable access, is configured by passing in StackerDbNet)
+pub struct StackerDbNetListen {
+    pub net: StackerDbNet,
+    in_queue: VecDeque<Message>,
+    read: BTreeMap<u32, ReadPosition>,
+    /// Drops envelopes which fail authentication, if set
+    authenticator: Option<Authenticator>,
+    /// Counts inbound envelopes, if set
+    traffic: Option<Traffic>,
+    connected: bool,
+}
+
+impl StackerDbNetListen {
+    pub fn new(net: StackerDbNet) -> Self {
+        StackerDbNetListen {
+            net,
+            in_queue: VecDeque::new(),
+            read: BTreeMap::new(),
+            authenticator: None,
+            traffic: None,
+            connected: true,
+        }
+    }
+
+    /// Only queue messages in envelopes `authenticator` accepts
+    pub fn with_authenticator(mut self, authenticator: Authenticator) -> Self {
+        self.authenticator = Some(authenticator);
+        self
+    }
+
+    /// Only queue messages in envelopes `traffic` admits. Our own slot is never read, so
+    /// these are all peer envelopes.
+    pub fn with_traffic(mut self, traffic: Traffic) -> Self {
+        self.traffic = Some(traffic);
+        self
+    }
+
+    fn poll_slots(&mut self) -> Result<(), NetError> {
+        let slots: Vec<SlotMetadata> = self
+            .net
+            .egress
+            .get(&self.net.url(""))
+            .call()
+            .map_err(Box::new)?
+            .into_json()
+            .map_err(Error::from)?;
+        for slot in slots {
+            let read = self.read.get(&slot.slot_id).copied().unwrap_or_default();
+            if slot.slot_id == self.net.identity.sender_id
+                || slot.slot_id >= self.net.slots
+                || slot.slot_version <= read.slot_version
+            {
+                continue;
+            }
+            let mut chunk = vec![];
+            self.net
+                .egress
+                .get(&self.net.url(&format!("/{}", slot.slot_id)))
+                .call()
+                .map_err(Box::new)?
+                .into_reader()
+                .read_to_end(&mut chunk)
+                .map_err(Error::from)?;
+            self.receive_chunk(slot.slot_id, slot.slot_version, &chunk);
+        }
+        Ok(())
+    }
+
+    /// Queue the envelopes of version `slot_version` of `slot_id` we have not seen yet
+    fn receive_chunk(&mut self, slot_id: u32, slot_version: u32, data: &[u8]) {
+        let read = self.read.entry(slot_id).or_default();
+        read.slot_version = slot_version;
+        let chunk: Chunk<Vec<Envelope>> = match bincode::deserialize(data) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                warn!("dropping unreadable version {slot_version} of slot {slot_id}: {e}");
+                return;
+            }
+        };
+        if chunk.epoch != read.epoch {
+            read.epoch = chunk.epoch;
+            read.next_seq = 0;
+        }
+        let first_unread = read.next_seq.saturating_sub(chunk.first_seq) as usize;
+        read.next_seq = chunk.first_seq + chunk.envelopes.len() as u64;
+        for envelope in chunk.envelopes.into_iter().skip(first_unread) {
+            if envelope.namespace != self.net.namespace || envelope.sender_id != slot_id {
+                debug!("dropping message from another namespace or slot");
+                continue;
+            }
+            if let Err(e) = authenticate(&self.authenticator, &self.traffic, &envelope) {
+                warn!("dropping message from sender #{}: {e}", envelope.sender_id);
+                continue;
+            }
+            if !admit(&self.traffic, &envelope) {
+                continue;
+            }
+            debug!("received {:?}", envelope.message);
+            self.in_queue.push_back(envelope.message);
+        }
+    }
+}
+
+impl NetListen for StackerDbNetListen {
+    type Error = NetError;
+
+    fn listen(&self) {}
+
+    fn poll(&mut self, _id: u32) {
+        match self.poll_slots() {
+            Ok(()) => self.connected = true,
+            Err(e) => {
+                if self.connected {
+                    warn!("StackerDB poll of {} failed: {e}", self.net.node_url);
+                    self.connected = false;
+                }
+            }
+        }
+    }
+
+    fn next_message(&mut self) -> Option<Message> {
+        self.in_queue.pop_front()
+    }
+
+    // pass-thru to immutable net function
+    fn send_message(&self, msg: Message) -> Result<(), Self::Error> {
+        self.net.send_message(msg).map_err(|e| {
+            info!("StackerDB write to {} failed: {e}", self.net.node_url);
+            e
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_round::{DkgBegin, MessageTypes};
+    use rand_core::OsRng;
+    use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+    use wtfrost::Scalar;
+
+    fn net(sender_id: u32) -> StackerDbNet {
+        StackerDbNet::new(
+            "http://localhost:20443/".to_string(),
+            "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.signers"
+                .parse()
+                .unwrap(),
+            3,
+            Identity::new(sender_id, Scalar::random(&mut OsRng)),
+        )
+    }
+
+    fn message(dkg_id: u64) -> Message {
+        Message {
+            msg: MessageTypes::DkgBegin(DkgBegin {
+                dkg_id,
+                excluded_key_ids: vec![],
+            }),
+            sig: vec![],
+        }
+    }
+
+    fn chunk(writer: &StackerDbNet, epoch: u64, first_seq: u64, dkg_ids: &[u64]) -> Vec<u8> {
+        let envelopes: Vec<Envelope> = dkg_ids
+            .iter()
+            .map(|id| writer.identity.seal(String::new(), message(*id)).unwrap())
+            .collect();
+        bincode::serialize(&Chunk {
+            epoch,
+            first_seq,
+            envelopes,
+        })
+        .unwrap()
+    }
+
+    fn received(listen: &mut StackerDbNetListen) -> Vec<u64> {
+        std::iter::from_fn(|| listen.next_message())
+            .map(|message| match message.msg {
+                MessageTypes::DkgBegin(begin) => begin.dkg_id,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn contract_ids_need_an_address_and_a_name() {
+        assert_eq!(
+            "SP000000000000000000002Q6VF78.signers"
+                .parse::<ContractId>()
+                .unwrap(),
+            ContractId {
+                address: "SP000000000000000000002Q6VF78".to_string(),
+                name: "signers".to_string()
+            }
+        );
+        for id in ["signers", ".signers", "SP000000000000000000002Q6VF78."] {
+            assert!(id.parse::<ContractId>().is_err(), "{id}");
+        }
+        assert_eq!(
+            net(1).url("/chunks"),
+            "http://localhost:20443/v2/stackerdb/ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM/signers/chunks"
+        );
+    }
+
+    #[test]
+    fn chunks_are_signed_by_the_slot_owner() {
+        let net = net(2);
+        let chunk = net.chunk_data(7, b"chunk").unwrap();
+        assert_eq!(chunk.slot_id, 2);
+        assert_eq!(chunk.data, hex::encode(b"chunk"));
+
+        let sig = hex::decode(&chunk.sig).unwrap();
+        assert_eq!(sig.len(), 65);
+        let sig = RecoverableSignature::from_compact(
+            &sig[1..],
+            RecoveryId::from_i32(sig[0] as i32).unwrap(),
+        )
+        .unwrap();
+        let mut hasher = Sha512_256::new();
+        hasher.update(2u32.to_be_bytes());
+        hasher.update(7u32.to_be_bytes());
+        hasher.update(Sha512_256::digest(b"chunk"));
+        let digest = SecpMessage::from_slice(&hasher.finalize()).unwrap();
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&net.identity.private_key().to_bytes()).unwrap();
+        assert_eq!(
+            secp.recover_ecdsa(&digest, &sig).unwrap(),
+            key.public_key(&secp)
+        );
+    }
+
+    #[test]
+    fn chunks_only_deliver_unseen_envelopes_in_order() {
+        let writer = net(1);
+        let mut listen = StackerDbNetListen::new(net(0));
+
+        listen.receive_chunk(1, 1, &chunk(&writer, 100, 0, &[1, 2]));
+        assert_eq!(received(&mut listen), vec![1, 2]);
+        // the window moved on past an envelope we already read
+        listen.receive_chunk(1, 2, &chunk(&writer, 100, 1, &[2, 3, 4]));
+        assert_eq!(received(&mut listen), vec![3, 4]);
+        // a restarted writer numbers its envelopes from zero again
+        listen.receive_chunk(1, 3, &chunk(&writer, 200, 0, &[5]));
+        assert_eq!(received(&mut listen), vec![5]);
+        listen.receive_chunk(1, 4, b"garbage");
+        assert_eq!(received(&mut listen), Vec::<u64>::new());
+
+        // envelopes must come from the slot's owner
+        listen.receive_chunk(2, 1, &chunk(&writer, 100, 0, &[6]));
+        assert_eq!(received(&mut listen), Vec::<u64>::new());
+    }
+}
@@ -1,10 +1,14 @@
-#[derive(Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum States {
     Idle,
     DkgPublicDistribute,
     DkgPublicGather,
     DkgPrivateDistribute,
     DkgPrivateGather,
+    /// DKG computed our secret, waiting for the group keys of the other signers
+    DkgConfirm,
     SignGather,
     Signed,
 }
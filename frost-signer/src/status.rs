@@ -0,0 +1,118 @@
+//! Local status endpoint serving a [`RoundSnapshot`] as JSON, a [`crate::control`] route.
+//!
+//! Operators can `curl http://<status_listen_addr>/status` to see which commitments,
+//! shares and nonce requests a signer is still waiting on. `GET /group-key` serves just the
+//! signer's [`GroupKeyView`], to diff across the fleet when signers disagree on the group key.
+//! The endpoints only ever expose the redacted snapshot, never key material.
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::control::{self, Endpoint, Response, Route};
+use crate::signing_round::{GroupKeyView, RoundSnapshot};
+
+/// The latest snapshot published by the signing loop, `None` until the first round starts
+pub type SharedSnapshot = Arc<Mutex<Option<RoundSnapshot>>>;
+
+/// The `GET /status` control route
+#[derive(Clone, Default)]
+pub struct Status {
+    snapshot: SharedSnapshot,
+}
+
+impl Status {
+    pub fn new(snapshot: SharedSnapshot) -> Self {
+        Self { snapshot }
+    }
+}
+
+impl Route for Status {
+    fn respond(&self, method: &str, path: &str, _body: &str) -> Option<Response> {
+        if method != "GET" || !matches!(path, "/status" | "/group-key") {
+            return None;
+        }
+        let snapshot = match self.snapshot.lock() {
+            Ok(snapshot) => snapshot.clone(),
+            Err(_) => {
+                return Some(Response::json(
+                    "500 Internal Server Error",
+                    "null".to_string(),
+                ))
+            }
+        };
+        Some(match path {
+            "/status" => json(&snapshot),
+            _ => json(&snapshot.and_then(|snapshot| snapshot.group_key)),
+        })
+    }
+}
+
+fn json(value: &impl Serialize) -> Response {
+    match serde_json::to_string(value) {
+        Ok(body) => Response::json("200 OK", body),
+        Err(_) => Response::json("500 Internal Server Error", "null".to_string()),
+    }
+}
+
+/// Ask the signer serving control requests at `endpoint` for its view of the group key,
+/// `None` if it has not completed DKG
+pub fn request_group_key(
+    endpoint: &Endpoint,
+    token: Option<&str>,
+) -> std::io::Result<Option<GroupKeyView>> {
+    let body = control::request(endpoint, token, "GET", "/group-key", "")?;
+    serde_json::from_str(&body).map_err(std::io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_round::SigningRound;
+
+    #[test]
+    fn serves_latest_snapshot() {
+        let snapshot = SharedSnapshot::default();
+        let status = Status::new(snapshot.clone());
+        let response = status.respond("GET", "/status", "").unwrap();
+        assert_eq!(response.status, "200 OK");
+        assert_eq!(response.body, "null");
+
+        let round = SigningRound::new(1, 2, 3, vec![1]);
+        *snapshot.lock().unwrap() = Some(round.snapshot());
+        let response = status.respond("GET", "/status", "").unwrap();
+        assert_eq!(response.status, "200 OK");
+        let served: RoundSnapshot = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(served, round.snapshot());
+    }
+
+    #[test]
+    fn serves_the_group_key_view() {
+        let snapshot = SharedSnapshot::default();
+        let status = Status::new(snapshot.clone());
+        let response = status.respond("GET", "/group-key", "").unwrap();
+        assert_eq!(response.body, "null");
+
+        let mut round = SigningRound::new(1, 2, 3, vec![1]).snapshot();
+        let view = GroupKeyView {
+            signer_id: 3,
+            dkg_id: 1,
+            group_key: "02aa".to_string(),
+            key_ids: vec![1],
+            roster: [(0, 2), (1, 3)].into(),
+            roster_hash: "00".to_string(),
+        };
+        round.group_key = Some(view.clone());
+        *snapshot.lock().unwrap() = Some(round);
+        let response = status.respond("GET", "/group-key", "").unwrap();
+        assert_eq!(response.status, "200 OK");
+        let served: Option<GroupKeyView> = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(served, Some(view));
+    }
+
+    #[test]
+    fn unknown_paths_are_not_found() {
+        let status = Status::default();
+        assert!(status.respond("GET", "/", "").is_none());
+        assert!(status.respond("POST", "/status", "").is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt::Display;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::retry::{Classify, Retry, RetryPolicy};
+
+/// A run lasting at least this long is considered healthy and resets the backoff
+const HEALTHY_RUN: Duration = Duration::from_secs(60);
+
+/// Restarts a long-running loop after transient failures.
+///
+/// Unlike [`RetryPolicy::retry`] there is no attempt limit: the loop is restarted until it
+/// returns `Ok` or fails permanently. Consecutive failures back off according to the policy.
+pub struct Supervisor {
+    backoff: RetryPolicy,
+}
+
+impl Supervisor {
+    pub fn new(backoff: RetryPolicy) -> Self {
+        Self { backoff }
+    }
+
+    /// Run `task` until it finishes or fails with a [`Retry::Permanent`] error
+    pub fn run<E, F>(&self, mut task: F) -> Result<(), E>
+    where
+        E: Classify + Display,
+        F: FnMut() -> Result<(), E>,
+    {
+        let mut failures = 0;
+        loop {
+            let started = Instant::now();
+            match task() {
+                Ok(()) => return Ok(()),
+                Err(e) if e.classify() == Retry::Transient => {
+                    if started.elapsed() >= HEALTHY_RUN {
+                        failures = 0;
+                    }
+                    failures += 1;
+                    let delay = self.backoff.delay(failures);
+                    warn!("Network loop failed: {}. Restarting in {:?}", e, delay);
+                    thread::sleep(delay);
+                    info!("Restarting network loop");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::fmt;
+
+    use super::*;
+    use crate::retry::Backoff;
+
+    #[derive(Debug, PartialEq)]
+    struct TestError(Retry);
+
+    impl Classify for TestError {
+        fn classify(&self) -> Retry {
+            self.0
+        }
+    }
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    fn supervisor() -> Supervisor {
+        Supervisor::new(RetryPolicy {
+            max_attempts: 1,
+            backoff: Backoff::Fixed { interval_ms: 0 },
+        })
+    }
+
+    #[test]
+    fn restarts_after_transient_failures() {
+        let runs = Cell::new(0);
+        let result = supervisor().run(|| {
+            runs.set(runs.get() + 1);
+            if runs.get() < 10 {
+                Err(TestError(Retry::Transient))
+            } else {
+                Ok(())
+            }
+        });
+        // max_attempts does not limit restarts
+        assert_eq!(result, Ok(()));
+        assert_eq!(runs.get(), 10);
+    }
+
+    #[test]
+    fn stops_on_permanent_failure() {
+        let runs = Cell::new(0);
+        let result = supervisor().run(|| {
+            runs.set(runs.get() + 1);
+            if runs.get() < 3 {
+                Err(TestError(Retry::Transient))
+            } else {
+                Err(TestError(Retry::Permanent))
+            }
+        });
+        assert_eq!(result, Err(TestError(Retry::Permanent)));
+        assert_eq!(runs.get(), 3);
+    }
+}
@@ -0,0 +1,138 @@
+//! BIP-341 key path spends.
+//!
+//! A P2TR output pays to the group key tweaked by the output's commitment, `Q = P + t*G` with
+//! `t = hash_TapTweak(P.x || merkle_root)`, and a key path spend of it carries a BIP-340
+//! signature by `Q`. A round signing such a spend applies the tweak as it signs: the challenge
+//! commits to `Q`, and every share weights its key share by the [`TapTweak::key_factor`], which
+//! negates it when BIP-340's lift of the x-only keys flips `P` or `Q`. The tweak is public, so
+//! no share carries it; the coordinator adds [`TapTweak::tweak_term`] to the sum of the shares.
+use serde::{Deserialize, Serialize};
+use wtfrost::{Point, Scalar};
+
+use crate::digest::MessageDigest;
+
+/// BIP-341's domain tag of the tweak
+const TAP_TWEAK_TAG: &str = "TapTweak";
+
+/// The output a signing round signs a key path spend of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPath {
+    /// Merkle root of the output's script tree, `None` for an output without script paths
+    pub merkle_root: Option<[u8; 32]>,
+}
+
+impl KeyPath {
+    /// The tweak of the output of `internal_key`
+    pub fn tweak(&self, internal_key: &Point) -> TapTweak {
+        // BIP-340 keys are x-only, and their point is the one with an even y
+        let internal_negated = !internal_key.has_even_y();
+        let internal_key = if internal_negated {
+            -*internal_key
+        } else {
+            *internal_key
+        };
+        let mut preimage = x_only(&internal_key).to_vec();
+        if let Some(merkle_root) = &self.merkle_root {
+            preimage.extend_from_slice(merkle_root);
+        }
+        let tweak = Scalar::from(*MessageDigest::tagged(TAP_TWEAK_TAG, &preimage).as_bytes());
+        let output_key = internal_key + Point::from(tweak);
+        let output_negated = !output_key.has_even_y();
+        TapTweak {
+            tweak,
+            output_key: if output_negated {
+                -output_key
+            } else {
+                output_key
+            },
+            internal_negated,
+            output_negated,
+        }
+    }
+}
+
+/// The tweak of a group key for one output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TapTweak {
+    /// `t`
+    pub tweak: Scalar,
+    /// The output key, with the even y signatures verify it with
+    pub output_key: Point,
+    /// Whether the group key has an odd y
+    internal_negated: bool,
+    /// Whether `P + t*G` has an odd y
+    output_negated: bool,
+}
+
+impl TapTweak {
+    /// The x-only output key, as the output's script commits to it
+    pub fn output_key_bytes(&self) -> [u8; 32] {
+        x_only(&self.output_key)
+    }
+
+    /// What a share weights its key share by, `-1` if exactly one of the lifts negates a key
+    pub fn key_factor(&self) -> Scalar {
+        if self.internal_negated != self.output_negated {
+            -Scalar::from(1)
+        } else {
+            Scalar::from(1)
+        }
+    }
+
+    /// What the sum of the shares of a signature with challenge `c` lacks
+    pub fn tweak_term(&self, c: &Scalar) -> Scalar {
+        if self.output_negated {
+            -(*c * self.tweak)
+        } else {
+            *c * self.tweak
+        }
+    }
+}
+
+/// The x coordinate of `point`
+fn x_only(point: &Point) -> [u8; 32] {
+    let mut x = [0u8; 32];
+    x.copy_from_slice(&point.compress().as_bytes()[1..]);
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_core::OsRng;
+    use secp256k1::{Secp256k1, XOnlyPublicKey};
+
+    #[test]
+    fn tweaks_match_libsecp256k1() {
+        let secp = Secp256k1::verification_only();
+        for merkle_root in [None, Some([9; 32])] {
+            let key_path = KeyPath { merkle_root };
+            // both parities of the group key, which BIP-340 lifts to the same point
+            let key = Point::from(Scalar::random(&mut OsRng));
+            let tweak = key_path.tweak(&key);
+            let lifted = key_path.tweak(&-key);
+            assert_eq!(
+                (tweak.tweak, tweak.output_key),
+                (lifted.tweak, lifted.output_key)
+            );
+            assert_ne!(tweak.key_factor(), lifted.key_factor());
+
+            let internal = XOnlyPublicKey::from_slice(&x_only(&key)).unwrap();
+            let t = secp256k1::Scalar::from_be_bytes(tweak.tweak.to_bytes()).unwrap();
+            let (output, _) = internal.add_tweak(&secp, &t).unwrap();
+            assert_eq!(output.serialize(), tweak.output_key_bytes());
+            assert!(tweak.output_key.has_even_y());
+        }
+    }
+
+    #[test]
+    fn the_tweak_is_signed_for_by_the_tweaked_secret() {
+        // with the whole secret x of the group key, the shares sum to x' = f*x + t', the
+        // secret of the output key
+        let x = Scalar::random(&mut OsRng);
+        let tweak = KeyPath::default().tweak(&Point::from(x));
+        let c = Scalar::from(1);
+        let secret = tweak.key_factor() * x + tweak.tweak_term(&c);
+        assert_eq!(Point::from(secret), tweak.output_key);
+    }
+}
@@ -0,0 +1,49 @@
+//! The two thresholds of a signer group.
+//!
+//! A group of `total_signers` signers holds `total_keys` keys between them. The key threshold
+//! is cryptographic: any `keys_threshold` keys can sign, fewer cannot. The signer threshold is
+//! about liveness: a round only goes ahead once `signers_threshold` distinct signers take part,
+//! however many keys a smaller set of signers holds. Unset, one signer holding enough keys is
+//! enough, as before the two were told apart.
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// Distinct signers which must take part in a round
+    pub signers: usize,
+    /// Keys whose shares are needed to sign
+    pub keys: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            signers: 1,
+            keys: 1,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Whether `signers` signers holding `keys` keys between them meet both thresholds
+    pub fn met(&self, signers: usize, keys: usize) -> bool {
+        signers >= self.signers && keys >= self.keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_thresholds_must_be_met() {
+        let thresholds = Thresholds {
+            signers: 3,
+            keys: 4,
+        };
+        assert!(thresholds.met(3, 4));
+        // two signers holding every key are not enough signers
+        assert!(!thresholds.met(2, 6));
+        assert!(!thresholds.met(5, 3));
+    }
+}
@@ -0,0 +1,120 @@
+//! Inbound envelopes counted by whether the node sent them itself, a [`crate::control`] route.
+//!
+//! The relay and the bus deliver every broadcast to every reader, the sender included. Processing
+//! our own messages again is wasted work and skews the round counters, so listeners given a
+//! [`Traffic`] drop envelopes carrying our own sender id, unless `accept_own_messages` is set
+//! for loopback tests. StackerDB readers never read their own slot. Envelopes dropped for being
+//! older than `max_message_age_secs`, see [`crate::auth`], are counted too. The counts are served
+//! at `GET /traffic`:
+//!
+//! ```text
+//! {"own":12,"own_dropped":12,"peer":40,"stale":0}
+//! ```
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::control::{Response, Route};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TrafficCounts {
+    /// Envelopes we sent ourselves, delivered back to us
+    pub own: u64,
+    /// Those of `own` which were dropped rather than processed
+    pub own_dropped: u64,
+    /// Envelopes sent by other nodes
+    pub peer: u64,
+    /// Envelopes dropped for being older than the message age window
+    pub stale: u64,
+}
+
+/// Decides which inbound envelopes to process, counting them by sender
+#[derive(Clone, Debug, Default)]
+pub struct Traffic {
+    sender_id: u32,
+    accept_own: bool,
+    counts: Arc<Mutex<TrafficCounts>>,
+}
+
+impl Traffic {
+    /// Traffic of the node sending as `sender_id`, processing its own envelopes if `accept_own`
+    pub fn new(sender_id: u32, accept_own: bool) -> Self {
+        Self {
+            sender_id,
+            accept_own,
+            counts: Arc::default(),
+        }
+    }
+
+    /// Count an authenticated envelope from `sender_id`, returning whether to process it
+    pub fn admit(&self, sender_id: u32) -> bool {
+        let own = sender_id == self.sender_id;
+        let admit = !own || self.accept_own;
+        if let Ok(mut counts) = self.counts.lock() {
+            if !own {
+                counts.peer += 1;
+            } else {
+                counts.own += 1;
+                if !admit {
+                    counts.own_dropped += 1;
+                }
+            }
+        }
+        admit
+    }
+
+    /// Count an envelope dropped for being older than the message age window
+    pub fn drop_stale(&self) {
+        if let Ok(mut counts) = self.counts.lock() {
+            counts.stale += 1;
+        }
+    }
+
+    pub fn counts(&self) -> TrafficCounts {
+        self.counts.lock().map(|counts| *counts).unwrap_or_default()
+    }
+}
+
+impl Route for Traffic {
+    fn respond(&self, method: &str, path: &str, _body: &str) -> Option<Response> {
+        if (method, path) != ("GET", "/traffic") {
+            return None;
+        }
+        Some(match serde_json::to_string(&self.counts()) {
+            Ok(body) => Response::json("200 OK", body),
+            Err(_) => Response::json("500 Internal Server Error", "null".to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_envelopes_are_dropped_unless_accepted() {
+        let traffic = Traffic::new(2, false);
+        assert!(traffic.admit(0));
+        assert!(!traffic.admit(2));
+        assert!(traffic.admit(3));
+        assert_eq!(
+            traffic.counts(),
+            TrafficCounts {
+                own: 1,
+                own_dropped: 1,
+                peer: 2,
+                stale: 0,
+            }
+        );
+
+        let loopback = Traffic::new(2, true);
+        assert!(loopback.admit(2));
+        loopback.drop_stale();
+        let response = loopback.respond("GET", "/traffic", "").unwrap();
+        assert_eq!(
+            response.body,
+            r#"{"own":1,"own_dropped":0,"peer":0,"stale":1}"#
+        );
+        assert!(loopback.respond("GET", "/status", "").is_none());
+    }
+}
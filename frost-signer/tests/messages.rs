@@ -17,7 +17,10 @@ fn dkg_begin() {
     let mut signer = setup_signer(total, total - 1);
     assert_eq!(signer.commitments.len(), 0);
 
-    let dkg_begin_msg = MessageTypes::DkgBegin(DkgBegin { dkg_id: 0 });
+    let dkg_begin_msg = MessageTypes::DkgBegin(DkgBegin {
+        dkg_id: 0,
+        excluded_key_ids: vec![],
+    });
     let msgs = signer.process(dkg_begin_msg).unwrap();
     assert_eq!(msgs.len(), total);
 
@@ -32,7 +35,8 @@ fn signature_share() {
         sign_id: 0,
         dkg_id: 0,
         correlation_id: 0,
-        party_id: 0,
+        signer_id: 1,
+        key_id: 0,
         nonces: [(
             0,
             PublicNonce {
@@ -42,6 +46,7 @@ fn signature_share() {
         )]
         .to_vec(),
         message: vec![],
+        key_path: None,
     };
 
     let msg_share = MessageTypes::SignShareRequest(share);
@@ -4,7 +4,10 @@ use frost_signer::signing_round::{DkgBegin, MessageTypes};
 #[test]
 fn receive_msg() {
     let m1 = Message {
-        msg: MessageTypes::DkgBegin(DkgBegin { dkg_id: 0 }),
+        msg: MessageTypes::DkgBegin(DkgBegin {
+            dkg_id: 0,
+            excluded_key_ids: vec![],
+        }),
         sig: vec![0u8; 64],
     };
 
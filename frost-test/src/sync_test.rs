@@ -2,8 +2,14 @@
 mod tests {
     use std::str::from_utf8;
 
-    use frost_signer::signing_round::SigningRound;
-    use relay_server::Server;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use frost_signer::net::{HttpNet, HttpNetListen, Message, Net, NetListen};
+    use frost_signer::signing_round::{DkgBegin, MessageTypes, SigningRound};
+    use relay_server::{Server, SharedServer};
 
     #[test]
     fn template_test() {
@@ -27,4 +33,44 @@ mod tests {
             assert_eq!(from_utf8(&response).unwrap(), RESPONSE);
         }
     }
+
+    #[test]
+    fn batched_posts_reach_the_relay_as_separate_envelopes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = Arc::new(SharedServer::new(Duration::ZERO));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let server = server.clone();
+                thread::spawn(move || server.update(&mut stream.unwrap()));
+            }
+        });
+
+        let msgs = (0..7)
+            .map(|dkg_id| Message {
+                msg: MessageTypes::DkgBegin(DkgBegin {
+                    dkg_id,
+                    excluded_key_ids: vec![],
+                }),
+                sig: vec![],
+            })
+            .collect();
+        let net = HttpNet::new(url).with_batching(3, 2);
+        net.send_messages(msgs).unwrap();
+
+        let mut listen = HttpNetListen::new(net, vec![]);
+        let mut dkg_ids = vec![];
+        for _ in 0..8 {
+            listen.poll(1);
+            if let Some(Message {
+                msg: MessageTypes::DkgBegin(begin),
+                ..
+            }) = listen.next_message()
+            {
+                dkg_ids.push(begin.dkg_id);
+            }
+        }
+        dkg_ids.sort();
+        assert_eq!(dkg_ids, (0..7).collect::<Vec<_>>());
+    }
 }
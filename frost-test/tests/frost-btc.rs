@@ -1,22 +1,20 @@
 use bitcoin::consensus::{Decodable, Encodable};
 use bitcoin::psbt::serialize::Serialize;
-use bitcoin::schnorr::TweakedPublicKey;
 use bitcoin::secp256k1::{rand, Message};
 use bitcoin::{
     EcdsaSighashType, OutPoint, PackedLockTime, SchnorrSighashType, Script, Transaction,
     XOnlyPublicKey,
 };
+use frost_coordinator::share_verifier::ShareVerifier;
+use frost_signer::lagrange;
+use frost_signer::taproot::KeyPath;
 use frost_test::bitcoind;
 use frost_test::bitcoind::{bitcoind_mine, bitcoind_rpc, gen_config};
 use rand_core::OsRng;
-use wtfrost::common::PolyCommitment;
+use wtfrost::common::{PolyCommitment, PublicNonce};
 use wtfrost::{
-    bip340::{
-        test_helpers::{dkg, sign},
-        Error as Bip340Error, SchnorrProof,
-    },
-    v1::{self, SignatureAggregator},
-    Point,
+    bip340::{test_helpers::dkg, Error as Bip340Error, SchnorrProof},
+    compute, v1, Point,
 };
 
 #[test]
@@ -267,13 +265,13 @@ fn frost_btc() {
     let schnorr_proof = signing_round(
         &signing_payload,
         threshold,
-        total,
         &mut rng,
         &mut signers,
         public_key_shares,
     )
     .unwrap();
-    assert!(schnorr_proof.verify(&group_public_key.x(), &signing_payload));
+    let output_key = KeyPath::default().tweak(&group_public_key).output_key;
+    assert!(schnorr_proof.verify(&output_key.x(), &signing_payload));
 
     let _taproot_sighash_msg = Message::from_slice(&taproot_sighash).unwrap();
     let mut frost_sig_bytes = vec![];
@@ -332,19 +330,17 @@ fn build_peg_in_op_return(
         value: 0,
         script_pubkey: op_return,
     };
-    let _secp = bitcoin::util::key::Secp256k1::new();
+    let secp = bitcoin::util::key::Secp256k1::new();
     // crate type weirdness
     let peg_wallet_address_secp =
         bitcoin::secp256k1::PublicKey::from_slice(&peg_wallet_address.to_bytes()).unwrap();
     let peg_wallet_address_xonly = XOnlyPublicKey::from(peg_wallet_address_secp);
-    //let peg_wallet_address_tweaked = peg_wallet_address_xonly.tap_tweak(&secp, None);
-    let peg_wallet_address_tweaked =
-        TweakedPublicKey::dangerous_assume_tweaked(peg_wallet_address_xonly);
+    // a key path only output, tweaked by BIP-341
+    let taproot = Script::new_v1_p2tr(&secp, peg_wallet_address_xonly, None);
     println!(
-        "build peg-in with shared wallet public key {} tweaked {:?}",
-        peg_wallet_address_secp, peg_wallet_address_tweaked
+        "build peg-in with shared wallet public key {} taproot output {}",
+        peg_wallet_address_secp, taproot
     );
-    let taproot = Script::new_v1_p2tr_tweaked(peg_wallet_address_tweaked);
     let peg_in_output_1 = bitcoin::TxOut {
         value: satoshis,
         script_pubkey: taproot,
@@ -377,25 +373,55 @@ fn build_peg_out(satoshis: u64, user_address: bitcoin::PublicKey, utxo: OutPoint
     }
 }
 
+/// Sign `message` as a key path spend of the taproot output of the group key, whose tweak
+/// every share applies as the frost signers do
 fn signing_round(
     message: &[u8],
     threshold: usize,
-    total: usize,
     rng: &mut OsRng,
     signers: &mut [v1::Signer; 3],
     public_commitments: Vec<PolyCommitment>,
 ) -> Result<SchnorrProof, Bip340Error> {
     // decide which signers will be used
     let mut signers = [signers[0].clone(), signers[1].clone()];
-
-    let (nonces, shares) = sign(message, &mut signers, rng);
-
-    let sig = SignatureAggregator::new(total, threshold, public_commitments.clone())
-        .unwrap()
-        .sign(&message, &nonces, &shares)
-        .unwrap();
-
-    SchnorrProof::new(&sig)
+    let key_path = KeyPath::default();
+    let group_key = public_commitments
+        .iter()
+        .fold(Point::new(), |s, poly| s + poly.A[0]);
+    let tweak = key_path.tweak(&group_key);
+
+    // BIP-340 signatures need an R with an even y
+    let ids: Vec<usize> = signers
+        .iter()
+        .flat_map(|signer| signer.parties.iter().map(|party| party.id))
+        .collect();
+    let nonces: Vec<PublicNonce> = loop {
+        let nonces: Vec<PublicNonce> = signers
+            .iter_mut()
+            .flat_map(|signer| signer.parties.iter_mut())
+            .map(|party| party.gen_nonce(rng))
+            .collect();
+        if compute::intermediate(message, &ids, &nonces).1.has_even_y() {
+            break nonces;
+        }
+    };
+    let shares: Vec<v1::SignatureShare> = signers
+        .iter()
+        .flat_map(|signer| signer.parties.iter())
+        .map(|party| {
+            let nonce = party.save().nonce.unwrap();
+            let lambda = compute::lambda(party.id, &ids);
+            lagrange::sign_key_path(party, &nonce, &lambda, message, &ids, &nonces, &tweak)
+        })
+        .collect();
+    let nonces: Vec<(u32, PublicNonce)> = ids.iter().map(|id| *id as u32).zip(nonces).collect();
+
+    let verifier =
+        ShareVerifier::for_key_path(&public_commitments, threshold, message, &nonces, &key_path);
+    assert!(shares
+        .iter()
+        .all(|share| verifier.verify(share.id as u32, share)));
+    SchnorrProof::new(&verifier.aggregate(shares.iter()))
 }
 
 fn dkg_round(
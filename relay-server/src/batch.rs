@@ -0,0 +1,53 @@
+use std::io::{Error, ErrorKind};
+
+/// The query parameter marking a `POST` as a batch, e.g. `POST /?batch=1`
+pub const BATCH_PARAM: &str = "batch";
+/// The response header a relay sets on batched posts, to the number of messages it queued.
+/// Clients which don't see it are talking to a relay which stored the batch as one message.
+pub const BATCH_HEADER: &str = "x-batch";
+
+/// Frame `messages` into one batch body: each message is preceded by its length as a
+/// big-endian `u32`.
+pub fn encode(messages: &[&[u8]]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(messages.iter().map(|m| m.len() + 4).sum());
+    for message in messages {
+        body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        body.extend_from_slice(message);
+    }
+    body
+}
+
+/// Split a batch body into its messages
+pub fn decode(mut body: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut messages = Vec::new();
+    while !body.is_empty() {
+        let (len, rest) = body
+            .split_first_chunk::<4>()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "truncated batch frame length"))?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len {
+            return Err(Error::new(ErrorKind::InvalidData, "truncated batch frame"));
+        }
+        let (message, rest) = rest.split_at(len);
+        messages.push(message.to_vec());
+        body = rest;
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_round_trip() {
+        let body = encode(&[b"Hello!", b"", b"again"]);
+        assert_eq!(&body[..4], &[0, 0, 0, 6]);
+        assert_eq!(
+            decode(&body).unwrap(),
+            vec![b"Hello!".to_vec(), vec![], b"again".to_vec()]
+        );
+        assert!(decode(&body[..body.len() - 1]).is_err());
+        assert!(decode(&[0, 0]).is_err());
+    }
+}
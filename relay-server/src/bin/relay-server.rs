@@ -1,20 +1,64 @@
-use std::{io::Error, net::TcpListener};
+use std::{env, net::TcpListener, process, sync::Arc, thread};
 
-use relay_server::{IoStream, Server};
+use relay_server::{Config, SharedServer};
 
-pub fn run_server<T: IoStream>(i: &mut impl Iterator<Item = Result<T, Error>>) {
-    let mut server = Server::default();
-    for stream_or_error in i {
-        let f = || server.update(&mut stream_or_error?);
-        if let Err(e) = f() {
-            eprintln!("IO error: {e}");
+const USAGE: &str = "\
+usage: relay-server [<max-wait-secs>]
+       relay-server --config <path>
+       relay-server check-config <path>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => Config::default(),
+        ["check-config", path] => check_config(path),
+        ["--config", path] => Config::from_path(path).unwrap_or_else(|e| {
+            eprintln!("{path}: {e}");
+            process::exit(1);
+        }),
+        // the cap on long polls, as before configs
+        [max_wait] => Config {
+            max_wait_secs: max_wait
+                .parse()
+                .expect("max wait must be a number of seconds"),
+            ..Config::default()
+        },
+        _ => {
+            eprintln!("{USAGE}");
+            process::exit(2);
         }
+    };
+    let addr = &config.bind_addr;
+    let listner = TcpListener::bind(addr).unwrap();
+    println!(
+        "Listening {addr}, holding polls up to {}s...",
+        config.max_wait_secs
+    );
+    let server = Arc::new(SharedServer::from_config(&config));
+    for stream_or_error in listner.incoming() {
+        let server = server.clone();
+        // each connection gets its own thread so that held polls don't block posts
+        thread::spawn(move || {
+            if let Err(e) = stream_or_error.and_then(|mut stream| server.update(&mut stream)) {
+                eprintln!("IO error: {e}");
+            }
+        });
     }
 }
 
-fn main() {
-    let addr = "127.0.0.1:9776";
-    let listner = TcpListener::bind(addr).unwrap();
-    println!("Listening {addr}...");
-    run_server(&mut listner.incoming());
+/// Report every problem with the config at `path` and exit, 0 if there is none
+fn check_config(path: &str) -> ! {
+    match Config::from_path(path) {
+        Ok(_) => {
+            println!("{path}: ok");
+            process::exit(0);
+        }
+        Err(relay_server::config::Error::Invalid(problems)) => {
+            for problem in problems {
+                eprintln!("{path}: {problem}");
+            }
+        }
+        Err(e) => eprintln!("{path}: {e}"),
+    }
+    process::exit(1);
 }
@@ -0,0 +1,172 @@
+//! Configuration of a relay, read from a TOML file.
+//!
+//! Every key is optional; an empty file gives the defaults the relay runs with when started
+//! without a config. Zero disables a limit. See `conf/relay.toml` for an example.
+use std::{collections::BTreeSet, fs, net::SocketAddr, path::Path, time::Duration};
+
+use serde::Deserialize;
+
+/// Address the relay listens on unless configured otherwise
+pub const DEFAULT_BIND_ADDR: &str = "127.0.0.1:9776";
+
+/// The longest a `GET` request may be held open unless configured otherwise
+pub const DEFAULT_MAX_WAIT_SECS: u64 = 30;
+
+/// Publisher keys shorter than this are refused, as they can be guessed
+pub const MIN_PUBLISHER_KEY_LEN: usize = 16;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO Error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("TOML Error: {0}")]
+    TomlError(#[from] toml::de::Error),
+    #[error("Invalid config: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address and port to listen on
+    pub bind_addr: String,
+    /// Cap on the `wait` of long polls, 0 to disable long polling
+    pub max_wait_secs: u64,
+    pub queue: QueueConfig,
+    pub retention: RetentionConfig,
+    /// Keys a `POST` must carry as the password of its basic auth credentials. Anyone may
+    /// post when empty.
+    pub publisher_keys: Vec<String>,
+    /// Namespaces requests may name. Requests naming none, or another one, are refused.
+    /// Any namespace is served when empty.
+    pub namespaces: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_addr: DEFAULT_BIND_ADDR.to_string(),
+            max_wait_secs: DEFAULT_MAX_WAIT_SECS,
+            queue: QueueConfig::default(),
+            retention: RetentionConfig::default(),
+            publisher_keys: Vec::new(),
+            namespaces: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    /// Unread messages kept for each reader. A reader further behind skips the oldest ones.
+    pub max_pending: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Messages kept, dropping the oldest beyond it
+    pub max_messages: usize,
+    /// Seconds a message is kept after it was posted
+    pub max_age_secs: u64,
+}
+
+impl Config {
+    /// Read and validate the config at `path`
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parse and validate a config
+    pub fn parse(toml: &str) -> Result<Self, Error> {
+        let config: Self = toml::from_str(toml)?;
+        match config.problems() {
+            problems if problems.is_empty() => Ok(config),
+            problems => Err(Error::Invalid(problems)),
+        }
+    }
+
+    /// Everything wrong with the config, one line each
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.bind_addr.parse::<SocketAddr>().is_err() {
+            problems.push(format!(
+                "bind_addr {:?} is not an address and port",
+                self.bind_addr
+            ));
+        }
+        let (max_pending, max_messages) = (self.queue.max_pending, self.retention.max_messages);
+        if max_pending > 0 && max_messages > 0 && max_pending > max_messages {
+            problems.push(format!(
+                "queue.max_pending {max_pending} is above retention.max_messages {max_messages}, which already caps it"
+            ));
+        }
+        let mut keys = BTreeSet::new();
+        for (i, key) in self.publisher_keys.iter().enumerate() {
+            if key.len() < MIN_PUBLISHER_KEY_LEN {
+                problems.push(format!(
+                    "publisher_keys[{i}] is shorter than {MIN_PUBLISHER_KEY_LEN} characters"
+                ));
+            }
+            if !keys.insert(key) {
+                problems.push(format!("publisher_keys[{i}] is listed twice"));
+            }
+        }
+        let mut namespaces = BTreeSet::new();
+        for namespace in &self.namespaces {
+            if namespace.is_empty()
+                || !namespace
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+            {
+                problems.push(format!(
+                    "namespace {namespace:?} may only hold letters, digits, '-', '_' and '.'"
+                ));
+            }
+            if !namespaces.insert(namespace) {
+                problems.push(format!("namespace {namespace:?} is listed twice"));
+            }
+        }
+        problems
+    }
+
+    pub fn max_wait(&self) -> Duration {
+        Duration::from_secs(self.max_wait_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_example_config_parses() {
+        let config = Config::parse(include_str!("../conf/relay.toml")).unwrap();
+        assert_eq!(config.bind_addr, "0.0.0.0:9776");
+        assert_eq!(config.queue.max_pending, 10000);
+        assert_eq!(config.retention.max_age_secs, 86400);
+        assert_eq!(config.namespaces, ["testnet", "devnet"]);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn invalid_configs_are_refused_with_every_problem() {
+        let toml = r#"
+            bind_addr = "localhost"
+            publisher_keys = ["short", "short"]
+            namespaces = ["test/net"]
+            [queue]
+            max_pending = 100
+            [retention]
+            max_messages = 10
+        "#;
+        let Err(Error::Invalid(problems)) = Config::parse(toml) else {
+            panic!("invalid config accepted");
+        };
+        assert_eq!(problems.len(), 5, "{problems:?}");
+        assert!(matches!(
+            Config::parse("tls = true"),
+            Err(Error::TomlError(_))
+        ));
+    }
+}
@@ -1,14 +1,19 @@
+pub mod batch;
+pub mod config;
 mod http;
 mod io_stream;
 mod mem_io_stream;
 mod mem_state;
 mod remote_state;
 mod server;
+mod shared_server;
 mod state;
 mod url;
 
+pub use config::Config;
 pub use http::{Request, Response};
 pub use io_stream::IoStream;
 pub use remote_state::RemoteState;
 pub use server::Server;
+pub use shared_server::{SharedServer, LONG_POLL_HEADER};
 pub use state::State;
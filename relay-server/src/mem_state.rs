@@ -1,22 +1,69 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 
-use crate::state::State;
+use crate::{config::Config, state::State};
 
 #[derive(Default)]
 pub struct MemState {
     /// The value for this map is an index for the last read message for this node.
     highwaters: HashMap<String, usize>,
-    queue: Vec<Vec<u8>>,
+    /// Messages with the time they were posted at
+    queue: VecDeque<(Instant, Vec<u8>)>,
+    /// Index of the first message still in `queue`
+    first: usize,
+    /// Unread messages kept for each node, 0 for all of them
+    max_pending: usize,
+    /// Messages kept, 0 for all of them
+    max_messages: usize,
+    max_age: Option<Duration>,
+}
+
+impl MemState {
+    /// A state enforcing the queue caps and retention of `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_pending: config.queue.max_pending,
+            max_messages: config.retention.max_messages,
+            max_age: match config.retention.max_age_secs {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Drop the messages past their retention
+    fn expire(&mut self, now: Instant) {
+        while let Some((posted, _)) = self.queue.front() {
+            let too_old = self
+                .max_age
+                .is_some_and(|max_age| now.duration_since(*posted) > max_age);
+            let too_many = self.max_messages > 0 && self.queue.len() > self.max_messages;
+            if !too_old && !too_many {
+                break;
+            }
+            self.queue.pop_front();
+            self.first += 1;
+        }
+    }
 }
 
 impl State for MemState {
     fn get(&mut self, node_id: String) -> Vec<u8> {
-        let first_unread = self
+        self.expire(Instant::now());
+        let end = self.first + self.queue.len();
+        let mut first_unread = self
             .highwaters
             .get(&node_id)
-            .map_or(0, |last_read| *last_read + 1);
-        let result = self.queue.get(first_unread);
-        if let Some(r) = result {
+            .map_or(0, |last_read| *last_read + 1)
+            .max(self.first);
+        if self.max_pending > 0 {
+            first_unread = first_unread.max(end.saturating_sub(self.max_pending));
+        }
+        let result = self.queue.get(first_unread - self.first);
+        if let Some((_, r)) = result {
             self.highwaters.insert(node_id, first_unread);
             r.clone()
         } else {
@@ -24,7 +71,9 @@ impl State for MemState {
         }
     }
     fn post(&mut self, msg: Vec<u8>) {
-        self.queue.push(msg);
+        let now = Instant::now();
+        self.queue.push_back((now, msg));
+        self.expire(now);
     }
 }
 
@@ -51,4 +100,31 @@ mod tests {
         assert_eq!("Msg # 1".as_bytes().to_vec(), state.get(4.to_string()));
         assert_eq!("Msg # 2".as_bytes().to_vec(), state.get(4.to_string()));
     }
+
+    #[test]
+    fn readers_skip_messages_past_the_caps() {
+        let mut config = crate::config::Config::default();
+        config.queue.max_pending = 2;
+        config.retention.max_messages = 3;
+        let mut state = MemState::from_config(&config);
+        state.post(b"0".to_vec());
+        assert_eq!(state.get("early".to_string()), b"0");
+        for msg in ["1", "2", "3", "4"] {
+            state.post(msg.as_bytes().to_vec());
+        }
+        // "0" and "1" are dropped, and "2" is more than 2 unread messages behind
+        assert_eq!(state.queue.len(), 3);
+        assert_eq!(state.get("early".to_string()), b"3");
+        assert_eq!(state.get("late".to_string()), b"3");
+        assert_eq!(state.get("late".to_string()), b"4");
+        assert!(state.get("late".to_string()).is_empty());
+
+        state.max_age = Some(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(state.get("new".to_string()).is_empty());
+        assert!(state.queue.is_empty());
+        state.max_age = None;
+        state.post(b"5".to_vec());
+        assert_eq!(state.get("early".to_string()), b"5");
+    }
 }
@@ -0,0 +1,189 @@
+use std::{
+    io::{Error, ErrorKind, Write},
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::Config,
+    http::{Message, Request, Response},
+    io_stream::IoStream,
+    mem_io_stream::MemIoStreamEx,
+    server::Server,
+    url::QueryEx,
+};
+
+/// The response header a long-polling relay sets on `GET` responses. Its value is the
+/// number of seconds the request was allowed to wait. Clients which don't see it in a
+/// response should fall back to short polls.
+pub const LONG_POLL_HEADER: &str = "x-long-poll";
+
+/// A [`Server`] which can be shared between connection threads.
+///
+/// A `GET` request with a `wait=<seconds>` query parameter is held open until a message is
+/// available for the node or the wait expires, whichever comes first. The wait is capped at
+/// `max_wait`.
+///
+/// ## Example
+///
+/// ```
+/// let server = relay_server::SharedServer::new(std::time::Duration::from_secs(0));
+/// const REQUEST: &str = "\
+///   GET /?id=alice&wait=10 HTTP/1.0\r\n\
+///   \r\n";
+/// let response = server.call(REQUEST.as_bytes()).unwrap();
+/// const RESPONSE: &str = "\
+///   HTTP/1.0 200 OK\r\n\
+///   x-long-poll:0\r\n\
+///   \r\n";
+/// assert_eq!(std::str::from_utf8(&response).unwrap(), RESPONSE);
+/// ```
+pub struct SharedServer {
+    server: Mutex<Server>,
+    posted: Condvar,
+    max_wait: Duration,
+}
+
+impl SharedServer {
+    pub fn new(max_wait: Duration) -> Self {
+        Self {
+            server: Mutex::default(),
+            posted: Condvar::new(),
+            max_wait,
+        }
+    }
+
+    /// A server enforcing `config`, see [`Server::from_config`]
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            server: Mutex::new(Server::from_config(config)),
+            posted: Condvar::new(),
+            max_wait: config.max_wait(),
+        }
+    }
+
+    pub fn update(&self, io: &mut impl IoStream) -> Result<(), Error> {
+        let request = Request::read(io.istream())?;
+        let response = self.respond(request)?;
+        let ostream = io.ostream();
+        response.write(ostream)?;
+        ostream.flush()?;
+        Ok(())
+    }
+
+    pub fn respond(&self, request: Request) -> Result<Response, Error> {
+        if request.method != "GET" {
+            let response = self.lock()?.respond(&request)?;
+            self.posted.notify_all();
+            return Ok(response);
+        }
+
+        let wait = request
+            .url
+            .url_query()
+            .get("wait")
+            .and_then(|wait| wait.parse().ok())
+            .map_or(Duration::ZERO, Duration::from_secs)
+            .min(self.max_wait);
+        let deadline = Instant::now() + wait;
+        let mut server = self.lock()?;
+        let mut response = loop {
+            let response = server.respond(&request)?;
+            let now = Instant::now();
+            if !response.content.is_empty() || response.code != 200 || now >= deadline {
+                break response;
+            }
+            server = self
+                .posted
+                .wait_timeout(server, deadline - now)
+                .map_err(|_| poisoned())?
+                .0;
+        };
+        response
+            .headers
+            .insert(LONG_POLL_HEADER.to_string(), wait.as_secs().to_string());
+        Ok(response)
+    }
+
+    // TODO: move this function to a `test` mod.
+    pub fn call(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut result = Vec::default();
+        let mut stream = msg.mem_io_stream(&mut result);
+        self.update(&mut stream)?;
+        if stream.i.position() != msg.len() as u64 {
+            return Err(Error::new(ErrorKind::InvalidData, "invalid request"));
+        }
+        Ok(result)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Server>, Error> {
+        self.server.lock().map_err(|_| poisoned())
+    }
+}
+
+fn poisoned() -> Error {
+    Error::other("server state poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::from_utf8, sync::Arc, thread};
+
+    use super::*;
+
+    const POST: &str = "\
+        POST / HTTP/1.0\r\n\
+        Content-Length: 6\r\n\
+        \r\n\
+        Hello!";
+
+    #[test]
+    fn long_poll_returns_when_a_message_is_posted() {
+        let server = Arc::new(SharedServer::new(Duration::from_secs(30)));
+        let poller = {
+            let server = server.clone();
+            thread::spawn(move || {
+                let started = Instant::now();
+                let response = server
+                    .call("GET /?id=x&wait=30 HTTP/1.0\r\n\r\n".as_bytes())
+                    .unwrap();
+                (response, started.elapsed())
+            })
+        };
+        thread::sleep(Duration::from_millis(50));
+        server.call(POST.as_bytes()).unwrap();
+
+        let (response, elapsed) = poller.join().unwrap();
+        let response = from_utf8(&response).unwrap();
+        assert!(response.ends_with("Hello!"));
+        assert!(response.contains("x-long-poll:30\r\n"));
+        assert!(elapsed < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn long_poll_times_out_empty() {
+        let server = SharedServer::new(Duration::from_secs(1));
+        let started = Instant::now();
+        let response = server
+            .call("GET /?id=x&wait=60 HTTP/1.0\r\n\r\n".as_bytes())
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(
+            from_utf8(&response).unwrap(),
+            "HTTP/1.0 200 OK\r\nx-long-poll:1\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn short_poll_without_wait() {
+        let server = SharedServer::new(Duration::from_secs(30));
+        server.call(POST.as_bytes()).unwrap();
+        let response = server
+            .call("GET /?id=x HTTP/1.0\r\n\r\n".as_bytes())
+            .unwrap();
+        assert_eq!(
+            from_utf8(&response).unwrap(),
+            "HTTP/1.0 200 OK\r\nx-long-poll:0\r\ncontent-length:6\r\n\r\nHello!"
+        );
+    }
+}
@@ -462,3 +462,39 @@ Clarinet.test({
         balance.result.expectOk().expectUint(4);
     },
 });
+
+Clarinet.test({
+    name: "Ensure a peg-in txid is only minted once",
+    async fn(chain: Chain, accounts: Map<string, Account>) {
+        const deployer = accounts.get("deployer")!;
+        const alice = accounts.get("wallet_1")!;
+
+        let block = chain.mineBlock([
+            Tx.contractCall("sbtc-alpha", "set-coordinator-data", [types.tuple({addr: types.principal(deployer.address), key: types.buff(0x000000000000000000000000000000000000000000000000000000000000000000)})], deployer.address),
+        ]);
+
+        let [receipt] = block.receipts;
+
+        receipt.result.expectOk().expectBool(true);
+
+        let processed = chain.callReadOnlyFn("sbtc-alpha", "is-peg-in-processed", [types.ascii("txid")], alice.address);
+
+        processed.result.expectBool(false);
+
+        block = chain.mineBlock([
+            Tx.contractCall("sbtc-alpha", "mint!", [types.uint(1234), types.principal(alice.address), types.ascii("txid")], deployer.address),
+            Tx.contractCall("sbtc-alpha", "mint!", [types.uint(1234), types.principal(alice.address), types.ascii("txid")], deployer.address),
+        ]);
+
+        block.receipts[0].result.expectOk().expectBool(true);
+        block.receipts[1].result.expectErr().expectUint(5);
+
+        processed = chain.callReadOnlyFn("sbtc-alpha", "is-peg-in-processed", [types.ascii("txid")], alice.address);
+
+        processed.result.expectBool(true);
+
+        let balance = chain.callReadOnlyFn("sbtc-alpha", "get-balance", [types.principal(alice.address)], alice.address);
+
+        balance.result.expectOk().expectUint(1234);
+    },
+});
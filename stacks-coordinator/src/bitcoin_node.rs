@@ -1,13 +1,107 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use bitcoin::hashes::hex::{Error as HexError, FromHex, ToHex};
+use bitcoin::{BlockHash, OutPoint, Txid};
+use frost_signer::logging::target::WALLET;
+use frost_signer::proxy::Egress;
+use frost_signer::retry::{Classify, Retry, RetryPolicy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tracing::debug;
+
 use crate::bitcoin_node::Error::{RpcMissingResult, RpcResultNotObject};
 
+#[cfg_attr(test, mockall::automock)]
 pub trait BitcoinNode {
-    fn broadcast_transaction(&self, tx: &BitcoinTransaction);
+    /// Submit `tx` to the node's mempool, returning the txid the node accepted it under
+    fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error>;
+    /// Height of the node's best chain
+    fn block_count(&self) -> Result<u64, Error>;
+    fn block(&self, block_height: u64) -> Result<BitcoinBlock, Error>;
+    /// Estimated fee rate for confirmation within `conf_target` blocks, in sats per vbyte
+    fn fee_rate(&self, conf_target: u16) -> Result<u64, Error>;
+    /// Total value in sats of the confirmed outputs paying to `script_pubkey`
+    fn balance(&self, script_pubkey: &bitcoin::Script) -> Result<u64, Error>;
+    /// The confirmed outputs paying to `script_pubkey` as of the tip of the node's best chain
+    fn utxos(&self, script_pubkey: &bitcoin::Script) -> Result<UtxoSnapshot, Error>;
 }
 
 pub type BitcoinTransaction = bitcoin::Transaction;
+pub type BitcoinBlock = bitcoin::Block;
+
+/// An unspent output, with the height of the block which confirmed it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Utxo {
+    #[serde(with = "as_string")]
+    pub outpoint: OutPoint,
+    /// Value in sats
+    pub amount: u64,
+    pub height: u64,
+}
+
+/// The unspent outputs of a script at one block, ordered by outpoint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UtxoSnapshot {
+    pub block_height: u64,
+    pub block_hash: BlockHash,
+    pub utxos: Vec<Utxo>,
+}
+
+/// Serialize bitcoin types as the strings they display as, e.g. `txid:vout` outpoints
+pub(crate) mod as_string {
+    use super::*;
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Sats in `btc`, as bitcoind reports amounts
+fn sats(btc: f64) -> u64 {
+    (btc * 100_000_000.0).round() as u64
+}
+
+/// Boxed nodes, so the coordinator can be given a `Box<dyn BitcoinNode>`
+impl<N: BitcoinNode + ?Sized> BitcoinNode for Box<N> {
+    fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error> {
+        (**self).broadcast_transaction(tx)
+    }
+    fn block_count(&self) -> Result<u64, Error> {
+        (**self).block_count()
+    }
+    fn block(&self, block_height: u64) -> Result<BitcoinBlock, Error> {
+        (**self).block(block_height)
+    }
+    fn fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        (**self).fee_rate(conf_target)
+    }
+    fn balance(&self, script_pubkey: &bitcoin::Script) -> Result<u64, Error> {
+        (**self).balance(script_pubkey)
+    }
+    fn utxos(&self, script_pubkey: &bitcoin::Script) -> Result<UtxoSnapshot, Error> {
+        (**self).utxos(script_pubkey)
+    }
+}
 
 pub struct LocalhostBitcoinNode {
-    _bitcoind_api: String,
+    bitcoind_api: String,
+    retry_policy: RetryPolicy,
+    /// Sends RPC requests to the node, through its proxy if it has one
+    egress: Egress,
 }
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -19,15 +113,134 @@ pub enum Error {
     RpcMissingResult,
     #[error("RPC result not an object")]
     RpcResultNotObject,
+    #[error("RPC result has an unexpected type: {0}")]
+    RpcUnexpectedResult(serde_json::Value),
+    #[error("Hex Error: {0}")]
+    HexError(#[from] HexError),
+    #[error("Bitcoin Decode Error: {0}")]
+    DecodeError(#[from] bitcoin::consensus::encode::Error),
+}
+
+impl Classify for Error {
+    fn classify(&self) -> Retry {
+        match self {
+            Error::IOError(_) => Retry::Transient,
+            Error::HttpError(e) => match e.as_ref() {
+                ureq::Error::Status(code, _) if *code < 500 => Retry::Permanent,
+                _ => Retry::Transient,
+            },
+            Error::RpcMissingResult
+            | Error::RpcResultNotObject
+            | Error::RpcUnexpectedResult(_)
+            | Error::HexError(_)
+            | Error::DecodeError(_) => Retry::Permanent,
+        }
+    }
 }
 
 impl BitcoinNode for LocalhostBitcoinNode {
-    fn broadcast_transaction(&self, _tx: &BitcoinTransaction) {
-        let _todo = self.rpc(&self._bitcoind_api, "sendrawtransaction", [""]); // todo
+    fn broadcast_transaction(&self, tx: &BitcoinTransaction) -> Result<Txid, Error> {
+        let raw_tx = bitcoin::consensus::encode::serialize_hex(tx);
+        let txid = self.rpc(&self.bitcoind_api, "sendrawtransaction", [raw_tx])?;
+        let txid = txid
+            .as_str()
+            .ok_or_else(|| Error::RpcUnexpectedResult(txid.clone()))?;
+        let txid = Txid::from_hex(txid)?;
+        debug!(target: WALLET, "Broadcast bitcoin transaction {}", txid);
+        Ok(txid)
+    }
+
+    fn block_count(&self) -> Result<u64, Error> {
+        let count = self.rpc(&self.bitcoind_api, "getblockcount", ureq::json!([]))?;
+        count.as_u64().ok_or(Error::RpcUnexpectedResult(count))
+    }
+
+    fn block(&self, block_height: u64) -> Result<BitcoinBlock, Error> {
+        let hash = self.rpc(&self.bitcoind_api, "getblockhash", [block_height])?;
+        let hash = hash
+            .as_str()
+            .ok_or_else(|| Error::RpcUnexpectedResult(hash.clone()))?;
+        // verbosity 0 returns the serialized block
+        let block = self.rpc(&self.bitcoind_api, "getblock", ureq::json!([hash, 0]))?;
+        let block = block
+            .as_str()
+            .ok_or_else(|| Error::RpcUnexpectedResult(block.clone()))?;
+        Ok(bitcoin::consensus::deserialize(&Vec::<u8>::from_hex(
+            block,
+        )?)?)
+    }
+
+    fn fee_rate(&self, conf_target: u16) -> Result<u64, Error> {
+        let estimate = self.rpc(&self.bitcoind_api, "estimatesmartfee", [conf_target])?;
+        // BTC per kvB, missing while the node lacks the data for an estimate
+        let btc_per_kvb = estimate["feerate"]
+            .as_f64()
+            .ok_or_else(|| Error::RpcUnexpectedResult(estimate.clone()))?;
+        Ok((btc_per_kvb * 100_000_000.0 / 1_000.0).ceil() as u64)
+    }
+
+    fn balance(&self, script_pubkey: &bitcoin::Script) -> Result<u64, Error> {
+        let scan = self.scan(script_pubkey)?;
+        let btc = scan["total_amount"]
+            .as_f64()
+            .ok_or_else(|| Error::RpcUnexpectedResult(scan.clone()))?;
+        Ok(sats(btc))
+    }
+
+    fn utxos(&self, script_pubkey: &bitcoin::Script) -> Result<UtxoSnapshot, Error> {
+        let scan = self.scan(script_pubkey)?;
+        let unexpected = || Error::RpcUnexpectedResult(scan.clone());
+        let mut utxos = Vec::new();
+        for unspent in scan["unspents"].as_array().ok_or_else(unexpected)? {
+            let (Some(txid), Some(vout), Some(btc), Some(height)) = (
+                unspent["txid"].as_str(),
+                unspent["vout"].as_u64(),
+                unspent["amount"].as_f64(),
+                unspent["height"].as_u64(),
+            ) else {
+                return Err(Error::RpcUnexpectedResult(unspent.clone()));
+            };
+            utxos.push(Utxo {
+                outpoint: OutPoint::new(Txid::from_hex(txid)?, vout as u32),
+                amount: sats(btc),
+                height,
+            });
+        }
+        utxos.sort_by_key(|utxo| utxo.outpoint);
+        let block_hash = scan["bestblock"].as_str().ok_or_else(unexpected)?;
+        Ok(UtxoSnapshot {
+            block_height: scan["height"].as_u64().ok_or_else(unexpected)?,
+            block_hash: BlockHash::from_hex(block_hash)?,
+            utxos,
+        })
     }
 }
 
 impl LocalhostBitcoinNode {
+    pub fn new(bitcoind_api: String, retry_policy: RetryPolicy) -> Self {
+        Self {
+            bitcoind_api,
+            retry_policy,
+            egress: Egress::direct(),
+        }
+    }
+
+    /// Send RPC requests through `egress`, see [`frost_signer::proxy`]
+    pub fn with_egress(mut self, egress: Egress) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Scan the node's UTXO set, so the address need not be in a wallet of the node
+    fn scan(&self, script_pubkey: &bitcoin::Script) -> Result<serde_json::Value, Error> {
+        let descriptor = format!("raw({})", script_pubkey.to_hex());
+        self.rpc(
+            &self.bitcoind_api,
+            "scantxoutset",
+            ureq::json!(["start", [{ "desc": descriptor }]]),
+        )
+    }
+
     fn rpc(
         &self,
         url: &str,
@@ -35,8 +248,10 @@ impl LocalhostBitcoinNode {
         params: impl ureq::serde::Serialize,
     ) -> Result<serde_json::Value, Error> {
         let rpc = ureq::json!({"jsonrpc": "1.0", "id": "stx", "method": method, "params": params});
-        let response = ureq::post(url).send_json(&rpc).map_err(Box::new)?;
-        let json = response.into_json::<serde_json::Value>()?;
+        let json = self.retry_policy.retry(|| {
+            let response = self.egress.post(url).send_json(&rpc).map_err(Box::new)?;
+            Ok::<_, Error>(response.into_json::<serde_json::Value>()?)
+        })?;
         let result = json.as_object().ok_or_else(|| RpcResultNotObject)?;
         let result_str = result.get("result").ok_or_else(|| RpcMissingResult)?;
         Ok(result_str.clone())
@@ -0,0 +1,322 @@
+//! Bitcoin transactions the coordinator broadcast, and how far each has confirmed.
+//!
+//! Every peg-out fulfillment and fee consolidation (sweep) the coordinator broadcasts is written
+//! to a `bitcoin_txs` table next to the peg queue, with its fee, virtual size and the peg ops it
+//! serves. The confirmation watcher, which scans each new bitcoin block for fulfillments, also
+//! marks the block including each of these transactions, so the `bitcoin-txs` view lists their
+//! confirmations as of the last scanned block without asking a node.
+use std::fmt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError};
+use serde::Serialize;
+
+use crate::bitcoin_node::BitcoinTransaction;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Unknown transaction kind {0}")]
+    UnknownKind(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxKind {
+    /// Pays a peg-out request's recipient
+    Fulfillment,
+    /// Sweeps collected fulfillment fees to the operator address
+    Consolidation,
+}
+
+impl TxKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TxKind::Fulfillment => "fulfillment",
+            TxKind::Consolidation => "consolidation",
+        }
+    }
+}
+
+impl fmt::Display for TxKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A broadcast transaction and its confirmation state
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BitcoinTx {
+    pub txid: String,
+    pub kind: TxKind,
+    /// Fee in sats, unknown for fulfillments as the wallet does not track the values it spends
+    pub fee: Option<u64>,
+    pub vsize: u64,
+    /// Op ids of the peg-outs a fulfillment pays, for a consolidation the fee outputs it spends
+    /// as `txid:vout` of the peg-out requests which paid them
+    pub peg_ops: Vec<String>,
+    /// Unix time in seconds it was broadcast at
+    pub broadcast_at: u64,
+    /// Height of the block which included it, once scanned
+    pub confirmed_height: Option<u64>,
+    /// Blocks on top of and including that block, as of the last scanned block
+    pub confirmations: u64,
+}
+
+impl BitcoinTx {
+    /// Fee rate in sats per vbyte, rounded up
+    pub fn fee_rate(&self) -> Option<u64> {
+        self.fee.map(|fee| fee.div_ceil(self.vsize.max(1)))
+    }
+}
+
+impl fmt::Display for BitcoinTx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fee = match (self.fee, self.fee_rate()) {
+            (Some(fee), Some(rate)) => format!("fee={fee} fee_rate={rate}"),
+            _ => "fee=unknown".to_string(),
+        };
+        let status = match self.confirmed_height {
+            Some(height) => format!("confirmations={} height={height}", self.confirmations),
+            None => "unconfirmed".to_string(),
+        };
+        write!(
+            f,
+            "{} {} {status} {fee} vsize={} broadcast_at={} peg_ops={}",
+            self.txid,
+            self.kind,
+            self.vsize,
+            self.broadcast_at,
+            self.peg_ops.join(",")
+        )
+    }
+}
+
+pub struct BitcoinTxLog {
+    conn: RusqliteConnection,
+}
+
+impl BitcoinTxLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute_batch(Self::sql_schema())?;
+        Ok(Self { conn })
+    }
+
+    /// Record the broadcast of `tx`. Recording a transaction twice has no effect.
+    pub fn record(
+        &self,
+        tx: &BitcoinTransaction,
+        kind: TxKind,
+        fee: Option<u64>,
+        peg_ops: &[String],
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_tx(),
+            rusqlite::params![
+                tx.txid().to_string(),
+                kind.as_str(),
+                fee.map(|fee| fee as i64),
+                tx.vsize() as i64,
+                peg_ops.join(","),
+                now() as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Transactions no scanned block included yet
+    pub fn unconfirmed(&self) -> Result<Vec<bitcoin::Txid>, Error> {
+        let txids = self
+            .conn
+            .prepare(Self::sql_select_unconfirmed())?
+            .query_map(rusqlite::params![], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        // only ever written from a Txid
+        Ok(txids.iter().filter_map(|txid| txid.parse().ok()).collect())
+    }
+
+    /// Record that the block at `height` included `txid`
+    pub fn confirm(&self, txid: &bitcoin::Txid, height: u64) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_confirm(),
+            rusqlite::params![txid.to_string(), height as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Every recorded transaction, newest first, with its confirmations as of the block at
+    /// `scanned_height`
+    pub fn list(&self, scanned_height: Option<u64>) -> Result<Vec<BitcoinTx>, Error> {
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_txs())?
+            .query_map(rusqlite::params![], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, i64>(5)?,
+                    row.get::<_, Option<i64>>(6)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(
+                |(txid, kind, fee, vsize, peg_ops, broadcast_at, confirmed_height)| {
+                    let kind = match kind.as_str() {
+                        "fulfillment" => TxKind::Fulfillment,
+                        "consolidation" => TxKind::Consolidation,
+                        _ => return Err(Error::UnknownKind(kind)),
+                    };
+                    let confirmed_height = confirmed_height.map(|height| height as u64);
+                    let confirmations = match (confirmed_height, scanned_height) {
+                        (Some(confirmed), Some(scanned)) if scanned >= confirmed => {
+                            scanned - confirmed + 1
+                        }
+                        _ => 0,
+                    };
+                    Ok(BitcoinTx {
+                        txid,
+                        kind,
+                        fee: fee.map(|fee| fee as u64),
+                        vsize: vsize as u64,
+                        peg_ops: peg_ops
+                            .split(',')
+                            .filter(|op| !op.is_empty())
+                            .map(String::from)
+                            .collect(),
+                        broadcast_at: broadcast_at as u64,
+                        confirmed_height,
+                        confirmations,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS bitcoin_txs (
+            txid TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            fee INTEGER,
+            vsize INTEGER NOT NULL,
+            peg_ops TEXT NOT NULL,
+            broadcast_at INTEGER NOT NULL,
+            confirmed_height INTEGER
+        );
+        "#
+    }
+
+    const fn sql_insert_tx() -> &'static str {
+        r#"
+        INSERT OR IGNORE INTO bitcoin_txs (txid, kind, fee, vsize, peg_ops, broadcast_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        "#
+    }
+
+    const fn sql_select_unconfirmed() -> &'static str {
+        r#"
+        SELECT txid FROM bitcoin_txs WHERE confirmed_height IS NULL
+        "#
+    }
+
+    const fn sql_confirm() -> &'static str {
+        r#"
+        UPDATE bitcoin_txs SET confirmed_height=?2 WHERE txid=?1
+        "#
+    }
+
+    const fn sql_select_txs() -> &'static str {
+        r#"
+        SELECT txid, kind, fee, vsize, peg_ops, broadcast_at, confirmed_height
+        FROM bitcoin_txs ORDER BY broadcast_at DESC, rowid DESC
+        "#
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{OutPoint, PackedLockTime, Script, Transaction, TxIn, TxOut};
+
+    fn tx(value: u64) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn confirmations_count_from_the_last_scanned_block() {
+        let log = BitcoinTxLog::in_memory().unwrap();
+        let (fulfillment, sweep) = (tx(1), tx(2));
+        log.record(
+            &fulfillment,
+            TxKind::Fulfillment,
+            None,
+            &["op-1".to_string()],
+        )
+        .unwrap();
+        log.record(&sweep, TxKind::Consolidation, Some(600), &[])
+            .unwrap();
+        // recorded once only
+        log.record(&sweep, TxKind::Consolidation, Some(600), &[])
+            .unwrap();
+        assert_eq!(log.unconfirmed().unwrap().len(), 2);
+
+        log.confirm(&fulfillment.txid(), 10).unwrap();
+        assert_eq!(log.unconfirmed().unwrap(), vec![sweep.txid()]);
+
+        let txs = log.list(Some(12)).unwrap();
+        assert_eq!(txs.len(), 2);
+        let listed = |txid: bitcoin::Txid| {
+            txs.iter()
+                .find(|tx| tx.txid == txid.to_string())
+                .unwrap()
+                .clone()
+        };
+        let fulfilled = listed(fulfillment.txid());
+        assert_eq!(fulfilled.kind, TxKind::Fulfillment);
+        assert_eq!(fulfilled.peg_ops, vec!["op-1".to_string()]);
+        assert_eq!(
+            (fulfilled.confirmed_height, fulfilled.confirmations),
+            (Some(10), 3)
+        );
+        assert_eq!(fulfilled.fee_rate(), None);
+        let swept = listed(sweep.txid());
+        assert_eq!((swept.confirmed_height, swept.confirmations), (None, 0));
+        assert_eq!(
+            swept.fee_rate(),
+            Some(600u64.div_ceil(sweep.vsize() as u64))
+        );
+        assert!(swept.peg_ops.is_empty());
+    }
+}
@@ -1,9 +1,14 @@
 use crate::bitcoin_node::BitcoinTransaction;
+use crate::fee_ledger::CollectedFee;
+use crate::fulfillment::{self, Template};
 use crate::peg_wallet::{BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError};
+use crate::stacks_node::bitcoin_ops::MAINNET_MAGIC;
 use crate::stacks_node::PegOutRequestOp;
 use bitcoin::hashes::hex::{FromHex, ToHex};
 use bitcoin::hashes::Hash;
-use bitcoin::Script;
+use bitcoin::{Script, TxOut};
+use frost_signer::logging::target::WALLET;
+use tracing::debug;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -11,49 +16,204 @@ pub enum Error {
     ConversionError(#[from] bitcoin::hashes::Error),
     #[error("type conversion error blockstack::bitcoin::hashes:hex {0}")]
     ConversionErrorHex(#[from] bitcoin::hashes::hex::Error),
+    #[error("non-conformant peg-out fulfillment: {0}")]
+    NonConformant(#[from] fulfillment::Error),
+    #[error("{0} sats of fees cannot cover a sweep fee of {1} sats")]
+    InsufficientFees(u64, u64),
+    #[error("output {0} spent by a fulfillment is not an unspent output of the peg wallet")]
+    UnknownSpentOutput(bitcoin::OutPoint),
 }
 
-pub struct BitcoinWallet {}
+/// Smallest output bitcoin nodes relay
+const DUST_LIMIT: u64 = 546;
 
-fn build_transaction(op: &PegOutRequestOp) -> Result<BitcoinTransaction, Error> {
-    let bitcoin_txid = bitcoin::Txid::from_slice(op.txid.as_bytes())?;
-    let utxo = bitcoin::OutPoint {
-        txid: bitcoin_txid,
+/// Virtual size of a sweep spending `inputs` taproot key-path outputs to one taproot output
+pub fn sweep_vsize(inputs: usize) -> u64 {
+    // version, locktime, counts and segwit marker, then 57.5 vbytes per input, 43 for the output
+    11 + (inputs as u64 * 115).div_ceil(2) + 43
+}
+
+/// Virtual size the segwit marker, flag and a key path signature add to a transaction
+const KEY_PATH_WITNESS_VSIZE: u64 = 18;
+
+/// Index of the peg-out request output which pays the fulfillment fee to the peg wallet
+const FEE_VOUT: u32 = 2;
+
+pub struct BitcoinWallet {
+    /// Stacks burn op magic bytes of the bitcoin network
+    magic: [u8; 2],
+}
+
+impl BitcoinWallet {
+    pub fn new(magic: [u8; 2]) -> Self {
+        Self { magic }
+    }
+}
+
+impl Default for BitcoinWallet {
+    fn default() -> Self {
+        Self::new(MAINNET_MAGIC)
+    }
+}
+
+/// Convert a blockstack script to a bitcoin one
+fn to_script(script: &impl ToHex) -> Result<Script, Error> {
+    Ok(Script::from_hex(&script.to_hex())?)
+}
+
+/// Script of the peg wallet `op` asks to pay it out of
+pub fn peg_wallet_script(op: &PegOutRequestOp) -> Result<Script, Error> {
+    to_script(&op.peg_wallet_address.to_bitcoin_tx_out(0).script_pubkey)
+}
+
+/// The outputs a fulfillment of `op` must have
+fn template(op: &PegOutRequestOp, magic: [u8; 2]) -> Result<Template, Error> {
+    Ok(Template {
+        magic,
+        recipient: to_script(&op.recipient.to_bitcoin_tx_out(op.amount).script_pubkey)?,
+        amount: op.amount,
+        peg_wallet: peg_wallet_script(op)?,
+        max_fee: op.fulfillment_fee,
+    })
+}
+
+/// The output of `op` paying its fulfillment fee into the peg wallet
+pub fn collected_fee(op: &PegOutRequestOp, generation: u64) -> Result<CollectedFee, Error> {
+    // stacks keeps txids in display order, bitcoin in internal order
+    let mut txid = op.txid.0;
+    txid.reverse();
+    Ok(CollectedFee {
+        outpoint: bitcoin::OutPoint {
+            txid: bitcoin::Txid::from_slice(&txid)?,
+            vout: FEE_VOUT,
+        },
+        generation,
+        amount: op.fulfillment_fee,
+        script_pubkey: peg_wallet_script(op)?,
+    })
+}
+
+/// The output of the peg wallet the fulfillment of `op` spends
+pub fn spent_outpoint(op: &PegOutRequestOp) -> Result<bitcoin::OutPoint, Error> {
+    Ok(bitcoin::OutPoint {
+        txid: bitcoin::Txid::from_slice(op.txid.as_bytes())?,
         vout: op.vtxindex,
-    };
+    })
+}
+
+fn build_transaction(
+    op: &PegOutRequestOp,
+    template: &Template,
+    chain_tip: [u8; 32],
+    spent: &TxOut,
+) -> Result<BitcoinTransaction, Error> {
     let peg_out_input = bitcoin::TxIn {
-        previous_output: utxo,
+        previous_output: spent_outpoint(op)?,
         script_sig: Default::default(),
         sequence: Default::default(),
         witness: Default::default(),
     };
     //let p2wpk = bitcoin::Script::new_v0_p2wpkh(&user_address.wpubkey_hash().unwrap());
-    let peg_out_output_stx = op.recipient.to_bitcoin_tx_out(op.amount);
-    let peg_out_script = Script::from_hex(&peg_out_output_stx.script_pubkey.to_hex())?;
     let peg_out_output = bitcoin::TxOut {
-        value: peg_out_output_stx.value,
-        script_pubkey: peg_out_script,
+        value: template.amount,
+        script_pubkey: template.recipient.clone(),
     };
-    Ok(bitcoin::blockdata::transaction::Transaction {
-        version: 0,
+    let op_return = fulfillment::op_return(template.magic, chain_tip, &[]);
+    let change = TxOut {
+        value: 0,
+        script_pubkey: template.peg_wallet.clone(),
+    };
+    let mut tx = bitcoin::blockdata::transaction::Transaction {
+        version: 2,
         lock_time: bitcoin::PackedLockTime(0),
         input: vec![peg_out_input],
-        output: vec![peg_out_output],
-    })
+        output: vec![op_return, peg_out_output, change],
+    };
+    // the change goes back to the peg wallet, less the relay fee of the signed transaction; a
+    // remainder too small to relay is left to the fee
+    let fee = tx.vsize() as u64 + KEY_PATH_WITNESS_VSIZE;
+    match spent
+        .value
+        .checked_sub(template.amount)
+        .and_then(|value| value.checked_sub(fee))
+        .filter(|value| *value >= DUST_LIMIT)
+    {
+        Some(value) => tx.output[2].value = value,
+        None => {
+            tx.output.pop();
+        }
+    }
+    Ok(tx)
 }
 
 impl BitcoinWalletTrait for BitcoinWallet {
     type Error = Error;
-    fn fulfill_peg_out(&self, op: &PegOutRequestOp) -> Result<BitcoinTransaction, PegWalletError> {
-        let tx = build_transaction(op)?;
+    fn fulfill_peg_out(
+        &self,
+        op: &PegOutRequestOp,
+        chain_tip: [u8; 32],
+        spent: &TxOut,
+    ) -> Result<BitcoinTransaction, PegWalletError> {
+        let template = template(op, self.magic)?;
+        let tx = build_transaction(op, &template, chain_tip, spent)?;
+        // the fee bounds are checked before anything is signed
+        template
+            .validate(&tx, Some(std::slice::from_ref(spent)))
+            .map_err(Error::from)?;
+        debug!(
+            target: WALLET,
+            "built fulfillment {} of peg-out {}",
+            tx.txid(),
+            op.txid
+        );
         Ok(tx)
     }
+
+    fn sweep_fees(
+        &self,
+        fees: &[CollectedFee],
+        to: &Script,
+        tx_fee: u64,
+    ) -> Result<BitcoinTransaction, PegWalletError> {
+        let total: u64 = fees.iter().map(|fee| fee.amount).sum();
+        let value = total
+            .checked_sub(tx_fee)
+            .filter(|value| *value >= DUST_LIMIT)
+            .ok_or(Error::InsufficientFees(total, tx_fee))?;
+        debug!(
+            target: WALLET,
+            "sweeping {} collected fees totalling {} sats, {} after the fee",
+            fees.len(),
+            total,
+            value
+        );
+        let input = fees
+            .iter()
+            .map(|fee| bitcoin::TxIn {
+                previous_output: fee.outpoint,
+                script_sig: Default::default(),
+                sequence: Default::default(),
+                witness: Default::default(),
+            })
+            .collect();
+        Ok(bitcoin::blockdata::transaction::Transaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input,
+            output: vec![bitcoin::TxOut {
+                value,
+                script_pubkey: to.clone(),
+            }],
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::BitcoinWallet;
+    use super::{collected_fee, peg_wallet_script, BitcoinWallet};
     use crate::peg_wallet::BitcoinWallet as BitcoinWalletTrait;
+    use crate::stacks_node::bitcoin_ops::op_return_data;
+    use bitcoin::TxOut;
     use blockstack_lib::burnchains::Txid;
     use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20};
     use blockstack_lib::types::chainstate::BurnchainHeaderHash;
@@ -63,22 +223,73 @@ mod tests {
 
     #[test]
     fn fufill_peg_out() {
-        let wallet = BitcoinWallet {};
+        let wallet = BitcoinWallet::default();
         let recipient = PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [0x01; 20]);
-        let peg_wallet_address = PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [0x01; 20]);
+        let peg_wallet_address = PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [0x02; 20]);
         let req_op = PegOutRequestOp {
             amount: 1000,
             recipient: recipient,
             signature: MessageSignature([0x00; 65]),
             peg_wallet_address: peg_wallet_address,
-            fulfillment_fee: 0,
+            fulfillment_fee: 500,
             memo: vec![],
             txid: Txid([0x04; 32]),
             vtxindex: 0,
             block_height: 0,
             burn_header_hash: BurnchainHeaderHash([0x00; 32]),
         };
-        let btc_tx = wallet.fulfill_peg_out(&req_op).unwrap();
-        assert_eq!(btc_tx.output[0].value, 1000)
+        let spent = |value| TxOut {
+            value,
+            script_pubkey: peg_wallet_script(&req_op).unwrap(),
+        };
+        let btc_tx = wallet
+            .fulfill_peg_out(&req_op, [0x07; 32], &spent(10_000))
+            .unwrap();
+        assert!(btc_tx.output[0].script_pubkey.is_op_return());
+        let payload = op_return_data(&btc_tx.output[0].script_pubkey).unwrap();
+        assert_eq!(payload[3..35], [0x07; 32]);
+        assert_eq!(btc_tx.output[1].value, 1000);
+        // the rest goes back to the peg wallet, less a fee within what the requester paid
+        assert_eq!(btc_tx.output[2].script_pubkey, spent(0).script_pubkey);
+        let fee = 10_000 - 1000 - btc_tx.output[2].value;
+        assert!((btc_tx.vsize() as u64..=500).contains(&fee));
+
+        // an output left with less than the relay fee cannot pay the fulfillment
+        assert!(wallet
+            .fulfill_peg_out(&req_op, [0x07; 32], &spent(1_100))
+            .is_err());
+    }
+
+    #[test]
+    fn sweep_fees() {
+        let wallet = BitcoinWallet::default();
+        let peg_wallet_address = PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [0x01; 20]);
+        let fees: Vec<_> = [0x04, 0x05]
+            .into_iter()
+            .map(|txid| {
+                let op = PegOutRequestOp {
+                    amount: 1000,
+                    recipient: peg_wallet_address.clone(),
+                    signature: MessageSignature([0x00; 65]),
+                    peg_wallet_address: peg_wallet_address.clone(),
+                    fulfillment_fee: 2000,
+                    memo: vec![],
+                    txid: Txid([txid; 32]),
+                    vtxindex: 0,
+                    block_height: 0,
+                    burn_header_hash: BurnchainHeaderHash([0x00; 32]),
+                };
+                collected_fee(&op, 1).unwrap()
+            })
+            .collect();
+        let to = bitcoin::Script::new_op_return(&[]);
+
+        let tx = wallet.sweep_fees(&fees, &to, 1000).unwrap();
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.input[0].previous_output.vout, 2);
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(tx.output[0].value, 3000);
+
+        assert!(wallet.sweep_fees(&fees, &to, 3500).is_err());
     }
 }
@@ -1,5 +1,9 @@
+use std::path::PathBuf;
+
 use clap::Parser;
 
+use crate::frost_types::GroupPublicKey;
+
 ///Command line interface for stacks coordinator
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -36,4 +40,204 @@ pub enum Command {
     Dkg,
     // Run distributed key generation round then sign a message
     DkgSign,
+    // Deploy a Clarity contract and wait for it to be confirmed
+    DeployContract {
+        /// Path to the Clarity source of the contract
+        #[arg(long)]
+        source: PathBuf,
+
+        /// Contract name to deploy under.
+        /// Defaults to the name of the configured sBTC contract
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Nonce of the deploying account
+        #[arg(long, default_value_t = 0)]
+        nonce: u64,
+
+        /// Seconds to wait for the contract to be confirmed
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
+    },
+    /// Inspect the config files
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print the log filter of the coordinator running with the config file, replacing it
+    /// first if directives are given, e.g. "info,coordinator=debug"
+    LogFilter {
+        /// Comma separated `target=level` pairs, with an optional bare default level
+        directives: Option<String>,
+    },
+    /// Inspect and manage incidents recorded in the coordinator database
+    Incidents {
+        #[clap(subcommand)]
+        action: IncidentsAction,
+    },
+    /// Inspect the DKG and signing rounds recorded in the frost coordinator journal
+    Rounds {
+        #[clap(subcommand)]
+        action: RoundsAction,
+    },
+    /// Show the fee rates sampled by the fee oracle and the rates it selects
+    Fees,
+    /// Show the stages one peg op went through, from the queue to its bitcoin fulfillment
+    Trace {
+        /// The id the op was queued with, as logged by the coordinator
+        op_id: uuid::Uuid,
+    },
+    /// Hold queued peg ops out of processing, e.g. a suspicious peg-out, and release them
+    PegOps {
+        #[clap(subcommand)]
+        action: PegOpsAction,
+    },
+    /// Show the operational mode, or pause and resume the coordinator running on the database
+    Mode {
+        #[clap(subcommand)]
+        action: ModeAction,
+    },
+    /// Restore the peg op history of a lost or corrupted database from chain state. Ops the sBTC
+    /// contract already minted or burned for are marked processed, the others are queued.
+    RebuildDb {
+        /// Burn block height to scan from, e.g. the start_block_height the database was created with
+        #[arg(long)]
+        from_height: u64,
+    },
+    /// List the fulfillment and consolidation transactions the coordinator broadcast, with their
+    /// confirmations as of the last scanned bitcoin block, fee rates and the peg ops they serve
+    BitcoinTxs,
+    /// Take a synthetic peg-out through the pipeline against the configured nodes, printing the
+    /// burn, the fulfillment and its signature. For regtest and staging deployments.
+    SimulatePegOut {
+        /// Bitcoin address the fulfillment pays
+        #[arg(long)]
+        recipient: bitcoin::Address,
+
+        /// Sats paid to the recipient
+        #[arg(long)]
+        amount: u64,
+
+        /// Peg wallet output the fulfillment spends, as txid:vout
+        #[arg(long)]
+        utxo: bitcoin::OutPoint,
+
+        /// Sats paid to the peg wallet for fulfilling the request
+        #[arg(long, default_value_t = 0)]
+        fulfillment_fee: u64,
+
+        /// Sign the fulfillment without broadcasting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Have the signers sign a statement of the peg wallet's unspent outputs and balance at
+    /// the bitcoin node's tip, printed as JSON
+    ProofOfReserve {
+        /// Write the attestation to this file instead of printing it
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check a reserve attestation made by `proof-of-reserve`. Exits nonzero if it does not
+    /// verify.
+    VerifyReserve {
+        /// Path to the attestation JSON
+        file: PathBuf,
+
+        /// Aggregate public key the attestation must be signed by, as hex
+        #[arg(long)]
+        key: Option<GroupPublicKey>,
+    },
+    /// Re-verify every signature in the round history against its generation's aggregate key,
+    /// and the confirmed bitcoin transactions against the signatures they carry. Prints each
+    /// discrepancy and exits nonzero if there is any.
+    VerifyHistory,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Check the coordinator and signer config files for unknown keys and invalid values.
+    /// Prints each problem with its line and exits nonzero if any are found.
+    Lint,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum IncidentsAction {
+    /// List open incidents
+    List {
+        /// Include resolved incidents
+        #[arg(long)]
+        all: bool,
+    },
+    /// Attach a note to an incident
+    Annotate {
+        id: i64,
+        /// Who is writing the note
+        #[arg(long)]
+        author: String,
+        note: String,
+    },
+    /// Mark an incident resolved with a closing note
+    Resolve {
+        id: i64,
+        /// Who is resolving the incident
+        #[arg(long)]
+        author: String,
+        note: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum PegOpsAction {
+    /// List the ops held by an operator
+    Held,
+    /// Hold a queued op until it is released. Ops already being processed cannot be held.
+    Hold {
+        /// The id the op was queued with, as logged by the coordinator
+        op_id: uuid::Uuid,
+        /// Why the op is held, recorded in its trace
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    /// Queue a held op to be processed again
+    Release {
+        op_id: uuid::Uuid,
+        /// Why the op is released, recorded in its trace
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum ModeAction {
+    /// Show the mode and its last change
+    Show,
+    /// Stop signing peg ops and sweeping fees, until resumed. Lasts across restarts.
+    Pause {
+        /// Who is pausing the coordinator
+        #[arg(long)]
+        author: String,
+        reason: String,
+    },
+    /// Leave the paused mode, for whichever mode the coordinator's health calls for
+    Resume {
+        /// Who is resuming the coordinator
+        #[arg(long)]
+        author: String,
+        reason: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum RoundsAction {
+    /// List the rounds started within a window, oldest first
+    List {
+        /// Unix time in seconds the window starts at
+        #[arg(long)]
+        since: Option<u64>,
+        /// Unix time in seconds the window ends before
+        #[arg(long)]
+        until: Option<u64>,
+    },
+    /// Show one round
+    Show { id: i64 },
 }
@@ -1,34 +1,72 @@
 use bitcoin::{
-    psbt::Prevouts, secp256k1::Error as Secp256k1Error, util::sighash::Error as SighashError,
-    SchnorrSighashType, XOnlyPublicKey,
+    hashes::Hash, psbt::Prevouts, secp256k1::Error as Secp256k1Error,
+    util::sighash::Error as SighashError, SchnorrSighashType, Script, TxOut, XOnlyPublicKey,
 };
 
-use frost_coordinator::{coordinator::Error as FrostCoordinatorError, create_coordinator};
-use frost_signer::net::{Error as HttpNetError, HttpNetListen};
+use frost_coordinator::events::RoundEvents;
+use frost_coordinator::history::Round;
+use frost_coordinator::quorum::SigningQuorum;
+use frost_coordinator::{
+    coordinator::Error as FrostCoordinatorError, create_coordinator, create_coordinator_with_key,
+};
+use frost_signer::digest::MessageDigest;
+use frost_signer::failure::{ErrorClass, FailureReport};
+use frost_signer::logging::target::COORDINATOR;
+use frost_signer::net::{Error as HttpNetError, TransportNetListen};
+use frost_signer::proxy::Error as ProxyError;
+use frost_signer::retention::Retention;
+use frost_signer::taproot::KeyPath;
+use frost_signer::thresholds::Thresholds;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::sync::mpsc;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{Receiver, Sender};
 use std::{thread, time};
-use tracing::info;
-use wtfrost::{bip340::SchnorrProof, common::Signature};
+use tracing::{info, info_span, warn};
 
-use crate::bitcoin_wallet::BitcoinWallet;
+use crate::bitcoin_txs::{BitcoinTxLog, Error as BitcoinTxsError, TxKind};
+use crate::bitcoin_wallet::{
+    collected_fee, peg_wallet_script, spent_outpoint, sweep_vsize, BitcoinWallet,
+    Error as BitcoinWalletError,
+};
 use crate::config::{Config, Error as ConfigError};
+use crate::fee_ledger::{CollectedFee, Error as FeeLedgerError, FeeLedger, FeeSweepPolicy};
+use crate::fee_oracle::{Chain, Error as FeeOracleError, FeeOracle};
+use crate::frost_types::{
+    Error as FrostTypesError, GroupPublicKey, PartyCommitment, ThresholdSignature,
+};
+use crate::fulfillment;
+use crate::handle::{CoordinatorStatus, Request};
+use crate::history_audit::Error as HistoryAuditError;
+use crate::incident_log::{Error as IncidentLogError, IncidentKind, IncidentLog, NewIncident};
+use crate::mode::{Error as ModeError, Mode, ModeStore, HEALTH_CHECK};
+use crate::peg_latency::{Error as PegLatencyError, PegLatency};
 use crate::peg_wallet::{
     BitcoinWallet as BitcoinWalletTrait, Error as PegWalletError, PegWallet,
     StacksWallet as StacksWalletTrait, WrapPegWallet,
 };
-use crate::stacks_node::{self, Error as StacksNodeError};
+use crate::proof_of_reserve::{Attestation, Error as ProofOfReserveError, ReserveStatement};
+use crate::stacks_node::{self, clarity, Error as StacksNodeError};
 use crate::stacks_wallet::StacksWallet;
 // Traits in scope
-use crate::bitcoin_node::{BitcoinNode, BitcoinTransaction, LocalhostBitcoinNode};
+use crate::bitcoin_node::{
+    BitcoinNode, BitcoinTransaction, Error as BitcoinNodeError, LocalhostBitcoinNode,
+};
 use crate::peg_queue::{
-    Error as PegQueueError, PegQueue, SbtcOp, SqlitePegQueue, SqlitePegQueueError,
+    Error as PegQueueError, OpId, OpKind, PegQueue, SbtcOp, SqlitePegQueue, SqlitePegQueueError,
+    Stage, TracedOp,
 };
+use crate::simulate::{self, Error as SimulateError, SimulatedPegOut};
+use crate::stacks_node::bitcoin_ops::MAINNET_MAGIC;
 use crate::stacks_node::client::NodeClient;
+use crate::stacks_node::contract_events::{ContractEventsNode, EventsClient};
+use crate::stacks_node::fallback::FallbackNode;
 use crate::stacks_node::StacksNode;
+use crate::stacks_transaction::Error as StacksTransactionError;
 use crate::stacks_wallet::Error as StacksWalletError;
 
-type FrostCoordinator = frost_coordinator::coordinator::Coordinator<HttpNetListen>;
+/// The frost coordinator talking to the signers over the network
+pub type NetFrostCoordinator = frost_coordinator::coordinator::Coordinator<TransportNetListen>;
 
 pub type PublicKey = XOnlyPublicKey;
 
@@ -69,72 +107,1081 @@ pub enum Error {
     UnexpectedSenderDisconnect(#[from] std::sync::mpsc::RecvError),
     #[error("Stacks Node Error: {0}")]
     StacksNodeError(#[from] StacksNodeError),
+    /// Error occurred reading blocks from the bitcoin node
+    #[error("Bitcoin Node Error: {0}")]
+    BitcoinNodeError(#[from] BitcoinNodeError),
+    /// A configured proxy is unusable
+    #[error("Proxy Error: {0}")]
+    ProxyError(#[from] ProxyError),
+    /// Error occurred converting a stacks.js transaction
+    #[error("Stacks Transaction Error: {0}")]
+    StacksTransactionError(#[from] StacksTransactionError),
+    /// The deployed contract was not confirmed in time
+    #[error("Contract {0} was not confirmed within {1:?}")]
+    ContractDeployTimeout(String, time::Duration),
+    /// The coordinator loop exited before answering a request
+    #[error("Coordinator stopped")]
+    CoordinatorStopped,
+    /// Error occurred reading or writing the incident log
+    #[error("Incident Log Error: {0}")]
+    IncidentLogError(#[from] IncidentLogError),
+    /// Error occurred reading or writing the fee ledger
+    #[error("Fee Ledger Error: {0}")]
+    FeeLedgerError(#[from] FeeLedgerError),
+    /// Error occurred reading or writing the fee rate samples
+    #[error("Fee Oracle Error: {0}")]
+    FeeOracleError(#[from] FeeOracleError),
+    /// Error occurred reading or writing the peg latencies
+    #[error("Peg Latency Error: {0}")]
+    PegLatencyError(#[from] PegLatencyError),
+    /// Error occurred reading or writing the broadcast bitcoin transactions
+    #[error("Bitcoin Txs Error: {0}")]
+    BitcoinTxsError(#[from] BitcoinTxsError),
+    /// A DKG or signing result could not be converted to its public type
+    #[error("Frost Types Error: {0}")]
+    FrostTypesError(#[from] FrostTypesError),
+    /// A synthetic peg-out could not be built
+    #[error("Simulation Error: {0}")]
+    SimulateError(#[from] SimulateError),
+    /// Error occurred reading or changing the operational mode
+    #[error("Mode Error: {0}")]
+    ModeError(#[from] ModeError),
+    /// The signers' reserve attestation did not verify
+    #[error("Proof Of Reserve Error: {0}")]
+    ProofOfReserveError(#[from] ProofOfReserveError),
+    /// The signature history could not be read to verify it
+    #[error("History Audit Error: {0}")]
+    HistoryAuditError(#[from] HistoryAuditError),
+}
+
+impl Error {
+    /// What an orchestrator should make of the coordinator stopping on this error
+    pub fn error_class(&self) -> ErrorClass {
+        match self {
+            Error::ConfigError(_) | Error::ProxyError(_) => ErrorClass::Config,
+            // a node, the relay or the signers stayed unreachable past the retry policy
+            Error::HttpNetError(_)
+            | Error::StacksNodeError(_)
+            | Error::BitcoinNodeError(_)
+            | Error::FrostCoordinatorError(_)
+            | Error::ContractDeployTimeout(..)
+            | Error::PegQueueError(PegQueueError::StacksNodeError(_)) => ErrorClass::Unavailable,
+            Error::PegQueueError(PegQueueError::SqlitePegQueueError(_))
+            | Error::SqlitePegQueueError(_)
+            | Error::IncidentLogError(_)
+            | Error::FeeLedgerError(_)
+            | Error::FeeOracleError(_)
+            | Error::PegLatencyError(_)
+            | Error::BitcoinTxsError(_)
+            | Error::HistoryAuditError(_)
+            | Error::ModeError(_) => ErrorClass::Storage,
+            Error::PegWalletError(_)
+            | Error::StacksWalletError(_)
+            | Error::BitcoinSecp256k1(_)
+            | Error::BitcoinSighash(_)
+            | Error::UnexpectedSenderDisconnect(_)
+            | Error::StacksTransactionError(_)
+            | Error::CoordinatorStopped
+            | Error::FrostTypesError(_)
+            | Error::SimulateError(_)
+            | Error::ProofOfReserveError(_) => ErrorClass::Internal,
+        }
+    }
+}
+
+/// The DKG and signing rounds the coordinator runs, with results as [`frost_types`](crate::frost_types).
+/// Implemented by the networked frost coordinator and by
+/// [`MockFrostCoordinator`](crate::mock_frost_coordinator::MockFrostCoordinator)
+pub trait FrostCoordinator {
+    fn run_distributed_key_generation(&mut self) -> Result<GroupPublicKey>;
+    fn get_aggregate_public_key(&self) -> Result<GroupPublicKey>;
+    /// Sign `digest` as is, see [`frost_signer::digest`]
+    fn sign_digest(&mut self, digest: &MessageDigest) -> Result<ThresholdSignature>;
+    /// Sign every digest, in a single round where the coordinator supports it
+    fn sign_digests(&mut self, digests: &[MessageDigest]) -> Result<Vec<ThresholdSignature>> {
+        digests
+            .iter()
+            .map(|digest| self.sign_digest(digest))
+            .collect()
+    }
+    /// Sign `digest`, the sighash of a taproot key path spend of the output of `key_path`, with
+    /// the aggregate key tweaked for that output, see [`frost_signer::taproot`]
+    fn sign_key_path(
+        &mut self,
+        digest: &MessageDigest,
+        key_path: &KeyPath,
+    ) -> Result<ThresholdSignature>;
+    /// Sign every digest as [`FrostCoordinator::sign_key_path`] does, in a single round where
+    /// the coordinator supports it
+    fn sign_digests_key_path(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: &KeyPath,
+    ) -> Result<Vec<ThresholdSignature>> {
+        digests
+            .iter()
+            .map(|digest| self.sign_key_path(digest, key_path))
+            .collect()
+    }
+    /// Commitments of the keys which took part in the last DKG round, empty before any
+    fn party_commitments(&self) -> Result<Vec<PartyCommitment>>;
+    fn current_dkg_id(&self) -> u64;
+    fn current_sign_id(&self) -> u64;
+    fn round_timeouts(&self) -> u64;
+    /// The signer and key thresholds rounds are held to
+    fn thresholds(&self) -> Thresholds;
+    fn excluded_signers(&self) -> Vec<usize>;
+    fn readmit_signers(&mut self);
+    /// Hold the signing rounds to come to `quorum`, see [`frost_coordinator::quorum`]
+    fn set_signing_quorum(&mut self, quorum: SigningQuorum) -> Result<()>;
+    fn signing_quorum(&self) -> SigningQuorum;
+    /// Usage of the artifacts pruned in the background, e.g. the frost coordinator's journal
+    fn retention(&self) -> Retention;
+    /// Finished rounds started within `since..until`, in unix seconds
+    fn list_rounds(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Round>>;
+    fn get_round(&self, id: i64) -> Result<Option<Round>>;
+    /// Live progress of the DKG and signing rounds, see [`frost_coordinator::events`]
+    fn round_events(&self) -> RoundEvents;
 }
 
-pub trait Coordinator: Sized {
+impl FrostCoordinator for NetFrostCoordinator {
+    fn run_distributed_key_generation(&mut self) -> Result<GroupPublicKey> {
+        let key = NetFrostCoordinator::run_distributed_key_generation(self)?;
+        Ok(GroupPublicKey::try_from(&key)?)
+    }
+
+    fn get_aggregate_public_key(&self) -> Result<GroupPublicKey> {
+        let key = NetFrostCoordinator::get_aggregate_public_key(self)?;
+        Ok(GroupPublicKey::try_from(&key)?)
+    }
+
+    fn sign_digest(&mut self, digest: &MessageDigest) -> Result<ThresholdSignature> {
+        let (_frost_sig, schnorr_proof) = NetFrostCoordinator::sign_digest(self, digest)?;
+        Ok(ThresholdSignature::from(&schnorr_proof))
+    }
+
+    fn sign_digests(&mut self, digests: &[MessageDigest]) -> Result<Vec<ThresholdSignature>> {
+        Ok(NetFrostCoordinator::sign_digests(self, digests)?
+            .iter()
+            .map(|(_frost_sig, schnorr_proof)| ThresholdSignature::from(schnorr_proof))
+            .collect())
+    }
+
+    fn sign_key_path(
+        &mut self,
+        digest: &MessageDigest,
+        key_path: &KeyPath,
+    ) -> Result<ThresholdSignature> {
+        let (_frost_sig, schnorr_proof) =
+            NetFrostCoordinator::sign_key_path(self, digest, key_path)?;
+        Ok(ThresholdSignature::from(&schnorr_proof))
+    }
+
+    fn sign_digests_key_path(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: &KeyPath,
+    ) -> Result<Vec<ThresholdSignature>> {
+        Ok(
+            NetFrostCoordinator::sign_digests_key_path(self, digests, key_path)?
+                .iter()
+                .map(|(_frost_sig, schnorr_proof)| ThresholdSignature::from(schnorr_proof))
+                .collect(),
+        )
+    }
+
+    fn party_commitments(&self) -> Result<Vec<PartyCommitment>> {
+        if NetFrostCoordinator::get_aggregate_public_key(self).is_err() {
+            return Ok(vec![]);
+        }
+        Ok(NetFrostCoordinator::party_commitments(self)
+            .map(|(key_id, commitment)| PartyCommitment::new(key_id, commitment))
+            .collect::<std::result::Result<_, _>>()?)
+    }
+
+    fn current_dkg_id(&self) -> u64 {
+        NetFrostCoordinator::current_dkg_id(self)
+    }
+
+    fn current_sign_id(&self) -> u64 {
+        NetFrostCoordinator::current_sign_id(self)
+    }
+
+    fn round_timeouts(&self) -> u64 {
+        NetFrostCoordinator::round_timeouts(self)
+    }
+
+    fn thresholds(&self) -> Thresholds {
+        NetFrostCoordinator::thresholds(self)
+    }
+
+    fn excluded_signers(&self) -> Vec<usize> {
+        NetFrostCoordinator::excluded_signers(self)
+    }
+
+    fn readmit_signers(&mut self) {
+        NetFrostCoordinator::readmit_signers(self)
+    }
+
+    fn set_signing_quorum(&mut self, quorum: SigningQuorum) -> Result<()> {
+        Ok(NetFrostCoordinator::set_signing_quorum(self, quorum)?)
+    }
+
+    fn signing_quorum(&self) -> SigningQuorum {
+        NetFrostCoordinator::signing_quorum(self).clone()
+    }
+
+    fn retention(&self) -> Retention {
+        NetFrostCoordinator::retention(self).clone()
+    }
+
+    fn list_rounds(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Round>> {
+        Ok(NetFrostCoordinator::list_rounds(self, since, until)?)
+    }
+
+    fn get_round(&self, id: i64) -> Result<Option<Round>> {
+        Ok(NetFrostCoordinator::get_round(self, id)?)
+    }
+
+    fn round_events(&self) -> RoundEvents {
+        NetFrostCoordinator::round_events(self).clone()
+    }
+}
+
+impl<F: FrostCoordinator + ?Sized> FrostCoordinator for Box<F> {
+    fn run_distributed_key_generation(&mut self) -> Result<GroupPublicKey> {
+        (**self).run_distributed_key_generation()
+    }
+    fn get_aggregate_public_key(&self) -> Result<GroupPublicKey> {
+        (**self).get_aggregate_public_key()
+    }
+    fn sign_digest(&mut self, digest: &MessageDigest) -> Result<ThresholdSignature> {
+        (**self).sign_digest(digest)
+    }
+    fn sign_digests(&mut self, digests: &[MessageDigest]) -> Result<Vec<ThresholdSignature>> {
+        (**self).sign_digests(digests)
+    }
+    fn sign_key_path(
+        &mut self,
+        digest: &MessageDigest,
+        key_path: &KeyPath,
+    ) -> Result<ThresholdSignature> {
+        (**self).sign_key_path(digest, key_path)
+    }
+    fn sign_digests_key_path(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: &KeyPath,
+    ) -> Result<Vec<ThresholdSignature>> {
+        (**self).sign_digests_key_path(digests, key_path)
+    }
+    fn party_commitments(&self) -> Result<Vec<PartyCommitment>> {
+        (**self).party_commitments()
+    }
+    fn current_dkg_id(&self) -> u64 {
+        (**self).current_dkg_id()
+    }
+    fn current_sign_id(&self) -> u64 {
+        (**self).current_sign_id()
+    }
+    fn round_timeouts(&self) -> u64 {
+        (**self).round_timeouts()
+    }
+    fn thresholds(&self) -> Thresholds {
+        (**self).thresholds()
+    }
+    fn excluded_signers(&self) -> Vec<usize> {
+        (**self).excluded_signers()
+    }
+    fn readmit_signers(&mut self) {
+        (**self).readmit_signers()
+    }
+    fn set_signing_quorum(&mut self, quorum: SigningQuorum) -> Result<()> {
+        (**self).set_signing_quorum(quorum)
+    }
+    fn signing_quorum(&self) -> SigningQuorum {
+        (**self).signing_quorum()
+    }
+    fn retention(&self) -> Retention {
+        (**self).retention()
+    }
+    fn list_rounds(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Round>> {
+        (**self).list_rounds(since, until)
+    }
+    fn get_round(&self, id: i64) -> Result<Option<Round>> {
+        (**self).get_round(id)
+    }
+    fn round_events(&self) -> RoundEvents {
+        (**self).round_events()
+    }
+}
+
+/// The coordinator loop and its request handling, over injected peg queue, wallet, nodes and
+/// frost coordinator. Object-safe once the associated types are named: only the methods which
+/// consume the coordinator to run its loop need a sized implementation.
+pub trait Coordinator {
     type PegQueue: PegQueue;
     type FeeWallet: PegWallet;
     type StacksNode: StacksNode;
     type BitcoinNode: BitcoinNode;
+    type FrostCoordinator: FrostCoordinator;
 
     // Required methods
     fn peg_queue(&self) -> &Self::PegQueue;
     fn fee_wallet(&mut self) -> &mut Self::FeeWallet;
-    fn frost_coordinator(&self) -> &FrostCoordinator;
-    fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator;
+    fn frost_coordinator(&self) -> &Self::FrostCoordinator;
+    fn frost_coordinator_mut(&mut self) -> &mut Self::FrostCoordinator;
     fn stacks_node(&self) -> &Self::StacksNode;
     fn bitcoin_node(&self) -> &Self::BitcoinNode;
+    fn incident_log(&self) -> &IncidentLog;
+    fn fee_ledger(&self) -> &FeeLedger;
+    fn fee_sweep_policy(&self) -> Option<&FeeSweepPolicy>;
+    fn fee_oracle(&self) -> Option<&FeeOracle>;
+    fn peg_latency(&self) -> &PegLatency;
+    fn bitcoin_txs(&self) -> &BitcoinTxLog;
+    fn operational_mode(&self) -> &ModeStore;
 
     // Provided methods
-    fn run(mut self) -> Result<()> {
+    /// File the report of a fatal error is written to, see [`frost_signer::failure`]
+    fn failure_report_path(&self) -> Option<&str> {
+        None
+    }
+
+    /// Report of `error` stopping the coordinator, with the rounds in flight and the last
+    /// heights processed
+    fn failure_report(&self, error: &Error) -> FailureReport {
+        let frost_coordinator = self.frost_coordinator();
+        let program = concat!("stacks-coordinator ", env!("CARGO_PKG_VERSION"));
+        let report = FailureReport::new(program, error.error_class(), error)
+            .with_round("dkg_id", frost_coordinator.current_dkg_id())
+            .with_round("sign_id", frost_coordinator.current_sign_id());
+        // best effort, the error may well be the database these are read from
+        let report = match self.peg_queue().observed_block_height() {
+            Some(height) => report.with_height("burn_block_height", height),
+            None => report,
+        };
+        match self.peg_latency().scanned_height() {
+            Ok(Some(height)) => report.with_height("bitcoin_scanned_height", height),
+            _ => report,
+        }
+    }
+
+    fn run(self) -> Result<()>
+    where
+        Self: Sized,
+    {
         let (sender, receiver) = mpsc::channel::<Command>();
+        self.run_with_channel(sender, receiver)
+    }
+
+    /// Run the coordinator loop on an existing command channel.
+    /// `sender` feeds the poll timer; other senders may submit requests (see [`CoordinatorHandle`](crate::handle::CoordinatorHandle))
+    fn run_with_channel(
+        mut self,
+        sender: Sender<Command>,
+        receiver: Receiver<Command>,
+    ) -> Result<()>
+    where
+        Self: Sized,
+    {
         Self::poll_ping_thread(sender);
 
+        let result = self.run_loop(&receiver);
+        if let (Err(e), Some(path)) = (&result, self.failure_report_path()) {
+            if let Err(write_error) = self.failure_report(e).write(path) {
+                warn!(
+                    "Failed to write the failure report to {}: {}",
+                    path, write_error
+                );
+            }
+        }
+        result
+    }
+
+    fn run_loop(&mut self, receiver: &Receiver<Command>) -> Result<()> {
         loop {
             match receiver.recv()? {
                 Command::Stop => break,
                 Command::Timeout => {
-                    self.peg_queue().poll(self.stacks_node())?;
-                    self.process_queue()?;
+                    let mode = self.check_mode()?;
+                    let result = self
+                        .peg_queue()
+                        .poll(self.stacks_node())
+                        .map_err(Error::from)
+                        .and_then(|_| self.reconcile_peg_ops())
+                        .and_then(|_| self.sample_fees())
+                        .and_then(|_| {
+                            // a paused or bootstrapping coordinator signs nothing
+                            if mode.processes_queue() {
+                                self.process_queue()
+                            } else {
+                                Ok(())
+                            }
+                        })
+                        .and_then(|_| self.confirm_fulfillments())
+                        .and_then(|_| {
+                            if mode.sweeps_fees() {
+                                self.sweep_fees()
+                            } else {
+                                Ok(())
+                            }
+                        });
+                    self.record_incident(result)?;
                 }
+                Command::Request(Request::Shutdown(reply)) => {
+                    let _ = reply.send(Ok(()));
+                    break;
+                }
+                Command::Request(request) => self.handle_request(request),
             }
         }
         Ok(())
     }
 
-    fn poll_ping_thread(sender: Sender<Command>) {
-        thread::spawn(move || loop {
-            sender
-                .send(Command::Timeout)
-                .expect("thread send error {0}");
-            thread::sleep(time::Duration::from_millis(500));
+    fn poll_ping_thread(sender: Sender<Command>)
+    where
+        Self: Sized,
+    {
+        thread::spawn(move || {
+            // Stop pinging once the coordinator loop has exited
+            while sender.send(Command::Timeout).is_ok() {
+                thread::sleep(time::Duration::from_millis(500));
+            }
         });
     }
 
+    fn handle_request(&mut self, request: Request) {
+        // A dropped reply only means the caller stopped waiting
+        match request {
+            Request::SubmitPegOp(op, reply) => {
+                let _ = reply.send(self.peg_queue().submit(*op).map_err(Error::from));
+            }
+            Request::HoldPegOp(op_id, reason, reply) => {
+                let _ = reply.send(self.peg_queue().hold(&op_id, &reason).map_err(Error::from));
+            }
+            Request::ReleasePegOp(op_id, reason, reply) => {
+                let _ = reply.send(
+                    self.peg_queue()
+                        .release(&op_id, &reason)
+                        .map_err(Error::from),
+                );
+            }
+            Request::HeldPegOps(reply) => {
+                let _ = reply.send(self.peg_queue().held().map_err(Error::from));
+            }
+            Request::Pause(author, reason, reply) => {
+                let _ = reply.send(
+                    self.operational_mode()
+                        .pause(&author, &reason)
+                        .map_err(Error::from),
+                );
+            }
+            Request::Resume(author, reason, reply) => {
+                let _ = reply.send(
+                    self.operational_mode()
+                        .resume(&author, &reason)
+                        .map_err(Error::from),
+                );
+            }
+            Request::Sign(digest, reply) => {
+                let result = self.frost_coordinator_mut().sign_digest(&digest);
+                let _ = reply.send(self.record_incident(result));
+            }
+            Request::Dkg(reply) => {
+                let _ = reply.send(self.run_dkg());
+            }
+            Request::SetSigningQuorum(quorum, reply) => {
+                let _ = reply.send(self.frost_coordinator_mut().set_signing_quorum(quorum));
+            }
+            Request::Status(reply) => {
+                let aggregate_public_key = self.frost_coordinator().get_aggregate_public_key().ok();
+                let status = self
+                    .peg_queue()
+                    .queue_depths()
+                    .map_err(Error::from)
+                    .and_then(|queue_depths| {
+                        Ok(CoordinatorStatus {
+                            mode: self.operational_mode().get()?,
+                            aggregate_public_key,
+                            party_commitments: self.party_commitments()?,
+                            priority_policy: self.peg_queue().priority_policy().clone(),
+                            queue_depths,
+                            rejected_peg_outs: self.peg_queue().rejected_peg_outs()?,
+                            round_timeouts: self.frost_coordinator().round_timeouts(),
+                            thresholds: self.frost_coordinator().thresholds(),
+                            signing_quorum: self.frost_coordinator().signing_quorum(),
+                            fees: self.fee_ledger().totals()?,
+                            fee_rates: self.fee_oracle().map(FeeOracle::rates).transpose()?,
+                            storage: self.frost_coordinator().retention().usage(),
+                            peg_latency: self.peg_latency().stats()?,
+                        })
+                    });
+                let _ = reply.send(status);
+            }
+            Request::ListIncidents(include_resolved, reply) => {
+                let _ = reply.send(
+                    self.incident_log()
+                        .list(include_resolved)
+                        .map_err(Error::from),
+                );
+            }
+            Request::AnnotateIncident(id, author, text, reply) => {
+                let _ = reply.send(
+                    self.incident_log()
+                        .annotate(id, &author, &text)
+                        .map_err(Error::from),
+                );
+            }
+            Request::ResolveIncident(id, author, text, reply) => {
+                let _ = reply.send(
+                    self.incident_log()
+                        .resolve(id, &author, &text)
+                        .map_err(Error::from),
+                );
+            }
+            Request::ListRounds(since, until, reply) => {
+                let _ = reply.send(self.frost_coordinator().list_rounds(since, until));
+            }
+            Request::GetRound(id, reply) => {
+                let _ = reply.send(self.frost_coordinator().get_round(id));
+            }
+            Request::BitcoinTxs(reply) => {
+                let txs = self
+                    .peg_latency()
+                    .scanned_height()
+                    .map_err(Error::from)
+                    .and_then(|scanned| Ok(self.bitcoin_txs().list(scanned)?));
+                let _ = reply.send(txs);
+            }
+            Request::Shutdown(reply) => {
+                let _ = reply.send(Ok(()));
+            }
+        }
+    }
+
+    /// Run DKG, opening incidents for the round's failures and for every signer the
+    /// frost coordinator dropped from the roster along the way
+    fn run_dkg(&mut self) -> Result<PublicKey> {
+        let excluded = self.frost_coordinator().excluded_signers();
+        let result = self
+            .frost_coordinator_mut()
+            .run_distributed_key_generation()
+            .map(|key| key.to_x_only());
+        for signer_id in self.frost_coordinator().excluded_signers() {
+            if excluded.contains(&signer_id) {
+                continue;
+            }
+            let incident = NewIncident {
+                kind: IncidentKind::Byzantine,
+                signer_ids: vec![signer_id as u32],
+                summary: format!(
+                    "signer #{signer_id} was excluded from DKG for sending shares which failed verification"
+                ),
+            };
+            self.open_incident(&incident);
+        }
+        self.record_incident(result)
+    }
+
+    /// Commitments of the keys which took part in the last DKG round, empty before any
+    fn party_commitments(&self) -> Result<Vec<PartyCommitment>> {
+        self.frost_coordinator().party_commitments()
+    }
+
+    /// Have the signers attest to the peg wallet's unspent outputs at the tip of the bitcoin
+    /// node's best chain. See [`crate::proof_of_reserve`].
+    fn prove_reserve(&mut self) -> Result<Attestation> {
+        let key = self.frost_coordinator().get_aggregate_public_key()?;
+        let script = crate::proof_of_reserve::wallet_script(&key);
+        let snapshot = self.bitcoin_node().utxos(&script)?;
+        let attested_at = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        let statement = ReserveStatement::new(key, snapshot, attested_at);
+        let signature = self
+            .frost_coordinator_mut()
+            .sign_digest(&statement.digest());
+        let attestation = Attestation {
+            statement,
+            signature: self.record_incident(signature)?,
+        };
+        attestation.verify(Some(&key))?;
+        Ok(attestation)
+    }
+
+    /// Check the coordinator's health, moving it between bootstrap, normal and degraded, and
+    /// return the mode the tick runs in. See [`crate::mode`].
+    fn check_mode(&self) -> Result<Mode> {
+        let current = self.operational_mode().get()?.mode;
+        let has_key = self.frost_coordinator().get_aggregate_public_key().is_ok();
+        let alarms: Vec<i64> = self
+            .incident_log()
+            .list(false)?
+            .iter()
+            .filter(|incident| {
+                matches!(
+                    incident.kind,
+                    IncidentKind::InvariantBreach | IncidentKind::Byzantine
+                )
+            })
+            .map(|incident| incident.id)
+            .collect();
+        let mode = current.checked(has_key, alarms.is_empty());
+        if mode != current {
+            let reason = if !has_key {
+                "no aggregate key".to_string()
+            } else if alarms.is_empty() {
+                "no incident calls for an operator".to_string()
+            } else {
+                format!("incidents {alarms:?} are open")
+            };
+            let state = self.operational_mode().set(mode, HEALTH_CHECK, &reason)?;
+            warn!(
+                target: COORDINATOR,
+                "Operational mode changed from {}: {}", current, state
+            );
+        }
+        Ok(mode)
+    }
+
+    /// Queue peg ops the stacks node reports for blocks read from bitcoin while it was
+    /// unavailable, and open an incident for every disagreement between the two views
+    fn reconcile_peg_ops(&self) -> Result<()> {
+        let reconciliation = self.stacks_node().reconcile()?;
+        for discrepancy in reconciliation.discrepancies {
+            let incident = NewIncident {
+                kind: IncidentKind::InvariantBreach,
+                signer_ids: vec![],
+                summary: discrepancy.to_string(),
+            };
+            self.open_incident(&incident);
+        }
+        for op in reconciliation.missed {
+            let op_id = self.peg_queue().submit(op)?;
+            info!(target: COORDINATOR, "Queued missed op {}", op_id);
+        }
+        Ok(())
+    }
+
+    /// Sample the fee rates of both chains when due, and have the stacks wallet pay the rate
+    /// the oracle selects
+    fn sample_fees(&mut self) -> Result<()> {
+        let Some(oracle) = self.fee_oracle() else {
+            return Ok(());
+        };
+        oracle.sample(self.stacks_node(), self.bitcoin_node())?;
+        if let Some(rate) = oracle.rate(Chain::Stacks)? {
+            self.fee_wallet().stacks_mut().set_fee_rate(rate);
+        }
+        Ok(())
+    }
+
+    /// Sweep the unswept fees of the current key generation to the operator address, once the
+    /// policy's interval has passed and enough fees have accrued
+    fn sweep_fees(&mut self) -> Result<()> {
+        let Some(policy) = self.fee_sweep_policy().cloned() else {
+            return Ok(());
+        };
+        if !self.fee_ledger().sweep_due(&policy)? {
+            return Ok(());
+        }
+        let generation = self.frost_coordinator().current_dkg_id();
+        let fees = self.fee_ledger().unswept(generation)?;
+        let total: u64 = fees.iter().map(|fee| fee.amount).sum();
+        if total < policy.min_amount {
+            return Ok(());
+        }
+        let to = policy.script_pubkey()?;
+        // the policy's fixed fee unless the oracle has a bitcoin rate
+        let tx_fee = match self.fee_oracle() {
+            Some(oracle) => oracle
+                .rate(Chain::Bitcoin)?
+                .map_or(policy.tx_fee, |rate| rate * sweep_vsize(fees.len())),
+            None => policy.tx_fee,
+        };
+        let sweep_tx = self.btc_sweep_fees(&fees, &to, tx_fee)?;
+        self.bitcoin_node().broadcast_transaction(&sweep_tx)?;
+        self.fee_ledger().record_sweep(&sweep_tx.txid(), &fees)?;
+        let outpoints: Vec<String> = fees.iter().map(|fee| fee.outpoint.to_string()).collect();
+        self.bitcoin_txs()
+            .record(&sweep_tx, TxKind::Consolidation, Some(tx_fee), &outpoints)?;
+        info!(
+            target: COORDINATOR,
+            "Swept {} sats of generation #{} fees to {} in {}",
+            total - tx_fee,
+            generation,
+            policy.address,
+            sweep_tx.txid()
+        );
+        Ok(())
+    }
+
+    /// Acknowledge the peg-outs whose fulfillment was confirmed in a bitcoin block since the
+    /// last tick, recording the latency of each, and mark the blocks including the other
+    /// transactions the coordinator broadcast
+    fn confirm_fulfillments(&self) -> Result<()> {
+        let mut awaiting: HashMap<bitcoin::Txid, TracedOp> = HashMap::new();
+        for traced in self.peg_queue().pending()? {
+            let broadcast = traced
+                .events
+                .iter()
+                .rev()
+                .find(|event| event.stage == Stage::BitcoinBroadcast);
+            if let Some(txid) = broadcast.and_then(|event| event.detail.parse().ok()) {
+                awaiting.insert(txid, traced);
+            }
+        }
+        let mut unconfirmed: HashSet<bitcoin::Txid> =
+            self.bitcoin_txs().unconfirmed()?.into_iter().collect();
+        if awaiting.is_empty() && unconfirmed.is_empty() {
+            return Ok(());
+        }
+        let scanned_height = self.peg_latency().scanned_height()?;
+        let tip_height = self.bitcoin_node().block_count()?;
+        // fulfillments are only broadcast after the block of their request, and the other
+        // transactions were broadcast by this coordinator since its last scan
+        let logged_height =
+            (!unconfirmed.is_empty()).then(|| scanned_height.map_or(tip_height, |h| h + 1));
+        let first_height = awaiting
+            .values()
+            .map(|traced| traced.op.block_height())
+            .chain(logged_height)
+            .min()
+            .unwrap_or(tip_height);
+        let start_height = match scanned_height {
+            Some(height) => first_height.max(height + 1),
+            None => first_height,
+        };
+        for height in start_height..=tip_height {
+            let block = self.bitcoin_node().block(height)?;
+            for tx in &block.txdata {
+                let txid = tx.txid();
+                if let Some(traced) = awaiting.remove(&txid) {
+                    self.confirm_op(&traced, u64::from(block.header.time))?;
+                }
+                if unconfirmed.remove(&txid) {
+                    self.bitcoin_txs().confirm(&txid, height)?;
+                }
+            }
+            self.peg_latency().set_scanned_height(height)?;
+        }
+        Ok(())
+    }
+
     fn process_queue(&mut self) -> Result<()> {
-        match self.peg_queue().sbtc_op()? {
-            Some(SbtcOp::PegIn(op)) => self.peg_in(op),
-            Some(SbtcOp::PegOutRequest(op)) => self.peg_out(op),
-            None => Ok(()),
+        // what each peg wallet can still pay this tick
+        let mut available = HashMap::new();
+        // peg-outs held for funds go first, oldest first, so funds coming back pay them before
+        // any newer request
+        let held = self.peg_queue().awaiting_funds()?.into_iter();
+        let ops: Vec<SbtcOp> = held
+            .map(|traced| traced.op)
+            .chain(self.peg_queue().sbtc_ops_for_tick()?)
+            .collect();
+        // peg-outs ready to be fulfilled, all signed in one round once the ops are through
+        let mut peg_outs = Vec::new();
+        let mut result = Ok(());
+        for op in ops {
+            // everything logged while processing the op carries its id
+            let op_id = self.peg_queue().op_id(&op)?.unwrap_or_default();
+            let span = info_span!(target: COORDINATOR, "peg_op", %op_id);
+            let _entered = span.enter();
+            let op_result = match op {
+                SbtcOp::PegIn(op) => self.peg_in(&op_id, op),
+                SbtcOp::PegOutRequest(op) => {
+                    self.peg_out(&op_id, &op, &mut available).map(|ready| {
+                        if ready {
+                            peg_outs.push((op_id.clone(), op));
+                        }
+                    })
+                }
+            };
+            if let Err(e) = op_result {
+                self.peg_queue()
+                    .record_stage(&op_id, Stage::Failed, &e.to_string())?;
+                result = Err(e);
+                break;
+            }
         }
+        // the peg-outs readied before an op failed are fulfilled all the same
+        let fulfilled = self.fulfill_peg_outs(&peg_outs);
+        result.and(fulfilled)
     }
 }
 
 // Private helper functions
 trait CoordinatorHelpers: Coordinator {
-    fn peg_in(&mut self, op: stacks_node::PegInOp) -> Result<()> {
+    /// Open an incident if `result` failed in a way operators should follow up on.
+    /// The result is passed through; failing to write the log is only logged.
+    fn record_incident<T>(&self, result: Result<T>) -> Result<T> {
+        if let Err(error) = &result {
+            if let Some(incident) = NewIncident::from_error(error) {
+                self.open_incident(&incident);
+            }
+        }
+        result
+    }
+
+    /// Record `incident`; failing to write the log is only logged
+    fn open_incident(&self, incident: &NewIncident) {
+        match self.incident_log().record(incident) {
+            Ok(id) => warn!(
+                target: COORDINATOR,
+                "Opened incident #{}: {}",
+                id,
+                incident.summary
+            ),
+            Err(e) => warn!(
+                target: COORDINATOR,
+                "Failed to record incident {:?}: {}",
+                incident,
+                e
+            ),
+        }
+    }
+
+    /// Acknowledge `traced`, confirmed at unix time `confirmed_at`, and record how long it took
+    /// since its burn block, opening an incident if that breaches its SLO
+    fn confirm_op(&self, traced: &TracedOp, confirmed_at: u64) -> Result<()> {
+        let op = &traced.op;
+        self.peg_queue()
+            .acknowledge(op.txid(), op.burn_header_hash())?;
+        // when the op was queued stands in for its burn block if the node cannot serve it
+        let included_at = match self.bitcoin_node().block(op.block_height()) {
+            Ok(block) => u64::from(block.header.time),
+            Err(e) => {
+                warn!(
+                    target: COORDINATOR,
+                    "Failed to read burn block {} of op {}: {}",
+                    op.block_height(),
+                    traced.op_id,
+                    e
+                );
+                traced
+                    .events
+                    .first()
+                    .map_or(confirmed_at, |event| event.recorded_at)
+            }
+        };
+        let latency_secs = confirmed_at.saturating_sub(included_at);
+        let (kind, name) = match op {
+            SbtcOp::PegIn(_) => (OpKind::PegIn, "peg-in"),
+            SbtcOp::PegOutRequest(_) => (OpKind::PegOut, "peg-out"),
+        };
+        info!(
+            target: COORDINATOR,
+            "Op {} confirmed {}s after its burn block", traced.op_id, latency_secs
+        );
+        if self
+            .peg_latency()
+            .record(&traced.op_id, kind, latency_secs)?
+        {
+            let slo_secs = self
+                .peg_latency()
+                .config()
+                .slo_secs(kind)
+                .unwrap_or_default();
+            self.open_incident(&NewIncident {
+                kind: IncidentKind::SloBreach,
+                signer_ids: vec![],
+                summary: format!(
+                    "{name} op {} took {latency_secs}s, over its SLO of {slo_secs}s",
+                    traced.op_id
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether the sBTC contract already processed the bitcoin transaction `txid`, e.g. because
+    /// another coordinator or an earlier run of this one did, asking its read-only `function`
+    fn already_processed(
+        &mut self,
+        function: &str,
+        txid: &blockstack_lib::burnchains::Txid,
+    ) -> Result<bool> {
+        let (address, name) = self.fee_wallet().stacks_mut().contract();
+        let (address, name) = (address.to_string(), name.to_string());
+        let result = self.stacks_node().call_read_only(
+            &address,
+            &name,
+            function,
+            &[clarity::string_ascii(&txid.to_string())?],
+        )?;
+        Ok(clarity::bool_value(&result)?)
+    }
+
+    fn peg_in(&mut self, op_id: &OpId, op: stacks_node::PegInOp) -> Result<()> {
+        if self.already_processed("is-peg-in-processed", &op.txid)? {
+            info!(target: COORDINATOR, "Peg-in {} was already minted", op.txid);
+            self.peg_queue()
+                .record_stage(op_id, Stage::AlreadyProcessed, "mint")?;
+            return Ok(());
+        }
         let _tx = self.fee_wallet().stacks_mut().build_mint_transaction(&op)?;
         //self.stacks_node().broadcast_transaction(&tx);
+        self.peg_queue()
+            .record_stage(op_id, Stage::StacksTxBuilt, "mint")?;
         Ok(())
     }
 
-    fn peg_out(&mut self, op: stacks_node::PegOutRequestOp) -> Result<()> {
-        let _stacks = self.fee_wallet().stacks_mut();
-        let _burn_tx = self.fee_wallet().stacks_mut().build_burn_transaction(&op)?;
-        //self.stacks_node().broadcast_transaction(&burn_tx);
+    /// Whether the peg wallet `op` names can pay it, taking the fulfillments broadcast but not
+    /// yet confirmed out of its balance. `available` keeps what each wallet can still pay this
+    /// tick. A peg-out it cannot pay is held awaiting funds, opening a `shortfall` incident, and
+    /// released by the first tick it can be paid in.
+    fn fund_peg_out(
+        &self,
+        op_id: &OpId,
+        op: &stacks_node::PegOutRequestOp,
+        available: &mut HashMap<Script, u64>,
+    ) -> Result<bool> {
+        let script = peg_wallet_script(op).map_err(PegWalletError::from)?;
+        let funds = match available.entry(script) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let balance = self.bitcoin_node().balance(entry.key())?;
+                let mut committed = 0;
+                for traced in self.peg_queue().pending()? {
+                    let Some(pending) = traced.op.as_peg_out_request() else {
+                        continue;
+                    };
+                    let broadcast = traced
+                        .events
+                        .iter()
+                        .any(|event| event.stage == Stage::BitcoinBroadcast);
+                    if broadcast && peg_wallet_script(pending).as_ref().ok() == Some(entry.key()) {
+                        committed += pending.amount;
+                    }
+                }
+                entry.insert(balance.saturating_sub(committed))
+            }
+        };
+        let held = self
+            .peg_queue()
+            .trace(op_id)?
+            .is_some_and(|traced| traced.status == "awaiting_funds");
+        if op.amount <= *funds {
+            *funds -= op.amount;
+            if held {
+                info!(
+                    target: COORDINATOR,
+                    "Peg wallet can pay peg-out {} again", op.txid
+                );
+                self.peg_queue()
+                    .resume(op_id, &format!("{} sats available", *funds + op.amount))?;
+            }
+            return Ok(true);
+        }
+        if !held {
+            let detail = format!("needs {} sats, {} available", op.amount, funds);
+            self.peg_queue().await_funds(op_id, &detail)?;
+            self.open_incident(&NewIncident {
+                kind: IncidentKind::Shortfall,
+                signer_ids: vec![],
+                summary: format!(
+                    "peg-out op {op_id} {detail}, held until the peg wallet can pay it"
+                ),
+            });
+        }
+        Ok(false)
+    }
 
-        let fulfill_tx = self.btc_fulfill_peg_out(&op)?;
-        self.bitcoin_node().broadcast_transaction(&fulfill_tx);
+    /// Check the peg wallet can pay `op` and burn its sBTC. Returns whether the peg-out is
+    /// ready to be fulfilled.
+    fn peg_out(
+        &mut self,
+        op_id: &OpId,
+        op: &stacks_node::PegOutRequestOp,
+        available: &mut HashMap<Script, u64>,
+    ) -> Result<bool> {
+        // checked before the burn, so a peg-out the wallet cannot pay is not burned until it can
+        if !self.fund_peg_out(op_id, op, available)? {
+            return Ok(false);
+        }
+        if self.already_processed("is-peg-out-processed", &op.txid)? {
+            info!(target: COORDINATOR, "Peg-out {} was already burned", op.txid);
+            self.peg_queue()
+                .record_stage(op_id, Stage::AlreadyProcessed, "burn")?;
+        } else {
+            let _burn_tx = self.fee_wallet().stacks_mut().build_burn_transaction(op)?;
+            //self.stacks_node().broadcast_transaction(&burn_tx);
+            self.peg_queue()
+                .record_stage(op_id, Stage::StacksTxBuilt, "burn")?;
+        }
+        Ok(true)
+    }
+
+    /// Fulfill `peg_outs`, their fulfillments signed in a single signing round. A failed round
+    /// fails every peg-out; one failing after the round does not stop the others.
+    fn fulfill_peg_outs(
+        &mut self,
+        peg_outs: &[(OpId, stacks_node::PegOutRequestOp)],
+    ) -> Result<()> {
+        if peg_outs.is_empty() {
+            return Ok(());
+        }
+        let ops: Vec<&stacks_node::PegOutRequestOp> = peg_outs.iter().map(|(_, op)| op).collect();
+        let fulfill_txs = match self.btc_fulfill_peg_outs(&ops) {
+            Ok(fulfill_txs) => fulfill_txs,
+            Err(e) => {
+                for (op_id, _) in peg_outs {
+                    self.peg_queue()
+                        .record_stage(op_id, Stage::Failed, &e.to_string())?;
+                }
+                return Err(e);
+            }
+        };
+        let mut result = Ok(());
+        for ((op_id, op), (fulfill_tx, fee)) in peg_outs.iter().zip(fulfill_txs) {
+            let span = info_span!(target: COORDINATOR, "peg_op", %op_id);
+            let _entered = span.enter();
+            if let Err(e) = self.broadcast_fulfillment(op_id, op, &fulfill_tx, fee) {
+                self.peg_queue()
+                    .record_stage(op_id, Stage::Failed, &e.to_string())?;
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Broadcast the signed fulfillment of `op`, paying `tx_fee`, and once the node accepted it,
+    /// record it and the fee it collects
+    fn broadcast_fulfillment(
+        &mut self,
+        op_id: &OpId,
+        op: &stacks_node::PegOutRequestOp,
+        fulfill_tx: &BitcoinTransaction,
+        tx_fee: u64,
+    ) -> Result<()> {
+        let generation = self.frost_coordinator().current_dkg_id();
+        self.peg_queue().record_stage(
+            op_id,
+            Stage::Signed,
+            &format!("by generation #{generation}"),
+        )?;
+        let txid = self.bitcoin_node().broadcast_transaction(fulfill_tx)?;
+        self.peg_queue()
+            .record_stage(op_id, Stage::BitcoinBroadcast, &txid.to_string())?;
+        self.bitcoin_txs().record(
+            fulfill_tx,
+            TxKind::Fulfillment,
+            Some(tx_fee),
+            &[op_id.to_string()],
+        )?;
+
+        // the fee output now belongs to the peg wallet of the current key generation
+        if op.fulfillment_fee > 0 {
+            let fee = collected_fee(op, generation).map_err(PegWalletError::from)?;
+            self.fee_ledger().record(&fee)?;
+            self.peg_queue().record_stage(
+                op_id,
+                Stage::FeeRecorded,
+                &format!("{} sats to generation #{generation}", fee.amount),
+            )?;
+        }
         Ok(())
     }
 
@@ -142,81 +1189,356 @@ trait CoordinatorHelpers: Coordinator {
         &mut self,
         op: &stacks_node::PegOutRequestOp,
     ) -> Result<BitcoinTransaction> {
-        let mut fulfill_tx = self.fee_wallet().bitcoin_mut().fulfill_peg_out(op)?;
-        let pubkey = self.frost_coordinator().get_aggregate_public_key()?;
-        let _xonly_pubkey =
-            PublicKey::from_slice(&pubkey.x().to_bytes()).map_err(Error::BitcoinSecp256k1)?;
-        let mut comp = bitcoin::util::sighash::SighashCache::new(&fulfill_tx);
-        let taproot_sighash = comp.taproot_signature_hash(
-            1,
-            &Prevouts::All(&[&fulfill_tx.output[0]]),
-            None,
-            None,
-            SchnorrSighashType::All,
-        )?;
+        Ok(self.btc_fulfill_peg_outs(&[op])?.remove(0).0)
+    }
+
+    /// Build the fulfillment of each of `ops` and threshold-sign them all in one signing round.
+    /// Each fulfillment comes with the fee it pays.
+    fn btc_fulfill_peg_outs(
+        &mut self,
+        ops: &[&stacks_node::PegOutRequestOp],
+    ) -> Result<Vec<(BitcoinTransaction, u64)>> {
+        // the tip the peg-outs were read at this tick
+        let chain_tip = self.stacks_node().stacks_tip()?;
+        let mut fulfill_txs = Vec::with_capacity(ops.len());
+        let mut digests = Vec::with_capacity(ops.len());
+        for op in ops {
+            let spent = self.fulfillment_input(op)?;
+            let fulfill_tx = self
+                .fee_wallet()
+                .bitcoin_mut()
+                .fulfill_peg_out(op, chain_tip, &spent)?;
+            // the wallet checked the outputs pay no more than the spent output holds
+            let fee = spent.value - fulfill_tx.output.iter().map(|out| out.value).sum::<u64>();
+            // the fulfillment has a single input
+            let taproot_sighash = fulfillment::sighash(&fulfill_tx)?;
+            digests.push(MessageDigest::new(taproot_sighash.into_inner()));
+            fulfill_txs.push((fulfill_tx, fee));
+        }
 
-        let (_frost_sig, schnorr_proof) = self
+        // the peg wallet is a taproot output without script paths
+        let signatures = self
             .frost_coordinator_mut()
-            .sign_message(&taproot_sighash)?;
+            .sign_digests_key_path(&digests, &KeyPath::default())?;
+        for ((fulfill_tx, _), signature) in fulfill_txs.iter_mut().zip(signatures) {
+            info!(target: COORDINATOR, "Fulfill Tx {:?} Signature {}", fulfill_tx, signature);
 
-        info!(
-            "Fulfill Tx {:?} SchnorrProof ({},{})",
-            &fulfill_tx, schnorr_proof.r, schnorr_proof.s
-        );
+            let finalized = [
+                signature.as_bytes().as_ref(),
+                &[SchnorrSighashType::All as u8],
+            ]
+            .concat();
+            let finalized_b58 = bitcoin::util::base58::encode_slice(&finalized);
+            info!(target: COORDINATOR, "CALC SIG ({}) {}", finalized.len(), finalized_b58);
+            fulfill_tx.input[0].witness.push(finalized);
+        }
+        Ok(fulfill_txs)
+    }
 
-        let finalized = [
-            schnorr_proof.to_bytes().as_ref(),
-            &[SchnorrSighashType::All as u8],
-        ]
-        .concat();
-        let finalized_b58 = bitcoin::util::base58::encode_slice(&finalized);
-        info!("CALC SIG ({}) {}", finalized.len(), finalized_b58);
-        fulfill_tx.input[0].witness.push(finalized);
-        Ok(fulfill_tx)
+    /// The output of its peg wallet the fulfillment of `op` spends, as the bitcoin node sees it.
+    /// An output the node does not know as unspent fails the fulfillment, as its fee could not
+    /// be checked.
+    fn fulfillment_input(&self, op: &stacks_node::PegOutRequestOp) -> Result<TxOut> {
+        let outpoint = spent_outpoint(op).map_err(PegWalletError::from)?;
+        let script_pubkey = peg_wallet_script(op).map_err(PegWalletError::from)?;
+        let utxo = self
+            .bitcoin_node()
+            .utxos(&script_pubkey)?
+            .utxos
+            .into_iter()
+            .find(|utxo| utxo.outpoint == outpoint)
+            .ok_or_else(|| {
+                PegWalletError::from(BitcoinWalletError::UnknownSpentOutput(outpoint))
+            })?;
+        Ok(TxOut {
+            value: utxo.amount,
+            script_pubkey,
+        })
+    }
+
+    /// Build the sweep of `fees` to `to` and threshold-sign each of its inputs
+    fn btc_sweep_fees(
+        &mut self,
+        fees: &[CollectedFee],
+        to: &Script,
+        tx_fee: u64,
+    ) -> Result<BitcoinTransaction> {
+        let mut sweep_tx = self
+            .fee_wallet()
+            .bitcoin_mut()
+            .sweep_fees(fees, to, tx_fee)?;
+        let prevouts: Vec<TxOut> = fees
+            .iter()
+            .map(|fee| TxOut {
+                value: fee.amount,
+                script_pubkey: fee.script_pubkey.clone(),
+            })
+            .collect();
+        let mut comp = bitcoin::util::sighash::SighashCache::new(&sweep_tx);
+        let sighashes = (0..fees.len())
+            .map(|index| {
+                comp.taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    SchnorrSighashType::All,
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        // every input in one signing round
+        let digests: Vec<MessageDigest> = sighashes
+            .into_iter()
+            .map(|sighash| MessageDigest::new(sighash.into_inner()))
+            .collect();
+        let signatures = self
+            .frost_coordinator_mut()
+            .sign_digests_key_path(&digests, &KeyPath::default())?;
+        for (input, signature) in sweep_tx.input.iter_mut().zip(signatures) {
+            let finalized = [
+                signature.as_bytes().as_ref(),
+                &[SchnorrSighashType::All as u8],
+            ]
+            .concat();
+            input.witness.push(finalized);
+        }
+        Ok(sweep_tx)
     }
 }
 
-impl<T: Coordinator> CoordinatorHelpers for T {}
+impl<T: Coordinator + ?Sized> CoordinatorHelpers for T {}
 
 pub enum Command {
     Stop,
     Timeout,
+    Request(Request),
 }
 
-pub struct StacksCoordinator {
-    frost_coordinator: FrostCoordinator,
+/// The stacks node the coordinator reads peg ops from unless given another
+pub type ConfiguredStacksNode =
+    ContractEventsNode<FallbackNode<NodeClient, LocalhostBitcoinNode>, EventsClient>;
+
+/// The stacks node `config` sets up, reading peg ops from the burnchain op API, the bitcoin
+/// node and the sBTC contract's events as configured
+pub fn configured_stacks_node(config: &Config) -> Result<ConfiguredStacksNode> {
+    let mut burn_ops_node = FallbackNode::new(
+        NodeClient::new(&config.stacks_node_rpc_url)
+            .with_retry_policy(config.retry.clone())
+            .with_proxy(&config.proxy)?,
+    );
+    if let Some(fallback) = &config.peg_op_fallback {
+        let bitcoin_node =
+            LocalhostBitcoinNode::new(config.bitcoin_node_rpc_url.clone(), config.retry.clone())
+                .with_egress(config.proxy.egress(&config.bitcoin_node_rpc_url)?);
+        burn_ops_node = burn_ops_node.with_bitcoin_fallback(bitcoin_node, fallback)?;
+    }
+    let mut stacks_node = ContractEventsNode::new(burn_ops_node);
+    if let Some(events) = &config.peg_op_events {
+        let api = EventsClient::new(&events.api_url, &config.sbtc_contract)
+            .with_retry_policy(config.retry.clone())
+            .with_proxy(&config.proxy)?;
+        let mainnet =
+            config.sbtc_contract.starts_with("SP") || config.sbtc_contract.starts_with("SM");
+        stacks_node = stacks_node.with_contract_events(api, events, mainnet);
+    }
+    Ok(stacks_node)
+}
+
+pub struct StacksCoordinator<
+    F = NetFrostCoordinator,
+    S = ConfiguredStacksNode,
+    B = LocalhostBitcoinNode,
+> {
+    frost_coordinator: F,
     local_peg_queue: SqlitePegQueue,
-    local_stacks_node: NodeClient,
+    local_stacks_node: S,
+    local_bitcoin_node: B,
+    local_incident_log: IncidentLog,
+    local_fee_ledger: FeeLedger,
+    fee_sweep: Option<FeeSweepPolicy>,
+    local_fee_oracle: Option<FeeOracle>,
+    local_peg_latency: PegLatency,
+    local_bitcoin_txs: BitcoinTxLog,
+    local_operational_mode: ModeStore,
+    failure_report_path: Option<String>,
     pub local_fee_wallet: WrapPegWallet,
 }
 
-impl StacksCoordinator {
+impl<F: FrostCoordinator, S: StacksNode, B: BitcoinNode> StacksCoordinator<F, S, B> {
     pub fn run_dkg_round(&mut self) -> Result<PublicKey> {
-        let p = self.frost_coordinator.run_distributed_key_generation()?;
-        PublicKey::from_slice(&p.x().to_bytes()).map_err(Error::BitcoinSecp256k1)
+        self.run_dkg()
+    }
+
+    /// Sign `digest` as is, e.g. a taproot sighash
+    pub fn sign_digest(&mut self, digest: [u8; 32]) -> Result<ThresholdSignature> {
+        let result = self
+            .frost_coordinator
+            .sign_digest(&MessageDigest::new(digest));
+        self.record_incident(result)
     }
 
-    pub fn sign_message(&mut self, message: &str) -> Result<(Signature, SchnorrProof)> {
-        Ok(self.frost_coordinator.sign_message(message.as_bytes())?)
+    /// Sign the tagged hash of `bytes` under the domain tag `tag`
+    pub fn sign_bytes(&mut self, tag: &str, bytes: &[u8]) -> Result<ThresholdSignature> {
+        self.sign_digest(*MessageDigest::tagged(tag, bytes).as_bytes())
+    }
+
+    /// Deploy a contract from the configured stacks account and wait until the node reports it
+    pub fn deploy_contract(
+        &mut self,
+        name: &str,
+        source: &str,
+        nonce: u64,
+        timeout: time::Duration,
+    ) -> Result<()> {
+        let tx = self
+            .local_fee_wallet
+            .stacks_mut()
+            .build_contract_deploy_transaction(name, source, nonce)?;
+        let tx = stacks_node::StacksTransaction::try_from(&tx)?;
+        self.local_stacks_node.broadcast_transaction(&tx)?;
+
+        let contract_address = self.local_fee_wallet.stacks_wallet.contract_address();
+        let contract_id = format!("{contract_address}.{name}");
+        info!(
+            target: COORDINATOR,
+            "Broadcast deploy of {}, waiting for confirmation",
+            contract_id
+        );
+        let deadline = time::Instant::now() + timeout;
+        while !self
+            .local_stacks_node
+            .contract_deployed(contract_address, name)?
+        {
+            if time::Instant::now() >= deadline {
+                return Err(Error::ContractDeployTimeout(contract_id, timeout));
+            }
+            thread::sleep(time::Duration::from_secs(5));
+        }
+        info!(target: COORDINATOR, "Contract {} confirmed", contract_id);
+        Ok(())
+    }
+
+    /// Take a synthetic peg-out of `amount` sats to `recipient`, spending the peg wallet output
+    /// `utxo`, through the steps of a queued one: build the burn, have the signers sign the
+    /// fulfillment and, unless `dry_run`, broadcast it. Runs DKG first if there is no key yet.
+    pub fn simulate_peg_out(
+        &mut self,
+        recipient: &bitcoin::Address,
+        amount: u64,
+        fulfillment_fee: u64,
+        utxo: bitcoin::OutPoint,
+        dry_run: bool,
+    ) -> Result<SimulatedPegOut> {
+        if self.frost_coordinator.get_aggregate_public_key().is_err() {
+            self.run_dkg()?;
+        }
+        let peg_wallet_key = self
+            .frost_coordinator
+            .get_aggregate_public_key()?
+            .output_key(&KeyPath::default())
+            .to_inner();
+        let op =
+            simulate::peg_out_request(recipient, amount, fulfillment_fee, utxo, &peg_wallet_key)?;
+        let burn_tx = self
+            .local_fee_wallet
+            .stacks_mut()
+            .build_burn_transaction(&op)?;
+        let fulfill_tx = self.btc_fulfill_peg_out(&op)?;
+        if !dry_run {
+            self.local_bitcoin_node.broadcast_transaction(&fulfill_tx)?;
+        }
+        Ok(SimulatedPegOut {
+            op,
+            burn_tx: serde_json::to_string(&burn_tx).unwrap_or_default(),
+            fulfill_tx,
+            broadcast: !dry_run,
+        })
     }
 }
 
 impl TryFrom<Config> for StacksCoordinator {
     type Error = Error;
     fn try_from(mut config: Config) -> Result<Self> {
-        let local_stacks_node = NodeClient::new(&config.stacks_node_rpc_url);
+        let frost_coordinator = match config.network_private_key.take() {
+            Some(key) => create_coordinator_with_key(&config.signer_config_path, key)?,
+            None => create_coordinator(&config.signer_config_path)?,
+        };
+        Self::new(config, frost_coordinator)
+    }
+}
+
+impl<F: FrostCoordinator> StacksCoordinator<F> {
+    /// A coordinator running its DKG and signing rounds on `frost_coordinator`, e.g. a
+    /// [`MockFrostCoordinator`](crate::mock_frost_coordinator::MockFrostCoordinator) in tests.
+    /// The signer config of `config` is not read.
+    pub fn new(mut config: Config, frost_coordinator: F) -> Result<Self> {
+        let local_stacks_node = configured_stacks_node(&config)?;
+        let bitcoin_egress = config.proxy.egress(&config.bitcoin_node_rpc_url)?;
         // If a user has not specified a start block height, begin from the current burn block height by default
         config.start_block_height = config
             .start_block_height
             .or_else(|| local_stacks_node.burn_block_height().ok());
+        let local_bitcoin_node =
+            LocalhostBitcoinNode::new(config.bitcoin_node_rpc_url.clone(), config.retry.clone())
+                .with_egress(bitcoin_egress);
+        // fulfillments carry the magic bytes peg ops are read with, mainnet unless configured
+        let bitcoin_magic = config
+            .peg_op_fallback
+            .as_ref()
+            .and_then(|fallback| fallback.magic.as_bytes().try_into().ok())
+            .unwrap_or(MAINNET_MAGIC);
+        let local_incident_log = match &config.rusqlite_path {
+            Some(path) => IncidentLog::new(path)?,
+            None => IncidentLog::in_memory()?,
+        };
+        let local_fee_ledger = match &config.rusqlite_path {
+            Some(path) => FeeLedger::new(path)?,
+            None => FeeLedger::in_memory()?,
+        };
+        let local_fee_oracle = match (&config.fee_oracle, &config.rusqlite_path) {
+            (Some(oracle), Some(path)) => Some(FeeOracle::new(path, oracle.clone())?),
+            (Some(oracle), None) => Some(FeeOracle::in_memory(oracle.clone())?),
+            (None, _) => None,
+        };
+        let local_peg_latency = match &config.rusqlite_path {
+            Some(path) => PegLatency::new(path, config.peg_latency.clone())?,
+            None => PegLatency::in_memory(config.peg_latency.clone())?,
+        };
+        let local_bitcoin_txs = match &config.rusqlite_path {
+            Some(path) => BitcoinTxLog::new(path)?,
+            None => BitcoinTxLog::in_memory()?,
+        };
+        let local_operational_mode = match &config.rusqlite_path {
+            Some(path) => ModeStore::new(path)?,
+            None => ModeStore::in_memory()?,
+        };
+        let retention = frost_coordinator.retention();
+        // resolved incidents are pruned over a connection of their own
+        if let (Some(path), Some(limits)) =
+            (&config.rusqlite_path, config.retention.limits("incidents"))
+        {
+            retention.spawn(IncidentLog::new(path)?, limits, config.retention.interval());
+        }
+        // a bad sweep address should stop startup, not every sweep
+        if let Some(policy) = &config.fee_sweep {
+            policy.script_pubkey()?;
+        }
         Ok(Self {
             local_peg_queue: SqlitePegQueue::try_from(&config)?,
+            local_incident_log,
+            local_fee_ledger,
+            fee_sweep: config.fee_sweep,
+            local_fee_oracle,
+            local_peg_latency,
+            local_bitcoin_txs,
+            local_operational_mode,
+            failure_report_path: config.failure_report_path,
             local_stacks_node,
-            frost_coordinator: create_coordinator(config.signer_config_path)?,
+            local_bitcoin_node,
+            frost_coordinator,
             local_fee_wallet: WrapPegWallet {
-                bitcoin_wallet: BitcoinWallet {},
-                stacks_wallet: StacksWallet::new(
-                    "..",
+                bitcoin_wallet: BitcoinWallet::new(bitcoin_magic),
+                stacks_wallet: StacksWallet::from_config(
+                    &config.js,
                     config.sbtc_contract,
                     config.stacks_private_key,
                 )?,
@@ -225,11 +1547,57 @@ impl TryFrom<Config> for StacksCoordinator {
     }
 }
 
-impl Coordinator for StacksCoordinator {
+impl<F, S, B> StacksCoordinator<F, S, B> {
+    /// Read peg ops from and broadcast stacks transactions to `stacks_node` instead of the
+    /// configured node, e.g. a `Box<dyn StacksNode>`. The start block height was already
+    /// resolved by [`StacksCoordinator::new`], so set it in the config if the configured node
+    /// is unreachable.
+    pub fn with_stacks_node<N: StacksNode>(self, stacks_node: N) -> StacksCoordinator<F, N, B> {
+        StacksCoordinator {
+            frost_coordinator: self.frost_coordinator,
+            local_peg_queue: self.local_peg_queue,
+            local_stacks_node: stacks_node,
+            local_bitcoin_node: self.local_bitcoin_node,
+            local_incident_log: self.local_incident_log,
+            local_fee_ledger: self.local_fee_ledger,
+            fee_sweep: self.fee_sweep,
+            local_fee_oracle: self.local_fee_oracle,
+            local_peg_latency: self.local_peg_latency,
+            local_bitcoin_txs: self.local_bitcoin_txs,
+            local_operational_mode: self.local_operational_mode,
+            failure_report_path: self.failure_report_path,
+            local_fee_wallet: self.local_fee_wallet,
+        }
+    }
+
+    /// Broadcast bitcoin transactions to `bitcoin_node` instead of the configured node
+    pub fn with_bitcoin_node<N: BitcoinNode>(self, bitcoin_node: N) -> StacksCoordinator<F, S, N> {
+        StacksCoordinator {
+            frost_coordinator: self.frost_coordinator,
+            local_peg_queue: self.local_peg_queue,
+            local_stacks_node: self.local_stacks_node,
+            local_bitcoin_node: bitcoin_node,
+            local_incident_log: self.local_incident_log,
+            local_fee_ledger: self.local_fee_ledger,
+            fee_sweep: self.fee_sweep,
+            local_fee_oracle: self.local_fee_oracle,
+            local_peg_latency: self.local_peg_latency,
+            local_bitcoin_txs: self.local_bitcoin_txs,
+            local_operational_mode: self.local_operational_mode,
+            failure_report_path: self.failure_report_path,
+            local_fee_wallet: self.local_fee_wallet,
+        }
+    }
+}
+
+impl<F: FrostCoordinator, S: StacksNode, B: BitcoinNode> Coordinator
+    for StacksCoordinator<F, S, B>
+{
     type PegQueue = SqlitePegQueue;
     type FeeWallet = WrapPegWallet;
-    type StacksNode = NodeClient;
-    type BitcoinNode = LocalhostBitcoinNode;
+    type StacksNode = S;
+    type BitcoinNode = B;
+    type FrostCoordinator = F;
 
     fn peg_queue(&self) -> &Self::PegQueue {
         &self.local_peg_queue
@@ -239,68 +1607,776 @@ impl Coordinator for StacksCoordinator {
         &mut self.local_fee_wallet
     }
 
-    fn frost_coordinator(&self) -> &FrostCoordinator {
+    fn frost_coordinator(&self) -> &F {
         &self.frost_coordinator
     }
 
-    fn frost_coordinator_mut(&mut self) -> &mut FrostCoordinator {
+    fn frost_coordinator_mut(&mut self) -> &mut F {
         &mut self.frost_coordinator
     }
 
-    fn stacks_node(&self) -> &Self::StacksNode {
+    fn stacks_node(&self) -> &S {
         &self.local_stacks_node
     }
 
-    fn bitcoin_node(&self) -> &Self::BitcoinNode {
-        todo!()
+    fn bitcoin_node(&self) -> &B {
+        &self.local_bitcoin_node
+    }
+
+    fn incident_log(&self) -> &IncidentLog {
+        &self.local_incident_log
+    }
+
+    fn fee_ledger(&self) -> &FeeLedger {
+        &self.local_fee_ledger
+    }
+
+    fn fee_sweep_policy(&self) -> Option<&FeeSweepPolicy> {
+        self.fee_sweep.as_ref()
+    }
+
+    fn fee_oracle(&self) -> Option<&FeeOracle> {
+        self.local_fee_oracle.as_ref()
+    }
+
+    fn peg_latency(&self) -> &PegLatency {
+        &self.local_peg_latency
+    }
+
+    fn bitcoin_txs(&self) -> &BitcoinTxLog {
+        &self.local_bitcoin_txs
+    }
+
+    fn operational_mode(&self) -> &ModeStore {
+        &self.local_operational_mode
+    }
+
+    fn failure_report_path(&self) -> Option<&str> {
+        self.failure_report_path.as_deref()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::Config;
-    use crate::coordinator::{CoordinatorHelpers, StacksCoordinator};
-    use crate::stacks_node::PegOutRequestOp;
-    use bitcoin::consensus::Encodable;
+    use super::*;
+    use crate::bitcoin_node::MockBitcoinNode;
+    use crate::mock_frost_coordinator::MockFrostCoordinator;
+    use crate::peg_latency::PegLatencyConfig;
+    use crate::peg_queue::PriorityPolicy;
+    use crate::peg_wallet::PegWalletAddress;
+    use crate::stacks_node::{MockStacksNode, PegInOp, PegOutRequestOp};
+    use crate::stacks_transaction::StacksTransaction;
+    use bitcoin::secp256k1::{Message, Secp256k1};
     use blockstack_lib::burnchains::Txid;
     use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20};
-    use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+    use blockstack_lib::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+    use blockstack_lib::util::hash::Hash160;
+    use futures_channel::oneshot;
+
+    /// Stacks transactions are built by stacks.js, which needs deno
+    struct NoStacksWallet;
+
+    impl StacksWalletTrait for NoStacksWallet {
+        fn build_mint_transaction(
+            &mut self,
+            _op: &stacks_node::PegInOp,
+        ) -> std::result::Result<StacksTransaction, PegWalletError> {
+            unimplemented!()
+        }
+        fn build_burn_transaction(
+            &mut self,
+            _op: &stacks_node::PegOutRequestOp,
+        ) -> std::result::Result<StacksTransaction, PegWalletError> {
+            unimplemented!()
+        }
+        fn build_set_address_transaction(
+            &mut self,
+            _address: PegWalletAddress,
+        ) -> std::result::Result<StacksTransaction, PegWalletError> {
+            unimplemented!()
+        }
+        fn build_contract_deploy_transaction(
+            &mut self,
+            _name: &str,
+            _source: &str,
+            _nonce: u64,
+        ) -> std::result::Result<StacksTransaction, PegWalletError> {
+            unimplemented!()
+        }
+        fn contract(&self) -> (&str, &str) {
+            ("ST000000000000000000002AMW42H", "sbtc-alpha")
+        }
+    }
+
+    struct TestPegWallet {
+        bitcoin_wallet: BitcoinWallet,
+        stacks_wallet: NoStacksWallet,
+    }
+
+    impl PegWallet for TestPegWallet {
+        type StacksWallet = NoStacksWallet;
+        type BitcoinWallet = BitcoinWallet;
+        fn stacks_mut(&mut self) -> &mut NoStacksWallet {
+            &mut self.stacks_wallet
+        }
+        fn bitcoin_mut(&mut self) -> &mut BitcoinWallet {
+            &mut self.bitcoin_wallet
+        }
+    }
+
+    /// A coordinator over boxed nodes and frost coordinator, standing in for remote ones
+    struct TestCoordinator {
+        frost_coordinator: Box<dyn FrostCoordinator>,
+        peg_queue: SqlitePegQueue,
+        fee_wallet: TestPegWallet,
+        stacks_node: Box<dyn StacksNode>,
+        bitcoin_node: Box<dyn BitcoinNode>,
+        incident_log: IncidentLog,
+        fee_ledger: FeeLedger,
+        peg_latency: PegLatency,
+        bitcoin_txs: BitcoinTxLog,
+        operational_mode: ModeStore,
+    }
+
+    impl TestCoordinator {
+        fn new() -> Self {
+            Self {
+                frost_coordinator: Box::new(MockFrostCoordinator::new()),
+                peg_queue: SqlitePegQueue::in_memory(0).unwrap(),
+                fee_wallet: TestPegWallet {
+                    bitcoin_wallet: BitcoinWallet::default(),
+                    stacks_wallet: NoStacksWallet,
+                },
+                stacks_node: Box::new(MockStacksNode::new()),
+                bitcoin_node: Box::new(MockBitcoinNode::new()),
+                incident_log: IncidentLog::in_memory().unwrap(),
+                fee_ledger: FeeLedger::in_memory().unwrap(),
+                peg_latency: PegLatency::in_memory(Default::default()).unwrap(),
+                bitcoin_txs: BitcoinTxLog::in_memory().unwrap(),
+                operational_mode: ModeStore::in_memory().unwrap(),
+            }
+        }
+    }
+
+    impl Coordinator for TestCoordinator {
+        type PegQueue = SqlitePegQueue;
+        type FeeWallet = TestPegWallet;
+        type StacksNode = Box<dyn StacksNode>;
+        type BitcoinNode = Box<dyn BitcoinNode>;
+        type FrostCoordinator = Box<dyn FrostCoordinator>;
+
+        fn peg_queue(&self) -> &SqlitePegQueue {
+            &self.peg_queue
+        }
+        fn fee_wallet(&mut self) -> &mut TestPegWallet {
+            &mut self.fee_wallet
+        }
+        fn frost_coordinator(&self) -> &Box<dyn FrostCoordinator> {
+            &self.frost_coordinator
+        }
+        fn frost_coordinator_mut(&mut self) -> &mut Box<dyn FrostCoordinator> {
+            &mut self.frost_coordinator
+        }
+        fn stacks_node(&self) -> &Box<dyn StacksNode> {
+            &self.stacks_node
+        }
+        fn bitcoin_node(&self) -> &Box<dyn BitcoinNode> {
+            &self.bitcoin_node
+        }
+        fn incident_log(&self) -> &IncidentLog {
+            &self.incident_log
+        }
+        fn fee_ledger(&self) -> &FeeLedger {
+            &self.fee_ledger
+        }
+        fn fee_sweep_policy(&self) -> Option<&FeeSweepPolicy> {
+            None
+        }
+        fn fee_oracle(&self) -> Option<&FeeOracle> {
+            None
+        }
+        fn peg_latency(&self) -> &PegLatency {
+            &self.peg_latency
+        }
+        fn bitcoin_txs(&self) -> &BitcoinTxLog {
+            &self.bitcoin_txs
+        }
+        fn operational_mode(&self) -> &ModeStore {
+            &self.operational_mode
+        }
+    }
+
+    /// Stacks chain tip the mock stacks nodes report
+    const STACKS_TIP: [u8; 32] = [7; 32];
+
+    /// Have `bitcoin_node` know the output the fulfillment of each of `ops` spends, holding the
+    /// peg-out's amount and fulfillment fee
+    fn expect_spendable(bitcoin_node: &mut MockBitcoinNode, ops: Vec<PegOutRequestOp>) {
+        bitcoin_node.expect_utxos().returning(move |_| {
+            Ok(crate::bitcoin_node::UtxoSnapshot {
+                block_height: 3,
+                block_hash: bitcoin::BlockHash::all_zeros(),
+                utxos: ops
+                    .iter()
+                    .map(|op| crate::bitcoin_node::Utxo {
+                        outpoint: spent_outpoint(op).unwrap(),
+                        amount: op.amount + op.fulfillment_fee,
+                        height: 3,
+                    })
+                    .collect(),
+            })
+        });
+    }
 
-    #[ignore]
     #[test]
     fn btc_fulfill_peg_out() {
-        let config = Config {
-            sbtc_contract: "".to_string(),
-            stacks_private_key: "".to_string(),
-            stacks_node_rpc_url: "".to_string(),
-            bitcoin_node_rpc_url: "".to_string(),
-            frost_dkg_round_id: 0,
-            signer_config_path: "conf/signer.toml".to_string(),
-            start_block_height: None,
-            rusqlite_path: None,
-        };
-        // todo: make StacksCoordinator with mock FrostCoordinator to locally generate PublicKey and Signature for unit test
-        let mut sc = StacksCoordinator::try_from(config).unwrap();
+        let mut coordinator = TestCoordinator::new();
+        let public_key = coordinator.run_dkg().unwrap();
         let recipient = PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]);
         let peg_wallet_address = PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]);
         let op = PegOutRequestOp {
-            amount: 0,
-            recipient: recipient,
+            amount: 1000,
+            recipient,
             signature: blockstack_lib::util::secp256k1::MessageSignature([0; 65]),
-            peg_wallet_address: peg_wallet_address,
-            fulfillment_fee: 0,
+            peg_wallet_address,
+            fulfillment_fee: 500,
             memo: vec![],
             txid: Txid([0; 32]),
             vtxindex: 0,
             block_height: 0,
             burn_header_hash: BurnchainHeaderHash([0; 32]),
         };
-        let btc_tx_result = sc.btc_fulfill_peg_out(&op);
-        assert!(btc_tx_result.is_ok());
-        let btc_tx = btc_tx_result.unwrap();
-        let mut btc_tx_encoded: Vec<u8> = vec![];
-        btc_tx.consensus_encode(&mut btc_tx_encoded).unwrap();
-        let verify_result = bitcoin::bitcoinconsensus::verify(&[], 100, &btc_tx_encoded, 0);
-        assert!(verify_result.is_ok())
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_stacks_tip().returning(|| Ok(STACKS_TIP));
+        coordinator.stacks_node = Box::new(stacks_node);
+        let mut bitcoin_node = MockBitcoinNode::new();
+        // nothing to spend, then the output the peg-out request names
+        bitcoin_node.expect_utxos().times(1).returning(|_| {
+            Ok(crate::bitcoin_node::UtxoSnapshot {
+                block_height: 3,
+                block_hash: bitcoin::BlockHash::all_zeros(),
+                utxos: vec![],
+            })
+        });
+        expect_spendable(&mut bitcoin_node, vec![op.clone()]);
+        coordinator.bitcoin_node = Box::new(bitcoin_node);
+        assert!(coordinator.btc_fulfill_peg_out(&op).is_err());
+        let btc_tx = coordinator.btc_fulfill_peg_out(&op).unwrap();
+        let payload =
+            crate::stacks_node::bitcoin_ops::op_return_data(&btc_tx.output[0].script_pubkey)
+                .unwrap();
+        assert_eq!(payload[3..35], STACKS_TIP);
+
+        let sighash = bitcoin::util::sighash::SighashCache::new(&btc_tx)
+            .taproot_signature_hash(
+                0,
+                &Prevouts::All(&[&btc_tx.output[0]]),
+                None,
+                None,
+                SchnorrSighashType::All,
+            )
+            .unwrap();
+        let witness = btc_tx.input[0].witness.to_vec();
+        assert_eq!(witness.len(), 1);
+        assert_eq!(witness[0].len(), 65);
+        assert_eq!(witness[0][64], SchnorrSighashType::All as u8);
+        let signature = ThresholdSignature::from_slice(&witness[0][..64]).unwrap();
+        // a key path spend of the P2TR output of the aggregate key
+        let secp = Secp256k1::verification_only();
+        let wallet = Script::new_v1_p2tr(&secp, public_key, None);
+        let output_key = XOnlyPublicKey::from_slice(&wallet.as_bytes()[2..]).unwrap();
+        secp.verify_schnorr(
+            &signature.to_schnorr().unwrap(),
+            &Message::from_slice(&sighash).unwrap(),
+            &output_key,
+        )
+        .unwrap();
+        assert!(secp
+            .verify_schnorr(
+                &signature.to_schnorr().unwrap(),
+                &Message::from_slice(&sighash).unwrap(),
+                &public_key,
+            )
+            .is_err());
+    }
+
+    fn block(time: u32, txdata: Vec<BitcoinTransaction>) -> bitcoin::Block {
+        bitcoin::Block {
+            header: bitcoin::BlockHeader {
+                version: 1,
+                prev_blockhash: bitcoin::hashes::Hash::all_zeros(),
+                merkle_root: bitcoin::hashes::Hash::all_zeros(),
+                time,
+                bits: 0,
+                nonce: 0,
+            },
+            txdata,
+        }
+    }
+
+    #[test]
+    fn confirmed_fulfillments_are_acknowledged_and_held_to_their_slo() {
+        let mut coordinator = TestCoordinator::new();
+        coordinator.peg_latency = PegLatency::in_memory(PegLatencyConfig {
+            peg_out_slo_secs: Some(1_800),
+            ..Default::default()
+        })
+        .unwrap();
+        let op = SbtcOp::PegOutRequest(PegOutRequestOp {
+            amount: 1000,
+            recipient: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+            signature: blockstack_lib::util::secp256k1::MessageSignature([0; 65]),
+            peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+            fulfillment_fee: 0,
+            memo: vec![],
+            txid: Txid([1; 32]),
+            vtxindex: 0,
+            block_height: 3,
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+        });
+        let op_id = coordinator.peg_queue.submit(op.clone()).unwrap();
+        assert_eq!(coordinator.peg_queue.sbtc_ops_for_tick().unwrap(), vec![op]);
+        let fulfill_tx = BitcoinTransaction {
+            version: 2,
+            lock_time: bitcoin::PackedLockTime(0),
+            input: vec![],
+            output: vec![],
+        };
+        coordinator
+            .peg_queue
+            .record_stage(
+                &op_id,
+                Stage::BitcoinBroadcast,
+                &fulfill_tx.txid().to_string(),
+            )
+            .unwrap();
+        coordinator
+            .bitcoin_txs
+            .record(&fulfill_tx, TxKind::Fulfillment, None, &[op_id.to_string()])
+            .unwrap();
+        let txid = fulfill_tx.txid();
+
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_count().returning(|| Ok(5));
+        bitcoin_node.expect_block().returning(move |height| {
+            Ok(match height {
+                3 => block(1_000, vec![]),
+                5 => block(4_000, vec![fulfill_tx.clone()]),
+                _ => block(2_000, vec![]),
+            })
+        });
+        coordinator.bitcoin_node = Box::new(bitcoin_node);
+        coordinator.confirm_fulfillments().unwrap();
+
+        let traced = coordinator.peg_queue.trace(&op_id).unwrap().unwrap();
+        assert_eq!(traced.status, "acknowledged");
+        let stats = coordinator.peg_latency.stats().unwrap();
+        assert_eq!(stats.peg_out.count, 1);
+        assert_eq!(stats.peg_out.max_secs, 3_000);
+        assert_eq!(stats.peg_out.breaches, 1);
+        let incidents = coordinator.incident_log.list(false).unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].kind, IncidentKind::SloBreach);
+        assert_eq!(coordinator.peg_latency.scanned_height().unwrap(), Some(5));
+        assert!(coordinator.bitcoin_txs.unconfirmed().unwrap().is_empty());
+        let txs = coordinator.bitcoin_txs.list(Some(5)).unwrap();
+        assert_eq!(txs[0].txid, txid.to_string());
+        assert_eq!(
+            (txs[0].confirmed_height, txs[0].confirmations),
+            (Some(5), 1)
+        );
+    }
+
+    #[test]
+    fn peg_ins_the_contract_already_minted_are_skipped() {
+        let mut coordinator = TestCoordinator::new();
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_call_read_only()
+            .withf(|address, name, function, args| {
+                address == "ST000000000000000000002AMW42H"
+                    && name == "sbtc-alpha"
+                    && function == "is-peg-in-processed"
+                    && args == [clarity::string_ascii(&Txid([2; 32]).to_string()).unwrap()]
+            })
+            .returning(|_, _, _, _| Ok("0x03".to_string()));
+        coordinator.stacks_node = Box::new(stacks_node);
+        let op = PegInOp {
+            recipient: StacksAddress::new(26, Hash160([0; 20])).into(),
+            peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+            amount: 1000,
+            memo: vec![],
+            txid: Txid([2; 32]),
+            vtxindex: 0,
+            block_height: 3,
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+        };
+        let op_id = coordinator
+            .peg_queue
+            .submit(SbtcOp::PegIn(op.clone()))
+            .unwrap();
+
+        // NoStacksWallet panics if asked to build the mint
+        coordinator.peg_in(&op_id, op).unwrap();
+
+        let traced = coordinator.peg_queue.trace(&op_id).unwrap().unwrap();
+        let last = traced.events.last().unwrap();
+        assert_eq!(last.stage, Stage::AlreadyProcessed);
+        assert_eq!(last.detail, "mint");
+    }
+
+    #[test]
+    fn peg_outs_the_wallet_cannot_pay_wait_for_funds() {
+        let mut coordinator = TestCoordinator::new();
+        coordinator.peg_queue =
+            SqlitePegQueue::in_memory(0)
+                .unwrap()
+                .with_priority_policy(PriorityPolicy {
+                    peg_out_quota: 2,
+                    ..Default::default()
+                });
+        coordinator.run_dkg().unwrap();
+        // NoStacksWallet panics if asked to build the burn
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_call_read_only()
+            .returning(|_, _, _, _| Ok("0x03".to_string()));
+        stacks_node.expect_stacks_tip().returning(|| Ok(STACKS_TIP));
+        coordinator.stacks_node = Box::new(stacks_node);
+        let peg_out = |txid, amount| PegOutRequestOp {
+            amount,
+            recipient: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+            signature: blockstack_lib::util::secp256k1::MessageSignature([0; 65]),
+            peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [1; 20]),
+            fulfillment_fee: 500,
+            memo: vec![],
+            txid: Txid([txid; 32]),
+            vtxindex: 0,
+            block_height: 3,
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+        };
+        let ops = vec![peg_out(1, 5_000), peg_out(2, 1_000)];
+        let large = coordinator
+            .peg_queue
+            .submit(SbtcOp::PegOutRequest(ops[0].clone()))
+            .unwrap();
+        let small = coordinator
+            .peg_queue
+            .submit(SbtcOp::PegOutRequest(ops[1].clone()))
+            .unwrap();
+        let wallet = |balance: u64, broadcasts: usize| {
+            let mut bitcoin_node = MockBitcoinNode::new();
+            bitcoin_node
+                .expect_balance()
+                .returning(move |_| Ok(balance));
+            bitcoin_node
+                .expect_broadcast_transaction()
+                .times(broadcasts)
+                .returning(|tx| Ok(tx.txid()));
+            expect_spendable(&mut bitcoin_node, ops.clone());
+            Box::new(bitcoin_node)
+        };
+        let status = |coordinator: &TestCoordinator, op_id| {
+            coordinator.peg_queue.trace(op_id).unwrap().unwrap().status
+        };
+
+        coordinator.bitcoin_node = wallet(2_000, 1);
+        coordinator.process_queue().unwrap();
+        assert_eq!(status(&coordinator, &large), "awaiting_funds");
+        assert_eq!(status(&coordinator, &small), "pending");
+        let incidents = coordinator.incident_log.list(false).unwrap();
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].kind, IncidentKind::Shortfall);
+
+        // the unconfirmed fulfillment of the small peg-out still counts against the balance
+        coordinator.bitcoin_node = wallet(5_500, 0);
+        coordinator.process_queue().unwrap();
+        assert_eq!(status(&coordinator, &large), "awaiting_funds");
+        assert_eq!(coordinator.incident_log.list(false).unwrap().len(), 1);
+
+        coordinator.bitcoin_node = wallet(6_000, 1);
+        coordinator.process_queue().unwrap();
+        let traced = coordinator.peg_queue.trace(&large).unwrap().unwrap();
+        assert_eq!(traced.status, "pending");
+        let stages: Vec<Stage> = traced.events.iter().map(|event| event.stage).collect();
+        assert_eq!(
+            &stages[2..],
+            [
+                Stage::AwaitingFunds,
+                Stage::FundsAvailable,
+                Stage::AlreadyProcessed,
+                Stage::Signed,
+                Stage::BitcoinBroadcast,
+                Stage::FeeRecorded,
+            ]
+        );
+    }
+
+    #[test]
+    fn peg_outs_of_a_tick_are_signed_in_one_round() {
+        let mut coordinator = TestCoordinator::new();
+        coordinator.peg_queue =
+            SqlitePegQueue::in_memory(0)
+                .unwrap()
+                .with_priority_policy(PriorityPolicy {
+                    peg_out_quota: 3,
+                    ..Default::default()
+                });
+        coordinator.run_dkg().unwrap();
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_call_read_only()
+            .returning(|_, _, _, _| Ok("0x03".to_string()));
+        stacks_node.expect_stacks_tip().returning(|| Ok(STACKS_TIP));
+        coordinator.stacks_node = Box::new(stacks_node);
+        let ops: Vec<PegOutRequestOp> = (1..=3)
+            .map(|txid| PegOutRequestOp {
+                amount: 1_000,
+                recipient: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+                signature: blockstack_lib::util::secp256k1::MessageSignature([0; 65]),
+                peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [1; 20]),
+                fulfillment_fee: 500,
+                memo: vec![],
+                txid: Txid([txid; 32]),
+                vtxindex: 0,
+                block_height: 3,
+                burn_header_hash: BurnchainHeaderHash([0; 32]),
+            })
+            .collect();
+        let op_ids: Vec<OpId> = ops
+            .iter()
+            .map(|op| {
+                coordinator
+                    .peg_queue
+                    .submit(SbtcOp::PegOutRequest(op.clone()))
+                    .unwrap()
+            })
+            .collect();
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_balance().returning(|_| Ok(10_000));
+        expect_spendable(&mut bitcoin_node, ops);
+        bitcoin_node
+            .expect_broadcast_transaction()
+            .times(3)
+            .returning(|tx| Ok(tx.txid()));
+        coordinator.bitcoin_node = Box::new(bitcoin_node);
+
+        let sign_id = coordinator.frost_coordinator.current_sign_id();
+        coordinator.process_queue().unwrap();
+        assert_eq!(coordinator.frost_coordinator.current_sign_id(), sign_id + 1);
+        for op_id in &op_ids {
+            let traced = coordinator.peg_queue.trace(op_id).unwrap().unwrap();
+            let stages: Vec<Stage> = traced.events.iter().map(|event| event.stage).collect();
+            assert_eq!(
+                stages[stages.len() - 3..],
+                [Stage::Signed, Stage::BitcoinBroadcast, Stage::FeeRecorded]
+            );
+        }
+    }
+
+    #[test]
+    fn refused_fulfillments_are_not_recorded_as_broadcast() {
+        let mut coordinator = TestCoordinator::new();
+        coordinator.run_dkg().unwrap();
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_call_read_only()
+            .returning(|_, _, _, _| Ok("0x03".to_string()));
+        stacks_node.expect_stacks_tip().returning(|| Ok(STACKS_TIP));
+        coordinator.stacks_node = Box::new(stacks_node);
+        let op = PegOutRequestOp {
+            amount: 1_000,
+            recipient: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+            signature: blockstack_lib::util::secp256k1::MessageSignature([0; 65]),
+            peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [1; 20]),
+            fulfillment_fee: 500,
+            memo: vec![],
+            txid: Txid([1; 32]),
+            vtxindex: 0,
+            block_height: 3,
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+        };
+        let op_id = coordinator
+            .peg_queue
+            .submit(SbtcOp::PegOutRequest(op.clone()))
+            .unwrap();
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_balance().returning(|_| Ok(10_000));
+        expect_spendable(&mut bitcoin_node, vec![op]);
+        bitcoin_node
+            .expect_broadcast_transaction()
+            .times(1)
+            .returning(|_| Err(BitcoinNodeError::RpcMissingResult));
+        coordinator.bitcoin_node = Box::new(bitcoin_node);
+
+        assert!(matches!(
+            coordinator.process_queue(),
+            Err(Error::BitcoinNodeError(BitcoinNodeError::RpcMissingResult))
+        ));
+        let traced = coordinator.peg_queue.trace(&op_id).unwrap().unwrap();
+        let stages: Vec<Stage> = traced.events.iter().map(|event| event.stage).collect();
+        assert_eq!(stages[stages.len() - 2..], [Stage::Signed, Stage::Failed]);
+        assert!(coordinator.bitcoin_txs.list(None).unwrap().is_empty());
+        assert!(coordinator.fee_ledger.totals().unwrap().is_empty());
+    }
+
+    #[test]
+    fn health_checks_move_the_mode_until_an_operator_pauses_it() {
+        let mut coordinator = TestCoordinator::new();
+        assert_eq!(coordinator.check_mode().unwrap(), Mode::Bootstrap);
+        coordinator.run_dkg().unwrap();
+        assert_eq!(coordinator.check_mode().unwrap(), Mode::Normal);
+
+        let id = coordinator
+            .incident_log
+            .record(&NewIncident {
+                kind: IncidentKind::InvariantBreach,
+                signer_ids: vec![],
+                summary: "aggregate signature does not verify".to_string(),
+            })
+            .unwrap();
+        assert_eq!(coordinator.check_mode().unwrap(), Mode::Degraded);
+        let state = coordinator.operational_mode.get().unwrap();
+        assert_eq!(state.changed_by, HEALTH_CHECK);
+        assert_eq!(state.reason, format!("incidents [{id}] are open"));
+        coordinator
+            .incident_log
+            .resolve(id, "alice", "bad relay")
+            .unwrap();
+        assert_eq!(coordinator.check_mode().unwrap(), Mode::Normal);
+
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::Pause(
+            "alice".to_string(),
+            "wallet handoff".to_string(),
+            reply,
+        ));
+        receiver.try_recv().unwrap().unwrap().unwrap();
+        assert_eq!(coordinator.check_mode().unwrap(), Mode::Paused);
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::Status(reply));
+        let status = receiver.try_recv().unwrap().unwrap().unwrap();
+        assert_eq!(status.mode.mode, Mode::Paused);
+        assert_eq!(status.mode.reason, "wallet handoff");
+
+        coordinator
+            .operational_mode
+            .resume("alice", "handoff done")
+            .unwrap();
+        assert_eq!(coordinator.check_mode().unwrap(), Mode::Normal);
+    }
+
+    type DynCoordinator = dyn Coordinator<
+        PegQueue = SqlitePegQueue,
+        FeeWallet = TestPegWallet,
+        StacksNode = Box<dyn StacksNode>,
+        BitcoinNode = Box<dyn BitcoinNode>,
+        FrostCoordinator = Box<dyn FrostCoordinator>,
+    >;
+
+    #[test]
+    fn requests_are_served_through_a_trait_object() {
+        let mut test_coordinator = TestCoordinator::new();
+        let coordinator: &mut DynCoordinator = &mut test_coordinator;
+
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::Dkg(reply));
+        let public_key = receiver.try_recv().unwrap().unwrap().unwrap();
+
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::Sign(MessageDigest::new([7; 32]), reply));
+        let signature = receiver.try_recv().unwrap().unwrap().unwrap();
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &signature.to_schnorr().unwrap(),
+                &Message::from_slice(&[7; 32]).unwrap(),
+                &public_key,
+            )
+            .unwrap();
+
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::Status(reply));
+        let status = receiver.try_recv().unwrap().unwrap().unwrap();
+        assert_eq!(status.aggregate_public_key.unwrap().to_x_only(), public_key);
+        assert_eq!(status.party_commitments.len(), 1);
+
+        // the mock's only signer cannot be left out
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::SetSigningQuorum(
+            SigningQuorum::exclude([1]),
+            reply,
+        ));
+        assert!(receiver.try_recv().unwrap().unwrap().is_err());
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::SetSigningQuorum(SigningQuorum::pin([1]), reply));
+        receiver.try_recv().unwrap().unwrap().unwrap();
+        let (reply, mut receiver) = oneshot::channel();
+        coordinator.handle_request(Request::Status(reply));
+        let status = receiver.try_recv().unwrap().unwrap().unwrap();
+        assert_eq!(status.signing_quorum, SigningQuorum::pin([1]));
+    }
+
+    #[test]
+    fn failure_reports_name_the_rounds_and_heights_in_flight() {
+        let mut coordinator = TestCoordinator::new();
+        coordinator.run_dkg().unwrap();
+        let report = coordinator.failure_report(&Error::CoordinatorStopped);
+        assert_eq!(report.error_class, ErrorClass::Internal);
+        assert_eq!(report.exit_code, 70);
+        assert_eq!(report.rounds["dkg_id"], 1);
+        assert!(!report.heights.contains_key("burn_block_height"));
+
+        coordinator
+            .peg_queue
+            .submit(SbtcOp::PegOutRequest(PegOutRequestOp {
+                amount: 1_000,
+                recipient: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [0; 20]),
+                signature: blockstack_lib::util::secp256k1::MessageSignature([0; 65]),
+                peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [1; 20]),
+                fulfillment_fee: 0,
+                memo: vec![],
+                txid: Txid([1; 32]),
+                vtxindex: 0,
+                block_height: 7,
+                burn_header_hash: BurnchainHeaderHash([0; 32]),
+            }))
+            .unwrap();
+        let error = Error::BitcoinNodeError(BitcoinNodeError::RpcMissingResult);
+        let report = coordinator.failure_report(&error);
+        assert_eq!(report.error_class, ErrorClass::Unavailable);
+        assert!(report.restartable);
+        assert_eq!(report.heights["burn_block_height"], 7);
+    }
+
+    #[test]
+    fn prove_reserve_signs_the_peg_wallet_utxos_of_the_aggregate_key() {
+        let mut coordinator = TestCoordinator::new();
+        assert!(coordinator.prove_reserve().is_err());
+        coordinator.run_dkg().unwrap();
+        let key = coordinator
+            .frost_coordinator
+            .get_aggregate_public_key()
+            .unwrap();
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node
+            .expect_utxos()
+            .withf(move |script| *script == crate::proof_of_reserve::wallet_script(&key))
+            .returning(|_| {
+                Ok(crate::bitcoin_node::UtxoSnapshot {
+                    block_height: 812,
+                    block_hash: bitcoin::BlockHash::all_zeros(),
+                    utxos: vec![crate::bitcoin_node::Utxo {
+                        outpoint: bitcoin::OutPoint::new(bitcoin::Txid::all_zeros(), 1),
+                        amount: 25_000,
+                        height: 800,
+                    }],
+                })
+            });
+        coordinator.bitcoin_node = Box::new(bitcoin_node);
+
+        let attestation = coordinator.prove_reserve().unwrap();
+        assert_eq!(attestation.statement.block_height, 812);
+        assert_eq!(attestation.statement.total, 25_000);
+        attestation.verify(Some(&key)).unwrap();
     }
 }
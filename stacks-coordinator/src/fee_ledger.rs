@@ -0,0 +1,301 @@
+//! Accounting of the fulfillment fees peg-out requesters pay into the peg wallet.
+//!
+//! Every fulfilled peg-out leaves its fee output in the peg wallet. The outputs are recorded
+//! per key generation, the DKG round whose aggregate key controls them, in a `collected_fees`
+//! table next to the peg queue. A [`FeeSweepPolicy`] periodically moves the unswept outputs of
+//! the current generation to an operator address in one threshold-signed transaction.
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::{Address, OutPoint, Script};
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension};
+use serde::Serialize;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Invalid outpoint {0}")]
+    InvalidOutPoint(String),
+    #[error("Invalid script {0}")]
+    InvalidScript(String),
+    #[error("Invalid sweep address {0}: {1}")]
+    InvalidAddress(String, bitcoin::util::address::Error),
+}
+
+/// When and where collected fees are swept
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct FeeSweepPolicy {
+    /// Operator controlled bitcoin address the fees are swept to
+    pub address: String,
+    /// Least amount of unswept fees, in sats, worth a sweep
+    pub min_amount: u64,
+    /// Least time between two sweeps
+    pub interval_secs: u64,
+    /// Fee paid by the sweep transaction, in sats
+    #[serde(default = "default_tx_fee")]
+    pub tx_fee: u64,
+}
+
+fn default_tx_fee() -> u64 {
+    1_000
+}
+
+impl FeeSweepPolicy {
+    /// Output script of the sweep address
+    pub fn script_pubkey(&self) -> Result<Script, Error> {
+        Address::from_str(&self.address)
+            .map(|address| address.script_pubkey())
+            .map_err(|e| Error::InvalidAddress(self.address.clone(), e))
+    }
+}
+
+/// A fee output held by the peg wallet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollectedFee {
+    pub outpoint: OutPoint,
+    /// DKG round whose aggregate key controls the output
+    pub generation: u64,
+    /// Value in sats
+    pub amount: u64,
+    pub script_pubkey: Script,
+}
+
+/// Fees collected under one key generation, in sats
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GenerationFees {
+    pub generation: u64,
+    pub accrued: u64,
+    pub swept: u64,
+}
+
+pub struct FeeLedger {
+    conn: RusqliteConnection,
+}
+
+impl FeeLedger {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute_batch(Self::sql_schema())?;
+        Ok(Self { conn })
+    }
+
+    /// Record a fee output. Recording the same output twice has no effect.
+    pub fn record(&self, fee: &CollectedFee) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_fee(),
+            rusqlite::params![
+                fee.outpoint.to_string(),
+                fee.generation as i64,
+                fee.amount as i64,
+                fee.script_pubkey.to_hex(),
+                now() as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Fee outputs of `generation` which have not been swept, oldest first
+    pub fn unswept(&self, generation: u64) -> Result<Vec<CollectedFee>, Error> {
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_unswept())?
+            .query_map(rusqlite::params![generation as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(outpoint, amount, script)| {
+                Ok(CollectedFee {
+                    outpoint: OutPoint::from_str(&outpoint)
+                        .map_err(|_| Error::InvalidOutPoint(outpoint))?,
+                    generation,
+                    amount: amount as u64,
+                    script_pubkey: Script::from_hex(&script)
+                        .map_err(|_| Error::InvalidScript(script))?,
+                })
+            })
+            .collect()
+    }
+
+    /// Mark `fees` as spent by the sweep transaction `txid`
+    pub fn record_sweep(&self, txid: &bitcoin::Txid, fees: &[CollectedFee]) -> Result<(), Error> {
+        let swept_at = now() as i64;
+        for fee in fees {
+            self.conn.execute(
+                Self::sql_mark_swept(),
+                rusqlite::params![fee.outpoint.to_string(), txid.to_string(), swept_at],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Unix time in seconds of the last sweep, if any
+    pub fn last_sweep_at(&self) -> Result<Option<u64>, Error> {
+        Ok(self
+            .conn
+            .query_row(Self::sql_select_last_sweep(), rusqlite::params![], |row| {
+                row.get::<_, Option<i64>>(0)
+            })
+            .optional()?
+            .flatten()
+            .map(|t| t as u64))
+    }
+
+    /// Whether `policy.interval_secs` have passed since the last sweep
+    pub fn sweep_due(&self, policy: &FeeSweepPolicy) -> Result<bool, Error> {
+        Ok(match self.last_sweep_at()? {
+            Some(last) => now() >= last.saturating_add(policy.interval_secs),
+            None => true,
+        })
+    }
+
+    /// Accrued and swept fees of each generation, oldest first
+    pub fn totals(&self) -> Result<Vec<GenerationFees>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_totals())?
+            .query_map(rusqlite::params![], |row| {
+                Ok(GenerationFees {
+                    generation: row.get::<_, i64>(0)? as u64,
+                    accrued: row.get::<_, i64>(1)? as u64,
+                    swept: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS collected_fees (
+            outpoint TEXT PRIMARY KEY,
+            generation INTEGER NOT NULL,
+            amount INTEGER NOT NULL,
+            script TEXT NOT NULL,
+            collected_at INTEGER NOT NULL,
+            sweep_txid TEXT,
+            swept_at INTEGER
+        );
+        "#
+    }
+
+    const fn sql_insert_fee() -> &'static str {
+        r#"
+        INSERT OR IGNORE INTO collected_fees (outpoint, generation, amount, script, collected_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#
+    }
+
+    const fn sql_select_unswept() -> &'static str {
+        r#"
+        SELECT outpoint, amount, script FROM collected_fees
+        WHERE generation=?1 AND sweep_txid IS NULL ORDER BY rowid ASC
+        "#
+    }
+
+    const fn sql_mark_swept() -> &'static str {
+        r#"
+        UPDATE collected_fees SET sweep_txid=?2, swept_at=?3 WHERE outpoint=?1
+        "#
+    }
+
+    const fn sql_select_last_sweep() -> &'static str {
+        r#"
+        SELECT MAX(swept_at) FROM collected_fees
+        "#
+    }
+
+    const fn sql_select_totals() -> &'static str {
+        r#"
+        SELECT generation, SUM(amount), SUM(CASE WHEN sweep_txid IS NULL THEN 0 ELSE amount END)
+        FROM collected_fees GROUP BY generation ORDER BY generation ASC
+        "#
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+
+    fn fee(vout: u32, generation: u64, amount: u64) -> CollectedFee {
+        CollectedFee {
+            outpoint: OutPoint::new(bitcoin::Txid::from_inner([1; 32]), vout),
+            generation,
+            amount,
+            script_pubkey: Script::from_hex(
+                "51202222222222222222222222222222222222222222222222222222222222222222",
+            )
+            .unwrap(),
+        }
+    }
+
+    #[test]
+    fn totals_separate_accrued_and_swept_fees_per_generation() {
+        let ledger = FeeLedger::in_memory().unwrap();
+        ledger.record(&fee(0, 1, 1_000)).unwrap();
+        ledger.record(&fee(1, 2, 2_000)).unwrap();
+        ledger.record(&fee(2, 2, 3_000)).unwrap();
+        // a fee seen twice is only counted once
+        ledger.record(&fee(2, 2, 3_000)).unwrap();
+        assert_eq!(ledger.last_sweep_at().unwrap(), None);
+
+        let unswept = ledger.unswept(2).unwrap();
+        assert_eq!(unswept, vec![fee(1, 2, 2_000), fee(2, 2, 3_000)]);
+        ledger
+            .record_sweep(&bitcoin::Txid::from_inner([9; 32]), &unswept[..1])
+            .unwrap();
+
+        assert!(ledger.last_sweep_at().unwrap().is_some());
+        assert_eq!(ledger.unswept(2).unwrap(), vec![fee(2, 2, 3_000)]);
+        assert_eq!(
+            ledger.totals().unwrap(),
+            vec![
+                GenerationFees {
+                    generation: 1,
+                    accrued: 1_000,
+                    swept: 0
+                },
+                GenerationFees {
+                    generation: 2,
+                    accrued: 5_000,
+                    swept: 2_000
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn sweep_address_must_parse() {
+        let policy = FeeSweepPolicy {
+            address: "not an address".to_string(),
+            min_amount: 10_000,
+            interval_secs: 86_400,
+            tx_fee: 1_000,
+        };
+        assert!(matches!(
+            policy.script_pubkey(),
+            Err(Error::InvalidAddress(..))
+        ));
+    }
+}
@@ -0,0 +1,453 @@
+//! Fee rates of both chains, sampled from the nodes over time.
+//!
+//! A [`FeeOracle`] asks the stacks node for its fee estimate and the bitcoin node for its
+//! `estimatesmartfee` rate every `interval_secs`, and keeps the samples of the last `window_secs`
+//! in a `fee_samples` table next to the peg queue, so the history survives restarts. The rate the
+//! wallets pay is, per chain, in order of precedence:
+//!
+//! 1. `override_rate`, if set
+//! 2. the `percentile` of the samples in the window, if set
+//! 3. the exponential moving average of the samples, each weighing half as much per
+//!    `half_life_secs` passed
+//!
+//! clamped to `min_rate..=max_rate`. Without samples and without an override there is no rate,
+//! and the wallets fall back to their fixed fees.
+use std::fmt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use frost_signer::logging::target::WALLET;
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::bitcoin_node::BitcoinNode;
+use crate::stacks_node::StacksNode;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+}
+
+/// The chain a fee rate is paid on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Chain {
+    /// Microstacks per byte
+    Stacks,
+    /// Sats per vbyte
+    Bitcoin,
+}
+
+impl Chain {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Chain::Stacks => "stacks",
+            Chain::Bitcoin => "bitcoin",
+        }
+    }
+}
+
+/// A `[fee_oracle]` config table
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct FeeOracleConfig {
+    /// Least time between two samples
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    /// Age at which a sample weighs half as much in the moving average
+    #[serde(default = "default_half_life_secs")]
+    pub half_life_secs: u64,
+    /// Age of the oldest sample kept
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    /// Blocks within which bitcoin transactions should confirm
+    #[serde(default = "default_conf_target")]
+    pub conf_target: u16,
+    #[serde(default)]
+    pub stacks: RateSelection,
+    #[serde(default)]
+    pub bitcoin: RateSelection,
+}
+
+fn default_interval_secs() -> u64 {
+    60
+}
+
+fn default_half_life_secs() -> u64 {
+    1_800
+}
+
+fn default_window_secs() -> u64 {
+    86_400
+}
+
+fn default_conf_target() -> u16 {
+    6
+}
+
+impl Default for FeeOracleConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_interval_secs(),
+            half_life_secs: default_half_life_secs(),
+            window_secs: default_window_secs(),
+            conf_target: default_conf_target(),
+            stacks: RateSelection::default(),
+            bitcoin: RateSelection::default(),
+        }
+    }
+}
+
+impl FeeOracleConfig {
+    fn selection(&self, chain: Chain) -> &RateSelection {
+        match chain {
+            Chain::Stacks => &self.stacks,
+            Chain::Bitcoin => &self.bitcoin,
+        }
+    }
+}
+
+/// How the rate paid on one chain is picked from its samples
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+pub struct RateSelection {
+    /// Pay this rate whatever the samples say
+    #[serde(default)]
+    pub override_rate: Option<u64>,
+    /// Pay this percentile of the samples in the window instead of their moving average
+    #[serde(default)]
+    pub percentile: Option<u8>,
+    #[serde(default)]
+    pub min_rate: Option<u64>,
+    #[serde(default)]
+    pub max_rate: Option<u64>,
+}
+
+impl RateSelection {
+    fn clamp(&self, rate: u64) -> u64 {
+        let rate = self.min_rate.map_or(rate, |min| rate.max(min));
+        self.max_rate.map_or(rate, |max| rate.min(max))
+    }
+}
+
+/// Sampled and selected rates of one chain
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ChainRates {
+    /// Number of samples in the window
+    pub samples: usize,
+    pub latest: Option<u64>,
+    /// Moving average, rounded up
+    pub average: Option<u64>,
+    pub median: Option<u64>,
+    pub p90: Option<u64>,
+    /// The rate the wallets pay
+    pub selected: Option<u64>,
+    /// Whether `selected` is the configured override
+    pub overridden: bool,
+}
+
+/// Fee rates of both chains, as shown by `stacks-coordinator fees`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FeeRates {
+    pub stacks: ChainRates,
+    pub bitcoin: ChainRates,
+}
+
+impl fmt::Display for FeeRates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rate = |rate: Option<u64>| rate.map_or("-".to_string(), |rate| rate.to_string());
+        for (chain, unit, rates) in [
+            ("stacks", "uSTX/byte", &self.stacks),
+            ("bitcoin", "sat/vB", &self.bitcoin),
+        ] {
+            writeln!(
+                f,
+                "{chain:<8} selected {}{} {unit}, latest {}, average {}, median {}, p90 {} over {} samples",
+                rate(rates.selected),
+                if rates.overridden { " (override)" } else { "" },
+                rate(rates.latest),
+                rate(rates.average),
+                rate(rates.median),
+                rate(rates.p90),
+                rates.samples
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub struct FeeOracle {
+    conn: RusqliteConnection,
+    config: FeeOracleConfig,
+}
+
+impl FeeOracle {
+    pub fn new<P: AsRef<Path>>(path: P, config: FeeOracleConfig) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?, config)
+    }
+
+    pub fn in_memory(config: FeeOracleConfig) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?, config)
+    }
+
+    fn from_connection(conn: RusqliteConnection, config: FeeOracleConfig) -> Result<Self, Error> {
+        conn.execute_batch(Self::sql_schema())?;
+        Ok(Self { conn, config })
+    }
+
+    /// Sample both nodes if `interval_secs` have passed since the last sample. A node which
+    /// cannot give an estimate is skipped until the next interval.
+    pub fn sample(
+        &self,
+        stacks_node: &impl StacksNode,
+        bitcoin_node: &impl BitcoinNode,
+    ) -> Result<(), Error> {
+        let now = now();
+        if !self.sample_due(now)? {
+            return Ok(());
+        }
+        match stacks_node.fee_rate() {
+            Ok(rate) => self.record(Chain::Stacks, rate, now)?,
+            Err(e) => warn!(target: WALLET, "Failed to sample the stacks fee rate: {}", e),
+        }
+        match bitcoin_node.fee_rate(self.config.conf_target) {
+            Ok(rate) => self.record(Chain::Bitcoin, rate, now)?,
+            Err(e) => warn!(target: WALLET, "Failed to sample the bitcoin fee rate: {}", e),
+        }
+        // attempts count as samples, so unreachable nodes are not asked every tick
+        self.conn
+            .execute(Self::sql_set_last_sample(), rusqlite::params![now as i64])?;
+        self.conn.execute(
+            Self::sql_prune(),
+            rusqlite::params![now.saturating_sub(self.config.window_secs) as i64],
+        )?;
+        Ok(())
+    }
+
+    fn sample_due(&self, now: u64) -> Result<bool, Error> {
+        let last = self
+            .conn
+            .query_row(Self::sql_select_last_sample(), rusqlite::params![], |row| {
+                row.get::<_, i64>(0)
+            })
+            .optional()?;
+        Ok(match last {
+            Some(last) => now >= (last as u64).saturating_add(self.config.interval_secs),
+            None => true,
+        })
+    }
+
+    /// Record a sample of `rate` taken at `sampled_at`, in unix seconds
+    pub fn record(&self, chain: Chain, rate: u64, sampled_at: u64) -> Result<(), Error> {
+        debug!(target: WALLET, "{} fee rate sampled at {}", chain.as_str(), rate);
+        self.conn.execute(
+            Self::sql_insert_sample(),
+            rusqlite::params![chain.as_str(), rate as i64, sampled_at as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Samples of `chain` in the window ending at `now`, oldest first
+    fn samples(&self, chain: Chain, now: u64) -> Result<Vec<(u64, u64)>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_samples())?
+            .query_map(
+                rusqlite::params![
+                    chain.as_str(),
+                    now.saturating_sub(self.config.window_secs) as i64
+                ],
+                |row| Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// The rate to pay on `chain`, if there is an override or any samples
+    pub fn rate(&self, chain: Chain) -> Result<Option<u64>, Error> {
+        Ok(self.rates_at(chain, now())?.selected)
+    }
+
+    /// Sampled and selected rates of both chains
+    pub fn rates(&self) -> Result<FeeRates, Error> {
+        let now = now();
+        Ok(FeeRates {
+            stacks: self.rates_at(Chain::Stacks, now)?,
+            bitcoin: self.rates_at(Chain::Bitcoin, now)?,
+        })
+    }
+
+    fn rates_at(&self, chain: Chain, now: u64) -> Result<ChainRates, Error> {
+        let samples = self.samples(chain, now)?;
+        let selection = self.config.selection(chain);
+        let average = moving_average(&samples, self.config.half_life_secs).map(|a| a.ceil() as u64);
+        let sampled = match selection.percentile {
+            Some(p) => percentile(&samples, p),
+            None => average,
+        };
+        Ok(ChainRates {
+            samples: samples.len(),
+            latest: samples.last().map(|(_, rate)| *rate),
+            average,
+            median: percentile(&samples, 50),
+            p90: percentile(&samples, 90),
+            selected: selection
+                .override_rate
+                .or(sampled)
+                .map(|rate| selection.clamp(rate)),
+            overridden: selection.override_rate.is_some(),
+        })
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS fee_samples (
+            chain TEXT NOT NULL,
+            rate INTEGER NOT NULL,
+            sampled_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS fee_sampling (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            last_sample_at INTEGER NOT NULL
+        );
+        "#
+    }
+
+    const fn sql_insert_sample() -> &'static str {
+        r#"
+        INSERT INTO fee_samples (chain, rate, sampled_at) VALUES (?1, ?2, ?3)
+        "#
+    }
+
+    const fn sql_select_samples() -> &'static str {
+        r#"
+        SELECT sampled_at, rate FROM fee_samples
+        WHERE chain=?1 AND sampled_at>=?2 ORDER BY sampled_at ASC, rowid ASC
+        "#
+    }
+
+    const fn sql_prune() -> &'static str {
+        r#"
+        DELETE FROM fee_samples WHERE sampled_at<?1
+        "#
+    }
+
+    const fn sql_select_last_sample() -> &'static str {
+        r#"
+        SELECT last_sample_at FROM fee_sampling WHERE id=0
+        "#
+    }
+
+    const fn sql_set_last_sample() -> &'static str {
+        r#"
+        REPLACE INTO fee_sampling (id, last_sample_at) VALUES (0, ?1)
+        "#
+    }
+}
+
+/// Exponential moving average of `samples`, oldest first, where a sample `half_life_secs`
+/// older than the next counts for half as much
+fn moving_average(samples: &[(u64, u64)], half_life_secs: u64) -> Option<f64> {
+    let ((first_at, first), rest) = samples.split_first()?;
+    let mut average = *first as f64;
+    let mut last_at = *first_at;
+    for (sampled_at, rate) in rest {
+        let elapsed = sampled_at.saturating_sub(last_at) as f64;
+        let weight = 1.0 - 0.5f64.powf(elapsed / half_life_secs.max(1) as f64);
+        average += weight * (*rate as f64 - average);
+        last_at = *sampled_at;
+    }
+    Some(average)
+}
+
+/// Nearest-rank `p`th percentile of the rates of `samples`
+fn percentile(samples: &[(u64, u64)], p: u8) -> Option<u64> {
+    let mut rates: Vec<u64> = samples.iter().map(|(_, rate)| *rate).collect();
+    rates.sort_unstable();
+    let rank = (rates.len() * p.min(100) as usize).div_ceil(100);
+    rates.get(rank.saturating_sub(1)).copied()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin_node::MockBitcoinNode;
+    use crate::stacks_node::MockStacksNode;
+
+    #[test]
+    fn selects_the_override_percentile_or_average_clamped() {
+        let config = FeeOracleConfig {
+            half_life_secs: 100,
+            stacks: RateSelection {
+                percentile: Some(90),
+                ..Default::default()
+            },
+            bitcoin: RateSelection {
+                max_rate: Some(30),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let oracle = FeeOracle::in_memory(config.clone()).unwrap();
+        assert_eq!(oracle.rates().unwrap(), FeeRates::default());
+
+        let start = now() - 1_000;
+        for (i, rate) in [10, 20, 30, 40, 50, 60, 70, 80, 90, 100].iter().enumerate() {
+            oracle
+                .record(Chain::Stacks, *rate, start + i as u64)
+                .unwrap();
+        }
+        // the older sample has decayed to a quarter of the newer one's weight
+        oracle.record(Chain::Bitcoin, 10, start).unwrap();
+        oracle.record(Chain::Bitcoin, 50, start + 200).unwrap();
+
+        let rates = oracle.rates().unwrap();
+        assert_eq!(rates.stacks.samples, 10);
+        assert_eq!(rates.stacks.median, Some(50));
+        assert_eq!(rates.stacks.selected, Some(90));
+        assert_eq!(rates.bitcoin.latest, Some(50));
+        assert_eq!(rates.bitcoin.average, Some(40));
+        assert_eq!(rates.bitcoin.selected, Some(30));
+        assert!(!rates.bitcoin.overridden);
+
+        let oracle = FeeOracle {
+            config: FeeOracleConfig {
+                bitcoin: RateSelection {
+                    override_rate: Some(7),
+                    ..Default::default()
+                },
+                ..config
+            },
+            ..oracle
+        };
+        assert_eq!(oracle.rate(Chain::Bitcoin).unwrap(), Some(7));
+        assert!(oracle.rates().unwrap().bitcoin.overridden);
+    }
+
+    #[test]
+    fn samples_both_nodes_once_per_interval() {
+        let oracle = FeeOracle::in_memory(FeeOracleConfig::default()).unwrap();
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_fee_rate().times(1).returning(|| Ok(4));
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node
+            .expect_fee_rate()
+            .withf(|conf_target| *conf_target == 6)
+            .times(1)
+            .returning(|_| Err(crate::bitcoin_node::Error::RpcMissingResult));
+
+        oracle.sample(&stacks_node, &bitcoin_node).unwrap();
+        oracle.sample(&stacks_node, &bitcoin_node).unwrap();
+
+        let rates = oracle.rates().unwrap();
+        assert_eq!(rates.stacks.selected, Some(4));
+        assert_eq!(rates.bitcoin.samples, 0);
+        assert_eq!(rates.bitcoin.selected, None);
+    }
+}
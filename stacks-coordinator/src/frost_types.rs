@@ -0,0 +1,295 @@
+//! Stable types for the results of DKG and signing rounds.
+//!
+//! The coordinator computes with wtfrost types, whose API changes from one revision to the
+//! next. These wrappers hold the results as plain bytes instead: compressed secp256k1 points
+//! and BIP-340 signatures. They display and serialize as hex, and convert to the bitcoin and
+//! stacks key types, so callers need not depend on wtfrost to use them.
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{self, schnorr, Secp256k1, XOnlyPublicKey};
+use bitcoin::util::schnorr::{TapTweak, TweakedPublicKey};
+use bitcoin::util::taproot::TapBranchHash;
+use blockstack_lib::util::hash::{hex_bytes, to_hex};
+use blockstack_lib::util::secp256k1::Secp256k1PublicKey;
+use blockstack_lib::util::HexError;
+use frost_signer::taproot::KeyPath;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use wtfrost::{bip340::SchnorrProof, common::PolyCommitment, Point};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Hex codec error: {0}")]
+    HexError(#[from] HexError),
+    #[error("expected {0} bytes, got {1}")]
+    InvalidLength(usize, usize),
+    #[error("Bitcoin Secp256k1 Error: {0}")]
+    BitcoinSecp256k1(#[from] secp256k1::Error),
+    #[error("Invalid stacks public key: {0}")]
+    StacksPublicKey(&'static str),
+}
+
+/// `bytes` as an array of `N` bytes
+fn array<const N: usize>(bytes: &[u8]) -> Result<[u8; N], Error> {
+    bytes
+        .try_into()
+        .map_err(|_| Error::InvalidLength(N, bytes.len()))
+}
+
+/// Serialize as a hex string, using `Display` and `FromStr`
+macro_rules! hex_serde {
+    ($name:ident) => {
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                String::deserialize(deserializer)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+    };
+}
+
+/// The aggregate public key of a DKG round, a compressed secp256k1 point
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GroupPublicKey([u8; 33]);
+
+impl GroupPublicKey {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes = array(bytes)?;
+        // only points on the curve are accepted
+        secp256k1::PublicKey::from_slice(&bytes)?;
+        Ok(Self(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 33] {
+        &self.0
+    }
+
+    /// The key taproot outputs commit to
+    pub fn to_x_only(&self) -> XOnlyPublicKey {
+        self.to_secp256k1().x_only_public_key().0
+    }
+
+    /// The key of the taproot output of `key_path`, tweaked by BIP-341. Key path spends of the
+    /// output verify with it, and its script commits to it.
+    pub fn output_key(&self, key_path: &KeyPath) -> TweakedPublicKey {
+        let merkle_root = key_path.merkle_root.map(TapBranchHash::from_inner);
+        self.to_x_only()
+            .tap_tweak(&Secp256k1::verification_only(), merkle_root)
+            .0
+    }
+
+    pub fn to_secp256k1(&self) -> secp256k1::PublicKey {
+        secp256k1::PublicKey::from_slice(&self.0).expect("checked when constructed")
+    }
+
+    pub fn to_stacks_public_key(&self) -> Result<Secp256k1PublicKey, Error> {
+        Secp256k1PublicKey::from_slice(&self.0).map_err(Error::StacksPublicKey)
+    }
+}
+
+impl TryFrom<&Point> for GroupPublicKey {
+    type Error = Error;
+    fn try_from(point: &Point) -> Result<Self, Error> {
+        Self::from_slice(point.compress().as_bytes())
+    }
+}
+
+impl fmt::Display for GroupPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&to_hex(&self.0))
+    }
+}
+
+impl FromStr for GroupPublicKey {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::from_slice(&hex_bytes(s)?)
+    }
+}
+
+hex_serde!(GroupPublicKey);
+
+/// The polynomial commitment one party published in a DKG round: compressed points, the first
+/// being the party's contribution to the group key
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartyCommitment {
+    pub party_id: u32,
+    pub coefficients: Vec<GroupPublicKey>,
+}
+
+impl PartyCommitment {
+    pub fn new(party_id: u32, commitment: &PolyCommitment) -> Result<Self, Error> {
+        Ok(Self {
+            party_id,
+            coefficients: commitment
+                .A
+                .iter()
+                .map(GroupPublicKey::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl fmt::Display for PartyCommitment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.party_id)?;
+        for (i, coefficient) in self.coefficients.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            write!(f, "{separator}{coefficient}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A BIP-340 Schnorr signature made by the signers under the group key
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ThresholdSignature([u8; 64]);
+
+impl ThresholdSignature {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Self(array(bytes)?))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 64] {
+        &self.0
+    }
+
+    pub fn to_schnorr(&self) -> Result<schnorr::Signature, Error> {
+        Ok(schnorr::Signature::from_slice(&self.0)?)
+    }
+}
+
+impl From<&SchnorrProof> for ThresholdSignature {
+    fn from(proof: &SchnorrProof) -> Self {
+        Self(proof.to_bytes())
+    }
+}
+
+impl fmt::Display for ThresholdSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&to_hex(&self.0))
+    }
+}
+
+impl FromStr for ThresholdSignature {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::from_slice(&hex_bytes(s)?)
+    }
+}
+
+hex_serde!(ThresholdSignature);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+    fn public_key(secret: u8) -> secp256k1::PublicKey {
+        let secret = SecretKey::from_slice(&[secret; 32]).unwrap();
+        secp256k1::PublicKey::from_secret_key(&Secp256k1::new(), &secret)
+    }
+
+    #[test]
+    fn output_keys_are_the_ones_the_signers_sign_with() {
+        for (secret, merkle_root) in [(3, None), (7, Some([9; 32]))] {
+            let key_path = KeyPath { merkle_root };
+            let point = Point::from(wtfrost::Scalar::from(secret));
+            let key = GroupPublicKey::try_from(&point).unwrap();
+            assert_eq!(
+                key.output_key(&key_path).serialize(),
+                key_path.tweak(&point).output_key_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn group_public_key_converts_to_bitcoin_and_stacks_keys() {
+        let secp256k1_key = public_key(7);
+        let key = GroupPublicKey::from_slice(&secp256k1_key.serialize()).unwrap();
+        assert_eq!(key.to_secp256k1(), secp256k1_key);
+        assert_eq!(key.to_x_only(), secp256k1_key.x_only_public_key().0);
+        assert_eq!(
+            key.to_stacks_public_key().unwrap().to_bytes_compressed(),
+            secp256k1_key.serialize().to_vec()
+        );
+
+        let hex = key.to_string();
+        assert_eq!(hex, to_hex(&secp256k1_key.serialize()));
+        assert_eq!(hex.parse::<GroupPublicKey>().unwrap(), key);
+        assert_eq!(serde_json::to_string(&key).unwrap(), format!("\"{hex}\""));
+        assert_eq!(
+            serde_json::from_str::<GroupPublicKey>(&format!("\"{hex}\"")).unwrap(),
+            key
+        );
+
+        assert!(matches!(
+            GroupPublicKey::from_slice(&[2; 32]),
+            Err(Error::InvalidLength(33, 32))
+        ));
+        assert!(matches!(
+            GroupPublicKey::from_slice(&[9; 33]),
+            Err(Error::BitcoinSecp256k1(_))
+        ));
+    }
+
+    #[test]
+    fn party_commitments_display_and_serialize_as_hex() {
+        let commitment = PartyCommitment {
+            party_id: 3,
+            coefficients: vec![
+                GroupPublicKey::from_slice(&public_key(1).serialize()).unwrap(),
+                GroupPublicKey::from_slice(&public_key(2).serialize()).unwrap(),
+            ],
+        };
+        assert_eq!(
+            commitment.to_string(),
+            format!(
+                "3:{},{}",
+                commitment.coefficients[0], commitment.coefficients[1]
+            )
+        );
+        let json = serde_json::to_string(&commitment).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"party_id":3,"coefficients":["{}","{}"]}}"#,
+                commitment.coefficients[0], commitment.coefficients[1]
+            )
+        );
+        assert_eq!(
+            serde_json::from_str::<PartyCommitment>(&json).unwrap(),
+            commitment
+        );
+    }
+
+    #[test]
+    fn threshold_signature_round_trips_through_hex() {
+        let signature = ThresholdSignature::from_slice(&[5; 64]).unwrap();
+        let hex = signature.to_string();
+        assert_eq!(hex, "05".repeat(64));
+        assert_eq!(hex.parse::<ThresholdSignature>().unwrap(), signature);
+        assert_eq!(
+            format!("{signature:?}"),
+            format!("ThresholdSignature({hex})")
+        );
+        assert!(matches!(
+            "0505".parse::<ThresholdSignature>(),
+            Err(Error::InvalidLength(64, 2))
+        ));
+    }
+}
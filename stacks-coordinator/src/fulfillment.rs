@@ -0,0 +1,160 @@
+//! Wire-format checks for peg-out fulfillment transactions.
+//!
+//! A fulfillment follows the sBTC peg-out fulfill layout read by the stacks node's burnchain op
+//! parser: the first output is an `OP_RETURN` carrying the stacks magic bytes, the `!` opcode,
+//! the 32 byte stacks chain tip and an optional memo, and the second output pays the requested
+//! amount to the recipient. The only other output allowed is change back to the peg wallet.
+//! Transactions are checked before they are signed, so a non-conformant fulfillment fails to
+//! build instead of being broadcast.
+use bitcoin::blockdata::opcodes::all::OP_RETURN;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::psbt::Prevouts;
+use bitcoin::util::sighash::{Error as SighashError, SighashCache};
+use bitcoin::{SchnorrSighashType, Script, TapSighashHash, Transaction, TxOut};
+
+use crate::stacks_node::bitcoin_ops::op_return_data;
+
+pub const PEG_OUT_FULFILL_OPCODE: u8 = b'!';
+
+/// Most data bitcoin nodes relay in an `OP_RETURN` output
+pub const MAX_OP_RETURN_LEN: usize = 80;
+
+const CHAIN_TIP_LEN: usize = 32;
+/// Magic bytes, opcode and chain tip
+const PAYLOAD_LEN: usize = 2 + 1 + CHAIN_TIP_LEN;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    #[error("transaction version {0} is not standard")]
+    Version(i32),
+    #[error("transaction has no inputs")]
+    NoInputs,
+    #[error("the first output is not an OP_RETURN")]
+    MissingOpReturn,
+    #[error("the OP_RETURN payload {0}")]
+    Payload(&'static str),
+    #[error("the second output does not pay the recipient")]
+    Recipient,
+    #[error("the recipient is paid {0} sats instead of the requested {1}")]
+    Amount(u64, u64),
+    #[error("output {0} pays neither the recipient nor the peg wallet")]
+    UnexpectedOutput(usize),
+    #[error("expected the {0} outputs spent by the inputs, got {1}")]
+    SpentCount(usize, usize),
+    #[error("outputs pay {0} sats but the inputs only hold {1}")]
+    Overspend(u64, u64),
+    #[error("fee of {0} sats is below the minimum relay fee of {1}")]
+    FeeTooLow(u64, u64),
+    #[error("fee of {0} sats exceeds the {1} sats paid for the fulfillment")]
+    FeeTooHigh(u64, u64),
+}
+
+/// What the fulfillment of one peg-out request must look like
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    /// Stacks burn op magic bytes of the bitcoin network
+    pub magic: [u8; 2],
+    pub recipient: Script,
+    pub amount: u64,
+    pub peg_wallet: Script,
+    /// The fulfillment fee the requester paid the peg wallet
+    pub max_fee: u64,
+}
+
+/// The `OP_RETURN` output of a fulfillment
+pub fn op_return(magic: [u8; 2], chain_tip: [u8; 32], memo: &[u8]) -> TxOut {
+    let data = [&magic[..], &[PEG_OUT_FULFILL_OPCODE], &chain_tip, memo].concat();
+    TxOut {
+        value: 0,
+        script_pubkey: Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(&data)
+            .into_script(),
+    }
+}
+
+/// The taproot sighash the signers sign for the single input of the fulfillment `tx`, with its
+/// first output standing in for the output it spends
+pub fn sighash(tx: &Transaction) -> Result<TapSighashHash, SighashError> {
+    SighashCache::new(tx).taproot_signature_hash(
+        0,
+        &Prevouts::All(tx.output.get(..1).unwrap_or_default()),
+        None,
+        None,
+        SchnorrSighashType::All,
+    )
+}
+
+impl Template {
+    /// Check `tx` against the template. `spent` holds the outputs spent by each input, in order;
+    /// the fee bounds are only checked when they are known.
+    pub fn validate(&self, tx: &Transaction, spent: Option<&[TxOut]>) -> Result<(), Error> {
+        if !(1..=2).contains(&tx.version) {
+            return Err(Error::Version(tx.version));
+        }
+        if tx.input.is_empty() {
+            return Err(Error::NoInputs);
+        }
+        self.check_payload(tx.output.first())?;
+
+        let recipient = tx.output.get(1).ok_or(Error::Recipient)?;
+        if recipient.script_pubkey != self.recipient {
+            return Err(Error::Recipient);
+        }
+        if recipient.value != self.amount {
+            return Err(Error::Amount(recipient.value, self.amount));
+        }
+        // at most one change output may follow the recipient
+        for (i, output) in tx.output.iter().enumerate().skip(2) {
+            if i > 2 || output.script_pubkey != self.peg_wallet {
+                return Err(Error::UnexpectedOutput(i));
+            }
+        }
+
+        if let Some(spent) = spent {
+            self.check_fee(tx, spent)?;
+        }
+        Ok(())
+    }
+
+    fn check_payload(&self, output: Option<&TxOut>) -> Result<(), Error> {
+        let script = &output.ok_or(Error::MissingOpReturn)?.script_pubkey;
+        if !script.is_op_return() {
+            return Err(Error::MissingOpReturn);
+        }
+        let data = op_return_data(script).ok_or(Error::Payload("is not a single push"))?;
+        if data.len() > MAX_OP_RETURN_LEN {
+            return Err(Error::Payload("is longer than 80 bytes"));
+        }
+        if data.len() < PAYLOAD_LEN {
+            return Err(Error::Payload("is too short to hold a chain tip"));
+        }
+        if data[..2] != self.magic {
+            return Err(Error::Payload("has the wrong magic bytes"));
+        }
+        if data[2] != PEG_OUT_FULFILL_OPCODE {
+            return Err(Error::Payload("is not a peg-out fulfillment"));
+        }
+        Ok(())
+    }
+
+    fn check_fee(&self, tx: &Transaction, spent: &[TxOut]) -> Result<(), Error> {
+        if spent.len() != tx.input.len() {
+            return Err(Error::SpentCount(tx.input.len(), spent.len()));
+        }
+        let inputs: u64 = spent.iter().map(|out| out.value).sum();
+        let outputs: u64 = tx.output.iter().map(|out| out.value).sum();
+        let fee = inputs
+            .checked_sub(outputs)
+            .ok_or(Error::Overspend(outputs, inputs))?;
+        // one sat per vbyte of the unsigned transaction, witnesses only add to the size
+        let min_fee = tx.vsize() as u64;
+        if fee < min_fee {
+            return Err(Error::FeeTooLow(fee, min_fee));
+        }
+        if fee > self.max_fee {
+            return Err(Error::FeeTooHigh(fee, self.max_fee));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+//! Programmatic interface for embedding a coordinator in another binary.
+//!
+//! A [`CoordinatorHandle`] owns the sending half of the coordinator's command channel.
+//! Each method enqueues a request for the coordinator thread and resolves once the
+//! coordinator has processed it, so callers can drive coordination from any async runtime.
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+use frost_coordinator::history::Round;
+use frost_coordinator::quorum::SigningQuorum;
+use frost_signer::digest::MessageDigest;
+use frost_signer::retention::Usage;
+use frost_signer::thresholds::Thresholds;
+use futures_channel::oneshot;
+
+use crate::bitcoin_txs::BitcoinTx;
+use crate::coordinator::{Command, Coordinator, Error, PublicKey, Result};
+use crate::fee_ledger::GenerationFees;
+use crate::fee_oracle::FeeRates;
+use crate::frost_types::{GroupPublicKey, PartyCommitment, ThresholdSignature};
+use crate::incident_log::Incident;
+use crate::mode::ModeState;
+use crate::peg_latency::LatencyStats;
+use crate::peg_queue::{OpId, PriorityPolicy, QueueDepths, RejectedPegOut, SbtcOp, TracedOp};
+
+/// Snapshot of the coordinator state returned by [`CoordinatorHandle::status`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoordinatorStatus {
+    /// The operational mode, and who last changed it and why
+    pub mode: ModeState,
+    /// The aggregate public key of the last successful DKG round, if any
+    pub aggregate_public_key: Option<GroupPublicKey>,
+    /// The commitments of each key which took part in that round
+    pub party_commitments: Vec<PartyCommitment>,
+    /// The active peg queue prioritization policy
+    pub priority_policy: PriorityPolicy,
+    /// Number of queued peg ops in each class
+    pub queue_depths: QueueDepths,
+    /// Peg-out requests refused by the peg-out policy
+    pub rejected_peg_outs: Vec<RejectedPegOut>,
+    /// Number of frost rounds aborted by the watchdog since startup
+    pub round_timeouts: u64,
+    /// The signer and key thresholds rounds are held to
+    pub thresholds: Thresholds,
+    /// Signers the signing rounds are held to, see [`CoordinatorHandle::set_signing_quorum`]
+    pub signing_quorum: SigningQuorum,
+    /// Fulfillment fees accrued and swept under each key generation
+    pub fees: Vec<GenerationFees>,
+    /// Fee rates sampled and selected by the fee oracle, if one is configured
+    pub fee_rates: Option<FeeRates>,
+    /// Disk usage of each artifact under a retention limit, after its last pruning pass
+    pub storage: BTreeMap<&'static str, Usage>,
+    /// Latency histograms and SLO breaches of the confirmed peg-ins and peg-outs
+    pub peg_latency: LatencyStats,
+}
+
+/// Requests the coordinator loop services on behalf of a [`CoordinatorHandle`]
+pub enum Request {
+    SubmitPegOp(Box<SbtcOp>, oneshot::Sender<Result<OpId>>),
+    HoldPegOp(OpId, String, oneshot::Sender<Result<()>>),
+    ReleasePegOp(OpId, String, oneshot::Sender<Result<()>>),
+    HeldPegOps(oneshot::Sender<Result<Vec<TracedOp>>>),
+    Pause(String, String, oneshot::Sender<Result<ModeState>>),
+    Resume(String, String, oneshot::Sender<Result<ModeState>>),
+    Sign(MessageDigest, oneshot::Sender<Result<ThresholdSignature>>),
+    Dkg(oneshot::Sender<Result<PublicKey>>),
+    SetSigningQuorum(SigningQuorum, oneshot::Sender<Result<()>>),
+    Status(oneshot::Sender<Result<CoordinatorStatus>>),
+    ListIncidents(bool, oneshot::Sender<Result<Vec<Incident>>>),
+    AnnotateIncident(i64, String, String, oneshot::Sender<Result<()>>),
+    ResolveIncident(i64, String, String, oneshot::Sender<Result<()>>),
+    ListRounds(
+        Option<u64>,
+        Option<u64>,
+        oneshot::Sender<Result<Vec<Round>>>,
+    ),
+    GetRound(i64, oneshot::Sender<Result<Option<Round>>>),
+    BitcoinTxs(oneshot::Sender<Result<Vec<BitcoinTx>>>),
+    Shutdown(oneshot::Sender<Result<()>>),
+}
+
+/// Handle to a coordinator running on its own thread
+pub struct CoordinatorHandle {
+    sender: Sender<Command>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl CoordinatorHandle {
+    /// Start `coordinator` on a dedicated thread and return a handle to it
+    pub fn spawn<C>(coordinator: C) -> Self
+    where
+        C: Coordinator + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Command>();
+        let loop_sender = sender.clone();
+        let thread = thread::spawn(move || coordinator.run_with_channel(loop_sender, receiver));
+        Self {
+            sender,
+            thread: Some(thread),
+        }
+    }
+
+    /// Wrap an existing command channel, for coordinators driven by the caller
+    pub fn from_sender(sender: Sender<Command>) -> Self {
+        Self {
+            sender,
+            thread: None,
+        }
+    }
+
+    /// Queue a peg-in or peg-out request for processing, returning the id its lifecycle is
+    /// traced under
+    pub async fn submit_peg_op(&self, op: SbtcOp) -> Result<OpId> {
+        self.request(|reply| Request::SubmitPegOp(Box::new(op), reply))
+            .await
+    }
+
+    /// Hold the queued peg op `op_id` out of processing until it is released, e.g. while an
+    /// operator looks into it. Ops already being processed cannot be held.
+    pub async fn hold_peg_op(&self, op_id: OpId, reason: &str) -> Result<()> {
+        let reason = reason.to_string();
+        self.request(|reply| Request::HoldPegOp(op_id, reason, reply))
+            .await
+    }
+
+    /// Queue the held peg op `op_id` to be processed again
+    pub async fn release_peg_op(&self, op_id: OpId, reason: &str) -> Result<()> {
+        let reason = reason.to_string();
+        self.request(|reply| Request::ReleasePegOp(op_id, reason, reply))
+            .await
+    }
+
+    /// List the peg ops held by an operator, oldest first
+    pub async fn held_peg_ops(&self) -> Result<Vec<TracedOp>> {
+        self.request(Request::HeldPegOps).await
+    }
+
+    /// Stop signing peg ops and sweeping fees until [`CoordinatorHandle::resume`], across
+    /// restarts. See [`crate::mode`].
+    pub async fn pause(&self, author: &str, reason: &str) -> Result<ModeState> {
+        let (author, reason) = (author.to_string(), reason.to_string());
+        self.request(|reply| Request::Pause(author, reason, reply))
+            .await
+    }
+
+    /// Leave the paused mode, for whichever mode the coordinator's health calls for
+    pub async fn resume(&self, author: &str, reason: &str) -> Result<ModeState> {
+        let (author, reason) = (author.to_string(), reason.to_string());
+        self.request(|reply| Request::Resume(author, reason, reply))
+            .await
+    }
+
+    /// Sign `digest` as is with the current aggregate key, e.g. a taproot sighash
+    pub async fn sign_digest(&self, digest: [u8; 32]) -> Result<ThresholdSignature> {
+        self.request(|reply| Request::Sign(MessageDigest::new(digest), reply))
+            .await
+    }
+
+    /// Sign the tagged hash of `msg` under the domain tag `tag` with the current aggregate key
+    pub async fn sign_bytes(&self, tag: &str, msg: &[u8]) -> Result<ThresholdSignature> {
+        let digest = MessageDigest::tagged(tag, msg);
+        self.request(|reply| Request::Sign(digest, reply)).await
+    }
+
+    /// Run a distributed key generation round and return the new aggregate key
+    pub async fn dkg(&self) -> Result<PublicKey> {
+        self.request(Request::Dkg).await
+    }
+
+    /// Sign with the signers `quorum` pins, and without those it excludes, until it is
+    /// replaced, e.g. to leave out a signer undergoing maintenance. Fails without changing the
+    /// quorum if the signers left could not meet the thresholds. An empty quorum signs with
+    /// every signer again. The quorum lasts until the coordinator exits.
+    pub async fn set_signing_quorum(&self, quorum: SigningQuorum) -> Result<()> {
+        self.request(|reply| Request::SetSigningQuorum(quorum, reply))
+            .await
+    }
+
+    /// Report the current coordinator state
+    pub async fn status(&self) -> Result<CoordinatorStatus> {
+        self.request(Request::Status).await
+    }
+
+    /// List open incidents, or all incidents if `include_resolved` is set
+    pub async fn incidents(&self, include_resolved: bool) -> Result<Vec<Incident>> {
+        self.request(|reply| Request::ListIncidents(include_resolved, reply))
+            .await
+    }
+
+    /// Attach an operator note to an incident
+    pub async fn annotate_incident(&self, id: i64, author: &str, text: &str) -> Result<()> {
+        let (author, text) = (author.to_string(), text.to_string());
+        self.request(|reply| Request::AnnotateIncident(id, author, text, reply))
+            .await
+    }
+
+    /// Mark an incident resolved with a closing note
+    pub async fn resolve_incident(&self, id: i64, author: &str, text: &str) -> Result<()> {
+        let (author, text) = (author.to_string(), text.to_string());
+        self.request(|reply| Request::ResolveIncident(id, author, text, reply))
+            .await
+    }
+
+    /// List the DKG and signing rounds started within `since..until`, in unix seconds
+    pub async fn list_rounds(&self, since: Option<u64>, until: Option<u64>) -> Result<Vec<Round>> {
+        self.request(|reply| Request::ListRounds(since, until, reply))
+            .await
+    }
+
+    /// Look up one round of the history by id
+    pub async fn get_round(&self, id: i64) -> Result<Option<Round>> {
+        self.request(|reply| Request::GetRound(id, reply)).await
+    }
+
+    /// List the fulfillment and consolidation transactions the coordinator broadcast, with
+    /// their confirmations as of the last scanned bitcoin block
+    pub async fn bitcoin_txs(&self) -> Result<Vec<BitcoinTx>> {
+        self.request(Request::BitcoinTxs).await
+    }
+
+    /// Stop the coordinator loop and wait for its thread to exit
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.request(Request::Shutdown).await?;
+        match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| Error::CoordinatorStopped)?,
+            None => Ok(()),
+        }
+    }
+
+    async fn request<T>(
+        &self,
+        request: impl FnOnce(oneshot::Sender<Result<T>>) -> Request,
+    ) -> Result<T> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(Command::Request(request(reply)))
+            .map_err(|_| Error::CoordinatorStopped)?;
+        response.await.map_err(|_| Error::CoordinatorStopped)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    use super::*;
+
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn requests_are_answered_by_the_command_loop() {
+        let (sender, receiver) = mpsc::channel::<Command>();
+        let handle = CoordinatorHandle::from_sender(sender);
+        let coordinator = thread::spawn(move || {
+            while let Ok(command) = receiver.recv() {
+                match command {
+                    Command::Request(Request::Status(reply)) => {
+                        let _ = reply.send(Ok(CoordinatorStatus {
+                            mode: Default::default(),
+                            aggregate_public_key: None,
+                            party_commitments: vec![],
+                            priority_policy: Default::default(),
+                            queue_depths: Default::default(),
+                            rejected_peg_outs: vec![],
+                            round_timeouts: 0,
+                            thresholds: Default::default(),
+                            signing_quorum: Default::default(),
+                            fees: vec![],
+                            fee_rates: None,
+                            storage: Default::default(),
+                            peg_latency: Default::default(),
+                        }));
+                    }
+                    Command::Request(Request::Shutdown(reply)) => {
+                        let _ = reply.send(Ok(()));
+                        break;
+                    }
+                    _ => panic!("unexpected command"),
+                }
+            }
+        });
+
+        let status = block_on(handle.status()).unwrap();
+        assert_eq!(status.aggregate_public_key, None);
+        block_on(handle.shutdown()).unwrap();
+        coordinator.join().unwrap();
+    }
+
+    #[test]
+    fn requests_fail_once_the_coordinator_has_stopped() {
+        let (sender, receiver) = mpsc::channel::<Command>();
+        let handle = CoordinatorHandle::from_sender(sender);
+        drop(receiver);
+
+        assert!(matches!(
+            block_on(handle.dkg()),
+            Err(Error::CoordinatorStopped)
+        ));
+    }
+}
@@ -0,0 +1,502 @@
+//! Integrity check of the signatures in the round history.
+//!
+//! The frost coordinator records every DKG and signing round in its round history, with the
+//! aggregate key a generation produced and each signature with the digest it signed. That record
+//! is the audit trail of what the signers signed, and `verify-history` walks it end to end: every
+//! signature has to verify under the aggregate key recorded for its generation, or under the
+//! output key of its peg wallet if its round signed key path spends, and every
+//! fulfillment and consolidation the coordinator broadcast has to be spent on chain with
+//! signatures from the history. A fulfillment's signature must also be of the sighash of the
+//! transaction as it was mined. A consolidation's sighashes commit to the outputs it spends,
+//! which the chain does not hold, so its signatures are only looked up.
+//!
+//! The history is pruned by `[retention.history]`: transactions broadcast before its oldest round
+//! are skipped, and signatures of a generation whose DKG round was pruned are counted as
+//! unverifiable rather than reported.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::Txid;
+use frost_coordinator::history::{Error as HistoryError, Round, RoundHistory, RoundKind};
+use frost_signer::taproot::KeyPath;
+
+use crate::bitcoin_node::{
+    BitcoinBlock, BitcoinNode, BitcoinTransaction, Error as BitcoinNodeError,
+};
+use crate::bitcoin_txs::{BitcoinTxLog, Error as BitcoinTxsError, TxKind};
+use crate::frost_types::{GroupPublicKey, ThresholdSignature};
+use crate::fulfillment;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Round History Error: {0}")]
+    HistoryError(#[from] HistoryError),
+    #[error("Bitcoin Txs Error: {0}")]
+    BitcoinTxsError(#[from] BitcoinTxsError),
+    #[error("Bitcoin Node Error: {0}")]
+    BitcoinNodeError(#[from] BitcoinNodeError),
+}
+
+/// Something the history and the chain disagree on
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    #[error("round #{0} recorded {1} which does not parse")]
+    Malformed(i64, &'static str),
+    #[error("generation #{0} recorded aggregate key {1}, then {2}")]
+    KeyChanged(u64, GroupPublicKey, GroupPublicKey),
+    #[error("round #{0} signed {1} digests but recorded {2} signatures")]
+    SignatureCount(i64, usize, usize),
+    #[error(
+        "round #{0} recorded signature {1}, which does not verify under the key of generation #{2}"
+    )]
+    BadSignature(i64, ThresholdSignature, u64),
+    #[error("transaction {0} is not in block {1}, which confirmed it")]
+    NotInBlock(Txid, u64),
+    #[error("input {1} of transaction {0} carries no schnorr signature")]
+    Unsigned(Txid, usize),
+    #[error(
+        "input {1} of transaction {0} carries signature {2}, which no recorded round produced"
+    )]
+    NotArchived(Txid, usize, ThresholdSignature),
+    #[error("transaction {0} has sighash {1}, but its signature was made over {2}")]
+    SighashMismatch(Txid, String, String),
+}
+
+/// The outcome of [`verify_history`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Audit {
+    /// Signatures which verified under the key of their generation
+    pub verified: usize,
+    /// Signatures of a generation whose aggregate key is no longer in the history
+    pub unverifiable: usize,
+    /// Transactions checked against the chain
+    pub transactions: usize,
+    /// Transactions not confirmed as of the last scanned block
+    pub unconfirmed: usize,
+    /// Transactions broadcast before the oldest round in the history
+    pub predating: usize,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl fmt::Display for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} signatures verified, {} unverifiable, {} transactions checked ({} unconfirmed, {} predating the history), {} discrepancies",
+            self.verified,
+            self.unverifiable,
+            self.transactions,
+            self.unconfirmed,
+            self.predating,
+            self.discrepancies.len()
+        )
+    }
+}
+
+/// The aggregate keys and signatures the rounds of the history produced
+struct Archive {
+    keys: BTreeMap<u64, GroupPublicKey>,
+    /// The digest each signature signed
+    signatures: HashMap<ThresholdSignature, [u8; 32]>,
+}
+
+impl Archive {
+    /// Index `rounds`, verifying each signature under the key of its generation
+    fn new(rounds: &[Round], audit: &mut Audit) -> Self {
+        let mut archive = Self {
+            keys: BTreeMap::new(),
+            signatures: HashMap::new(),
+        };
+        let succeeded = rounds
+            .iter()
+            .filter_map(|round| Some((round, round.round.result.as_ref()?)))
+            .filter(|(round, _)| round.succeeded());
+        // DKG, reshare and roster change rounds all produce the generation's key
+        for (round, result) in succeeded.clone() {
+            if signs(round.round.kind) {
+                continue;
+            }
+            let Ok(key) = result.parse::<GroupPublicKey>() else {
+                audit
+                    .discrepancies
+                    .push(Discrepancy::Malformed(round.id, "an aggregate key"));
+                continue;
+            };
+            match archive.keys.insert(round.round.dkg_id, key) {
+                Some(previous) if previous != key => audit
+                    .discrepancies
+                    .push(Discrepancy::KeyChanged(round.round.dkg_id, previous, key)),
+                _ => {}
+            }
+        }
+        for (round, result) in succeeded {
+            if signs(round.round.kind) {
+                archive.add_signatures(round, result, audit);
+            }
+        }
+        archive
+    }
+
+    /// Index and verify the signatures of the signing round `round`, which produced `result`
+    fn add_signatures(&mut self, round: &Round, result: &str, audit: &mut Audit) {
+        // batches record their digests concatenated, and their signatures comma separated
+        let digests: Option<Vec<[u8; 32]>> = round
+            .round
+            .message
+            .as_deref()
+            .and_then(|message| Vec::<u8>::from_hex(message).ok())
+            .filter(|bytes| !bytes.is_empty() && bytes.len() % 32 == 0)
+            .map(|bytes| {
+                bytes
+                    .chunks(32)
+                    .map(|chunk| chunk.try_into().expect("32 byte chunks"))
+                    .collect()
+            });
+        let Some(digests) = digests else {
+            audit
+                .discrepancies
+                .push(Discrepancy::Malformed(round.id, "a message"));
+            return;
+        };
+        let Ok(signatures) = result
+            .split(',')
+            .map(str::parse::<ThresholdSignature>)
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            audit
+                .discrepancies
+                .push(Discrepancy::Malformed(round.id, "a signature"));
+            return;
+        };
+        if digests.len() != signatures.len() {
+            audit.discrepancies.push(Discrepancy::SignatureCount(
+                round.id,
+                digests.len(),
+                signatures.len(),
+            ));
+            return;
+        }
+        let dkg_id = round.round.dkg_id;
+        // key path spends are signed by the output key of the peg wallet, anything else by
+        // the aggregate key itself
+        let key_path = (round.round.kind == RoundKind::KeyPathSign).then(KeyPath::default);
+        for (digest, signature) in digests.into_iter().zip(signatures) {
+            match self.keys.get(&dkg_id) {
+                Some(key) if verifies(key, key_path.as_ref(), &digest, &signature) => {
+                    audit.verified += 1
+                }
+                // a bad signature vouches for no transaction
+                Some(_) => {
+                    audit
+                        .discrepancies
+                        .push(Discrepancy::BadSignature(round.id, signature, dkg_id));
+                    continue;
+                }
+                None => audit.unverifiable += 1,
+            }
+            self.signatures.insert(signature, digest);
+        }
+    }
+
+    /// Check the signatures `tx`, of `kind`, was mined with against the history
+    fn check_transaction(&self, tx: &BitcoinTransaction, kind: TxKind, audit: &mut Audit) {
+        let txid = tx.txid();
+        for (index, input) in tx.input.iter().enumerate() {
+            // a key path spend: the signature, then the sighash type if not the default
+            let signature = input
+                .witness
+                .iter()
+                .next()
+                .filter(|item| item.len() == 64 || item.len() == 65)
+                .and_then(|item| ThresholdSignature::from_slice(&item[..64]).ok());
+            let Some(signature) = signature else {
+                audit.discrepancies.push(Discrepancy::Unsigned(txid, index));
+                continue;
+            };
+            let Some(digest) = self.signatures.get(&signature) else {
+                audit
+                    .discrepancies
+                    .push(Discrepancy::NotArchived(txid, index, signature));
+                continue;
+            };
+            if kind != TxKind::Fulfillment || index > 0 {
+                continue;
+            }
+            match fulfillment::sighash(tx) {
+                Ok(sighash) if sighash.into_inner() == *digest => {}
+                Ok(sighash) => audit.discrepancies.push(Discrepancy::SighashMismatch(
+                    txid,
+                    sighash.into_inner().to_hex(),
+                    digest.to_hex(),
+                )),
+                Err(_) => audit.discrepancies.push(Discrepancy::Unsigned(txid, index)),
+            }
+        }
+    }
+}
+
+/// Whether the round kind `kind` produced signatures
+fn signs(kind: RoundKind) -> bool {
+    matches!(kind, RoundKind::Sign | RoundKind::KeyPathSign)
+}
+
+/// Whether `signature` is a BIP-340 signature of `digest` under `key`, or under the output key
+/// of `key_path` for a key path spend
+fn verifies(
+    key: &GroupPublicKey,
+    key_path: Option<&KeyPath>,
+    digest: &[u8; 32],
+    signature: &ThresholdSignature,
+) -> bool {
+    let Ok(signature) = signature.to_schnorr() else {
+        return false;
+    };
+    let msg = Message::from_slice(digest).expect("32 bytes");
+    let key = match key_path {
+        Some(key_path) => key.output_key(key_path).to_inner(),
+        None => key.to_x_only(),
+    };
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &msg, &key)
+        .is_ok()
+}
+
+/// Re-verify every signature of `history`, and check the confirmed transactions of `txs` were
+/// mined with them, reading the blocks which confirmed them from `bitcoin_node`
+pub fn verify_history<B: BitcoinNode>(
+    history: &RoundHistory,
+    txs: &BitcoinTxLog,
+    bitcoin_node: &B,
+) -> Result<Audit, Error> {
+    let mut audit = Audit::default();
+    let rounds = history.list(None, None)?;
+    let archive = Archive::new(&rounds, &mut audit);
+    let oldest = rounds.iter().map(|round| round.round.started_at).min();
+    let mut blocks: HashMap<u64, BitcoinBlock> = HashMap::new();
+    // oldest first
+    for tx in txs.list(None)?.into_iter().rev() {
+        // its signing round was pruned, if there was one
+        if oldest.map_or(true, |oldest| tx.broadcast_at < oldest) {
+            audit.predating += 1;
+            continue;
+        }
+        let Some(height) = tx.confirmed_height else {
+            audit.unconfirmed += 1;
+            continue;
+        };
+        // only ever written from a Txid
+        let Ok(txid) = tx.txid.parse::<Txid>() else {
+            continue;
+        };
+        if !blocks.contains_key(&height) {
+            blocks.insert(height, bitcoin_node.block(height)?);
+        }
+        let block = &blocks[&height];
+        audit.transactions += 1;
+        match block.txdata.iter().find(|mined| mined.txid() == txid) {
+            Some(mined) => archive.check_transaction(mined, tx.kind, &mut audit),
+            None => audit
+                .discrepancies
+                .push(Discrepancy::NotInBlock(txid, height)),
+        }
+    }
+    Ok(audit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin_node::MockBitcoinNode;
+    use crate::coordinator::FrostCoordinator;
+    use crate::mock_frost_coordinator::MockFrostCoordinator;
+    use bitcoin::{OutPoint, PackedLockTime, Script, TxIn, TxOut, Witness};
+    use frost_coordinator::history::NewRound;
+    use frost_signer::digest::MessageDigest;
+
+    fn round(kind: RoundKind, message: Option<Vec<u8>>, result: String) -> NewRound {
+        NewRound {
+            kind,
+            dkg_id: 1,
+            sign_id: 1,
+            started_at: 1,
+            duration_ms: 10,
+            participants: vec![1, 2],
+            message: message.map(|message| message.to_hex()),
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn fulfillment(value: u64) -> BitcoinTransaction {
+        BitcoinTransaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: Script::new(),
+            }],
+        }
+    }
+
+    /// `tx` spent with `signature`
+    fn signed(mut tx: BitcoinTransaction, signature: &ThresholdSignature) -> BitcoinTransaction {
+        tx.input[0].witness = Witness::from_vec(vec![signature.as_bytes().to_vec()]);
+        tx
+    }
+
+    fn block(txdata: Vec<BitcoinTransaction>) -> BitcoinBlock {
+        BitcoinBlock {
+            header: bitcoin::BlockHeader {
+                version: 1,
+                prev_blockhash: Hash::all_zeros(),
+                merkle_root: Hash::all_zeros(),
+                time: 0,
+                bits: 0,
+                nonce: 0,
+            },
+            txdata,
+        }
+    }
+
+    #[test]
+    fn signatures_are_checked_against_their_key_and_the_chain() {
+        let mut frost = MockFrostCoordinator::new();
+        let key = frost.run_distributed_key_generation().unwrap();
+        let history = RoundHistory::in_memory().unwrap();
+        history
+            .record(&round(RoundKind::Dkg, None, key.to_string()))
+            .unwrap();
+
+        // a fulfillment signed over its sighash, and one over something else
+        let paid = fulfillment(1_000);
+        let sighash = fulfillment::sighash(&paid).unwrap().into_inner();
+        let signature = frost
+            .sign_key_path(&MessageDigest::new(sighash), &KeyPath::default())
+            .unwrap();
+        history
+            .record(&round(
+                RoundKind::KeyPathSign,
+                Some(sighash.to_vec()),
+                signature.to_string(),
+            ))
+            .unwrap();
+        let misdirected = fulfillment(2_000);
+        let other = frost.sign_digest(&MessageDigest::new([3; 32])).unwrap();
+        history
+            .record(&round(
+                RoundKind::Sign,
+                Some(vec![3; 32]),
+                other.to_string(),
+            ))
+            .unwrap();
+        // a signature recorded for another digest too
+        history
+            .record(&round(
+                RoundKind::Sign,
+                Some(vec![4; 32]),
+                other.to_string(),
+            ))
+            .unwrap();
+
+        let txs = BitcoinTxLog::in_memory().unwrap();
+        let mined = vec![signed(paid, &signature), signed(misdirected, &other)];
+        for tx in &mined {
+            txs.record(tx, TxKind::Fulfillment, None, &[]).unwrap();
+            txs.confirm(&tx.txid(), 7).unwrap();
+        }
+        let stray = signed(
+            fulfillment(3_000),
+            &ThresholdSignature::from_slice(&[1; 64]).unwrap(),
+        );
+        txs.record(&stray, TxKind::Fulfillment, None, &[]).unwrap();
+        txs.confirm(&stray.txid(), 8).unwrap();
+        let unmined = fulfillment(4_000);
+        txs.record(&unmined, TxKind::Fulfillment, None, &[])
+            .unwrap();
+
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node
+            .expect_block()
+            .times(2)
+            .returning(move |height| {
+                Ok(match height {
+                    7 => block(mined.clone()),
+                    _ => block(vec![]),
+                })
+            });
+        let audit = verify_history(&history, &txs, &bitcoin_node).unwrap();
+        assert_eq!((audit.verified, audit.unverifiable), (2, 0));
+        assert_eq!(
+            (audit.transactions, audit.unconfirmed, audit.predating),
+            (3, 1, 0)
+        );
+        let misdirected = fulfillment(2_000);
+        assert_eq!(
+            audit.discrepancies,
+            vec![
+                Discrepancy::BadSignature(4, other, 1),
+                Discrepancy::SighashMismatch(
+                    signed(misdirected.clone(), &other).txid(),
+                    fulfillment::sighash(&misdirected)
+                        .unwrap()
+                        .into_inner()
+                        .to_hex(),
+                    [3u8; 32].to_hex(),
+                ),
+                Discrepancy::NotInBlock(stray.txid(), 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn signatures_verify_only_under_the_key_of_their_round_kind() {
+        let mut frost = MockFrostCoordinator::new();
+        let key = frost.run_distributed_key_generation().unwrap();
+        let history = RoundHistory::in_memory().unwrap();
+        history
+            .record(&round(RoundKind::Dkg, None, key.to_string()))
+            .unwrap();
+
+        // a key path spend recorded as a plain signing round, and the other way around
+        let tweaked = frost
+            .sign_key_path(&MessageDigest::new([5; 32]), &KeyPath::default())
+            .unwrap();
+        history
+            .record(&round(
+                RoundKind::Sign,
+                Some(vec![5; 32]),
+                tweaked.to_string(),
+            ))
+            .unwrap();
+        let plain = frost.sign_digest(&MessageDigest::new([6; 32])).unwrap();
+        history
+            .record(&round(
+                RoundKind::KeyPathSign,
+                Some(vec![6; 32]),
+                plain.to_string(),
+            ))
+            .unwrap();
+
+        let audit = verify_history(
+            &history,
+            &BitcoinTxLog::in_memory().unwrap(),
+            &MockBitcoinNode::new(),
+        )
+        .unwrap();
+        assert_eq!(audit.verified, 0);
+        assert_eq!(
+            audit.discrepancies,
+            vec![
+                Discrepancy::BadSignature(2, tweaked, 1),
+                Discrepancy::BadSignature(3, plain, 1),
+            ]
+        );
+    }
+}
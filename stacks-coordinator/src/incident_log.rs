@@ -0,0 +1,524 @@
+//! Persistent record of coordinator incidents.
+//!
+//! Failed rounds, broken invariants and misbehaving signers are written to an `incidents`
+//! table next to the peg queue, where operators can list them, attach notes and mark them
+//! resolved. The log survives restarts so on-call handoffs don't depend on scraping logs.
+//! Resolved incidents are pruned by the `[retention.incidents]` limits, open ones never are.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use frost_coordinator::coordinator::Error as FrostCoordinatorError;
+use frost_signer::retention::{Artifact, Usage};
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension};
+use serde::Serialize;
+
+use crate::coordinator::Error as CoordinatorError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Did not recognize incident kind: {0}")]
+    InvalidKindError(String),
+    #[error("Incident #{0} does not exist")]
+    UnknownIncident(i64),
+    #[error("Incident #{0} is already resolved")]
+    AlreadyResolved(i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentKind {
+    /// A DKG or signing round did not complete
+    RoundFailure,
+    /// The coordinator observed a state that should be impossible, e.g. an aggregate
+    /// signature which does not verify
+    InvariantBreach,
+    /// A signer sent data which failed validation
+    Byzantine,
+    /// A peg op took longer than its latency SLO, see [`crate::peg_latency`]
+    SloBreach,
+    /// Pending peg-outs exceed what the peg wallet can pay
+    Shortfall,
+}
+
+impl IncidentKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::RoundFailure => "round_failure",
+            Self::InvariantBreach => "invariant_breach",
+            Self::Byzantine => "byzantine",
+            Self::SloBreach => "slo_breach",
+            Self::Shortfall => "shortfall",
+        }
+    }
+}
+
+impl FromStr for IncidentKind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "round_failure" => Self::RoundFailure,
+            "invariant_breach" => Self::InvariantBreach,
+            "byzantine" => Self::Byzantine,
+            "slo_breach" => Self::SloBreach,
+            "shortfall" => Self::Shortfall,
+            other => return Err(Error::InvalidKindError(other.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for IncidentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An incident about to be recorded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NewIncident {
+    pub kind: IncidentKind,
+    /// Signers implicated in the incident, if any
+    pub signer_ids: Vec<u32>,
+    pub summary: String,
+}
+
+impl NewIncident {
+    /// The incident an error represents, if it is worth an operator's attention
+    pub fn from_error(error: &CoordinatorError) -> Option<Self> {
+        let CoordinatorError::FrostCoordinatorError(error) = error else {
+            return None;
+        };
+        let (kind, signer_ids) = match error {
+            // signers blamed for sending bad shares or commitments are the ones to look at
+            FrostCoordinatorError::DkgFailed(_, failed, blamed) => {
+                let (kind, signers) = if blamed.is_empty() {
+                    (IncidentKind::RoundFailure, failed)
+                } else {
+                    (IncidentKind::Byzantine, blamed)
+                };
+                (kind, signers.iter().map(|id| *id as u32).collect())
+            }
+            FrostCoordinatorError::BadSignatureShares(_, signer_ids) => (
+                IncidentKind::Byzantine,
+                signer_ids.iter().map(|id| *id as u32).collect(),
+            ),
+            FrostCoordinatorError::Rejected(signer_id, code, _) if !code.is_transient() => {
+                (IncidentKind::Byzantine, vec![*signer_id])
+            }
+            FrostCoordinatorError::Rejected(signer_id, _, _) => {
+                (IncidentKind::RoundFailure, vec![*signer_id])
+            }
+            FrostCoordinatorError::Timeout
+            | FrostCoordinatorError::RoundTimeout(_)
+            | FrostCoordinatorError::RosterTooSmall(..)
+            | FrostCoordinatorError::Aggregator(_) => (IncidentKind::RoundFailure, vec![]),
+            FrostCoordinatorError::SchnorrProofFailed | FrostCoordinatorError::Bip340(_) => {
+                (IncidentKind::InvariantBreach, vec![])
+            }
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            signer_ids,
+            summary: error.to_string(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Note {
+    pub author: String,
+    pub text: String,
+    /// Unix time in seconds
+    pub created_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Incident {
+    pub id: i64,
+    pub kind: IncidentKind,
+    pub signer_ids: Vec<u32>,
+    pub summary: String,
+    /// Unix time in seconds
+    pub opened_at: u64,
+    /// Unix time in seconds, `None` while the incident is open
+    pub resolved_at: Option<u64>,
+    /// Annotations in the order they were added; resolving adds a final note
+    pub notes: Vec<Note>,
+}
+
+impl Incident {
+    pub fn is_resolved(&self) -> bool {
+        self.resolved_at.is_some()
+    }
+}
+
+pub struct IncidentLog {
+    conn: RusqliteConnection,
+}
+
+impl IncidentLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute_batch(Self::sql_schema())?;
+        Ok(Self { conn })
+    }
+
+    /// Open a new incident and return its id
+    pub fn record(&self, incident: &NewIncident) -> Result<i64, Error> {
+        let signer_ids = incident
+            .signer_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        self.conn.execute(
+            Self::sql_insert_incident(),
+            rusqlite::params![
+                incident.kind.as_str(),
+                signer_ids,
+                incident.summary,
+                now() as i64
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Incidents ordered by id, optionally including resolved ones
+    pub fn list(&self, include_resolved: bool) -> Result<Vec<Incident>, Error> {
+        let ids = self
+            .conn
+            .prepare(Self::sql_select_ids())?
+            .query_map(rusqlite::params![include_resolved], |row| row.get(0))?
+            .collect::<Result<Vec<i64>, _>>()?;
+        ids.into_iter().map(|id| self.get(id)).collect()
+    }
+
+    pub fn get(&self, id: i64) -> Result<Incident, Error> {
+        let row = self
+            .conn
+            .query_row(Self::sql_select_incident(), rusqlite::params![id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                ))
+            })
+            .optional()?;
+        let Some((kind, signer_ids, summary, opened_at, resolved_at)) = row else {
+            return Err(Error::UnknownIncident(id));
+        };
+        let notes = self
+            .conn
+            .prepare(Self::sql_select_notes())?
+            .query_map(rusqlite::params![id], |row| {
+                Ok(Note {
+                    author: row.get(0)?,
+                    text: row.get(1)?,
+                    created_at: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Incident {
+            id,
+            kind: kind.parse()?,
+            signer_ids: signer_ids
+                .split(',')
+                .filter_map(|id| id.parse().ok())
+                .collect(),
+            summary,
+            opened_at: opened_at as u64,
+            resolved_at: resolved_at.map(|t| t as u64),
+            notes,
+        })
+    }
+
+    /// Attach a note to an incident, resolved or not
+    pub fn annotate(&self, id: i64, author: &str, text: &str) -> Result<(), Error> {
+        self.get(id)?;
+        self.conn.execute(
+            Self::sql_insert_note(),
+            rusqlite::params![id, author, text, now() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Close an incident with a final note
+    pub fn resolve(&self, id: i64, author: &str, text: &str) -> Result<(), Error> {
+        if self.get(id)?.is_resolved() {
+            return Err(Error::AlreadyResolved(id));
+        }
+        self.annotate(id, author, text)?;
+        self.conn
+            .execute(Self::sql_resolve(), rusqlite::params![id, now() as i64])?;
+        Ok(())
+    }
+
+    /// Delete the resolved incidents which `clause` selects, and their notes. `clause` is
+    /// appended to the query of resolved incidents and takes `params`.
+    fn delete_resolved(&self, clause: &str, params: &[&dyn rusqlite::ToSql]) -> Result<u64, Error> {
+        let incidents = format!("SELECT id FROM incidents WHERE resolved_at IS NOT NULL {clause}");
+        self.conn.execute(
+            &format!("DELETE FROM incident_notes WHERE incident_id IN ({incidents})"),
+            params,
+        )?;
+        Ok(self.conn.execute(
+            &format!("DELETE FROM incidents WHERE id IN ({incidents})"),
+            params,
+        )? as u64)
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS incidents (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            signer_ids TEXT NOT NULL,
+            summary TEXT NOT NULL,
+            opened_at INTEGER NOT NULL,
+            resolved_at INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS incident_notes (
+            incident_id INTEGER NOT NULL REFERENCES incidents(id),
+            author TEXT NOT NULL,
+            text TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        "#
+    }
+
+    const fn sql_insert_incident() -> &'static str {
+        r#"
+        INSERT INTO incidents (kind, signer_ids, summary, opened_at) VALUES (?1, ?2, ?3, ?4)
+        "#
+    }
+
+    const fn sql_insert_note() -> &'static str {
+        r#"
+        INSERT INTO incident_notes (incident_id, author, text, created_at) VALUES (?1, ?2, ?3, ?4)
+        "#
+    }
+
+    const fn sql_resolve() -> &'static str {
+        r#"
+        UPDATE incidents SET resolved_at=?2 WHERE id=?1
+        "#
+    }
+
+    const fn sql_select_ids() -> &'static str {
+        r#"
+        SELECT id FROM incidents WHERE ?1 OR resolved_at IS NULL ORDER BY id ASC
+        "#
+    }
+
+    const fn sql_select_incident() -> &'static str {
+        r#"
+        SELECT kind, signer_ids, summary, opened_at, resolved_at FROM incidents WHERE id=?1
+        "#
+    }
+
+    const fn sql_select_usage() -> &'static str {
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM incidents),
+            (SELECT COALESCE(SUM(LENGTH(kind) + LENGTH(signer_ids) + LENGTH(summary)), 0) FROM incidents)
+                + (SELECT COALESCE(SUM(LENGTH(author) + LENGTH(text)), 0) FROM incident_notes),
+            (SELECT MIN(resolved_at) FROM incidents)
+        "#
+    }
+
+    const fn sql_select_notes() -> &'static str {
+        r#"
+        SELECT author, text, created_at FROM incident_notes WHERE incident_id=?1 ORDER BY rowid ASC
+        "#
+    }
+}
+
+impl Artifact for IncidentLog {
+    type Error = Error;
+
+    fn name(&self) -> &'static str {
+        "incidents"
+    }
+
+    fn usage(&self) -> Result<Usage, Error> {
+        Ok(self
+            .conn
+            .query_row(Self::sql_select_usage(), rusqlite::params![], |row| {
+                Ok(Usage {
+                    entries: row.get::<_, i64>(0)? as u64,
+                    bytes: row.get::<_, i64>(1)? as u64,
+                    oldest: row.get::<_, Option<i64>>(2)?.map(|time| time as u64),
+                })
+            })?)
+    }
+
+    /// Resolved incidents are pruned by when they were resolved, open ones are kept
+    fn prune(&self, before: Option<u64>, max_bytes: Option<u64>) -> Result<u64, Error> {
+        let mut pruned = 0;
+        if let Some(before) = before {
+            pruned +=
+                self.delete_resolved("AND resolved_at < ?1", rusqlite::params![before as i64])?;
+        }
+        if let Some(max_bytes) = max_bytes {
+            while self.usage()?.bytes > max_bytes {
+                let deleted = self.delete_resolved(
+                    "ORDER BY resolved_at ASC, id ASC LIMIT 1",
+                    rusqlite::params![],
+                )?;
+                if deleted == 0 {
+                    break;
+                }
+                pruned += deleted;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frost_signer::signing_round::RejectionCode;
+
+    fn incident(kind: IncidentKind) -> NewIncident {
+        NewIncident {
+            kind,
+            signer_ids: vec![2, 3],
+            summary: "DKG round #1 failed on signers [2, 3]".to_string(),
+        }
+    }
+
+    #[test]
+    fn incidents_can_be_annotated_and_resolved() {
+        let log = IncidentLog::in_memory().unwrap();
+        let first = log.record(&incident(IncidentKind::RoundFailure)).unwrap();
+        let second = log.record(&incident(IncidentKind::Byzantine)).unwrap();
+
+        log.annotate(first, "alice", "signer 2 was restarting")
+            .unwrap();
+        log.resolve(first, "bob", "retried round succeeded")
+            .unwrap();
+        assert!(matches!(
+            log.resolve(first, "bob", "again"),
+            Err(Error::AlreadyResolved(id)) if id == first
+        ));
+
+        let open = log.list(false).unwrap();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].id, second);
+        assert_eq!(open[0].kind, IncidentKind::Byzantine);
+        assert_eq!(open[0].signer_ids, vec![2, 3]);
+
+        let resolved = log.get(first).unwrap();
+        assert!(resolved.is_resolved());
+        let notes: Vec<(&str, &str)> = resolved
+            .notes
+            .iter()
+            .map(|note| (note.author.as_str(), note.text.as_str()))
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                ("alice", "signer 2 was restarting"),
+                ("bob", "retried round succeeded")
+            ]
+        );
+        assert_eq!(log.list(true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn only_resolved_incidents_are_pruned() {
+        let log = IncidentLog::in_memory().unwrap();
+        let first = log.record(&incident(IncidentKind::RoundFailure)).unwrap();
+        let second = log.record(&incident(IncidentKind::RoundFailure)).unwrap();
+        let open = log.record(&incident(IncidentKind::Byzantine)).unwrap();
+        log.resolve(first, "alice", "retried").unwrap();
+        log.resolve(second, "alice", "retried").unwrap();
+
+        let usage = log.usage().unwrap();
+        assert_eq!(usage.entries, 3);
+        assert!(usage.oldest.is_some());
+
+        // nothing was resolved before the epoch
+        assert_eq!(log.prune(Some(0), None).unwrap(), 0);
+        assert_eq!(log.prune(None, Some(usage.bytes - 1)).unwrap(), 1);
+        assert!(matches!(log.get(first), Err(Error::UnknownIncident(_))));
+        assert_eq!(log.prune(Some(now() + 1), Some(0)).unwrap(), 1);
+
+        let left = log.list(true).unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].id, open);
+        assert_eq!(log.usage().unwrap().oldest, None);
+    }
+
+    #[test]
+    fn unknown_incidents_are_reported() {
+        let log = IncidentLog::in_memory().unwrap();
+        assert!(matches!(log.get(7), Err(Error::UnknownIncident(7))));
+        assert!(matches!(
+            log.annotate(7, "alice", "note"),
+            Err(Error::UnknownIncident(7))
+        ));
+    }
+
+    #[test]
+    fn errors_map_to_incident_kinds() {
+        let kind = |error: FrostCoordinatorError| {
+            NewIncident::from_error(&CoordinatorError::from(error)).map(|i| (i.kind, i.signer_ids))
+        };
+        assert_eq!(
+            kind(FrostCoordinatorError::DkgFailed(1, vec![2], vec![])),
+            Some((IncidentKind::RoundFailure, vec![2]))
+        );
+        assert_eq!(
+            kind(FrostCoordinatorError::DkgFailed(1, vec![1, 2], vec![3])),
+            Some((IncidentKind::Byzantine, vec![3]))
+        );
+        assert_eq!(
+            kind(FrostCoordinatorError::Rejected(
+                3,
+                RejectionCode::InvalidDkgPublicShare,
+                String::new()
+            )),
+            Some((IncidentKind::Byzantine, vec![3]))
+        );
+        assert_eq!(
+            kind(FrostCoordinatorError::Rejected(
+                3,
+                RejectionCode::InvalidState,
+                String::new()
+            )),
+            Some((IncidentKind::RoundFailure, vec![3]))
+        );
+        assert_eq!(
+            kind(FrostCoordinatorError::BadSignatureShares(vec![4], vec![3])),
+            Some((IncidentKind::Byzantine, vec![3]))
+        );
+        assert_eq!(
+            kind(FrostCoordinatorError::SchnorrProofFailed),
+            Some((IncidentKind::InvariantBreach, vec![]))
+        );
+        assert_eq!(kind(FrostCoordinatorError::NoAggregatePublicKey), None);
+        assert!(NewIncident::from_error(&CoordinatorError::CoordinatorStopped).is_none());
+    }
+}
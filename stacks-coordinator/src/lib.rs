@@ -1,11 +1,26 @@
 pub mod bitcoin_node;
+pub mod bitcoin_txs;
 pub mod bitcoin_wallet;
 pub mod cli;
 pub mod config;
 pub mod coordinator;
+pub mod fee_ledger;
+pub mod fee_oracle;
+pub mod frost_types;
+pub mod fulfillment;
+pub mod handle;
+pub mod history_audit;
+pub mod incident_log;
 pub mod make_contract_call;
+pub mod mock_frost_coordinator;
+pub mod mode;
+pub mod peg_latency;
 pub mod peg_queue;
 pub mod peg_wallet;
+pub mod proof_of_reserve;
+pub mod secrets;
+pub mod simulate;
+pub mod sponsor;
 pub mod stacks_node;
 pub mod stacks_transaction;
 pub mod stacks_wallet;
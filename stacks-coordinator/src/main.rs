@@ -1,37 +1,393 @@
 use clap::Parser;
+use frost_coordinator::history::{Error as HistoryError, RoundHistory};
+use frost_signer::config::Config as SignerConfig;
+use frost_signer::control::{Endpoint, Server as ControlServer};
+use frost_signer::failure::ErrorClass;
 use frost_signer::logging;
-use stacks_coordinator::cli::{Cli, Command};
+use frost_signer::redact::ConfigRoute;
+use stacks_coordinator::bitcoin_node::LocalhostBitcoinNode;
+use stacks_coordinator::bitcoin_txs::BitcoinTxLog;
+use stacks_coordinator::cli::{
+    Cli, Command, ConfigAction, IncidentsAction, ModeAction, PegOpsAction, RoundsAction,
+};
 use stacks_coordinator::config::Config;
-use stacks_coordinator::coordinator::{Coordinator, StacksCoordinator};
-use tracing::{info, warn};
+use stacks_coordinator::coordinator::{
+    configured_stacks_node, Coordinator, Error as CoordinatorError, StacksCoordinator,
+};
+use stacks_coordinator::fee_oracle::FeeOracle;
+use stacks_coordinator::history_audit::{verify_history, Audit, Error as HistoryAuditError};
+use stacks_coordinator::incident_log::{Error as IncidentLogError, IncidentLog};
+use stacks_coordinator::mode::{Error as ModeError, ModeStore};
+use stacks_coordinator::peg_latency::PegLatency;
+use stacks_coordinator::peg_queue::{Error as PegQueueError, PegQueue, SqlitePegQueue};
+use stacks_coordinator::proof_of_reserve::Attestation;
+use stacks_coordinator::sponsor::{Error as SponsorError, Sponsor};
+use stacks_coordinator::stacks_node::client::NodeClient;
+use std::fs;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
 fn main() {
     let cli = Cli::parse();
 
     // Initialize logging
-    logging::initiate_tracing_subscriber(if cli.debug {
+    let log_control = logging::initiate_tracing_subscriber(if cli.debug {
         tracing::Level::DEBUG
     } else {
         tracing::Level::INFO
     })
     .unwrap();
 
+    // the files are linted before they are loaded, as loading stops at the first error
+    if let Command::Config {
+        action: ConfigAction::Lint,
+    } = cli.command
+    {
+        // the coordinator's network key may be fetched from a secret manager instead
+        let fetched_network_key = Config::from_path(&cli.config)
+            .is_ok_and(|config| config.secrets.network_private_key.is_some());
+        let reports = [
+            Config::lint(&cli.config).map_err(|e| e.to_string()),
+            SignerConfig::lint(&cli.signer_config)
+                .map(|mut report| {
+                    if fetched_network_key {
+                        report
+                            .findings
+                            .retain(|finding| finding.key != "network_private_key");
+                    }
+                    report
+                })
+                .map_err(|e| e.to_string()),
+        ];
+        let mut clean = true;
+        for (path, report) in [&cli.config, &cli.signer_config].into_iter().zip(reports) {
+            match report {
+                Ok(report) => {
+                    println!("{report}");
+                    clean &= report.is_clean();
+                }
+                Err(e) => {
+                    warn!("An error occurred reading config file {}: {}", path, e);
+                    clean = false;
+                }
+            }
+        }
+        std::process::exit(if clean { 0 } else { 1 });
+    }
+
+    // attestations are checked on their own, as anyone receiving one would
+    if let Command::VerifyReserve { file, key } = &cli.command {
+        let attestation = fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str::<Attestation>(&json).map_err(|e| e.to_string()));
+        let verified = attestation.and_then(|attestation| {
+            attestation
+                .verify(key.as_ref())
+                .map(|()| attestation)
+                .map_err(|e| e.to_string())
+        });
+        match verified {
+            Ok(attestation) => {
+                let statement = attestation.statement;
+                println!(
+                    "verified: {} sats in {} outputs at block {} ({}), signed by {}",
+                    statement.total,
+                    statement.utxos.len(),
+                    statement.block_height,
+                    statement.block_hash,
+                    statement.aggregate_public_key
+                );
+            }
+            Err(e) => {
+                warn!("Attestation {} does not verify: {}", file.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     //TODO: get configs from sBTC contract
     match Config::from_path(&cli.config) {
         Ok(mut config) => {
             config.signer_config_path = cli.signer_config;
+            // incidents are read straight from the database, without connecting to any node
+            if let Command::Incidents { action } = cli.command {
+                match &config.rusqlite_path {
+                    Some(path) => {
+                        if let Err(e) = manage_incidents(path, action) {
+                            warn!("An error occurred managing incidents: {}", e);
+                        }
+                    }
+                    None => warn!("Incidents are only kept when rusqlite_path is configured"),
+                }
+                return;
+            }
+            // so are rounds, from the journal database of the frost coordinator
+            if let Command::Rounds { action } = cli.command {
+                let journal_path = SignerConfig::from_path(&config.signer_config_path)
+                    .map(|signer_config| signer_config.coordinator_journal_path);
+                match journal_path {
+                    Ok(Some(path)) => {
+                        if let Err(e) = show_rounds(&path, action) {
+                            warn!("An error occurred reading rounds: {}", e);
+                        }
+                    }
+                    Ok(None) => {
+                        warn!("Rounds are only kept when coordinator_journal_path is configured")
+                    }
+                    Err(e) => warn!(
+                        "An error occurred reading signer config file {}: {}",
+                        config.signer_config_path, e
+                    ),
+                }
+                return;
+            }
+            // and so are fee rates
+            if let Command::Fees = cli.command {
+                let Some(path) = &config.rusqlite_path else {
+                    warn!("Fee rates are only kept when rusqlite_path is configured");
+                    return;
+                };
+                let oracle = config.fee_oracle.clone().unwrap_or_default();
+                match FeeOracle::new(path, oracle).and_then(|oracle| oracle.rates()) {
+                    Ok(rates) => println!("{rates}"),
+                    Err(e) => warn!("An error occurred reading fee rates: {}", e),
+                }
+                return;
+            }
+            // and so are the stages of a peg op, from the peg queue
+            if let Command::Trace { op_id } = cli.command {
+                let Some(path) = &config.rusqlite_path else {
+                    warn!("Peg ops are only traced when rusqlite_path is configured");
+                    return;
+                };
+                let start_block_height = config.start_block_height.unwrap_or_default();
+                match SqlitePegQueue::new(path, start_block_height)
+                    .map_err(PegQueueError::from)
+                    .and_then(|queue| queue.trace(&op_id))
+                {
+                    Ok(Some(traced)) => println!("{traced}"),
+                    Ok(None) => {
+                        warn!("No peg op {}", op_id);
+                        std::process::exit(1);
+                    }
+                    Err(e) => warn!("An error occurred tracing peg op {}: {}", op_id, e),
+                }
+                return;
+            }
+            // held ops are kept there too, where the running coordinator reads them every tick
+            if let Command::PegOps { action } = cli.command {
+                let Some(path) = &config.rusqlite_path else {
+                    warn!("Peg ops can only be held when rusqlite_path is configured");
+                    return;
+                };
+                let start_block_height = config.start_block_height.unwrap_or_default();
+                if let Err(e) = SqlitePegQueue::new(path, start_block_height)
+                    .map_err(PegQueueError::from)
+                    .and_then(|queue| manage_peg_ops(&queue, action))
+                {
+                    warn!("An error occurred managing peg ops: {}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
+            // and so is the operational mode, which the running coordinator checks every tick
+            if let Command::Mode { action } = cli.command {
+                let Some(path) = &config.rusqlite_path else {
+                    warn!("The operational mode is only kept when rusqlite_path is configured");
+                    return;
+                };
+                if let Err(e) = manage_mode(path, action) {
+                    warn!("An error occurred managing the operational mode: {}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
+            // the history of the peg queue is rebuilt from chain state, without a frost coordinator
+            if let Command::RebuildDb { from_height } = cli.command {
+                let Some(path) = &config.rusqlite_path else {
+                    warn!("There is no database to rebuild unless rusqlite_path is configured");
+                    return;
+                };
+                let Some((contract_address, contract_name)) = config.sbtc_contract.split_once('.')
+                else {
+                    warn!(
+                        "sbtc_contract {} has no contract name",
+                        config.sbtc_contract
+                    );
+                    std::process::exit(1);
+                };
+                let rebuilt =
+                    configured_stacks_node(&config).and_then(|stacks_node| {
+                        let queue = SqlitePegQueue::new(path, from_height)?
+                            .with_peg_out_policy(config.peg_out_policy.clone());
+                        Ok(queue.rebuild(
+                            &stacks_node,
+                            contract_address,
+                            contract_name,
+                            from_height,
+                        )?)
+                    });
+                match rebuilt {
+                    Ok(rebuilt) => println!("Rebuilt {rebuilt}"),
+                    Err(e) => {
+                        warn!("An error occurred rebuilding the database: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            // and so are the bitcoin transactions broadcast, as of the last scanned block
+            if let Command::BitcoinTxs = cli.command {
+                let Some(path) = &config.rusqlite_path else {
+                    warn!("Bitcoin transactions are only kept when rusqlite_path is configured");
+                    return;
+                };
+                let txs = || -> Result<_, CoordinatorError> {
+                    let scanned_height =
+                        PegLatency::new(path, config.peg_latency.clone())?.scanned_height()?;
+                    Ok(BitcoinTxLog::new(path)?.list(scanned_height)?)
+                };
+                match txs() {
+                    Ok(txs) => {
+                        for tx in txs {
+                            println!("{tx}");
+                        }
+                    }
+                    Err(e) => warn!("An error occurred reading bitcoin transactions: {}", e),
+                }
+                return;
+            }
+            // the history is checked against the chain, without a frost coordinator
+            if let Command::VerifyHistory = cli.command {
+                let journal_path = match SignerConfig::from_path(&config.signer_config_path) {
+                    Ok(signer_config) => signer_config.coordinator_journal_path,
+                    Err(e) => {
+                        warn!(
+                            "An error occurred reading signer config file {}: {}",
+                            config.signer_config_path, e
+                        );
+                        std::process::exit(1);
+                    }
+                };
+                let Some(journal_path) = journal_path else {
+                    warn!("Rounds are only kept when coordinator_journal_path is configured");
+                    std::process::exit(1);
+                };
+                match verify_signature_history(&config, &journal_path) {
+                    Ok(audit) => {
+                        for discrepancy in &audit.discrepancies {
+                            println!("{discrepancy}");
+                        }
+                        println!("{audit}");
+                        if !audit.discrepancies.is_empty() {
+                            std::process::exit(1);
+                        }
+                    }
+                    Err(e) => {
+                        warn!("An error occurred verifying the history: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+            if let Command::LogFilter { directives } = &cli.command {
+                let endpoint = config
+                    .control
+                    .endpoint()
+                    .or(config.log_control_listen_addr.clone().map(Endpoint::Tcp));
+                let Some(endpoint) = endpoint else {
+                    warn!(
+                        "{} sets neither [control] nor log_control_listen_addr",
+                        cli.config
+                    );
+                    std::process::exit(1);
+                };
+                let token = config.control.token();
+                match logging::request(&endpoint, token, directives.as_deref()) {
+                    Ok(directives) => println!("{directives}"),
+                    Err(e) => {
+                        warn!("Log control request to {} failed: {}", endpoint, e);
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
             if cli.start_block_height.is_some() {
                 config.start_block_height = cli.start_block_height;
             }
+            if let Err(e) = config.load_secrets() {
+                warn!("An error occurred fetching secrets: {}", e);
+                std::process::exit(ErrorClass::Config.exit_code());
+            }
+            let log_control_listen_addr = config.log_control_listen_addr.clone();
+            let control = config.control.clone();
+            let effective = config.effective();
+            let sponsor = match &config.sponsor {
+                Some(sponsor_config) => {
+                    let sponsor = NodeClient::new(&config.stacks_node_rpc_url)
+                        .with_retry_policy(config.retry.clone())
+                        .with_proxy(&config.proxy)
+                        .map_err(SponsorError::from)
+                        .and_then(|stacks_node| {
+                            Sponsor::new(
+                                stacks_node,
+                                &config.stacks_private_key,
+                                &config.sbtc_contract,
+                                sponsor_config,
+                            )
+                        });
+                    match sponsor {
+                        Ok(sponsor) => Some((sponsor, sponsor_config.listen_addr.clone())),
+                        Err(e) => {
+                            warn!("An error occurred setting up fee sponsorship: {}", e);
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            let sbtc_contract_name = config
+                .sbtc_contract
+                .split('.')
+                .nth(1)
+                .unwrap_or_default()
+                .to_string();
             match StacksCoordinator::try_from(config) {
                 Ok(mut coordinator) => {
                     // Determine what action the caller wishes to perform
                     match cli.command {
                         Command::Run => {
                             info!("Running coordinator");
+                            info!("Effective configuration:\n{}", effective);
+                            let server = ControlServer::new(&control)
+                                .with_route(log_control)
+                                .with_route(ConfigRoute::new(effective))
+                                .with_route(coordinator.frost_coordinator().round_events().clone());
+                            if let Some(addr) = &log_control_listen_addr {
+                                if let Err(e) = server.serve_tcp(addr.as_str()) {
+                                    warn!("Failed to serve the log filter on {}: {}", addr, e);
+                                }
+                            }
+                            if let Err(e) = server.serve(&control) {
+                                warn!("Failed to serve the control endpoints: {}", e);
+                            }
+                            if let Some((sponsor, addr)) = sponsor {
+                                if let Err(e) = sponsor.serve(addr.as_str()) {
+                                    warn!("Failed to serve fee sponsorship on {}: {}", addr, e);
+                                }
+                            }
                             //TODO: set up coordination with the stacks node
+                            // the failure report, if configured, is written before run returns
                             if let Err(e) = coordinator.run() {
-                                warn!("An error occurred running the coordinator: {}", e);
+                                let class = e.error_class();
+                                error!(
+                                    "Coordinator stopped on a {} error, exiting with {}: {}",
+                                    class,
+                                    class.exit_code(),
+                                    e
+                                );
+                                std::process::exit(class.exit_code());
                             }
                         }
                         Command::Dkg => {
@@ -46,27 +402,196 @@ fn main() {
                                 warn!("An error occurred during DKG round: {}", e);
                             };
                             info!("Running Signing Round");
-                            let (signature, schnorr_proof) =
-                                match coordinator.sign_message("Hello, world!") {
-                                    Ok((sig, proof)) => (sig, proof),
+                            let signature =
+                                match coordinator.sign_bytes("sbtc/dkg-sign", b"Hello, world!") {
+                                    Ok(signature) => signature,
                                     Err(e) => {
                                         panic!("signing message failed: {e}");
                                     }
                                 };
-                            info!(
-                                "Got good signature ({},{}) and schnorr proof ({},{})",
-                                &signature.R, &signature.z, &schnorr_proof.r, &schnorr_proof.s
-                            );
+                            info!("Got good signature {}", signature);
+                        }
+                        Command::SimulatePegOut {
+                            recipient,
+                            amount,
+                            utxo,
+                            fulfillment_fee,
+                            dry_run,
+                        } => {
+                            info!("Simulating a peg-out of {} sats to {}", amount, recipient);
+                            match coordinator.simulate_peg_out(
+                                &recipient,
+                                amount,
+                                fulfillment_fee,
+                                utxo,
+                                dry_run,
+                            ) {
+                                Ok(simulated) => println!("{simulated}"),
+                                Err(e) => {
+                                    warn!("An error occurred simulating a peg-out: {}", e);
+                                    std::process::exit(1);
+                                }
+                            }
+                        }
+                        Command::ProofOfReserve { out } => {
+                            info!("Attesting to the peg wallet reserves");
+                            let written = coordinator
+                                .prove_reserve()
+                                .map_err(|e| e.to_string())
+                                .and_then(|attestation| {
+                                    serde_json::to_string_pretty(&attestation)
+                                        .map_err(|e| e.to_string())
+                                })
+                                .and_then(|json| match &out {
+                                    Some(path) => fs::write(path, json).map_err(|e| e.to_string()),
+                                    None => {
+                                        println!("{json}");
+                                        Ok(())
+                                    }
+                                });
+                            if let Err(e) = written {
+                                warn!("An error occurred attesting to the reserves: {}", e);
+                                std::process::exit(1);
+                            }
+                        }
+                        Command::Incidents { .. }
+                        | Command::VerifyReserve { .. }
+                        | Command::Rounds { .. }
+                        | Command::Fees
+                        | Command::Trace { .. }
+                        | Command::PegOps { .. }
+                        | Command::Mode { .. }
+                        | Command::BitcoinTxs
+                        | Command::VerifyHistory
+                        | Command::RebuildDb { .. }
+                        | Command::Config { .. }
+                        | Command::LogFilter { .. } => {
+                            unreachable!("handled above")
+                        }
+                        Command::DeployContract {
+                            source,
+                            name,
+                            nonce,
+                            timeout,
+                        } => {
+                            let name = name.unwrap_or(sbtc_contract_name);
+                            info!("Deploying contract {}", name);
+                            match fs::read_to_string(&source) {
+                                Ok(code_body) => {
+                                    if let Err(e) = coordinator.deploy_contract(
+                                        &name,
+                                        &code_body,
+                                        nonce,
+                                        Duration::from_secs(timeout),
+                                    ) {
+                                        warn!("An error occurred deploying contract: {}", e);
+                                    }
+                                }
+                                Err(e) => {
+                                    warn!(
+                                        "An error occurred reading contract source {}: {}",
+                                        source.display(),
+                                        e
+                                    );
+                                }
+                            }
                         }
                     };
                 }
                 Err(e) => {
                     warn!("An error occurred creating coordinator: {}", e);
+                    std::process::exit(e.error_class().exit_code());
                 }
             }
         }
         Err(e) => {
             warn!("An error occrred reading config file {}: {}", cli.config, e);
+            std::process::exit(ErrorClass::Config.exit_code());
+        }
+    }
+}
+
+fn manage_incidents(path: &str, action: IncidentsAction) -> Result<(), IncidentLogError> {
+    let log = IncidentLog::new(path)?;
+    match action {
+        IncidentsAction::List { all } => {
+            for incident in log.list(all)? {
+                let status = match incident.resolved_at {
+                    Some(_) => "resolved",
+                    None => "open",
+                };
+                println!(
+                    "#{} [{}] {} signers={:?} opened_at={} {}",
+                    incident.id,
+                    status,
+                    incident.kind,
+                    incident.signer_ids,
+                    incident.opened_at,
+                    incident.summary
+                );
+                for note in &incident.notes {
+                    println!("    {} {}: {}", note.created_at, note.author, note.text);
+                }
+            }
+        }
+        IncidentsAction::Annotate { id, author, note } => log.annotate(id, &author, &note)?,
+        IncidentsAction::Resolve { id, author, note } => log.resolve(id, &author, &note)?,
+    }
+    Ok(())
+}
+
+fn manage_peg_ops(queue: &SqlitePegQueue, action: PegOpsAction) -> Result<(), PegQueueError> {
+    match action {
+        PegOpsAction::Held => {
+            for traced in queue.held()? {
+                println!("{traced}");
+            }
+        }
+        PegOpsAction::Hold { op_id, reason } => queue.hold(&op_id, &reason)?,
+        PegOpsAction::Release { op_id, reason } => queue.release(&op_id, &reason)?,
+    }
+    Ok(())
+}
+
+fn manage_mode(path: &str, action: ModeAction) -> Result<(), ModeError> {
+    let modes = ModeStore::new(path)?;
+    let state = match action {
+        ModeAction::Show => modes.get()?,
+        ModeAction::Pause { author, reason } => modes.pause(&author, &reason)?,
+        ModeAction::Resume { author, reason } => modes.resume(&author, &reason)?,
+    };
+    println!("{state}");
+    Ok(())
+}
+
+fn verify_signature_history(
+    config: &Config,
+    journal_path: &str,
+) -> Result<Audit, CoordinatorError> {
+    let history = RoundHistory::new(journal_path).map_err(HistoryAuditError::from)?;
+    // without a database, only the signatures themselves are checked
+    let txs = match &config.rusqlite_path {
+        Some(path) => BitcoinTxLog::new(path)?,
+        None => BitcoinTxLog::in_memory()?,
+    };
+    let bitcoin_node =
+        LocalhostBitcoinNode::new(config.bitcoin_node_rpc_url.clone(), config.retry.clone())
+            .with_egress(config.proxy.egress(&config.bitcoin_node_rpc_url)?);
+    Ok(verify_history(&history, &txs, &bitcoin_node)?)
+}
+
+fn show_rounds(path: &str, action: RoundsAction) -> Result<(), HistoryError> {
+    let history = RoundHistory::new(path)?;
+    match action {
+        RoundsAction::List { since, until } => {
+            for round in history.list(since, until)? {
+                println!("{round}");
+            }
         }
+        RoundsAction::Show { id } => match history.get(id)? {
+            Some(round) => println!("{round}"),
+            None => println!("no round #{id}"),
+        },
     }
+    Ok(())
 }
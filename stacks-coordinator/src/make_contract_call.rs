@@ -1,7 +1,10 @@
+use std::marker::PhantomData;
 use std::path::Path;
 
 use blockstack_lib::vm::{database::ClaritySerializable, Value};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+#[cfg(feature = "js")]
 use yarpc::{dispatch_command::DispatchCommand, js::Js, rpc::Rpc};
 
 use crate::stacks_transaction::StacksTransaction;
@@ -23,6 +26,42 @@ pub enum Error {
     IO(#[from] std::io::Error),
     #[error("Invalid Path: {0}")]
     InvalidPath(std::path::PathBuf),
+    #[error(
+        "Stacks transactions are built by the JS subsystem, which is disabled by `js.enabled`"
+    )]
+    Disabled,
+    #[error("Stacks transactions are built by the JS subsystem, which was not compiled in (feature `js`)")]
+    NotCompiled,
+    #[error("JS subsystem script {0} not found, check `js.path`")]
+    ScriptNotFound(std::path::PathBuf),
+    #[error("Could not start deno to run {0}: {1}")]
+    Spawn(std::path::PathBuf, std::io::Error),
+}
+
+/// Where stacks transactions are built: stacks.js scripts run by deno.
+/// Deserializes from a `[js]` table, for example
+/// ```toml
+/// [js]
+/// # directory holding yarpc/js/stacks/transactions.ts
+/// path = "/opt/core-eng"
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JsConfig {
+    /// Set to false on deployments without deno or the scripts. Building a stacks
+    /// transaction then fails with [`Error::Disabled`].
+    pub enabled: bool,
+    /// Directory holding `yarpc/js`, relative to the working directory
+    pub path: String,
+}
+
+impl Default for JsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: "..".to_string(),
+        }
+    }
 }
 
 #[allow(non_snake_case)]
@@ -66,30 +105,199 @@ pub struct SignedContractCallOptions {
 }
 
 impl SignedContractCallOptions {
+    /// A builder of the options, which only builds once the contract, the function and the
+    /// sender key are set
+    pub fn builder() -> SignedContractCallBuilder<Unset, Unset, Unset> {
+        SignedContractCallBuilder {
+            options: Self {
+                contractAddress: String::new(),
+                contractName: String::new(),
+                functionName: String::new(),
+                functionArgs: Vec::new(),
+                fee: None,
+                feeEstimateApiUrl: None,
+                nonce: None,
+                network: None,
+                anchorMode: ANY,
+                postConditionMode: None,
+                postConditions: None,
+                validateWithAbi: None,
+                sponsored: None,
+                senderKey: String::new(),
+            },
+            state: PhantomData,
+        }
+    }
+}
+
+/// A required field of a [`SignedContractCallBuilder`] which is not set yet
+pub struct Unset;
+
+/// A required field of a [`SignedContractCallBuilder`] which is set
+pub struct Set;
+
+/// Builds [`SignedContractCallOptions`]. The type parameters track whether the contract, the
+/// function and the sender key are set, and `build` only exists once all three are.
+/// ```
+/// use stacks_coordinator::make_contract_call::SignedContractCallOptions;
+///
+/// let options = SignedContractCallOptions::builder()
+///     .contract("SP000000000000000000002Q6VF78", "sbtc")
+///     .function("mint!", &[])
+///     .sender_key("0001020304050607080910111213141516171819202122232425262728293031")
+///     .fee(180)
+///     .build();
+/// assert_eq!(options.fee.as_deref(), Some("180"));
+/// ```
+pub struct SignedContractCallBuilder<Contract, Function, SenderKey> {
+    options: SignedContractCallOptions,
+    state: PhantomData<(Contract, Function, SenderKey)>,
+}
+
+impl<Contract, Function, SenderKey> SignedContractCallBuilder<Contract, Function, SenderKey> {
+    fn into_state<C, F, K>(self) -> SignedContractCallBuilder<C, F, K> {
+        SignedContractCallBuilder {
+            options: self.options,
+            state: PhantomData,
+        }
+    }
+
+    pub fn fee(mut self, fee: u128) -> Self {
+        self.options.fee = Some(fee.to_string());
+        self
+    }
+
+    pub fn fee_estimate_api_url(mut self, url: impl Into<String>) -> Self {
+        self.options.feeEstimateApiUrl = Some(url.into());
+        self
+    }
+
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.options.nonce = Some(nonce.to_string());
+        self
+    }
+
+    pub fn network(mut self, network: StacksNetworkNameOrStacksNetwork) -> Self {
+        self.options.network = Some(network);
+        self
+    }
+
+    /// [`ANY`] unless set
+    pub fn anchor_mode(mut self, anchor_mode: AnchorMode) -> Self {
+        self.options.anchorMode = anchor_mode;
+        self
+    }
+
+    pub fn post_condition_mode(mut self, mode: PostConditionMode) -> Self {
+        self.options.postConditionMode = Some(mode);
+        self
+    }
+
+    pub fn post_conditions(mut self, post_conditions: PostCondition) -> Self {
+        self.options.postConditions = Some(post_conditions);
+        self
+    }
+
+    pub fn validate_with_abi(mut self, abi: BooleanOrClarityAbi) -> Self {
+        self.options.validateWithAbi = Some(abi);
+        self
+    }
+
+    pub fn sponsored(mut self, sponsored: bool) -> Self {
+        self.options.sponsored = Some(sponsored);
+        self
+    }
+}
+
+impl<Function, SenderKey> SignedContractCallBuilder<Unset, Function, SenderKey> {
+    /// The contract to call, deployed by `address` under `name`
+    pub fn contract(
+        mut self,
+        address: impl Into<String>,
+        name: impl Into<String>,
+    ) -> SignedContractCallBuilder<Set, Function, SenderKey> {
+        self.options.contractAddress = address.into();
+        self.options.contractName = name.into();
+        self.into_state()
+    }
+}
+
+impl<Contract, SenderKey> SignedContractCallBuilder<Contract, Unset, SenderKey> {
+    /// The public function to call, with its arguments
+    pub fn function(
+        mut self,
+        name: impl Into<String>,
+        args: &[Value],
+    ) -> SignedContractCallBuilder<Contract, Set, SenderKey> {
+        self.options.functionName = name.into();
+        self.options.functionArgs = args.iter().map(ClaritySerializable::serialize).collect();
+        self.into_state()
+    }
+}
+
+impl<Contract, Function> SignedContractCallBuilder<Contract, Function, Unset> {
+    /// The hex private key signing the transaction
+    pub fn sender_key(
+        mut self,
+        sender_key: impl Into<String>,
+    ) -> SignedContractCallBuilder<Contract, Function, Set> {
+        self.options.senderKey = sender_key.into();
+        self.into_state()
+    }
+}
+
+impl SignedContractCallBuilder<Set, Set, Set> {
+    pub fn build(self) -> SignedContractCallOptions {
+        self.options
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Serialize)]
+pub struct SignedContractDeployOptions {
+    pub contractName: String,
+
+    pub codeBody: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<IntegerType>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<IntegerType>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<StacksNetworkNameOrStacksNetwork>,
+
+    pub anchorMode: AnchorMode,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postConditionMode: Option<PostConditionMode>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postConditions: Option<PostCondition>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sponsored: Option<bool>,
+
+    pub senderKey: String,
+}
+
+impl SignedContractDeployOptions {
     pub fn new(
-        contract_address: impl Into<String>,
         contract_name: impl Into<String>,
-        function_name: impl Into<String>,
-        function_args: &[Value],
+        code_body: impl Into<String>,
         anchor_mode: AnchorMode,
         sender_key: impl Into<String>,
     ) -> Self {
         Self {
-            contractAddress: contract_address.into(),
             contractName: contract_name.into(),
-            functionName: function_name.into(),
-            functionArgs: function_args
-                .iter()
-                .map(ClaritySerializable::serialize)
-                .collect(),
+            codeBody: code_body.into(),
             fee: None,
-            feeEstimateApiUrl: None,
             nonce: None,
             network: None,
             anchorMode: anchor_mode,
             postConditionMode: None,
             postConditions: None,
-            validateWithAbi: None,
             sponsored: None,
             senderKey: sender_key.into(),
         }
@@ -98,6 +306,10 @@ impl SignedContractCallOptions {
         self.fee = Some(fee.to_string());
         self
     }
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce.to_string());
+        self
+    }
 }
 
 pub type TransactionVersion = serde_json::Number;
@@ -118,21 +330,146 @@ pub type PostConditionMode = serde_json::Value;
 
 pub type LengthPrefixedList = serde_json::Value;
 
-pub struct MakeContractCall(Js);
+/// Builds stacks transactions by calling `yarpc/js/stacks/transactions.ts`.
+///
+/// Deno is only started by the first call, so a deployment lacking deno or the scripts starts
+/// up fine and only fails once it has a stacks transaction to build.
+pub struct MakeContractCall {
+    enabled: bool,
+    #[cfg(feature = "js")]
+    script: std::path::PathBuf,
+    #[cfg(feature = "js")]
+    js: Option<Js>,
+}
 
 impl MakeContractCall {
     pub fn call(&mut self, input: &SignedContractCallOptions) -> Result<StacksTransaction, Error> {
-        Ok(self
-            .0
-            .call(&DispatchCommand("makeContractCall".to_string(), input))?)
+        self.dispatch("makeContractCall", input)
+    }
+    pub fn deploy(
+        &mut self,
+        input: &SignedContractDeployOptions,
+    ) -> Result<StacksTransaction, Error> {
+        self.dispatch("makeContractDeploy", input)
     }
     pub fn new(path: &str) -> Result<Self, Error> {
-        let file_name = Path::new(path).join("yarpc/js/stacks/transactions.ts");
-        Ok(Self(Js::new(
-            file_name
-                .clone()
-                .to_str()
-                .ok_or_else(|| Error::InvalidPath(file_name))?,
-        )?))
+        Self::from_config(&JsConfig {
+            enabled: true,
+            path: path.to_string(),
+        })
+    }
+    pub fn from_config(config: &JsConfig) -> Result<Self, Error> {
+        let script = Path::new(&config.path).join("yarpc/js/stacks/transactions.ts");
+        if script.to_str().is_none() {
+            return Err(Error::InvalidPath(script));
+        }
+        if config.enabled && !script.is_file() {
+            warn!(
+                "JS subsystem script {} not found, building stacks transactions will fail",
+                script.display()
+            );
+        }
+        Ok(Self {
+            enabled: config.enabled,
+            #[cfg(feature = "js")]
+            script,
+            #[cfg(feature = "js")]
+            js: None,
+        })
+    }
+
+    #[cfg(feature = "js")]
+    fn dispatch<T: Serialize>(
+        &mut self,
+        command: &str,
+        input: &T,
+    ) -> Result<StacksTransaction, Error> {
+        Ok(self
+            .js()?
+            .call(&DispatchCommand(command.to_string(), input))?)
+    }
+
+    #[cfg(not(feature = "js"))]
+    fn dispatch<T: Serialize>(
+        &mut self,
+        _command: &str,
+        _input: &T,
+    ) -> Result<StacksTransaction, Error> {
+        Err(if self.enabled {
+            Error::NotCompiled
+        } else {
+            Error::Disabled
+        })
+    }
+
+    /// The deno process running the script, started on first use
+    #[cfg(feature = "js")]
+    fn js(&mut self) -> Result<&mut Js, Error> {
+        if !self.enabled {
+            return Err(Error::Disabled);
+        }
+        if self.js.is_none() {
+            if !self.script.is_file() {
+                return Err(Error::ScriptNotFound(self.script.clone()));
+            }
+            let script = self.script.to_str().expect("checked when constructed");
+            let js = Js::new(script).map_err(|e| Error::Spawn(self.script.clone(), e))?;
+            self.js = Some(js);
+        }
+        Ok(self.js.as_mut().expect("started above"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> SignedContractCallOptions {
+        SignedContractCallOptions::builder()
+            .contract("SP000000000000000000002Q6VF78", "sbtc")
+            .function("mint!", &[])
+            .sender_key("")
+            .build()
+    }
+
+    #[test]
+    fn builder_serializes_only_the_options_set() {
+        let options = SignedContractCallOptions::builder()
+            .sender_key("key")
+            .function("burn!", &[Value::UInt(7)])
+            .contract("SP000000000000000000002Q6VF78", "sbtc")
+            .sponsored(true)
+            .build();
+        assert_eq!(
+            serde_json::to_value(&options).unwrap(),
+            serde_json::json!({
+                "contractAddress": "SP000000000000000000002Q6VF78",
+                "contractName": "sbtc",
+                "functionName": "burn!",
+                "functionArgs": [Value::UInt(7).serialize()],
+                "anchorMode": ANY,
+                "sponsored": true,
+                "senderKey": "key",
+            })
+        );
+    }
+
+    #[test]
+    fn fails_only_when_a_transaction_is_built() {
+        let mut disabled = MakeContractCall::from_config(&JsConfig {
+            enabled: false,
+            path: "/nonexistent".to_string(),
+        })
+        .unwrap();
+        assert!(matches!(disabled.call(&options()), Err(Error::Disabled)));
+
+        let mut missing = MakeContractCall::new("/nonexistent").unwrap();
+        let result = missing.call(&options());
+        #[cfg(feature = "js")]
+        assert!(
+            matches!(result, Err(Error::ScriptNotFound(path)) if path.starts_with("/nonexistent"))
+        );
+        #[cfg(not(feature = "js"))]
+        assert!(matches!(result, Err(Error::NotCompiled)));
     }
 }
@@ -0,0 +1,277 @@
+//! An in-process stand-in for the frost coordinator.
+//!
+//! [`MockFrostCoordinator`] holds a single secp256k1 keypair where the real coordinator has a
+//! signer set: DKG generates a new keypair, and signing makes a BIP-340 signature with it on the
+//! spot. Injected into a [`StacksCoordinator`](crate::coordinator::StacksCoordinator), it lets the
+//! paths which sign bitcoin transactions run without a relay or any signers.
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{
+    rand::thread_rng, All, KeyPair, Parity, Scalar, Secp256k1, SecretKey, XOnlyPublicKey,
+};
+use bitcoin::util::schnorr::TapTweak;
+use bitcoin::util::taproot::TapBranchHash;
+use frost_coordinator::coordinator::Error as FrostCoordinatorError;
+use frost_coordinator::events::RoundEvents;
+use frost_coordinator::history::Round;
+use frost_coordinator::quorum::SigningQuorum;
+use frost_signer::digest::MessageDigest;
+use frost_signer::retention::Retention;
+use frost_signer::taproot::KeyPath;
+use frost_signer::thresholds::Thresholds;
+
+use crate::coordinator::{FrostCoordinator, Result};
+use crate::frost_types::{GroupPublicKey, PartyCommitment, ThresholdSignature};
+
+pub struct MockFrostCoordinator {
+    secp: Secp256k1<All>,
+    key_pair: Option<KeyPair>,
+    dkg_id: u64,
+    /// Signing calls made, each standing for a round of the real coordinator
+    sign_id: u64,
+    retention: Retention,
+    events: RoundEvents,
+    signing_quorum: SigningQuorum,
+}
+
+impl MockFrostCoordinator {
+    pub fn new() -> Self {
+        Self {
+            secp: Secp256k1::new(),
+            key_pair: None,
+            dkg_id: 0,
+            sign_id: 0,
+            retention: Retention::default(),
+            events: RoundEvents::default(),
+            signing_quorum: SigningQuorum::default(),
+        }
+    }
+
+    fn key_pair(&self) -> Result<&KeyPair> {
+        Ok(self
+            .key_pair
+            .as_ref()
+            .ok_or(FrostCoordinatorError::NoAggregatePublicKey)?)
+    }
+
+    /// A secret key whose public key has an even y coordinate, as the real coordinator
+    /// re-runs DKG until the group key has
+    fn even_secret_key(&self) -> SecretKey {
+        let secret_key = SecretKey::new(&mut thread_rng());
+        match secret_key.x_only_public_key(&self.secp).1 {
+            Parity::Even => secret_key,
+            Parity::Odd => secret_key.negate(),
+        }
+    }
+
+    /// A BIP-340 signature of `digest` by the keypair, or by its tweak for the output of
+    /// `key_path` as the signers would sign a key path spend of it
+    fn sign(
+        &self,
+        digest: &MessageDigest,
+        key_path: Option<&KeyPath>,
+    ) -> Result<ThresholdSignature> {
+        let mut key_pair = *self.key_pair()?;
+        if let Some(key_path) = key_path {
+            let merkle_root = key_path.merkle_root.map(TapBranchHash::from_inner);
+            key_pair = key_pair.tap_tweak(&self.secp, merkle_root).to_inner();
+        }
+        let (public_key, parity) = key_pair.x_only_public_key();
+        // BIP-340 signs with the secret of the even y point, which a tweak may have flipped
+        let secret_key = match parity {
+            Parity::Even => SecretKey::from_keypair(&key_pair),
+            Parity::Odd => SecretKey::from_keypair(&key_pair).negate(),
+        };
+        loop {
+            let nonce = self.even_secret_key();
+            let (r, _) = nonce.x_only_public_key(&self.secp);
+            let Some(e) = challenge(&r, &public_key, digest.as_bytes()) else {
+                continue;
+            };
+            // s = k + e * d
+            let s = secret_key.mul_tweak(&e)?.add_tweak(&Scalar::from(nonce))?;
+            let signature = [r.serialize(), s.secret_bytes()].concat();
+            return Ok(ThresholdSignature::from_slice(&signature)?);
+        }
+    }
+}
+
+impl Default for MockFrostCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The BIP-340 challenge of a signature with nonce `r` by `public_key` over `msg`, or `None`
+/// in the negligible case of the hash not being below the curve order
+fn challenge(r: &XOnlyPublicKey, public_key: &XOnlyPublicKey, msg: &[u8]) -> Option<Scalar> {
+    let tag = sha256::Hash::hash(b"BIP0340/challenge");
+    let mut engine = sha256::Hash::engine();
+    engine.input(&tag);
+    engine.input(&tag);
+    engine.input(&r.serialize());
+    engine.input(&public_key.serialize());
+    engine.input(msg);
+    Scalar::from_be_bytes(sha256::Hash::from_engine(engine).into_inner()).ok()
+}
+
+impl FrostCoordinator for MockFrostCoordinator {
+    fn run_distributed_key_generation(&mut self) -> Result<GroupPublicKey> {
+        let secret_key = self.even_secret_key();
+        self.key_pair = Some(KeyPair::from_secret_key(&self.secp, &secret_key));
+        self.dkg_id += 1;
+        self.get_aggregate_public_key()
+    }
+
+    fn get_aggregate_public_key(&self) -> Result<GroupPublicKey> {
+        let public_key = self.key_pair()?.public_key();
+        Ok(GroupPublicKey::from_slice(&public_key.serialize())?)
+    }
+
+    fn sign_digest(&mut self, digest: &MessageDigest) -> Result<ThresholdSignature> {
+        self.sign_id += 1;
+        self.sign(digest, None)
+    }
+
+    /// All in one call, as the real coordinator signs them in one round
+    fn sign_digests(&mut self, digests: &[MessageDigest]) -> Result<Vec<ThresholdSignature>> {
+        self.sign_id += 1;
+        digests
+            .iter()
+            .map(|digest| self.sign(digest, None))
+            .collect()
+    }
+
+    fn sign_key_path(
+        &mut self,
+        digest: &MessageDigest,
+        key_path: &KeyPath,
+    ) -> Result<ThresholdSignature> {
+        self.sign_id += 1;
+        self.sign(digest, Some(key_path))
+    }
+
+    fn sign_digests_key_path(
+        &mut self,
+        digests: &[MessageDigest],
+        key_path: &KeyPath,
+    ) -> Result<Vec<ThresholdSignature>> {
+        self.sign_id += 1;
+        digests
+            .iter()
+            .map(|digest| self.sign(digest, Some(key_path)))
+            .collect()
+    }
+
+    fn party_commitments(&self) -> Result<Vec<PartyCommitment>> {
+        if self.key_pair.is_none() {
+            return Ok(vec![]);
+        }
+        // the keypair stands for a single party whose polynomial is constant
+        Ok(vec![PartyCommitment {
+            party_id: 0,
+            coefficients: vec![self.get_aggregate_public_key()?],
+        }])
+    }
+
+    fn current_dkg_id(&self) -> u64 {
+        self.dkg_id
+    }
+
+    fn current_sign_id(&self) -> u64 {
+        self.sign_id
+    }
+
+    fn round_timeouts(&self) -> u64 {
+        0
+    }
+
+    fn thresholds(&self) -> Thresholds {
+        Thresholds::default()
+    }
+
+    fn excluded_signers(&self) -> Vec<usize> {
+        vec![]
+    }
+
+    fn readmit_signers(&mut self) {}
+
+    /// The keypair stands for signer #1 holding key #0
+    fn set_signing_quorum(&mut self, quorum: SigningQuorum) -> Result<()> {
+        let roster = [(1, vec![0])].into_iter().collect();
+        quorum
+            .key_ids(&roster, self.thresholds())
+            .map_err(FrostCoordinatorError::from)?;
+        self.signing_quorum = quorum;
+        Ok(())
+    }
+
+    fn signing_quorum(&self) -> SigningQuorum {
+        self.signing_quorum.clone()
+    }
+
+    fn retention(&self) -> Retention {
+        self.retention.clone()
+    }
+
+    fn list_rounds(&self, _since: Option<u64>, _until: Option<u64>) -> Result<Vec<Round>> {
+        Ok(vec![])
+    }
+
+    fn get_round(&self, _id: i64) -> Result<Option<Round>> {
+        Ok(None)
+    }
+
+    /// The mock runs no rounds, so nothing is ever published
+    fn round_events(&self) -> RoundEvents {
+        self.events.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::Message;
+
+    #[test]
+    fn signs_with_the_key_of_the_last_dkg_round() {
+        let mut frost = MockFrostCoordinator::new();
+        assert!(frost.sign_digest(&MessageDigest::new([1; 32])).is_err());
+        assert!(frost.party_commitments().unwrap().is_empty());
+
+        let key = frost.run_distributed_key_generation().unwrap();
+        assert_eq!(frost.current_dkg_id(), 1);
+        assert_eq!(frost.get_aggregate_public_key().unwrap(), key);
+        assert_eq!(
+            frost.party_commitments().unwrap()[0].coefficients,
+            vec![key]
+        );
+
+        let secp = Secp256k1::verification_only();
+        let msg = Message::from_slice(&[1; 32]).unwrap();
+        let signature = frost.sign_digest(&MessageDigest::new([1; 32])).unwrap();
+        secp.verify_schnorr(&signature.to_schnorr().unwrap(), &msg, &key.to_x_only())
+            .unwrap();
+
+        // a key path spend verifies with the output key only
+        let key_path = KeyPath {
+            merkle_root: Some([9; 32]),
+        };
+        let signature = frost
+            .sign_key_path(&MessageDigest::new([1; 32]), &key_path)
+            .unwrap()
+            .to_schnorr()
+            .unwrap();
+        secp.verify_schnorr(&signature, &msg, &key.output_key(&key_path).to_inner())
+            .unwrap();
+        assert!(secp
+            .verify_schnorr(&signature, &msg, &key.to_x_only())
+            .is_err());
+
+        let next_key = frost.run_distributed_key_generation().unwrap();
+        assert_ne!(next_key, key);
+        let signature = frost.sign_digest(&MessageDigest::new([1; 32])).unwrap();
+        assert!(secp
+            .verify_schnorr(&signature.to_schnorr().unwrap(), &msg, &key.to_x_only())
+            .is_err());
+    }
+}
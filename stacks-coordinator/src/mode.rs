@@ -0,0 +1,284 @@
+//! Operational mode of the coordinator.
+//!
+//! The mode says which stages of the pipeline run on each tick:
+//!
+//! - `bootstrap`: there is no aggregate key yet. Peg ops are read and queued, but nothing is
+//!   signed until DKG produced a key.
+//! - `normal`: every stage runs.
+//! - `degraded`: an `invariant_breach` or `byzantine` incident is open. Peg ops are still
+//!   processed, but fees are not swept until the incidents are resolved.
+//! - `paused`: an operator paused the coordinator. Peg ops are read and queued, and
+//!   fulfillments already broadcast are followed, but nothing is signed until it is resumed.
+//!
+//! The mode is written to an `operational_mode` table next to the peg queue, so a pause
+//! survives restarts and can be set from the command line while the coordinator runs. The
+//! coordinator checks its health at the start of every tick, moving between `bootstrap`,
+//! `normal` and `degraded` on its own; only an operator enters or leaves `paused`.
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension};
+use serde::Serialize;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+    #[error("Did not recognize operational mode: {0}")]
+    InvalidModeError(String),
+    #[error("The coordinator is not paused but {0}")]
+    NotPaused(Mode),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    #[default]
+    Bootstrap,
+    Normal,
+    Degraded,
+    Paused,
+}
+
+impl Mode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bootstrap => "bootstrap",
+            Self::Normal => "normal",
+            Self::Degraded => "degraded",
+            Self::Paused => "paused",
+        }
+    }
+
+    /// Whether queued peg ops are processed, which signs their fulfillments
+    pub fn processes_queue(&self) -> bool {
+        matches!(self, Self::Normal | Self::Degraded)
+    }
+
+    /// Whether collected fees are swept to the operator address
+    pub fn sweeps_fees(&self) -> bool {
+        *self == Self::Normal
+    }
+
+    /// The mode a health check moves this one to, given whether there is an aggregate key and
+    /// whether no incident calling for an operator is open. Paused stays paused.
+    pub fn checked(self, has_key: bool, healthy: bool) -> Self {
+        match self {
+            Self::Paused => Self::Paused,
+            _ if !has_key => Self::Bootstrap,
+            _ if healthy => Self::Normal,
+            _ => Self::Degraded,
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Ok(match s {
+            "bootstrap" => Self::Bootstrap,
+            "normal" => Self::Normal,
+            "degraded" => Self::Degraded,
+            "paused" => Self::Paused,
+            other => return Err(Error::InvalidModeError(other.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The current mode and the last change to it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ModeState {
+    pub mode: Mode,
+    /// Who changed the mode: an operator, or `health_check`
+    pub changed_by: String,
+    pub reason: String,
+    /// Unix time in seconds, 0 if the mode was never changed
+    pub changed_at: u64,
+}
+
+impl fmt::Display for ModeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mode)?;
+        if self.changed_at > 0 {
+            write!(
+                f,
+                " since {} by {}: {}",
+                self.changed_at, self.changed_by, self.reason
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Who health check transitions are recorded as changed by
+pub const HEALTH_CHECK: &str = "health_check";
+
+pub struct ModeStore {
+    conn: RusqliteConnection,
+}
+
+impl ModeStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?)
+    }
+
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: RusqliteConnection) -> Result<Self, Error> {
+        conn.execute_batch(Self::sql_schema())?;
+        Ok(Self { conn })
+    }
+
+    /// The current mode, `bootstrap` if it was never set
+    pub fn get(&self) -> Result<ModeState, Error> {
+        let row = self
+            .conn
+            .query_row(Self::sql_select_mode(), rusqlite::params![], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })
+            .optional()?;
+        let Some((mode, changed_by, reason, changed_at)) = row else {
+            return Ok(ModeState::default());
+        };
+        Ok(ModeState {
+            mode: mode.parse()?,
+            changed_by,
+            reason,
+            changed_at: changed_at as u64,
+        })
+    }
+
+    /// Change the mode to `mode`, unless it already is
+    pub fn set(&self, mode: Mode, changed_by: &str, reason: &str) -> Result<ModeState, Error> {
+        let current = self.get()?;
+        if current.mode == mode {
+            return Ok(current);
+        }
+        let state = ModeState {
+            mode,
+            changed_by: changed_by.to_string(),
+            reason: reason.to_string(),
+            changed_at: now(),
+        };
+        self.conn.execute(
+            Self::sql_set_mode(),
+            rusqlite::params![
+                mode.as_str(),
+                state.changed_by,
+                state.reason,
+                state.changed_at as i64
+            ],
+        )?;
+        Ok(state)
+    }
+
+    /// Stop signing until [`ModeStore::resume`] is called
+    pub fn pause(&self, author: &str, reason: &str) -> Result<ModeState, Error> {
+        self.set(Mode::Paused, author, reason)
+    }
+
+    /// Leave `paused` for `bootstrap`, which the next health check moves on from
+    pub fn resume(&self, author: &str, reason: &str) -> Result<ModeState, Error> {
+        let current = self.get()?.mode;
+        if current != Mode::Paused {
+            return Err(Error::NotPaused(current));
+        }
+        self.set(Mode::Bootstrap, author, reason)
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS operational_mode (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            mode TEXT NOT NULL,
+            changed_by TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            changed_at INTEGER NOT NULL
+        );
+        "#
+    }
+
+    const fn sql_select_mode() -> &'static str {
+        r#"
+        SELECT mode, changed_by, reason, changed_at FROM operational_mode WHERE id=0
+        "#
+    }
+
+    const fn sql_set_mode() -> &'static str {
+        r#"
+        INSERT OR REPLACE INTO operational_mode (id, mode, changed_by, reason, changed_at)
+        VALUES (0, ?1, ?2, ?3, ?4)
+        "#
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_checks_move_between_every_mode_but_paused() {
+        assert_eq!(Mode::Bootstrap.checked(false, true), Mode::Bootstrap);
+        assert_eq!(Mode::Bootstrap.checked(true, true), Mode::Normal);
+        assert_eq!(Mode::Normal.checked(true, false), Mode::Degraded);
+        assert_eq!(Mode::Degraded.checked(true, true), Mode::Normal);
+        // a restarted coordinator has no key until it runs DKG again
+        assert_eq!(Mode::Degraded.checked(false, false), Mode::Bootstrap);
+        assert_eq!(Mode::Paused.checked(true, true), Mode::Paused);
+        assert_eq!(Mode::Paused.checked(false, false), Mode::Paused);
+
+        assert!(!Mode::Bootstrap.processes_queue());
+        assert!(Mode::Degraded.processes_queue());
+        assert!(!Mode::Degraded.sweeps_fees());
+        assert!(!Mode::Paused.processes_queue());
+    }
+
+    #[test]
+    fn a_pause_survives_a_reopen_until_resumed() {
+        let path =
+            std::env::temp_dir().join(format!("operational-mode-{}.sqlite", std::process::id()));
+        {
+            let modes = ModeStore::new(&path).unwrap();
+            assert_eq!(modes.get().unwrap(), ModeState::default());
+            modes.set(Mode::Normal, HEALTH_CHECK, "").unwrap();
+            modes.pause("alice", "bitcoind upgrade").unwrap();
+        }
+        let modes = ModeStore::new(&path).unwrap();
+        let paused = modes.get().unwrap();
+        assert_eq!(paused.mode, Mode::Paused);
+        assert_eq!(paused.changed_by, "alice");
+        assert_eq!(paused.reason, "bitcoind upgrade");
+        // setting the mode it is in changes nothing
+        assert_eq!(modes.pause("bob", "again").unwrap(), paused);
+
+        assert_eq!(modes.resume("alice", "done").unwrap().mode, Mode::Bootstrap);
+        assert!(matches!(
+            modes.resume("alice", "done"),
+            Err(Error::NotPaused(Mode::Bootstrap))
+        ));
+        drop(modes);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+//! Latency of confirmed peg ops, held against service level objectives.
+//!
+//! A peg-in's latency runs from the bitcoin block which included it to the confirmation of its
+//! sBTC mint, a peg-out's from the bitcoin block which included the request to the block which
+//! confirmed its fulfillment. The latency of every confirmed op is written to a `peg_latencies`
+//! table next to the peg queue and binned into a histogram per op kind. An op slower than its
+//! kind's `slo_secs` counts as a breach, for which the coordinator opens an `slo_breach` incident.
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::peg_queue::{OpId, OpKind};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Rusqlite Error: {0}")]
+    RusqliteError(#[from] RusqliteError),
+}
+
+/// A `[peg_latency]` config table
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct PegLatencyConfig {
+    /// Most seconds a peg-in may take before it counts as a breach
+    #[serde(default)]
+    pub peg_in_slo_secs: Option<u64>,
+    /// Most seconds a peg-out may take before it counts as a breach
+    #[serde(default)]
+    pub peg_out_slo_secs: Option<u64>,
+    /// Upper bounds of the histogram buckets, ascending. Slower ops land in a last, unbounded one
+    #[serde(default = "default_buckets_secs")]
+    pub buckets_secs: Vec<u64>,
+}
+
+fn default_buckets_secs() -> Vec<u64> {
+    vec![600, 1_800, 3_600, 7_200, 14_400, 43_200, 86_400]
+}
+
+impl Default for PegLatencyConfig {
+    fn default() -> Self {
+        Self {
+            peg_in_slo_secs: None,
+            peg_out_slo_secs: None,
+            buckets_secs: default_buckets_secs(),
+        }
+    }
+}
+
+impl PegLatencyConfig {
+    /// The SLO of `kind`, if one is configured
+    pub fn slo_secs(&self, kind: OpKind) -> Option<u64> {
+        match kind {
+            OpKind::PegIn => self.peg_in_slo_secs,
+            OpKind::PegOut => self.peg_out_slo_secs,
+        }
+    }
+}
+
+/// Latencies of the confirmed ops of one kind
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Histogram {
+    /// Upper bound of each bucket in seconds, the last bucket has none
+    pub bounds: Vec<u64>,
+    /// Ops in each bucket, one more than there are bounds
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum_secs: u64,
+    pub max_secs: u64,
+    /// Ops slower than the SLO they were confirmed under
+    pub breaches: u64,
+    pub slo_secs: Option<u64>,
+}
+
+impl Histogram {
+    fn new(bounds: &[u64], slo_secs: Option<u64>) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            slo_secs,
+            ..Default::default()
+        }
+    }
+
+    fn observe(&mut self, latency_secs: u64, breached: bool) {
+        let bucket = self.bounds.partition_point(|bound| *bound < latency_secs);
+        self.counts[bucket] += 1;
+        self.count += 1;
+        self.sum_secs = self.sum_secs.saturating_add(latency_secs);
+        self.max_secs = self.max_secs.max(latency_secs);
+        self.breaches += u64::from(breached);
+    }
+}
+
+/// Latency histograms of both op kinds
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct LatencyStats {
+    pub peg_in: Histogram,
+    pub peg_out: Histogram,
+}
+
+pub struct PegLatency {
+    conn: RusqliteConnection,
+    config: PegLatencyConfig,
+}
+
+impl PegLatency {
+    pub fn new<P: AsRef<Path>>(path: P, config: PegLatencyConfig) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open(path)?, config)
+    }
+
+    pub fn in_memory(config: PegLatencyConfig) -> Result<Self, Error> {
+        Self::from_connection(RusqliteConnection::open_in_memory()?, config)
+    }
+
+    fn from_connection(conn: RusqliteConnection, config: PegLatencyConfig) -> Result<Self, Error> {
+        conn.execute_batch(Self::sql_schema())?;
+        Ok(Self { conn, config })
+    }
+
+    pub fn config(&self) -> &PegLatencyConfig {
+        &self.config
+    }
+
+    /// Record that the op `op_id` of `kind` was confirmed `latency_secs` after its burn block,
+    /// returning whether that breaches its SLO. Recording an op twice has no effect.
+    pub fn record(&self, op_id: &OpId, kind: OpKind, latency_secs: u64) -> Result<bool, Error> {
+        let breached = self
+            .config
+            .slo_secs(kind)
+            .is_some_and(|slo| latency_secs > slo);
+        let inserted = self.conn.execute(
+            Self::sql_insert_latency(),
+            rusqlite::params![
+                op_id.to_string(),
+                kind_str(kind),
+                latency_secs as i64,
+                breached,
+                now() as i64
+            ],
+        )?;
+        Ok(breached && inserted > 0)
+    }
+
+    /// Histograms of every latency recorded, binned into the configured buckets
+    pub fn stats(&self) -> Result<LatencyStats, Error> {
+        let bounds = &self.config.buckets_secs;
+        let mut stats = LatencyStats {
+            peg_in: Histogram::new(bounds, self.config.peg_in_slo_secs),
+            peg_out: Histogram::new(bounds, self.config.peg_out_slo_secs),
+        };
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_latencies())?
+            .query_map(rusqlite::params![], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (kind, latency_secs, breached) in rows {
+            let histogram = if kind == kind_str(OpKind::PegIn) {
+                &mut stats.peg_in
+            } else {
+                &mut stats.peg_out
+            };
+            histogram.observe(latency_secs as u64, breached);
+        }
+        Ok(stats)
+    }
+
+    /// Last bitcoin block searched for fulfillments, if any
+    pub fn scanned_height(&self) -> Result<Option<u64>, Error> {
+        Ok(self
+            .conn
+            .query_row(
+                Self::sql_select_scanned_height(),
+                rusqlite::params![],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?
+            .map(|height| height as u64))
+    }
+
+    pub fn set_scanned_height(&self, height: u64) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_set_scanned_height(),
+            rusqlite::params![height as i64],
+        )?;
+        Ok(())
+    }
+
+    const fn sql_schema() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS peg_latencies (
+            op_id TEXT PRIMARY KEY,
+            kind TEXT NOT NULL,
+            latency_secs INTEGER NOT NULL,
+            breached INTEGER NOT NULL,
+            observed_at INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS peg_latency_scan (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            height INTEGER NOT NULL
+        );
+        "#
+    }
+
+    const fn sql_insert_latency() -> &'static str {
+        r#"
+        INSERT OR IGNORE INTO peg_latencies (op_id, kind, latency_secs, breached, observed_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#
+    }
+
+    const fn sql_select_latencies() -> &'static str {
+        r#"
+        SELECT kind, latency_secs, breached FROM peg_latencies
+        "#
+    }
+
+    const fn sql_select_scanned_height() -> &'static str {
+        r#"
+        SELECT height FROM peg_latency_scan WHERE id=0
+        "#
+    }
+
+    const fn sql_set_scanned_height() -> &'static str {
+        r#"
+        INSERT OR REPLACE INTO peg_latency_scan (id, height) VALUES (0, ?1)
+        "#
+    }
+}
+
+fn kind_str(kind: OpKind) -> &'static str {
+    match kind {
+        OpKind::PegIn => "peg_in",
+        OpKind::PegOut => "peg_out",
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PegLatencyConfig {
+        PegLatencyConfig {
+            peg_in_slo_secs: None,
+            peg_out_slo_secs: Some(3_600),
+            buckets_secs: vec![600, 3_600],
+        }
+    }
+
+    #[test]
+    fn latencies_are_binned_per_kind_and_held_to_their_slo() {
+        let latency = PegLatency::in_memory(config()).unwrap();
+        assert!(!latency
+            .record(&OpId::from_u128(1), OpKind::PegOut, 600)
+            .unwrap());
+        assert!(!latency
+            .record(&OpId::from_u128(2), OpKind::PegOut, 3_600)
+            .unwrap());
+        assert!(latency
+            .record(&OpId::from_u128(3), OpKind::PegOut, 3_601)
+            .unwrap());
+        // an op confirmed twice is only counted, and only breaches, once
+        assert!(!latency
+            .record(&OpId::from_u128(3), OpKind::PegOut, 3_601)
+            .unwrap());
+        // peg-ins have no SLO here
+        assert!(!latency
+            .record(&OpId::from_u128(4), OpKind::PegIn, 90_000)
+            .unwrap());
+
+        let stats = latency.stats().unwrap();
+        assert_eq!(
+            stats.peg_out,
+            Histogram {
+                bounds: vec![600, 3_600],
+                counts: vec![1, 1, 1],
+                count: 3,
+                sum_secs: 7_801,
+                max_secs: 3_601,
+                breaches: 1,
+                slo_secs: Some(3_600),
+            }
+        );
+        assert_eq!(stats.peg_in.counts, vec![0, 0, 1]);
+        assert_eq!(stats.peg_in.breaches, 0);
+    }
+
+    #[test]
+    fn scanned_height_is_kept() {
+        let latency = PegLatency::in_memory(config()).unwrap();
+        assert_eq!(latency.scanned_height().unwrap(), None);
+        latency.set_scanned_height(10).unwrap();
+        latency.set_scanned_height(12).unwrap();
+        assert_eq!(latency.scanned_height().unwrap(), Some(12));
+    }
+}
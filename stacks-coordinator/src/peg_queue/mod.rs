@@ -3,9 +3,17 @@ use blockstack_lib::types::chainstate::BurnchainHeaderHash;
 
 use crate::stacks_node;
 use crate::stacks_node::Error as StacksNodeError;
+mod op_trace;
+mod peg_out_policy;
+mod priority;
 mod sqlite_peg_queue;
+mod stored_op;
 
-pub use sqlite_peg_queue::{Error as SqlitePegQueueError, SqlitePegQueue};
+pub use op_trace::{OpEvent, OpId, Stage, TracedOp};
+pub use peg_out_policy::{PegOutPolicy, RejectedPegOut};
+pub use priority::{OpClass, OpKind, PriorityPolicy, QueueDepths};
+pub use sqlite_peg_queue::{Error as SqlitePegQueueError, Rebuilt, SqlitePegQueue};
+pub use stored_op::{Error as StoredOpError, VERSION as STORED_OP_VERSION};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -17,13 +25,49 @@ pub enum Error {
 
 pub trait PegQueue {
     fn sbtc_op(&self) -> Result<Option<SbtcOp>, Error>;
+    /// The ops to process this tick, as chosen by the priority policy
+    fn sbtc_ops_for_tick(&self) -> Result<Vec<SbtcOp>, Error>;
     fn poll<N: stacks_node::StacksNode>(&self, stacks_node: &N) -> Result<(), Error>;
+    /// Queue `op`, returning the id it was given when it first entered the queue
+    fn submit(&self, op: SbtcOp) -> Result<OpId, Error>;
 
     fn acknowledge(&self, txid: &Txid, burn_header_hash: &BurnchainHeaderHash)
         -> Result<(), Error>;
+
+    fn priority_policy(&self) -> &PriorityPolicy;
+    /// Number of ops waiting to be processed in each class
+    fn queue_depths(&self) -> Result<QueueDepths, Error>;
+    /// Peg-out requests rejected by the peg-out policy, oldest first
+    fn rejected_peg_outs(&self) -> Result<Vec<RejectedPegOut>, Error>;
+    /// Highest burn block whose peg ops have been read, `None` before any or if it cannot be read
+    fn observed_block_height(&self) -> Option<u64>;
+
+    /// Id of `op`, if it is queued
+    fn op_id(&self, op: &SbtcOp) -> Result<Option<OpId>, Error>;
+    /// Record that the op `op_id` reached `stage`
+    fn record_stage(&self, op_id: &OpId, stage: Stage, detail: &str) -> Result<(), Error>;
+    /// The op `op_id` with every stage it reached, `None` if no op has that id
+    fn trace(&self, op_id: &OpId) -> Result<Option<TracedOp>, Error>;
+    /// Ops being processed and not yet acknowledged, with their stages, oldest first
+    fn pending(&self) -> Result<Vec<TracedOp>, Error>;
+
+    /// Hold the pending op `op_id` until the peg wallet can pay it, recording why in `detail`
+    fn await_funds(&self, op_id: &OpId, detail: &str) -> Result<(), Error>;
+    /// Ops held until the peg wallet can pay them, with their stages, oldest first
+    fn awaiting_funds(&self) -> Result<Vec<TracedOp>, Error>;
+    /// Release the held op `op_id` to be processed again
+    fn resume(&self, op_id: &OpId, detail: &str) -> Result<(), Error>;
+
+    /// Hold the op `op_id`, new or awaiting funds, out of processing until an operator releases
+    /// it, recording why in `detail`
+    fn hold(&self, op_id: &OpId, detail: &str) -> Result<(), Error>;
+    /// Ops held by an operator, with their stages, oldest first
+    fn held(&self) -> Result<Vec<TracedOp>, Error>;
+    /// Queue the op `op_id` held by an operator to be processed again
+    fn release(&self, op_id: &OpId, detail: &str) -> Result<(), Error>;
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum SbtcOp {
     PegIn(stacks_node::PegInOp),
     PegOutRequest(stacks_node::PegOutRequestOp),
@@ -43,4 +87,25 @@ impl SbtcOp {
             _ => None,
         }
     }
+
+    pub fn txid(&self) -> &Txid {
+        match self {
+            Self::PegIn(op) => &op.txid,
+            Self::PegOutRequest(op) => &op.txid,
+        }
+    }
+
+    pub fn burn_header_hash(&self) -> &BurnchainHeaderHash {
+        match self {
+            Self::PegIn(op) => &op.burn_header_hash,
+            Self::PegOutRequest(op) => &op.burn_header_hash,
+        }
+    }
+
+    pub fn block_height(&self) -> u64 {
+        match self {
+            Self::PegIn(op) => op.block_height,
+            Self::PegOutRequest(op) => op.block_height,
+        }
+    }
 }
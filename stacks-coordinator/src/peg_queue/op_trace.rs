@@ -0,0 +1,149 @@
+//! The lifecycle of one peg op, from the queue to its bitcoin fulfillment.
+//!
+//! Every op gets an [`OpId`] when it first enters the queue, which never changes however often
+//! the op is observed again. The queue and the coordinator record each [`Stage`] the op reaches
+//! under that id, and the coordinator processes the op inside a `peg_op{op_id=..}` tracing span,
+//! so its stacks transactions, FROST rounds and bitcoin broadcast are logged with the id too.
+//! `grep <op id>` over the logs, or `stacks-coordinator trace <op id>` over the database, tells
+//! the story of one op.
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::peg_queue::SbtcOp;
+
+/// Stable id of a queued op
+pub type OpId = uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Entered the queue
+    Queued,
+    /// Refused by the peg-out policy
+    Rejected,
+    /// Picked for processing by the priority policy
+    Scheduled,
+    /// Its stacks mint or burn transaction was built
+    StacksTxBuilt,
+    /// The sBTC contract had already minted or burned for it, so no transaction was built
+    AlreadyProcessed,
+    /// Held because the peg wallet cannot pay it yet
+    AwaitingFunds,
+    /// Released once the peg wallet could pay it again
+    FundsAvailable,
+    /// Held by an operator, not processed until released
+    Held,
+    /// Released by an operator, queued to be processed again
+    Released,
+    /// Its bitcoin transaction was threshold signed
+    Signed,
+    /// Its bitcoin transaction was broadcast
+    BitcoinBroadcast,
+    /// Its fulfillment fee was credited to the peg wallet
+    FeeRecorded,
+    /// Confirmed and acknowledged
+    Acknowledged,
+    /// Found already minted or burned by the sBTC contract when the database was rebuilt
+    Rebuilt,
+    /// Processing failed
+    Failed,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Rejected => "rejected",
+            Self::Scheduled => "scheduled",
+            Self::StacksTxBuilt => "stacks_tx_built",
+            Self::AlreadyProcessed => "already_processed",
+            Self::AwaitingFunds => "awaiting_funds",
+            Self::FundsAvailable => "funds_available",
+            Self::Held => "held",
+            Self::Released => "released",
+            Self::Signed => "signed",
+            Self::BitcoinBroadcast => "bitcoin_broadcast",
+            Self::FeeRecorded => "fee_recorded",
+            Self::Acknowledged => "acknowledged",
+            Self::Rebuilt => "rebuilt",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "queued" => Self::Queued,
+            "rejected" => Self::Rejected,
+            "scheduled" => Self::Scheduled,
+            "stacks_tx_built" => Self::StacksTxBuilt,
+            "already_processed" => Self::AlreadyProcessed,
+            "awaiting_funds" => Self::AwaitingFunds,
+            "funds_available" => Self::FundsAvailable,
+            "held" => Self::Held,
+            "released" => Self::Released,
+            "signed" => Self::Signed,
+            "bitcoin_broadcast" => Self::BitcoinBroadcast,
+            "fee_recorded" => Self::FeeRecorded,
+            "acknowledged" => Self::Acknowledged,
+            "rebuilt" => Self::Rebuilt,
+            "failed" => Self::Failed,
+            other => return Err(other.to_owned()),
+        })
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A stage an op reached
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OpEvent {
+    pub stage: Stage,
+    /// Unix time in seconds
+    pub recorded_at: u64,
+    /// e.g. the id of the transaction the stage produced, empty if there is nothing to add
+    pub detail: String,
+}
+
+/// A queued op with the stages it went through, oldest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct TracedOp {
+    pub op_id: OpId,
+    pub op: SbtcOp,
+    /// Its status in the queue, e.g. "pending"
+    pub status: String,
+    pub events: Vec<OpEvent>,
+}
+
+impl fmt::Display for TracedOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match &self.op {
+            SbtcOp::PegIn(_) => "peg-in",
+            SbtcOp::PegOutRequest(_) => "peg-out",
+        };
+        write!(
+            f,
+            "op {} {} {} block_height={} status={}",
+            self.op_id,
+            kind,
+            self.op.txid(),
+            self.op.block_height(),
+            self.status
+        )?;
+        for event in &self.events {
+            write!(f, "\n    {} {}", event.recorded_at, event.stage)?;
+            if !event.detail.is_empty() {
+                write!(f, " {}", event.detail)?;
+            }
+        }
+        Ok(())
+    }
+}
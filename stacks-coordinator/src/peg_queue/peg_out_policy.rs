@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::stacks_node::PegOutRequestOp;
+
+/// Peg-outs the coordinator is willing to fulfill.
+///
+/// Fulfilling a peg-out costs a bitcoin transaction fee, so dust requests, or requests paying
+/// too little to cover it, would be fulfilled at a loss. Requests below either minimum are
+/// rejected when they enter the peg queue. The default accepts every request.
+/// Deserializes from a `[peg_out_policy]` table, for example
+/// ```toml
+/// [peg_out_policy]
+/// min_amount = 10000
+/// min_fulfillment_fee = 2000
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PegOutPolicy {
+    /// Smallest peg-out amount fulfilled, in sats
+    pub min_amount: u64,
+    /// Smallest fulfillment fee accepted, in sats
+    pub min_fulfillment_fee: u64,
+}
+
+impl PegOutPolicy {
+    /// Why `op` is rejected, if it is
+    pub fn check(&self, op: &PegOutRequestOp) -> Option<String> {
+        if op.amount < self.min_amount {
+            Some(format!(
+                "amount of {} sats is below the minimum of {}",
+                op.amount, self.min_amount
+            ))
+        } else if op.fulfillment_fee < self.min_fulfillment_fee {
+            Some(format!(
+                "fulfillment fee of {} sats is below the minimum of {}",
+                op.fulfillment_fee, self.min_fulfillment_fee
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// A peg-out request the coordinator will not fulfill
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RejectedPegOut {
+    /// Hex txid of the request
+    pub txid: String,
+    pub block_height: u64,
+    pub amount: u64,
+    pub fulfillment_fee: u64,
+    pub reason: String,
+}
@@ -0,0 +1,251 @@
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::peg_queue::SbtcOp;
+
+/// Type of a queued op
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpKind {
+    PegIn,
+    PegOut,
+}
+
+/// Scheduling class of a queued op: its type and amount band
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OpClass {
+    pub kind: OpKind,
+    /// Index into the policy's amount bands, 0 being the smallest amounts
+    pub band: usize,
+}
+
+/// Number of queued ops in each class
+pub type QueueDepths = BTreeMap<OpClass, usize>;
+
+/// Order in which queued ops are processed.
+///
+/// The default processes ops oldest first, one peg-in and one peg-out per tick.
+/// Deserializes from a `[peg_queue_priority]` table, for example
+/// ```toml
+/// [peg_queue_priority]
+/// amount_bands = [100000, 10000000]
+/// peg_in_quota = 2
+/// peg_out_quota = 1
+/// max_age_blocks = 6
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PriorityPolicy {
+    /// Ascending amount thresholds in sats. Ops in higher bands are processed first
+    pub amount_bands: Vec<u64>,
+    /// Peg-ins processed per tick, at least one
+    pub peg_in_quota: usize,
+    /// Peg-outs processed per tick, at least one
+    pub peg_out_quota: usize,
+    /// Ops which have waited this many blocks are processed before all others, oldest first
+    pub max_age_blocks: Option<u64>,
+}
+
+impl Default for PriorityPolicy {
+    fn default() -> Self {
+        Self {
+            amount_bands: vec![],
+            peg_in_quota: 1,
+            peg_out_quota: 1,
+            max_age_blocks: None,
+        }
+    }
+}
+
+impl PriorityPolicy {
+    pub fn class(&self, op: &SbtcOp) -> OpClass {
+        let (kind, amount) = match op {
+            SbtcOp::PegIn(op) => (OpKind::PegIn, op.amount),
+            SbtcOp::PegOutRequest(op) => (OpKind::PegOut, op.amount),
+        };
+        let band = self
+            .amount_bands
+            .iter()
+            .take_while(|threshold| amount >= **threshold)
+            .count();
+        OpClass { kind, band }
+    }
+
+    pub fn depths<'a>(&self, ops: impl IntoIterator<Item = &'a SbtcOp>) -> QueueDepths {
+        let mut depths = QueueDepths::new();
+        for op in ops {
+            *depths.entry(self.class(op)).or_default() += 1;
+        }
+        depths
+    }
+
+    /// Indices of `ops`, given oldest first, in the order they should be processed
+    /// when the chain is at `tip_height`
+    pub fn order<O: Borrow<SbtcOp>>(&self, ops: &[O], tip_height: u64) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..ops.len()).collect();
+        order.sort_by_key(|i| {
+            let op = ops[*i].borrow();
+            if self.is_starved(op, tip_height) {
+                (false, Reverse(0), *i)
+            } else {
+                (true, Reverse(self.class(op).band), *i)
+            }
+        });
+        order
+    }
+
+    /// Indices of the `ops` to process this tick, in order, within the per-type quotas
+    pub fn schedule<O: Borrow<SbtcOp>>(&self, ops: &[O], tip_height: u64) -> Vec<usize> {
+        let mut peg_ins = self.peg_in_quota.max(1);
+        let mut peg_outs = self.peg_out_quota.max(1);
+        self.order(ops, tip_height)
+            .into_iter()
+            .filter(|i| {
+                let remaining = match ops[*i].borrow() {
+                    SbtcOp::PegIn(_) => &mut peg_ins,
+                    SbtcOp::PegOutRequest(_) => &mut peg_outs,
+                };
+                if *remaining == 0 {
+                    return false;
+                }
+                *remaining -= 1;
+                true
+            })
+            .collect()
+    }
+
+    fn is_starved(&self, op: &SbtcOp, tip_height: u64) -> bool {
+        matches!(self.max_age_blocks, Some(max_age) if tip_height.saturating_sub(op.block_height()) >= max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blockstack_lib::{
+        burnchains::Txid,
+        chainstate::stacks::address::PoxAddress,
+        types::chainstate::{BurnchainHeaderHash, StacksAddress},
+        util::{hash::Hash160, secp256k1::MessageSignature},
+    };
+
+    use super::*;
+    use crate::stacks_node::{PegInOp, PegOutRequestOp};
+
+    fn peg_in(block_height: u64, amount: u64) -> SbtcOp {
+        SbtcOp::PegIn(PegInOp {
+            recipient: StacksAddress::new(26, Hash160([0; 20])).into(),
+            peg_wallet_address: PoxAddress::Standard(StacksAddress::new(0, Hash160([0; 20])), None),
+            amount,
+            memo: vec![],
+            txid: Txid([0; 32]),
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+            block_height,
+            vtxindex: 0,
+        })
+    }
+
+    fn peg_out(block_height: u64, amount: u64) -> SbtcOp {
+        SbtcOp::PegOutRequest(PegOutRequestOp {
+            recipient: PoxAddress::Standard(StacksAddress::new(26, Hash160([0; 20])), None),
+            peg_wallet_address: PoxAddress::Standard(StacksAddress::new(0, Hash160([0; 20])), None),
+            amount,
+            fulfillment_fee: 0,
+            signature: MessageSignature([0; 65]),
+            memo: vec![],
+            txid: Txid([0; 32]),
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+            block_height,
+            vtxindex: 0,
+        })
+    }
+
+    fn banded() -> PriorityPolicy {
+        PriorityPolicy {
+            amount_bands: vec![100, 10_000],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_policy_is_fifo() {
+        let ops = vec![peg_in(1, 5), peg_out(1, 50_000), peg_in(2, 50_000)];
+        assert_eq!(PriorityPolicy::default().order(&ops, 2), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn higher_bands_go_first() {
+        let policy = banded();
+        let ops = vec![peg_out(1, 5), peg_out(2, 500), peg_out(3, 50_000)];
+        assert_eq!(policy.class(&ops[0]).band, 0);
+        assert_eq!(policy.class(&ops[1]).band, 1);
+        assert_eq!(policy.class(&ops[2]).band, 2);
+        assert_eq!(policy.order(&ops, 3), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn starved_ops_go_first() {
+        let policy = PriorityPolicy {
+            max_age_blocks: Some(6),
+            ..banded()
+        };
+        let ops = vec![peg_out(1, 5), peg_out(5, 50_000), peg_out(2, 5)];
+        assert_eq!(policy.order(&ops, 6), vec![1, 0, 2]);
+        assert_eq!(policy.order(&ops, 8), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn schedule_respects_quotas() {
+        let policy = PriorityPolicy {
+            peg_in_quota: 2,
+            peg_out_quota: 1,
+            ..Default::default()
+        };
+        let ops = vec![
+            peg_out(1, 5),
+            peg_out(1, 5),
+            peg_in(1, 5),
+            peg_in(2, 5),
+            peg_in(3, 5),
+        ];
+        assert_eq!(policy.schedule(&ops, 3), vec![0, 2, 3]);
+    }
+
+    #[test]
+    fn depths_count_each_class() {
+        let policy = banded();
+        let ops = vec![peg_in(1, 5), peg_in(1, 6), peg_out(1, 50_000)];
+        let depths = policy.depths(&ops);
+        assert_eq!(
+            depths[&OpClass {
+                kind: OpKind::PegIn,
+                band: 0
+            }],
+            2
+        );
+        assert_eq!(
+            depths[&OpClass {
+                kind: OpKind::PegOut,
+                band: 2
+            }],
+            1
+        );
+    }
+
+    #[test]
+    fn deserialize_from_toml() {
+        let policy: PriorityPolicy = toml::from_str(
+            r#"
+            amount_bands = [100000]
+            peg_out_quota = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.amount_bands, vec![100_000]);
+        assert_eq!(policy.peg_in_quota, 1);
+        assert_eq!(policy.peg_out_quota, 3);
+        assert_eq!(policy.max_age_blocks, None);
+    }
+}
@@ -1,13 +1,22 @@
-use rusqlite::{Connection as RusqliteConnection, Error as RusqliteError, Row as SqliteRow};
+use rusqlite::{
+    Connection as RusqliteConnection, Error as RusqliteError, OptionalExtension, Row as SqliteRow,
+};
+use std::fmt;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use blockstack_lib::burnchains::Txid;
 use blockstack_lib::types::chainstate::BurnchainHeaderHash;
 use blockstack_lib::util::HexError;
 
 use crate::config::Config;
-use crate::peg_queue::{Error as PegQueueError, PegQueue, SbtcOp};
+use crate::peg_queue::stored_op::{self, Error as StoredOpError};
+use crate::peg_queue::{
+    Error as PegQueueError, OpEvent, OpId, PegOutPolicy, PegQueue, PriorityPolicy, QueueDepths,
+    RejectedPegOut, SbtcOp, Stage, TracedOp,
+};
+use crate::stacks_node::clarity;
 use crate::stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp, StacksNode};
 
 use tracing::{debug, info};
@@ -16,14 +25,20 @@ use tracing::{debug, info};
 pub enum Error {
     #[error("Rusqlite Error: {0}")]
     RusqliteError(#[from] RusqliteError),
-    #[error("JSON serialization failure: {0}")]
-    JsonError(#[from] serde_json::Error),
+    #[error("Stored Op Error: {0}")]
+    StoredOpError(#[from] StoredOpError),
     #[error("Hex codec error: {0}")]
     HexError(#[from] HexError),
     #[error("Did not recognize status: {0}")]
     InvalidStatusError(String),
+    #[error("Did not recognize op stage: {0}")]
+    InvalidStageError(String),
+    #[error("Invalid op id: {0}")]
+    InvalidOpId(#[from] uuid::Error),
     #[error("Entry does not exist")]
     EntryDoesNotExist,
+    #[error("Op {0} is {1}, not {2}")]
+    UnexpectedStatus(OpId, &'static str, &'static str),
     #[error("Missing Start Block Height")]
     MissingStartBlockHeight,
 }
@@ -35,9 +50,34 @@ impl From<Error> for rusqlite::Error {
     }
 }
 
+/// What [`SqlitePegQueue::rebuild`] restored
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rebuilt {
+    pub from_height: u64,
+    pub to_height: u64,
+    /// Ops the sBTC contract already minted or burned for, stored acknowledged
+    pub processed: usize,
+    /// Ops the contract has not processed, queued to be
+    pub queued: usize,
+    /// Ops the database still held
+    pub kept: usize,
+}
+
+impl fmt::Display for Rebuilt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block heights {} to {}: {} processed, {} queued, {} kept",
+            self.from_height, self.to_height, self.processed, self.queued, self.kept
+        )
+    }
+}
+
 pub struct SqlitePegQueue {
     conn: rusqlite::Connection,
     start_block_height: u64,
+    priority_policy: PriorityPolicy,
+    peg_out_policy: PegOutPolicy,
 }
 
 impl TryFrom<&Config> for SqlitePegQueue {
@@ -46,11 +86,14 @@ impl TryFrom<&Config> for SqlitePegQueue {
         let start_block_height = cfg
             .start_block_height
             .ok_or_else(|| Error::MissingStartBlockHeight)?;
-        if let Some(path) = &cfg.rusqlite_path {
+        let this = if let Some(path) = &cfg.rusqlite_path {
             Self::new(path, start_block_height)
         } else {
             Self::in_memory(start_block_height)
-        }
+        }?;
+        Ok(this
+            .with_priority_policy(cfg.peg_queue_priority.clone())
+            .with_peg_out_policy(cfg.peg_out_policy.clone()))
     }
 }
 impl SqlitePegQueue {
@@ -66,11 +109,104 @@ impl SqlitePegQueue {
         let this = Self {
             conn,
             start_block_height,
+            priority_policy: PriorityPolicy::default(),
+            peg_out_policy: PegOutPolicy::default(),
         };
         this.conn.execute(Self::sql_schema(), rusqlite::params![])?;
+        this.conn
+            .execute(Self::sql_schema_rejections(), rusqlite::params![])?;
+        // queues written before ops were stored in a versioned layout
+        let has_op_version: bool =
+            this.conn
+                .query_row(Self::sql_has_op_version(), rusqlite::params![], |row| {
+                    row.get(0)
+                })?;
+        if !has_op_version {
+            this.conn
+                .execute(Self::sql_add_op_version(), rusqlite::params![])?;
+        }
+        // and before ops had ids
+        let has_op_id: bool =
+            this.conn
+                .query_row(Self::sql_has_op_id(), rusqlite::params![], |row| row.get(0))?;
+        if !has_op_id {
+            this.conn
+                .execute(Self::sql_add_op_id(), rusqlite::params![])?;
+        }
+        this.conn
+            .execute(Self::sql_schema_events(), rusqlite::params![])?;
+        this.migrate()?;
+        this.assign_op_ids()?;
         Ok(this)
     }
 
+    /// Give the ops queued before ops had ids one
+    fn assign_op_ids(&self) -> Result<(), Error> {
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_without_op_id())?
+            .query_map(rusqlite::params![], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        for (txid, burn_header_hash) in rows {
+            tx.execute(
+                Self::sql_set_op_id(),
+                rusqlite::params![txid, burn_header_hash, OpId::new_v4().to_string()],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Rewrite the ops stored in older layouts in the current one
+    fn migrate(&self) -> Result<(), Error> {
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_outdated())?
+            .query_map(rusqlite::params![stored_op::VERSION], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, u32>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "Migrating {} queued ops to layout version {}",
+            rows.len(),
+            stored_op::VERSION
+        );
+        let tx = self.conn.unchecked_transaction()?;
+        for (txid, burn_header_hash, version, op) in rows {
+            let op = stored_op::encode(&stored_op::decode(version, &op)?)?;
+            tx.execute(
+                Self::sql_update_op(),
+                rusqlite::params![txid, burn_header_hash, op, stored_op::VERSION],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn with_priority_policy(mut self, priority_policy: PriorityPolicy) -> Self {
+        self.priority_policy = priority_policy;
+        self
+    }
+
+    pub fn with_peg_out_policy(mut self, peg_out_policy: PegOutPolicy) -> Self {
+        self.peg_out_policy = peg_out_policy;
+        self
+    }
+
     fn poll_peg_in_ops<N: StacksNode>(
         &self,
         stacks_node: &N,
@@ -83,8 +219,7 @@ impl SqlitePegQueue {
             Err(e) => return Err(PegQueueError::from(e)),
             Ok(peg_in_ops) => {
                 for peg_in_op in peg_in_ops {
-                    let entry = Entry::from(peg_in_op);
-                    self.insert(&entry)?;
+                    self.admit(Entry::from(peg_in_op))?;
                 }
             }
         }
@@ -103,13 +238,209 @@ impl SqlitePegQueue {
             Err(e) => return Err(PegQueueError::from(e)),
             Ok(peg_out_request_ops) => {
                 for peg_out_request_op in peg_out_request_ops {
-                    let entry = Entry::from(peg_out_request_op);
+                    self.admit(Entry::from(peg_out_request_op))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuild the history of a lost or corrupted database from chain state: read the peg ops
+    /// of every burn block from `from_height` to the tip, and ask the sBTC contract
+    /// `contract_address.contract_name` whether it already minted or burned for each. Those it
+    /// did are stored acknowledged, so the coordinator does not process them again; the others
+    /// are queued as if newly observed. Ops the database still holds are left as they are.
+    pub fn rebuild<N: StacksNode>(
+        &self,
+        stacks_node: &N,
+        contract_address: &str,
+        contract_name: &str,
+        from_height: u64,
+    ) -> Result<Rebuilt, PegQueueError> {
+        let to_height = stacks_node.burn_block_height()?;
+        info!(
+            "Rebuilding peg op history for block heights {} to {}",
+            from_height, to_height
+        );
+        let mut rebuilt = Rebuilt {
+            from_height,
+            to_height,
+            ..Rebuilt::default()
+        };
+        for block_height in from_height..=to_height {
+            let mut entries: Vec<Entry> = match stacks_node.get_peg_in_ops(block_height) {
+                Err(StacksNodeError::UnknownBlockHeight(height)) => {
+                    debug!("Failed to find burn block height {}", height);
+                    continue;
+                }
+                result => result?.into_iter().map(Entry::from).collect(),
+            };
+            entries.extend(
+                stacks_node
+                    .get_peg_out_request_ops(block_height)?
+                    .into_iter()
+                    .map(Entry::from),
+            );
+            for mut entry in entries {
+                if self
+                    .find_op_id(&entry.txid, &entry.burn_header_hash)?
+                    .is_some()
+                {
+                    rebuilt.kept += 1;
+                    continue;
+                }
+                let (function, detail) = match &entry.op {
+                    SbtcOp::PegIn(_) => ("is-peg-in-processed", "mint"),
+                    SbtcOp::PegOutRequest(_) => ("is-peg-out-processed", "burn"),
+                };
+                let result = stacks_node.call_read_only(
+                    contract_address,
+                    contract_name,
+                    function,
+                    &[clarity::string_ascii(&entry.txid.to_string())?],
+                )?;
+                if clarity::bool_value(&result)? {
+                    entry.status = Status::Acknowledged;
                     self.insert(&entry)?;
+                    self.record(&entry.op_id, Stage::Rebuilt, detail)?;
+                    info!("Restored {} as processed op {}", entry.txid, entry.op_id);
+                    rebuilt.processed += 1;
+                } else {
+                    self.admit(entry)?;
+                    rebuilt.queued += 1;
                 }
             }
         }
+        Ok(rebuilt)
+    }
+
+    /// Queue a newly observed op, rejecting peg-outs the peg-out policy does not accept.
+    /// An op observed again keeps the id it was first queued with.
+    fn admit(&self, mut entry: Entry) -> Result<OpId, Error> {
+        let queued = self.find_op_id(&entry.txid, &entry.burn_header_hash)?;
+        if let Some(op_id) = queued {
+            entry.op_id = op_id;
+        }
+        let mut rejection = None;
+        if let SbtcOp::PegOutRequest(op) = &entry.op {
+            if let Some(reason) = self.peg_out_policy.check(op) {
+                info!(
+                    "Rejecting peg-out request {} as op {}: {}",
+                    op.txid, entry.op_id, reason
+                );
+                entry.status = Status::Rejected;
+                self.conn.execute(
+                    Self::sql_insert_rejection(),
+                    rusqlite::params![entry.txid.to_hex(), entry.burn_header_hash.to_hex(), reason],
+                )?;
+                rejection = Some(reason);
+            }
+        }
+        self.insert(&entry)?;
+        if queued.is_none() {
+            match rejection {
+                Some(reason) => self.record(&entry.op_id, Stage::Rejected, &reason)?,
+                None => {
+                    info!("Queued {} as op {}", entry.txid, entry.op_id);
+                    self.record(&entry.op_id, Stage::Queued, "")?
+                }
+            }
+        }
+        Ok(entry.op_id)
+    }
+
+    fn find_op_id(
+        &self,
+        txid: &Txid,
+        burn_header_hash: &BurnchainHeaderHash,
+    ) -> Result<Option<OpId>, Error> {
+        let op_id = self
+            .conn
+            .query_row(
+                Self::sql_select_op_id(),
+                rusqlite::params![txid.to_hex(), burn_header_hash.to_hex()],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(op_id.map(|op_id| op_id.parse()).transpose()?)
+    }
+
+    fn record(&self, op_id: &OpId, stage: Stage, detail: &str) -> Result<(), Error> {
+        self.conn.execute(
+            Self::sql_insert_event(),
+            rusqlite::params![op_id.to_string(), now() as i64, stage.as_str(), detail],
+        )?;
         Ok(())
     }
+
+    fn get_by_op_id(&self, op_id: &OpId) -> Result<Option<Entry>, Error> {
+        Ok(self
+            .conn
+            .prepare(Self::sql_select_by_op_id())?
+            .query_row(rusqlite::params![op_id.to_string()], Entry::from_row)
+            .optional()?)
+    }
+
+    /// Move the op `op_id` from status `from` to `to`, recording that it reached `stage`
+    fn transition(
+        &self,
+        op_id: &OpId,
+        from: Status,
+        to: Status,
+        stage: Stage,
+        detail: &str,
+    ) -> Result<(), Error> {
+        let mut entry = self.get_by_op_id(op_id)?.ok_or(Error::EntryDoesNotExist)?;
+        if entry.status != from {
+            return Err(Error::UnexpectedStatus(
+                *op_id,
+                entry.status.as_str(),
+                from.as_str(),
+            ));
+        }
+        entry.status = to;
+        self.insert(&entry)?;
+        self.record(op_id, stage, detail)
+    }
+
+    /// The ops with `status` and their stages, oldest first
+    fn traced_with_status(&self, status: &Status) -> Result<Vec<TracedOp>, Error> {
+        let mut traced = vec![];
+        for entry in self.get_entries_with_status(status)? {
+            let events = self.events(&entry.op_id)?;
+            traced.push(TracedOp {
+                op_id: entry.op_id,
+                op: entry.op,
+                status: entry.status.as_str().to_string(),
+                events,
+            });
+        }
+        Ok(traced)
+    }
+
+    fn events(&self, op_id: &OpId) -> Result<Vec<OpEvent>, Error> {
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_events())?
+            .query_map(rusqlite::params![op_id.to_string()], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(stage, recorded_at, detail)| {
+                Ok(OpEvent {
+                    stage: stage.parse().map_err(Error::InvalidStageError)?,
+                    recorded_at: recorded_at as u64,
+                    detail,
+                })
+            })
+            .collect()
+    }
+
     fn insert(&self, entry: &Entry) -> Result<(), Error> {
         self.conn.execute(
             Self::sql_insert(),
@@ -117,21 +448,46 @@ impl SqlitePegQueue {
                 entry.txid.to_hex(),
                 entry.burn_header_hash.to_hex(),
                 entry.block_height as i64, // Stacks will crash before the coordinator if this is invalid
-                serde_json::to_string(&entry.op)?,
+                stored_op::encode(&entry.op)?,
                 entry.status.as_str(),
+                stored_op::VERSION,
+                entry.op_id.to_string(),
             ],
         )?;
 
         Ok(())
     }
 
-    fn get_single_entry_with_status(&self, status: &Status) -> Result<Option<Entry>, Error> {
+    fn get_entries_with_status(&self, status: &Status) -> Result<Vec<Entry>, Error> {
         Ok(self
             .conn
             .prepare(Self::sql_select_status())?
             .query_map(rusqlite::params![status.as_str()], Entry::from_row)?
-            .next()
-            .transpose()?)
+            .collect::<Result<Vec<Entry>, _>>()?)
+    }
+
+    /// Mark the new entries picked by `select` as pending and return their ops
+    fn take_new_entries(
+        &self,
+        select: impl FnOnce(&[&SbtcOp], u64) -> Vec<usize>,
+    ) -> Result<Vec<SbtcOp>, Error> {
+        let entries = self.get_entries_with_status(&Status::New)?;
+        let tip_height = self.max_observed_block_height().unwrap_or_default();
+        let selected = select(
+            &entries.iter().map(|entry| &entry.op).collect::<Vec<_>>(),
+            tip_height,
+        );
+        let mut entries: Vec<Option<Entry>> = entries.into_iter().map(Some).collect();
+        let mut ops = Vec::with_capacity(selected.len());
+        for i in selected {
+            if let Some(mut entry) = entries[i].take() {
+                entry.status = Status::Pending;
+                self.insert(&entry)?;
+                self.record(&entry.op_id, Stage::Scheduled, "")?;
+                ops.push(entry.op);
+            }
+        }
+        Ok(ops)
     }
 
     fn get_entry(
@@ -145,6 +501,33 @@ impl SqlitePegQueue {
         )?)
     }
 
+    fn get_rejections(&self) -> Result<Vec<RejectedPegOut>, Error> {
+        let rows = self
+            .conn
+            .prepare(Self::sql_select_rejections())?
+            .query_map(rusqlite::params![], |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut rejected = Vec::with_capacity(rows.len());
+        for (version, op, reason) in rows {
+            if let SbtcOp::PegOutRequest(op) = stored_op::decode(version, &op)? {
+                rejected.push(RejectedPegOut {
+                    txid: op.txid.to_hex(),
+                    block_height: op.block_height,
+                    amount: op.amount,
+                    fulfillment_fee: op.fulfillment_fee,
+                    reason,
+                });
+            }
+        }
+        Ok(rejected)
+    }
+
     fn max_observed_block_height(&self) -> Result<u64, Error> {
         Ok(self
             .conn
@@ -164,6 +547,31 @@ impl SqlitePegQueue {
             block_height INTEGER NOT NULL,
             op TEXT NOT NULL,
             status TEXT NOT NULL,
+            op_version INTEGER NOT NULL DEFAULT 0,
+            op_id TEXT,
+
+            PRIMARY KEY(txid, burn_header_hash)
+        )
+        "#
+    }
+
+    const fn sql_schema_events() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS sbtc_op_events (
+            op_id TEXT NOT NULL,
+            recorded_at INTEGER NOT NULL,
+            stage TEXT NOT NULL,
+            detail TEXT NOT NULL
+        )
+        "#
+    }
+
+    const fn sql_schema_rejections() -> &'static str {
+        r#"
+        CREATE TABLE IF NOT EXISTS peg_out_rejections (
+            txid TEXT NOT NULL,
+            burn_header_hash TEXT NOT NULL,
+            reason TEXT NOT NULL,
 
             PRIMARY KEY(txid, burn_header_hash)
         )
@@ -172,19 +580,105 @@ impl SqlitePegQueue {
 
     const fn sql_insert() -> &'static str {
         r#"
-        REPLACE INTO sbtc_ops (txid, burn_header_hash, block_height, op, status) VALUES (?1, ?2, ?3, ?4, ?5)
+        REPLACE INTO sbtc_ops (txid, burn_header_hash, block_height, op, status, op_version, op_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        "#
+    }
+
+    const fn sql_has_op_id() -> &'static str {
+        r#"
+        SELECT COUNT(*) > 0 FROM pragma_table_info('sbtc_ops') WHERE name='op_id'
+        "#
+    }
+
+    const fn sql_add_op_id() -> &'static str {
+        r#"
+        ALTER TABLE sbtc_ops ADD COLUMN op_id TEXT
+        "#
+    }
+
+    const fn sql_select_without_op_id() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash FROM sbtc_ops WHERE op_id IS NULL
+        "#
+    }
+
+    const fn sql_set_op_id() -> &'static str {
+        r#"
+        UPDATE sbtc_ops SET op_id=?3 WHERE txid=?1 AND burn_header_hash=?2
+        "#
+    }
+
+    const fn sql_select_op_id() -> &'static str {
+        r#"
+        SELECT op_id FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
+        "#
+    }
+
+    const fn sql_select_by_op_id() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, block_height, op, status, op_version, op_id FROM sbtc_ops WHERE op_id=?1
+        "#
+    }
+
+    const fn sql_insert_event() -> &'static str {
+        r#"
+        INSERT INTO sbtc_op_events (op_id, recorded_at, stage, detail) VALUES (?1, ?2, ?3, ?4)
+        "#
+    }
+
+    const fn sql_select_events() -> &'static str {
+        r#"
+        SELECT stage, recorded_at, detail FROM sbtc_op_events WHERE op_id=?1 ORDER BY rowid ASC
+        "#
+    }
+
+    const fn sql_has_op_version() -> &'static str {
+        r#"
+        SELECT COUNT(*) > 0 FROM pragma_table_info('sbtc_ops') WHERE name='op_version'
+        "#
+    }
+
+    const fn sql_add_op_version() -> &'static str {
+        r#"
+        ALTER TABLE sbtc_ops ADD COLUMN op_version INTEGER NOT NULL DEFAULT 0
+        "#
+    }
+
+    const fn sql_select_outdated() -> &'static str {
+        r#"
+        SELECT txid, burn_header_hash, op_version, op FROM sbtc_ops WHERE op_version < ?1
+        "#
+    }
+
+    const fn sql_update_op() -> &'static str {
+        r#"
+        UPDATE sbtc_ops SET op=?3, op_version=?4 WHERE txid=?1 AND burn_header_hash=?2
+        "#
+    }
+
+    const fn sql_insert_rejection() -> &'static str {
+        r#"
+        REPLACE INTO peg_out_rejections (txid, burn_header_hash, reason) VALUES (?1, ?2, ?3)
+        "#
+    }
+
+    const fn sql_select_rejections() -> &'static str {
+        r#"
+        SELECT sbtc_ops.op_version, sbtc_ops.op, peg_out_rejections.reason FROM sbtc_ops
+        JOIN peg_out_rejections USING (txid, burn_header_hash)
+        WHERE sbtc_ops.status='rejected' ORDER BY sbtc_ops.block_height, sbtc_ops.op ASC
         "#
     }
 
     const fn sql_select_status() -> &'static str {
         r#"
-        SELECT txid, burn_header_hash, block_height, op, status FROM sbtc_ops WHERE status=?1 ORDER BY block_height, op ASC
+        SELECT txid, burn_header_hash, block_height, op, status, op_version, op_id FROM sbtc_ops WHERE status=?1 ORDER BY block_height, op ASC
         "#
     }
 
     const fn sql_select_pk() -> &'static str {
         r#"
-        SELECT txid, burn_header_hash, block_height, op, status FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
+        SELECT txid, burn_header_hash, block_height, op, status, op_version, op_id FROM sbtc_ops WHERE txid=?1 AND burn_header_hash=?2
         "#
     }
 
@@ -197,16 +691,17 @@ impl SqlitePegQueue {
 
 impl PegQueue for SqlitePegQueue {
     fn sbtc_op(&self) -> Result<Option<SbtcOp>, PegQueueError> {
-        let maybe_entry = self.get_single_entry_with_status(&Status::New)?;
-
-        let Some(mut entry) = maybe_entry else {
-            return Ok(None)
-        };
-
-        entry.status = Status::Pending;
-        self.insert(&entry)?;
+        let policy = &self.priority_policy;
+        Ok(self
+            .take_new_entries(|ops, tip_height| {
+                policy.order(ops, tip_height).into_iter().take(1).collect()
+            })?
+            .pop())
+    }
 
-        Ok(Some(entry.op))
+    fn sbtc_ops_for_tick(&self) -> Result<Vec<SbtcOp>, PegQueueError> {
+        let policy = &self.priority_policy;
+        Ok(self.take_new_entries(|ops, tip_height| policy.schedule(ops, tip_height))?)
     }
 
     fn poll<N: StacksNode>(&self, stacks_node: &N) -> Result<(), PegQueueError> {
@@ -226,6 +721,14 @@ impl PegQueue for SqlitePegQueue {
         Ok(())
     }
 
+    fn submit(&self, op: SbtcOp) -> Result<OpId, PegQueueError> {
+        let entry = match op {
+            SbtcOp::PegIn(op) => Entry::from(op),
+            SbtcOp::PegOutRequest(op) => Entry::from(op),
+        };
+        Ok(self.admit(entry)?)
+    }
+
     fn acknowledge(
         &self,
         txid: &Txid,
@@ -235,9 +738,105 @@ impl PegQueue for SqlitePegQueue {
 
         entry.status = Status::Acknowledged;
         self.insert(&entry)?;
+        info!("Acknowledged {} as op {}", entry.txid, entry.op_id);
+        self.record(&entry.op_id, Stage::Acknowledged, "")?;
 
         Ok(())
     }
+
+    fn priority_policy(&self) -> &PriorityPolicy {
+        &self.priority_policy
+    }
+
+    fn observed_block_height(&self) -> Option<u64> {
+        self.max_observed_block_height().ok()
+    }
+
+    fn queue_depths(&self) -> Result<QueueDepths, PegQueueError> {
+        let entries = self.get_entries_with_status(&Status::New)?;
+        Ok(self
+            .priority_policy
+            .depths(entries.iter().map(|entry| &entry.op)))
+    }
+
+    fn rejected_peg_outs(&self) -> Result<Vec<RejectedPegOut>, PegQueueError> {
+        Ok(self.get_rejections()?)
+    }
+
+    fn op_id(&self, op: &SbtcOp) -> Result<Option<OpId>, PegQueueError> {
+        Ok(self.find_op_id(op.txid(), op.burn_header_hash())?)
+    }
+
+    fn record_stage(&self, op_id: &OpId, stage: Stage, detail: &str) -> Result<(), PegQueueError> {
+        Ok(self.record(op_id, stage, detail)?)
+    }
+
+    fn trace(&self, op_id: &OpId) -> Result<Option<TracedOp>, PegQueueError> {
+        let Some(entry) = self.get_by_op_id(op_id)? else {
+            return Ok(None);
+        };
+        Ok(Some(TracedOp {
+            op_id: entry.op_id,
+            op: entry.op,
+            status: entry.status.as_str().to_string(),
+            events: self.events(op_id)?,
+        }))
+    }
+
+    fn pending(&self) -> Result<Vec<TracedOp>, PegQueueError> {
+        Ok(self.traced_with_status(&Status::Pending)?)
+    }
+
+    fn await_funds(&self, op_id: &OpId, detail: &str) -> Result<(), PegQueueError> {
+        Ok(self.transition(
+            op_id,
+            Status::Pending,
+            Status::AwaitingFunds,
+            Stage::AwaitingFunds,
+            detail,
+        )?)
+    }
+
+    fn awaiting_funds(&self) -> Result<Vec<TracedOp>, PegQueueError> {
+        Ok(self.traced_with_status(&Status::AwaitingFunds)?)
+    }
+
+    fn resume(&self, op_id: &OpId, detail: &str) -> Result<(), PegQueueError> {
+        Ok(self.transition(
+            op_id,
+            Status::AwaitingFunds,
+            Status::Pending,
+            Stage::FundsAvailable,
+            detail,
+        )?)
+    }
+
+    fn hold(&self, op_id: &OpId, detail: &str) -> Result<(), PegQueueError> {
+        let entry = self.get_by_op_id(op_id)?.ok_or(Error::EntryDoesNotExist)?;
+        let from = match entry.status {
+            from @ (Status::New | Status::AwaitingFunds) => from,
+            status => {
+                return Err(Error::UnexpectedStatus(
+                    *op_id,
+                    status.as_str(),
+                    "new or awaiting_funds",
+                )
+                .into())
+            }
+        };
+        info!("Holding op {}: {}", op_id, detail);
+        Ok(self.transition(op_id, from, Status::Held, Stage::Held, detail)?)
+    }
+
+    fn held(&self) -> Result<Vec<TracedOp>, PegQueueError> {
+        Ok(self.traced_with_status(&Status::Held)?)
+    }
+
+    fn release(&self, op_id: &OpId, detail: &str) -> Result<(), PegQueueError> {
+        info!("Releasing op {}: {}", op_id, detail);
+        // back to new, so an op held while awaiting funds is scheduled and checked again
+        Ok(self.transition(op_id, Status::Held, Status::New, Stage::Released, detail)?)
+    }
 }
 
 #[derive(Debug)]
@@ -247,6 +846,7 @@ struct Entry {
     block_height: u64,
     op: SbtcOp,
     status: Status,
+    op_id: OpId,
 }
 
 impl Entry {
@@ -258,16 +858,23 @@ impl Entry {
 
         let block_height = row.get::<_, i64>(2)? as u64; // Stacks will crash before the coordinator if this is invalid
 
-        let op: SbtcOp = serde_json::from_str(&row.get::<_, String>(3)?).map_err(Error::from)?;
-
         let status: Status = row.get::<_, String>(4)?.parse()?;
 
+        let op = stored_op::decode(row.get::<_, u32>(5)?, &row.get::<_, String>(3)?)
+            .map_err(Error::from)?;
+
+        let op_id = row
+            .get::<_, String>(6)?
+            .parse::<OpId>()
+            .map_err(Error::from)?;
+
         Ok(Self {
             burn_header_hash,
             txid,
             block_height,
             op,
             status,
+            op_id,
         })
     }
 }
@@ -280,6 +887,7 @@ impl From<PegInOp> for Entry {
             txid: op.txid,
             burn_header_hash: op.burn_header_hash,
             op: SbtcOp::PegIn(op),
+            op_id: OpId::new_v4(),
         }
     }
 }
@@ -292,15 +900,22 @@ impl From<PegOutRequestOp> for Entry {
             txid: op.txid,
             burn_header_hash: op.burn_header_hash,
             op: SbtcOp::PegOutRequest(op),
+            op_id: OpId::new_v4(),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum Status {
     New,
     Pending,
     Acknowledged,
+    /// Refused by the peg-out policy, never processed
+    Rejected,
+    /// A peg-out the peg wallet cannot pay yet, retried every tick
+    AwaitingFunds,
+    /// Held by an operator, never scheduled until released
+    Held,
 }
 
 impl Status {
@@ -309,6 +924,9 @@ impl Status {
             Self::New => "new",
             Self::Pending => "pending",
             Self::Acknowledged => "acknowledged",
+            Self::Rejected => "rejected",
+            Self::AwaitingFunds => "awaiting_funds",
+            Self::Held => "held",
         }
     }
 }
@@ -320,11 +938,21 @@ impl FromStr for Status {
             "new" => Self::New,
             "pending" => Self::Pending,
             "acknowledged" => Self::Acknowledged,
+            "rejected" => Self::Rejected,
+            "awaiting_funds" => Self::AwaitingFunds,
+            "held" => Self::Held,
             other => return Err(Error::InvalidStatusError(other.to_owned())),
         })
     }
 }
 
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use crate::stacks_node;
@@ -440,6 +1068,280 @@ mod tests {
         assert_eq!(entry.status, Status::Acknowledged);
     }
 
+    #[test]
+    fn submitted_ops_should_be_returned_by_sbtc_op() {
+        let peg_queue = SqlitePegQueue::in_memory(1).unwrap();
+
+        peg_queue.submit(SbtcOp::PegIn(peg_in_op(7))).unwrap();
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_in().unwrap().block_height, 7);
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+    }
+
+    #[test]
+    fn sbtc_ops_for_tick_should_respect_quotas() {
+        let peg_queue =
+            SqlitePegQueue::in_memory(1)
+                .unwrap()
+                .with_priority_policy(PriorityPolicy {
+                    peg_in_quota: 2,
+                    ..Default::default()
+                });
+        peg_queue.poll(&default_stacks_node_mock(3)).unwrap();
+        assert_eq!(peg_queue.queue_depths().unwrap().values().sum::<usize>(), 6);
+
+        let ops = peg_queue.sbtc_ops_for_tick().unwrap();
+        let heights: Vec<(bool, u64)> = ops
+            .iter()
+            .map(|op| (op.as_peg_in().is_some(), op.block_height()))
+            .collect();
+        assert_eq!(heights, vec![(true, 1), (false, 1), (true, 2)]);
+        assert_eq!(peg_queue.queue_depths().unwrap().values().sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn ops_should_keep_their_id_and_trace_their_stages() {
+        let peg_queue = SqlitePegQueue::in_memory(1).unwrap();
+        let op = SbtcOp::PegIn(peg_in_op(7));
+
+        let op_id = peg_queue.submit(op.clone()).unwrap();
+        assert_eq!(peg_queue.submit(op.clone()).unwrap(), op_id);
+        assert_eq!(peg_queue.op_id(&op).unwrap(), Some(op_id));
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        peg_queue
+            .record_stage(&op_id, Stage::StacksTxBuilt, "mint")
+            .unwrap();
+        let peg_in_op = next_op.as_peg_in().unwrap();
+        peg_queue
+            .acknowledge(&peg_in_op.txid, &peg_in_op.burn_header_hash)
+            .unwrap();
+
+        let traced = peg_queue.trace(&op_id).unwrap().unwrap();
+        assert_eq!(traced.op, op);
+        assert_eq!(traced.status, "acknowledged");
+        let stages: Vec<(Stage, &str)> = traced
+            .events
+            .iter()
+            .map(|event| (event.stage, event.detail.as_str()))
+            .collect();
+        assert_eq!(
+            stages,
+            vec![
+                (Stage::Queued, ""),
+                (Stage::Scheduled, ""),
+                (Stage::StacksTxBuilt, "mint"),
+                (Stage::Acknowledged, ""),
+            ]
+        );
+        assert!(peg_queue.trace(&OpId::new_v4()).unwrap().is_none());
+    }
+
+    #[test]
+    fn peg_outs_awaiting_funds_should_be_held_until_resumed() {
+        let peg_queue = SqlitePegQueue::in_memory(1).unwrap();
+        let op = SbtcOp::PegOutRequest(peg_out_request_op(7));
+        let op_id = peg_queue.submit(op.clone()).unwrap();
+        assert_eq!(peg_queue.sbtc_ops_for_tick().unwrap(), vec![op.clone()]);
+
+        peg_queue.await_funds(&op_id, "short 500 sats").unwrap();
+        assert!(peg_queue.pending().unwrap().is_empty());
+        assert!(peg_queue.sbtc_ops_for_tick().unwrap().is_empty());
+        let held = peg_queue.awaiting_funds().unwrap();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].op, op);
+        assert_eq!(held[0].status, "awaiting_funds");
+        // only pending ops can be held
+        assert!(peg_queue.await_funds(&op_id, "").is_err());
+
+        peg_queue.resume(&op_id, "").unwrap();
+        assert!(peg_queue.awaiting_funds().unwrap().is_empty());
+        let pending = peg_queue.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+        let stages: Vec<Stage> = pending[0].events.iter().map(|event| event.stage).collect();
+        assert_eq!(
+            stages,
+            vec![
+                Stage::Queued,
+                Stage::Scheduled,
+                Stage::AwaitingFunds,
+                Stage::FundsAvailable,
+            ]
+        );
+        assert!(peg_queue.resume(&op_id, "").is_err());
+    }
+
+    #[test]
+    fn held_ops_should_not_be_scheduled_until_released() {
+        let peg_queue = SqlitePegQueue::in_memory(1).unwrap();
+        let suspicious = SbtcOp::PegOutRequest(peg_out_request_op(7));
+        let other = SbtcOp::PegIn(peg_in_op(7));
+        let op_id = peg_queue.submit(suspicious.clone()).unwrap();
+        peg_queue.submit(other.clone()).unwrap();
+
+        peg_queue.hold(&op_id, "unknown recipient").unwrap();
+        assert_eq!(peg_queue.sbtc_op().unwrap(), Some(other));
+        assert_eq!(peg_queue.sbtc_op().unwrap(), None);
+        let held = peg_queue.held().unwrap();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].op, suspicious);
+        assert_eq!(held[0].status, "held");
+        // ops already being processed cannot be held, and only held ops released
+        assert!(peg_queue.hold(&op_id, "").is_err());
+        assert!(peg_queue.hold(&OpId::new_v4(), "").is_err());
+
+        peg_queue.release(&op_id, "recipient checked").unwrap();
+        assert!(peg_queue.held().unwrap().is_empty());
+        assert!(peg_queue.release(&op_id, "").is_err());
+        assert_eq!(peg_queue.sbtc_op().unwrap(), Some(suspicious));
+        let events = peg_queue.trace(&op_id).unwrap().unwrap().events;
+        let stages: Vec<(Stage, &str)> = events
+            .iter()
+            .map(|event| (event.stage, event.detail.as_str()))
+            .collect();
+        assert_eq!(
+            stages,
+            vec![
+                (Stage::Queued, ""),
+                (Stage::Held, "unknown recipient"),
+                (Stage::Released, "recipient checked"),
+                (Stage::Scheduled, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn held_ops_should_stay_held_across_a_reopen() {
+        let path = std::env::temp_dir().join(format!("held-ops-{}.sqlite", std::process::id()));
+        let op = SbtcOp::PegOutRequest(peg_out_request_op(7));
+        let op_id = {
+            let peg_queue = SqlitePegQueue::new(&path, 1).unwrap();
+            let op_id = peg_queue.submit(op.clone()).unwrap();
+            peg_queue.hold(&op_id, "").unwrap();
+            op_id
+        };
+        let peg_queue = SqlitePegQueue::new(&path, 1).unwrap();
+        assert_eq!(peg_queue.sbtc_op().unwrap(), None);
+        assert_eq!(peg_queue.held().unwrap()[0].op_id, op_id);
+        drop(peg_queue);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn peg_outs_below_the_policy_minimums_should_be_rejected() {
+        let peg_queue = SqlitePegQueue::in_memory(1)
+            .unwrap()
+            .with_peg_out_policy(PegOutPolicy {
+                min_amount: 1000,
+                min_fulfillment_fee: 500,
+            });
+        let mut dust = peg_out_request_op(1);
+        dust.amount = 999;
+        let mut cheap = peg_out_request_op(2);
+        cheap.fulfillment_fee = 499;
+        let accepted = peg_out_request_op(3);
+        for op in [&dust, &cheap, &accepted] {
+            peg_queue.submit(SbtcOp::PegOutRequest(op.clone())).unwrap();
+        }
+
+        let next_op = peg_queue.sbtc_op().unwrap().unwrap();
+        assert_eq!(next_op.as_peg_out_request().unwrap(), &accepted);
+        assert!(peg_queue.sbtc_op().unwrap().is_none());
+        assert_eq!(
+            peg_queue
+                .get_entry(&dust.txid, &dust.burn_header_hash)
+                .unwrap()
+                .status,
+            Status::Rejected
+        );
+
+        let rejected = peg_queue.rejected_peg_outs().unwrap();
+        let reasons: Vec<(&str, &str)> = rejected
+            .iter()
+            .map(|r| (r.txid.as_str(), r.reason.as_str()))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (
+                    dust.txid.to_hex().as_str(),
+                    "amount of 999 sats is below the minimum of 1000"
+                ),
+                (
+                    cheap.txid.to_hex().as_str(),
+                    "fulfillment fee of 499 sats is below the minimum of 500"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rebuild_should_acknowledge_the_ops_the_contract_processed() {
+        let peg_queue = SqlitePegQueue::in_memory(1).unwrap();
+        // the database lost everything but one op
+        let kept = peg_queue
+            .submit(SbtcOp::PegOutRequest(peg_out_request_op(2)))
+            .unwrap();
+        let processed: Vec<String> = [
+            peg_in_op(1).txid,
+            peg_in_op(2).txid,
+            peg_out_request_op(1).txid,
+        ]
+        .iter()
+        .map(|txid| clarity::string_ascii(&txid.to_string()).unwrap())
+        .collect();
+        let mut stacks_node_mock = default_stacks_node_mock(3);
+        stacks_node_mock
+            .expect_call_read_only()
+            .returning(move |_, _, function, args| {
+                assert!(function == "is-peg-in-processed" || function == "is-peg-out-processed");
+                Ok(if processed.contains(&args[0]) {
+                    "0x03"
+                } else {
+                    "0x04"
+                }
+                .to_string())
+            });
+
+        let rebuilt = peg_queue
+            .rebuild(
+                &stacks_node_mock,
+                "ST000000000000000000002AMW42H",
+                "sbtc-alpha",
+                1,
+            )
+            .unwrap();
+        assert_eq!(
+            rebuilt,
+            Rebuilt {
+                from_height: 1,
+                to_height: 3,
+                processed: 3,
+                queued: 2,
+                kept: 1,
+            }
+        );
+
+        let op_id = peg_queue
+            .op_id(&SbtcOp::PegIn(peg_in_op(1)))
+            .unwrap()
+            .unwrap();
+        let traced = peg_queue.trace(&op_id).unwrap().unwrap();
+        assert_eq!(traced.status, "acknowledged");
+        assert_eq!(traced.events.len(), 1);
+        assert_eq!(traced.events[0].stage, Stage::Rebuilt);
+        assert_eq!(traced.events[0].detail, "mint");
+        assert!(peg_queue.trace(&kept).unwrap().is_some());
+
+        // only the ops the contract did not process are left to process
+        let mut heights: Vec<u64> = std::iter::from_fn(|| peg_queue.sbtc_op().unwrap())
+            .map(|op| op.block_height())
+            .collect();
+        heights.sort();
+        assert_eq!(heights, vec![2, 3, 3]);
+    }
+
     fn default_stacks_node_mock(block_height: u64) -> stacks_node::MockStacksNode {
         let mut stacks_node_mock = stacks_node::MockStacksNode::new();
 
@@ -0,0 +1,383 @@
+//! On-disk representation of queued ops.
+//!
+//! Ops are stored as JSON laid out by the structs below, not by the serde implementations of
+//! blockstack_lib, so upgrading blockstack_lib cannot change what an existing queue decodes to.
+//! Each row records the version of the layout it was written with. Rows written before layouts
+//! were versioned are version 0, blockstack_lib's own encoding of [`SbtcOp`], and are only read
+//! to migrate them.
+use blockstack_lib::address::AddressHashMode;
+use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20, PoxAddressType32};
+use blockstack_lib::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+use blockstack_lib::util::hash::{hex_bytes, to_hex, Hash160};
+use blockstack_lib::util::secp256k1::MessageSignature;
+use blockstack_lib::util::HexError;
+use blockstack_lib::vm::types::{
+    PrincipalData, QualifiedContractIdentifier, StandardPrincipalData,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::peg_queue::SbtcOp;
+use crate::stacks_node::{PegInOp, PegOutRequestOp};
+
+/// Layout version written by this release
+pub const VERSION: u32 = 1;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("JSON serialization failure: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("Hex codec error: {0}")]
+    HexError(#[from] HexError),
+    #[error("Queue entry written by a newer release, layout version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid {0} in queue entry: {1}")]
+    InvalidField(&'static str, String),
+}
+
+/// Serialize `op` in the current layout
+pub fn encode(op: &SbtcOp) -> Result<String, Error> {
+    Ok(serde_json::to_string(&OpV1::from(op))?)
+}
+
+/// Deserialize an op stored in layout `version`
+pub fn decode(version: u32, op: &str) -> Result<SbtcOp, Error> {
+    match version {
+        0 => Ok(serde_json::from_str(op)?),
+        1 => serde_json::from_str::<OpV1>(op)?.try_into(),
+        other => Err(Error::UnsupportedVersion(other)),
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum OpV1 {
+    PegIn {
+        recipient: PrincipalV1,
+        peg_wallet_address: PoxAddressV1,
+        amount: u64,
+        memo: String,
+        txid: String,
+        vtxindex: u32,
+        block_height: u64,
+        burn_header_hash: String,
+    },
+    PegOutRequest {
+        amount: u64,
+        recipient: PoxAddressV1,
+        signature: String,
+        peg_wallet_address: PoxAddressV1,
+        fulfillment_fee: u64,
+        memo: String,
+        txid: String,
+        vtxindex: u32,
+        block_height: u64,
+        burn_header_hash: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PrincipalV1 {
+    Standard {
+        version: u8,
+        hash160: String,
+    },
+    Contract {
+        version: u8,
+        hash160: String,
+        name: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PoxAddressV1 {
+    Standard {
+        version: u8,
+        hash160: String,
+        hash_mode: Option<String>,
+    },
+    Addr20 {
+        mainnet: bool,
+        address_type: String,
+        bytes: String,
+    },
+    Addr32 {
+        mainnet: bool,
+        address_type: String,
+        bytes: String,
+    },
+}
+
+impl From<&SbtcOp> for OpV1 {
+    fn from(op: &SbtcOp) -> Self {
+        match op {
+            SbtcOp::PegIn(op) => Self::PegIn {
+                recipient: PrincipalV1::from(&op.recipient),
+                peg_wallet_address: PoxAddressV1::from(&op.peg_wallet_address),
+                amount: op.amount,
+                memo: to_hex(&op.memo),
+                txid: op.txid.to_hex(),
+                vtxindex: op.vtxindex,
+                block_height: op.block_height,
+                burn_header_hash: op.burn_header_hash.to_hex(),
+            },
+            SbtcOp::PegOutRequest(op) => Self::PegOutRequest {
+                amount: op.amount,
+                recipient: PoxAddressV1::from(&op.recipient),
+                signature: to_hex(&op.signature.0),
+                peg_wallet_address: PoxAddressV1::from(&op.peg_wallet_address),
+                fulfillment_fee: op.fulfillment_fee,
+                memo: to_hex(&op.memo),
+                txid: op.txid.to_hex(),
+                vtxindex: op.vtxindex,
+                block_height: op.block_height,
+                burn_header_hash: op.burn_header_hash.to_hex(),
+            },
+        }
+    }
+}
+
+impl TryFrom<OpV1> for SbtcOp {
+    type Error = Error;
+    fn try_from(op: OpV1) -> Result<Self, Error> {
+        Ok(match op {
+            OpV1::PegIn {
+                recipient,
+                peg_wallet_address,
+                amount,
+                memo,
+                txid,
+                vtxindex,
+                block_height,
+                burn_header_hash,
+            } => Self::PegIn(PegInOp {
+                recipient: recipient.try_into()?,
+                peg_wallet_address: peg_wallet_address.try_into()?,
+                amount,
+                memo: hex_bytes(&memo)?,
+                txid: Txid::from_hex(&txid)?,
+                vtxindex,
+                block_height,
+                burn_header_hash: BurnchainHeaderHash::from_hex(&burn_header_hash)?,
+            }),
+            OpV1::PegOutRequest {
+                amount,
+                recipient,
+                signature,
+                peg_wallet_address,
+                fulfillment_fee,
+                memo,
+                txid,
+                vtxindex,
+                block_height,
+                burn_header_hash,
+            } => Self::PegOutRequest(PegOutRequestOp {
+                amount,
+                recipient: recipient.try_into()?,
+                signature: MessageSignature(bytes("signature", &signature)?),
+                peg_wallet_address: peg_wallet_address.try_into()?,
+                fulfillment_fee,
+                memo: hex_bytes(&memo)?,
+                txid: Txid::from_hex(&txid)?,
+                vtxindex,
+                block_height,
+                burn_header_hash: BurnchainHeaderHash::from_hex(&burn_header_hash)?,
+            }),
+        })
+    }
+}
+
+impl From<&PrincipalData> for PrincipalV1 {
+    fn from(principal: &PrincipalData) -> Self {
+        match principal {
+            PrincipalData::Standard(StandardPrincipalData(version, hash160)) => Self::Standard {
+                version: *version,
+                hash160: to_hex(hash160),
+            },
+            PrincipalData::Contract(contract) => Self::Contract {
+                version: contract.issuer.0,
+                hash160: to_hex(&contract.issuer.1),
+                name: contract.name.as_str().to_string(),
+            },
+        }
+    }
+}
+
+impl TryFrom<PrincipalV1> for PrincipalData {
+    type Error = Error;
+    fn try_from(principal: PrincipalV1) -> Result<Self, Error> {
+        Ok(match principal {
+            PrincipalV1::Standard { version, hash160 } => {
+                Self::Standard(StandardPrincipalData(version, bytes("hash160", &hash160)?))
+            }
+            PrincipalV1::Contract {
+                version,
+                hash160,
+                name,
+            } => Self::Contract(QualifiedContractIdentifier {
+                issuer: StandardPrincipalData(version, bytes("hash160", &hash160)?),
+                name: name
+                    .try_into()
+                    .map_err(|e| Error::InvalidField("contract name", format!("{e:?}")))?,
+            }),
+        })
+    }
+}
+
+impl From<&PoxAddress> for PoxAddressV1 {
+    fn from(address: &PoxAddress) -> Self {
+        match address {
+            PoxAddress::Standard(address, hash_mode) => Self::Standard {
+                version: address.version,
+                hash160: address.bytes.to_hex(),
+                hash_mode: hash_mode.map(|mode| hash_mode_str(mode).to_string()),
+            },
+            PoxAddress::Addr20(mainnet, address_type, bytes) => Self::Addr20 {
+                mainnet: *mainnet,
+                address_type: match address_type {
+                    PoxAddressType20::P2WPKH => "p2wpkh",
+                }
+                .to_string(),
+                bytes: to_hex(bytes),
+            },
+            PoxAddress::Addr32(mainnet, address_type, bytes) => Self::Addr32 {
+                mainnet: *mainnet,
+                address_type: match address_type {
+                    PoxAddressType32::P2WSH => "p2wsh",
+                    PoxAddressType32::P2TR => "p2tr",
+                }
+                .to_string(),
+                bytes: to_hex(bytes),
+            },
+        }
+    }
+}
+
+impl TryFrom<PoxAddressV1> for PoxAddress {
+    type Error = Error;
+    fn try_from(address: PoxAddressV1) -> Result<Self, Error> {
+        Ok(match address {
+            PoxAddressV1::Standard {
+                version,
+                hash160,
+                hash_mode,
+            } => Self::Standard(
+                StacksAddress::new(version, Hash160(bytes("hash160", &hash160)?)),
+                hash_mode.as_deref().map(parse_hash_mode).transpose()?,
+            ),
+            PoxAddressV1::Addr20 {
+                mainnet,
+                address_type,
+                bytes: address,
+            } => {
+                let address_type = match address_type.as_str() {
+                    "p2wpkh" => PoxAddressType20::P2WPKH,
+                    _ => return Err(Error::InvalidField("address type", address_type)),
+                };
+                Self::Addr20(mainnet, address_type, bytes("address", &address)?)
+            }
+            PoxAddressV1::Addr32 {
+                mainnet,
+                address_type,
+                bytes: address,
+            } => {
+                let address_type = match address_type.as_str() {
+                    "p2wsh" => PoxAddressType32::P2WSH,
+                    "p2tr" => PoxAddressType32::P2TR,
+                    _ => return Err(Error::InvalidField("address type", address_type)),
+                };
+                Self::Addr32(mainnet, address_type, bytes("address", &address)?)
+            }
+        })
+    }
+}
+
+fn hash_mode_str(mode: AddressHashMode) -> &'static str {
+    match mode {
+        AddressHashMode::SerializeP2PKH => "p2pkh",
+        AddressHashMode::SerializeP2SH => "p2sh",
+        AddressHashMode::SerializeP2WPKH => "p2wpkh",
+        AddressHashMode::SerializeP2WSH => "p2wsh",
+    }
+}
+
+fn parse_hash_mode(mode: &str) -> Result<AddressHashMode, Error> {
+    Ok(match mode {
+        "p2pkh" => AddressHashMode::SerializeP2PKH,
+        "p2sh" => AddressHashMode::SerializeP2SH,
+        "p2wpkh" => AddressHashMode::SerializeP2WPKH,
+        "p2wsh" => AddressHashMode::SerializeP2WSH,
+        other => return Err(Error::InvalidField("hash mode", other.to_string())),
+    })
+}
+
+/// Decode the fixed length hex `field`
+fn bytes<const N: usize>(field: &'static str, hex: &str) -> Result<[u8; N], Error> {
+    hex_bytes(hex)?
+        .try_into()
+        .map_err(|_| Error::InvalidField(field, hex.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peg_out_request_op() -> PegOutRequestOp {
+        PegOutRequestOp {
+            amount: 1337,
+            recipient: PoxAddress::Addr32(true, PoxAddressType32::P2TR, [3; 32]),
+            signature: MessageSignature([1; 65]),
+            peg_wallet_address: PoxAddress::Standard(
+                StacksAddress::new(26, Hash160([2; 20])),
+                Some(AddressHashMode::SerializeP2WPKH),
+            ),
+            fulfillment_fee: 1000,
+            memo: vec![1, 3, 3, 7],
+            txid: Txid([4; 32]),
+            vtxindex: 2,
+            block_height: 9,
+            burn_header_hash: BurnchainHeaderHash([5; 32]),
+        }
+    }
+
+    #[test]
+    fn ops_round_trip_through_the_current_layout() {
+        let peg_in = SbtcOp::PegIn(PegInOp {
+            recipient: PrincipalData::Standard(StandardPrincipalData(26, [6; 20])),
+            peg_wallet_address: PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [7; 20]),
+            amount: 42,
+            memo: vec![],
+            txid: Txid([8; 32]),
+            vtxindex: 0,
+            block_height: 3,
+            burn_header_hash: BurnchainHeaderHash([9; 32]),
+        });
+        let peg_out = SbtcOp::PegOutRequest(peg_out_request_op());
+        for op in [peg_in, peg_out] {
+            assert_eq!(decode(VERSION, &encode(&op).unwrap()).unwrap(), op);
+        }
+    }
+
+    #[test]
+    fn the_layout_does_not_depend_on_blockstack_serde() {
+        let stored = encode(&SbtcOp::PegOutRequest(peg_out_request_op())).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(json["kind"], "peg_out_request");
+        assert_eq!(json["txid"], "04".repeat(32));
+        assert_eq!(json["recipient"]["type"], "addr32");
+        assert_eq!(json["recipient"]["address_type"], "p2tr");
+        assert_eq!(json["peg_wallet_address"]["hash_mode"], "p2wpkh");
+        assert_eq!(json["memo"], "01030307");
+    }
+
+    #[test]
+    fn newer_layouts_are_refused() {
+        let stored = encode(&SbtcOp::PegOutRequest(peg_out_request_op())).unwrap();
+        assert!(matches!(
+            decode(VERSION + 1, &stored),
+            Err(Error::UnsupportedVersion(v)) if v == VERSION + 1
+        ));
+    }
+}
@@ -1,8 +1,10 @@
 use crate::bitcoin_node;
 use crate::bitcoin_wallet::{BitcoinWallet as BitcoinWalletStruct, Error as BitcoinWalletError};
+use crate::fee_ledger::CollectedFee;
 use crate::stacks_node;
 use crate::stacks_transaction::StacksTransaction;
 use crate::stacks_wallet::{Error as StacksWalletError, StacksWallet as StacksWalletStruct};
+use bitcoin::Script;
 use serde::Serialize;
 use std::fmt::Debug;
 
@@ -27,13 +29,35 @@ pub trait StacksWallet {
         &mut self,
         address: PegWalletAddress,
     ) -> Result<StacksTransaction, Error>;
+    fn build_contract_deploy_transaction(
+        &mut self,
+        name: &str,
+        source: &str,
+        nonce: u64,
+    ) -> Result<StacksTransaction, Error>;
+    /// Pay `rate` microstacks per byte for the transactions built from now on. Wallets paying
+    /// fixed fees ignore it.
+    fn set_fee_rate(&mut self, _rate: u64) {}
+    /// Address and name of the sBTC contract the transactions call
+    fn contract(&self) -> (&str, &str);
 }
 
 pub trait BitcoinWallet {
     type Error: Debug;
+    /// Build the fulfillment of `op`, committing to the stacks `chain_tip` the peg-out was read
+    /// at and spending `spent`, the output of the peg wallet it pays out of
     fn fulfill_peg_out(
         &self,
         op: &stacks_node::PegOutRequestOp,
+        chain_tip: [u8; 32],
+        spent: &bitcoin::TxOut,
+    ) -> Result<bitcoin_node::BitcoinTransaction, Error>;
+    /// Build a transaction spending `fees` to `to`, paying `tx_fee` out of them
+    fn sweep_fees(
+        &self,
+        fees: &[CollectedFee],
+        to: &Script,
+        tx_fee: u64,
     ) -> Result<bitcoin_node::BitcoinTransaction, Error>;
 }
 
@@ -0,0 +1,223 @@
+//! Signed attestations of the peg wallet's reserves.
+//!
+//! `proof-of-reserve` scans the bitcoin node's UTXO set for the outputs paying the peg wallet,
+//! the taproot output of the current aggregate key, and has the signers threshold-sign a
+//! [`ReserveStatement`] of them: the block the scan ran at, every output with its value and the
+//! total. The statement is signed as the BIP-340 tagged hash of its [`ReserveStatement::to_bytes`]
+//! under [`TAG`], so the signature cannot be replayed as a signature of a transaction.
+//!
+//! Anyone holding an [`Attestation`] checks it with [`Attestation::verify`], or `verify-reserve`,
+//! without trusting the coordinator which published it: the wallet has to be the aggregate key's,
+//! the total the sum of the outputs, and the signature valid under the key. Whether the outputs
+//! were unspent at the block is for the verifier to check against a node of their own.
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::{BlockHash, Script};
+use frost_signer::digest::MessageDigest;
+use frost_signer::taproot::KeyPath;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::bitcoin_node::{as_string, Utxo, UtxoSnapshot};
+use crate::frost_types::{Error as FrostTypesError, GroupPublicKey, ThresholdSignature};
+
+/// Domain tag reserve statements are signed under
+pub const TAG: &str = "sbtc/proof-of-reserve";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("signed by {0}, expected {1}")]
+    UnexpectedKey(GroupPublicKey, GroupPublicKey),
+    #[error("wallet script {0} is not the taproot output of the aggregate key")]
+    WalletMismatch(String),
+    #[error("total of {0} sats is not the {1} sats the outputs add up to")]
+    TotalMismatch(u64, u64),
+    #[error("signature does not verify under the aggregate key")]
+    BadSignature,
+    #[error("Frost Types Error: {0}")]
+    FrostTypesError(#[from] FrostTypesError),
+}
+
+/// Script of the peg wallet owned by `key`, a taproot output without script paths
+pub fn wallet_script(key: &GroupPublicKey) -> Script {
+    Script::new_v1_p2tr_tweaked(key.output_key(&KeyPath::default()))
+}
+
+/// The peg wallet's unspent outputs at a block, as the signers attest to them
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReserveStatement {
+    pub block_height: u64,
+    #[serde(with = "as_string")]
+    pub block_hash: BlockHash,
+    /// The key owning the wallet, which signs the statement
+    pub aggregate_public_key: GroupPublicKey,
+    #[serde(with = "script_hex")]
+    pub wallet_script: Script,
+    /// Ordered by outpoint
+    pub utxos: Vec<Utxo>,
+    /// Value of the outputs in sats
+    pub total: u64,
+    /// Unix time in seconds the statement was made at
+    pub attested_at: u64,
+}
+
+impl ReserveStatement {
+    /// A statement of the outputs of `snapshot`, which paid the wallet of `key`
+    pub fn new(key: GroupPublicKey, snapshot: UtxoSnapshot, attested_at: u64) -> Self {
+        Self {
+            block_height: snapshot.block_height,
+            block_hash: snapshot.block_hash,
+            aggregate_public_key: key,
+            wallet_script: wallet_script(&key),
+            total: snapshot.utxos.iter().map(|utxo| utxo.amount).sum(),
+            utxos: snapshot.utxos,
+            attested_at,
+        }
+    }
+
+    /// The bytes the signers sign: every field in order, integers big-endian, the script and
+    /// the outputs prefixed by their length
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(self.block_height.to_be_bytes());
+        bytes.extend(self.block_hash.into_inner());
+        bytes.extend(self.aggregate_public_key.as_bytes());
+        bytes.extend((self.wallet_script.len() as u32).to_be_bytes());
+        bytes.extend(self.wallet_script.as_bytes());
+        bytes.extend((self.utxos.len() as u32).to_be_bytes());
+        for utxo in &self.utxos {
+            bytes.extend(utxo.outpoint.txid.into_inner());
+            bytes.extend(utxo.outpoint.vout.to_be_bytes());
+            bytes.extend(utxo.amount.to_be_bytes());
+            bytes.extend(utxo.height.to_be_bytes());
+        }
+        bytes.extend(self.total.to_be_bytes());
+        bytes.extend(self.attested_at.to_be_bytes());
+        bytes
+    }
+
+    pub fn digest(&self) -> MessageDigest {
+        MessageDigest::tagged(TAG, &self.to_bytes())
+    }
+}
+
+/// A reserve statement with the signers' signature of it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub statement: ReserveStatement,
+    pub signature: ThresholdSignature,
+}
+
+impl Attestation {
+    /// Check the statement is consistent and signed by its key, which must be `key` if given,
+    /// e.g. the aggregate key published in the sBTC contract
+    pub fn verify(&self, key: Option<&GroupPublicKey>) -> Result<(), Error> {
+        let statement = &self.statement;
+        let signer = statement.aggregate_public_key;
+        if let Some(key) = key {
+            if *key != signer {
+                return Err(Error::UnexpectedKey(signer, *key));
+            }
+        }
+        if statement.wallet_script != wallet_script(&signer) {
+            return Err(Error::WalletMismatch(statement.wallet_script.to_hex()));
+        }
+        let sum = statement.utxos.iter().map(|utxo| utxo.amount).sum();
+        if statement.total != sum {
+            return Err(Error::TotalMismatch(statement.total, sum));
+        }
+        let msg = Message::from_slice(statement.digest().as_bytes()).expect("32 bytes");
+        Secp256k1::verification_only()
+            .verify_schnorr(&self.signature.to_schnorr()?, &msg, &signer.to_x_only())
+            .map_err(|_| Error::BadSignature)
+    }
+}
+
+/// Serialize scripts as hex
+mod script_hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(script: &Script, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&script.to_hex())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Script, D::Error> {
+        Script::from_hex(&String::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinator::FrostCoordinator;
+    use crate::mock_frost_coordinator::MockFrostCoordinator;
+    use bitcoin::OutPoint;
+    use std::str::FromStr;
+
+    fn snapshot() -> UtxoSnapshot {
+        let utxo = |txid: &str, vout, amount| Utxo {
+            outpoint: OutPoint::new(bitcoin::Txid::from_str(txid).unwrap(), vout),
+            amount,
+            height: 810,
+        };
+        UtxoSnapshot {
+            block_height: 812,
+            block_hash: BlockHash::from_str(
+                "000000000000000000024bead8df69990852c202db0e0097c1a12ea637d7e96d",
+            )
+            .unwrap(),
+            utxos: vec![
+                utxo(&"01".repeat(32), 0, 50_000),
+                utxo(&"02".repeat(32), 3, 7_000),
+            ],
+        }
+    }
+
+    #[test]
+    fn signed_statements_verify_and_tampered_ones_do_not() {
+        let mut frost = MockFrostCoordinator::new();
+        let key = frost.run_distributed_key_generation().unwrap();
+        let statement = ReserveStatement::new(key, snapshot(), 1690000000);
+        assert_eq!(statement.total, 57_000);
+        // the wallet pays to the tweaked key, not the aggregate key itself
+        assert_eq!(
+            statement.wallet_script.as_bytes()[2..],
+            key.output_key(&KeyPath::default()).serialize()
+        );
+        assert_ne!(statement.wallet_script.as_bytes()[2..], key.as_bytes()[1..]);
+        let signature = frost.sign_digest(&statement.digest()).unwrap();
+        let attestation = Attestation {
+            statement,
+            signature,
+        };
+        attestation.verify(None).unwrap();
+        attestation.verify(Some(&key)).unwrap();
+
+        let json = serde_json::to_string(&attestation).unwrap();
+        assert!(json.contains(&format!(r#""outpoint":"{}:3""#, "02".repeat(32))));
+        let parsed: Attestation = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, attestation);
+        parsed.verify(Some(&key)).unwrap();
+
+        let mut other = MockFrostCoordinator::new();
+        let other_key = other.run_distributed_key_generation().unwrap();
+        assert!(matches!(
+            attestation.verify(Some(&other_key)),
+            Err(Error::UnexpectedKey(..))
+        ));
+
+        let mut inflated = attestation.clone();
+        inflated.statement.utxos[1].amount = 70_000;
+        inflated.statement.total = 120_000;
+        assert!(matches!(inflated.verify(None), Err(Error::BadSignature)));
+        inflated.statement.total = 100_000;
+        assert!(matches!(
+            inflated.verify(None),
+            Err(Error::TotalMismatch(100_000, 120_000))
+        ));
+
+        let mut moved = attestation;
+        moved.statement.wallet_script = wallet_script(&other_key);
+        assert!(matches!(moved.verify(None), Err(Error::WalletMismatch(_))));
+    }
+}
@@ -0,0 +1,445 @@
+//! Keys fetched from a secret manager at startup instead of read from the config file.
+//!
+//! Each key of the `[secrets]` table names the [`SecretSource`] a secret is fetched from:
+//! ```toml
+//! [secrets.stacks_private_key]
+//! source = "vault"
+//! addr = "https://vault.internal:8200"
+//! path = "secret/data/sbtc/coordinator"
+//! field = "stacks_private_key"
+//!
+//! [secrets.network_private_key]
+//! source = "aws_secrets_manager"
+//! secret_id = "sbtc/coordinator"
+//! region = "us-east-1"
+//! field = "network_private_key"
+//! ```
+//! The credentials of the secret managers are read from the environment, never from the file:
+//! `VAULT_TOKEN` (or the variable `token_var` names) for Vault, `AWS_ACCESS_KEY_ID`,
+//! `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN` for AWS. Fetched secrets are only held in
+//! memory, in [`Secret`]s which wipe them when dropped, and only their fingerprints are shown.
+use std::env;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use frost_signer::proxy::{Egress, Error as ProxyError, ProxyConfig};
+use frost_signer::secret::Secret;
+use serde::{Deserialize, Serialize};
+
+/// Environment variable holding the Vault token unless `token_var` names another
+pub const VAULT_TOKEN_VAR: &str = "VAULT_TOKEN";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Environment variable {0} is not set")]
+    MissingVar(String),
+    #[error("HTTP Error fetching {0}: {1}")]
+    HttpError(String, Box<ureq::Error>),
+    #[error("IO Error fetching {0}: {1}")]
+    IOError(String, std::io::Error),
+    /// The secret manager answered, but without the secret
+    #[error("{0} holds no field {1}")]
+    MissingField(String, String),
+    #[error("Proxy Error: {0}")]
+    ProxyError(#[from] ProxyError),
+}
+
+/// Somewhere a secret is fetched from
+pub trait SecretSource {
+    /// Where the secret is, for logs and errors. Never the secret itself.
+    fn describe(&self) -> String;
+    fn fetch(&self) -> Result<Secret<String>, Error>;
+}
+
+/// Where to fetch a secret from, a `[secrets.<key>]` table
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum SecretConfig {
+    /// An environment variable of the coordinator
+    Env { var: String },
+    /// A field of a Vault KV secret, version 1 or 2
+    Vault {
+        /// Vault server URL
+        addr: String,
+        /// Path of the secret, e.g. `secret/data/sbtc` with KV version 2
+        path: String,
+        field: String,
+        /// Environment variable holding the token
+        #[serde(default = "vault_token_var")]
+        token_var: String,
+    },
+    /// An AWS Secrets Manager secret, or a field of its JSON
+    AwsSecretsManager {
+        secret_id: String,
+        region: String,
+        field: Option<String>,
+        /// Endpoint URL in place of the region's, e.g. a VPC endpoint
+        endpoint: Option<String>,
+    },
+}
+
+fn vault_token_var() -> String {
+    VAULT_TOKEN_VAR.to_string()
+}
+
+impl SecretConfig {
+    /// The source to fetch the secret from, its requests sent through `proxy`
+    pub fn source(&self, proxy: &ProxyConfig) -> Result<Box<dyn SecretSource>, Error> {
+        Ok(match self {
+            SecretConfig::Env { var } => Box::new(EnvSource { var: var.clone() }),
+            SecretConfig::Vault {
+                addr,
+                path,
+                field,
+                token_var,
+            } => Box::new(VaultSource {
+                url: format!(
+                    "{}/v1/{}",
+                    addr.trim_end_matches('/'),
+                    path.trim_start_matches('/')
+                ),
+                field: field.clone(),
+                token_var: token_var.clone(),
+                egress: proxy.egress(addr)?,
+            }),
+            SecretConfig::AwsSecretsManager {
+                secret_id,
+                region,
+                field,
+                endpoint,
+            } => {
+                let endpoint = endpoint
+                    .clone()
+                    .unwrap_or_else(|| format!("https://secretsmanager.{region}.amazonaws.com"));
+                Box::new(AwsSource {
+                    egress: proxy.egress(&endpoint)?,
+                    endpoint,
+                    secret_id: secret_id.clone(),
+                    region: region.clone(),
+                    field: field.clone(),
+                })
+            }
+        })
+    }
+}
+
+/// The secrets of the coordinator which can be fetched from a [`SecretSource`], the `[secrets]`
+/// table. A secret fetched must be left out of the file it would otherwise be in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretsConfig {
+    /// In place of the coordinator config's `stacks_private_key`
+    pub stacks_private_key: Option<SecretConfig>,
+    /// In place of the signer config's `network_private_key`, which the coordinator signs its
+    /// messages to the signers with
+    pub network_private_key: Option<SecretConfig>,
+}
+
+/// A secret in an environment variable
+pub struct EnvSource {
+    var: String,
+}
+
+impl SecretSource for EnvSource {
+    fn describe(&self) -> String {
+        format!("environment variable {}", self.var)
+    }
+
+    fn fetch(&self) -> Result<Secret<String>, Error> {
+        env::var(&self.var)
+            .map(Secret::new)
+            .map_err(|_| Error::MissingVar(self.var.clone()))
+    }
+}
+
+/// A field of a secret in a Vault KV engine
+pub struct VaultSource {
+    url: String,
+    field: String,
+    token_var: String,
+    egress: Egress,
+}
+
+impl SecretSource for VaultSource {
+    fn describe(&self) -> String {
+        format!("vault secret {} field {}", self.url, self.field)
+    }
+
+    fn fetch(&self) -> Result<Secret<String>, Error> {
+        let token = Secret::new(
+            env::var(&self.token_var).map_err(|_| Error::MissingVar(self.token_var.clone()))?,
+        );
+        let response = self
+            .egress
+            .get(&self.url)
+            .set("X-Vault-Token", &token)
+            .call()
+            .map_err(|e| Error::HttpError(self.describe(), Box::new(e)))?;
+        let json: serde_json::Value = response
+            .into_json()
+            .map_err(|e| Error::IOError(self.describe(), e))?;
+        // KV version 2 nests the secret's fields one level deeper than version 1
+        let data = &json["data"];
+        data["data"][&self.field]
+            .as_str()
+            .or_else(|| data[&self.field].as_str())
+            .map(|value| Secret::new(value.to_string()))
+            .ok_or_else(|| Error::MissingField(self.url.clone(), self.field.clone()))
+    }
+}
+
+/// A secret in AWS Secrets Manager, fetched with a SigV4 signed `GetSecretValue` request
+pub struct AwsSource {
+    endpoint: String,
+    secret_id: String,
+    region: String,
+    field: Option<String>,
+    egress: Egress,
+}
+
+impl SecretSource for AwsSource {
+    fn describe(&self) -> String {
+        match &self.field {
+            Some(field) => format!("aws secret {} field {}", self.secret_id, field),
+            None => format!("aws secret {}", self.secret_id),
+        }
+    }
+
+    fn fetch(&self) -> Result<Secret<String>, Error> {
+        let var = |name: &str| env::var(name).map_err(|_| Error::MissingVar(name.to_string()));
+        let credentials = AwsCredentials {
+            access_key_id: var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: Secret::new(var("AWS_SECRET_ACCESS_KEY")?),
+            session_token: env::var("AWS_SESSION_TOKEN").ok().map(Secret::new),
+        };
+        let body = serde_json::json!({ "SecretId": self.secret_id }).to_string();
+        let signed = credentials.sign(&self.endpoint, &self.region, &body, unix_now());
+        let mut request = self.egress.post(&self.endpoint);
+        for (name, value) in &signed {
+            request = request.set(name, value);
+        }
+        let response = request
+            .send_string(&body)
+            .map_err(|e| Error::HttpError(self.describe(), Box::new(e)))?;
+        let json: serde_json::Value = response
+            .into_json()
+            .map_err(|e| Error::IOError(self.describe(), e))?;
+        let missing = |field: &str| Error::MissingField(self.secret_id.clone(), field.to_string());
+        let secret = Secret::new(
+            json["SecretString"]
+                .as_str()
+                .ok_or_else(|| missing("SecretString"))?
+                .to_string(),
+        );
+        let Some(field) = &self.field else {
+            return Ok(secret);
+        };
+        let fields: serde_json::Value =
+            serde_json::from_str(&secret).map_err(|_| missing(field))?;
+        fields[field]
+            .as_str()
+            .map(|value| Secret::new(value.to_string()))
+            .ok_or_else(|| missing(field))
+    }
+}
+
+struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: Secret<String>,
+    session_token: Option<Secret<String>>,
+}
+
+impl fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+const AWS_SERVICE: &str = "secretsmanager";
+
+impl AwsCredentials {
+    /// The headers of a SigV4 signed `GetSecretValue` request posting `body` to `endpoint` at
+    /// unix time `now`
+    fn sign(&self, endpoint: &str, region: &str, body: &str, now: u64) -> Vec<(String, String)> {
+        let (date, amz_date) = amz_dates(now);
+        let host = endpoint
+            .split_once("://")
+            .map_or(endpoint, |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        // lowercase names in order, as they are signed
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1".to_string()),
+            ("host", host.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.to_string()));
+        }
+        headers.push(("x-amz-target", "secretsmanager.GetSecretValue".to_string()));
+
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_request = format!(
+            "POST\n/\n\n{canonical_headers}\n{signed_headers}\n{}",
+            sha256_hex(body.as_bytes())
+        );
+        let scope = format!("{date}/{region}/{AWS_SERVICE}/aws4_request");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+            sha256_hex(canonical_request.as_bytes())
+        );
+        let key = format!("AWS4{}", self.secret_access_key.as_str());
+        let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+        let key = hmac_sha256(&key, region.as_bytes());
+        let key = hmac_sha256(&key, AWS_SERVICE.as_bytes());
+        let key = hmac_sha256(&key, b"aws4_request");
+        let signature = hmac_sha256(&key, string_to_sign.as_bytes()).to_hex();
+
+        let mut signed: Vec<(String, String)> = headers
+            .into_iter()
+            .filter(|(name, _)| *name != "host")
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        signed.push((
+            "authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                self.access_key_id
+            ),
+        ));
+        signed
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    sha256::Hash::hash(data).into_inner().to_hex()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(key);
+    engine.input(data);
+    hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// `YYYYMMDD` and `YYYYMMDDTHHMMSSZ` of unix time `now`, in UTC
+fn amz_dates(now: u64) -> (String, String) {
+    let (days, secs) = ((now / 86400) as i64, now % 86400);
+    // civil date of the days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let date = format!("{year:04}{month:02}{day:02}");
+    let time = format!("{:02}{:02}{:02}", secs / 3600, secs % 3600 / 60, secs % 60);
+    (date.clone(), format!("{date}T{time}Z"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_are_configured_by_table() {
+        let secrets: SecretsConfig = toml::from_str(
+            r#"
+            [stacks_private_key]
+            source = "vault"
+            addr = "https://vault.internal:8200/"
+            path = "/secret/data/sbtc"
+            field = "stacks_private_key"
+
+            [network_private_key]
+            source = "env"
+            var = "SECRETS_TEST_NETWORK_KEY"
+            "#,
+        )
+        .unwrap();
+        let proxy = ProxyConfig::default();
+        let vault = secrets.stacks_private_key.unwrap().source(&proxy).unwrap();
+        assert_eq!(
+            vault.describe(),
+            "vault secret https://vault.internal:8200/v1/secret/data/sbtc field stacks_private_key"
+        );
+
+        let env = secrets.network_private_key.unwrap().source(&proxy).unwrap();
+        assert!(matches!(env.fetch(), Err(Error::MissingVar(_))));
+        env::set_var("SECRETS_TEST_NETWORK_KEY", "9aF...network");
+        assert_eq!(env.fetch().unwrap().as_str(), "9aF...network");
+        env::remove_var("SECRETS_TEST_NETWORK_KEY");
+    }
+
+    #[test]
+    fn aws_requests_are_signed_with_sigv4() {
+        assert_eq!(
+            amz_dates(1440938160),
+            ("20150830".to_string(), "20150830T123600Z".to_string())
+        );
+        assert_eq!(amz_dates(951782400).0, "20000229");
+
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: Secret::new("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string()),
+            session_token: None,
+        };
+        let headers = credentials.sign(
+            "https://secretsmanager.us-east-1.amazonaws.com",
+            "us-east-1",
+            r#"{"SecretId":"sbtc/coordinator"}"#,
+            1440938160,
+        );
+        let names: Vec<&str> = headers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "content-type",
+                "x-amz-date",
+                "x-amz-target",
+                "authorization"
+            ]
+        );
+        let authorization = &headers[3].1;
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/secretsmanager/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-target, Signature="
+        ));
+        // the signing key is that of the SigV4 documentation's example
+        assert_eq!(
+            hmac_sha256(
+                &hmac_sha256(
+                    &hmac_sha256(
+                        &hmac_sha256(b"AWS4wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", b"20150830"),
+                        b"us-east-1"
+                    ),
+                    b"iam"
+                ),
+                b"aws4_request"
+            )
+            .to_hex(),
+            "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+        );
+        assert!(!format!("{credentials:?}").contains("EXAMPLEKEY"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Synthetic peg-outs for exercising a new deployment end to end.
+//!
+//! `simulate-peg-out` builds a [`PegOutRequestOp`] no user sent, paying a real recipient from an
+//! output of the peg wallet, and takes it through the steps a queued peg-out goes through: the
+//! stacks burn is built, the signers sign the bitcoin fulfillment and it is broadcast. The op
+//! never enters the peg queue, so a running coordinator does not process it a second time.
+use std::fmt;
+
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::hex::ToHex;
+use bitcoin::hashes::Hash;
+use bitcoin::{Address, Network, OutPoint, XOnlyPublicKey};
+use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType32};
+use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+use blockstack_lib::util::secp256k1::MessageSignature;
+
+use crate::bitcoin_node::BitcoinTransaction;
+use crate::stacks_node::bitcoin_ops::pox_address;
+use crate::stacks_node::PegOutRequestOp;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Peg-outs cannot pay {0}")]
+    UnsupportedRecipient(String),
+}
+
+/// A peg-out of `amount` sats to `recipient`, spending `utxo` of the peg wallet whose taproot
+/// output key is `peg_wallet_key` and paying it `fulfillment_fee`
+pub fn peg_out_request(
+    recipient: &Address,
+    amount: u64,
+    fulfillment_fee: u64,
+    utxo: OutPoint,
+    peg_wallet_key: &XOnlyPublicKey,
+) -> Result<PegOutRequestOp, Error> {
+    let mainnet = recipient.network == Network::Bitcoin;
+    let recipient_address = pox_address(&recipient.script_pubkey(), mainnet)
+        .ok_or_else(|| Error::UnsupportedRecipient(recipient.to_string()))?;
+    Ok(PegOutRequestOp {
+        amount,
+        recipient: recipient_address,
+        // no user signed it
+        signature: MessageSignature([0; 65]),
+        peg_wallet_address: PoxAddress::Addr32(
+            mainnet,
+            PoxAddressType32::P2TR,
+            peg_wallet_key.serialize(),
+        ),
+        fulfillment_fee,
+        memo: vec![],
+        txid: Txid(utxo.txid.into_inner()),
+        vtxindex: utxo.vout,
+        block_height: 0,
+        burn_header_hash: BurnchainHeaderHash([0; 32]),
+    })
+}
+
+/// What a synthetic peg-out produced at each step
+pub struct SimulatedPegOut {
+    pub op: PegOutRequestOp,
+    /// The burn as stacks.js JSON
+    pub burn_tx: String,
+    /// The fulfillment, its witness holding the signers' signature
+    pub fulfill_tx: BitcoinTransaction,
+    pub broadcast: bool,
+}
+
+impl fmt::Display for SimulatedPegOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "peg-out request {}:{} amount={} fulfillment_fee={}",
+            self.op.txid, self.op.vtxindex, self.op.amount, self.op.fulfillment_fee
+        )?;
+        writeln!(f, "burn tx {}", self.burn_tx)?;
+        writeln!(
+            f,
+            "fulfillment tx {} {}",
+            self.fulfill_tx.txid(),
+            serialize_hex(&self.fulfill_tx)
+        )?;
+        for (index, input) in self.fulfill_tx.input.iter().enumerate() {
+            for signature in input.witness.iter() {
+                writeln!(f, "signature input={index} {}", signature.to_hex())?;
+            }
+        }
+        if self.broadcast {
+            write!(f, "fulfillment broadcast")
+        } else {
+            write!(f, "fulfillment not broadcast (dry run)")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockstack_lib::chainstate::stacks::address::PoxAddressType20;
+    use std::str::FromStr;
+
+    #[test]
+    fn synthetic_peg_outs_pay_the_recipient_from_the_peg_wallet() {
+        let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+        let key = XOnlyPublicKey::from_str(
+            "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115",
+        )
+        .unwrap();
+        let utxo = OutPoint::from_str(
+            "0101010101010101010101010101010101010101010101010101010101010101:2",
+        )
+        .unwrap();
+
+        let op = peg_out_request(&recipient, 1000, 10, utxo, &key).unwrap();
+        let mut hash = [0; 20];
+        hash.copy_from_slice(&recipient.script_pubkey().as_bytes()[2..]);
+        assert_eq!(
+            op.recipient,
+            PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, hash)
+        );
+        assert_eq!(
+            op.peg_wallet_address,
+            PoxAddress::Addr32(false, PoxAddressType32::P2TR, key.serialize())
+        );
+        assert_eq!((op.txid, op.vtxindex), (Txid([1; 32]), 2));
+    }
+}
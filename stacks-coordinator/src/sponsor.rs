@@ -0,0 +1,479 @@
+//! Fee sponsorship of user transactions calling the sBTC contract.
+//!
+//! Users build a contract call with sponsored authorization and sign it as its origin, leaving
+//! the sponsor condition empty. [`Sponsor`] checks the call is to one of the whitelisted
+//! functions of the sBTC contract, signs it as sponsor with the coordinator's stacks key, paying
+//! the configured fee, and broadcasts it. [`Sponsor::serve`] accepts the transactions over a
+//! local endpoint:
+//!
+//! ```text
+//! curl -X POST --data <hex of the serialized transaction> http://<listen_addr>/sponsor
+//! ```
+//!
+//! The fees paid are capped per period, for each origin and for all of them, so that an endpoint
+//! anyone can reach cannot drain the sponsor's STX.
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use blockstack_lib::address::{
+    C32_ADDRESS_VERSION_MAINNET_MULTISIG, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use blockstack_lib::{
+    chainstate::stacks::{
+        StacksPrivateKey, StacksPublicKey, StacksTransaction, StacksTransactionSigner,
+        TransactionAuth, TransactionPayload, TransactionSpendingCondition, TransactionVersion,
+    },
+    codec::{Error as CodecError, StacksMessageCodec},
+    types::chainstate::StacksAddress,
+    util::hash::hex_bytes,
+};
+use frost_signer::control::{write_response, RequestHead, Response, IO_TIMEOUT};
+use frost_signer::logging::target::WALLET;
+use tracing::{info, warn};
+
+use crate::stacks_node::{Error as StacksNodeError, StacksNode};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid transaction encoding: {0}")]
+    InvalidEncoding(String),
+    #[error("Transaction does not use sponsored authorization")]
+    NotSponsored,
+    #[error("Transaction is not a contract call")]
+    NotContractCall,
+    #[error("Transaction calls {0}, not the sBTC contract")]
+    WrongContract(String),
+    #[error("Function {0} is not sponsored")]
+    FunctionNotAllowed(String),
+    #[error("Transaction is for another network than the sBTC contract")]
+    WrongNetwork,
+    #[error("Sponsorship budget of {0} is spent for this period")]
+    OverBudget(String),
+    #[error("Invalid sponsor key: {0}")]
+    InvalidKey(String),
+    #[error("Invalid sBTC contract: {0}")]
+    InvalidContract(String),
+    #[error("Sponsor signing failed: {0}")]
+    Signing(String),
+    #[error("Stacks Node Error: {0}")]
+    StacksNodeError(#[from] StacksNodeError),
+}
+
+impl Error {
+    /// Whether the user's transaction is at fault rather than the sponsor
+    pub fn is_rejection(&self) -> bool {
+        matches!(
+            self,
+            Error::InvalidEncoding(_)
+                | Error::NotSponsored
+                | Error::NotContractCall
+                | Error::WrongContract(_)
+                | Error::FunctionNotAllowed(_)
+                | Error::WrongNetwork
+        )
+    }
+}
+
+impl From<CodecError> for Error {
+    fn from(e: CodecError) -> Self {
+        Error::InvalidEncoding(e.to_string())
+    }
+}
+
+/// A `[sponsor]` config table
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+pub struct SponsorConfig {
+    /// Local address accepting transactions at `POST /sponsor`, e.g. "127.0.0.1:9902"
+    pub listen_addr: String,
+    /// sBTC contract functions whose calls are sponsored
+    pub functions: Vec<String>,
+    /// Fee paid for each sponsored transaction, in micro-STX
+    #[serde(default = "default_tx_fee")]
+    pub tx_fee: u64,
+    /// Most micro-STX paid in fees for the transactions of one origin per period, unlimited
+    /// unless set
+    #[serde(default)]
+    pub origin_budget: Option<u64>,
+    /// Most micro-STX paid in fees for all transactions per period, unlimited unless set
+    #[serde(default)]
+    pub total_budget: Option<u64>,
+    /// Length of a budget period, in seconds
+    #[serde(default = "default_budget_period_secs")]
+    pub budget_period_secs: u64,
+}
+
+fn default_tx_fee() -> u64 {
+    10_000
+}
+
+fn default_budget_period_secs() -> u64 {
+    24 * 60 * 60
+}
+
+/// Fees paid in the current budget period
+#[derive(Default)]
+struct Spent {
+    since: Option<Instant>,
+    total: u64,
+    by_origin: HashMap<StacksAddress, u64>,
+}
+
+pub struct Sponsor<N> {
+    stacks_node: N,
+    private_key: StacksPrivateKey,
+    contract_address: StacksAddress,
+    contract_name: String,
+    functions: Vec<String>,
+    tx_fee: u64,
+    origin_budget: Option<u64>,
+    total_budget: Option<u64>,
+    budget_period: Duration,
+    spent: Mutex<Spent>,
+}
+
+impl<N: StacksNode> Sponsor<N> {
+    /// Sponsor calls to `functions` of `contract`, e.g. "SP000...000.sbtc-alpha", with the
+    /// hex encoded stacks `private_key`
+    pub fn new(
+        stacks_node: N,
+        private_key: &str,
+        contract: &str,
+        config: &SponsorConfig,
+    ) -> Result<Self, Error> {
+        let private_key = StacksPrivateKey::from_hex(private_key)
+            .map_err(|e| Error::InvalidKey(e.to_string()))?;
+        let (address, name) = contract
+            .split_once('.')
+            .ok_or_else(|| Error::InvalidContract(contract.to_string()))?;
+        let contract_address = StacksAddress::from_string(address)
+            .ok_or_else(|| Error::InvalidContract(contract.to_string()))?;
+        Ok(Self {
+            stacks_node,
+            private_key,
+            contract_address,
+            contract_name: name.to_string(),
+            functions: config.functions.clone(),
+            tx_fee: config.tx_fee,
+            origin_budget: config.origin_budget,
+            total_budget: config.total_budget,
+            budget_period: Duration::from_secs(config.budget_period_secs),
+            spent: Mutex::default(),
+        })
+    }
+
+    /// Check `tx` calls a sponsored function within the budget, then sign it as sponsor and
+    /// broadcast it
+    pub fn sponsor(&self, tx: &StacksTransaction) -> Result<StacksTransaction, Error> {
+        self.validate(tx)?;
+        // held until the fee is counted, so that concurrent calls cannot both fit the budget
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        let origin = tx.origin_address();
+        self.check_budget(&mut spent, &origin)?;
+        let mut condition = TransactionSpendingCondition::new_singlesig_p2pkh(
+            StacksPublicKey::from_private(&self.private_key),
+        )
+        .ok_or_else(|| Error::InvalidKey("not a compressed key".to_string()))?;
+        let TransactionSpendingCondition::Singlesig(singlesig) = &condition else {
+            return Err(Error::InvalidKey("not a singlesig key".to_string()));
+        };
+        let version = match tx.version {
+            TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+            TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+        };
+        let sponsor_address = StacksAddress::new(version, singlesig.signer);
+        condition.set_nonce(self.stacks_node.next_nonce(sponsor_address)?);
+        condition.set_tx_fee(self.tx_fee);
+
+        let mut signer = StacksTransactionSigner::new_sponsor(tx, condition)
+            .map_err(|e| Error::Signing(e.to_string()))?;
+        signer
+            .sign_sponsor(&self.private_key)
+            .map_err(|e| Error::Signing(e.to_string()))?;
+        let sponsored = signer
+            .get_tx()
+            .ok_or_else(|| Error::Signing("incomplete transaction".to_string()))?;
+        self.stacks_node.broadcast_transaction(&sponsored)?;
+        spent.total += self.tx_fee;
+        *spent.by_origin.entry(origin).or_default() += self.tx_fee;
+        info!(
+            target: WALLET,
+            "sponsored transaction {} with a fee of {}",
+            sponsored.txid().to_hex(),
+            self.tx_fee
+        );
+        Ok(sponsored)
+    }
+
+    /// Fail if paying the fee of one more transaction of `origin` would exceed a budget,
+    /// starting a new period if the current one is over
+    fn check_budget(&self, spent: &mut Spent, origin: &StacksAddress) -> Result<(), Error> {
+        let now = Instant::now();
+        let current = spent
+            .since
+            .is_some_and(|since| now.duration_since(since) < self.budget_period);
+        if !current {
+            *spent = Spent {
+                since: Some(now),
+                ..Default::default()
+            };
+        }
+        let over = |budget: Option<u64>, spent: u64| {
+            budget.is_some_and(|budget| spent.saturating_add(self.tx_fee) > budget)
+        };
+        if over(self.total_budget, spent.total) {
+            return Err(Error::OverBudget("the sponsor".to_string()));
+        }
+        let by_origin = spent.by_origin.get(origin).copied().unwrap_or_default();
+        if over(self.origin_budget, by_origin) {
+            return Err(Error::OverBudget(origin.to_string()));
+        }
+        Ok(())
+    }
+
+    /// The network of the sBTC contract
+    fn network(&self) -> TransactionVersion {
+        match self.contract_address.version {
+            C32_ADDRESS_VERSION_MAINNET_SINGLESIG | C32_ADDRESS_VERSION_MAINNET_MULTISIG => {
+                TransactionVersion::Mainnet
+            }
+            _ => TransactionVersion::Testnet,
+        }
+    }
+
+    fn validate(&self, tx: &StacksTransaction) -> Result<(), Error> {
+        if tx.version != self.network() {
+            return Err(Error::WrongNetwork);
+        }
+        if !matches!(tx.auth, TransactionAuth::Sponsored(_, _)) {
+            return Err(Error::NotSponsored);
+        }
+        let TransactionPayload::ContractCall(call) = &tx.payload else {
+            return Err(Error::NotContractCall);
+        };
+        if call.address != self.contract_address
+            || call.contract_name.as_str() != self.contract_name
+        {
+            return Err(Error::WrongContract(
+                call.contract_name.as_str().to_string(),
+            ));
+        }
+        let function = call.function_name.as_str();
+        if !self.functions.iter().any(|allowed| allowed == function) {
+            return Err(Error::FunctionNotAllowed(function.to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Decode a hex encoded, consensus serialized transaction
+pub fn decode(hex: &str) -> Result<StacksTransaction, Error> {
+    let bytes = hex_bytes(hex.trim()).map_err(|e| Error::InvalidEncoding(e.to_string()))?;
+    Ok(StacksTransaction::consensus_deserialize(&mut &bytes[..])?)
+}
+
+impl<N: StacksNode + Send + 'static> Sponsor<N> {
+    /// Serve `POST /sponsor` on `addr` from a background thread, answering with the txid
+    pub fn serve(self, addr: impl ToSocketAddrs) -> std::io::Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(addr)?;
+        info!(
+            target: WALLET,
+            "sponsor endpoint listening on {}",
+            listener.local_addr()?
+        );
+        Ok(thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|stream| handle(stream, &self));
+                if let Err(e) = result {
+                    warn!(target: WALLET, "sponsor request failed: {}", e);
+                }
+            }
+        }))
+    }
+}
+
+fn handle<N: StacksNode>(stream: TcpStream, sponsor: &Sponsor<N>) -> std::io::Result<()> {
+    // requests are served one at a time, so none may hold up the others for long
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let head = RequestHead::read(&mut reader)?;
+    let (status, body) = if head.too_large() {
+        ("413 Payload Too Large", String::new())
+    } else {
+        let body = head.read_body(&mut reader)?;
+        respond(&head.request_line, &body, sponsor)
+    };
+    write_response(reader.get_mut(), &Response::text(status, body))
+}
+
+fn respond<N: StacksNode>(
+    request_line: &str,
+    body: &str,
+    sponsor: &Sponsor<N>,
+) -> (&'static str, String) {
+    let mut parts = request_line.split_whitespace();
+    if (parts.next(), parts.next()) != (Some("POST"), Some("/sponsor")) {
+        return ("404 Not Found", String::new());
+    }
+    match decode(body).and_then(|tx| sponsor.sponsor(&tx)) {
+        Ok(tx) => ("200 OK", format!("{}\n", tx.txid().to_hex())),
+        Err(e) if e.is_rejection() => ("400 Bad Request", format!("{e}\n")),
+        Err(e @ Error::OverBudget(_)) => ("429 Too Many Requests", format!("{e}\n")),
+        Err(e) => {
+            warn!(target: WALLET, "sponsoring failed: {}", e);
+            ("500 Internal Server Error", format!("{e}\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blockstack_lib::chainstate::stacks::{
+        TransactionAnchorMode, TransactionContractCall, TransactionPostConditionMode,
+    };
+    use blockstack_lib::util::hash::Hash160;
+    use blockstack_lib::vm::{ClarityName, ContractName};
+
+    use crate::stacks_node::MockStacksNode;
+
+    const PRIVATE_KEY: &str = "b244296d5907de9864c0b0d51f98a13c52890be0404e83f273144cd5b9960eed01";
+
+    fn config() -> SponsorConfig {
+        SponsorConfig {
+            listen_addr: "127.0.0.1:0".to_string(),
+            functions: vec!["transfer".to_string()],
+            tx_fee: 2_000,
+            origin_budget: None,
+            total_budget: None,
+            budget_period_secs: 60,
+        }
+    }
+
+    fn call(contract_address: StacksAddress, function: &str) -> StacksTransaction {
+        let origin = TransactionSpendingCondition::new_singlesig_p2pkh(
+            StacksPublicKey::from_private(&StacksPrivateKey::from_hex(PRIVATE_KEY).unwrap()),
+        )
+        .unwrap();
+        StacksTransaction {
+            version: TransactionVersion::Testnet,
+            chain_id: 0x80000000,
+            auth: TransactionAuth::Sponsored(origin.clone(), origin),
+            anchor_mode: TransactionAnchorMode::Any,
+            post_condition_mode: TransactionPostConditionMode::Deny,
+            post_conditions: vec![],
+            payload: TransactionPayload::ContractCall(TransactionContractCall {
+                address: contract_address,
+                contract_name: ContractName::try_from("sbtc-alpha".to_string()).unwrap(),
+                function_name: ClarityName::try_from(function.to_string()).unwrap(),
+                function_args: vec![],
+            }),
+        }
+    }
+
+    fn sponsor(stacks_node: MockStacksNode) -> (Sponsor<MockStacksNode>, StacksAddress) {
+        let contract = "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.sbtc-alpha";
+        let sponsor = Sponsor::new(stacks_node, PRIVATE_KEY, contract, &config()).unwrap();
+        let contract_address = sponsor.contract_address;
+        (sponsor, contract_address)
+    }
+
+    #[test]
+    fn sponsors_whitelisted_calls_to_the_sbtc_contract() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_next_nonce()
+            .times(1)
+            .returning(|_| Ok(5));
+        stacks_node
+            .expect_broadcast_transaction()
+            .times(1)
+            .returning(|_| Ok(()));
+        let (sponsor, contract_address) = sponsor(stacks_node);
+
+        let tx = sponsor
+            .sponsor(&call(contract_address, "transfer"))
+            .unwrap();
+        match tx.auth {
+            TransactionAuth::Sponsored(_, TransactionSpendingCondition::Singlesig(condition)) => {
+                assert_eq!(condition.nonce, 5);
+                assert_eq!(condition.tx_fee, 2_000);
+            }
+            auth => panic!("expected a singlesig sponsor, got {auth:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_other_calls_without_broadcasting() {
+        // any call to the node fails the test
+        let (sponsor, contract_address) = sponsor(MockStacksNode::new());
+
+        let mut standard = call(contract_address, "transfer");
+        if let TransactionAuth::Sponsored(origin, _) = standard.auth {
+            standard.auth = TransactionAuth::Standard(origin);
+        }
+        assert!(matches!(
+            sponsor.sponsor(&standard),
+            Err(Error::NotSponsored)
+        ));
+        assert!(matches!(
+            sponsor.sponsor(&call(contract_address, "burn!")),
+            Err(Error::FunctionNotAllowed(function)) if function == "burn!"
+        ));
+        let other = StacksAddress::new(26, Hash160([9; 20]));
+        assert!(matches!(
+            sponsor.sponsor(&call(other, "transfer")),
+            Err(Error::WrongContract(_))
+        ));
+        assert_eq!(
+            respond("GET /status HTTP/1.1\r\n", "", &sponsor).0,
+            "404 Not Found"
+        );
+        assert_eq!(
+            respond("POST /sponsor HTTP/1.1\r\n", "not hex", &sponsor).0,
+            "400 Bad Request"
+        );
+    }
+
+    #[test]
+    fn rejects_transactions_for_another_network() {
+        let (sponsor, contract_address) = sponsor(MockStacksNode::new());
+        let mut mainnet = call(contract_address, "transfer");
+        mainnet.version = TransactionVersion::Mainnet;
+        assert!(matches!(
+            sponsor.sponsor(&mainnet),
+            Err(Error::WrongNetwork)
+        ));
+    }
+
+    #[test]
+    fn stops_sponsoring_once_the_budget_is_spent() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_next_nonce().returning(|_| Ok(5));
+        stacks_node
+            .expect_broadcast_transaction()
+            .times(2)
+            .returning(|_| Ok(()));
+        let contract = "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.sbtc-alpha";
+        let config = SponsorConfig {
+            origin_budget: Some(4_000),
+            ..config()
+        };
+        let sponsor = Sponsor::new(stacks_node, PRIVATE_KEY, contract, &config).unwrap();
+        let tx = call(sponsor.contract_address, "transfer");
+
+        sponsor.sponsor(&tx).unwrap();
+        sponsor.sponsor(&tx).unwrap();
+        assert!(matches!(sponsor.sponsor(&tx), Err(Error::OverBudget(_))));
+        let hex = blockstack_lib::util::hash::to_hex(&tx.serialize_to_vec());
+        assert_eq!(
+            respond("POST /sponsor HTTP/1.1\r\n", &hex, &sponsor).0,
+            "429 Too Many Requests"
+        );
+    }
+}
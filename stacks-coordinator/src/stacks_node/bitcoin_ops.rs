@@ -0,0 +1,337 @@
+//! sBTC peg operations read straight from bitcoin blocks.
+//!
+//! The wire formats follow the sBTC spec as implemented by the stacks node's burnchain op
+//! parser: the first output is an `OP_RETURN` carrying the stacks magic bytes, an opcode and
+//! the op's payload, and the following outputs carry the peg wallet and recipient addresses.
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::hashes::Hash;
+use bitcoin::{Block, Script, Transaction};
+use blockstack_lib::address::{
+    AddressHashMode, C32_ADDRESS_VERSION_MAINNET_SINGLESIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20, PoxAddressType32};
+use blockstack_lib::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+use blockstack_lib::util::hash::Hash160;
+use blockstack_lib::util::secp256k1::MessageSignature;
+use blockstack_lib::vm::types::{
+    PrincipalData, QualifiedContractIdentifier, StandardPrincipalData,
+};
+use blockstack_lib::vm::ContractName;
+
+use crate::stacks_node::{PegInOp, PegOutRequestOp};
+
+/// Magic bytes of stacks burn ops on bitcoin mainnet
+pub const MAINNET_MAGIC: [u8; 2] = *b"X2";
+
+const PEG_IN_OPCODE: u8 = b'<';
+const PEG_OUT_REQUEST_OPCODE: u8 = b'>';
+
+const C32_ADDRESS_VERSION_MAINNET_MULTISIG: u8 = 20;
+const C32_ADDRESS_VERSION_TESTNET_MULTISIG: u8 = 21;
+
+/// Address version and hash160 of the recipient's stacks address
+const PRINCIPAL_LEN: usize = 21;
+/// Optional contract name following the recipient address, zero padded
+const CONTRACT_NAME_LEN: usize = 40;
+const AMOUNT_LEN: usize = 8;
+const SIGNATURE_LEN: usize = 65;
+
+/// Peg ops found in one bitcoin block, in block order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockOps {
+    pub peg_in: Vec<PegInOp>,
+    pub peg_out_request: Vec<PegOutRequestOp>,
+}
+
+/// Where a transaction sits in the burnchain
+struct Position {
+    txid: Txid,
+    vtxindex: u32,
+    block_height: u64,
+    burn_header_hash: BurnchainHeaderHash,
+}
+
+/// Parse the peg ops out of `block`, skipping transactions which are not valid peg ops
+pub fn parse_block(block: &Block, block_height: u64, magic: &[u8; 2]) -> BlockOps {
+    let mainnet = *magic == MAINNET_MAGIC;
+    // the stacks node keeps the header hash in internal byte order and reverses txids
+    let burn_header_hash = BurnchainHeaderHash(block.block_hash().into_inner());
+    let mut ops = BlockOps::default();
+    for (vtxindex, tx) in block.txdata.iter().enumerate() {
+        let Some(data) = tx
+            .output
+            .first()
+            .and_then(|out| op_return_data(&out.script_pubkey))
+        else {
+            continue;
+        };
+        let Some((opcode, payload)) = data.strip_prefix(magic).and_then(|d| d.split_first()) else {
+            continue;
+        };
+        let mut txid = tx.txid().into_inner();
+        txid.reverse();
+        let position = Position {
+            txid: Txid(txid),
+            vtxindex: vtxindex as u32,
+            block_height,
+            burn_header_hash,
+        };
+        match *opcode {
+            PEG_IN_OPCODE => ops.peg_in.extend(peg_in(tx, payload, mainnet, position)),
+            PEG_OUT_REQUEST_OPCODE => ops
+                .peg_out_request
+                .extend(peg_out_request(tx, payload, mainnet, position)),
+            _ => {}
+        }
+    }
+    ops
+}
+
+fn peg_in(tx: &Transaction, payload: &[u8], mainnet: bool, at: Position) -> Option<PegInOp> {
+    let version = *payload.first()?;
+    let hash: [u8; 20] = payload.get(1..PRINCIPAL_LEN)?.try_into().ok()?;
+    let issuer = StandardPrincipalData(version, hash);
+    let contract_name = payload
+        .get(PRINCIPAL_LEN..PRINCIPAL_LEN + CONTRACT_NAME_LEN)
+        .map(|name| std::str::from_utf8(name).map(|name| name.trim_end_matches('\0')))
+        .transpose()
+        .ok()?
+        .unwrap_or_default();
+    let recipient = if contract_name.is_empty() {
+        PrincipalData::Standard(issuer)
+    } else {
+        PrincipalData::Contract(QualifiedContractIdentifier {
+            issuer,
+            name: ContractName::try_from(contract_name.to_string()).ok()?,
+        })
+    };
+    let peg_wallet = tx.output.get(1)?;
+    Some(PegInOp {
+        recipient,
+        peg_wallet_address: pox_address(&peg_wallet.script_pubkey, mainnet)?,
+        amount: peg_wallet.value,
+        memo: memo(payload, PRINCIPAL_LEN + CONTRACT_NAME_LEN),
+        txid: at.txid,
+        vtxindex: at.vtxindex,
+        block_height: at.block_height,
+        burn_header_hash: at.burn_header_hash,
+    })
+}
+
+fn peg_out_request(
+    tx: &Transaction,
+    payload: &[u8],
+    mainnet: bool,
+    at: Position,
+) -> Option<PegOutRequestOp> {
+    let amount = u64::from_be_bytes(payload.get(..AMOUNT_LEN)?.try_into().ok()?);
+    let signature: [u8; SIGNATURE_LEN] = payload
+        .get(AMOUNT_LEN..AMOUNT_LEN + SIGNATURE_LEN)?
+        .try_into()
+        .ok()?;
+    let recipient = tx.output.get(1)?;
+    let peg_wallet = tx.output.get(2)?;
+    Some(PegOutRequestOp {
+        amount,
+        recipient: pox_address(&recipient.script_pubkey, mainnet)?,
+        signature: MessageSignature(signature),
+        peg_wallet_address: pox_address(&peg_wallet.script_pubkey, mainnet)?,
+        fulfillment_fee: peg_wallet.value,
+        memo: memo(payload, AMOUNT_LEN + SIGNATURE_LEN),
+        txid: at.txid,
+        vtxindex: at.vtxindex,
+        block_height: at.block_height,
+        burn_header_hash: at.burn_header_hash,
+    })
+}
+
+fn memo(payload: &[u8], offset: usize) -> Vec<u8> {
+    payload.get(offset..).unwrap_or_default().to_vec()
+}
+
+/// The data pushed after `OP_RETURN`
+pub(crate) fn op_return_data(script: &Script) -> Option<&[u8]> {
+    if !script.is_op_return() {
+        return None;
+    }
+    match script.instructions().nth(1)? {
+        Ok(Instruction::PushBytes(data)) => Some(data),
+        _ => None,
+    }
+}
+
+pub(crate) fn pox_address(script: &Script, mainnet: bool) -> Option<PoxAddress> {
+    let bytes = script.as_bytes();
+    let standard = |version: u8, hash: &[u8], mode: AddressHashMode| {
+        Some(PoxAddress::Standard(
+            StacksAddress::new(version, Hash160(hash.try_into().ok()?)),
+            Some(mode),
+        ))
+    };
+    if script.is_p2pkh() {
+        let version = if mainnet {
+            C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+        } else {
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+        };
+        standard(version, &bytes[3..23], AddressHashMode::SerializeP2PKH)
+    } else if script.is_p2sh() {
+        let version = if mainnet {
+            C32_ADDRESS_VERSION_MAINNET_MULTISIG
+        } else {
+            C32_ADDRESS_VERSION_TESTNET_MULTISIG
+        };
+        standard(version, &bytes[2..22], AddressHashMode::SerializeP2SH)
+    } else if script.is_v0_p2wpkh() {
+        let hash = bytes[2..22].try_into().ok()?;
+        Some(PoxAddress::Addr20(mainnet, PoxAddressType20::P2WPKH, hash))
+    } else if script.is_v0_p2wsh() {
+        let hash = bytes[2..34].try_into().ok()?;
+        Some(PoxAddress::Addr32(mainnet, PoxAddressType32::P2WSH, hash))
+    } else if script.is_v1_p2tr() {
+        let key = bytes[2..34].try_into().ok()?;
+        Some(PoxAddress::Addr32(mainnet, PoxAddressType32::P2TR, key))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::blockdata::opcodes::all::OP_RETURN;
+    use bitcoin::blockdata::script::Builder;
+    use bitcoin::hashes::hash160;
+    use bitcoin::{BlockHeader, PackedLockTime, PubkeyHash, TxMerkleNode, TxOut, WPubkeyHash};
+
+    use super::*;
+
+    const REGTEST_MAGIC: [u8; 2] = *b"id";
+
+    fn tx(data: &[u8], outputs: Vec<TxOut>) -> Transaction {
+        let op_return = TxOut {
+            value: 0,
+            script_pubkey: Builder::new()
+                .push_opcode(OP_RETURN)
+                .push_slice(data)
+                .into_script(),
+        };
+        Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![],
+            output: [vec![op_return], outputs].concat(),
+        }
+    }
+
+    fn block(txdata: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                version: 1,
+                prev_blockhash: Hash::all_zeros(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: 0,
+                nonce: 0,
+            },
+            txdata,
+        }
+    }
+
+    fn p2wpkh(byte: u8, value: u64) -> TxOut {
+        let hash = WPubkeyHash::from_hash(hash160::Hash::from_inner([byte; 20]));
+        TxOut {
+            value,
+            script_pubkey: Script::new_v0_p2wpkh(&hash),
+        }
+    }
+
+    fn p2pkh(byte: u8, value: u64) -> TxOut {
+        let hash = PubkeyHash::from_hash(hash160::Hash::from_inner([byte; 20]));
+        TxOut {
+            value,
+            script_pubkey: Script::new_p2pkh(&hash),
+        }
+    }
+
+    #[test]
+    fn parses_peg_ins() {
+        let contract = format!("{:\0<40}", "sbtc-vault");
+        let to_address = [&b"id<"[..], &[26], &[7; 20]].concat();
+        let to_contract = [&to_address[..], contract.as_bytes(), b"memo"].concat();
+        let block = block(vec![
+            tx(&to_address, vec![p2wpkh(1, 5_000)]),
+            // foreign magic and a missing peg wallet output are skipped
+            tx(
+                &[&b"T2<"[..], &[26], &[7; 20]].concat(),
+                vec![p2wpkh(1, 5_000)],
+            ),
+            tx(&to_address, vec![]),
+            tx(&to_contract, vec![p2pkh(2, 6_000)]),
+        ]);
+
+        let ops = parse_block(&block, 42, &REGTEST_MAGIC);
+        assert!(ops.peg_out_request.is_empty());
+        assert_eq!(ops.peg_in.len(), 2);
+
+        let first = &ops.peg_in[0];
+        assert_eq!(
+            first.recipient,
+            PrincipalData::Standard(StandardPrincipalData(26, [7; 20]))
+        );
+        assert_eq!(
+            first.peg_wallet_address,
+            PoxAddress::Addr20(false, PoxAddressType20::P2WPKH, [1; 20])
+        );
+        assert_eq!(first.amount, 5_000);
+        assert_eq!(first.block_height, 42);
+        assert_eq!(first.vtxindex, 0);
+        let mut txid = block.txdata[0].txid().into_inner();
+        txid.reverse();
+        assert_eq!(first.txid, Txid(txid));
+
+        let second = &ops.peg_in[1];
+        assert_eq!(second.vtxindex, 3);
+        assert_eq!(second.memo, b"memo".to_vec());
+        assert!(matches!(
+            &second.recipient,
+            PrincipalData::Contract(contract) if contract.name.as_str() == "sbtc-vault"
+        ));
+        assert_eq!(
+            second.peg_wallet_address,
+            PoxAddress::Standard(
+                StacksAddress::new(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, Hash160([2; 20])),
+                Some(AddressHashMode::SerializeP2PKH)
+            )
+        );
+    }
+
+    #[test]
+    fn parses_peg_out_requests() {
+        let data = [&b"X2>"[..], &70_000u64.to_be_bytes(), &[9; 65]].concat();
+        let block = block(vec![
+            tx(&data, vec![p2wpkh(3, 546), p2wpkh(4, 10_000)]),
+            // the fulfillment fee output is missing
+            tx(&data, vec![p2wpkh(3, 546)]),
+            // truncated signature
+            tx(&data[..40], vec![p2wpkh(3, 546), p2wpkh(4, 10_000)]),
+        ]);
+
+        let ops = parse_block(&block, 7, &MAINNET_MAGIC);
+        assert!(ops.peg_in.is_empty());
+        assert_eq!(ops.peg_out_request.len(), 1);
+        let op = &ops.peg_out_request[0];
+        assert_eq!(op.amount, 70_000);
+        assert_eq!(op.signature, MessageSignature([9; 65]));
+        assert_eq!(
+            op.recipient,
+            PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [3; 20])
+        );
+        assert_eq!(
+            op.peg_wallet_address,
+            PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [4; 20])
+        );
+        assert_eq!(op.fulfillment_fee, 10_000);
+        assert!(op.memo.is_empty());
+    }
+}
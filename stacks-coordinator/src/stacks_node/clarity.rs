@@ -0,0 +1,261 @@
+//! The few Clarity values exchanged with read-only contract calls, in their consensus
+//! serialization, hex encoded with a `0x` prefix as the stacks node's RPC API takes them.
+//! Values printed by contract events are decoded whole, see [`decode`].
+use std::collections::BTreeMap;
+
+use bitcoin::hashes::hex::{FromHex, ToHex};
+use blockstack_lib::vm::types::{
+    PrincipalData, QualifiedContractIdentifier, StandardPrincipalData,
+};
+use blockstack_lib::vm::ContractName;
+
+use crate::stacks_node::Error;
+
+const TYPE_INT: u8 = 0x00;
+const TYPE_UINT: u8 = 0x01;
+const TYPE_BUFFER: u8 = 0x02;
+const TYPE_TRUE: u8 = 0x03;
+const TYPE_FALSE: u8 = 0x04;
+const TYPE_STANDARD_PRINCIPAL: u8 = 0x05;
+const TYPE_CONTRACT_PRINCIPAL: u8 = 0x06;
+const TYPE_OK: u8 = 0x07;
+const TYPE_ERR: u8 = 0x08;
+const TYPE_NONE: u8 = 0x09;
+const TYPE_SOME: u8 = 0x0a;
+const TYPE_LIST: u8 = 0x0b;
+const TYPE_TUPLE: u8 = 0x0c;
+const TYPE_STRING_ASCII: u8 = 0x0d;
+const TYPE_STRING_UTF8: u8 = 0x0e;
+
+/// Clarity nests values at most this deep
+const MAX_DEPTH: usize = 32;
+
+/// A decoded Clarity value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Int(i128),
+    UInt(u128),
+    Bool(bool),
+    Buffer(Vec<u8>),
+    Principal(PrincipalData),
+    Optional(Option<Box<Value>>),
+    Response(Result<Box<Value>, Box<Value>>),
+    List(Vec<Value>),
+    Tuple(BTreeMap<String, Value>),
+    StringAscii(String),
+    StringUtf8(String),
+}
+
+impl Value {
+    /// The field `name` of a tuple
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Tuple(fields) => fields.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_uint(&self) -> Option<u128> {
+        match self {
+            Value::UInt(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_buffer(&self) -> Option<&[u8]> {
+        match self {
+            Value::Buffer(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::StringAscii(s) | Value::StringUtf8(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_principal(&self) -> Option<&PrincipalData> {
+        match self {
+            Value::Principal(principal) => Some(principal),
+            _ => None,
+        }
+    }
+}
+
+/// A `string-ascii` argument
+pub fn string_ascii(s: &str) -> Result<String, Error> {
+    if !s.is_ascii() {
+        return Err(Error::InvalidClarityValue(s.to_string()));
+    }
+    let mut bytes = vec![TYPE_STRING_ASCII];
+    bytes.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(s.as_bytes());
+    Ok(format!("0x{}", bytes.to_hex()))
+}
+
+/// A `bool` result
+pub fn bool_value(value: &str) -> Result<bool, Error> {
+    let bytes = Vec::<u8>::from_hex(value.trim_start_matches("0x"))
+        .map_err(|_| Error::InvalidClarityValue(value.to_string()))?;
+    match bytes.as_slice() {
+        [TYPE_TRUE] => Ok(true),
+        [TYPE_FALSE] => Ok(false),
+        _ => Err(Error::InvalidClarityValue(value.to_string())),
+    }
+}
+
+/// Decode a hex encoded value, with or without a `0x` prefix
+pub fn decode(value: &str) -> Result<Value, Error> {
+    let invalid = || Error::InvalidClarityValue(value.to_string());
+    let bytes = Vec::<u8>::from_hex(value.trim_start_matches("0x")).map_err(|_| invalid())?;
+    let mut reader = Reader(&bytes);
+    match reader.value(0) {
+        Some(decoded) if reader.0.is_empty() => Ok(decoded),
+        _ => Err(invalid()),
+    }
+}
+
+/// The bytes of a serialized value not yet decoded
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        Some(u32::from_be_bytes(self.array()?) as usize)
+    }
+
+    fn standard_principal(&mut self) -> Option<StandardPrincipalData> {
+        Some(StandardPrincipalData(self.u8()?, self.array()?))
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Value> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let value = match self.u8()? {
+            TYPE_INT => Value::Int(i128::from_be_bytes(self.array()?)),
+            TYPE_UINT => Value::UInt(u128::from_be_bytes(self.array()?)),
+            TYPE_BUFFER => {
+                let len = self.len()?;
+                Value::Buffer(self.take(len)?.to_vec())
+            }
+            TYPE_TRUE => Value::Bool(true),
+            TYPE_FALSE => Value::Bool(false),
+            TYPE_STANDARD_PRINCIPAL => {
+                Value::Principal(PrincipalData::Standard(self.standard_principal()?))
+            }
+            TYPE_CONTRACT_PRINCIPAL => {
+                let issuer = self.standard_principal()?;
+                let len = self.u8()? as usize;
+                let name = std::str::from_utf8(self.take(len)?).ok()?;
+                Value::Principal(PrincipalData::Contract(QualifiedContractIdentifier {
+                    issuer,
+                    name: ContractName::try_from(name.to_string()).ok()?,
+                }))
+            }
+            TYPE_OK => Value::Response(Ok(Box::new(self.value(depth + 1)?))),
+            TYPE_ERR => Value::Response(Err(Box::new(self.value(depth + 1)?))),
+            TYPE_NONE => Value::Optional(None),
+            TYPE_SOME => Value::Optional(Some(Box::new(self.value(depth + 1)?))),
+            TYPE_LIST => {
+                let len = self.len()?;
+                let mut items = vec![];
+                for _ in 0..len {
+                    items.push(self.value(depth + 1)?);
+                }
+                Value::List(items)
+            }
+            TYPE_TUPLE => {
+                let len = self.len()?;
+                let mut fields = BTreeMap::new();
+                for _ in 0..len {
+                    let name_len = self.u8()? as usize;
+                    let name = std::str::from_utf8(self.take(name_len)?).ok()?;
+                    fields.insert(name.to_string(), self.value(depth + 1)?);
+                }
+                Value::Tuple(fields)
+            }
+            TYPE_STRING_ASCII => {
+                let len = self.len()?;
+                let s = std::str::from_utf8(self.take(len)?).ok()?;
+                if !s.is_ascii() {
+                    return None;
+                }
+                Value::StringAscii(s.to_string())
+            }
+            TYPE_STRING_UTF8 => {
+                let len = self.len()?;
+                Value::StringUtf8(std::str::from_utf8(self.take(len)?).ok()?.to_string())
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_use_the_consensus_serialization() {
+        assert_eq!(string_ascii("ab").unwrap(), "0x0d000000026162");
+        assert!(string_ascii("ä").is_err());
+        assert!(bool_value("0x03").unwrap());
+        assert!(!bool_value("0x04").unwrap());
+        assert!(bool_value("0x0100").is_err());
+    }
+
+    #[test]
+    fn decodes_printed_tuples() {
+        // (tuple (amount u5) (event "peg-in") (owner 'ST000000000000000000002AMW42H.pox) (memo none))
+        let mut bytes = vec![TYPE_TUPLE, 0, 0, 0, 4];
+        bytes.extend([6]);
+        bytes.extend(b"amount");
+        bytes.push(TYPE_UINT);
+        bytes.extend(5u128.to_be_bytes());
+        bytes.extend([5]);
+        bytes.extend(b"event");
+        bytes.extend([TYPE_STRING_ASCII, 0, 0, 0, 6]);
+        bytes.extend(b"peg-in");
+        bytes.extend([4]);
+        bytes.extend(b"memo");
+        bytes.push(TYPE_NONE);
+        bytes.extend([5]);
+        bytes.extend(b"owner");
+        bytes.extend([TYPE_CONTRACT_PRINCIPAL, 26]);
+        bytes.extend([0; 20]);
+        bytes.extend([3]);
+        bytes.extend(b"pox");
+        let value = decode(&format!("0x{}", bytes.to_hex())).unwrap();
+
+        assert_eq!(value.field("amount").and_then(Value::as_uint), Some(5));
+        assert_eq!(value.field("event").and_then(Value::as_str), Some("peg-in"));
+        assert_eq!(value.field("memo"), Some(&Value::Optional(None)));
+        assert!(matches!(
+            value.field("owner").and_then(Value::as_principal),
+            Some(PrincipalData::Contract(contract)) if contract.name.as_str() == "pox"
+        ));
+        // trailing and missing bytes are both errors
+        assert!(decode(&format!("{}00", bytes.to_hex())).is_err());
+        assert!(decode(&bytes[..bytes.len() - 1].to_hex()).is_err());
+    }
+}
@@ -1,8 +1,12 @@
 use crate::stacks_node::{Error as StacksNodeError, PegInOp, PegOutRequestOp, StacksNode};
 use blockstack_lib::{
-    chainstate::stacks::address::StacksAddressExtensions, chainstate::stacks::StacksTransaction,
-    codec::StacksMessageCodec, types::chainstate::StacksAddress,
+    chainstate::stacks::address::StacksAddressExtensions,
+    chainstate::stacks::StacksTransaction,
+    codec::StacksMessageCodec,
+    types::chainstate::{BlockHeaderHash, ConsensusHash, StacksAddress, StacksBlockId},
 };
+use frost_signer::proxy::{self, ProxyConfig};
+use frost_signer::retry::RetryPolicy;
 use reqwest::blocking::Client;
 use serde_json::Value;
 use tracing::debug;
@@ -17,6 +21,7 @@ pub enum Error {
 pub struct NodeClient {
     node_url: String,
     client: Client,
+    retry_policy: RetryPolicy,
 }
 
 impl NodeClient {
@@ -24,9 +29,26 @@ impl NodeClient {
         Self {
             node_url: url.to_string(),
             client: Client::new(),
+            retry_policy: RetryPolicy::never(),
         }
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Send requests through the node's proxy in `proxy`, if it has one
+    pub fn with_proxy(mut self, proxy: &ProxyConfig) -> Result<Self, StacksNodeError> {
+        if let Some(proxy) = proxy.proxy_for(&self.node_url) {
+            proxy::parse(proxy)?;
+            self.client = Client::builder()
+                .proxy(reqwest::Proxy::all(proxy)?)
+                .build()?;
+        }
+        Ok(self)
+    }
+
     fn build_url(&self, route: &str) -> String {
         format!("{}{}", self.node_url, route)
     }
@@ -34,7 +56,8 @@ impl NodeClient {
     fn get_response(&self, route: &str) -> Result<String, StacksNodeError> {
         let url = self.build_url(route);
         debug!("Sending Request to Stacks Node: {}", &url);
-        Ok(self.client.get(&url).send()?.text()?)
+        self.retry_policy
+            .retry(|| Ok::<_, StacksNodeError>(self.client.get(&url).send()?.text()?))
     }
 
     fn get_burn_ops<T>(&self, block_height: u64, op: &str) -> Result<Vec<T>, StacksNodeError>
@@ -73,15 +96,34 @@ impl StacksNode for NodeClient {
             .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
     }
 
+    fn stacks_tip(&self) -> Result<[u8; 32], StacksNodeError> {
+        let response = self.get_response("/v2/info")?;
+        let json: Value = serde_json::from_str(&response)?;
+        let invalid = |entry: &str| StacksNodeError::InvalidJsonEntry(entry.to_string());
+        let hex = |entry: &str| {
+            json[entry]
+                .as_str()
+                .map(|hex| hex.trim_start_matches("0x"))
+                .ok_or_else(|| invalid(entry))
+        };
+        let consensus_hash = ConsensusHash::from_hex(hex("stacks_tip_consensus_hash")?)
+            .map_err(|_| invalid("stacks_tip_consensus_hash"))?;
+        let block_hash =
+            BlockHeaderHash::from_hex(hex("stacks_tip")?).map_err(|_| invalid("stacks_tip"))?;
+        // the index block hash, as stacks names a block across forks
+        Ok(StacksBlockId::new(&consensus_hash, &block_hash).0)
+    }
+
     fn next_nonce(&self, addr: StacksAddress) -> Result<u64, StacksNodeError> {
         let url = self.build_url(&format!("/v2/accounts/{}", addr.to_b58()));
         let entry = "nonce";
-        self.client.get(url).send()?.json::<Value>().map(|json| {
-            json[entry]
-                .as_u64()
-                .map(|val| val + 1)
-                .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
-        })?
+        let json = self
+            .retry_policy
+            .retry(|| Ok::<_, StacksNodeError>(self.client.get(&url).send()?.json::<Value>()?))?;
+        json[entry]
+            .as_u64()
+            .map(|val| val + 1)
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(entry.to_string()))
     }
 
     fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), StacksNodeError> {
@@ -91,15 +133,79 @@ impl StacksNode for NodeClient {
 
         tx.consensus_serialize(&mut buffer)?;
 
-        let _return = self
-            .client
-            .post(url)
-            .body(buffer)
-            // .json(tx)
-            .send()
-            .and_then(|res| res.json::<Value>())?;
+        let _return = self.retry_policy.retry(|| {
+            Ok::<_, StacksNodeError>(
+                self.client
+                    .post(&url)
+                    .body(buffer.clone())
+                    // .json(tx)
+                    .send()
+                    .and_then(|res| res.json::<Value>())?,
+            )
+        })?;
         Ok(())
     }
+
+    fn contract_deployed(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+    ) -> Result<bool, StacksNodeError> {
+        let url = self.build_url(&format!(
+            "/v2/contracts/source/{contract_address}/{contract_name}"
+        ));
+        debug!("Sending Request to Stacks Node: {}", &url);
+        let response = self
+            .retry_policy
+            .retry(|| Ok::<_, StacksNodeError>(self.client.get(&url).send()?))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        response.error_for_status()?;
+        Ok(true)
+    }
+
+    fn call_read_only(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+        function: &str,
+        args: &[String],
+    ) -> Result<String, StacksNodeError> {
+        let url = self.build_url(&format!(
+            "/v2/contracts/call-read/{contract_address}/{contract_name}/{function}"
+        ));
+        debug!("Sending Request to Stacks Node: {}", &url);
+        let body = serde_json::json!({"sender": contract_address, "arguments": args});
+        let response = self.retry_policy.retry(|| {
+            Ok::<_, StacksNodeError>(
+                self.client
+                    .post(&url)
+                    .json(&body)
+                    .send()?
+                    .error_for_status()?
+                    .json::<Value>()?,
+            )
+        })?;
+        if response["okay"].as_bool() != Some(true) {
+            return Err(StacksNodeError::ReadOnlyCallFailed(
+                function.to_string(),
+                response["cause"].as_str().unwrap_or_default().to_string(),
+            ));
+        }
+        response["result"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(response.to_string()))
+    }
+
+    fn fee_rate(&self) -> Result<u64, StacksNodeError> {
+        // the node answers with a bare number
+        let response = self.get_response("/v2/fees/transfer")?;
+        serde_json::from_str::<Value>(&response)?
+            .as_u64()
+            .ok_or_else(|| StacksNodeError::InvalidJsonEntry(response.clone()))
+    }
 }
 
 #[cfg(test)]
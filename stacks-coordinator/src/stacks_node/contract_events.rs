@@ -0,0 +1,679 @@
+//! A stacks node which reads peg ops from the events the sBTC contract prints.
+//!
+//! The burnchain op API only reports ops the stacks node parsed from bitcoin. A contract which
+//! processes peg ops itself prints an event for each, which the stacks blockchain API serves at
+//! `/extended/v1/contract/<contract>/events`. Events are read newest first down to the last one
+//! already seen, normalized into peg ops and held by burn block height until the peg queue polls
+//! that block. They are served alongside the burnchain ops, or instead of them.
+//!
+//! A peg op event prints a tuple with these fields, of which `recipient` is a principal for a
+//! peg-in and a `(buff 34)` output script for a peg-out request:
+//! ```clarity
+//! {
+//!     event: "peg-in" | "peg-out-request",
+//!     txid: (buff 32),
+//!     vtxindex: uint,
+//!     burn-height: uint,
+//!     burn-header-hash: (buff 32),
+//!     amount: uint,
+//!     recipient: principal | (buff 34),
+//!     peg-wallet: (buff 34),
+//!     ;; peg-out requests only
+//!     fulfillment-fee: uint,
+//!     signature: (buff 65),
+//!     ;; optional
+//!     memo: (buff 80),
+//! }
+//! ```
+//! Other events the contract prints are skipped.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use bitcoin::Script;
+use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::StacksTransaction;
+use blockstack_lib::types::chainstate::{BurnchainHeaderHash, StacksAddress};
+use blockstack_lib::util::secp256k1::MessageSignature;
+use frost_signer::proxy::{self, ProxyConfig};
+use frost_signer::retry::RetryPolicy;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tracing::{debug, warn};
+
+use crate::stacks_node::bitcoin_ops::{pox_address, BlockOps};
+use crate::stacks_node::clarity::{self, Value};
+use crate::stacks_node::fallback::Reconciliation;
+use crate::stacks_node::{Error, PegInOp, PegOutRequestOp, StacksNode};
+
+/// Most events the API serves per page
+const PAGE_LIMIT: usize = 50;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventsMode {
+    /// Serve the ops of both sources, the burnchain op API's first
+    #[default]
+    Alongside,
+    /// Serve only the ops read from contract events
+    Instead,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ContractEventsConfig {
+    /// Stacks blockchain API serving the sBTC contract's events
+    pub api_url: String,
+    #[serde(default)]
+    pub mode: EventsMode,
+    /// Blocks the polled burn height trails the tip, so the contract has printed the events
+    /// of a block before the peg queue polls it
+    #[serde(default = "ContractEventsConfig::default_lag_blocks")]
+    pub lag_blocks: u64,
+}
+
+impl ContractEventsConfig {
+    fn default_lag_blocks() -> u64 {
+        1
+    }
+}
+
+/// A `print` event of the contract, with its Clarity value hex encoded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContractEvent {
+    pub tx_id: String,
+    pub event_index: u64,
+    pub hex: String,
+}
+
+#[cfg_attr(test, mockall::automock)]
+pub trait EventsApi {
+    /// Up to `limit` print events of the contract, newest first, after skipping the `offset`
+    /// newest
+    fn events(&self, offset: usize, limit: usize) -> Result<Vec<ContractEvent>, Error>;
+}
+
+/// Reads contract events from the stacks blockchain API
+pub struct EventsClient {
+    api_url: String,
+    contract_id: String,
+    client: Client,
+    retry_policy: RetryPolicy,
+}
+
+impl EventsClient {
+    pub fn new(api_url: &str, contract_id: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            contract_id: contract_id.to_string(),
+            client: Client::new(),
+            retry_policy: RetryPolicy::never(),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Send requests through the API's proxy in `proxy`, if it has one
+    pub fn with_proxy(mut self, proxy: &ProxyConfig) -> Result<Self, Error> {
+        if let Some(proxy) = proxy.proxy_for(&self.api_url) {
+            proxy::parse(proxy)?;
+            self.client = Client::builder()
+                .proxy(reqwest::Proxy::all(proxy)?)
+                .build()?;
+        }
+        Ok(self)
+    }
+}
+
+impl EventsApi for EventsClient {
+    fn events(&self, offset: usize, limit: usize) -> Result<Vec<ContractEvent>, Error> {
+        let url = format!(
+            "{}/extended/v1/contract/{}/events?offset={offset}&limit={limit}",
+            self.api_url, self.contract_id
+        );
+        debug!("Reading contract events: {}", &url);
+        let json = self
+            .retry_policy
+            .retry(|| Ok::<_, Error>(self.client.get(&url).send()?.json::<JsonValue>()?))?;
+        let results = json["results"]
+            .as_array()
+            .ok_or_else(|| Error::InvalidJsonEntry("results".to_string()))?;
+        // events other than prints carry no `contract_log`
+        Ok(results
+            .iter()
+            .filter_map(|event| {
+                Some(ContractEvent {
+                    tx_id: event["tx_id"].as_str()?.to_string(),
+                    event_index: event["event_index"].as_u64()?,
+                    hex: event["contract_log"]["value"]["hex"].as_str()?.to_string(),
+                })
+            })
+            .collect())
+    }
+}
+
+pub struct ContractEventsNode<N, A> {
+    stacks_node: N,
+    /// `None` serves the burnchain ops only
+    events: Option<EventSource<A>>,
+}
+
+struct EventSource<A> {
+    api: A,
+    mode: EventsMode,
+    lag_blocks: u64,
+    mainnet: bool,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Newest event read, as its transaction and index
+    newest: Option<(String, u64)>,
+    /// Block height events were last read for
+    read_for: Option<u64>,
+    /// Ops read from events by block height, until served
+    ops: BTreeMap<u64, BlockOps>,
+}
+
+impl<N: StacksNode, A: EventsApi> ContractEventsNode<N, A> {
+    pub fn new(stacks_node: N) -> Self {
+        Self {
+            stacks_node,
+            events: None,
+        }
+    }
+
+    /// Read peg ops from the events of the contract `api` serves, whose address is a mainnet
+    /// address if `mainnet`
+    pub fn with_contract_events(
+        mut self,
+        api: A,
+        config: &ContractEventsConfig,
+        mainnet: bool,
+    ) -> Self {
+        self.events = Some(EventSource {
+            api,
+            mode: config.mode,
+            lag_blocks: config.lag_blocks,
+            mainnet,
+            state: Mutex::default(),
+        });
+        self
+    }
+}
+
+impl<A: EventsApi> EventSource<A> {
+    /// Read the events printed since the last read, once per polled block. The first read goes
+    /// back to the events of `block_height`, as the peg queue polled the blocks before it.
+    fn read(&self, state: &mut State, block_height: u64) -> Result<(), Error> {
+        if state.read_for == Some(block_height) {
+            return Ok(());
+        }
+        let mut offset = 0;
+        let mut newest = None;
+        'pages: loop {
+            let page = self.api.events(offset, PAGE_LIMIT)?;
+            for event in &page {
+                let id = (event.tx_id.clone(), event.event_index);
+                if state.newest.as_ref() == Some(&id) {
+                    break 'pages;
+                }
+                newest.get_or_insert(id);
+                let Some(op) = self.op(event) else {
+                    continue;
+                };
+                let height = op.block_height();
+                if height < block_height {
+                    if state.newest.is_none() {
+                        break 'pages;
+                    }
+                    warn!(
+                        "Contract event for peg op {} at {} arrived after its block was polled",
+                        event.tx_id, height
+                    );
+                    continue;
+                }
+                op.add_to(state.ops.entry(height).or_default());
+            }
+            if page.len() < PAGE_LIMIT {
+                break;
+            }
+            offset += page.len();
+        }
+        if newest.is_some() {
+            state.newest = newest;
+        }
+        state.read_for = Some(block_height);
+        Ok(())
+    }
+
+    /// The peg op `event` prints, if any
+    fn op(&self, event: &ContractEvent) -> Option<EventOp> {
+        let value = clarity::decode(&event.hex).ok()?;
+        let kind = value.field("event").and_then(Value::as_str)?;
+        let op = match kind {
+            "peg-in" => peg_in(&value, self.mainnet).map(EventOp::PegIn),
+            "peg-out-request" => peg_out_request(&value, self.mainnet).map(EventOp::PegOutRequest),
+            _ => return None,
+        };
+        if op.is_none() {
+            warn!(
+                "Skipping malformed {} event in transaction {}",
+                kind, event.tx_id
+            );
+        }
+        op
+    }
+
+    /// Ops of `block_height`, which is polled after the blocks before it
+    fn take(&self, block_height: u64) -> Result<BlockOps, Error> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.read(&mut state, block_height)?;
+        state.ops = state.ops.split_off(&block_height);
+        Ok(state.ops.get(&block_height).cloned().unwrap_or_default())
+    }
+}
+
+enum EventOp {
+    PegIn(PegInOp),
+    PegOutRequest(PegOutRequestOp),
+}
+
+impl EventOp {
+    fn block_height(&self) -> u64 {
+        match self {
+            EventOp::PegIn(op) => op.block_height,
+            EventOp::PegOutRequest(op) => op.block_height,
+        }
+    }
+
+    /// Add to `ops` unless a page boundary shift read it twice
+    fn add_to(self, ops: &mut BlockOps) {
+        match self {
+            EventOp::PegIn(op) => {
+                if !ops.peg_in.iter().any(|other| other.txid == op.txid) {
+                    ops.peg_in.push(op);
+                }
+            }
+            EventOp::PegOutRequest(op) => {
+                if !ops
+                    .peg_out_request
+                    .iter()
+                    .any(|other| other.txid == op.txid)
+                {
+                    ops.peg_out_request.push(op);
+                }
+            }
+        }
+    }
+}
+
+/// Where the op sits in the burnchain
+fn position(value: &Value) -> Option<(Txid, u32, u64, BurnchainHeaderHash)> {
+    let bytes32 = |name| -> Option<[u8; 32]> { value.field(name)?.as_buffer()?.try_into().ok() };
+    Some((
+        Txid(bytes32("txid")?),
+        value.field("vtxindex")?.as_uint()?.try_into().ok()?,
+        value.field("burn-height")?.as_uint()?.try_into().ok()?,
+        BurnchainHeaderHash(bytes32("burn-header-hash")?),
+    ))
+}
+
+fn amount(value: &Value, name: &str) -> Option<u64> {
+    value.field(name)?.as_uint()?.try_into().ok()
+}
+
+fn script(value: &Value, name: &str) -> Option<Script> {
+    Some(Script::from(value.field(name)?.as_buffer()?.to_vec()))
+}
+
+fn memo(value: &Value) -> Vec<u8> {
+    value
+        .field("memo")
+        .and_then(Value::as_buffer)
+        .unwrap_or_default()
+        .to_vec()
+}
+
+fn peg_in(value: &Value, mainnet: bool) -> Option<PegInOp> {
+    let (txid, vtxindex, block_height, burn_header_hash) = position(value)?;
+    Some(PegInOp {
+        recipient: value.field("recipient")?.as_principal()?.clone(),
+        peg_wallet_address: pox_address(&script(value, "peg-wallet")?, mainnet)?,
+        amount: amount(value, "amount")?,
+        memo: memo(value),
+        txid,
+        vtxindex,
+        block_height,
+        burn_header_hash,
+    })
+}
+
+fn peg_out_request(value: &Value, mainnet: bool) -> Option<PegOutRequestOp> {
+    let (txid, vtxindex, block_height, burn_header_hash) = position(value)?;
+    let signature = value.field("signature")?.as_buffer()?.try_into().ok()?;
+    Some(PegOutRequestOp {
+        amount: amount(value, "amount")?,
+        recipient: pox_address(&script(value, "recipient")?, mainnet)?,
+        signature: MessageSignature(signature),
+        peg_wallet_address: pox_address(&script(value, "peg-wallet")?, mainnet)?,
+        fulfillment_fee: amount(value, "fulfillment-fee")?,
+        memo: memo(value),
+        txid,
+        vtxindex,
+        block_height,
+        burn_header_hash,
+    })
+}
+
+/// `ops` followed by those of `events` with a transaction `ops` lacks
+fn merge<T>(mut ops: Vec<T>, events: Vec<T>, txid: impl Fn(&T) -> &Txid) -> Vec<T> {
+    let new: Vec<T> = events
+        .into_iter()
+        .filter(|event| !ops.iter().any(|op| txid(op) == txid(event)))
+        .collect();
+    ops.extend(new);
+    ops
+}
+
+impl<N: StacksNode, A: EventsApi> StacksNode for ContractEventsNode<N, A> {
+    fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error> {
+        let Some(events) = &self.events else {
+            return self.stacks_node.get_peg_in_ops(block_height);
+        };
+        let read = events.take(block_height)?.peg_in;
+        match events.mode {
+            EventsMode::Alongside => Ok(merge(
+                self.stacks_node.get_peg_in_ops(block_height)?,
+                read,
+                |op| &op.txid,
+            )),
+            EventsMode::Instead => Ok(read),
+        }
+    }
+
+    fn get_peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error> {
+        let Some(events) = &self.events else {
+            return self.stacks_node.get_peg_out_request_ops(block_height);
+        };
+        let read = events.take(block_height)?.peg_out_request;
+        match events.mode {
+            EventsMode::Alongside => Ok(merge(
+                self.stacks_node.get_peg_out_request_ops(block_height)?,
+                read,
+                |op| &op.txid,
+            )),
+            EventsMode::Instead => Ok(read),
+        }
+    }
+
+    fn burn_block_height(&self) -> Result<u64, Error> {
+        let height = self.stacks_node.burn_block_height()?;
+        Ok(match &self.events {
+            Some(events) => height.saturating_sub(events.lag_blocks),
+            None => height,
+        })
+    }
+
+    fn stacks_tip(&self) -> Result<[u8; 32], Error> {
+        self.stacks_node.stacks_tip()
+    }
+
+    fn next_nonce(&self, addr: StacksAddress) -> Result<u64, Error> {
+        self.stacks_node.next_nonce(addr)
+    }
+
+    fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), Error> {
+        self.stacks_node.broadcast_transaction(tx)
+    }
+
+    fn contract_deployed(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+    ) -> Result<bool, Error> {
+        self.stacks_node
+            .contract_deployed(contract_address, contract_name)
+    }
+
+    fn fee_rate(&self) -> Result<u64, Error> {
+        self.stacks_node.fee_rate()
+    }
+
+    fn call_read_only(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+        function: &str,
+        args: &[String],
+    ) -> Result<String, Error> {
+        self.stacks_node
+            .call_read_only(contract_address, contract_name, function, args)
+    }
+
+    fn reconcile(&self) -> Result<Reconciliation, Error> {
+        self.stacks_node.reconcile()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::hex::ToHex;
+    use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType20};
+    use blockstack_lib::vm::types::{PrincipalData, StandardPrincipalData};
+
+    use super::*;
+    use crate::stacks_node::MockStacksNode;
+
+    fn uint(n: u64) -> Vec<u8> {
+        let mut bytes = vec![0x01];
+        bytes.extend((n as u128).to_be_bytes());
+        bytes
+    }
+
+    fn buff(data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x02];
+        bytes.extend((data.len() as u32).to_be_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    fn ascii(s: &str) -> Vec<u8> {
+        let mut bytes = vec![0x0d];
+        bytes.extend((s.len() as u32).to_be_bytes());
+        bytes.extend(s.as_bytes());
+        bytes
+    }
+
+    fn p2wpkh(hash: u8) -> Vec<u8> {
+        let mut script = vec![0x00, 0x14];
+        script.extend([hash; 20]);
+        buff(&script)
+    }
+
+    fn event(index: u64, fields: Vec<(&str, Vec<u8>)>) -> ContractEvent {
+        let mut bytes = vec![0x0c];
+        bytes.extend((fields.len() as u32).to_be_bytes());
+        for (name, value) in fields {
+            bytes.push(name.len() as u8);
+            bytes.extend(name.as_bytes());
+            bytes.extend(value);
+        }
+        ContractEvent {
+            tx_id: format!("0x{index:064x}"),
+            event_index: 0,
+            hex: format!("0x{}", bytes.to_hex()),
+        }
+    }
+
+    fn peg_in_event(index: u64, txid: u8, height: u64) -> ContractEvent {
+        let mut recipient = vec![0x05, 26];
+        recipient.extend([1; 20]);
+        event(
+            index,
+            vec![
+                ("event", ascii("peg-in")),
+                ("txid", buff(&[txid; 32])),
+                ("vtxindex", uint(1)),
+                ("burn-height", uint(height)),
+                ("burn-header-hash", buff(&[0; 32])),
+                ("amount", uint(1000)),
+                ("recipient", recipient),
+                ("peg-wallet", p2wpkh(2)),
+            ],
+        )
+    }
+
+    fn peg_out_request_event(index: u64, txid: u8, height: u64) -> ContractEvent {
+        event(
+            index,
+            vec![
+                ("event", ascii("peg-out-request")),
+                ("txid", buff(&[txid; 32])),
+                ("vtxindex", uint(2)),
+                ("burn-height", uint(height)),
+                ("burn-header-hash", buff(&[0; 32])),
+                ("amount", uint(500)),
+                ("recipient", p2wpkh(3)),
+                ("peg-wallet", p2wpkh(2)),
+                ("fulfillment-fee", uint(10)),
+                ("signature", buff(&[9; 65])),
+                ("memo", buff(b"hi")),
+            ],
+        )
+    }
+
+    fn config(mode: EventsMode) -> ContractEventsConfig {
+        ContractEventsConfig {
+            api_url: "http://localhost:3999".to_string(),
+            mode,
+            lag_blocks: 1,
+        }
+    }
+
+    /// An API serving `events`, newest first, until `more` are printed on top
+    fn api(events: Vec<ContractEvent>, more: Vec<ContractEvent>) -> MockEventsApi {
+        let mut api = MockEventsApi::new();
+        let mut reads = 0;
+        api.expect_events().returning(move |offset, limit| {
+            reads += 1;
+            let mut all = if reads > 1 { more.clone() } else { vec![] };
+            all.extend(events.clone());
+            Ok(all.into_iter().skip(offset).take(limit).collect())
+        });
+        api
+    }
+
+    #[test]
+    fn serves_events_instead_of_burnchain_ops() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_burn_block_height().returning(|| Ok(12));
+        let events = vec![
+            peg_out_request_event(3, 3, 11),
+            peg_in_event(2, 2, 10),
+            // polled before the coordinator started
+            peg_in_event(1, 1, 9),
+        ];
+        let more = vec![
+            peg_in_event(5, 5, 11),
+            // printed after its block was polled
+            peg_in_event(4, 4, 10),
+        ];
+        let node = ContractEventsNode::new(stacks_node).with_contract_events(
+            api(events, more),
+            &config(EventsMode::Instead),
+            true,
+        );
+
+        assert_eq!(node.burn_block_height().unwrap(), 11);
+        let peg_in = node.get_peg_in_ops(10).unwrap();
+        assert_eq!(peg_in.len(), 1);
+        let op = &peg_in[0];
+        assert_eq!(op.txid, Txid([2; 32]));
+        assert_eq!((op.vtxindex, op.block_height, op.amount), (1, 10, 1000));
+        assert_eq!(
+            op.recipient,
+            PrincipalData::Standard(StandardPrincipalData(26, [1; 20]))
+        );
+        assert_eq!(
+            op.peg_wallet_address,
+            PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [2; 20])
+        );
+        assert!(node.get_peg_out_request_ops(10).unwrap().is_empty());
+
+        let peg_in: Vec<Txid> = node
+            .get_peg_in_ops(11)
+            .unwrap()
+            .into_iter()
+            .map(|op| op.txid)
+            .collect();
+        assert_eq!(peg_in, vec![Txid([5; 32])]);
+        let peg_out_request = node.get_peg_out_request_ops(11).unwrap();
+        assert_eq!(peg_out_request.len(), 1);
+        let op = &peg_out_request[0];
+        assert_eq!((op.amount, op.fulfillment_fee), (500, 10));
+        assert_eq!(op.signature, MessageSignature([9; 65]));
+        assert_eq!(op.memo, b"hi".to_vec());
+        assert_eq!(
+            op.recipient,
+            PoxAddress::Addr20(true, PoxAddressType20::P2WPKH, [3; 20])
+        );
+    }
+
+    #[test]
+    fn merges_events_with_burnchain_ops() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node.expect_get_peg_in_ops().returning(|height| {
+            let mut event_op = peg_in(&decoded(&peg_in_event(0, 1, height)), true).unwrap();
+            event_op.amount = 1;
+            Ok(vec![event_op])
+        });
+        let events = vec![peg_in_event(2, 2, 10), peg_in_event(1, 1, 10)];
+        let node = ContractEventsNode::new(stacks_node).with_contract_events(
+            api(events, vec![]),
+            &config(EventsMode::Alongside),
+            true,
+        );
+
+        let peg_in = node.get_peg_in_ops(10).unwrap();
+        // the burnchain op wins over the event for the same transaction
+        assert_eq!(
+            peg_in
+                .iter()
+                .map(|op| (op.txid, op.amount))
+                .collect::<Vec<_>>(),
+            vec![(Txid([1; 32]), 1), (Txid([2; 32]), 1000)]
+        );
+    }
+
+    fn decoded(event: &ContractEvent) -> Value {
+        clarity::decode(&event.hex).unwrap()
+    }
+
+    #[test]
+    fn skips_other_and_malformed_events() {
+        let other = event(1, vec![("event", ascii("wallet-handoff"))]);
+        let mut malformed = peg_in_event(2, 2, 10);
+        malformed.hex.truncate(malformed.hex.len() - 2);
+        let mut short = decoded(&peg_in_event(3, 3, 10));
+        if let Value::Tuple(fields) = &mut short {
+            fields.remove("amount");
+        }
+        let events = vec![other.clone(), malformed, peg_in_event(4, 4, 10)];
+        let source = EventSource {
+            api: api(events, vec![]),
+            mode: EventsMode::Instead,
+            lag_blocks: 0,
+            mainnet: true,
+            state: Mutex::default(),
+        };
+        assert!(source.op(&other).is_none());
+        assert!(peg_in(&short, true).is_none());
+        let ops = source.take(10).unwrap();
+        assert_eq!(ops.peg_in.len(), 1);
+        assert_eq!(ops.peg_in[0].txid, Txid([4; 32]));
+    }
+}
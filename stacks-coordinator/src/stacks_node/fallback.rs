@@ -0,0 +1,448 @@
+//! A stacks node which reads peg ops from bitcoin while its burnchain op API is unavailable.
+//!
+//! Ops served from bitcoin are remembered by block height. Once the stacks node answers
+//! again, [`StacksNode::reconcile`] compares them with the node's own view: ops only the
+//! node knows about are handed back for queueing and every disagreement is reported.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use blockstack_lib::chainstate::stacks::StacksTransaction;
+use blockstack_lib::types::chainstate::StacksAddress;
+use tracing::{info, warn};
+
+use crate::bitcoin_node::BitcoinNode;
+use crate::peg_queue::SbtcOp;
+use crate::stacks_node::bitcoin_ops::{self, BlockOps};
+use crate::stacks_node::{Error, PegInOp, PegOutRequestOp, StacksNode};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct FallbackConfig {
+    /// Stacks burn op magic bytes of the bitcoin network, `X2` on mainnet and `T2` on testnet
+    pub magic: String,
+}
+
+/// Disagreement between ops read from bitcoin and the stacks node's view of the same block
+#[derive(Clone, Debug, PartialEq)]
+pub enum Discrepancy {
+    /// The stacks node reports an op the bitcoin scan did not find
+    MissedByBitcoin(SbtcOp),
+    /// An op read from bitcoin which the stacks node does not report
+    UnknownToStacksNode(SbtcOp),
+    /// Both views have the op but disagree on its contents
+    Mismatch {
+        bitcoin: Box<SbtcOp>,
+        stacks_node: Box<SbtcOp>,
+    },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissedByBitcoin(op) => write!(
+                f,
+                "peg op {} at height {} was missed by the bitcoin scan",
+                op.txid().to_hex(),
+                op.block_height()
+            ),
+            Self::UnknownToStacksNode(op) => write!(
+                f,
+                "peg op {} at height {} was read from bitcoin but is unknown to the stacks node",
+                op.txid().to_hex(),
+                op.block_height()
+            ),
+            Self::Mismatch { bitcoin, .. } => write!(
+                f,
+                "peg op {} at height {} differs between bitcoin and the stacks node",
+                bitcoin.txid().to_hex(),
+                bitcoin.block_height()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Reconciliation {
+    /// Ops which never reached the peg queue
+    pub missed: Vec<SbtcOp>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+pub struct FallbackNode<N, B> {
+    stacks_node: N,
+    /// `None` disables the fallback
+    bitcoin: Option<BitcoinSource<B>>,
+}
+
+struct BitcoinSource<B> {
+    node: B,
+    magic: [u8; 2],
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Last block scanned, so both kinds of op come from one download
+    last_scan: Option<(u64, BlockOps)>,
+    /// Ops handed out from bitcoin by block height, until checked against the stacks node
+    served: BTreeMap<u64, Served>,
+}
+
+#[derive(Default)]
+struct Served {
+    peg_in: Option<Vec<PegInOp>>,
+    peg_out_request: Option<Vec<PegOutRequestOp>>,
+}
+
+impl<N: StacksNode, B: BitcoinNode> FallbackNode<N, B> {
+    pub fn new(stacks_node: N) -> Self {
+        Self {
+            stacks_node,
+            bitcoin: None,
+        }
+    }
+
+    pub fn with_bitcoin_fallback(
+        mut self,
+        node: B,
+        config: &FallbackConfig,
+    ) -> Result<Self, Error> {
+        let magic = config
+            .magic
+            .as_bytes()
+            .try_into()
+            .map_err(|_| Error::InvalidMagic(config.magic.clone()))?;
+        self.bitcoin = Some(BitcoinSource {
+            node,
+            magic,
+            state: Mutex::default(),
+        });
+        Ok(self)
+    }
+
+    /// Whether an op API error should be covered by reading bitcoin
+    fn fallback(&self, error: &Error) -> Option<&BitcoinSource<B>> {
+        match error {
+            Error::UnknownBlockHeight(_) => None,
+            _ => self.bitcoin.as_ref(),
+        }
+    }
+}
+
+impl<B: BitcoinNode> BitcoinSource<B> {
+    fn scan(&self, state: &mut State, block_height: u64) -> Result<BlockOps, Error> {
+        if let Some((height, ops)) = &state.last_scan {
+            if *height == block_height {
+                return Ok(ops.clone());
+            }
+        }
+        if block_height > self.node.block_count()? {
+            return Err(Error::UnknownBlockHeight(block_height));
+        }
+        let block = self.node.block(block_height)?;
+        let ops = bitcoin_ops::parse_block(&block, block_height, &self.magic);
+        state.last_scan = Some((block_height, ops.clone()));
+        Ok(ops)
+    }
+
+    fn peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let ops = self.scan(&mut state, block_height)?.peg_in;
+        state.served.entry(block_height).or_default().peg_in = Some(ops.clone());
+        Ok(ops)
+    }
+
+    fn peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let ops = self.scan(&mut state, block_height)?.peg_out_request;
+        state
+            .served
+            .entry(block_height)
+            .or_default()
+            .peg_out_request = Some(ops.clone());
+        Ok(ops)
+    }
+}
+
+impl<N: StacksNode, B: BitcoinNode> StacksNode for FallbackNode<N, B> {
+    fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error> {
+        match self.stacks_node.get_peg_in_ops(block_height) {
+            Err(e) => match self.fallback(&e) {
+                Some(bitcoin) => {
+                    warn!("Reading peg-in ops at {} from bitcoin: {}", block_height, e);
+                    bitcoin.peg_in_ops(block_height)
+                }
+                None => Err(e),
+            },
+            ops => ops,
+        }
+    }
+
+    fn get_peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error> {
+        match self.stacks_node.get_peg_out_request_ops(block_height) {
+            Err(e) => match self.fallback(&e) {
+                Some(bitcoin) => {
+                    warn!(
+                        "Reading peg-out request ops at {} from bitcoin: {}",
+                        block_height, e
+                    );
+                    bitcoin.peg_out_request_ops(block_height)
+                }
+                None => Err(e),
+            },
+            ops => ops,
+        }
+    }
+
+    fn burn_block_height(&self) -> Result<u64, Error> {
+        match self.stacks_node.burn_block_height() {
+            Err(e) => match self.fallback(&e) {
+                Some(bitcoin) => {
+                    warn!("Reading burn block height from bitcoin: {}", e);
+                    Ok(bitcoin.node.block_count()?)
+                }
+                None => Err(e),
+            },
+            height => height,
+        }
+    }
+
+    fn stacks_tip(&self) -> Result<[u8; 32], Error> {
+        // bitcoin knows nothing of the stacks chain
+        self.stacks_node.stacks_tip()
+    }
+
+    fn next_nonce(&self, addr: StacksAddress) -> Result<u64, Error> {
+        self.stacks_node.next_nonce(addr)
+    }
+
+    fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), Error> {
+        self.stacks_node.broadcast_transaction(tx)
+    }
+
+    fn contract_deployed(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+    ) -> Result<bool, Error> {
+        self.stacks_node
+            .contract_deployed(contract_address, contract_name)
+    }
+
+    fn fee_rate(&self) -> Result<u64, Error> {
+        self.stacks_node.fee_rate()
+    }
+
+    fn call_read_only(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+        function: &str,
+        args: &[String],
+    ) -> Result<String, Error> {
+        self.stacks_node
+            .call_read_only(contract_address, contract_name, function, args)
+    }
+
+    /// Check ops served from bitcoin against the stacks node, oldest block first.
+    /// Blocks the node cannot answer for yet stay pending until the next call.
+    fn reconcile(&self) -> Result<Reconciliation, Error> {
+        let mut reconciliation = Reconciliation::default();
+        let Some(bitcoin) = &self.bitcoin else {
+            return Ok(reconciliation);
+        };
+        let mut state = bitcoin.state.lock().unwrap_or_else(|e| e.into_inner());
+        while let Some(entry) = state.served.first_entry() {
+            let block_height = *entry.key();
+            let served = entry.get();
+            let (bitcoin_ops, stacks_node_ops) = match self.stacks_node_view(block_height, served) {
+                Ok(stacks_node_ops) => (served.ops(), stacks_node_ops),
+                // still unavailable, or behind bitcoin
+                Err(_) => break,
+            };
+            entry.remove();
+            info!(
+                "Reconciling peg ops at {} with the stacks node",
+                block_height
+            );
+            compare(bitcoin_ops, stacks_node_ops, &mut reconciliation);
+        }
+        Ok(reconciliation)
+    }
+}
+
+impl<N: StacksNode, B> FallbackNode<N, B> {
+    /// The stacks node's ops of the kinds `served` from bitcoin
+    fn stacks_node_view(&self, block_height: u64, served: &Served) -> Result<Vec<SbtcOp>, Error> {
+        let mut ops = vec![];
+        if served.peg_in.is_some() {
+            let peg_in = self.stacks_node.get_peg_in_ops(block_height)?;
+            ops.extend(peg_in.into_iter().map(SbtcOp::PegIn));
+        }
+        if served.peg_out_request.is_some() {
+            let peg_out_request = self.stacks_node.get_peg_out_request_ops(block_height)?;
+            ops.extend(peg_out_request.into_iter().map(SbtcOp::PegOutRequest));
+        }
+        Ok(ops)
+    }
+}
+
+impl Served {
+    fn ops(&self) -> Vec<SbtcOp> {
+        let peg_in = self.peg_in.iter().flatten().cloned().map(SbtcOp::PegIn);
+        let peg_out_request = self
+            .peg_out_request
+            .iter()
+            .flatten()
+            .cloned()
+            .map(SbtcOp::PegOutRequest);
+        peg_in.chain(peg_out_request).collect()
+    }
+}
+
+fn compare(mut bitcoin: Vec<SbtcOp>, stacks_node: Vec<SbtcOp>, out: &mut Reconciliation) {
+    for op in stacks_node {
+        match bitcoin.iter().position(|other| other.txid() == op.txid()) {
+            Some(i) => {
+                let ours = bitcoin.swap_remove(i);
+                if ours != op {
+                    out.discrepancies.push(Discrepancy::Mismatch {
+                        bitcoin: Box::new(ours),
+                        stacks_node: Box::new(op),
+                    });
+                }
+            }
+            None => {
+                out.missed.push(op.clone());
+                out.discrepancies.push(Discrepancy::MissedByBitcoin(op));
+            }
+        }
+    }
+    out.discrepancies
+        .extend(bitcoin.into_iter().map(Discrepancy::UnknownToStacksNode));
+}
+
+#[cfg(test)]
+mod tests {
+    use blockstack_lib::burnchains::Txid;
+    use blockstack_lib::chainstate::stacks::address::PoxAddress;
+    use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+    use blockstack_lib::util::hash::Hash160;
+    use blockstack_lib::vm::types::{PrincipalData, StandardPrincipalData};
+
+    use super::*;
+    use crate::bitcoin_node::MockBitcoinNode;
+    use crate::stacks_node::MockStacksNode;
+
+    fn peg_in(txid: u8, amount: u64) -> PegInOp {
+        PegInOp {
+            recipient: PrincipalData::Standard(StandardPrincipalData(26, [0; 20])),
+            peg_wallet_address: PoxAddress::Standard(
+                StacksAddress::new(26, Hash160([0; 20])),
+                None,
+            ),
+            amount,
+            memo: vec![],
+            txid: Txid([txid; 32]),
+            vtxindex: 0,
+            block_height: 5,
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+        }
+    }
+
+    fn config() -> FallbackConfig {
+        FallbackConfig {
+            magic: "id".to_string(),
+        }
+    }
+
+    #[test]
+    fn passes_through_while_the_stacks_node_answers() {
+        let mut stacks_node = MockStacksNode::new();
+        stacks_node
+            .expect_get_peg_in_ops()
+            .returning(|_| Ok(vec![peg_in(1, 100)]));
+        stacks_node
+            .expect_get_peg_out_request_ops()
+            .returning(|height| Err(Error::UnknownBlockHeight(height)));
+        let node = FallbackNode::new(stacks_node)
+            .with_bitcoin_fallback(MockBitcoinNode::new(), &config())
+            .unwrap();
+
+        assert_eq!(node.get_peg_in_ops(5).unwrap(), vec![peg_in(1, 100)]);
+        // an unknown height is not an outage
+        assert!(matches!(
+            node.get_peg_out_request_ops(5),
+            Err(Error::UnknownBlockHeight(5))
+        ));
+        assert_eq!(node.reconcile().unwrap(), Reconciliation::default());
+    }
+
+    #[test]
+    fn reconciles_ops_read_from_bitcoin() {
+        let mut stacks_node = MockStacksNode::new();
+        let mut calls = 0;
+        stacks_node.expect_get_peg_in_ops().returning(move |_| {
+            calls += 1;
+            match calls {
+                1 => Err(Error::InvalidJsonEntry("peg_in".to_string())),
+                // the node's view once it is back: one op was missed, one differs
+                _ => Ok(vec![peg_in(1, 100), peg_in(2, 200)]),
+            }
+        });
+        let mut bitcoin_node = MockBitcoinNode::new();
+        bitcoin_node.expect_block_count().returning(|| Ok(10));
+        bitcoin_node.expect_block().returning(|_| {
+            Ok(bitcoin::Block {
+                header: bitcoin::BlockHeader {
+                    version: 1,
+                    prev_blockhash: bitcoin::hashes::Hash::all_zeros(),
+                    merkle_root: bitcoin::hashes::Hash::all_zeros(),
+                    time: 0,
+                    bits: 0,
+                    nonce: 0,
+                },
+                txdata: vec![],
+            })
+        });
+        let node = FallbackNode::new(stacks_node)
+            .with_bitcoin_fallback(bitcoin_node, &config())
+            .unwrap();
+
+        // the scanned block is empty
+        assert!(node.get_peg_in_ops(5).unwrap().is_empty());
+        {
+            let bitcoin = node.bitcoin.as_ref().unwrap();
+            let mut state = bitcoin.state.lock().unwrap();
+            state.served.get_mut(&5).unwrap().peg_in = Some(vec![peg_in(1, 90), peg_in(3, 300)]);
+        }
+
+        let reconciliation = node.reconcile().unwrap();
+        assert_eq!(reconciliation.missed, vec![SbtcOp::PegIn(peg_in(2, 200))]);
+        assert_eq!(
+            reconciliation.discrepancies,
+            vec![
+                Discrepancy::Mismatch {
+                    bitcoin: Box::new(SbtcOp::PegIn(peg_in(1, 90))),
+                    stacks_node: Box::new(SbtcOp::PegIn(peg_in(1, 100))),
+                },
+                Discrepancy::MissedByBitcoin(SbtcOp::PegIn(peg_in(2, 200))),
+                Discrepancy::UnknownToStacksNode(SbtcOp::PegIn(peg_in(3, 300))),
+            ]
+        );
+        // each block is reconciled once
+        assert_eq!(node.reconcile().unwrap(), Reconciliation::default());
+    }
+
+    #[test]
+    fn rejects_malformed_magic() {
+        let node = FallbackNode::<MockStacksNode, MockBitcoinNode>::new(MockStacksNode::new())
+            .with_bitcoin_fallback(
+                MockBitcoinNode::new(),
+                &FallbackConfig {
+                    magic: "X2>".to_string(),
+                },
+            );
+        assert!(matches!(node, Err(Error::InvalidMagic(_))));
+    }
+}
@@ -1,7 +1,15 @@
+pub mod bitcoin_ops;
+pub mod clarity;
 pub mod client;
+pub mod contract_events;
+pub mod fallback;
 
 use blockstack_lib::chainstate::burn::operations as burn_ops;
 use blockstack_lib::types::chainstate::StacksAddress;
+use frost_signer::retry::{Classify, Retry};
+
+use crate::bitcoin_node::Error as BitcoinNodeError;
+use crate::stacks_node::fallback::Reconciliation;
 
 pub use blockstack_lib::chainstate::stacks::StacksTransaction;
 
@@ -18,6 +26,16 @@ pub enum Error {
     ReqwestError(#[from] reqwest::Error),
     #[error("Blockstack Error: {0}")]
     BlockstackError(#[from] blockstack_lib::codec::Error),
+    #[error("Bitcoin Node Error: {0}")]
+    BitcoinNodeError(#[from] BitcoinNodeError),
+    #[error("Burn op magic must be two bytes: {0:?}")]
+    InvalidMagic(String),
+    #[error("Proxy Error: {0}")]
+    ProxyError(#[from] frost_signer::proxy::Error),
+    #[error("Invalid Clarity value: {0}")]
+    InvalidClarityValue(String),
+    #[error("Read-only call {0} failed: {1}")]
+    ReadOnlyCallFailed(String, String),
 }
 
 #[cfg_attr(test, mockall::automock)]
@@ -25,8 +43,89 @@ pub trait StacksNode {
     fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error>;
     fn get_peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error>;
     fn burn_block_height(&self) -> Result<u64, Error>;
+    /// Index block hash of the node's stacks chain tip, which fulfillments commit to
+    fn stacks_tip(&self) -> Result<[u8; 32], Error>;
     fn next_nonce(&self, addr: StacksAddress) -> Result<u64, Error>;
     fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), Error>;
+    fn contract_deployed(&self, contract_address: &str, contract_name: &str)
+        -> Result<bool, Error>;
+    /// Estimated fee rate of a transaction, in microstacks per byte
+    fn fee_rate(&self) -> Result<u64, Error>;
+    /// Result of the read-only `function` of a contract called with `args`, values hex encoded
+    /// as in [`clarity`]
+    fn call_read_only(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+        function: &str,
+        args: &[String],
+    ) -> Result<String, Error>;
+
+    /// Peg ops this node served from another source which disagree with its own view,
+    /// see [`fallback::FallbackNode`]
+    fn reconcile(&self) -> Result<Reconciliation, Error> {
+        Ok(Reconciliation::default())
+    }
+}
+
+/// Boxed nodes, so the coordinator can be given a `Box<dyn StacksNode>`
+impl<N: StacksNode + ?Sized> StacksNode for Box<N> {
+    fn get_peg_in_ops(&self, block_height: u64) -> Result<Vec<PegInOp>, Error> {
+        (**self).get_peg_in_ops(block_height)
+    }
+    fn get_peg_out_request_ops(&self, block_height: u64) -> Result<Vec<PegOutRequestOp>, Error> {
+        (**self).get_peg_out_request_ops(block_height)
+    }
+    fn burn_block_height(&self) -> Result<u64, Error> {
+        (**self).burn_block_height()
+    }
+    fn stacks_tip(&self) -> Result<[u8; 32], Error> {
+        (**self).stacks_tip()
+    }
+    fn next_nonce(&self, addr: StacksAddress) -> Result<u64, Error> {
+        (**self).next_nonce(addr)
+    }
+    fn broadcast_transaction(&self, tx: &StacksTransaction) -> Result<(), Error> {
+        (**self).broadcast_transaction(tx)
+    }
+    fn contract_deployed(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+    ) -> Result<bool, Error> {
+        (**self).contract_deployed(contract_address, contract_name)
+    }
+    fn fee_rate(&self) -> Result<u64, Error> {
+        (**self).fee_rate()
+    }
+    fn call_read_only(
+        &self,
+        contract_address: &str,
+        contract_name: &str,
+        function: &str,
+        args: &[String],
+    ) -> Result<String, Error> {
+        (**self).call_read_only(contract_address, contract_name, function, args)
+    }
+    fn reconcile(&self) -> Result<Reconciliation, Error> {
+        (**self).reconcile()
+    }
+}
+
+impl Classify for Error {
+    fn classify(&self) -> Retry {
+        match self {
+            Error::ReqwestError(e)
+                if e.is_timeout()
+                    || e.is_connect()
+                    || matches!(e.status(), Some(status) if status.is_server_error()) =>
+            {
+                Retry::Transient
+            }
+            Error::BitcoinNodeError(e) => e.classify(),
+            _ => Retry::Permanent,
+        }
+    }
 }
 
 pub type PegInOp = burn_ops::PegInOp;
@@ -1,10 +1,48 @@
+use blockstack_lib::{
+    chainstate::stacks::{
+        FungibleConditionCode, PostConditionPrincipal, SinglesigHashMode,
+        SinglesigSpendingCondition, StacksTransaction as BlockstackTransaction,
+        TransactionAnchorMode, TransactionAuth, TransactionContractCall, TransactionPayload,
+        TransactionPostCondition, TransactionPostConditionMode, TransactionPublicKeyEncoding,
+        TransactionSmartContract, TransactionSpendingCondition,
+        TransactionVersion as BlockstackTransactionVersion,
+    },
+    types::chainstate::StacksAddress,
+    util::{
+        hash::{hex_bytes, to_hex, Hash160},
+        secp256k1::MessageSignature,
+        HexError,
+    },
+    util_lib::strings::StacksString,
+    vm::{
+        types::{
+            ASCIIData, BuffData, CharType, OptionalData, PrincipalData,
+            QualifiedContractIdentifier, ResponseData, SequenceData, StandardPrincipalData,
+        },
+        ClarityName, ContractName, Value,
+    },
+};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as Json};
 
 use crate::make_contract_call::{
     AnchorMode, Authorization, ChainID, LengthPrefixedList, Payload, PostConditionMode,
     TransactionVersion,
 };
 
+/// Errors converting between stacks.js and blockstack transactions
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Missing or malformed field: {0}")]
+    InvalidField(&'static str),
+    #[error("Unsupported {0}: {1}")]
+    Unsupported(&'static str, String),
+    #[error("Invalid hex in {0}: {1}")]
+    InvalidHex(&'static str, HexError),
+    #[error("{0} must be {1} bytes, got {2}")]
+    InvalidLength(&'static str, usize, usize),
+}
+
 /// Current type is compatible with stacks.js JSON
 /// TODO: Find appropriate type
 #[allow(non_snake_case)]
@@ -18,3 +56,539 @@ pub struct StacksTransaction {
     pub postConditionMode: PostConditionMode,
     pub postConditions: LengthPrefixedList,
 }
+
+// stacks.js enum values, see @stacks/transactions/src/constants.ts
+const AUTH_TYPE_STANDARD: u64 = 0x04;
+const AUTH_TYPE_SPONSORED: u64 = 0x05;
+const PAYLOAD_TYPE_CONTRACT_CALL: u64 = 0x02;
+const PAYLOAD_TYPE_SMART_CONTRACT: u64 = 0x01;
+const POST_CONDITION_TYPE_STX: u64 = 0x00;
+const POST_CONDITION_PRINCIPAL_ORIGIN: u64 = 0x01;
+const POST_CONDITION_PRINCIPAL_STANDARD: u64 = 0x02;
+const POST_CONDITION_PRINCIPAL_CONTRACT: u64 = 0x03;
+const CLARITY_TYPE_INT: u64 = 0x00;
+const CLARITY_TYPE_UINT: u64 = 0x01;
+const CLARITY_TYPE_BUFFER: u64 = 0x02;
+const CLARITY_TYPE_TRUE: u64 = 0x03;
+const CLARITY_TYPE_FALSE: u64 = 0x04;
+const CLARITY_TYPE_PRINCIPAL_STANDARD: u64 = 0x05;
+const CLARITY_TYPE_PRINCIPAL_CONTRACT: u64 = 0x06;
+const CLARITY_TYPE_RESPONSE_OK: u64 = 0x07;
+const CLARITY_TYPE_RESPONSE_ERR: u64 = 0x08;
+const CLARITY_TYPE_OPTIONAL_NONE: u64 = 0x09;
+const CLARITY_TYPE_OPTIONAL_SOME: u64 = 0x0a;
+const CLARITY_TYPE_STRING_ASCII: u64 = 0x0d;
+// stacks.js StacksMessageType values
+const MESSAGE_TYPE_ADDRESS: u64 = 0;
+const MESSAGE_TYPE_PRINCIPAL: u64 = 1;
+const MESSAGE_TYPE_LENGTH_PREFIXED_STRING: u64 = 2;
+const MESSAGE_TYPE_POST_CONDITION: u64 = 5;
+const MESSAGE_TYPE_LENGTH_PREFIXED_LIST: u64 = 7;
+const MESSAGE_TYPE_PAYLOAD: u64 = 8;
+const MESSAGE_TYPE_MESSAGE_SIGNATURE: u64 = 9;
+const MAX_CODE_BODY_BYTES: u64 = 100_000;
+
+impl TryFrom<&StacksTransaction> for BlockstackTransaction {
+    type Error = Error;
+    fn try_from(tx: &StacksTransaction) -> Result<Self, Error> {
+        let version = match tx.version.as_u64() {
+            Some(0x00) => BlockstackTransactionVersion::Mainnet,
+            Some(0x80) => BlockstackTransactionVersion::Testnet,
+            _ => return Err(Error::Unsupported("version", tx.version.to_string())),
+        };
+        let chain_id = tx
+            .chainId
+            .as_u64()
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or(Error::InvalidField("chainId"))?;
+        let anchor_mode = match tx.anchorMode {
+            1 => TransactionAnchorMode::OnChainOnly,
+            2 => TransactionAnchorMode::OffChainOnly,
+            3 => TransactionAnchorMode::Any,
+            other => return Err(Error::Unsupported("anchorMode", other.to_string())),
+        };
+        let post_condition_mode = match tx.postConditionMode.as_u64() {
+            Some(0x01) => TransactionPostConditionMode::Allow,
+            Some(0x02) => TransactionPostConditionMode::Deny,
+            _ => return Err(Error::InvalidField("postConditionMode")),
+        };
+        let post_conditions = field(&tx.postConditions, "values", "postConditions")?
+            .as_array()
+            .ok_or(Error::InvalidField("postConditions"))?
+            .iter()
+            .map(post_condition)
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(BlockstackTransaction {
+            version,
+            chain_id,
+            auth: auth(&tx.auth)?,
+            anchor_mode,
+            post_condition_mode,
+            post_conditions,
+            payload: payload(&tx.payload)?,
+        })
+    }
+}
+
+/// The reverse conversion, producing the JSON layout stacks.js uses
+impl TryFrom<&BlockstackTransaction> for StacksTransaction {
+    type Error = Error;
+    fn try_from(tx: &BlockstackTransaction) -> Result<Self, Error> {
+        let post_conditions = tx
+            .post_conditions
+            .iter()
+            .map(post_condition_json)
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(StacksTransaction {
+            version: (tx.version as u8).into(),
+            chainId: tx.chain_id.into(),
+            auth: auth_json(&tx.auth)?,
+            anchorMode: tx.anchor_mode as u8,
+            payload: payload_json(&tx.payload)?,
+            postConditionMode: json!(tx.post_condition_mode as u8),
+            postConditions: json!({
+                "type": MESSAGE_TYPE_LENGTH_PREFIXED_LIST,
+                "lengthPrefixBytes": 4,
+                "values": post_conditions,
+            }),
+        })
+    }
+}
+
+fn field<'a>(json: &'a Json, key: &str, name: &'static str) -> Result<&'a Json, Error> {
+    json.get(key).ok_or(Error::InvalidField(name))
+}
+
+fn u64_field(json: &Json, key: &str, name: &'static str) -> Result<u64, Error> {
+    // stacks.js serializes bigints as strings
+    match field(json, key, name)? {
+        Json::Number(n) => n.as_u64(),
+        Json::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or(Error::InvalidField(name))
+}
+
+fn str_field<'a>(json: &'a Json, key: &str, name: &'static str) -> Result<&'a str, Error> {
+    field(json, key, name)?
+        .as_str()
+        .ok_or(Error::InvalidField(name))
+}
+
+/// Decode a hex string field of exactly `N` bytes
+fn hex_field<const N: usize>(json: &Json, key: &str, name: &'static str) -> Result<[u8; N], Error> {
+    let bytes = hex_bytes(str_field(json, key, name)?).map_err(|e| Error::InvalidHex(name, e))?;
+    let len = bytes.len();
+    bytes
+        .try_into()
+        .map_err(|_| Error::InvalidLength(name, N, len))
+}
+
+fn hash160_field(json: &Json, key: &str, name: &'static str) -> Result<Hash160, Error> {
+    // the field is already a hash, so it is decoded rather than hashed again
+    Ok(Hash160(hex_field(json, key, name)?))
+}
+
+fn auth(json: &Json) -> Result<TransactionAuth, Error> {
+    let origin = || spending_condition(field(json, "spendingCondition", "auth.spendingCondition")?);
+    match u64_field(json, "authType", "auth.authType")? {
+        AUTH_TYPE_STANDARD => Ok(TransactionAuth::Standard(origin()?)),
+        AUTH_TYPE_SPONSORED => Ok(TransactionAuth::Sponsored(
+            origin()?,
+            spending_condition(field(
+                json,
+                "sponsorSpendingCondition",
+                "auth.sponsorSpendingCondition",
+            )?)?,
+        )),
+        other => Err(Error::Unsupported("authType", other.to_string())),
+    }
+}
+
+fn spending_condition(condition: &Json) -> Result<TransactionSpendingCondition, Error> {
+    let hash_mode = match u64_field(condition, "hashMode", "spendingCondition.hashMode")? {
+        0x00 => SinglesigHashMode::P2PKH,
+        0x02 => SinglesigHashMode::P2WPKH,
+        other => return Err(Error::Unsupported("hashMode", other.to_string())),
+    };
+    let key_encoding = match u64_field(condition, "keyEncoding", "spendingCondition.keyEncoding")? {
+        0x00 => TransactionPublicKeyEncoding::Compressed,
+        0x01 => TransactionPublicKeyEncoding::Uncompressed,
+        other => return Err(Error::Unsupported("keyEncoding", other.to_string())),
+    };
+    // a short signature must not be silently padded or truncated
+    let signature = hex_field(
+        field(condition, "signature", "spendingCondition.signature")?,
+        "data",
+        "spendingCondition.signature.data",
+    )?;
+    Ok(TransactionSpendingCondition::Singlesig(
+        SinglesigSpendingCondition {
+            hash_mode,
+            signer: hash160_field(condition, "signer", "spendingCondition.signer")?,
+            nonce: u64_field(condition, "nonce", "spendingCondition.nonce")?,
+            tx_fee: u64_field(condition, "fee", "spendingCondition.fee")?,
+            key_encoding,
+            signature: MessageSignature(signature),
+        },
+    ))
+}
+
+fn payload(json: &Json) -> Result<TransactionPayload, Error> {
+    match u64_field(json, "payloadType", "payload.payloadType")? {
+        PAYLOAD_TYPE_CONTRACT_CALL => {
+            let function_args = field(json, "functionArgs", "payload.functionArgs")?
+                .as_array()
+                .ok_or(Error::InvalidField("payload.functionArgs"))?
+                .iter()
+                .map(clarity_value)
+                .collect::<Result<Vec<Value>, Error>>()?;
+            Ok(TransactionPayload::ContractCall(TransactionContractCall {
+                address: address(field(json, "contractAddress", "payload.contractAddress")?)?,
+                contract_name: contract_name(json)?,
+                function_name: ClarityName::try_from(
+                    lp_string(json, "functionName", "payload.functionName")?.to_string(),
+                )
+                .map_err(|_| Error::InvalidField("payload.functionName"))?,
+                function_args,
+            }))
+        }
+        PAYLOAD_TYPE_SMART_CONTRACT => Ok(TransactionPayload::SmartContract(
+            TransactionSmartContract {
+                name: contract_name(json)?,
+                code_body: StacksString::from_str(lp_string(json, "codeBody", "payload.codeBody")?)
+                    .ok_or(Error::InvalidField("payload.codeBody"))?,
+            },
+            None,
+        )),
+        other => Err(Error::Unsupported("payloadType", other.to_string())),
+    }
+}
+
+/// stacks.js Address
+fn address(json: &Json) -> Result<StacksAddress, Error> {
+    let version = u8::try_from(u64_field(json, "version", "address.version")?)
+        .map_err(|_| Error::InvalidField("address.version"))?;
+    Ok(StacksAddress::new(
+        version,
+        hash160_field(json, "hash160", "address.hash160")?,
+    ))
+}
+
+/// stacks.js LengthPrefixedString
+fn lp_string<'a>(json: &'a Json, key: &str, name: &'static str) -> Result<&'a str, Error> {
+    str_field(field(json, key, name)?, "content", name)
+}
+
+fn contract_name(json: &Json) -> Result<ContractName, Error> {
+    ContractName::try_from(lp_string(json, "contractName", "contractName")?.to_string())
+        .map_err(|_| Error::InvalidField("contractName"))
+}
+
+fn post_condition(json: &Json) -> Result<TransactionPostCondition, Error> {
+    let condition_type = u64_field(json, "conditionType", "postCondition.conditionType")?;
+    if condition_type != POST_CONDITION_TYPE_STX {
+        return Err(Error::Unsupported(
+            "post condition type",
+            condition_type.to_string(),
+        ));
+    }
+    let principal = field(json, "principal", "postCondition.principal")?;
+    let principal = match u64_field(principal, "prefix", "postCondition.principal.prefix")? {
+        POST_CONDITION_PRINCIPAL_ORIGIN => PostConditionPrincipal::Origin,
+        POST_CONDITION_PRINCIPAL_STANDARD => PostConditionPrincipal::Standard(address(field(
+            principal,
+            "address",
+            "postCondition.principal.address",
+        )?)?),
+        POST_CONDITION_PRINCIPAL_CONTRACT => PostConditionPrincipal::Contract(
+            address(field(
+                principal,
+                "address",
+                "postCondition.principal.address",
+            )?)?,
+            contract_name(principal)?,
+        ),
+        other => return Err(Error::Unsupported("principal prefix", other.to_string())),
+    };
+    let condition_code = match u64_field(json, "conditionCode", "postCondition.conditionCode")? {
+        0x01 => FungibleConditionCode::SentEq,
+        0x02 => FungibleConditionCode::SentGt,
+        0x03 => FungibleConditionCode::SentGe,
+        0x04 => FungibleConditionCode::SentLt,
+        0x05 => FungibleConditionCode::SentLe,
+        other => return Err(Error::Unsupported("condition code", other.to_string())),
+    };
+    Ok(TransactionPostCondition::STX(
+        principal,
+        condition_code,
+        u64_field(json, "amount", "postCondition.amount")?,
+    ))
+}
+
+fn clarity_value(json: &Json) -> Result<Value, Error> {
+    let invalid = || Error::InvalidField("functionArgs");
+    let value = || str_field(json, "value", "functionArgs.value");
+    let inner = || clarity_value(field(json, "value", "functionArgs.value")?);
+    match u64_field(json, "type", "functionArgs.type")? {
+        CLARITY_TYPE_INT => Ok(Value::Int(value()?.parse().map_err(|_| invalid())?)),
+        CLARITY_TYPE_UINT => Ok(Value::UInt(value()?.parse().map_err(|_| invalid())?)),
+        CLARITY_TYPE_BUFFER => {
+            Value::buff_from(bytes(field(json, "buffer", "functionArgs.buffer")?)?)
+                .map_err(|_| invalid())
+        }
+        CLARITY_TYPE_TRUE => Ok(Value::Bool(true)),
+        CLARITY_TYPE_FALSE => Ok(Value::Bool(false)),
+        CLARITY_TYPE_PRINCIPAL_STANDARD => {
+            let address = address(field(json, "address", "functionArgs.address")?)?;
+            Ok(Value::Principal(PrincipalData::from(address)))
+        }
+        CLARITY_TYPE_PRINCIPAL_CONTRACT => {
+            let address = address(field(json, "address", "functionArgs.address")?)?;
+            Ok(Value::Principal(PrincipalData::Contract(
+                QualifiedContractIdentifier {
+                    issuer: StandardPrincipalData(address.version, address.bytes.0),
+                    name: contract_name(json)?,
+                },
+            )))
+        }
+        CLARITY_TYPE_RESPONSE_OK => Value::okay(inner()?).map_err(|_| invalid()),
+        CLARITY_TYPE_RESPONSE_ERR => Value::error(inner()?).map_err(|_| invalid()),
+        CLARITY_TYPE_OPTIONAL_NONE => Ok(Value::none()),
+        CLARITY_TYPE_OPTIONAL_SOME => Value::some(inner()?).map_err(|_| invalid()),
+        CLARITY_TYPE_STRING_ASCII => Value::string_ascii_from_bytes(
+            str_field(json, "data", "functionArgs.data")?
+                .as_bytes()
+                .to_vec(),
+        )
+        .map_err(|_| invalid()),
+        other => Err(Error::Unsupported("clarity value type", other.to_string())),
+    }
+}
+
+/// A Uint8Array, which JSON.stringify writes as an object keyed by index
+fn bytes(json: &Json) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::InvalidField("functionArgs.buffer");
+    let map = json.as_object().ok_or_else(invalid)?;
+    (0..map.len())
+        .map(|i| {
+            map.get(&i.to_string())
+                .and_then(Json::as_u64)
+                .and_then(|b| u8::try_from(b).ok())
+                .ok_or_else(invalid)
+        })
+        .collect()
+}
+
+fn auth_json(auth: &TransactionAuth) -> Result<Json, Error> {
+    Ok(match auth {
+        TransactionAuth::Standard(origin) => json!({
+            "authType": AUTH_TYPE_STANDARD,
+            "spendingCondition": spending_condition_json(origin)?,
+        }),
+        TransactionAuth::Sponsored(origin, sponsor) => json!({
+            "authType": AUTH_TYPE_SPONSORED,
+            "spendingCondition": spending_condition_json(origin)?,
+            "sponsorSpendingCondition": spending_condition_json(sponsor)?,
+        }),
+    })
+}
+
+fn spending_condition_json(condition: &TransactionSpendingCondition) -> Result<Json, Error> {
+    match condition {
+        TransactionSpendingCondition::Singlesig(condition) => Ok(json!({
+            "fee": condition.tx_fee.to_string(),
+            "hashMode": condition.hash_mode as u8,
+            "keyEncoding": condition.key_encoding as u8,
+            "nonce": condition.nonce.to_string(),
+            "signature": {
+                "data": to_hex(&condition.signature.0),
+                "type": MESSAGE_TYPE_MESSAGE_SIGNATURE,
+            },
+            "signer": condition.signer.to_hex(),
+        })),
+        TransactionSpendingCondition::Multisig(_) => {
+            Err(Error::Unsupported("spending condition", "multisig".into()))
+        }
+    }
+}
+
+fn payload_json(payload: &TransactionPayload) -> Result<Json, Error> {
+    match payload {
+        TransactionPayload::ContractCall(call) => Ok(json!({
+            "contractAddress": address_json(&call.address),
+            "contractName": lp_string_json(call.contract_name.as_str(), 1, 128),
+            "functionArgs": call
+                .function_args
+                .iter()
+                .map(clarity_value_json)
+                .collect::<Result<Vec<_>, Error>>()?,
+            "functionName": lp_string_json(call.function_name.as_str(), 1, 128),
+            "payloadType": PAYLOAD_TYPE_CONTRACT_CALL,
+            "type": MESSAGE_TYPE_PAYLOAD,
+        })),
+        TransactionPayload::SmartContract(contract, None) => Ok(json!({
+            "codeBody": lp_string_json(&contract.code_body.to_string(), 4, MAX_CODE_BODY_BYTES),
+            "contractName": lp_string_json(contract.name.as_str(), 1, 128),
+            "payloadType": PAYLOAD_TYPE_SMART_CONTRACT,
+            "type": MESSAGE_TYPE_PAYLOAD,
+        })),
+        TransactionPayload::SmartContract(_, Some(version)) => Err(Error::Unsupported(
+            "versioned smart contract",
+            format!("{version:?}"),
+        )),
+        other => Err(Error::Unsupported("payload", format!("{other:?}"))),
+    }
+}
+
+fn address_json(address: &StacksAddress) -> Json {
+    json!({
+        "hash160": address.bytes.to_hex(),
+        "type": MESSAGE_TYPE_ADDRESS,
+        "version": address.version,
+    })
+}
+
+fn lp_string_json(content: &str, length_prefix_bytes: u64, max_length_bytes: u64) -> Json {
+    json!({
+        "content": content,
+        "lengthPrefixBytes": length_prefix_bytes,
+        "maxLengthBytes": max_length_bytes,
+        "type": MESSAGE_TYPE_LENGTH_PREFIXED_STRING,
+    })
+}
+
+fn post_condition_json(condition: &TransactionPostCondition) -> Result<Json, Error> {
+    match condition {
+        TransactionPostCondition::STX(principal, code, amount) => {
+            let principal = match principal {
+                PostConditionPrincipal::Origin => json!({
+                    "prefix": POST_CONDITION_PRINCIPAL_ORIGIN,
+                    "type": MESSAGE_TYPE_PRINCIPAL,
+                }),
+                PostConditionPrincipal::Standard(address) => json!({
+                    "address": address_json(address),
+                    "prefix": POST_CONDITION_PRINCIPAL_STANDARD,
+                    "type": MESSAGE_TYPE_PRINCIPAL,
+                }),
+                PostConditionPrincipal::Contract(address, name) => json!({
+                    "address": address_json(address),
+                    "contractName": lp_string_json(name.as_str(), 1, 128),
+                    "prefix": POST_CONDITION_PRINCIPAL_CONTRACT,
+                    "type": MESSAGE_TYPE_PRINCIPAL,
+                }),
+            };
+            Ok(json!({
+                "amount": amount.to_string(),
+                "conditionCode": *code as u8,
+                "conditionType": POST_CONDITION_TYPE_STX,
+                "principal": principal,
+                "type": MESSAGE_TYPE_POST_CONDITION,
+            }))
+        }
+        #[allow(unreachable_patterns)]
+        other => Err(Error::Unsupported("post condition", format!("{other:?}"))),
+    }
+}
+
+fn clarity_value_json(value: &Value) -> Result<Json, Error> {
+    Ok(match value {
+        Value::Int(i) => json!({"type": CLARITY_TYPE_INT, "value": i.to_string()}),
+        Value::UInt(i) => json!({"type": CLARITY_TYPE_UINT, "value": i.to_string()}),
+        Value::Bool(true) => json!({ "type": CLARITY_TYPE_TRUE }),
+        Value::Bool(false) => json!({ "type": CLARITY_TYPE_FALSE }),
+        Value::Sequence(SequenceData::Buffer(BuffData { data })) => {
+            let buffer: Map<String, Json> = data
+                .iter()
+                .enumerate()
+                .map(|(i, b)| (i.to_string(), json!(b)))
+                .collect();
+            json!({"buffer": buffer, "type": CLARITY_TYPE_BUFFER})
+        }
+        Value::Sequence(SequenceData::String(CharType::ASCII(ASCIIData { data }))) => json!({
+            "data": String::from_utf8_lossy(data),
+            "type": CLARITY_TYPE_STRING_ASCII,
+        }),
+        Value::Principal(PrincipalData::Standard(StandardPrincipalData(version, bytes))) => {
+            json!({
+                "address": address_json(&StacksAddress::new(*version, Hash160(*bytes))),
+                "type": CLARITY_TYPE_PRINCIPAL_STANDARD,
+            })
+        }
+        Value::Principal(PrincipalData::Contract(contract)) => json!({
+            "address": address_json(&StacksAddress::new(
+                contract.issuer.0,
+                Hash160(contract.issuer.1)
+            )),
+            "contractName": lp_string_json(contract.name.as_str(), 1, 128),
+            "type": CLARITY_TYPE_PRINCIPAL_CONTRACT,
+        }),
+        Value::Response(ResponseData { committed, data }) => json!({
+            "type": if *committed { CLARITY_TYPE_RESPONSE_OK } else { CLARITY_TYPE_RESPONSE_ERR },
+            "value": clarity_value_json(data)?,
+        }),
+        Value::Optional(OptionalData { data: None }) => {
+            json!({ "type": CLARITY_TYPE_OPTIONAL_NONE })
+        }
+        Value::Optional(OptionalData { data: Some(data) }) => json!({
+            "type": CLARITY_TYPE_OPTIONAL_SOME,
+            "value": clarity_value_json(data)?,
+        }),
+        #[allow(unreachable_patterns)]
+        other => return Err(Error::Unsupported("clarity value", format!("{other:?}"))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract_call_json() -> &'static str {
+        r#"{"version":0,"chainId":1,"auth":{"authType":4,"spendingCondition":{"fee":"0","hashMode":0,"keyEncoding":1,"nonce":"0","signature":{"data":"007b8e678be460d78e6c0aee43d13d7765694decc9b2bb26ba90cee89a250530cd7cc3514411c85eba326f1ca0bae21f9e9467ed0ff77df57db99a04c1d605440e","type":9},"signer":"12016c066cb72c7098a01564eeadae379a266ec1"}},"anchorMode":3,"payload":{"contractAddress":{"hash160":"174c3f16b418d70de34138c95a68b5e50fa269bc","type":0,"version":22},"contractName":{"content":"sbtc-alpha","lengthPrefixBytes":1,"maxLengthBytes":128,"type":2},"functionArgs":[{"type":1,"value":"42"}],"functionName":{"content":"mint","lengthPrefixBytes":1,"maxLengthBytes":128,"type":2},"payloadType":2,"type":8},"postConditionMode":2,"postConditions":{"lengthPrefixBytes":4,"type":7,"values":[]}}"#
+    }
+
+    #[test]
+    fn contract_call() {
+        let tx: StacksTransaction = serde_json::from_str(contract_call_json()).unwrap();
+        let tx = BlockstackTransaction::try_from(&tx).unwrap();
+        assert_eq!(tx.version, BlockstackTransactionVersion::Mainnet);
+        assert_eq!(tx.anchor_mode, TransactionAnchorMode::Any);
+        match tx.payload {
+            TransactionPayload::ContractCall(call) => {
+                assert_eq!(call.address.version, 22);
+                assert_eq!(call.function_name.as_str(), "mint");
+                assert_eq!(call.function_args, vec![Value::UInt(42)]);
+            }
+            _ => panic!("expected contract call payload"),
+        }
+    }
+
+    #[test]
+    fn smart_contract() {
+        let mut tx: StacksTransaction = serde_json::from_str(contract_call_json()).unwrap();
+        tx.payload = serde_json::json!({
+            "type": 8,
+            "payloadType": 1,
+            "contractName": {"type": 2, "content": "sbtc-alpha", "lengthPrefixBytes": 1, "maxLengthBytes": 128},
+            "codeBody": {"type": 2, "content": "(define-data-var x uint u0)", "lengthPrefixBytes": 4, "maxLengthBytes": 100000},
+        });
+        let tx = BlockstackTransaction::try_from(&tx).unwrap();
+        match tx.payload {
+            TransactionPayload::SmartContract(contract, _) => {
+                assert_eq!(contract.name.as_str(), "sbtc-alpha");
+                assert_eq!(
+                    contract.code_body.to_string(),
+                    "(define-data-var x uint u0)"
+                );
+            }
+            _ => panic!("expected smart contract payload"),
+        }
+    }
+
+    #[test]
+    fn invalid_signature() {
+        let json = contract_call_json().replace("\"data\":\"007b", "\"data\":\"7b");
+        let tx: StacksTransaction = serde_json::from_str(&json).unwrap();
+        assert!(BlockstackTransaction::try_from(&tx).is_err());
+    }
+}
@@ -1,6 +1,10 @@
+use frost_signer::logging::target::WALLET;
+use tracing::debug;
+
 use crate::{
     make_contract_call::{
-        Error as ContractError, MakeContractCall, SignedContractCallOptions, ANY,
+        Error as ContractError, JsConfig, MakeContractCall, SignedContractCallOptions,
+        SignedContractDeployOptions, ANY,
     },
     peg_wallet::{Error as PegWalletError, PegWalletAddress, StacksWallet as StacksWalletTrait},
     stacks_node::{PegInOp, PegOutRequestOp},
@@ -26,43 +30,65 @@ pub struct StacksWallet {
     contract_address: String,
     contract_name: String,
     sender_key: String,
+    /// Microstacks per byte, see [`crate::fee_oracle`]
+    fee_rate: u64,
 }
 
+/// Bytes budgeted for a contract call, whose exact length is only known once it is signed
+const CONTRACT_CALL_LEN: u64 = 300;
+
 impl StacksWallet {
     pub fn new(path: &str, contract: String, sender_key: String) -> Result<Self, Error> {
+        let js = JsConfig {
+            enabled: true,
+            path: path.to_string(),
+        };
+        Self::from_config(&js, contract, sender_key)
+    }
+    /// A wallet building its transactions with the JS subsystem configured by `js`
+    pub fn from_config(js: &JsConfig, contract: String, sender_key: String) -> Result<Self, Error> {
         let contract_info: Vec<&str> = contract.split('.').collect();
         if contract_info.len() != 2 {
             return Err(Error::InvalidContract(contract));
         }
         Ok(Self {
-            make_contract_call: MakeContractCall::new(path)?,
+            make_contract_call: MakeContractCall::from_config(js)?,
             contract_address: contract_info[0].to_owned(),
             contract_name: contract_info[1].to_owned(),
             sender_key,
+            fee_rate: 0,
         })
     }
     fn call(&mut self, function_name: String) -> Result<StacksTransaction, Error> {
-        let input = SignedContractCallOptions {
-            contractAddress: self.contract_address.clone(),
-            contractName: self.contract_name.to_string(),
-            functionName: function_name,
-            functionArgs: Vec::default(),
-            fee: Some(0.to_string()),
-            feeEstimateApiUrl: None,
-            nonce: None,
-            network: None,
-            anchorMode: ANY,
-            postConditionMode: None,
-            postConditions: None,
-            validateWithAbi: None,
-            sponsored: None,
-            senderKey: self.sender_key.clone(),
-        };
+        debug!(
+            target: WALLET,
+            "building call to {}.{}::{}",
+            self.contract_address,
+            self.contract_name,
+            function_name
+        );
+        let input = SignedContractCallOptions::builder()
+            .contract(&self.contract_address, &self.contract_name)
+            .function(function_name, &[])
+            .sender_key(&self.sender_key)
+            .fee(u128::from(self.fee_rate * CONTRACT_CALL_LEN))
+            .build();
         Ok(self.make_contract_call.call(&input)?)
     }
+    /// The address the configured sBTC contract is deployed under
+    pub fn contract_address(&self) -> &str {
+        &self.contract_address
+    }
+    /// The name of the configured sBTC contract
+    pub fn contract_name(&self) -> &str {
+        &self.contract_name
+    }
 }
 
 impl StacksWalletTrait for StacksWallet {
+    fn contract(&self) -> (&str, &str) {
+        (&self.contract_address, &self.contract_name)
+    }
     fn build_mint_transaction(
         &mut self,
         _op: &PegInOp,
@@ -81,4 +107,23 @@ impl StacksWalletTrait for StacksWallet {
     ) -> Result<StacksTransaction, PegWalletError> {
         Ok(self.call("set-bitcoin-wallet-address".to_string())?)
     }
+    fn build_contract_deploy_transaction(
+        &mut self,
+        name: &str,
+        source: &str,
+        nonce: u64,
+    ) -> Result<StacksTransaction, PegWalletError> {
+        let input = SignedContractDeployOptions::new(name, source, ANY, self.sender_key.clone())
+            .with_fee(u128::from(
+                self.fee_rate * (CONTRACT_CALL_LEN + source.len() as u64),
+            ))
+            .with_nonce(nonce);
+        Ok(self
+            .make_contract_call
+            .deploy(&input)
+            .map_err(Error::from)?)
+    }
+    fn set_fee_rate(&mut self, rate: u64) {
+        self.fee_rate = rate;
+    }
 }
@@ -0,0 +1,23 @@
+-- A peg queue as written before ops were stored in a versioned layout: `op` holds
+-- blockstack_lib's serde encoding of `SbtcOp`, and `sbtc_ops` has no `op_version` column.
+CREATE TABLE sbtc_ops (
+    txid TEXT NOT NULL,
+    burn_header_hash TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    op TEXT NOT NULL,
+    status TEXT NOT NULL,
+
+    PRIMARY KEY(txid, burn_header_hash)
+);
+CREATE TABLE peg_out_rejections (
+    txid TEXT NOT NULL,
+    burn_header_hash TEXT NOT NULL,
+    reason TEXT NOT NULL,
+
+    PRIMARY KEY(txid, burn_header_hash)
+);
+INSERT INTO sbtc_ops VALUES ('1111111111111111111111111111111111111111111111111111111111111111', 'a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1', 1, '{"PegIn":{"recipient":{"Standard":[26,[1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1]]},"peg_wallet_address":{"Addr32":[false,"P2TR",[2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2]]},"amount":100000,"memo":[],"txid":"1111111111111111111111111111111111111111111111111111111111111111","vtxindex":1,"block_height":1,"burn_header_hash":"a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1"}}', 'new');
+INSERT INTO sbtc_ops VALUES ('1212121212121212121212121212121212121212121212121212121212121212', 'a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1', 1, '{"PegOutRequest":{"amount":50000,"recipient":{"Addr20":[false,"P2WPKH",[4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4]]},"signature":"0505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505050505","peg_wallet_address":{"Addr32":[false,"P2TR",[2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2]]},"fulfillment_fee":1000,"memo":[1,3,3,7],"txid":"1212121212121212121212121212121212121212121212121212121212121212","vtxindex":2,"block_height":1,"burn_header_hash":"a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1a1"}}', 'acknowledged');
+INSERT INTO sbtc_ops VALUES ('2222222222222222222222222222222222222222222222222222222222222222', 'a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2', 2, '{"PegOutRequest":{"amount":999,"recipient":{"Standard":[{"version":26,"bytes":"0303030303030303030303030303030303030303"},"SerializeP2PKH"]},"signature":"0606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606060606","peg_wallet_address":{"Addr32":[false,"P2TR",[2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2]]},"fulfillment_fee":1000,"memo":[],"txid":"2222222222222222222222222222222222222222222222222222222222222222","vtxindex":1,"block_height":2,"burn_header_hash":"a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2"}}', 'rejected');
+INSERT INTO sbtc_ops VALUES ('2121212121212121212121212121212121212121212121212121212121212121', 'a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2', 2, '{"PegIn":{"recipient":{"Contract":{"issuer":[26,[7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7]],"name":"sbtc-vault"}},"peg_wallet_address":{"Addr32":[false,"P2TR",[2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2,2]]},"amount":2500,"memo":[9],"txid":"2121212121212121212121212121212121212121212121212121212121212121","vtxindex":0,"block_height":2,"burn_header_hash":"a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2"}}', 'new');
+INSERT INTO peg_out_rejections VALUES ('2222222222222222222222222222222222222222222222222222222222222222', 'a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2a2', 'amount of 999 sats is below the minimum of 1000');
@@ -0,0 +1,87 @@
+//! Peg-out fulfillment template vectors.
+//!
+//! `fixtures/fulfillment/vectors.json` holds unsigned fulfillment transactions laid out after the
+//! sBTC peg-out fulfill wire format, with the request each one fulfills and the values of the
+//! outputs it spends. Each vector names the `fulfillment::Error` variant it must fail with, or
+//! `null` if it conforms.
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::hex::FromHex;
+use bitcoin::{Script, Transaction, TxOut};
+use serde::Deserialize;
+use stacks_coordinator::fulfillment::Template;
+
+const VECTORS: &str = include_str!("fixtures/fulfillment/vectors.json");
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    magic: String,
+    recipient: String,
+    amount: u64,
+    peg_wallet: String,
+    max_fee: u64,
+    spent: Vec<u64>,
+    tx: String,
+    error: Option<String>,
+}
+
+impl Vector {
+    fn template(&self) -> Template {
+        Template {
+            magic: self.magic.as_bytes().try_into().unwrap(),
+            recipient: Script::from_hex(&self.recipient).unwrap(),
+            amount: self.amount,
+            peg_wallet: Script::from_hex(&self.peg_wallet).unwrap(),
+            max_fee: self.max_fee,
+        }
+    }
+
+    fn spent(&self) -> Vec<TxOut> {
+        self.spent
+            .iter()
+            .map(|value| TxOut {
+                value: *value,
+                script_pubkey: Script::new(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn fulfillment_vectors() {
+    let vectors: Vec<Vector> = serde_json::from_str(VECTORS).unwrap();
+    assert!(!vectors.is_empty());
+    for vector in vectors {
+        let tx: Transaction = deserialize(&Vec::from_hex(&vector.tx).unwrap()).unwrap();
+        let result = vector.template().validate(&tx, Some(&vector.spent()));
+        match (&vector.error, result) {
+            (None, Ok(())) => {}
+            (Some(expected), Err(e)) => {
+                let variant = format!("{e:?}");
+                assert!(
+                    variant.starts_with(expected.as_str()),
+                    "{}: expected {expected}, got {variant}",
+                    vector.name
+                );
+            }
+            (expected, result) => {
+                panic!("{}: expected {expected:?}, got {result:?}", vector.name)
+            }
+        }
+    }
+}
+
+#[test]
+fn structure_is_checked_without_spent_outputs() {
+    let vectors: Vec<Vector> = serde_json::from_str(VECTORS).unwrap();
+    let fee_errors = ["SpentCount", "Overspend", "FeeTooLow", "FeeTooHigh"];
+    for vector in vectors {
+        let tx: Transaction = deserialize(&Vec::from_hex(&vector.tx).unwrap()).unwrap();
+        let result = vector.template().validate(&tx, None);
+        let conforms = match &vector.error {
+            None => true,
+            Some(error) => fee_errors.contains(&error.as_str()),
+        };
+        assert_eq!(result.is_ok(), conforms, "{}: {result:?}", vector.name);
+    }
+}
@@ -1,18 +1,18 @@
+#![cfg(feature = "js")]
+
 use blockstack_lib::vm::Value;
 use stacks_coordinator::make_contract_call::{MakeContractCall, SignedContractCallOptions, ANY};
 
 #[test]
 fn make_contract_call_test() {
     let mut c = MakeContractCall::new("..").unwrap();
-    let input = SignedContractCallOptions::new(
-        "SPBMRFRPPGCDE3F384WCJPK8PQJGZ8K9QKK7F59X",
-        "",
-        "mint",
-        &[Value::UInt(42)],
-        ANY,
-        "0001020304050607080910111213141516171819202122232425262728293031",
-    )
-    .with_fee(0);
+    let input = SignedContractCallOptions::builder()
+        .contract("SPBMRFRPPGCDE3F384WCJPK8PQJGZ8K9QKK7F59X", "")
+        .function("mint", &[Value::UInt(42)])
+        .anchor_mode(ANY)
+        .sender_key("0001020304050607080910111213141516171819202122232425262728293031")
+        .fee(0)
+        .build();
     {
         let input_s = serde_json::to_string(&input).unwrap();
         println!("{input_s}");
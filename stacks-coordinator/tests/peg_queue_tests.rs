@@ -0,0 +1,86 @@
+//! Peg queues written by older releases.
+//!
+//! Each `fixtures/peg_queue/v<N>.sql` recreates a queue database as written by a release storing
+//! ops in layout version `N`. Opening one must migrate every op to the current layout without
+//! losing or reordering any of them.
+use blockstack_lib::burnchains::Txid;
+use blockstack_lib::chainstate::stacks::address::{PoxAddress, PoxAddressType32};
+use blockstack_lib::types::chainstate::BurnchainHeaderHash;
+use blockstack_lib::vm::types::{PrincipalData, StandardPrincipalData};
+use stacks_coordinator::peg_queue::{PegQueue, SqlitePegQueue, STORED_OP_VERSION};
+use stacks_coordinator::stacks_node::PegInOp;
+use std::path::PathBuf;
+
+const V0: &str = include_str!("fixtures/peg_queue/v0.sql");
+
+/// A database file holding `fixture`
+fn database(fixture: &str, name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("peg-queue-{name}-{}.sqlite", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    rusqlite::Connection::open(&path)
+        .unwrap()
+        .execute_batch(fixture)
+        .unwrap();
+    path
+}
+
+fn op_versions(path: &PathBuf) -> Vec<u32> {
+    rusqlite::Connection::open(path)
+        .unwrap()
+        .prepare("SELECT op_version FROM sbtc_ops ORDER BY txid")
+        .unwrap()
+        .query_map(rusqlite::params![], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn v0_queues_are_migrated_to_the_current_layout() {
+    let path = database(V0, "v0");
+    let peg_queue = SqlitePegQueue::new(&path, 1).unwrap();
+    assert_eq!(op_versions(&path), vec![STORED_OP_VERSION; 4]);
+
+    let peg_in = peg_queue.sbtc_op().unwrap().unwrap();
+    assert_eq!(
+        peg_in.as_peg_in().unwrap(),
+        &PegInOp {
+            recipient: PrincipalData::Standard(StandardPrincipalData(26, [1; 20])),
+            peg_wallet_address: PoxAddress::Addr32(false, PoxAddressType32::P2TR, [2; 32]),
+            amount: 100000,
+            memo: vec![],
+            txid: Txid([0x11; 32]),
+            vtxindex: 1,
+            block_height: 1,
+            burn_header_hash: BurnchainHeaderHash([0xa1; 32]),
+        }
+    );
+    let contract_peg_in = peg_queue.sbtc_op().unwrap().unwrap();
+    let contract_peg_in = contract_peg_in.as_peg_in().unwrap();
+    assert_eq!(contract_peg_in.txid, Txid([0x21; 32]));
+    match &contract_peg_in.recipient {
+        PrincipalData::Contract(contract) => {
+            assert_eq!(contract.issuer, StandardPrincipalData(26, [7; 20]));
+            assert_eq!(contract.name.as_str(), "sbtc-vault");
+        }
+        other => panic!("expected a contract recipient, got {other:?}"),
+    }
+    // the acknowledged and rejected peg-outs are not handed out again
+    assert!(peg_queue.sbtc_op().unwrap().is_none());
+
+    let rejected = peg_queue.rejected_peg_outs().unwrap();
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].txid, "22".repeat(32));
+    assert_eq!(rejected[0].amount, 999);
+    assert_eq!(
+        rejected[0].reason,
+        "amount of 999 sats is below the minimum of 1000"
+    );
+    drop(peg_queue);
+
+    // reopening a migrated queue leaves it as it is
+    let peg_queue = SqlitePegQueue::new(&path, 1).unwrap();
+    assert_eq!(op_versions(&path), vec![STORED_OP_VERSION; 4]);
+    assert_eq!(peg_queue.rejected_peg_outs().unwrap().len(), 1);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,229 @@
+//! Conversion vectors for stacks.js transaction JSON.
+//!
+//! `make_contract_call.json` is the output of `make_contract_call_test`. The other fixtures
+//! follow the layout stacks.js produces for the auth types, argument types and post-conditions
+//! the conversion supports. Each `.hex` file is the SIP-005 serialization of the matching
+//! `.json` fixture, so converted transactions can be compared byte-for-byte.
+use blockstack_lib::{
+    chainstate::stacks::{
+        FungibleConditionCode, PostConditionPrincipal, StacksTransaction as BlockstackTransaction,
+        TransactionAuth, TransactionPayload, TransactionPostCondition,
+        TransactionPostConditionMode, TransactionSpendingCondition, TransactionVersion,
+    },
+    codec::StacksMessageCodec,
+    util::hash::{hex_bytes, to_hex},
+    vm::Value,
+};
+use stacks_coordinator::stacks_transaction::{Error, StacksTransaction};
+
+const MAKE_CONTRACT_CALL: &str =
+    include_str!("fixtures/stacks_transactions/make_contract_call.json");
+const CONTRACT_CALL_ARGS: &str =
+    include_str!("fixtures/stacks_transactions/contract_call_args.json");
+const SPONSORED_CONTRACT_CALL: &str =
+    include_str!("fixtures/stacks_transactions/sponsored_contract_call.json");
+const SMART_CONTRACT: &str = include_str!("fixtures/stacks_transactions/smart_contract.json");
+
+const CONTRACT_CALL_ARGS_HEX: &str =
+    include_str!("fixtures/stacks_transactions/contract_call_args.hex");
+const SPONSORED_CONTRACT_CALL_HEX: &str =
+    include_str!("fixtures/stacks_transactions/sponsored_contract_call.hex");
+const SMART_CONTRACT_HEX: &str = include_str!("fixtures/stacks_transactions/smart_contract.hex");
+
+fn convert(fixture: &str) -> Result<BlockstackTransaction, Error> {
+    let tx: StacksTransaction = serde_json::from_str(fixture).unwrap();
+    BlockstackTransaction::try_from(&tx)
+}
+
+/// JSON -> blockstack -> JSON must reproduce the fixture exactly
+fn assert_round_trip(fixture: &str) -> BlockstackTransaction {
+    let tx = convert(fixture).unwrap();
+    let json = serde_json::to_value(StacksTransaction::try_from(&tx).unwrap()).unwrap();
+    let expected: serde_json::Value = serde_json::from_str(fixture).unwrap();
+    assert_eq!(json, expected);
+    tx
+}
+
+/// The converted transaction must serialize to exactly the known-good bytes
+fn assert_serializes_to(fixture: &str, expected_hex: &str) {
+    let tx = convert(fixture).unwrap();
+    assert_eq!(
+        to_hex(&tx.serialize_to_vec()),
+        to_hex(&hex_bytes(expected_hex.trim()).unwrap())
+    );
+}
+
+fn singlesig(condition: &TransactionSpendingCondition) -> (String, String, u64, u64) {
+    match condition {
+        TransactionSpendingCondition::Singlesig(condition) => (
+            condition.signer.to_hex(),
+            to_hex(&condition.signature.0),
+            condition.nonce,
+            condition.tx_fee,
+        ),
+        _ => panic!("expected singlesig spending condition"),
+    }
+}
+
+#[test]
+fn contract_call_args() {
+    let tx = assert_round_trip(CONTRACT_CALL_ARGS);
+    assert_eq!(tx.version, TransactionVersion::Testnet);
+    assert_eq!(tx.chain_id, 0x80000000);
+    assert_eq!(tx.post_condition_mode, TransactionPostConditionMode::Deny);
+
+    let TransactionAuth::Standard(origin) = &tx.auth else {
+        panic!("expected standard auth");
+    };
+    let (signer, signature, nonce, fee) = singlesig(origin);
+    // the signer is already a hash160 and must not be hashed again
+    assert_eq!(signer, "a46ff88886c2ef9762d970b4d2c63678835bd39d");
+    // all 65 signature bytes, including the recovery id, are kept
+    assert_eq!(signature.len(), 130);
+    assert!(signature.starts_with("01a3"));
+    assert_eq!((nonce, fee), (17, 180));
+
+    let TransactionPayload::ContractCall(call) = &tx.payload else {
+        panic!("expected contract call payload");
+    };
+    assert_eq!(call.function_name.as_str(), "set-bitcoin-wallet-address");
+    assert_eq!(call.function_args.len(), 13);
+    assert_eq!(call.function_args[0], Value::Int(i128::MIN));
+    assert_eq!(call.function_args[1], Value::UInt(u128::MAX));
+    assert_eq!(
+        call.function_args[2],
+        Value::buff_from(vec![0xde, 0xad, 0xbe, 0xef]).unwrap()
+    );
+    assert_eq!(call.function_args[10], Value::none());
+    assert_eq!(
+        call.function_args[9],
+        Value::error(Value::string_ascii_from_bytes(b"insufficient funds".to_vec()).unwrap())
+            .unwrap()
+    );
+
+    assert_eq!(tx.post_conditions.len(), 3);
+    assert!(matches!(
+        &tx.post_conditions[0],
+        TransactionPostCondition::STX(
+            PostConditionPrincipal::Origin,
+            FungibleConditionCode::SentGe,
+            1000
+        )
+    ));
+    assert!(matches!(
+        &tx.post_conditions[2],
+        TransactionPostCondition::STX(
+            PostConditionPrincipal::Contract(_, name),
+            FungibleConditionCode::SentLe,
+            0
+        ) if name.as_str() == "sbtc-alpha"
+    ));
+}
+
+#[test]
+fn sponsored_contract_call() {
+    let tx = assert_round_trip(SPONSORED_CONTRACT_CALL);
+    let TransactionAuth::Sponsored(origin, sponsor) = &tx.auth else {
+        panic!("expected sponsored auth");
+    };
+    assert_eq!(singlesig(origin).3, 0);
+    let (signer, _, nonce, fee) = singlesig(sponsor);
+    assert_eq!(signer, "174c3f16b418d70de34138c95a68b5e50fa269bc");
+    assert_eq!((nonce, fee), (42, 2000));
+}
+
+#[test]
+fn smart_contract() {
+    let tx = assert_round_trip(SMART_CONTRACT);
+    let TransactionPayload::SmartContract(contract, None) = &tx.payload else {
+        panic!("expected smart contract payload");
+    };
+    assert_eq!(contract.name.as_str(), "sbtc-alpha");
+    assert!(contract
+        .code_body
+        .to_string()
+        .starts_with("(define-data-var bitcoin-wallet-address"));
+}
+
+#[test]
+fn empty_contract_name_is_rejected() {
+    // stacks.js signs a call to an empty contract name, which is not a valid contract name
+    assert!(matches!(
+        convert(MAKE_CONTRACT_CALL),
+        Err(Error::InvalidField("contractName"))
+    ));
+    let fixed = MAKE_CONTRACT_CALL.replacen(r#""content": """#, r#""content": "sbtc-alpha""#, 1);
+    assert_round_trip(&fixed);
+}
+
+#[test]
+fn short_signature_is_rejected() {
+    let json = CONTRACT_CALL_ARGS.replacen(r#""data": "01a3"#, r#""data": "a3"#, 1);
+    assert!(matches!(
+        convert(&json),
+        Err(Error::InvalidLength(
+            "spendingCondition.signature.data",
+            65,
+            64
+        ))
+    ));
+}
+
+#[test]
+fn serialization_matches_known_good_bytes() {
+    assert_serializes_to(CONTRACT_CALL_ARGS, CONTRACT_CALL_ARGS_HEX);
+    assert_serializes_to(SPONSORED_CONTRACT_CALL, SPONSORED_CONTRACT_CALL_HEX);
+    assert_serializes_to(SMART_CONTRACT, SMART_CONTRACT_HEX);
+}
+
+#[test]
+fn malformed_hex_is_rejected() {
+    let signer = "a46ff88886c2ef9762d970b4d2c63678835bd39d";
+
+    // the signer must be decoded, not hashed, so non-hex characters are an error
+    let not_hex = CONTRACT_CALL_ARGS.replacen(signer, &"zz".repeat(20), 1);
+    assert!(matches!(
+        convert(&not_hex),
+        Err(Error::InvalidHex("spendingCondition.signer", _))
+    ));
+
+    let odd_length = CONTRACT_CALL_ARGS.replacen(signer, &signer[1..], 1);
+    assert!(matches!(
+        convert(&odd_length),
+        Err(Error::InvalidHex("spendingCondition.signer", _))
+    ));
+
+    let short = CONTRACT_CALL_ARGS.replacen(signer, &signer[2..], 1);
+    assert!(matches!(
+        convert(&short),
+        Err(Error::InvalidLength("spendingCondition.signer", 20, 19))
+    ));
+
+    let signature = CONTRACT_CALL_ARGS.replacen(r#""data": "01a3"#, r#""data": "0xa3"#, 1);
+    assert!(matches!(
+        convert(&signature),
+        Err(Error::InvalidHex("spendingCondition.signature.data", _))
+    ));
+}
+
+#[test]
+fn unsupported_values_are_rejected() {
+    let multisig = SPONSORED_CONTRACT_CALL.replacen(r#""hashMode": 0"#, r#""hashMode": 1"#, 1);
+    assert!(matches!(
+        convert(&multisig),
+        Err(Error::Unsupported("hashMode", _))
+    ));
+
+    let list = SPONSORED_CONTRACT_CALL.replacen(r#""type": 1,"#, r#""type": 11,"#, 1);
+    assert!(matches!(
+        convert(&list),
+        Err(Error::Unsupported("clarity value type", _))
+    ));
+
+    let token_post_condition =
+        CONTRACT_CALL_ARGS.replacen(r#""conditionType": 0"#, r#""conditionType": 1"#, 1);
+    assert!(matches!(
+        convert(&token_post_condition),
+        Err(Error::Unsupported("post condition type", _))
+    ));
+}
@@ -1,3 +1,5 @@
+#![cfg(feature = "js")]
+
 use blockstack_lib::{
     burnchains::Txid,
     chainstate::{
@@ -1,3 +1,4 @@
+use crate::keygen::{Keygen, Recover};
 use crate::secp256k1::Secp256k1;
 use clap::{Parser, Subcommand};
 
@@ -26,6 +27,33 @@ pub enum Command {
         #[arg(short, long)]
         config: String,
     },
+    /// Exchange signed hellos with the other signers and print a roster fingerprint
+    /// for operators to cross-check before the first DKG
+    Ceremony {
+        /// Associated signer id
+        #[arg(short, long)]
+        id: u32,
+        /// Config file path
+        #[arg(short, long)]
+        config: String,
+        /// Seconds to wait for the other signers
+        #[arg(short, long, default_value_t = 300)]
+        timeout: u64,
+    },
+    /// Decode and print a message blob captured from the relay, verifying its signature
+    /// when a config is given. Exits nonzero if the signature does not verify.
+    Decode {
+        /// File holding the raw or hex encoded blob, or the hex itself
+        input: String,
+        /// Config file whose public keys the signature is checked against
+        #[arg(short, long)]
+        config: Option<String>,
+    },
+    /// Inspect a config file
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
     /// Generate Secp256k1 Private Key
     PrivateKey(Secp256k1),
     /// Generate Secp256k1 Public Key
@@ -34,4 +62,36 @@ pub enum Command {
         #[arg(short, long)]
         config: String,
     },
+    /// Print the log filter of the signer running with the config file, replacing it first
+    /// if directives are given, e.g. "info,signing_round=trace"
+    LogFilter {
+        /// Config file path
+        #[arg(short, long)]
+        config: String,
+        /// Comma separated `target=level` pairs, with an optional bare default level
+        directives: Option<String>,
+    },
+    /// Print the group key, key ids and roster hash of the signer running with the config file,
+    /// as of its last successful DKG round
+    GroupKey {
+        /// Config file path
+        #[arg(short, long)]
+        config: String,
+    },
+    /// Generate a network private key, and optionally a stacks private key, printed as config
+    /// lines. With --mnemonic they are derived from a new BIP39 mnemonic
+    Keygen(Keygen),
+    /// Derive the keys of a BIP39 mnemonic again, e.g. on new hardware
+    Recover(Recover),
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// Check the config file for unknown keys and invalid values.
+    /// Prints each problem with its line and exits nonzero if any are found.
+    Lint {
+        /// Config file path
+        #[arg(short, long)]
+        config: String,
+    },
 }
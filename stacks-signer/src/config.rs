@@ -0,0 +1,220 @@
+//! The frost-signer settings of a stacks signer config go in a `[frost]` table, and the
+//! settings only the stacks signer reads in a `[stacks]` table:
+//! ```toml
+//! [frost]
+//! http_relay_url = "http://localhost:9776"
+//! total_signers = 3
+//! # ...
+//!
+//! [stacks]
+//! contract = "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.sbtc-alpha"
+//! node_url = "http://localhost:20443"
+//! ```
+//! Configs from before the split, holding the frost-signer settings at the top level, are
+//! still read as they are, with default stacks settings.
+use std::fs;
+use std::path::Path;
+
+use frost_signer::config::Config as FrostConfig;
+use frost_signer::lint::{Lint, Report};
+use frost_signer::secret::Secret;
+use frost_signer::stackerdb::ContractId;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+const KEYS: &[&str] = &["frost", "stacks"];
+
+const STACKS_KEYS: &[&str] = &["contract", "node_url"];
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("IO Error: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Toml Deserializer Error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("{0}")]
+    Frost(#[from] frost_signer::config::Error),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Config {
+    pub frost: FrostConfig,
+    #[serde(default)]
+    pub stacks: StacksConfig,
+}
+
+/// Settings only the stacks signer reads
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct StacksConfig {
+    /// The sBTC contract, as `<address>.<name>`
+    pub contract: Option<String>,
+    /// Stacks node requests to sign are checked against
+    pub node_url: Option<String>,
+}
+
+impl StacksConfig {
+    pub fn contract_id(&self) -> Option<ContractId> {
+        self.contract.as_deref()?.parse().ok()
+    }
+
+    fn lint_values(&self, lint: &mut Lint) {
+        if let Some(contract) = &self.contract {
+            if contract.parse::<ContractId>().is_err() {
+                lint.report("contract", "is not a contract id like `<address>.<name>`");
+            }
+        }
+        if let Some(node_url) = &self.node_url {
+            lint.url("node_url", node_url);
+        }
+    }
+}
+
+/// Only reads which top level keys a config has, so that the secrets it holds are not copied
+#[derive(Deserialize)]
+struct Layout {
+    frost: Option<IgnoredAny>,
+}
+
+impl Config {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Config, Error> {
+        let content = Secret::new(fs::read_to_string(path)?);
+        let mut config = Self::parse(&content)?;
+        config.frost.validate()?;
+        if config.frost.lock_private_key {
+            config.frost.network_private_key.mlock()?;
+        }
+        Ok(config)
+    }
+
+    /// Parse a config with `[frost]` and `[stacks]` tables, or a frost-signer config
+    pub fn parse(source: &str) -> Result<Config, Error> {
+        let layout: Layout = toml::from_str(source)?;
+        if layout.frost.is_some() {
+            return Ok(toml::from_str(source)?);
+        }
+        Ok(Config {
+            frost: toml::from_str(source)?,
+            stacks: StacksConfig::default(),
+        })
+    }
+
+    /// Check the file at `path` against the config schema, reporting every problem found.
+    /// Frost-signer configs are checked as they would be by frost-signer. Only fails if the
+    /// file cannot be read.
+    pub fn lint(path: impl AsRef<Path>) -> Result<Report, Error> {
+        let source = Secret::new(fs::read_to_string(&path)?);
+        let file = path.as_ref().display().to_string();
+        let mut lint = Lint::new(&source);
+        let Some(table) = lint.table() else {
+            return Ok(lint.finish(file));
+        };
+        if !table.contains_key("frost") {
+            FrostConfig::lint_with(&mut lint);
+            return Ok(lint.finish(file));
+        }
+        lint.unknown_keys(&table, "", KEYS);
+        let mut report = lint.finish(&file);
+
+        let mut frost = Lint::new(&source).with_scope("frost");
+        FrostConfig::lint_with(&mut frost);
+        report.findings.extend(frost.finish(&file).findings);
+        if table.contains_key("stacks") {
+            let mut stacks = Lint::new(&source).with_scope("stacks");
+            if let Some(table) = stacks.table() {
+                stacks.unknown_keys(&table, "", STACKS_KEYS);
+                if let Some(config) = stacks.parse::<StacksConfig>() {
+                    config.lint_values(&mut stacks);
+                }
+            }
+            report.findings.extend(stacks.finish(&file).findings);
+        }
+        Ok(report)
+    }
+}
+
+impl From<Config> for FrostConfig {
+    fn from(config: Config) -> Self {
+        config.frost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FROST: &str = r#"http_relay_url = "http://localhost:9776"
+total_signers = 3
+total_keys = 6
+keys_threshold = 4
+frost_state_file = "frost.state.bin"
+signer_public_keys = []
+key_public_keys = []
+
+[retry]
+max_attempts = 3
+"#;
+
+    fn layered(stacks: &str) -> String {
+        format!(
+            "[frost]\n{}\n[stacks]\n{stacks}",
+            FROST.replace("[retry]", "[frost.retry]")
+        )
+    }
+
+    #[test]
+    fn frost_only_configs_still_parse() {
+        let old = Config::parse(FROST).unwrap();
+        assert_eq!(old.stacks, StacksConfig::default());
+        assert_eq!(old.frost.total_keys, 6);
+        assert_eq!(old.frost.retry.max_attempts, 3);
+        Config::parse(include_str!("../conf/signer.toml")).unwrap();
+
+        let new = Config::parse(&layered(
+            r#"contract = "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.sbtc-alpha"
+node_url = "http://localhost:20443"
+"#,
+        ))
+        .unwrap();
+        assert_eq!(new.frost.group_hash(), old.frost.group_hash());
+        assert_eq!(new.frost.retry.max_attempts, 3);
+        assert_eq!(new.stacks.contract_id().unwrap().name, "sbtc-alpha");
+        assert_eq!(
+            new.stacks.node_url.as_deref(),
+            Some("http://localhost:20443")
+        );
+        // the stacks table is optional
+        let frost: FrostConfig = Config::parse(&layered("")).unwrap().into();
+        assert_eq!(frost.http_relay_url, "http://localhost:9776");
+    }
+
+    #[test]
+    fn lint_reports_keys_of_both_tables_with_their_path() {
+        let dir = std::env::temp_dir().join(format!("stacks-signer-lint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("signer.toml");
+        std::fs::write(
+            &path,
+            layered("contract = \"sbtc\"\nnode_ulr = \"http://localhost:20443\"\n")
+                .replace("keys_threshold = 4", "keys_threshold = 7"),
+        )
+        .unwrap();
+        let report = Config::lint(&path).unwrap();
+        let keys: Vec<&str> = report.findings.iter().map(|f| f.key.as_str()).collect();
+        assert!(keys.contains(&"frost.keys_threshold"), "{report}");
+        assert!(keys.contains(&"stacks.node_ulr"), "{report}");
+        assert!(keys.contains(&"stacks.contract"), "{report}");
+        let threshold = &report.findings[keys
+            .iter()
+            .position(|k| *k == "frost.keys_threshold")
+            .unwrap()];
+        assert_eq!(threshold.line, Some(5));
+
+        std::fs::write(&path, FROST).unwrap();
+        assert!(Config::lint(&path)
+            .unwrap()
+            .findings
+            .iter()
+            .all(|f| !f.key.starts_with("frost.")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
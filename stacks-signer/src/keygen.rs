@@ -0,0 +1,196 @@
+use std::io::Read;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use bip39::Mnemonic;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{self, ChildNumber, DerivationPath, ExtendedPrivKey};
+use bitcoin::Network;
+use clap::Args;
+use rand_core::{OsRng, RngCore};
+use wtfrost::{Point, Scalar};
+
+/// BIP32 path of the network private keys, the last index being chosen by the operator
+pub const NETWORK_KEY_PATH: &str = "m/44'/5757'/1'/0";
+/// BIP32 path of the stacks private keys, the standard stacks wallet account
+pub const STACKS_KEY_PATH: &str = "m/44'/5757'/0'/0";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("Key derivation failed: {0}")]
+    Bip32(#[from] bip32::Error),
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Keys derived from a BIP39 seed
+pub struct DerivedKeys {
+    pub index: u32,
+    pub network_private_key: [u8; 32],
+    pub stacks_private_key: [u8; 32],
+}
+
+impl DerivedKeys {
+    /// The keys at `index` of the paths above, derived from `mnemonic` and `passphrase`
+    pub fn derive(mnemonic: &Mnemonic, passphrase: &str, index: u32) -> Result<Self, Error> {
+        let seed = mnemonic.to_seed(passphrase);
+        let master = ExtendedPrivKey::new_master(Network::Bitcoin, &seed)?;
+        let derive = |path: &str| -> Result<[u8; 32], Error> {
+            let path = DerivationPath::from_str(path)?.child(ChildNumber::from_normal_idx(index)?);
+            Ok(master
+                .derive_priv(&Secp256k1::new(), &path)?
+                .private_key
+                .secret_bytes())
+        };
+        Ok(Self {
+            index,
+            network_private_key: derive(NETWORK_KEY_PATH)?,
+            stacks_private_key: derive(STACKS_KEY_PATH)?,
+        })
+    }
+
+    /// Print the keys as config lines, the stacks key only if `stacks` is set
+    fn print(&self, stacks: bool) {
+        let network_private_key = Scalar::from(self.network_private_key);
+        println!("# {}/{}", NETWORK_KEY_PATH, self.index);
+        println!("network_private_key = \"{network_private_key}\"");
+        println!(
+            "network_public_key = \"{}\"",
+            Point::from(network_private_key)
+        );
+        if stacks {
+            println!("# {}/{}", STACKS_KEY_PATH, self.index);
+            println!(
+                "stacks_private_key = \"{}01\"",
+                hex(&self.stacks_private_key)
+            );
+        }
+    }
+}
+
+/// A new 24 word mnemonic
+pub fn generate_mnemonic() -> Mnemonic {
+    let mut entropy = [0u8; 32];
+    OsRng.fill_bytes(&mut entropy);
+    Mnemonic::from_entropy(&entropy).expect("32 bytes is a valid entropy length")
+}
+
+#[derive(Args)]
+pub struct Keygen {
+    /// Derive the keys from a new BIP39 mnemonic, printed for backup, instead of generating
+    /// them at random
+    #[arg(long)]
+    mnemonic: bool,
+    /// Also output a stacks private key
+    #[arg(long)]
+    stacks: bool,
+    /// Index the keys are derived at, e.g. the signer id
+    #[arg(long, default_value_t = 0)]
+    index: u32,
+    /// Optional BIP39 passphrase protecting the mnemonic
+    #[arg(
+        long,
+        env = "STACKS_SIGNER_PASSPHRASE",
+        hide_env_values = true,
+        default_value = ""
+    )]
+    passphrase: String,
+}
+
+impl Keygen {
+    pub fn run(self) {
+        if !self.mnemonic {
+            let network_private_key = Scalar::random(&mut OsRng);
+            println!("network_private_key = \"{network_private_key}\"");
+            println!(
+                "network_public_key = \"{}\"",
+                Point::from(network_private_key)
+            );
+            if self.stacks {
+                let mut stacks_private_key = [0u8; 32];
+                OsRng.fill_bytes(&mut stacks_private_key);
+                println!("stacks_private_key = \"{}01\"", hex(&stacks_private_key));
+            }
+            return;
+        }
+        let mnemonic = generate_mnemonic();
+        let keys = DerivedKeys::derive(&mnemonic, &self.passphrase, self.index)
+            .expect("a generated mnemonic derives keys");
+        println!("# write down this mnemonic, `recover` derives the same keys from it");
+        println!("# mnemonic: {mnemonic}");
+        keys.print(self.stacks);
+    }
+}
+
+#[derive(Args)]
+pub struct Recover {
+    /// File holding the mnemonic, read from stdin if omitted
+    #[arg(long)]
+    mnemonic_file: Option<PathBuf>,
+    /// Also output the stacks private key
+    #[arg(long)]
+    stacks: bool,
+    /// Index the keys were derived at
+    #[arg(long, default_value_t = 0)]
+    index: u32,
+    /// BIP39 passphrase the keys were derived with, if any
+    #[arg(
+        long,
+        env = "STACKS_SIGNER_PASSPHRASE",
+        hide_env_values = true,
+        default_value = ""
+    )]
+    passphrase: String,
+}
+
+impl Recover {
+    pub fn run(self) -> Result<(), Error> {
+        let text = match &self.mnemonic_file {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            }
+        };
+        let mnemonic = Mnemonic::parse(text.trim())?;
+        DerivedKeys::derive(&mnemonic, &self.passphrase, self.index)?.print(self.stacks);
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn derives_keys_at_the_documented_paths() {
+        let mnemonic = Mnemonic::parse(MNEMONIC).unwrap();
+        let keys = DerivedKeys::derive(&mnemonic, "", 0).unwrap();
+        assert_eq!(
+            hex(&keys.network_private_key),
+            "861cc8583eac0fd217f5148dbba6adb959f83a4d4e2ba4a5739395d9d268eab2"
+        );
+        assert_eq!(
+            hex(&keys.stacks_private_key),
+            "47382d0211f3bbb11812b5e60b696a93d7ad0a91cdeb2162f7d69d4adef48b5d"
+        );
+        let keys = DerivedKeys::derive(&mnemonic, "", 3).unwrap();
+        assert_eq!(
+            hex(&keys.network_private_key),
+            "0173705eec7c6151185f1d7dfc45daf055a09769f9de87f8c8bf16b08d1e0186"
+        );
+
+        let other = DerivedKeys::derive(&mnemonic, "TREZOR", 3).unwrap();
+        assert_ne!(other.network_private_key, keys.network_private_key);
+        assert!(Mnemonic::parse("abandon abandon abandon").is_err());
+    }
+}
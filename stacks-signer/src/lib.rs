@@ -1,5 +1,9 @@
 /// Module for defining the CLI and its operations
 pub mod cli;
+/// Module for the layered signer config
+pub mod config;
+/// Module for deriving keys from BIP39 mnemonics
+pub mod keygen;
 /// Module for secp256k1 operations
 pub mod secp256k1;
 /// Module for signer operations
@@ -1,16 +1,25 @@
 use clap::Parser;
-use frost_signer::config::Config;
+use frost_signer::auth::Authenticator;
+use frost_signer::ceremony::{self, Ceremony};
+use frost_signer::control::{Endpoint, Server as ControlServer};
+use frost_signer::failure::{ErrorClass, FailureReport};
 use frost_signer::logging;
-use stacks_signer::cli::{Cli, Command};
+use frost_signer::net::{Envelope, Message};
+use frost_signer::signer::PeerKeys;
+use frost_signer::signing_round::{GroupKeyView, MessageTypes};
+use frost_signer::status;
+use stacks_signer::cli::{Cli, Command, ConfigAction};
+use stacks_signer::config::Config;
 use stacks_signer::secp256k1::Secp256k1;
 use stacks_signer::signer::Signer;
-use tracing::info;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
 fn main() {
     let cli = Cli::parse();
 
     // Initialize logging
-    logging::initiate_tracing_subscriber(if cli.debug {
+    let log_control = logging::initiate_tracing_subscriber(if cli.debug {
         tracing::Level::DEBUG
     } else {
         tracing::Level::INFO
@@ -23,17 +32,74 @@ fn main() {
             //TODO: getConf from sBTC contract instead
             match Config::from_path(&config) {
                 Ok(config) => {
-                    let mut signer = Signer::new(config, id);
+                    if let Some(addr) = &config.frost.log_control_listen_addr {
+                        let server = ControlServer::new(&config.frost.control)
+                            .with_route(log_control.clone());
+                        if let Err(e) = server.serve_tcp(addr.as_str()) {
+                            warn!("Failed to serve the log filter on {}: {}", addr, e);
+                        }
+                    }
+                    let control = ControlServer::new(&config.frost.control).with_route(log_control);
+                    let report_path = config.frost.failure_report_path.clone();
+                    let mut signer = Signer::new(config, id).with_control(control);
                     info!("{} signer id #{}", stacks_signer::version(), id); // sign-on message
+                                                                             // transient network failures are retried inside, so this is unrecoverable
                     if let Err(e) = signer.start_p2p_sync() {
-                        panic!("An error occurred on the P2P Network: {}", e);
+                        signer.failure_report(&e).exit(report_path.as_deref());
                     }
                 }
                 Err(e) => {
-                    panic!("An error occurred reading config file {}: {}", config, e);
+                    // without a config there is no report path, only the exit code
+                    let error = format!("An error occurred reading config file {config}: {e}");
+                    FailureReport::new(&stacks_signer::version(), ErrorClass::Config, &error)
+                        .exit(None);
+                }
+            }
+        }
+        Command::Ceremony {
+            id,
+            config,
+            timeout,
+        } => match Config::from_path(&config) {
+            Ok(config) => match ceremony::run(&config.frost, id, Duration::from_secs(timeout)) {
+                Ok(ceremony) => print_roster(&ceremony),
+                Err(e) => {
+                    error!("Ceremony failed: {}", e);
+                    std::process::exit(1);
+                }
+            },
+            Err(e) => {
+                panic!("An error occurred reading config file {}: {}", config, e);
+            }
+        },
+        Command::Decode { input, config } => {
+            let keys = config.map(|path| match Config::from_path(&path) {
+                Ok(config) => PeerKeys::try_from(&config.frost).unwrap_or_else(|e| {
+                    panic!("An error occurred reading keys from {}: {}", path, e)
+                }),
+                Err(e) => {
+                    panic!("An error occurred reading config file {}: {}", path, e);
                 }
+            });
+            if let Err(e) = decode(&input, keys.as_ref()) {
+                error!("{}", e);
+                std::process::exit(1);
             }
         }
+        Command::Config {
+            action: ConfigAction::Lint { config },
+        } => match Config::lint(&config) {
+            Ok(report) => {
+                println!("{report}");
+                if !report.is_clean() {
+                    std::process::exit(1);
+                }
+            }
+            Err(e) => {
+                error!("An error occurred reading config file {}: {}", config, e);
+                std::process::exit(1);
+            }
+        },
         Command::PrivateKey(secp256k1) => {
             if let Err(e) = secp256k1.generate_private_key() {
                 panic!("An error occurred generating private key: {}", e);
@@ -41,11 +107,178 @@ fn main() {
         }
         Command::PublicKey { config } => match Config::from_path(&config) {
             Ok(config) => {
-                Secp256k1::generate_public_key(&config.network_private_key);
+                Secp256k1::generate_public_key(&config.frost.network_private_key);
             }
             Err(e) => {
                 panic!("An error occurred reading config file {}: {}", config, e);
             }
         },
+        Command::LogFilter { config, directives } => match Config::from_path(&config) {
+            Ok(settings) => {
+                let endpoint = settings.frost.control.endpoint().or(settings
+                    .frost
+                    .log_control_listen_addr
+                    .clone()
+                    .map(Endpoint::Tcp));
+                let Some(endpoint) = endpoint else {
+                    error!(
+                        "{} sets neither [control] nor log_control_listen_addr",
+                        config
+                    );
+                    std::process::exit(1);
+                };
+                let token = settings.frost.control.token();
+                match logging::request(&endpoint, token, directives.as_deref()) {
+                    Ok(directives) => println!("{directives}"),
+                    Err(e) => {
+                        error!("Log control request to {} failed: {}", endpoint, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Err(e) => {
+                panic!("An error occurred reading config file {}: {}", config, e);
+            }
+        },
+        Command::GroupKey { config } => match Config::from_path(&config) {
+            Ok(settings) => {
+                let endpoint = settings.frost.control.endpoint().or(settings
+                    .frost
+                    .status_listen_addr
+                    .clone()
+                    .map(Endpoint::Tcp));
+                let Some(endpoint) = endpoint else {
+                    error!("{} sets neither [control] nor status_listen_addr", config);
+                    std::process::exit(1);
+                };
+                match status::request_group_key(&endpoint, settings.frost.control.token()) {
+                    Ok(Some(view)) => print_group_key(&view),
+                    Ok(None) => {
+                        error!("The signer has not completed a DKG round");
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        error!("Group key request to {} failed: {}", endpoint, e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            Err(e) => {
+                panic!("An error occurred reading config file {}: {}", config, e);
+            }
+        },
+        Command::Keygen(keygen) => keygen.run(),
+        Command::Recover(recover) => {
+            if let Err(e) = recover.run() {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    };
+}
+
+fn print_roster(ceremony: &Ceremony) {
+    let key_mismatches = ceremony.key_mismatches();
+    let config_mismatches = ceremony.config_mismatches();
+    for hello in ceremony.roster() {
+        let mut notes = vec![];
+        if key_mismatches.contains(&hello.signer_id) {
+            notes.push("KEY NOT IN CONFIG");
+        }
+        if config_mismatches.contains(&hello.signer_id) {
+            notes.push("CONFIG DIFFERS");
+        }
+        println!(
+            "signer {:>3}  key {}  config {}  {}",
+            hello.signer_id,
+            hex(&hello.public_key),
+            hex(&hello.config_hash[..8]),
+            notes.join(", ")
+        );
+    }
+    if !key_mismatches.is_empty() || !config_mismatches.is_empty() {
+        warn!("Signers disagree on the group, fix the configs before running DKG");
+    }
+    println!("roster fingerprint: {}", ceremony.fingerprint());
+}
+
+fn print_group_key(view: &GroupKeyView) {
+    println!("signer:      {}", view.signer_id);
+    println!("dkg round:   {}", view.dkg_id);
+    println!("group key:   {}", view.group_key);
+    println!("key ids:     {:?}", view.key_ids);
+    println!("roster hash: {}", view.roster_hash);
+    let roster: Vec<String> = view
+        .roster
+        .iter()
+        .map(|(key_id, signer_id)| format!("{key_id}:{signer_id}"))
+        .collect();
+    println!("roster:      {}", roster.join(" "));
+}
+
+/// Print the message in `input` and check its signature against `keys`, if given
+fn decode(input: &str, keys: Option<&PeerKeys>) -> Result<(), String> {
+    let bytes = match std::fs::read(input) {
+        Ok(bytes) => match std::str::from_utf8(&bytes) {
+            Ok(text) => from_hex(text.trim()).unwrap_or(bytes),
+            Err(_) => bytes,
+        },
+        Err(_) => from_hex(input.trim()).ok_or(format!("{input} is neither a file nor hex"))?,
     };
+    let envelope = Envelope::from_bytes(&bytes).map_err(|e| format!("Not a relay message: {e}"))?;
+    let message = &envelope.message;
+    let variant = format!("{:?}", message.msg);
+    println!(
+        "variant:   {}",
+        variant.split('(').next().unwrap_or_default()
+    );
+    println!("version:   {}", envelope.version);
+    println!("namespace: {:?}", envelope.namespace);
+    println!("sender:    {}", envelope.sender_id);
+    println!("timestamp: {}", envelope.timestamp);
+    println!("signature: {}", hex(&message.sig));
+    println!("{:#?}", message.msg);
+
+    // captures are decoded long after they were sent, so their age is not checked
+    if let Some(keys) = keys {
+        let authenticator = Authenticator::new(keys.clone(), Duration::ZERO);
+        match authenticator.check_at(&envelope, envelope.timestamp) {
+            Ok(()) => println!("envelope verified"),
+            Err(e) => return Err(format!("envelope does NOT verify: {e}")),
+        }
+    }
+    match verify(message, keys) {
+        Some(true) => {
+            println!("signature verified");
+            Ok(())
+        }
+        Some(false) => Err("signature does NOT verify".to_string()),
+        None => {
+            println!("signature not verified, pass --config to check it");
+            Ok(())
+        }
+    }
+}
+
+/// Hellos are checked against the key they carry, everything else needs the config
+fn verify(message: &Message, keys: Option<&PeerKeys>) -> Option<bool> {
+    match &message.msg {
+        MessageTypes::Hello(hello) => Some(hello.is_self_signed(&message.sig)),
+        _ => keys.map(|keys| keys.verify(message)),
+    }
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    if text.is_empty() || !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
 }
@@ -1,22 +1,50 @@
 use serde::Deserialize;
 
-use frost_signer::config::Config;
+use frost_signer::control::Server as ControlServer;
+use frost_signer::failure::FailureReport;
+use frost_signer::middleware::Middleware;
 use frost_signer::signer::{Error as SignerError, Signer as FrostSigner};
 
+use crate::config::{Config, StacksConfig};
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct Signer {
     frost_signer: FrostSigner,
-    //TODO: Are there any StacksSigner specific items or maybe a stacks signer specific config that needs to be wrapped around Config?
+    stacks: StacksConfig,
 }
 
 impl Signer {
     pub fn new(config: Config, id: u32) -> Self {
         Self {
-            frost_signer: FrostSigner::new(config, id),
+            frost_signer: FrostSigner::new(config.frost, id),
+            stacks: config.stacks,
         }
     }
 
+    /// The `[stacks]` settings the signer runs with
+    pub fn stacks_config(&self) -> &StacksConfig {
+        &self.stacks
+    }
+
+    /// Serve the routes of `control` on the `[control]` listeners, along with the status
+    pub fn with_control(mut self, control: ControlServer) -> Self {
+        self.frost_signer = self.frost_signer.with_control(control);
+        self
+    }
+
+    /// Run inbound messages through `layer` before the rounds process them, see
+    /// [`frost_signer::middleware`]
+    pub fn with_middleware(mut self, layer: impl Middleware + 'static) -> Self {
+        self.frost_signer = self.frost_signer.with_middleware(layer);
+        self
+    }
+
     pub fn start_p2p_sync(&mut self) -> Result<(), SignerError> {
         self.frost_signer.start_p2p_sync()
     }
+
+    /// Report of `error` stopping the signer, with the rounds it had in flight
+    pub fn failure_report(&self, error: &SignerError) -> FailureReport {
+        self.frost_signer.failure_report(&crate::version(), error)
+    }
 }
@@ -1,7 +1,57 @@
 use assert_cmd::prelude::*;
+use frost_signer::auth::{Identity, COORDINATOR_SENDER_ID};
+use frost_signer::net::Message;
+use frost_signer::signing_round::{DkgBegin, MessageTypes, Signable};
 use predicates::prelude::*;
+use std::path::PathBuf;
 use std::process::Command;
 use testdir::testdir;
+use wtfrost::{Point, Scalar};
+
+/// Write a signer config in `dir` where every key belongs to `private_key`
+fn signer_config(mut dir: PathBuf, private_key: &Scalar) -> PathBuf {
+    let key = format!("\"{}\"", Point::from(private_key));
+    dir.push("signer.toml");
+    std::fs::write(
+        &dir,
+        format!(
+            r#"http_relay_url = "http://localhost:9776"
+total_signers = 1
+total_keys = 1
+keys_threshold = 1
+frost_state_file = ""
+network_private_key = "{private_key}"
+signer_public_keys = [{key}]
+key_public_keys = [{key}]
+coordinator_public_key = {key}
+"#
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+fn dkg_begin_hex(private_key: &Scalar) -> String {
+    let msg = DkgBegin {
+        dkg_id: 7,
+        excluded_key_ids: vec![],
+    };
+    let envelope = Identity::new(COORDINATOR_SENDER_ID, *private_key)
+        .seal(
+            "devnet".to_string(),
+            Message {
+                sig: msg.sign(private_key).unwrap(),
+                msg: MessageTypes::DkgBegin(msg),
+            },
+        )
+        .unwrap();
+    envelope
+        .to_bytes()
+        .unwrap()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
 
 #[test]
 fn secp256k1_to_stdout() {
@@ -31,3 +81,97 @@ fn secp256k1_to_file() {
     cmd.assert().success();
     assert!(output_path.exists());
 }
+
+#[test]
+fn decode_verifies_against_the_config() {
+    let key = Scalar::from(3);
+    let mut cmd = Command::cargo_bin("stacks-signer").unwrap();
+    cmd.arg("decode")
+        .arg(dkg_begin_hex(&key))
+        .arg("--config")
+        .arg(signer_config(testdir!(), &key));
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("variant:   DkgBegin"))
+        .stdout(predicate::str::contains("dkg_id: 7"))
+        .stdout(predicate::str::contains("envelope verified"))
+        .stdout(predicate::str::contains("signature verified"));
+}
+
+#[test]
+fn decode_fails_on_a_forged_signature() {
+    let dir = testdir!();
+    let config = signer_config(dir.clone(), &Scalar::from(3));
+    let path = dir.join("capture.hex");
+    std::fs::write(&path, dkg_begin_hex(&Scalar::from(5))).unwrap();
+
+    let mut cmd = Command::cargo_bin("stacks-signer").unwrap();
+    cmd.arg("decode").arg(&path);
+    // without a config the signature is not checked
+    cmd.assert().success();
+
+    cmd.arg("--config").arg(config);
+    cmd.assert()
+        .failure()
+        .stdout(predicate::str::contains("variant:   DkgBegin"));
+}
+
+#[test]
+fn decode_rejects_garbage() {
+    let mut cmd = Command::cargo_bin("stacks-signer").unwrap();
+    cmd.arg("decode").arg("zz");
+    cmd.assert().failure();
+}
+
+#[test]
+fn config_lint_reports_unknown_keys() {
+    let config = signer_config(testdir!(), &Scalar::from(3));
+    let mut cmd = Command::cargo_bin("stacks-signer").unwrap();
+    cmd.arg("config").arg("lint").arg("--config").arg(&config);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::ends_with(": ok\n"));
+
+    let mut contents = std::fs::read_to_string(&config).unwrap();
+    contents.push_str("keys_treshold = 1\n");
+    std::fs::write(&config, contents).unwrap();
+    cmd.assert().failure().stdout(predicate::str::contains(
+        ":10: keys_treshold: unknown key, did you mean `keys_threshold`?",
+    ));
+}
+
+#[test]
+fn recover_derives_the_keys_keygen_printed() {
+    let mut cmd = assert_cmd::Command::cargo_bin("stacks-signer").unwrap();
+    cmd.args(["keygen", "--mnemonic", "--stacks", "--index", "2"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output).unwrap();
+    let mnemonic = output
+        .lines()
+        .find_map(|line| line.strip_prefix("# mnemonic: "))
+        .unwrap();
+    assert_eq!(mnemonic.split(' ').count(), 24);
+    let keys: String = output
+        .lines()
+        .skip_while(|line| !line.starts_with("# m/"))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert!(keys.contains("network_private_key = "));
+    assert!(keys.contains("stacks_private_key = "));
+
+    let mut cmd = assert_cmd::Command::cargo_bin("stacks-signer").unwrap();
+    cmd.args(["recover", "--stacks", "--index", "2"])
+        .write_stdin(mnemonic);
+    cmd.assert().success().stdout(keys);
+
+    // another index derives other keys
+    let mut cmd = assert_cmd::Command::cargo_bin("stacks-signer").unwrap();
+    cmd.args(["recover", "--index", "3"]).write_stdin(mnemonic);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("m/44'/5757'/1'/0/3"));
+
+    let mut cmd = assert_cmd::Command::cargo_bin("stacks-signer").unwrap();
+    cmd.arg("recover").write_stdin("abandon abandon abandon");
+    cmd.assert().failure();
+}
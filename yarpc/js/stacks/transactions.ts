@@ -2,6 +2,7 @@ import { listenStdio, dispatch, CommandMap } from '../lib.ts'
 import {
     type SignedContractCallOptions,
     makeContractCall,
+    makeContractDeploy,
     deserializeCV,
 } from 'npm:@stacks/transactions'
 
@@ -12,7 +13,8 @@ type MakeContractCallInput = {
 
 const t = {
     makeContractCall: (input: MakeContractCallInput) =>
-        makeContractCall({ ...input, functionArgs: input.functionArgs.map(deserializeCV) })
+        makeContractCall({ ...input, functionArgs: input.functionArgs.map(deserializeCV) }),
+    makeContractDeploy,
 }
 
 listenStdio(dispatch(t as unknown as CommandMap))